@@ -0,0 +1,187 @@
+//! `atlas fetch-collateral`: download a platform's collateral into a bundle
+//! that air-gapped machines verify quotes with (`atlas verify-quote
+//! --collateral bundle.tar`).
+//!
+//! The bundle is a tar archive of:
+//!
+//! - `manifest.json`: the FMSPC, PCK CA, PCCS URL and download time.
+//! - `collateral.json`: everything verification needs, as `QuoteCollateralV3`
+//!   serializes it.
+//! - `tcb_info.json`, `qe_identity.json`, `root_ca_crl.der`, `pck_crl.der`:
+//!   the same TCB info, QE identity and CRLs as separate files, for other
+//!   verifiers.
+//! - `quote.bin`: the quote, when the platform was named by one.
+
+use crate::BoxError;
+use atlas_rs::dstack::policy::DEFAULT_PCCS_URL;
+use atlas_rs::tdx::collateral_cache;
+use atlas_rs::QuoteCollateralV3;
+use chrono::Utc;
+use clap::ArgGroup;
+use serde_json::json;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The bundle entry holding the collateral.
+const COLLATERAL: &str = "collateral.json";
+
+#[derive(clap::Args)]
+#[command(group(ArgGroup::new("platform").required(true).args(["fmspc", "quote"])))]
+pub struct Args {
+    /// FMSPC of the platform, in hex
+    #[arg(long)]
+    fmspc: Option<String>,
+
+    /// CA of the platform's PCK certificate (with --fmspc)
+    #[arg(long, default_value = "processor", value_parser = ["processor", "platform"])]
+    ca: String,
+
+    /// A quote from the platform (raw bytes or hex text), added to the bundle
+    #[arg(long, value_name = "FILE")]
+    quote: Option<PathBuf>,
+
+    /// PCCS to download from
+    #[arg(long, env = "ATLAS_PCCS_URL", default_value = DEFAULT_PCCS_URL)]
+    pccs_url: String,
+
+    /// Where to write the bundle (tar)
+    #[arg(long, value_name = "FILE")]
+    out: PathBuf,
+}
+
+pub async fn run(args: Args) -> Result<(), BoxError> {
+    let quote = match &args.quote {
+        Some(path) => Some(crate::verify_quote::read_quote(path)?),
+        None => None,
+    };
+    let (fmspc, ca) = match (&quote, args.fmspc) {
+        (Some(quote), _) => {
+            let (fmspc, ca) = collateral_cache::platform(quote)?;
+            (fmspc, ca.to_string())
+        }
+        (None, Some(fmspc)) => {
+            hex::decode(&fmspc).map_err(|e| format!("invalid FMSPC {fmspc:?}: {e}"))?;
+            (fmspc.to_ascii_uppercase(), args.ca)
+        }
+        (None, None) => unreachable!("clap requires --fmspc or --quote"),
+    };
+
+    let collateral = collateral_cache::fetch(&args.pccs_url, &fmspc, &ca).await?;
+    let file = File::create(&args.out)
+        .map_err(|e| format!("failed to create {}: {e}", args.out.display()))?;
+    let manifest = json!({
+        "fmspc": fmspc,
+        "ca": ca,
+        "pccs_url": args.pccs_url,
+        "fetched_at": Utc::now().to_rfc3339(),
+    });
+    write_bundle(file, &manifest, &collateral, quote.as_deref())?;
+    eprintln!(
+        "wrote {}: FMSPC {fmspc} ({ca} CA) from {}",
+        args.out.display(),
+        args.pccs_url
+    );
+    Ok(())
+}
+
+/// Write a bundle of `collateral` to `out`.
+fn write_bundle<W: Write>(
+    out: W,
+    manifest: &serde_json::Value,
+    collateral: &QuoteCollateralV3,
+    quote: Option<&[u8]>,
+) -> Result<(), BoxError> {
+    let mtime = u64::try_from(Utc::now().timestamp()).unwrap_or_default();
+    let mut entries: Vec<(&str, Vec<u8>)> = vec![
+        ("manifest.json", serde_json::to_vec_pretty(manifest)?),
+        (COLLATERAL, serde_json::to_vec_pretty(collateral)?),
+        ("tcb_info.json", collateral.tcb_info.clone().into_bytes()),
+        (
+            "qe_identity.json",
+            collateral.qe_identity.clone().into_bytes(),
+        ),
+        ("root_ca_crl.der", collateral.root_ca_crl.clone()),
+        ("pck_crl.der", collateral.pck_crl.clone()),
+    ];
+    if let Some(quote) = quote {
+        entries.push(("quote.bin", quote.to_vec()));
+    }
+
+    let mut tar = tar::Builder::new(out);
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, name, data.as_slice())?;
+    }
+    tar.into_inner()?.flush()?;
+    Ok(())
+}
+
+/// Whether `path` is a bundle rather than bare collateral JSON.
+pub fn is_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "tar")
+}
+
+/// The collateral in the bundle `path`.
+pub fn read_bundle(path: &Path) -> Result<QuoteCollateralV3, BoxError> {
+    let file = File::open(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let mut archive = tar::Archive::new(file);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() == COLLATERAL {
+            let mut json = Vec::new();
+            entry.read_to_end(&mut json)?;
+            return serde_json::from_slice(&json)
+                .map_err(|e| format!("invalid {COLLATERAL} in {}: {e}", path.display()).into());
+        }
+    }
+    Err(format!("{} has no {COLLATERAL}", path.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let collateral = QuoteCollateralV3 {
+            pck_crl_issuer_chain: "chain".into(),
+            root_ca_crl: vec![1, 2],
+            pck_crl: vec![3],
+            tcb_info_issuer_chain: String::new(),
+            tcb_info: r#"{"fmspc":"90C06F000000"}"#.into(),
+            tcb_info_signature: vec![4],
+            qe_identity_issuer_chain: String::new(),
+            qe_identity: "{}".into(),
+            qe_identity_signature: vec![5],
+            pck_certificate_chain: None,
+        };
+        let path = std::env::temp_dir().join(format!("atlas-bundle-{}.tar", std::process::id()));
+        let manifest = json!({ "fmspc": "90C06F000000" });
+        write_bundle(
+            File::create(&path).unwrap(),
+            &manifest,
+            &collateral,
+            Some(b"quote".as_slice()),
+        )
+        .unwrap();
+
+        assert!(is_bundle(&path));
+        let read = read_bundle(&path).unwrap();
+        assert_eq!(read.tcb_info, collateral.tcb_info);
+        assert_eq!(read.root_ca_crl, collateral.root_ca_crl);
+
+        let mut archive = tar::Archive::new(File::open(&path).unwrap());
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert!(names.contains(&"pck_crl.der".to_string()));
+        assert!(names.contains(&"quote.bin".to_string()));
+        std::fs::remove_file(path).unwrap();
+    }
+}
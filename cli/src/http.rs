@@ -0,0 +1,334 @@
+//! Just enough HTTP/1.1 for `atlas request`: one request per connection
+//! (`Connection: close`), and a response whose body is copied out as it
+//! arrives, whether framed by `Content-Length`, chunked or the end of the
+//! connection.
+
+use crate::BoxError;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use url::{Position, Url};
+
+/// Largest response head (status line and headers) accepted.
+const MAX_HEAD: usize = 64 * 1024;
+
+/// The bytes of a `method` request for `url`. `headers` (`Name: value`)
+/// replace the default header of the same name.
+pub fn request(
+    method: &str,
+    url: &Url,
+    headers: &[String],
+    body: Option<&[u8]>,
+) -> Result<Vec<u8>, BoxError> {
+    if method.is_empty()
+        || !method
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    {
+        return Err(format!("invalid method {method:?}").into());
+    }
+    let mut custom = Vec::new();
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| format!("invalid header {header:?}: expected `Name: value`"))?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) || header.contains(['\r', '\n']) {
+            return Err(format!("invalid header {header:?}").into());
+        }
+        custom.push((name, value.trim()));
+    }
+
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let length = body.map(|body| body.len().to_string());
+    let agent = concat!("atlas/", env!("CARGO_PKG_VERSION"));
+    let mut defaults = vec![
+        ("Host", host.as_str()),
+        ("User-Agent", agent),
+        ("Accept", "*/*"),
+    ];
+    if let Some(length) = &length {
+        defaults.push(("Content-Length", length));
+    }
+    defaults.push(("Connection", "close"));
+
+    let target = &url[Position::BeforePath..Position::AfterQuery];
+    let mut request = format!("{method} {target} HTTP/1.1\r\n");
+    for (name, value) in defaults {
+        if !custom
+            .iter()
+            .any(|(custom, _)| custom.eq_ignore_ascii_case(name))
+        {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+    }
+    for (name, value) in custom {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    let mut request = request.into_bytes();
+    request.extend_from_slice(body.unwrap_or_default());
+    Ok(request)
+}
+
+/// A response's status line and headers.
+#[derive(Debug)]
+pub struct Head {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The head as received, for `--include`.
+    pub raw: Vec<u8>,
+}
+
+impl Head {
+    /// The first value of header `name`.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read the final response head, skipping interim `1xx` responses.
+pub async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Head, BoxError> {
+    loop {
+        let mut raw = Vec::new();
+        let mut lines = Vec::new();
+        loop {
+            let mut line = Vec::new();
+            let read = (&mut *reader)
+                .take((MAX_HEAD - raw.len()) as u64 + 1)
+                .read_until(b'\n', &mut line)
+                .await?;
+            if read == 0 {
+                return Err("the connection closed before a response".into());
+            }
+            raw.extend_from_slice(&line);
+            if raw.len() > MAX_HEAD {
+                return Err("the response head is too large".into());
+            }
+            let line = String::from_utf8_lossy(&line).trim_end().to_string();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+        let status_line = lines.first().ok_or("empty response head")?;
+        let status = status_line
+            .strip_prefix("HTTP/1.")
+            .and_then(|rest| rest.get(2..5))
+            .and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| format!("invalid status line {status_line:?}"))?;
+        if (100..200).contains(&status) && status != 101 {
+            continue;
+        }
+        let headers = lines[1..]
+            .iter()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        return Ok(Head {
+            status,
+            headers,
+            raw,
+        });
+    }
+}
+
+/// Copy the body of the response with `head` to `out`, flushing as it
+/// arrives. `head_request` is whether it answers a `HEAD`.
+pub async fn copy_body<R, W>(
+    reader: &mut R,
+    head: &Head,
+    head_request: bool,
+    out: &mut W,
+) -> Result<(), BoxError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    if head_request || head.status == 204 || head.status == 304 {
+        return Ok(());
+    }
+    let chunked = head
+        .header("Transfer-Encoding")
+        .is_some_and(|encoding| encoding.to_ascii_lowercase().ends_with("chunked"));
+    if chunked {
+        return copy_chunks(reader, out).await;
+    }
+    if let Some(length) = head.header("Content-Length") {
+        let length: u64 = length
+            .parse()
+            .map_err(|_| format!("invalid Content-Length {length:?}"))?;
+        return copy_exact(reader, length, out).await;
+    }
+    // Framed by the end of the connection; servers often close without a
+    // TLS close_notify, which is no error here
+    let mut buf = vec![0; 16 * 1024];
+    loop {
+        let read = match reader.read(&mut buf).await {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => 0,
+            Err(e) => return Err(e.into()),
+        };
+        if read == 0 {
+            return Ok(());
+        }
+        out.write_all(&buf[..read]).await?;
+        out.flush().await?;
+    }
+}
+
+async fn copy_chunks<R, W>(reader: &mut R, out: &mut W) -> Result<(), BoxError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let line = read_line(reader).await?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size =
+            u64::from_str_radix(size, 16).map_err(|_| format!("invalid chunk size {size:?}"))?;
+        if size == 0 {
+            // Trailers, if any, end with an empty line
+            while !read_line(reader).await?.is_empty() {}
+            return Ok(());
+        }
+        copy_exact(reader, size, out).await?;
+        if !read_line(reader).await?.is_empty() {
+            return Err("a chunk is longer than its size".into());
+        }
+    }
+}
+
+/// Copy exactly `length` bytes.
+async fn copy_exact<R, W>(reader: &mut R, length: u64, out: &mut W) -> Result<(), BoxError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut remaining = length;
+    while remaining > 0 {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Err("the connection closed mid-body".into());
+        }
+        let n = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+        out.write_all(&buf[..n]).await?;
+        reader.consume(n);
+        remaining -= n as u64;
+    }
+    out.flush().await?;
+    Ok(())
+}
+
+/// One line of chunked framing, without its line ending.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<String, BoxError> {
+    let mut line = Vec::new();
+    (&mut *reader)
+        .take(MAX_HEAD as u64)
+        .read_until(b'\n', &mut line)
+        .await?;
+    if !line.ends_with(b"\n") {
+        return Err("the connection closed mid-body".into());
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn response(bytes: &[u8], head_request: bool) -> Result<(u16, Vec<u8>), BoxError> {
+        let mut reader = bytes;
+        let head = read_head(&mut reader).await?;
+        let mut body = Vec::new();
+        copy_body(&mut reader, &head, head_request, &mut body).await?;
+        Ok((head.status, body))
+    }
+
+    #[test]
+    fn test_request() {
+        let url = Url::parse("https://tee.example.com:8443/api?x=1").unwrap();
+        let headers = vec!["Accept: application/json".to_string()];
+        let bytes = request("POST", &url, &headers, Some(b"{}")).unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "POST /api?x=1 HTTP/1.1\r\n\
+             Host: tee.example.com:8443\r\n\
+             User-Agent: atlas/0.1.0\r\n\
+             Content-Length: 2\r\n\
+             Connection: close\r\n\
+             Accept: application/json\r\n\
+             \r\n\
+             {}"
+        );
+
+        let url = Url::parse("https://tee.example.com/").unwrap();
+        let bytes = request("GET", &url, &[], None).unwrap();
+        assert!(String::from_utf8(bytes)
+            .unwrap()
+            .starts_with("GET / HTTP/1.1\r\nHost: tee.example.com\r\n"));
+
+        assert!(request("GET /", &url, &[], None).is_err());
+        let injected = vec!["X-A: 1\r\nX-B: 2".to_string()];
+        assert!(request("GET", &url, &injected, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_content_length() {
+        let (status, body) = response(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello trailing",
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(body, b"hello");
+
+        let truncated = response(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nhello", false);
+        assert!(truncated.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chunked() {
+        let (_, body) = response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\n",
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(body, b"hello world");
+
+        let truncated = response(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel",
+            false,
+        );
+        assert!(truncated.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_interim_and_bodiless_responses() {
+        let (status, body) = response(
+            b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\n\r\nuntil close",
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, 201);
+        assert_eq!(body, b"until close");
+
+        let (_, body) = response(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n", true)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+        let (status, _) = response(b"HTTP/1.1 204 No Content\r\n\r\n", false)
+            .await
+            .unwrap();
+        assert_eq!(status, 204);
+    }
+}
@@ -0,0 +1,73 @@
+//! `atlas`: command-line tools for attested TLS deployments.
+//!
+//! - `atlas request`: an attested, curl-like HTTPS request (see `request`).
+//! - `atlas verify-quote`: offline verification of a recorded quote (see
+//!   `verify_quote`).
+//! - `atlas fetch-collateral`: a collateral bundle for air-gapped verifiers
+//!   (see `fetch_collateral`).
+//! - `atlas measure`: the expected bootchain of a dstack image (see
+//!   `measure`).
+//! - `atlas policy lint` / `atlas policy convert`: policy checks and JSON/YAML
+//!   conversion (see `policy`).
+//!
+//! Errors go to stderr, with the stable code of an attestation failure (such
+//! as `TCB_STATUS_REJECTED`), and exit with status 1.
+
+mod fetch_collateral;
+mod http;
+mod measure;
+mod policy;
+mod request;
+mod summary;
+mod verify_quote;
+
+use atlas_rs::AtlsVerificationError;
+use clap::{Parser, Subcommand};
+use std::process::ExitCode;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Parser)]
+#[command(name = "atlas", version, about = "Attested TLS tools")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send an HTTPS request to a TEE over attested TLS
+    Request(request::Args),
+    /// Verify a recorded TDX quote against recorded collateral, offline
+    VerifyQuote(verify_quote::Args),
+    /// Download a platform's collateral into a bundle for offline verification
+    FetchCollateral(fetch_collateral::Args),
+    /// Compute the bootchain measurements of a dstack image, for a policy
+    Measure(measure::Args),
+    /// Check attestation policies, or convert them between JSON and YAML
+    #[command(subcommand)]
+    Policy(policy::Command),
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    let result = match cli.command {
+        Command::Request(args) => request::run(args).await,
+        Command::VerifyQuote(args) => verify_quote::run(args),
+        Command::FetchCollateral(args) => fetch_collateral::run(args).await,
+        Command::Measure(args) => measure::run(args),
+        Command::Policy(command) => policy::run(command),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e.downcast_ref::<AtlsVerificationError>() {
+                Some(verification) => eprintln!("error [{}]: {e}", verification.code()),
+                None => eprintln!("error: {e}"),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! `atlas measure`: compute the bootchain measurements (MRTD, RTMR0-2) a
+//! dstack image boots to, and print them as the `expected_bootchain` of a
+//! policy.
+//!
+//! The measurements are replayed by dstack's `dstack-mr` tool, which isn't
+//! published as a crate: this writes the image's firmware, kernel, initrd and
+//! kernel command line (into which the rootfs hash goes) to a `metadata.json`,
+//! runs `dstack-mr measure` on it with the VM the image runs in (CPU count,
+//! memory, GPUs, PCI hole and QEMU version all change RTMR0), and reads the
+//! registers back from its output. RTMR0 also measures the ACPI tables QEMU
+//! generates, so `dstack-acpi-tables` and QEMU's firmware files must be on
+//! `PATH` too (see `core/BOOTCHAIN-VERIFICATION.md`).
+
+use crate::BoxError;
+use atlas_rs::ExpectedBootchain;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The kernel command line of dstack images, before the rootfs hash.
+const DEFAULT_CMDLINE: &str = "console=ttyS0 init=/init panic=1 net.ifnames=0 biosdevname=0 \
+    mce=off oops=panic pci=noearly pci=nommconf random.trust_cpu=y random.trust_bootloader=n \
+    tsc=reliable no-kvmclock";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Firmware (OVMF/TDVF), e.g. the release's `ovmf.fd`
+    #[arg(long, value_name = "FILE")]
+    ovmf: PathBuf,
+
+    /// Kernel, e.g. the release's `bzImage`
+    #[arg(long, value_name = "FILE")]
+    kernel: PathBuf,
+
+    /// Initrd, e.g. the release's `initramfs.cpio.gz`
+    #[arg(long, value_name = "FILE")]
+    initrd: PathBuf,
+
+    /// Root filesystem hash, in hex (`rootfs_hash` in the release's `metadata.json`)
+    #[arg(long, value_name = "HASH")]
+    rootfs: String,
+
+    /// Kernel command line, without the rootfs hash (`cmdline` in `metadata.json`)
+    #[arg(long, default_value = DEFAULT_CMDLINE)]
+    cmdline: String,
+
+    /// Number of vCPUs
+    #[arg(long, default_value_t = 1)]
+    cpu: u8,
+
+    /// Memory size, in bytes or with a K, M, G or T suffix
+    #[arg(long, default_value = "2G", value_parser = parse_size)]
+    memory: u64,
+
+    /// Number of GPUs passed through
+    #[arg(long, default_value_t = 0)]
+    num_gpus: u32,
+
+    /// Number of NVSwitches passed through
+    #[arg(long, default_value_t = 0)]
+    num_nvswitches: u32,
+
+    /// Whether PCI hotplug is disabled
+    #[arg(long, default_value_t = false, action = clap::ArgAction::Set)]
+    hotplug_off: bool,
+
+    /// 64-bit PCI hole size, in bytes or with a suffix [default: QEMU's]
+    #[arg(long, value_parser = parse_size)]
+    pci_hole64_size: Option<u64>,
+
+    /// Whether guest memory is backed by hugepages
+    #[arg(long)]
+    hugepages: bool,
+
+    /// QEMU version the VM runs on, e.g. 9.2.1 [default: dstack-mr's]
+    #[arg(long)]
+    qemu_version: Option<String>,
+
+    /// The `dstack-mr` executable
+    #[arg(
+        long,
+        env = "ATLAS_DSTACK_MR",
+        default_value = "dstack-mr",
+        value_name = "PATH"
+    )]
+    dstack_mr: PathBuf,
+}
+
+pub fn run(args: Args) -> Result<(), BoxError> {
+    if args.rootfs.is_empty() || hex::decode(&args.rootfs).is_err() {
+        return Err(format!("invalid rootfs hash {:?}: expected hex", args.rootfs).into());
+    }
+    let metadata = json!({
+        "bios": absolute(&args.ovmf)?,
+        "kernel": absolute(&args.kernel)?,
+        "initrd": absolute(&args.initrd)?,
+        "cmdline": kernel_cmdline(&args.cmdline, &args.rootfs),
+        "rootfs_hash": args.rootfs.to_ascii_lowercase(),
+    });
+    // A fresh private directory (0700, unpredictable name), so no other user
+    // can swap metadata.json before dstack-mr reads it
+    let dir = tempfile::Builder::new()
+        .prefix("atlas-measure-")
+        .tempdir()
+        .map_err(|e| format!("failed to create a temporary directory: {e}"))?;
+    let metadata_path = dir.path().join("metadata.json");
+    let output = std::fs::write(&metadata_path, serde_json::to_vec_pretty(&metadata)?)
+        .map_err(|e| format!("failed to write {}: {e}", metadata_path.display()))
+        .and_then(|()| {
+            Command::new(&args.dstack_mr)
+                .arg("measure")
+                .args(dstack_mr_args(&args))
+                .arg(&metadata_path)
+                .output()
+                .map_err(|e| {
+                    format!(
+                        "failed to run {}: {e} (build it from dstack's dstack-mr/cli, see \
+                         core/BOOTCHAIN-VERIFICATION.md)",
+                        args.dstack_mr.display()
+                    )
+                })
+        });
+    drop(dir);
+    let output = output?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed ({}): {}",
+            args.dstack_mr.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    let bootchain = parse_measurements(&String::from_utf8_lossy(&output.stdout))?;
+    println!("{}", serde_json::to_string_pretty(&bootchain)?);
+    Ok(())
+}
+
+/// The VM options of `dstack-mr measure`.
+fn dstack_mr_args(args: &Args) -> Vec<String> {
+    let mut options = vec![
+        "--cpu".to_string(),
+        args.cpu.to_string(),
+        "--memory".to_string(),
+        args.memory.to_string(),
+        "--num-gpus".to_string(),
+        args.num_gpus.to_string(),
+        "--num-nvswitches".to_string(),
+        args.num_nvswitches.to_string(),
+        "--hotplug-off".to_string(),
+        args.hotplug_off.to_string(),
+    ];
+    if let Some(size) = args.pci_hole64_size {
+        options.extend(["--pci-hole64-size".to_string(), size.to_string()]);
+    }
+    if args.hugepages {
+        options.extend(["--hugepages".to_string(), "true".to_string()]);
+    }
+    if let Some(version) = &args.qemu_version {
+        options.extend(["--qemu-version".to_string(), version.clone()]);
+    }
+    options
+}
+
+/// The bootchain in the output of `dstack-mr measure`: `MRTD: <hex>` and
+/// `RTMR0:` to `RTMR2:` lines, among others.
+fn parse_measurements(output: &str) -> Result<ExpectedBootchain, BoxError> {
+    let register = |name: &str| -> Result<String, BoxError> {
+        let value = output
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim().to_ascii_lowercase())
+            .ok_or_else(|| format!("dstack-mr printed no {name}"))?;
+        match hex::decode(&value) {
+            Ok(bytes) if bytes.len() == 48 => Ok(value),
+            _ => Err(format!("dstack-mr printed an invalid {name}: {value:?}").into()),
+        }
+    };
+    Ok(ExpectedBootchain {
+        mrtd: register("MRTD")?,
+        rtmr0: register("RTMR0")?,
+        rtmr1: register("RTMR1")?,
+        rtmr2: register("RTMR2")?,
+    })
+}
+
+/// The command line the image boots with: `cmdline` and the rootfs hash the
+/// initrd checks the root filesystem against. `dstack-mr` adds the initrd.
+fn kernel_cmdline(cmdline: &str, rootfs: &str) -> String {
+    format!(
+        "{} dstack.rootfs_hash={}",
+        cmdline.trim(),
+        rootfs.to_ascii_lowercase()
+    )
+}
+
+/// The absolute path of the existing file `path`.
+fn absolute(path: &Path) -> Result<PathBuf, BoxError> {
+    std::fs::canonicalize(path)
+        .map_err(|e| format!("failed to read {}: {e}", path.display()).into())
+}
+
+/// A size in bytes, optionally with a binary K, M, G or T suffix.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (digits, shift) = match size.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => {
+            let shift = match unit.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("unknown size suffix {unit:?} (K, M, G or T)")),
+            };
+            (&size[..index], shift)
+        }
+        _ => (size, 0),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|e| format!("invalid size {size:?}: {e}"))?;
+    value
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size {size:?} is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("274877906944").unwrap(), 274_877_906_944);
+        assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+        assert_eq!(parse_size("512m").unwrap(), 512 << 20);
+        assert_eq!(parse_size("1T").unwrap(), 1 << 40);
+        assert!(parse_size("2X").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn test_kernel_cmdline() {
+        assert_eq!(
+            kernel_cmdline("console=ttyS0 init=/init ", "ABCD"),
+            "console=ttyS0 init=/init dstack.rootfs_hash=abcd"
+        );
+    }
+
+    #[test]
+    fn test_parse_measurements() {
+        let mrtd = "b2".repeat(48);
+        let output = format!(
+            "Machine measurements:\nMRTD: {}\nRTMR0: {}\nRTMR1: {}\nRTMR2: {}\n",
+            mrtd.to_ascii_uppercase(),
+            "24".repeat(48),
+            "6e".repeat(48),
+            "89".repeat(48)
+        );
+        let bootchain = parse_measurements(&output).unwrap();
+        assert_eq!(bootchain.mrtd, mrtd);
+        assert_eq!(bootchain.rtmr2, "89".repeat(48));
+
+        assert!(parse_measurements("MRTD: b2\n").is_err());
+        let truncated = output.replace(&format!("RTMR1: {}\n", "6e".repeat(48)), "");
+        let error = parse_measurements(&truncated).unwrap_err().to_string();
+        assert_eq!(error, "dstack-mr printed no RTMR1");
+    }
+}
@@ -0,0 +1,355 @@
+//! `atlas policy`: check and convert attestation policy files.
+//!
+//! Policies are JSON, or YAML when the file name ends in `.yaml` or `.yml`;
+//! every `atlas` command that takes `--policy` reads both (see [`read`]).
+//!
+//! - `lint`: parses the policy and runs the checks connecting would, then
+//!   warns about settings that weaken it and prints its fingerprint.
+//! - `convert`: rewrites a policy from JSON to YAML or back.
+//!
+//! The fingerprint is the SHA-256 of the policy's canonical JSON (sorted keys,
+//! no whitespace) once defaults are filled in, so the same policy has the same
+//! fingerprint whatever its format, key order or omitted defaults.
+
+use crate::BoxError;
+use atlas_rs::{DstackTdxPolicy, Policy};
+use clap::{Subcommand, ValueEnum};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Check a policy for errors and insecure settings, and print its fingerprint
+    Lint {
+        /// Policy file (JSON or YAML)
+        policy: PathBuf,
+
+        /// Fail (exit status 1) on warnings too
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Convert a policy between JSON and YAML
+    Convert {
+        /// Policy file (JSON or YAML)
+        policy: PathBuf,
+
+        /// Output format [default: the other one]
+        #[arg(long)]
+        to: Option<Format>,
+
+        /// Write here instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// The format of the file `path`, by its extension.
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// Policy fields, as `DstackTdxPolicy` (de)serializes them.
+const FIELDS: [&str; 9] = [
+    "type",
+    "expected_bootchain",
+    "app_compose",
+    "os_image_hash",
+    "allowed_tcb_status",
+    "grace_period",
+    "pccs_url",
+    "cache_collateral",
+    "disable_runtime_verification",
+];
+
+const BOOTCHAIN_FIELDS: [&str; 4] = ["mrtd", "rtmr0", "rtmr1", "rtmr2"];
+
+pub fn run(command: Command) -> Result<(), BoxError> {
+    match command {
+        Command::Lint { policy, strict } => lint_file(&policy, strict),
+        Command::Convert { policy, to, out } => convert(&policy, to, out.as_deref()),
+    }
+}
+
+/// Read the attestation policy in `path`.
+pub fn read(path: &Path) -> Result<Policy, BoxError> {
+    let document = read_document(path)?;
+    serde_json::from_value(document)
+        .map_err(|e| format!("invalid policy {}: {e}", path.display()).into())
+}
+
+/// The policy document in `path`, before it's read as a [`Policy`].
+fn read_document(path: &Path) -> Result<Value, BoxError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read policy {}: {e}", path.display()))?;
+    let document = match Format::of(path) {
+        Format::Json => serde_json::from_str(&text).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+    };
+    document.map_err(|e| format!("invalid policy {}: {e}", path.display()).into())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Finding {
+    Error(String),
+    Warning(String),
+}
+
+fn lint_file(path: &Path, strict: bool) -> Result<(), BoxError> {
+    let document = read_document(path)?;
+    let (findings, fingerprint) = lint(&document);
+    for finding in &findings {
+        match finding {
+            Finding::Error(message) => println!("error: {message}"),
+            Finding::Warning(message) => println!("warning: {message}"),
+        }
+    }
+    if let Some(fingerprint) = fingerprint {
+        println!("fingerprint: {fingerprint}");
+    }
+    let errors = findings
+        .iter()
+        .filter(|finding| matches!(finding, Finding::Error(_)))
+        .count();
+    let warnings = findings.len() - errors;
+    if errors > 0 || (strict && warnings > 0) {
+        return Err(format!(
+            "{}: {errors} error(s), {warnings} warning(s)",
+            path.display()
+        )
+        .into());
+    }
+    println!("{}: ok ({warnings} warning(s))", path.display());
+    Ok(())
+}
+
+/// The problems with the policy `document`, and its fingerprint if it parses.
+fn lint(document: &Value) -> (Vec<Finding>, Option<String>) {
+    let mut findings = unknown_fields(document);
+    let policy: Policy = match serde_json::from_value(document.clone()) {
+        Ok(policy) => policy,
+        Err(e) => {
+            findings.push(Finding::Error(format!("not a valid policy: {e}")));
+            return (findings, None);
+        }
+    };
+    let fingerprint = fingerprint(&policy);
+    let Policy::DstackTdx(tdx) = &policy;
+    findings.extend(insecure_settings(tdx));
+    if let Err(e) = policy.into_verifier() {
+        findings.push(Finding::Error(e.to_string()));
+    }
+    (findings, Some(fingerprint))
+}
+
+/// Fields serde would ignore, which are mostly typos.
+fn unknown_fields(document: &Value) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let Some(fields) = document.as_object() else {
+        return findings;
+    };
+    for name in fields.keys() {
+        if !FIELDS.contains(&name.as_str()) {
+            findings.push(Finding::Warning(format!(
+                "unknown field `{name}` is ignored"
+            )));
+        }
+    }
+    if let Some(bootchain) = fields.get("expected_bootchain").and_then(Value::as_object) {
+        for name in bootchain.keys() {
+            if !BOOTCHAIN_FIELDS.contains(&name.as_str()) {
+                findings.push(Finding::Warning(format!(
+                    "unknown field `expected_bootchain.{name}` is ignored"
+                )));
+            }
+        }
+    }
+    findings
+}
+
+/// Settings that are valid but let weaker platforms or deployments through.
+fn insecure_settings(policy: &DstackTdxPolicy) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if policy.disable_runtime_verification {
+        findings.push(Finding::Warning(
+            "disable_runtime_verification is set: the bootchain, app compose and OS image are \
+             not checked, so any code in a genuine TEE passes (local testing only)"
+                .into(),
+        ));
+    }
+    let allows = |status: &str| policy.allowed_tcb_status.iter().any(|s| s == status);
+    if allows("OutOfDate") && policy.grace_period.is_none() {
+        findings.push(Finding::Warning(
+            "OutOfDate is allowed without a grace_period: platforms missing security updates \
+             are accepted indefinitely"
+                .into(),
+        ));
+    }
+    if allows("Revoked") {
+        findings.push(Finding::Warning(
+            "Revoked is allowed: platforms whose keys Intel revoked are accepted".into(),
+        ));
+    }
+    if let Some(url) = &policy.pccs_url {
+        if !url.is_empty() && !url.starts_with("https://") {
+            findings.push(Finding::Warning(format!(
+                "pccs_url {url} is not https: anyone on the path can serve stale collateral"
+            )));
+        }
+    }
+    findings
+}
+
+/// `sha256:` and the hex SHA-256 of the canonical JSON of `policy`.
+fn fingerprint(policy: &Policy) -> String {
+    let value = serde_json::to_value(policy).unwrap_or_default();
+    let mut canonical = String::new();
+    write_canonical(&value, &mut canonical);
+    format!(
+        "sha256:{}",
+        hex::encode(Sha256::digest(canonical.as_bytes()))
+    )
+}
+
+/// JSON with object keys sorted and no whitespace.
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(fields) => {
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            out.push('{');
+            for (index, name) in names.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(name.clone()).to_string());
+                out.push(':');
+                write_canonical(&fields[name], out);
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+fn convert(path: &Path, to: Option<Format>, out: Option<&Path>) -> Result<(), BoxError> {
+    let document = read_document(path)?;
+    // Refuse to convert what isn't a policy
+    serde_json::from_value::<Policy>(document.clone())
+        .map_err(|e| format!("invalid policy {}: {e}", path.display()))?;
+    let to = to.unwrap_or(match Format::of(path) {
+        Format::Json => Format::Yaml,
+        Format::Yaml => Format::Json,
+    });
+    let text = match to {
+        Format::Json => serde_json::to_string_pretty(&document)? + "\n",
+        Format::Yaml => serde_yaml::to_string(&document)?,
+    };
+    match out {
+        Some(out) => std::fs::write(out, text)
+            .map_err(|e| format!("failed to write {}: {e}", out.display()).into()),
+        None => {
+            print!("{text}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lint_flags_insecure_settings() {
+        let (findings, fingerprint) = lint(&json!({
+            "type": "dstack_tdx",
+            "disable_runtime_verification": true,
+            "allowed_tcb_status": ["UpToDate", "OutOfDate"],
+            "pccs_url": "http://pccs.internal",
+            "grace_perod": 86400,
+        }));
+        let warnings: Vec<&str> = findings
+            .iter()
+            .map(|finding| match finding {
+                Finding::Warning(message) => message.as_str(),
+                Finding::Error(message) => panic!("unexpected error: {message}"),
+            })
+            .collect();
+        assert_eq!(warnings.len(), 4);
+        assert!(warnings[0].contains("`grace_perod`"));
+        assert!(warnings[1].contains("disable_runtime_verification"));
+        assert!(warnings[2].contains("without a grace_period"));
+        assert!(warnings[3].contains("not https"));
+        assert!(fingerprint.unwrap().starts_with("sha256:"));
+    }
+
+    #[test]
+    fn test_lint_reports_errors() {
+        // Runtime verification on, but nothing to verify against
+        let (findings, _) = lint(&json!({ "type": "dstack_tdx" }));
+        assert!(matches!(&findings[..], [Finding::Error(_)]));
+
+        let (findings, fingerprint) = lint(&json!({
+            "type": "dstack_tdx",
+            "allowed_tcb_status": ["Fine"],
+            "disable_runtime_verification": true,
+        }));
+        assert!(findings
+            .iter()
+            .any(|finding| matches!(finding, Finding::Error(e) if e.contains("Fine"))));
+        assert!(fingerprint.is_some());
+
+        let (findings, fingerprint) = lint(&json!({ "type": "sev_snp" }));
+        assert!(matches!(&findings[..], [Finding::Error(_)]));
+        assert!(fingerprint.is_none());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_format_and_defaults() {
+        let explicit: Policy = serde_json::from_value(json!({
+            "type": "dstack_tdx",
+            "allowed_tcb_status": ["UpToDate"],
+            "disable_runtime_verification": true,
+        }))
+        .unwrap();
+        let yaml: Value =
+            serde_yaml::from_str("disable_runtime_verification: true\ntype: dstack_tdx\n").unwrap();
+        let implicit: Policy = serde_json::from_value(yaml).unwrap();
+        assert_eq!(fingerprint(&explicit), fingerprint(&implicit));
+
+        let dev = Policy::DstackTdx(DstackTdxPolicy::dev());
+        assert_ne!(fingerprint(&explicit), fingerprint(&dev));
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let mut out = String::new();
+        write_canonical(
+            &json!({ "b": [1, "x"], "a": { "d": null, "c": true } }),
+            &mut out,
+        );
+        assert_eq!(out, r#"{"a":{"c":true,"d":null},"b":[1,"x"]}"#);
+    }
+}
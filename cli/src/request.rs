@@ -0,0 +1,138 @@
+//! `atlas request`: send one HTTPS request over attested TLS, like curl.
+//!
+//! The attestation summary goes to stderr once the TEE is verified, then the
+//! response body streams to stdout (after its status line and headers with
+//! `--include`). A request whose attestation fails is never sent.
+
+use crate::summary::Summary;
+use crate::{http, BoxError};
+use atlas_rs::atls_connect;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use url::{Host, Url};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// `https://` URL to request
+    url: Url,
+
+    /// Attestation policy (JSON or YAML) the TEE must satisfy
+    #[arg(long)]
+    policy: PathBuf,
+
+    /// Request method [default: GET, or POST with --data]
+    #[arg(short = 'X', long = "request", value_name = "METHOD")]
+    method: Option<String>,
+
+    /// Extra header, `Name: value` (repeatable); replaces a default one
+    #[arg(short = 'H', long = "header", value_name = "HEADER")]
+    headers: Vec<String>,
+
+    /// Request body: the text itself, `@file` for a file or `@-` for stdin
+    #[arg(short = 'd', long)]
+    data: Option<String>,
+
+    /// Print the response status line and headers before the body
+    #[arg(short = 'i', long)]
+    include: bool,
+
+    /// Don't print the attestation summary
+    #[arg(short = 's', long)]
+    silent: bool,
+
+    /// Fail (exit status 1) when the response status is 400 or above
+    #[arg(short = 'f', long)]
+    fail: bool,
+
+    /// Connect to this `host:port` instead, still attesting the URL's host
+    #[arg(long, value_name = "HOST:PORT")]
+    connect_to: Option<String>,
+
+    /// Seconds to connect, handshake and verify the attestation in
+    #[arg(long, default_value_t = 30, value_name = "SECS")]
+    connect_timeout: u64,
+}
+
+pub async fn run(args: Args) -> Result<(), BoxError> {
+    if args.url.scheme() != "https" {
+        return Err(format!("{}: only https:// URLs can be attested", args.url).into());
+    }
+    // The name attested and sent as SNI, without an IPv6 address' brackets
+    let server_name = match args.url.host() {
+        Some(Host::Domain(domain)) => domain.to_string(),
+        Some(Host::Ipv4(ip)) => ip.to_string(),
+        Some(Host::Ipv6(ip)) => ip.to_string(),
+        None => return Err(format!("{}: the URL has no host", args.url).into()),
+    };
+    let address = match args.connect_to {
+        Some(address) => address,
+        None => format!(
+            "{}:{}",
+            args.url.host_str().unwrap_or_default(),
+            args.url.port_or_known_default().unwrap_or(443)
+        ),
+    };
+    let policy = crate::policy::read(&args.policy)?;
+    let body = match &args.data {
+        Some(data) => Some(read_data(data).await?),
+        None => None,
+    };
+    let method = match args.method {
+        Some(method) => method,
+        None if body.is_some() => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+    let request = http::request(&method, &args.url, &args.headers, body.as_deref())?;
+
+    let timeout = Duration::from_secs(args.connect_timeout);
+    let connect = async {
+        let start = Instant::now();
+        let tcp = TcpStream::connect(&address)
+            .await
+            .map_err(|e| format!("failed to connect to {address}: {e}"))?;
+        let tcp_connect = start.elapsed();
+        let alpn = Some(vec!["http/1.1".to_string()]);
+        let (tls, mut report) = atls_connect(tcp, &server_name, policy, alpn).await?;
+        report.timings_mut().tcp_connect = Some(tcp_connect);
+        Ok::<_, BoxError>((tls, report))
+    };
+    let (mut tls, report) = tokio::time::timeout(timeout, connect)
+        .await
+        .map_err(|_| format!("timed out after {timeout:?} connecting to {address}"))??;
+    if !args.silent {
+        eprint!("{}", Summary(&report));
+    }
+
+    tls.write_all(&request).await?;
+    tls.flush().await?;
+    let mut reader = BufReader::new(tls);
+    let head = http::read_head(&mut reader).await?;
+    let mut stdout = tokio::io::stdout();
+    if args.include {
+        stdout.write_all(&head.raw).await?;
+    }
+    let head_request = method.eq_ignore_ascii_case("HEAD");
+    http::copy_body(&mut reader, &head, head_request, &mut stdout).await?;
+    stdout.flush().await?;
+    if args.fail && head.status >= 400 {
+        return Err(format!("the server answered {}", head.status).into());
+    }
+    Ok(())
+}
+
+/// The request body `--data` names.
+async fn read_data(data: &str) -> Result<Vec<u8>, BoxError> {
+    match data.strip_prefix('@') {
+        Some("-") => {
+            let mut body = Vec::new();
+            tokio::io::stdin().read_to_end(&mut body).await?;
+            Ok(body)
+        }
+        Some(path) => tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("failed to read {path}: {e}").into()),
+        None => Ok(data.as_bytes().to_vec()),
+    }
+}
@@ -0,0 +1,57 @@
+//! Human-readable summary of a verified attestation, printed to stderr in
+//! curl's `* ` style so it stays apart from the response on stdout.
+
+use atlas_rs::{CollateralSource, Report};
+use std::fmt;
+
+/// The fields of a verified [`Report`] worth checking a deployment against.
+pub struct Summary<'a>(pub &'a Report);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Report::Tdx(tdx) => {
+                writeln!(f, "* attestation verified (TDX)")?;
+                writeln!(f, "*   TCB status: {}", tdx.status)?;
+                if !tdx.advisory_ids.is_empty() {
+                    writeln!(f, "*   advisories: {}", tdx.advisory_ids.join(", "))?;
+                }
+                if let Some(td) = tdx.report.as_td10() {
+                    writeln!(f, "*   MRTD:  {}", hex::encode(td.mr_td))?;
+                    writeln!(f, "*   RTMR0: {}", hex::encode(td.rt_mr0))?;
+                    writeln!(f, "*   RTMR1: {}", hex::encode(td.rt_mr1))?;
+                    writeln!(f, "*   RTMR2: {}", hex::encode(td.rt_mr2))?;
+                    writeln!(f, "*   RTMR3: {}", hex::encode(td.rt_mr3))?;
+                }
+                if let Some(fmspc) = &tdx.fmspc {
+                    writeln!(f, "*   FMSPC: {fmspc}")?;
+                }
+                match &tdx.collateral_source {
+                    Some(CollateralSource::Cache) => writeln!(f, "*   collateral: cache")?,
+                    Some(CollateralSource::Pccs(url)) => writeln!(f, "*   collateral: {url}")?,
+                    None => {}
+                }
+                let timings = &tdx.timings;
+                let phases = [
+                    ("connect", timings.tcp_connect),
+                    ("handshake", timings.tls_handshake),
+                    ("evidence", timings.evidence_exchange),
+                    ("collateral", timings.collateral_fetch),
+                    ("verification", timings.verification),
+                ];
+                let phases: Vec<String> = phases
+                    .iter()
+                    .filter_map(|(name, duration)| {
+                        duration.map(|d| format!("{name} {} ms", d.as_millis()))
+                    })
+                    .collect();
+                writeln!(
+                    f,
+                    "*   timings: {} (total {} ms)",
+                    phases.join(", "),
+                    timings.total().as_millis()
+                )
+            }
+        }
+    }
+}
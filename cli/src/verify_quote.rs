@@ -0,0 +1,347 @@
+//! `atlas verify-quote`: verify a recorded TDX quote against recorded
+//! collateral, without network access.
+//!
+//! Runs the pipeline of `DstackTDXVerifier::verify_quote_offline` as of
+//! `--at` and reports each check: those it ran until the first failure, then
+//! those it never reached, then those that need the live session (certificate
+//! and report data binding, RTMR3 replay, app compose, OS image) and can't be
+//! checked from a quote alone.
+
+use crate::{fetch_collateral, BoxError};
+use atlas_rs::{AtlsVerificationError, ErrorCode, Policy, QuoteCollateralV3, TdxReport, Verifier};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// The quote: raw bytes, or hex text
+    #[arg(long, value_name = "FILE")]
+    quote: PathBuf,
+
+    /// The collateral: JSON, as `QuoteCollateralV3` serializes it, or a
+    /// bundle from `atlas fetch-collateral` (`.tar`)
+    #[arg(long, value_name = "FILE")]
+    collateral: PathBuf,
+
+    /// Attestation policy (JSON or YAML) the quote must satisfy
+    #[arg(long, value_name = "FILE")]
+    policy: PathBuf,
+
+    /// Verify as of this time (RFC 3339, or Unix seconds) [default: now]
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    at: Option<DateTime<Utc>>,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+/// The checks of offline verification, in the order they run.
+const CHECKS: [(&str, &str); 4] = [
+    ("dcap", "DCAP quote signature and collateral"),
+    ("grace_period", "OutOfDate grace period"),
+    ("tcb_status", "TCB status allowed"),
+    ("bootchain", "bootchain (MRTD, RTMR0-2)"),
+];
+
+/// The checks that need the live TLS session or the event log.
+const SESSION_CHECKS: [(&str, &str); 5] = [
+    ("certificate_binding", "certificate in event log"),
+    ("report_data", "report data binds session"),
+    ("rtmr_replay", "RTMR3 event log replay"),
+    ("app_compose", "app compose hash"),
+    ("os_image", "OS image hash"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail,
+    /// Not reached after an earlier failure.
+    NotRun,
+    /// Turned off by the policy, or impossible offline.
+    Skipped,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail => "fail",
+            Outcome::NotRun => "not_run",
+            Outcome::Skipped => "skipped",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::NotRun => "----",
+            Outcome::Skipped => "SKIP",
+        }
+    }
+}
+
+struct Check {
+    name: &'static str,
+    description: &'static str,
+    outcome: Outcome,
+    detail: Option<String>,
+}
+
+pub fn run(args: Args) -> Result<(), BoxError> {
+    let quote = read_quote(&args.quote)?;
+    let collateral = read_collateral(&args.collateral)?;
+    let policy = crate::policy::read(&args.policy)?;
+    let at = args.at.unwrap_or_else(Utc::now);
+    let at_secs = u64::try_from(at.timestamp()).map_err(|_| "--at is before 1970")?;
+
+    let Policy::DstackTdx(tdx_policy) = &policy;
+    let runtime_verification = !tdx_policy.disable_runtime_verification;
+    let result = match policy.into_verifier()? {
+        Verifier::DstackTdx(verifier) => {
+            verifier.verify_quote_offline(&quote, &collateral, at_secs)
+        }
+    };
+    let checks = checks(&result, runtime_verification);
+
+    if args.json {
+        println!("{:#}", to_json(&result, &checks, at));
+    } else {
+        print_text(&result, &checks, at);
+    }
+    result.map(|_| ()).map_err(Into::into)
+}
+
+/// What each check came to, given the pipeline's `result`.
+fn checks(
+    result: &Result<TdxReport, AtlsVerificationError>,
+    runtime_verification: bool,
+) -> Vec<Check> {
+    let failed = result
+        .as_ref()
+        .err()
+        .map(|e| (stage(e.code()), e.to_string()));
+    let mut checks: Vec<Check> = CHECKS
+        .iter()
+        .enumerate()
+        .map(|(index, &(name, description))| {
+            let (outcome, detail) = match &failed {
+                Some((stage, message)) if *stage == index => (Outcome::Fail, Some(message.clone())),
+                Some((stage, _)) if *stage < index => (Outcome::NotRun, None),
+                _ => (Outcome::Pass, None),
+            };
+            Check {
+                name,
+                description,
+                outcome,
+                detail,
+            }
+        })
+        .collect();
+
+    if let Ok(report) = result {
+        checks[0].detail = report.fmspc.as_ref().map(|fmspc| format!("FMSPC {fmspc}"));
+        checks[2].detail = Some(report.status.clone());
+    }
+    let bootchain = &mut checks[3];
+    if !runtime_verification && bootchain.outcome == Outcome::Pass {
+        bootchain.outcome = Outcome::Skipped;
+        bootchain.detail = Some("runtime verification disabled".into());
+    }
+    checks.extend(SESSION_CHECKS.iter().map(|&(name, description)| Check {
+        name,
+        description,
+        outcome: Outcome::Skipped,
+        detail: Some("needs the live session".into()),
+    }));
+    checks
+}
+
+/// The index in `CHECKS` of the check that fails with `code`.
+fn stage(code: ErrorCode) -> usize {
+    match code {
+        ErrorCode::GracePeriodExpired | ErrorCode::TcbInfoInvalid => 1,
+        ErrorCode::TcbStatusRejected => 2,
+        ErrorCode::MrtdMismatch
+        | ErrorCode::BootchainMismatch
+        | ErrorCode::TeeTypeMismatch
+        | ErrorCode::InvalidConfiguration => 3,
+        _ => 0,
+    }
+}
+
+fn print_text(
+    result: &Result<TdxReport, AtlsVerificationError>,
+    checks: &[Check],
+    at: DateTime<Utc>,
+) {
+    println!("verified as of {}", at.to_rfc3339());
+    for check in checks {
+        let detail = check.detail.as_deref().unwrap_or_default();
+        println!(
+            "{}  {:<36} {detail}",
+            check.outcome.label(),
+            check.description
+        );
+    }
+    match result {
+        Ok(report) => {
+            println!("result: PASS");
+            if !report.advisory_ids.is_empty() {
+                println!("advisories: {}", report.advisory_ids.join(", "));
+            }
+            if let Some(td) = report.report.as_td10() {
+                println!("MRTD:        {}", hex::encode(td.mr_td));
+                println!("RTMR0:       {}", hex::encode(td.rt_mr0));
+                println!("RTMR1:       {}", hex::encode(td.rt_mr1));
+                println!("RTMR2:       {}", hex::encode(td.rt_mr2));
+                println!("RTMR3:       {}", hex::encode(td.rt_mr3));
+                println!("report data: {}", hex::encode(td.report_data));
+            }
+        }
+        Err(e) => println!("result: FAIL [{}]", e.code()),
+    }
+}
+
+fn to_json(
+    result: &Result<TdxReport, AtlsVerificationError>,
+    checks: &[Check],
+    at: DateTime<Utc>,
+) -> Value {
+    let checks: Vec<Value> = checks
+        .iter()
+        .map(|check| {
+            json!({
+                "name": check.name,
+                "description": check.description,
+                "result": check.outcome.as_str(),
+                "detail": check.detail,
+            })
+        })
+        .collect();
+    let (report, error) = match result {
+        Ok(report) => {
+            let td = report.report.as_td10();
+            let report = json!({
+                "tee_type": "tdx",
+                "tcb_status": report.status,
+                "advisory_ids": report.advisory_ids,
+                "fmspc": report.fmspc,
+                "measurement": td.map(|td| hex::encode(td.mr_td)),
+                "rtmr0": td.map(|td| hex::encode(td.rt_mr0)),
+                "rtmr1": td.map(|td| hex::encode(td.rt_mr1)),
+                "rtmr2": td.map(|td| hex::encode(td.rt_mr2)),
+                "rtmr3": td.map(|td| hex::encode(td.rt_mr3)),
+                "report_data": td.map(|td| hex::encode(td.report_data)),
+            });
+            (report, Value::Null)
+        }
+        Err(e) => (
+            Value::Null,
+            json!({ "code": e.code(), "message": e.to_string() }),
+        ),
+    };
+    json!({
+        "verified": result.is_ok(),
+        "at": at.to_rfc3339(),
+        "checks": checks,
+        "report": report,
+        "error": error,
+    })
+}
+
+/// The collateral in `path`, bare JSON or a bundle.
+fn read_collateral(path: &Path) -> Result<QuoteCollateralV3, BoxError> {
+    if fetch_collateral::is_bundle(path) {
+        return fetch_collateral::read_bundle(path);
+    }
+    let json =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    serde_json::from_slice(&json)
+        .map_err(|e| format!("invalid collateral {}: {e}", path.display()).into())
+}
+
+/// The quote in `path`, decoding it when the file holds hex text.
+pub fn read_quote(path: &Path) -> Result<Vec<u8>, BoxError> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let text = std::str::from_utf8(&bytes)
+        .map(str::trim)
+        .unwrap_or_default();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    match hex::decode(text) {
+        Ok(decoded) if !text.is_empty() => Ok(decoded),
+        _ => Ok(bytes),
+    }
+}
+
+fn parse_time(time: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(secs) = time.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0).ok_or_else(|| format!("{secs} is out of range"));
+    }
+    DateTime::parse_from_rfc3339(time)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| format!("expected RFC 3339 (2025-01-01T00:00:00Z) or Unix seconds: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(checks: &[Check]) -> Vec<Outcome> {
+        checks.iter().map(|check| check.outcome).collect()
+    }
+
+    #[test]
+    fn test_checks_after_a_failure_do_not_run() {
+        let result = Err(AtlsVerificationError::TcbStatusNotAllowed {
+            status: "OutOfDate".into(),
+            allowed: vec!["UpToDate".into()],
+        });
+        let found = checks(&result, true);
+        assert_eq!(
+            outcomes(&found[..4]),
+            [Outcome::Pass, Outcome::Pass, Outcome::Fail, Outcome::NotRun]
+        );
+        assert!(found[2].detail.as_deref().unwrap().contains("OutOfDate"));
+        assert!(found[4..]
+            .iter()
+            .all(|check| check.outcome == Outcome::Skipped));
+
+        let result = Err(AtlsVerificationError::quote("bad signature"));
+        let found = checks(&result, false);
+        assert_eq!(
+            outcomes(&found[..4]),
+            [
+                Outcome::Fail,
+                Outcome::NotRun,
+                Outcome::NotRun,
+                Outcome::NotRun
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_quote_decodes_hex() {
+        let dir = std::env::temp_dir().join(format!("atlas-quote-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let hex_path = dir.join("quote.hex");
+        std::fs::write(&hex_path, "0x0400ff\n").unwrap();
+        assert_eq!(read_quote(&hex_path).unwrap(), [0x04, 0x00, 0xff]);
+        let raw_path = dir.join("quote.bin");
+        std::fs::write(&raw_path, [0x04, 0x00, 0xff, 0x81]).unwrap();
+        assert_eq!(read_quote(&raw_path).unwrap(), [0x04, 0x00, 0xff, 0x81]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let time = parse_time("2025-01-01T00:00:00Z").unwrap();
+        assert_eq!(time.timestamp(), 1_735_689_600);
+        assert_eq!(parse_time("1735689600").unwrap(), time);
+        assert!(parse_time("yesterday").is_err());
+    }
+}
@@ -3,10 +3,11 @@
 //! This module provides the `atls_connect` function that combines TLS handshake
 //! with attestation verification in a single call.
 
-use log::debug;
+use tracing::{debug, instrument};
 
 use crate::error::AtlsVerificationError;
 use crate::policy::Policy;
+use crate::timing::Stopwatch;
 use crate::verifier::{AsyncByteStream, Report};
 use crate::AtlsVerifier;
 use rustls::pki_types::ServerName;
@@ -39,6 +40,17 @@ use futures_rustls::TlsConnector;
 /// # Returns
 ///
 /// A tuple of (TlsStream, peer_certificate_der, session_ekm) on success.
+///
+/// # Cancellation safety
+///
+/// Like [`atls_connect`], this takes ownership of `stream`; cancelling the
+/// future closes the transport.
+#[instrument(
+    name = "atls.handshake",
+    level = "debug",
+    skip(stream, alpn),
+    fields(tls_version = tracing::field::Empty, cipher_suite = tracing::field::Empty)
+)]
 pub async fn tls_handshake<S>(
     stream: S,
     server_name: &str,
@@ -67,9 +79,29 @@ where
     let tls_stream = connector
         .connect(server_name_parsed, stream)
         .await
-        .map_err(|e| AtlsVerificationError::TlsHandshake(e.to_string()))?;
+        .map_err(|e| AtlsVerificationError::tls_handshake_with("connection failed", e))?;
 
-    // Get peer certificate from the connection
+    let (_, conn) = tls_stream.get_ref();
+    let span = tracing::Span::current();
+    if let Some(version) = conn.protocol_version() {
+        span.record("tls_version", tracing::field::debug(version));
+    }
+    if let Some(suite) = conn.negotiated_cipher_suite() {
+        span.record("cipher_suite", tracing::field::debug(suite.suite()));
+    }
+    let (peer_cert, session_ekm) = session_binding(&tls_stream)?;
+
+    Ok((tls_stream, peer_cert, session_ekm))
+}
+
+/// Peer leaf certificate and session EKM of an established TLS stream.
+///
+/// These are the values attestation is bound to. [`tls_handshake`] returns
+/// them; callers that keep a stream open can capture them again, e.g. to
+/// re-run a verifier over the same session later.
+pub fn session_binding<S>(
+    tls_stream: &TlsStream<S>,
+) -> Result<(Vec<u8>, Vec<u8>), AtlsVerificationError> {
     let (_, conn) = tls_stream.get_ref();
     let peer_cert = conn
         .peer_certificates()
@@ -86,12 +118,12 @@ where
     let mut session_ekm = vec![0u8; 32];
     conn.export_keying_material(&mut session_ekm, b"EXPORTER-Channel-Binding", None)
         .map_err(|e| {
-            AtlsVerificationError::TlsHandshake(format!("Failed to extract session EKM: {}", e))
+            AtlsVerificationError::tls_handshake_with("Failed to extract session EKM", e)
         })?;
 
     debug!("Session EKM extracted ({} bytes)", session_ekm.len());
 
-    Ok((tls_stream, peer_cert, session_ekm))
+    Ok((peer_cert, session_ekm))
 }
 
 /// Establish a TLS connection with attestation verification.
@@ -103,6 +135,9 @@ where
 /// 4. Performs attestation verification over the TLS stream
 /// 5. Returns the verified TLS stream and attestation report
 ///
+/// The returned report carries the duration of each phase (see
+/// [`Report::timings`]), except `tcp_connect` which happens before this call.
+///
 /// # Arguments
 ///
 /// * `stream` - The underlying transport stream (e.g., TcpStream)
@@ -114,6 +149,25 @@ where
 ///
 /// A tuple of (TlsStream, Report) on success.
 ///
+/// # Cancellation safety
+///
+/// The returned future can be dropped at any point (e.g. by `tokio::time::timeout`
+/// or a losing `tokio::select!` branch). Since `stream` is moved in, dropping the
+/// future drops the transport and closes the connection: no partially handshaked
+/// or partially verified stream is ever handed back. Nothing outside the future
+/// holds locks across `.await`, and the verifier's collateral cache is only written
+/// with complete entries, so a cancelled call leaves no shared state behind.
+/// Retry by dialing a fresh connection.
+///
+/// # Tracing
+///
+/// The whole call runs inside an `atls.connect` span (fields `sni` and
+/// `server_name`) with child spans for the handshake and each verification
+/// step, so per-connection traces show up in any installed `tracing` subscriber.
+///
+/// With the `metrics` feature enabled, the outcome and latency are also
+/// recorded (see [`crate::metrics`]).
+///
 /// # Example
 ///
 /// ```no_run
@@ -137,21 +191,109 @@ pub async fn atls_connect<S>(
     policy: Policy,
     alpn: Option<Vec<String>>,
 ) -> Result<(TlsStream<S>, Report), AtlsVerificationError>
+where
+    S: AsyncByteStream + 'static,
+{
+    atls_connect_with_sni(stream, server_name, server_name, policy, alpn).await
+}
+
+/// Like [`atls_connect`], but sends `sni` in the ClientHello instead of `server_name`.
+///
+/// `server_name` is still the hostname the attestation is verified for (it is
+/// the `Host` of the quote request). Use this when the TEE sits behind a
+/// TLS-passthrough ingress that routes on an SNI distinct from its public name.
+#[instrument(name = "atls.connect", skip(stream, policy, alpn))]
+pub async fn atls_connect_with_sni<S>(
+    stream: S,
+    sni: &str,
+    server_name: &str,
+    policy: Policy,
+    alpn: Option<Vec<String>>,
+) -> Result<(TlsStream<S>, Report), AtlsVerificationError>
 where
     S: AsyncByteStream + 'static,
 {
     // Initialize logging (idempotent, only runs once)
     crate::logging::init();
 
-    let (mut tls_stream, peer_cert, session_ekm) = tls_handshake(stream, server_name, alpn).await?;
+    let result = connect_and_verify(stream, sni, server_name, policy, alpn).await;
+    match &result {
+        Ok((_, report)) => crate::metrics::record_success(report),
+        Err(e) => crate::metrics::record_failure(e),
+    }
+    result
+}
+
+async fn connect_and_verify<S>(
+    stream: S,
+    sni: &str,
+    server_name: &str,
+    policy: Policy,
+    alpn: Option<Vec<String>>,
+) -> Result<(TlsStream<S>, Report), AtlsVerificationError>
+where
+    S: AsyncByteStream + 'static,
+{
+    let handshake_timer = Stopwatch::start();
+    let (mut tls_stream, peer_cert, session_ekm) = tls_handshake(stream, sni, alpn).await?;
+    let tls_handshake_duration = handshake_timer.elapsed();
 
     debug!("Starting attestation verification");
     let verifier = policy.into_verifier()?;
-    let report = verifier
+    let mut report = verifier
         .verify(&mut tls_stream, &peer_cert, &session_ekm, server_name)
         .await?;
+    report.timings_mut().tls_handshake = Some(tls_handshake_duration);
 
     debug!("Attestation verification successful");
 
     Ok((tls_stream, report))
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::DstackTdxPolicy;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    fn ensure_crypto_provider() {
+        // ring and aws-lc-rs are both enabled in the workspace, so rustls can't pick one
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_connect_closes_transport() {
+        ensure_crypto_provider();
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let policy = Policy::DstackTdx(DstackTdxPolicy::dev());
+
+        // The server never answers the ClientHello, so the handshake hangs until cancelled.
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            atls_connect(client, "tee.example.com", policy, None),
+        )
+        .await;
+        assert!(result.is_err(), "connect should have been cancelled");
+
+        // The client half was dropped with the future: the server sees the
+        // ClientHello followed by EOF instead of a dangling connection.
+        let mut received = Vec::new();
+        let n = tokio::time::timeout(Duration::from_secs(1), server.read_to_end(&mut received))
+            .await
+            .expect("transport should be closed after cancellation")
+            .unwrap();
+        assert!(n > 0, "ClientHello should have been sent before cancellation");
+        assert_eq!(received[0], 0x16, "first record should be a TLS handshake");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_sni_is_rejected() {
+        ensure_crypto_provider();
+        let (client, _server) = tokio::io::duplex(64 * 1024);
+        let policy = Policy::DstackTdx(DstackTdxPolicy::dev());
+
+        let result = atls_connect_with_sni(client, "bad sni", "tee.example.com", policy, None).await;
+        assert!(matches!(result, Err(AtlsVerificationError::InvalidServerName(_))));
+    }
+}
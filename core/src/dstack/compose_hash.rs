@@ -1,9 +1,20 @@
 //! Compose hash calculation module.
 //!
-//! Provides SHA256 hashing of AppCompose configurations.
+//! Provides SHA256 hashing of AppCompose configurations, and the
+//! `compose-hash` event a dstack CVM records for one.
 
+use dstack_sdk_types::dstack::EventLog;
 use serde_json::Value;
-use sha2::{Digest, Sha256};
+use sha2::{Digest, Sha256, Sha384};
+
+/// IMR (RTMR3) that dstack extends with runtime events such as `compose-hash`.
+pub const RUNTIME_EVENT_IMR: u32 = 3;
+
+/// Event type dstack uses for runtime events.
+pub const RUNTIME_EVENT_TYPE: u32 = 0x0800_0001;
+
+/// Name of the event carrying the app compose hash.
+pub const COMPOSE_HASH_EVENT: &str = "compose-hash";
 
 /// Calculate SHA256 hash of app compose configuration.
 ///
@@ -34,3 +45,53 @@ pub fn get_compose_hash(app_compose: &Value) -> Result<String, serde_json::Error
     let hash = Sha256::digest(json_str.as_bytes());
     Ok(hex::encode(hash))
 }
+
+/// The `compose-hash` event a dstack CVM running `app_compose` records in its
+/// event log.
+///
+/// Verification looks this event up and compares its `event_payload` with
+/// [`get_compose_hash`]; `digest` is what the event extends into RTMR3:
+/// SHA-384 of the event type (little-endian), `:`, the event name, `:` and
+/// the raw 32-byte hash.
+pub fn get_compose_hash_event(app_compose: &Value) -> Result<EventLog, serde_json::Error> {
+    let payload = Sha256::digest(serde_json::to_string(app_compose)?.as_bytes());
+
+    let mut hasher = Sha384::new();
+    hasher.update(RUNTIME_EVENT_TYPE.to_le_bytes());
+    hasher.update(b":");
+    hasher.update(COMPOSE_HASH_EVENT.as_bytes());
+    hasher.update(b":");
+    hasher.update(payload);
+
+    Ok(EventLog {
+        imr: RUNTIME_EVENT_IMR,
+        event_type: RUNTIME_EVENT_TYPE,
+        digest: hex::encode(hasher.finalize()),
+        event: COMPOSE_HASH_EVENT.to_string(),
+        event_payload: hex::encode(payload),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compose_hash_event_matches_compose_hash() {
+        let compose = json!({"runner": "docker-compose"});
+        let event = get_compose_hash_event(&compose).unwrap();
+
+        assert_eq!(event.event, "compose-hash");
+        assert_eq!(event.imr, 3);
+        assert_eq!(event.event_payload, get_compose_hash(&compose).unwrap());
+        assert_eq!(event.digest.len(), 96);
+    }
+
+    #[test]
+    fn test_compose_hash_is_sha256_of_compact_json() {
+        let compose = json!({"runner": "docker-compose"});
+        let expected = hex::encode(Sha256::digest(br#"{"runner":"docker-compose"}"#));
+        assert_eq!(get_compose_hash(&compose).unwrap(), expected);
+    }
+}
@@ -1,15 +1,20 @@
 //! Dstack-specific TDX verifier implementation.
 //!
 //! This module contains the `DstackTDXVerifier` and related types
-//! specific to dstack deployments.
+//! specific to dstack deployments, and the [`AtlsAcceptor`] serving the
+//! other end of the protocol from inside a CVM.
 
 pub mod compose_hash;
 pub mod config;
 pub mod default_app_compose;
 pub mod policy;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
 mod verifier;
 
 pub use config::{DstackTDXVerifierBuilder, DstackTDXVerifierConfig};
 pub use default_app_compose::{get_default_app_compose, merge_with_default_app_compose};
 pub use policy::DstackTdxPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+pub use server::{AtlsAcceptor, GuestAgent};
 pub use verifier::DstackTDXVerifier;
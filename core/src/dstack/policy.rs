@@ -76,6 +76,27 @@ impl Default for DstackTdxPolicy {
     }
 }
 
+/// Reference measurements of a published dstack OS release.
+///
+/// RTMR0 is left out: it measures the virtual hardware (vCPUs, memory, GPUs,
+/// QEMU version), so it differs between VM shapes running the same release.
+struct DstackRelease {
+    tag: &'static str,
+    os_image_hash: &'static str,
+    mrtd: &'static str,
+    rtmr1: &'static str,
+    rtmr2: &'static str,
+}
+
+/// Releases known to [`DstackTdxPolicy::for_dstack_release`].
+const DSTACK_RELEASES: &[DstackRelease] = &[DstackRelease {
+    tag: "0.5.4.1-nvidia",
+    os_image_hash: "86b181377635db21c415f9ece8cc8505f7d4936ad3be7043969005a8c4690c1a",
+    mrtd: "b24d3b24e9e3c16012376b52362ca09856c4adecb709d5fac33addf1c47e193da075b125b6c364115771390a5461e217",
+    rtmr1: "6e1afb7464ed0b941e8f5bf5b725cf1df9425e8105e3348dca52502f27c453f3018a28b90749cf05199d5a17820101a7",
+    rtmr2: "89e73cedf48f976ffebe8ac1129790ff59a0f52d54d969cb73455b1a79793f1dc16edc3b1fccc0fd65ea5905774bbd57",
+}];
+
 /// Check if a string is a valid lowercase hex string.
 fn is_valid_hex(s: &str) -> bool {
     !s.is_empty() && s.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase())
@@ -98,6 +119,56 @@ impl DstackTdxPolicy {
         }
     }
 
+    /// Strict policy for production.
+    ///
+    /// Only `UpToDate` platforms are accepted, with no grace period, and the
+    /// bootchain, OS image and app compose are all pinned.
+    pub fn strict(
+        expected_bootchain: ExpectedBootchain,
+        os_image_hash: impl Into<String>,
+        app_compose: serde_json::Value,
+    ) -> Self {
+        Self {
+            expected_bootchain: Some(expected_bootchain),
+            os_image_hash: Some(os_image_hash.into()),
+            app_compose: Some(app_compose),
+            ..Self::default()
+        }
+    }
+
+    /// Policy pinned to the measurements of a published dstack release.
+    ///
+    /// Fills in `os_image_hash` and `expected_bootchain` for `tag` (e.g.
+    /// `"0.5.4.1-nvidia"`, a leading `v` is accepted). `rtmr0` depends on the
+    /// VM shape rather than the release: compute it with `atlas measure` for
+    /// the deployment's CPU, memory and GPU configuration. `app_compose` is
+    /// deployment-specific and must still be set.
+    pub fn for_dstack_release(
+        tag: &str,
+        rtmr0: impl Into<String>,
+    ) -> Result<Self, AtlsVerificationError> {
+        let tag = tag.strip_prefix('v').unwrap_or(tag);
+        let release = DSTACK_RELEASES
+            .iter()
+            .find(|r| r.tag == tag)
+            .ok_or_else(|| {
+                let known: Vec<_> = DSTACK_RELEASES.iter().map(|r| r.tag).collect();
+                AtlsVerificationError::Configuration(format!(
+                    "unknown dstack release '{tag}', known releases are: {known:?}"
+                ))
+            })?;
+        Ok(Self {
+            expected_bootchain: Some(ExpectedBootchain {
+                mrtd: release.mrtd.into(),
+                rtmr0: rtmr0.into(),
+                rtmr1: release.rtmr1.into(),
+                rtmr2: release.rtmr2.into(),
+            }),
+            os_image_hash: Some(release.os_image_hash.into()),
+            ..Self::default()
+        })
+    }
+
     /// Validate the policy configuration.
     ///
     /// Checks that:
@@ -222,6 +293,37 @@ mod tests {
         assert!(policy.disable_runtime_verification);
     }
 
+    #[test]
+    fn test_dstack_tdx_policy_strict() {
+        let bootchain = ExpectedBootchain {
+            mrtd: "aa".into(),
+            rtmr0: "bb".into(),
+            rtmr1: "cc".into(),
+            rtmr2: "dd".into(),
+        };
+        let policy = DstackTdxPolicy::strict(bootchain, "ee", serde_json::json!({}));
+        assert!(policy.validate().is_ok());
+        assert!(policy.expected_bootchain.is_some());
+        assert_eq!(policy.os_image_hash.as_deref(), Some("ee"));
+        assert!(policy.app_compose.is_some());
+        assert_eq!(policy.allowed_tcb_status, vec!["UpToDate"]);
+        assert!(!policy.disable_runtime_verification);
+    }
+
+    #[test]
+    fn test_dstack_tdx_policy_for_dstack_release() {
+        let policy = DstackTdxPolicy::for_dstack_release("v0.5.4.1-nvidia", "ab12").unwrap();
+        assert!(policy.validate().is_ok());
+        let bootchain = policy.expected_bootchain.unwrap();
+        assert_eq!(bootchain.rtmr0, "ab12");
+        assert!(bootchain.mrtd.starts_with("b24d3b24"));
+        assert!(policy.os_image_hash.is_some());
+        assert!(!policy.disable_runtime_verification);
+
+        let err = DstackTdxPolicy::for_dstack_release("0.0.0", "ab12").unwrap_err();
+        assert!(err.to_string().contains("0.5.4.1-nvidia"));
+    }
+
     #[test]
     fn test_dstack_tdx_policy_json_roundtrip() {
         let policy = DstackTdxPolicy {
@@ -0,0 +1,694 @@
+//! Server side of dstack aTLS.
+//!
+//! [`AtlsAcceptor`] terminates TLS with the CVM's certificate and answers the
+//! client's `POST /tdx_quote` with a quote bound to the TLS session, which is
+//! what [`DstackTDXVerifier`](super::DstackTDXVerifier) checks on the other
+//! end. Quotes and events come from the dstack guest agent ([`GuestAgent`]),
+//! so this only works inside a dstack CVM.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, instrument};
+
+use crate::error::AtlsVerificationError;
+use crate::verifier::AsyncByteStream;
+
+pub use tokio_rustls::server::TlsStream as ServerTlsStream;
+
+/// Where the dstack guest agent listens inside a CVM.
+pub const DEFAULT_AGENT_SOCKET: &str = "/var/run/dstack.sock";
+
+/// Event the verifier takes the TLS certificate binding from (the last one wins).
+const CERT_EVENT: &str = "New TLS Certificate";
+
+/// Largest `/tdx_quote` request (headers and body) the acceptor reads.
+const MAX_QUOTE_REQUEST: usize = 16 * 1024;
+
+/// Largest guest agent response read; quotes with event logs are well under this.
+const MAX_AGENT_RESPONSE: usize = 4 * 1024 * 1024;
+
+/// Client of the dstack guest agent's HTTP API on its Unix socket.
+#[derive(Debug, Clone)]
+pub struct GuestAgent {
+    socket: PathBuf,
+}
+
+impl Default for GuestAgent {
+    fn default() -> Self {
+        Self::new(DEFAULT_AGENT_SOCKET)
+    }
+}
+
+impl GuestAgent {
+    /// Talk to the agent listening on `socket`.
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: socket.into(),
+        }
+    }
+
+    /// Path of the agent's socket.
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Ask the agent for a quote over `report_data`.
+    ///
+    /// Returns the agent's `GetQuote` response as is (`quote`, `event_log`,
+    /// ...), the object clients expect under `quote` in the `/tdx_quote` reply.
+    pub async fn get_quote(
+        &self,
+        report_data: &[u8; 64],
+    ) -> Result<serde_json::Value, AtlsVerificationError> {
+        let body = serde_json::json!({ "report_data": hex::encode(report_data) });
+        let response = self.call("/GetQuote", &body).await?;
+        serde_json::from_slice(&response).map_err(|e| {
+            self.error(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid GetQuote response: {e}"),
+            )
+        })
+    }
+
+    /// Record an event in the CVM's runtime event log (extending RTMR3).
+    pub async fn emit_event(
+        &self,
+        event: &str,
+        payload: &[u8],
+    ) -> Result<(), AtlsVerificationError> {
+        let body = serde_json::json!({ "event": event, "payload": hex::encode(payload) });
+        self.call("/EmitEvent", &body).await.map(|_| ())
+    }
+
+    /// An I/O error naming the agent: its failures are the server's, not the peer's.
+    fn error(&self, kind: std::io::ErrorKind, message: String) -> AtlsVerificationError {
+        AtlsVerificationError::Io(std::io::Error::new(
+            kind,
+            format!("guest agent at {}: {message}", self.socket.display()),
+        ))
+    }
+
+    /// POST `body` to `path` and return the response body of a 200 reply.
+    async fn call(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<Vec<u8>, AtlsVerificationError> {
+        let invalid = |message: String| self.error(std::io::ErrorKind::InvalidData, message);
+
+        let mut stream = UnixStream::connect(&self.socket)
+            .await
+            .map_err(|e| self.error(e.kind(), format!("connect failed: {e}")))?;
+        let body = body.to_string();
+        let request = format!(
+            "POST {path} HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\
+             \r\n\
+             {body}",
+            body.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| self.error(e.kind(), format!("{path} request failed: {e}")))?;
+
+        // `Connection: close`: the response ends at EOF
+        let mut response = Vec::new();
+        (&mut stream)
+            .take(MAX_AGENT_RESPONSE as u64 + 1)
+            .read_to_end(&mut response)
+            .await
+            .map_err(|e| self.error(e.kind(), format!("{path} response failed: {e}")))?;
+        if response.len() > MAX_AGENT_RESPONSE {
+            return Err(invalid(format!(
+                "{path} response exceeds {MAX_AGENT_RESPONSE} bytes"
+            )));
+        }
+
+        let body_start = find_body_start(&response)
+            .ok_or_else(|| invalid(format!("{path} response is not HTTP")))?;
+        let head = String::from_utf8_lossy(&response[..body_start]);
+        let status = head.split_whitespace().nth(1).unwrap_or_default();
+        let body = &response[body_start..];
+        if status != "200" {
+            return Err(self.error(
+                std::io::ErrorKind::Other,
+                format!(
+                    "{path} failed with status {status}: {}",
+                    String::from_utf8_lossy(body).trim()
+                ),
+            ));
+        }
+
+        let chunked = header(&head, "transfer-encoding")
+            .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+        if chunked {
+            return decode_chunked(body)
+                .ok_or_else(|| invalid(format!("{path} sent a malformed chunked body")));
+        }
+        match header(&head, "content-length").and_then(|value| value.parse::<usize>().ok()) {
+            Some(length) if length <= body.len() => Ok(body[..length].to_vec()),
+            Some(_) => Err(invalid(format!("{path} response was truncated"))),
+            None => Ok(body.to_vec()),
+        }
+    }
+}
+
+/// Accepts aTLS connections inside a dstack CVM.
+///
+/// Each accepted connection completes the TLS handshake with the configured
+/// certificate, then serves the client's quote request before it is handed to
+/// the application. The certificate must be the one registered in the event
+/// log (see [`register_certificate`](Self::register_certificate)), or clients
+/// reject the connection with `CertificateNotInEventLog`.
+///
+/// # Example
+///
+/// ```no_run
+/// use atlas_rs::dstack::{AtlsAcceptor, GuestAgent};
+///
+/// # async fn example(cert_pem: &[u8], key_pem: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+/// let acceptor = AtlsAcceptor::from_pem(cert_pem, key_pem, GuestAgent::default(), None)?;
+/// acceptor.register_certificate().await?;
+///
+/// let listener = tokio::net::TcpListener::bind("0.0.0.0:8443").await?;
+/// loop {
+///     let (tcp, _) = listener.accept().await?;
+///     let tls_stream = acceptor.accept(tcp).await?;
+///     // serve the application over `tls_stream`
+/// #   drop(tls_stream);
+/// }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AtlsAcceptor {
+    acceptor: TlsAcceptor,
+    leaf: CertificateDer<'static>,
+    agent: GuestAgent,
+}
+
+impl AtlsAcceptor {
+    /// Build an acceptor presenting `cert_chain` (leaf first) with `key`.
+    ///
+    /// `alpn` lists the protocols to accept, in order of preference; `None`
+    /// negotiates none.
+    pub fn new(
+        cert_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        agent: GuestAgent,
+        alpn: Option<Vec<String>>,
+    ) -> Result<Self, AtlsVerificationError> {
+        let leaf = cert_chain.first().cloned().ok_or_else(|| {
+            AtlsVerificationError::Configuration("certificate chain is empty".into())
+        })?;
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(cert_chain, key)
+            .map_err(|e| {
+                AtlsVerificationError::Configuration(format!("invalid certificate or key: {e}"))
+            })?;
+        if let Some(protocols) = alpn {
+            config.alpn_protocols = protocols.into_iter().map(String::into_bytes).collect();
+        }
+
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            leaf,
+            agent,
+        })
+    }
+
+    /// Like [`new`](Self::new), from a PEM certificate chain and PEM private key.
+    pub fn from_pem(
+        cert_chain_pem: &[u8],
+        key_pem: &[u8],
+        agent: GuestAgent,
+        alpn: Option<Vec<String>>,
+    ) -> Result<Self, AtlsVerificationError> {
+        let cert_chain = CertificateDer::pem_slice_iter(cert_chain_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                AtlsVerificationError::Configuration(format!("invalid certificate PEM: {e}"))
+            })?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem).map_err(|e| {
+            AtlsVerificationError::Configuration(format!("invalid private key PEM: {e}"))
+        })?;
+        Self::new(cert_chain, key, agent, alpn)
+    }
+
+    /// Record the leaf certificate in the event log, binding it to this CVM.
+    ///
+    /// Call it once per certificate, before accepting connections: every
+    /// call extends RTMR3, and clients only trust the last certificate
+    /// recorded.
+    pub async fn register_certificate(&self) -> Result<(), AtlsVerificationError> {
+        let cert_hash = hex::encode(Sha256::digest(self.leaf.as_ref()));
+        debug!(
+            "Registering TLS certificate {} with the guest agent",
+            cert_hash
+        );
+        self.agent
+            .emit_event(CERT_EVENT, cert_hash.as_bytes())
+            .await
+    }
+
+    /// Complete the TLS handshake on `stream` and serve the client's quote
+    /// request.
+    ///
+    /// The returned stream is positioned after the quote exchange, ready for
+    /// application traffic. On error the connection is dropped.
+    #[instrument(name = "atls.accept", level = "debug", skip_all)]
+    pub async fn accept<S>(&self, stream: S) -> Result<ServerTlsStream<S>, AtlsVerificationError>
+    where
+        S: AsyncByteStream + 'static,
+    {
+        let mut tls_stream = self
+            .acceptor
+            .accept(stream)
+            .await
+            .map_err(|e| AtlsVerificationError::tls_handshake_with("connection failed", e))?;
+        self.serve_quote(&mut tls_stream).await?;
+        Ok(tls_stream)
+    }
+
+    /// Read one `POST /tdx_quote` request from `tls_stream` and answer it.
+    ///
+    /// [`accept`](Self::accept) does this for the first request; call it
+    /// again when the application protocol lets clients re-attest an open
+    /// connection.
+    pub async fn serve_quote<S>(
+        &self,
+        tls_stream: &mut ServerTlsStream<S>,
+    ) -> Result<(), AtlsVerificationError>
+    where
+        S: AsyncByteStream,
+    {
+        let mut session_ekm = [0u8; 32];
+        tls_stream
+            .get_ref()
+            .1
+            .export_keying_material(&mut session_ekm, b"EXPORTER-Channel-Binding", None)
+            .map_err(|e| {
+                AtlsVerificationError::tls_handshake_with("Failed to extract session EKM", e)
+            })?;
+
+        let nonce = match read_quote_request(tls_stream).await {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                respond(tls_stream, "400 Bad Request", b"").await?;
+                return Err(e);
+            }
+        };
+
+        // The verifier expects report_data = SHA512(nonce || session_ekm)
+        let mut hasher = Sha512::new();
+        hasher.update(nonce);
+        hasher.update(session_ekm);
+        let report_data: [u8; 64] = hasher.finalize().into();
+
+        let quote = match self.agent.get_quote(&report_data).await {
+            Ok(quote) => quote,
+            Err(e) => {
+                respond(tls_stream, "502 Bad Gateway", b"").await?;
+                return Err(e);
+            }
+        };
+        let body = serde_json::json!({ "quote": quote }).to_string();
+        respond(tls_stream, "200 OK", body.as_bytes()).await?;
+        debug!("Served quote ({} bytes)", body.len());
+        Ok(())
+    }
+}
+
+/// Read a `POST /tdx_quote` request and return its nonce.
+async fn read_quote_request<S>(stream: &mut S) -> Result<[u8; 32], AtlsVerificationError>
+where
+    S: AsyncByteStream,
+{
+    let invalid = |message: String| {
+        AtlsVerificationError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            message,
+        ))
+    };
+
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let (body_start, content_length) = loop {
+        if let Some(body_start) = find_body_start(&request) {
+            let head = String::from_utf8_lossy(&request[..body_start]);
+            let request_line = head.lines().next().unwrap_or_default();
+            if request_line != "POST /tdx_quote HTTP/1.1" {
+                return Err(invalid(format!(
+                    "unexpected request {request_line:?}, expected POST /tdx_quote"
+                )));
+            }
+            let content_length = header(&head, "content-length")
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or_else(|| invalid("quote request has no Content-Length".into()))?;
+            break (body_start, content_length);
+        }
+        if request.len() >= MAX_QUOTE_REQUEST {
+            return Err(invalid("quote request headers too large".into()));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(invalid("connection closed before the quote request".into()));
+        }
+        request.extend_from_slice(&chunk[..n]);
+    };
+
+    let end = body_start + content_length;
+    if end > MAX_QUOTE_REQUEST {
+        return Err(invalid("quote request body too large".into()));
+    }
+    while request.len() < end {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(invalid("connection closed mid quote request".into()));
+        }
+        request.extend_from_slice(&chunk[..n]);
+    }
+    // The client waits for the quote before sending anything else
+    if request.len() > end {
+        return Err(invalid("unexpected data after the quote request".into()));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct QuoteRequest {
+        nonce_hex: String,
+    }
+    let body: QuoteRequest = serde_json::from_slice(&request[body_start..end])
+        .map_err(|e| invalid(format!("invalid quote request body: {e}")))?;
+    let mut nonce = [0u8; 32];
+    hex::decode_to_slice(&body.nonce_hex, &mut nonce)
+        .map_err(|e| invalid(format!("nonce_hex must be 32 hex-encoded bytes: {e}")))?;
+    Ok(nonce)
+}
+
+/// Write an HTTP/1.1 response; the connection stays open.
+async fn respond<S>(stream: &mut S, status: &str, body: &[u8]) -> Result<(), AtlsVerificationError>
+where
+    S: AsyncByteStream,
+{
+    let head = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         \r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Offset of the body, just past the blank line ending the headers.
+fn find_body_start(data: &[u8]) -> Option<usize> {
+    data.windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 4)
+}
+
+/// Value of the first header `name` (lowercase) in `head`.
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Decode a complete `Transfer-Encoding: chunked` body.
+fn decode_chunked(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|w| w == b"\r\n")?;
+        let size_field = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size_hex = size_field.split(';').next()?.trim();
+        let size = usize::from_str_radix(size_hex, 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        if data.len() < size + 2 || &data[size..size + 2] != b"\r\n" {
+            return None;
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::PrivatePkcs8KeyDer;
+    use rustls::{ClientConfig, RootCertStore};
+    use std::sync::Mutex;
+    use tokio::net::UnixListener;
+    use tokio_rustls::TlsConnector;
+
+    fn ensure_crypto_provider() {
+        // ring and aws-lc-rs are both enabled in the workspace, so rustls can't pick one
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    }
+
+    /// A guest agent on a Unix socket that answers `GetQuote` with the
+    /// report data as the "quote" and records the requests it gets.
+    struct FakeAgent {
+        _dir: tempfile::TempDir,
+        agent: GuestAgent,
+        requests: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+    }
+
+    impl FakeAgent {
+        fn start() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let socket = dir.path().join("dstack.sock");
+            let listener = UnixListener::bind(&socket).unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let seen = requests.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    let (path, body) = loop {
+                        let n = stream.read(&mut chunk).await.unwrap();
+                        assert!(n > 0, "agent client closed mid request");
+                        request.extend_from_slice(&chunk[..n]);
+                        let Some(body_start) = find_body_start(&request) else {
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&request[..body_start]).into_owned();
+                        let length: usize =
+                            header(&head, "content-length").unwrap().parse().unwrap();
+                        if request.len() < body_start + length {
+                            continue;
+                        }
+                        let path = head.split_whitespace().nth(1).unwrap().to_string();
+                        let body: serde_json::Value =
+                            serde_json::from_slice(&request[body_start..]).unwrap();
+                        break (path, body);
+                    };
+
+                    let reply = match path.as_str() {
+                        "/GetQuote" => serde_json::json!({
+                            "quote": body["report_data"],
+                            "event_log": "[]",
+                        })
+                        .to_string(),
+                        _ => "{}".to_string(),
+                    };
+                    seen.lock().unwrap().push((path, body));
+                    // Chunked, as agents built on HTTP frameworks may reply
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{reply}\r\n0\r\n\r\n",
+                        reply.len()
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+
+            Self {
+                agent: GuestAgent::new(socket),
+                _dir: dir,
+                requests,
+            }
+        }
+    }
+
+    /// An acceptor with a fresh self-signed certificate for `localhost`, and
+    /// a client config trusting it.
+    fn acceptor(agent: GuestAgent) -> (AtlsAcceptor, ClientConfig) {
+        ensure_crypto_provider();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert = certified.cert.der().clone();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()).into();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.clone()).unwrap();
+        let client = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        (
+            AtlsAcceptor::new(vec![cert], key, agent, None).unwrap(),
+            client,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_accept_serves_quote_bound_to_session() {
+        let agent = FakeAgent::start();
+        let (acceptor, client_config) = acceptor(agent.agent.clone());
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let server = tokio::spawn(async move {
+            let mut tls_stream = acceptor.accept(server).await.unwrap();
+            // Application traffic follows the quote exchange
+            let mut ping = [0u8; 4];
+            tls_stream.read_exact(&mut ping).await.unwrap();
+            tls_stream.write_all(b"pong").await.unwrap();
+            tls_stream.flush().await.unwrap();
+            ping
+        });
+
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let mut tls_stream = connector
+            .connect("localhost".try_into().unwrap(), client)
+            .await
+            .unwrap();
+        let (_, session_ekm) = crate::session_binding(&tls_stream).unwrap();
+
+        let nonce = [7u8; 32];
+        let body = serde_json::json!({ "nonce_hex": hex::encode(nonce) }).to_string();
+        let request = format!(
+            "POST /tdx_quote HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        tls_stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        let (body_start, length) = loop {
+            let mut chunk = [0u8; 4096];
+            let n = tls_stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "server closed before answering");
+            response.extend_from_slice(&chunk[..n]);
+            if let Some(body_start) = find_body_start(&response) {
+                let head = String::from_utf8_lossy(&response[..body_start]).into_owned();
+                assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+                let length: usize = header(&head, "content-length").unwrap().parse().unwrap();
+                if response.len() >= body_start + length {
+                    break (body_start, length);
+                }
+            }
+        };
+        let reply: serde_json::Value =
+            serde_json::from_slice(&response[body_start..body_start + length]).unwrap();
+
+        let expected: [u8; 64] = Sha512::new()
+            .chain_update(nonce)
+            .chain_update(&session_ekm)
+            .finalize()
+            .into();
+        assert_eq!(reply["quote"]["quote"], hex::encode(expected));
+        assert_eq!(reply["quote"]["event_log"], "[]");
+
+        tls_stream.write_all(b"ping").await.unwrap();
+        let mut pong = [0u8; 4];
+        tls_stream.read_exact(&mut pong).await.unwrap();
+        assert_eq!(&pong, b"pong");
+        assert_eq!(&server.await.unwrap(), b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_other_requests() {
+        let agent = FakeAgent::start();
+        let (acceptor, client_config) = acceptor(agent.agent.clone());
+        let (client, server) = tokio::io::duplex(64 * 1024);
+
+        let server = tokio::spawn(async move { acceptor.accept(server).await.map(|_| ()) });
+
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let mut tls_stream = connector
+            .connect("localhost".try_into().unwrap(), client)
+            .await
+            .unwrap();
+        tls_stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = [0u8; 24];
+        tls_stream.read_exact(&mut response).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 400 Bad Request"));
+
+        assert!(server.await.unwrap().is_err());
+        assert!(
+            agent.requests.lock().unwrap().is_empty(),
+            "no quote should be requested"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_register_certificate_emits_cert_hash() {
+        let agent = FakeAgent::start();
+        let (acceptor, _) = acceptor(agent.agent.clone());
+
+        acceptor.register_certificate().await.unwrap();
+
+        let requests = agent.requests.lock().unwrap();
+        let [(path, body)] = requests.as_slice() else {
+            panic!("expected one request, got {requests:?}");
+        };
+        assert_eq!(path, "/EmitEvent");
+        assert_eq!(body["event"], CERT_EVENT);
+        // The verifier hex-decodes the payload and compares it to the hex hash
+        let cert_hash = hex::encode(Sha256::digest(acceptor.leaf.as_ref()));
+        assert_eq!(body["payload"], hex::encode(cert_hash));
+    }
+
+    #[tokio::test]
+    async fn test_agent_error_status_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("dstack.sock");
+        let listener = UnixListener::bind(&socket).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut chunk = [0u8; 4096];
+            let _ = stream.read(&mut chunk).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4\r\n\r\nboom")
+                .await
+                .unwrap();
+        });
+
+        let err = GuestAgent::new(socket)
+            .get_quote(&[0; 64])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("status 500"), "{err}");
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        assert_eq!(
+            decode_chunked(b"3\r\nabc\r\n2;ext=1\r\nde\r\n0\r\n\r\n").as_deref(),
+            Some(&b"abcde"[..])
+        );
+        assert_eq!(decode_chunked(b"5\r\nabc\r\n0\r\n\r\n"), None);
+        assert_eq!(decode_chunked(b"3\r\nabc\r\n"), None);
+    }
+}
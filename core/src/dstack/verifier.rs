@@ -1,37 +1,27 @@
 //! DstackTDXVerifier implementation.
 
-use std::collections::{BTreeMap, HashMap};
-use std::sync::{Arc, RwLock};
+use std::collections::BTreeMap;
 
 use dcap_qvl::collateral::get_collateral;
 use dcap_qvl::quote::Quote;
 use dcap_qvl::verify::{verify, VerifiedReport};
 use dcap_qvl::QuoteCollateralV3;
 use dstack_sdk_types::dstack::{EventLog, GetQuoteResponse};
-use log::{debug, warn};
+use tracing::field::Empty;
+use tracing::{debug, debug_span, instrument, warn, Instrument, Span};
 use sha2::{Digest, Sha256, Sha512};
 
-use crate::dstack::compose_hash::get_compose_hash;
+use crate::dstack::compose_hash::{get_compose_hash, COMPOSE_HASH_EVENT};
 use crate::dstack::config::DstackTDXVerifierConfig;
 use crate::error::AtlsVerificationError;
+use crate::tdx::collateral_cache;
 use crate::tdx::grace_period::enforce_grace_period;
+use crate::tdx::{CollateralSource, TdxReport};
+use crate::timing::{PhaseTimings, Stopwatch};
 use crate::verifier::{AsyncByteStream, AsyncReadExt, AsyncWriteExt, AtlsVerifier, Report};
 
 pub use crate::dstack::config::DstackTDXVerifierBuilder;
 
-/// Cache key for collateral: (pccs_url, fmspc, ca)
-type CollateralCacheKey = (String, String, &'static str);
-
-/// Cached collateral with timestamp for TTL expiration.
-#[derive(Clone)]
-struct CachedCollateral {
-    collateral: QuoteCollateralV3,
-    cached_at_secs: u64,
-}
-
-/// Default collateral cache TTL: 8 hours (in seconds).
-const COLLATERAL_CACHE_TTL_SECS: u64 = 8 * 3600;
-
 /// Response from the /tdx_quote endpoint.
 #[derive(Debug, serde::Deserialize)]
 struct QuoteEndpointResponse {
@@ -48,10 +38,11 @@ struct QuoteEndpointResponse {
 /// 5. Verify bootchain measurements (MRTD, RTMR0-2)
 /// 6. Verify app compose hash
 /// 7. Verify OS image hash
+///
+/// With `cache_collateral` enabled, collateral is shared through the
+/// process-wide [`collateral_cache`], not per verifier.
 pub struct DstackTDXVerifier {
     config: DstackTDXVerifierConfig,
-    /// Cached collateral keyed by (pccs_url, fmspc, ca) with TTL expiration.
-    cached_collateral: Arc<RwLock<HashMap<CollateralCacheKey, CachedCollateral>>>,
 }
 
 impl DstackTDXVerifier {
@@ -70,10 +61,7 @@ impl DstackTDXVerifier {
                 ));
             }
         }
-        Ok(Self {
-            config,
-            cached_collateral: Arc::new(RwLock::new(HashMap::new())),
-        })
+        Ok(Self { config })
     }
 
     /// Create a new builder for DstackTDXVerifier.
@@ -82,7 +70,20 @@ impl DstackTDXVerifier {
     }
 
     /// Verify quote using dcap-qvl directly.
-    async fn verify_quote(&self, quote: &[u8]) -> Result<VerifiedReport, AtlsVerificationError> {
+    ///
+    /// Records the collateral retrieval duration in `timings`. Also returns the
+    /// quote's FMSPC and where the collateral came from, for diagnostics.
+    #[instrument(
+        name = "atls.quote_verify",
+        level = "debug",
+        skip_all,
+        fields(fmspc = Empty, ca = Empty, status = Empty)
+    )]
+    async fn verify_quote(
+        &self,
+        quote: &[u8],
+        timings: &mut PhaseTimings,
+    ) -> Result<(VerifiedReport, String, CollateralSource), AtlsVerificationError> {
         let pccs_url = self.config.pccs_url.as_deref().unwrap_or_default();
         let pccs_url = if pccs_url.is_empty() {
             "https://api.trustedservices.intel.com"
@@ -91,16 +92,23 @@ impl DstackTDXVerifier {
         };
 
         // Parse quote to get cache key components (FMSPC and CA)
-        let parsed_quote = Quote::parse(quote)
-            .map_err(|e| AtlsVerificationError::Quote(format!("Failed to parse quote: {}", e)))?;
-        let fmspc = hex::encode_upper(
-            parsed_quote
-                .fmspc()
-                .map_err(|e| AtlsVerificationError::Quote(format!("Failed to get FMSPC: {}", e)))?,
-        );
-        let ca = parsed_quote
-            .ca()
-            .map_err(|e| AtlsVerificationError::Quote(format!("Failed to get CA: {}", e)))?;
+        let (parsed_quote, fmspc, ca) = debug_span!("atls.quote_parse", len = quote.len())
+            .in_scope(|| {
+                let parsed_quote = Quote::parse(quote)
+                    .map_err(|e| AtlsVerificationError::quote_with("Failed to parse quote", e))?;
+                let fmspc = hex::encode_upper(
+                    parsed_quote
+                        .fmspc()
+                        .map_err(|e| AtlsVerificationError::quote_with("Failed to get FMSPC", e))?,
+                );
+                let ca = parsed_quote
+                    .ca()
+                    .map_err(|e| AtlsVerificationError::quote_with("Failed to get CA", e))?;
+                Ok::<_, AtlsVerificationError>((parsed_quote, fmspc, ca))
+            })?;
+        let span = Span::current();
+        span.record("fmspc", fmspc.as_str());
+        span.record("ca", ca);
 
         let cache_key = (pccs_url.to_string(), fmspc.clone(), ca);
 
@@ -108,9 +116,7 @@ impl DstackTDXVerifier {
         #[cfg(not(target_arch = "wasm32"))]
         let now_secs = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|e| {
-                AtlsVerificationError::Quote(format!("Failed to get current time: {}", e))
-            })?
+            .map_err(|e| AtlsVerificationError::quote_with("Failed to get current time", e))?
             .as_secs();
 
         #[cfg(target_arch = "wasm32")]
@@ -118,68 +124,75 @@ impl DstackTDXVerifier {
 
         // Try to get collateral from cache (with TTL check)
         let cached = if self.config.cache_collateral {
-            match self.cached_collateral.read() {
-                Ok(guard) => guard.get(&cache_key).and_then(|entry| {
-                    if now_secs.saturating_sub(entry.cached_at_secs) < COLLATERAL_CACHE_TTL_SECS {
-                        Some(entry.collateral.clone())
-                    } else {
-                        debug!("Cached collateral expired for FMSPC={}, CA={}", fmspc, ca);
-                        None
-                    }
-                }),
-                Err(_) => {
-                    warn!("Collateral cache lock poisoned, treating as cache miss");
-                    None
-                }
+            let cached = collateral_cache::get(&cache_key, now_secs);
+            if cached.is_none() {
+                debug!("No unexpired cached collateral for FMSPC={}, CA={}", fmspc, ca);
             }
+            cached
         } else {
             None
         };
 
-        let collateral = match cached {
+        let collateral_timer = Stopwatch::start();
+        let (collateral, collateral_source) = match cached {
             Some(c) => {
                 debug!(
                     "Using cached collateral for PCCS={}, FMSPC={}, CA={}",
                     pccs_url, fmspc, ca
                 );
-                c
+                (c, CollateralSource::Cache)
             }
             None => {
                 debug!("Fetching collateral from {}", pccs_url);
                 let c = get_collateral(pccs_url, quote)
+                    .instrument(debug_span!("atls.collateral_fetch", pccs_url, fmspc = %fmspc, ca))
                     .await
                     .map_err(|e| {
-                        AtlsVerificationError::Quote(format!("Failed to get collateral: {}", e))
+                        crate::metrics::record_collateral_error(pccs_url);
+                        AtlsVerificationError::collateral_with("Failed to get collateral", e)
                     })?;
 
                 // Cache if enabled
                 if self.config.cache_collateral {
-                    match self.cached_collateral.write() {
-                        Ok(mut guard) => {
-                            debug!("Caching collateral for FMSPC={}, CA={}", fmspc, ca);
-                            guard.insert(cache_key, CachedCollateral {
-                                collateral: c.clone(),
-                                cached_at_secs: now_secs,
-                            });
-                        }
-                        Err(_) => {
-                            warn!("Collateral cache lock poisoned, skipping cache write");
-                        }
-                    }
+                    debug!("Caching collateral for FMSPC={}, CA={}", fmspc, ca);
+                    collateral_cache::insert(cache_key, c.clone(), now_secs);
                 }
-                c
+                (c, CollateralSource::Pccs(pccs_url.to_string()))
             }
         };
+        timings.collateral_fetch = Some(collateral_timer.elapsed());
 
         debug!("Collateral received, verifying DCAP quote");
+        let report = self.check_quote(quote, &parsed_quote, &collateral, now_secs)?;
 
+        Ok((report, fmspc, collateral_source))
+    }
+
+    /// DCAP-verify `quote` against `collateral` at `now_secs`, then check its
+    /// TCB status against the policy (including the grace period).
+    fn check_quote(
+        &self,
+        quote: &[u8],
+        parsed_quote: &Quote,
+        collateral: &QuoteCollateralV3,
+        now_secs: u64,
+    ) -> Result<VerifiedReport, AtlsVerificationError> {
         // Verify the quote
-        let report = verify(quote, &collateral, now_secs)
-            .map_err(|e| AtlsVerificationError::Quote(format!("DCAP verification failed: {}", e)))?;
+        let report = verify(quote, collateral, now_secs).map_err(|e| {
+            match expired_collateral(collateral, now_secs) {
+                Some(item) => AtlsVerificationError::collateral_expired_with(
+                    format!("{item} is past its nextUpdate"),
+                    e,
+                ),
+                None => AtlsVerificationError::quote_with("DCAP verification failed", e),
+            }
+        })?;
 
         debug!("DCAP verification complete, TCB status: {}", report.status);
+        Span::current().record("status", report.status.as_str());
 
         // Check TCB status
+        let _tcb_span = debug_span!("atls.tcb_match", status = %report.status).entered();
         let tcb_allowed = self
             .config
             .allowed_tcb_status
@@ -187,6 +200,7 @@ impl DstackTDXVerifier {
             .any(|s| s == &report.status);
 
         debug!(
+            allowed = tcb_allowed,
             "TCB status '{}' allowed: {}",
             report.status, tcb_allowed
         );
@@ -197,7 +211,7 @@ impl DstackTDXVerifier {
         // extract the TCB date from the quote and collateral manually, which is not ideal.
         // We should update enforce_grace_period when dcap-qvl adds TCB info to the VerifiedReport.
         // This would remove almost all the tdx/grace_period.rs code.
-        enforce_grace_period(&report, &parsed_quote, &collateral, self.config.grace_period, now_secs)?;
+        enforce_grace_period(&report, parsed_quote, collateral, self.config.grace_period, now_secs)?;
 
         if !tcb_allowed {
             return Err(AtlsVerificationError::TcbStatusNotAllowed {
@@ -209,6 +223,36 @@ impl DstackTDXVerifier {
         Ok(report)
     }
 
+    /// Verify a recorded quote against recorded collateral, without network access.
+    ///
+    /// Runs the DCAP verification as of `now_secs` (Unix seconds), the TCB status
+    /// and grace period checks and, unless runtime verification is disabled, the
+    /// bootchain check. Checks that need the live session or the event log
+    /// (certificate binding, report data, RTMR replay, app compose, OS image) are
+    /// not performed. Useful to re-verify evidence kept for auditing.
+    pub fn verify_quote_offline(
+        &self,
+        quote: &[u8],
+        collateral: &QuoteCollateralV3,
+        now_secs: u64,
+    ) -> Result<TdxReport, AtlsVerificationError> {
+        let parsed_quote = Quote::parse(quote)
+            .map_err(|e| AtlsVerificationError::quote_with("Failed to parse quote", e))?;
+        let fmspc = parsed_quote
+            .fmspc()
+            .map_err(|e| AtlsVerificationError::quote_with("Failed to get FMSPC", e))?;
+
+        let verified = self.check_quote(quote, &parsed_quote, collateral, now_secs)?;
+        if !self.config.disable_runtime_verification {
+            self.verify_bootchain(&verified)?;
+        }
+
+        let mut report = TdxReport::new(verified);
+        report.fmspc = Some(hex::encode_upper(fmspc));
+        report.quote = quote.to_vec();
+        Ok(report)
+    }
+
     /// Verify bootchain measurements (MRTD, RTMR0-2) using the trusted verified report.
     ///
     /// Compares the cryptographically verified measurements from the report
@@ -295,17 +339,17 @@ impl DstackTDXVerifier {
             Some(event) => {
                 // event_payload is hex-encoded, decode it to get the cert hash string
                 let decoded = hex::decode(&event.event_payload).map_err(|e| {
-                    AtlsVerificationError::EventLogParse(format!(
-                        "failed to hex-decode certificate event payload: {}",
-                        e
-                    ))
+                    AtlsVerificationError::event_log_parse_with(
+                        "failed to hex-decode certificate event payload",
+                        e,
+                    )
                 })?;
 
                 let eventlog_cert_hash = String::from_utf8(decoded).map_err(|e| {
-                    AtlsVerificationError::EventLogParse(format!(
-                        "certificate event payload is not valid UTF-8: {}",
-                        e
-                    ))
+                    AtlsVerificationError::event_log_parse_with(
+                        "certificate event payload is not valid UTF-8",
+                        e,
+                    )
                 })?;
 
                 debug!("Certificate hash from event log: {}", eventlog_cert_hash);
@@ -343,7 +387,7 @@ impl DstackTDXVerifier {
         // Verify against event log (trusted after RTMR replay verification)
         let event = events
             .iter()
-            .find(|e| e.event == "compose-hash")
+            .find(|e| e.event == COMPOSE_HASH_EVENT)
             .ok_or_else(|| {
                 AtlsVerificationError::AppComposeHashMismatch {
                     expected: expected.clone(),
@@ -408,6 +452,7 @@ impl DstackTDXVerifier {
     ///
     /// Compares replayed RTMRs from the event log against the trusted values
     /// from the cryptographically verified report.
+    #[instrument(name = "atls.rtmr_replay", level = "debug", skip_all)]
     fn verify_rtmr_replay(
         &self,
         quote_response: &GetQuoteResponse,
@@ -437,7 +482,7 @@ impl DstackTDXVerifier {
 
         for i in 0..4u8 {
             let replayed_rtmr = replayed.get(&i).cloned().ok_or_else(|| {
-                AtlsVerificationError::Quote(format!(
+                AtlsVerificationError::quote(format!(
                     "RTMR{} missing from event log replay - malformed event log",
                     i
                 ))
@@ -505,6 +550,11 @@ impl DstackTDXVerifier {
 }
 
 impl AtlsVerifier for DstackTDXVerifier {
+    #[instrument(
+        name = "atls.verify",
+        skip_all,
+        fields(hostname = %hostname, tee = "tdx", status = Empty, measurement = Empty)
+    )]
     async fn verify<S>(
         &self,
         stream: &mut S,
@@ -516,19 +566,29 @@ impl AtlsVerifier for DstackTDXVerifier {
         S: AsyncByteStream,
     {
         debug!("Starting DStack TDX verification for {}", hostname);
+        let mut timings = PhaseTimings::default();
 
         // 1. Generate nonce and get quote via HTTP POST to /tdx_quote
         let mut nonce = [0u8; 32];
         rand::Rng::fill(&mut rand::thread_rng(), &mut nonce);
 
         // Get quote via HTTP POST to /tdx_quote
-        let quote_response = get_quote_over_http(stream, &nonce, hostname).await?;
+        let exchange_timer = Stopwatch::start();
+        let quote_response = get_quote_over_http(stream, &nonce, hostname)
+            .instrument(debug_span!("atls.evidence_exchange"))
+            .await?;
+        timings.evidence_exchange = Some(exchange_timer.elapsed());
+
+        // Everything from here on is verification (collateral fetch is subtracted below)
+        let verification_timer = Stopwatch::start();
 
         // 2. Parse event log using dstack-sdk-types
         debug!("Parsing event log");
-        let events = quote_response
-            .decode_event_log()
-            .map_err(|e| AtlsVerificationError::Other(e.into()))?;
+        let events = debug_span!("atls.event_log_parse").in_scope(|| {
+            quote_response
+                .decode_event_log()
+                .map_err(|e| AtlsVerificationError::event_log_parse_with("Failed to decode event log", e))
+        })?;
         debug!("Event log parsed, {} events found", events.len());
 
         // 3. Verify certificate in event log
@@ -542,11 +602,18 @@ impl AtlsVerifier for DstackTDXVerifier {
         debug!("Decoding quote for DCAP verification");
         let quote_bytes = quote_response
             .decode_quote()
-            .map_err(|e| AtlsVerificationError::Other(anyhow::anyhow!("Failed to decode quote: {}", e)))?;
+            .map_err(|e| AtlsVerificationError::quote_with("Failed to decode quote", e))?;
         debug!("Quote decoded ({} bytes)", quote_bytes.len());
 
         // Async quote verification - no blocking!
-        let verified_report = self.verify_quote(&quote_bytes).await?;
+        let (verified_report, fmspc, collateral_source) =
+            self.verify_quote(&quote_bytes, &mut timings).await?;
+
+        let span = Span::current();
+        span.record("status", verified_report.status.as_str());
+        if let Some(td) = verified_report.report.as_td10() {
+            span.record("measurement", hex::encode(td.mr_td).as_str());
+        }
 
         // 5. Verify report data
         let session_ekm: &[u8; 32] = session_ekm.try_into().map_err(|_| {
@@ -562,20 +629,32 @@ impl AtlsVerifier for DstackTDXVerifier {
         // Skip remaining checks if runtime verification is disabled
         if self.config.disable_runtime_verification {
             debug!("Runtime verification disabled, skipping bootchain/app-compose/os-image checks");
-            return Ok(Report::Tdx(verified_report));
-        }
+        } else {
+            // 7. Verify bootchain (MRTD, RTMR0-2) against verified report
+            self.verify_bootchain(&verified_report)?;
 
-        // 7. Verify bootchain (MRTD, RTMR0-2) against verified report
-        self.verify_bootchain(&verified_report)?;
+            // 8. Verify app compose hash against trusted event log
+            self.verify_app_compose(&events)?;
 
-        // 8. Verify app compose hash against trusted event log
-        self.verify_app_compose(&events)?;
+            // 9. Verify OS image hash against trusted event log
+            self.verify_os_image_hash(&events)?;
+        }
 
-        // 9. Verify OS image hash against trusted event log
-        self.verify_os_image_hash(&events)?;
+        timings.verification = Some(
+            verification_timer
+                .elapsed()
+                .saturating_sub(timings.collateral_fetch.unwrap_or_default()),
+        );
 
         debug!("DStack TDX verification complete");
-        Ok(Report::Tdx(verified_report))
+        Ok(Report::Tdx(TdxReport {
+            verified: verified_report,
+            timings,
+            fmspc: Some(fmspc),
+            collateral_source: Some(collateral_source),
+            quote: quote_bytes,
+            event_log: Some(quote_response.event_log),
+        }))
     }
 }
 
@@ -588,6 +667,12 @@ async fn get_quote_over_http<S>(
 where
     S: AsyncByteStream,
 {
+    // The hostname goes into the Host header verbatim: refuse anything that could
+    // end the line early and smuggle extra headers into the evidence request
+    if hostname.is_empty() || !hostname.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(AtlsVerificationError::InvalidServerName(hostname.escape_debug().to_string()));
+    }
+
     debug!("Sending POST /tdx_quote request to {}", hostname);
 
     // Build HTTP POST request for the /tdx_quote endpoint with EKM binding
@@ -609,14 +694,8 @@ where
         body_str
     );
 
-    stream
-        .write_all(request.as_bytes())
-        .await
-        .map_err(|e| AtlsVerificationError::Io(e.to_string()))?;
-    stream
-        .flush()
-        .await
-        .map_err(|e| AtlsVerificationError::Io(e.to_string()))?;
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
 
     // Read HTTP response
     let mut response_buf = Vec::new();
@@ -624,10 +703,7 @@ where
 
     // Read until we have the complete response
     loop {
-        let n = stream
-            .read(&mut chunk)
-            .await
-            .map_err(|e| AtlsVerificationError::Io(e.to_string()))?;
+        let n = stream.read(&mut chunk).await?;
         if n == 0 {
             break;
         }
@@ -648,15 +724,14 @@ where
 
     // Parse HTTP response
     let body_start = find_http_body_start(&response_buf)
-        .ok_or_else(|| AtlsVerificationError::Io("Invalid HTTP response".into()))?;
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid HTTP response")
+        })?;
     let response_body = &response_buf[body_start..];
 
     let response: QuoteEndpointResponse = serde_json::from_slice(response_body)
         .map_err(|e| {
-            AtlsVerificationError::Quote(format!(
-                "Failed to parse /tdx_quote response: {}",
-                e
-            ))
+            AtlsVerificationError::quote_with("Failed to parse /tdx_quote response", e)
         })?;
 
     Ok(response.quote)
@@ -683,3 +758,82 @@ fn parse_content_length(headers: &[u8]) -> Option<usize> {
     }
     None
 }
+
+/// The collateral item (TCB info or QE identity) past its `nextUpdate` at
+/// `now_secs`, if any. dcap-qvl rejects quotes with such collateral, and the
+/// fix is fresher collateral rather than a different quote.
+fn expired_collateral(collateral: &QuoteCollateralV3, now_secs: u64) -> Option<&'static str> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Validity {
+        next_update: String,
+    }
+
+    let items = [
+        ("TCB info", &collateral.tcb_info),
+        ("QE identity", &collateral.qe_identity),
+    ];
+    items
+        .into_iter()
+        .find(|(_, json)| {
+            serde_json::from_str::<Validity>(json)
+                .ok()
+                .and_then(|validity| chrono::DateTime::parse_from_rfc3339(&validity.next_update).ok())
+                .is_some_and(|next_update| now_secs as i64 > next_update.timestamp())
+        })
+        .map(|(item, _)| item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::{DstackTdxPolicy, IntoVerifier};
+
+    #[test]
+    fn test_verify_quote_offline_rejects_garbage_quote() {
+        let verifier = DstackTdxPolicy::dev().into_verifier().unwrap();
+        let collateral = QuoteCollateralV3 {
+            pck_crl_issuer_chain: String::new(),
+            root_ca_crl: Vec::new(),
+            pck_crl: Vec::new(),
+            tcb_info_issuer_chain: String::new(),
+            tcb_info: String::new(),
+            tcb_info_signature: Vec::new(),
+            qe_identity_issuer_chain: String::new(),
+            qe_identity: String::new(),
+            qe_identity_signature: Vec::new(),
+            pck_certificate_chain: None,
+        };
+
+        let err = verifier
+            .verify_quote_offline(b"not a quote", &collateral, 1_700_000_000)
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::QuoteInvalid);
+    }
+
+    #[test]
+    fn test_expired_collateral() {
+        let mut collateral = QuoteCollateralV3 {
+            pck_crl_issuer_chain: String::new(),
+            root_ca_crl: Vec::new(),
+            pck_crl: Vec::new(),
+            tcb_info_issuer_chain: String::new(),
+            tcb_info: r#"{"id":"TDX","nextUpdate":"2025-07-19T10:01:18Z"}"#.into(),
+            tcb_info_signature: Vec::new(),
+            qe_identity_issuer_chain: String::new(),
+            qe_identity: r#"{"id":"TD_QE","nextUpdate":"2025-08-01T00:00:00Z"}"#.into(),
+            qe_identity_signature: Vec::new(),
+            pck_certificate_chain: None,
+        };
+        // 2025-07-01, 2025-07-25 and 2025-09-01
+        assert_eq!(expired_collateral(&collateral, 1_751_328_000), None);
+        assert_eq!(expired_collateral(&collateral, 1_753_401_600), Some("TCB info"));
+        collateral.tcb_info = r#"{"nextUpdate":"2026-07-19T10:01:18Z"}"#.into();
+        assert_eq!(expired_collateral(&collateral, 1_756_684_800), Some("QE identity"));
+
+        // Unparseable collateral is the quote verification's to report
+        collateral.qe_identity = "not json".into();
+        assert_eq!(expired_collateral(&collateral, 1_756_684_800), None);
+    }
+}
@@ -1,17 +1,59 @@
 //! Error types for aTLS verification.
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+/// Boxed underlying error, preserved as [`std::error::Error::source`].
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Render `": <source>"` so the cause stays visible in single-line messages.
+fn cause(source: &Option<BoxError>) -> String {
+    source.as_ref().map(|e| format!(": {e}")).unwrap_or_default()
+}
+
 /// Errors that can occur during aTLS verification.
+///
+/// Variants that wrap a lower-level failure (I/O, rustls, dcap-qvl, hex/UTF-8
+/// decoding, ...) keep it as [`std::error::Error::source`], so callers can
+/// walk the chain and downcast to the concrete type:
+///
+/// ```
+/// use std::error::Error;
+/// use atlas_rs::AtlsVerificationError;
+///
+/// let err = AtlsVerificationError::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset));
+/// let io = err.source().and_then(|e| e.downcast_ref::<std::io::Error>()).unwrap();
+/// assert_eq!(io.kind(), std::io::ErrorKind::ConnectionReset);
+/// ```
 #[derive(Debug, Error)]
 pub enum AtlsVerificationError {
     /// I/O error during communication.
     #[error("I/O error: {0}")]
-    Io(String),
+    Io(#[from] std::io::Error),
 
     /// Quote verification failed.
-    #[error("quote verification failed: {0}")]
-    Quote(String),
+    #[error("quote verification failed: {message}{}", cause(.source))]
+    Quote {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// Collateral could not be retrieved from the PCCS.
+    #[error("collateral unavailable: {message}{}", cause(.source))]
+    Collateral {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// Collateral (TCB info, QE identity, CRLs) is past its validity period.
+    #[error("collateral expired: {message}{}", cause(.source))]
+    CollateralExpired {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
 
     /// Bootchain measurement mismatch.
     #[error("bootchain mismatch: {field} expected {expected}, got {actual}")]
@@ -34,8 +76,12 @@ pub enum AtlsVerificationError {
     CertificateNotInEventLog,
 
     /// Event log parsing failed.
-    #[error("failed to parse event log: {0}")]
-    EventLogParse(String),
+    #[error("failed to parse event log: {message}{}", cause(.source))]
+    EventLogParse {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
 
     /// TEE type mismatch.
     #[error("TEE type mismatch: {0}")]
@@ -57,8 +103,12 @@ pub enum AtlsVerificationError {
     TcbStatusNotAllowed { status: String, allowed: Vec<String> },
 
     /// TCB info could not be determined or parsed.
-    #[error("TCB info error: {0}")]
-    TcbInfoError(String),
+    #[error("TCB info error: {message}{}", cause(.source))]
+    TcbInfoError {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
 
     /// Grace period expired for an OutOfDate platform.
     #[error("grace period expired for status {status} (tcb_date: {tcb_date}, grace_period_secs: {grace_period_secs})")]
@@ -77,8 +127,12 @@ pub enum AtlsVerificationError {
     Configuration(String),
 
     /// TLS handshake failed.
-    #[error("TLS handshake failed: {0}")]
-    TlsHandshake(String),
+    #[error("TLS handshake failed: {message}{}", cause(.source))]
+    TlsHandshake {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
 
     /// Invalid server name.
     #[error("invalid server name: {0}")]
@@ -92,3 +146,214 @@ pub enum AtlsVerificationError {
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
+
+macro_rules! sourced_constructors {
+    ($($variant:ident => $name:ident, $name_with:ident;)*) => {
+        $(
+            #[doc = concat!("Build a [`AtlsVerificationError::", stringify!($variant), "`] without an underlying cause.")]
+            pub fn $name(message: impl Into<String>) -> Self {
+                Self::$variant {
+                    message: message.into(),
+                    source: None,
+                }
+            }
+
+            #[doc = concat!("Build a [`AtlsVerificationError::", stringify!($variant), "`] wrapping `source`.")]
+            pub fn $name_with(message: impl Into<String>, source: impl Into<BoxError>) -> Self {
+                Self::$variant {
+                    message: message.into(),
+                    source: Some(source.into()),
+                }
+            }
+        )*
+    };
+}
+
+impl AtlsVerificationError {
+    sourced_constructors! {
+        Quote => quote, quote_with;
+        Collateral => collateral, collateral_with;
+        CollateralExpired => collateral_expired, collateral_expired_with;
+        EventLogParse => event_log_parse, event_log_parse_with;
+        TcbInfoError => tcb_info, tcb_info_with;
+        TlsHandshake => tls_handshake, tls_handshake_with;
+    }
+
+    /// Stable, machine-readable code for this error.
+    ///
+    /// Unlike the `Display` message, codes are part of the public API and
+    /// do not change between releases.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::Io,
+            Self::Quote { .. } => ErrorCode::QuoteInvalid,
+            Self::Collateral { .. } => ErrorCode::CollateralUnavailable,
+            Self::CollateralExpired { .. } => ErrorCode::CollateralExpired,
+            Self::BootchainMismatch { field, .. } if field == "mrtd" => ErrorCode::MrtdMismatch,
+            Self::BootchainMismatch { .. } => ErrorCode::BootchainMismatch,
+            Self::RtmrMismatch { .. } => ErrorCode::RtmrReplayMismatch,
+            Self::CertificateNotInEventLog => ErrorCode::CertificateNotInEventLog,
+            Self::EventLogParse { .. } => ErrorCode::EventLogInvalid,
+            Self::TeeTypeMismatch(_) => ErrorCode::TeeTypeMismatch,
+            Self::AppComposeHashMismatch { .. } => ErrorCode::AppComposeMismatch,
+            Self::OsImageHashMismatch { .. } => ErrorCode::OsImageMismatch,
+            Self::TcbStatusNotAllowed { .. } => ErrorCode::TcbStatusRejected,
+            Self::TcbInfoError { .. } => ErrorCode::TcbInfoInvalid,
+            Self::GracePeriodExpired { .. } => ErrorCode::GracePeriodExpired,
+            Self::ReportDataMismatch { .. } => ErrorCode::ReportDataMismatch,
+            Self::Configuration(_) => ErrorCode::InvalidConfiguration,
+            Self::TlsHandshake { .. } => ErrorCode::TlsHandshakeFailed,
+            Self::InvalidServerName(_) => ErrorCode::InvalidServerName,
+            Self::MissingCertificate => ErrorCode::MissingCertificate,
+            Self::Other(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Machine-readable error codes for [`AtlsVerificationError`].
+///
+/// Serialized (and displayed) as `SCREAMING_SNAKE_CASE` strings, e.g. `TCB_STATUS_REJECTED`.
+/// The same strings are exposed as the `code` attribute of Python exceptions and the
+/// `code` property of JavaScript errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// I/O failure on the underlying stream.
+    Io,
+    /// Quote could not be parsed or failed DCAP verification.
+    QuoteInvalid,
+    /// Collateral could not be fetched from the PCCS.
+    CollateralUnavailable,
+    /// Collateral is past its validity period.
+    CollateralExpired,
+    /// MRTD does not match the expected bootchain.
+    MrtdMismatch,
+    /// RTMR0-2 do not match the expected bootchain.
+    BootchainMismatch,
+    /// Replaying the event log does not reproduce the quoted RTMRs.
+    RtmrReplayMismatch,
+    /// TLS certificate is not bound in the event log.
+    CertificateNotInEventLog,
+    /// Event log is malformed.
+    EventLogInvalid,
+    /// Report is for a different TEE type than expected.
+    TeeTypeMismatch,
+    /// App compose hash does not match the policy.
+    AppComposeMismatch,
+    /// OS image hash does not match the policy.
+    OsImageMismatch,
+    /// TCB status is not in the policy's allowed list.
+    TcbStatusRejected,
+    /// TCB info could not be determined.
+    TcbInfoInvalid,
+    /// OutOfDate platform is past its grace period.
+    GracePeriodExpired,
+    /// Report data does not bind the nonce and session EKM.
+    ReportDataMismatch,
+    /// Policy or verifier configuration is invalid.
+    InvalidConfiguration,
+    /// TLS handshake failed.
+    TlsHandshakeFailed,
+    /// Server name is not a valid DNS name or IP address.
+    InvalidServerName,
+    /// Server presented no certificate.
+    MissingCertificate,
+    /// Unclassified internal error.
+    Internal,
+}
+
+impl ErrorCode {
+    /// The code as a static string, e.g. `"MRTD_MISMATCH"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Io => "IO",
+            Self::QuoteInvalid => "QUOTE_INVALID",
+            Self::CollateralUnavailable => "COLLATERAL_UNAVAILABLE",
+            Self::CollateralExpired => "COLLATERAL_EXPIRED",
+            Self::MrtdMismatch => "MRTD_MISMATCH",
+            Self::BootchainMismatch => "BOOTCHAIN_MISMATCH",
+            Self::RtmrReplayMismatch => "RTMR_REPLAY_MISMATCH",
+            Self::CertificateNotInEventLog => "CERTIFICATE_NOT_IN_EVENT_LOG",
+            Self::EventLogInvalid => "EVENT_LOG_INVALID",
+            Self::TeeTypeMismatch => "TEE_TYPE_MISMATCH",
+            Self::AppComposeMismatch => "APP_COMPOSE_MISMATCH",
+            Self::OsImageMismatch => "OS_IMAGE_MISMATCH",
+            Self::TcbStatusRejected => "TCB_STATUS_REJECTED",
+            Self::TcbInfoInvalid => "TCB_INFO_INVALID",
+            Self::GracePeriodExpired => "GRACE_PERIOD_EXPIRED",
+            Self::ReportDataMismatch => "REPORT_DATA_MISMATCH",
+            Self::InvalidConfiguration => "INVALID_CONFIGURATION",
+            Self::TlsHandshakeFailed => "TLS_HANDSHAKE_FAILED",
+            Self::InvalidServerName => "INVALID_SERVER_NAME",
+            Self::MissingCertificate => "MISSING_CERTIFICATE",
+            Self::Internal => "INTERNAL",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootchain_mismatch_codes() {
+        let mrtd = AtlsVerificationError::BootchainMismatch {
+            field: "mrtd".into(),
+            expected: "aa".into(),
+            actual: "bb".into(),
+        };
+        assert_eq!(mrtd.code(), ErrorCode::MrtdMismatch);
+
+        let rtmr = AtlsVerificationError::BootchainMismatch {
+            field: "rtmr1".into(),
+            expected: "aa".into(),
+            actual: "bb".into(),
+        };
+        assert_eq!(rtmr.code(), ErrorCode::BootchainMismatch);
+    }
+
+    #[test]
+    fn test_source_chain_preserved() {
+        use std::error::Error;
+
+        let inner = std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "eof");
+        let err = AtlsVerificationError::tls_handshake_with("handshake aborted", inner);
+
+        assert_eq!(err.to_string(), "TLS handshake failed: handshake aborted: eof");
+        let source = err.source().expect("source should be preserved");
+        assert_eq!(
+            source.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+            Some(std::io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_without_source() {
+        use std::error::Error;
+
+        let err = AtlsVerificationError::quote("RTMR3 missing");
+        assert_eq!(err.to_string(), "quote verification failed: RTMR3 missing");
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_code_serialization_matches_as_str() {
+        let codes = [
+            ErrorCode::TcbStatusRejected,
+            ErrorCode::CollateralExpired,
+            ErrorCode::CertificateNotInEventLog,
+            ErrorCode::Io,
+        ];
+        for code in codes {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+        }
+    }
+}
@@ -85,12 +85,14 @@ pub mod connect;
 pub mod dstack;
 pub mod error;
 pub mod logging;
+pub mod metrics;
 pub mod policy;
 pub mod tdx;
+pub mod timing;
 pub mod verifier;
 
 // High-level API
-pub use connect::{atls_connect, TlsStream};
+pub use connect::{atls_connect, atls_connect_with_sni, session_binding, TlsStream};
 pub use policy::Policy;
 
 // Dstack-specific (backward compatible re-exports)
@@ -98,14 +100,16 @@ pub use policy::Policy;
 pub use dstack::{DstackTDXVerifier, DstackTDXVerifierBuilder, DstackTDXVerifierConfig, DstackTdxPolicy};
 
 // Generic TDX
-pub use tdx::{ExpectedBootchain, TCB_STATUS_LIST};
+pub use tdx::{CollateralSource, ExpectedBootchain, TdxReport, TCB_STATUS_LIST};
+pub use timing::{PhaseTimings, PhaseTimingsMs};
 
 // Low-level API
-pub use error::AtlsVerificationError;
+pub use error::{AtlsVerificationError, ErrorCode};
 pub use verifier::{
     AsyncByteStream, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, IntoVerifier, AtlsVerifier,
     Report, Verifier,
 };
 
-// Re-export VerifiedReport from dcap-qvl for bindings
+// Re-export dcap-qvl types used by bindings
 pub use dcap_qvl::verify::VerifiedReport;
+pub use dcap_qvl::QuoteCollateralV3;
@@ -0,0 +1,122 @@
+//! Attestation metrics via the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Enabled with the `metrics` feature. Without it, every function in this module
+//! compiles to a no-op, so call sites don't need their own `cfg` guards.
+//!
+//! Install any `metrics` recorder (e.g. `metrics-exporter-prometheus`) in the
+//! application to collect:
+//!
+//! | Metric | Type | Labels |
+//! |--------|------|--------|
+//! | `atlas_attestation_success_total` | counter | `tee`, `tcb_status` |
+//! | `atlas_attestation_failure_total` | counter | `reason` |
+//! | `atlas_collateral_fetch_errors_total` | counter | `pccs_url` |
+//! | `atlas_verification_duration_seconds` | histogram | `tee` |
+//! | `atlas_collateral_fetch_duration_seconds` | histogram | |
+//!
+//! The duration histograms cover one phase each (see [`PhaseTimings`]): quote
+//! and report verification, and collateral fetching.
+//!
+//! [`PhaseTimings`]: crate::PhaseTimings
+
+use crate::error::AtlsVerificationError;
+use crate::verifier::Report;
+
+/// Record a successful attestation and its phase latencies.
+#[allow(unused_variables)]
+pub(crate) fn record_success(report: &Report) {
+    #[cfg(feature = "metrics")]
+    {
+        let (tee, tcb_status) = match report {
+            Report::Tdx(r) => ("tdx", r.status.clone()),
+        };
+        let timings = report.timings();
+
+        metrics::counter!(
+            "atlas_attestation_success_total",
+            "tee" => tee,
+            "tcb_status" => tcb_status
+        )
+        .increment(1);
+        if let Some(verification) = timings.verification {
+            metrics::histogram!("atlas_verification_duration_seconds", "tee" => tee)
+                .record(verification.as_secs_f64());
+        }
+        if let Some(collateral) = timings.collateral_fetch {
+            metrics::histogram!("atlas_collateral_fetch_duration_seconds")
+                .record(collateral.as_secs_f64());
+        }
+    }
+}
+
+/// Record a failed attestation, labelled by failure reason.
+#[allow(unused_variables)]
+pub(crate) fn record_failure(error: &AtlsVerificationError) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("atlas_attestation_failure_total", "reason" => failure_reason(error))
+        .increment(1);
+}
+
+/// Record a collateral fetch error from the given PCCS.
+#[allow(unused_variables)]
+pub(crate) fn record_collateral_error(pccs_url: &str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("atlas_collateral_fetch_errors_total", "pccs_url" => pccs_url.to_string())
+        .increment(1);
+}
+
+/// Low-cardinality label for a verification error.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+fn failure_reason(error: &AtlsVerificationError) -> &'static str {
+    match error {
+        AtlsVerificationError::Io(_) => "io",
+        AtlsVerificationError::Quote { .. } => "quote",
+        AtlsVerificationError::Collateral { .. } => "collateral",
+        AtlsVerificationError::CollateralExpired { .. } => "collateral_expired",
+        AtlsVerificationError::BootchainMismatch { .. } => "bootchain_mismatch",
+        AtlsVerificationError::RtmrMismatch { .. } => "rtmr_mismatch",
+        AtlsVerificationError::CertificateNotInEventLog => "certificate_not_in_event_log",
+        AtlsVerificationError::EventLogParse { .. } => "event_log_parse",
+        AtlsVerificationError::TeeTypeMismatch(_) => "tee_type_mismatch",
+        AtlsVerificationError::AppComposeHashMismatch { .. } => "app_compose_mismatch",
+        AtlsVerificationError::OsImageHashMismatch { .. } => "os_image_mismatch",
+        AtlsVerificationError::TcbStatusNotAllowed { status, .. } if status == "OutOfDate" => {
+            "tcb_out_of_date"
+        }
+        AtlsVerificationError::TcbStatusNotAllowed { .. } => "tcb_status_not_allowed",
+        AtlsVerificationError::TcbInfoError { .. } => "tcb_info",
+        AtlsVerificationError::GracePeriodExpired { .. } => "grace_period_expired",
+        AtlsVerificationError::ReportDataMismatch { .. } => "report_data_mismatch",
+        AtlsVerificationError::Configuration(_) => "configuration",
+        AtlsVerificationError::TlsHandshake { .. } => "tls_handshake",
+        AtlsVerificationError::InvalidServerName(_) => "invalid_server_name",
+        AtlsVerificationError::MissingCertificate => "missing_certificate",
+        AtlsVerificationError::Other(_) => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_reason_out_of_date() {
+        let err = AtlsVerificationError::TcbStatusNotAllowed {
+            status: "OutOfDate".into(),
+            allowed: vec!["UpToDate".into()],
+        };
+        assert_eq!(failure_reason(&err), "tcb_out_of_date");
+
+        let err = AtlsVerificationError::TcbStatusNotAllowed {
+            status: "ConfigurationNeeded".into(),
+            allowed: vec!["UpToDate".into()],
+        };
+        assert_eq!(failure_reason(&err), "tcb_status_not_allowed");
+    }
+
+    #[test]
+    fn test_failure_reason_handshake() {
+        let err = AtlsVerificationError::tls_handshake("boom");
+        assert_eq!(failure_reason(&err), "tls_handshake");
+    }
+}
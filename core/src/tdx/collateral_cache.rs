@@ -0,0 +1,522 @@
+//! Process-wide cache of Intel collateral.
+//!
+//! Verifiers built from a policy with `cache_collateral` enabled share this
+//! cache, so collateral fetched for one connection is reused by the next even
+//! though every `atls_connect` builds a fresh verifier. Entries are keyed by
+//! (PCCS URL, FMSPC, CA) and expire after the configured TTL. On native
+//! targets the cache can also be persisted to a JSON file, so restarted
+//! services don't refetch collateral they already have. The file is written
+//! off the async executor, readable by its owner only, and never holds
+//! expired entries.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use dcap_qvl::quote::Quote;
+use dcap_qvl::QuoteCollateralV3;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::error::AtlsVerificationError;
+
+/// Default time to live of a cached collateral entry: 8 hours.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(8 * 3600);
+
+/// Cache key: (pccs_url, fmspc, ca).
+pub(crate) type CacheKey = (String, String, &'static str);
+
+/// Configuration of the collateral cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollateralCacheConfig {
+    /// How long an entry is reused before collateral is fetched again.
+    pub ttl: Duration,
+    /// JSON file the cache is loaded from and saved to (native targets only).
+    pub disk_path: Option<PathBuf>,
+}
+
+impl Default for CollateralCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_TTL,
+            disk_path: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    collateral: QuoteCollateralV3,
+    cached_at_secs: u64,
+}
+
+/// On-disk form of one entry (`&'static str` CA can't be deserialized).
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    pccs_url: String,
+    fmspc: String,
+    ca: String,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+#[derive(Default)]
+struct CollateralCache {
+    config: CollateralCacheConfig,
+    entries: HashMap<CacheKey, Entry>,
+    /// Bumped for every snapshot, so an older one never overwrites a newer one.
+    generation: u64,
+    /// Generation of the last snapshot written, locked while writing.
+    written: Arc<Mutex<u64>>,
+}
+
+/// The entries to write to the disk file, taken under the cache lock and
+/// written after it is released.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+struct Snapshot {
+    path: PathBuf,
+    generation: u64,
+    written: Arc<Mutex<u64>>,
+    entries: Vec<DiskEntry>,
+}
+
+impl CollateralCache {
+    fn get(&self, key: &CacheKey, now_secs: u64) -> Option<QuoteCollateralV3> {
+        let entry = self.entries.get(key)?;
+        if entry.expired(self.config.ttl, now_secs) {
+            None
+        } else {
+            Some(entry.collateral.clone())
+        }
+    }
+
+    /// Store an entry and drop expired ones. Returns what to write to disk
+    /// when the cache is persisted.
+    fn insert(
+        &mut self,
+        key: CacheKey,
+        collateral: QuoteCollateralV3,
+        now_secs: u64,
+    ) -> Option<Snapshot> {
+        self.entries.insert(
+            key,
+            Entry {
+                collateral,
+                cached_at_secs: now_secs,
+            },
+        );
+        let ttl = self.config.ttl;
+        self.entries
+            .retain(|_, entry| !entry.expired(ttl, now_secs));
+
+        let path = self.config.disk_path.clone()?;
+        self.generation += 1;
+        let entries = self
+            .entries
+            .iter()
+            .map(|((pccs_url, fmspc, ca), entry)| DiskEntry {
+                pccs_url: pccs_url.clone(),
+                fmspc: fmspc.clone(),
+                ca: ca.to_string(),
+                entry: entry.clone(),
+            })
+            .collect();
+        Some(Snapshot {
+            path,
+            generation: self.generation,
+            written: self.written.clone(),
+            entries,
+        })
+    }
+}
+
+impl Entry {
+    fn expired(&self, ttl: Duration, now_secs: u64) -> bool {
+        now_secs.saturating_sub(self.cached_at_secs) >= ttl.as_secs()
+    }
+}
+
+fn cache() -> &'static RwLock<CollateralCache> {
+    static CACHE: OnceLock<RwLock<CollateralCache>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+/// The CA names dcap-qvl uses in cache keys.
+fn static_ca(ca: &str) -> Option<&'static str> {
+    match ca {
+        "processor" => Some("processor"),
+        "platform" => Some("platform"),
+        _ => None,
+    }
+}
+
+fn pck_ca(ca: &str) -> Result<&'static str, AtlsVerificationError> {
+    static_ca(ca).ok_or_else(|| {
+        AtlsVerificationError::Configuration(format!(
+            "unknown PCK CA {ca:?}, expected \"processor\" or \"platform\""
+        ))
+    })
+}
+
+/// Read the unexpired entries of a disk file.
+#[cfg(not(target_arch = "wasm32"))]
+fn load(
+    path: &Path,
+    ttl: Duration,
+    now_secs: u64,
+) -> Result<HashMap<CacheKey, Entry>, AtlsVerificationError> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let entries: Vec<DiskEntry> = serde_json::from_slice(&data).map_err(|e| {
+        AtlsVerificationError::Configuration(format!(
+            "invalid collateral cache file {}: {e}",
+            path.display()
+        ))
+    })?;
+    Ok(entries
+        .into_iter()
+        .filter(|e| !e.entry.expired(ttl, now_secs))
+        .filter_map(|e| Some(((e.pccs_url, e.fmspc, static_ca(&e.ca)?), e.entry)))
+        .collect())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Snapshot {
+    /// Write the snapshot to its file, unless a newer one was written already.
+    fn save(self) -> std::io::Result<()> {
+        use std::io::Write;
+
+        // Held while writing, so concurrent saves don't interleave
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if *written >= self.generation {
+            return Ok(());
+        }
+
+        let json = serde_json::to_vec(&self.entries)?;
+        // Write a private temporary file in the same directory, then rename it
+        // over the cache, so a crash never leaves a truncated cache behind.
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tmp.as_file()
+                .set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        tmp.write_all(&json)?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(&self.path).map_err(|e| e.error)?;
+        *written = self.generation;
+        Ok(())
+    }
+
+    /// [`save`](Self::save) on a blocking thread when called from a tokio
+    /// runtime, so the executor never waits on the disk.
+    fn save_in_background(self) {
+        let save = move || {
+            let path = self.path.clone();
+            if let Err(e) = self.save() {
+                warn!(
+                    "Failed to save collateral cache to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(save)),
+            Err(_) => save(),
+        }
+    }
+}
+
+/// Replace the cache configuration.
+///
+/// With a `disk_path`, unexpired entries already in that file are loaded (a
+/// missing file is fine) and every new entry is written back, in the
+/// background, with owner-only permissions. Existing in-memory entries are
+/// kept.
+pub fn configure(config: CollateralCacheConfig) -> Result<(), AtlsVerificationError> {
+    let loaded = match &config.disk_path {
+        #[cfg(target_arch = "wasm32")]
+        Some(_) => {
+            return Err(AtlsVerificationError::Configuration(
+                "collateral cache disk_path is not supported on wasm32".into(),
+            ))
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(path) => {
+            let now_secs = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| AtlsVerificationError::Other(e.into()))?
+                .as_secs();
+            load(path, config.ttl, now_secs)?
+        }
+        None => HashMap::new(),
+    };
+
+    let mut cache = cache().write().map_err(|_| {
+        AtlsVerificationError::Other(anyhow::anyhow!("collateral cache lock poisoned"))
+    })?;
+    debug!("Loaded {} collateral cache entries", loaded.len());
+    cache.entries.extend(loaded);
+    cache.config = config;
+    Ok(())
+}
+
+/// The current cache configuration.
+pub fn config() -> CollateralCacheConfig {
+    match cache().read() {
+        Ok(cache) => cache.config.clone(),
+        Err(_) => CollateralCacheConfig::default(),
+    }
+}
+
+/// Drop every in-memory entry (the disk file, if any, is left alone).
+pub fn clear() {
+    if let Ok(mut cache) = cache().write() {
+        cache.entries.clear();
+    }
+}
+
+/// Number of in-memory entries, including expired ones.
+pub fn len() -> usize {
+    cache().read().map(|cache| cache.entries.len()).unwrap_or(0)
+}
+
+/// Look up unexpired collateral.
+pub(crate) fn get(key: &CacheKey, now_secs: u64) -> Option<QuoteCollateralV3> {
+    match cache().read() {
+        Ok(cache) => cache.get(key, now_secs),
+        Err(_) => {
+            warn!("Collateral cache lock poisoned, treating as cache miss");
+            None
+        }
+    }
+}
+
+/// Store freshly fetched collateral, and write the cache to disk if it is
+/// persisted.
+pub(crate) fn insert(key: CacheKey, collateral: QuoteCollateralV3, now_secs: u64) {
+    let snapshot = match cache().write() {
+        Ok(mut cache) => cache.insert(key, collateral, now_secs),
+        Err(_) => {
+            warn!("Collateral cache lock poisoned, skipping cache write");
+            return;
+        }
+    };
+    // The lock is released: other connections aren't held up by the write
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(snapshot) = snapshot {
+        snapshot.save_in_background();
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = snapshot;
+}
+
+/// Fetch collateral for a TDX platform ahead of time and cache it.
+///
+/// `fmspc` is the platform's FMSPC in hex (see [`TdxReport::fmspc`](crate::TdxReport::fmspc))
+/// and `ca` the CA that issued its PCK certificate, `"processor"` or
+/// `"platform"`. Later verifications of quotes from that platform with
+/// `cache_collateral` then skip the PCCS round trip until the entry expires.
+pub async fn prefetch(pccs_url: &str, fmspc: &str, ca: &str) -> Result<(), AtlsVerificationError> {
+    let ca = pck_ca(ca)?;
+    let fmspc = fmspc.to_ascii_uppercase();
+    let collateral = fetch(pccs_url, &fmspc, ca).await?;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let now_secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| AtlsVerificationError::Other(e.into()))?
+        .as_secs();
+    #[cfg(target_arch = "wasm32")]
+    let now_secs = (js_sys::Date::now() / 1000.0) as u64;
+
+    insert((pccs_url.to_string(), fmspc, ca), collateral, now_secs);
+    Ok(())
+}
+
+/// Fetch collateral for a TDX platform, without the cache.
+///
+/// `fmspc` and `ca` are as for [`prefetch`]. The result can be kept to verify
+/// quotes from that platform offline (see
+/// [`DstackTDXVerifier::verify_quote_offline`](crate::DstackTDXVerifier::verify_quote_offline)).
+pub async fn fetch(
+    pccs_url: &str,
+    fmspc: &str,
+    ca: &str,
+) -> Result<QuoteCollateralV3, AtlsVerificationError> {
+    let ca = pck_ca(ca)?;
+    dcap_qvl::collateral::get_collateral_for_fmspc(pccs_url, fmspc.to_ascii_uppercase(), ca, false)
+        .await
+        .map_err(|e| AtlsVerificationError::collateral_with("Failed to fetch collateral", e))
+}
+
+/// The FMSPC (upper-case hex) and PCK CA of the platform that produced
+/// `quote`, as [`fetch`] takes them.
+pub fn platform(quote: &[u8]) -> Result<(String, &'static str), AtlsVerificationError> {
+    let quote = Quote::parse(quote)
+        .map_err(|e| AtlsVerificationError::quote_with("Failed to parse quote", e))?;
+    let fmspc = quote
+        .fmspc()
+        .map_err(|e| AtlsVerificationError::quote_with("Failed to get FMSPC", e))?;
+    let ca = quote
+        .ca()
+        .map_err(|e| AtlsVerificationError::quote_with("Failed to get CA", e))?;
+    Ok((hex::encode_upper(fmspc), ca))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collateral(tcb_info: &str) -> QuoteCollateralV3 {
+        QuoteCollateralV3 {
+            pck_crl_issuer_chain: String::new(),
+            root_ca_crl: vec![1],
+            pck_crl: vec![2],
+            tcb_info_issuer_chain: String::new(),
+            tcb_info: tcb_info.into(),
+            tcb_info_signature: vec![3],
+            qe_identity_issuer_chain: String::new(),
+            qe_identity: String::new(),
+            qe_identity_signature: vec![4],
+            pck_certificate_chain: None,
+        }
+    }
+
+    fn key() -> CacheKey {
+        (
+            "https://pccs.example".into(),
+            "00906ED50000".into(),
+            "processor",
+        )
+    }
+
+    fn other_key() -> CacheKey {
+        (
+            "https://pccs.example".into(),
+            "00906ED50000".into(),
+            "platform",
+        )
+    }
+
+    fn persisted_cache(path: &Path) -> CollateralCache {
+        CollateralCache {
+            config: CollateralCacheConfig {
+                ttl: Duration::from_secs(60),
+                disk_path: Some(path.to_path_buf()),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entries_expire_after_ttl() {
+        let mut cache = CollateralCache {
+            config: CollateralCacheConfig {
+                ttl: Duration::from_secs(60),
+                disk_path: None,
+            },
+            ..Default::default()
+        };
+        assert!(cache.insert(key(), collateral("tcb"), 1_000).is_none());
+
+        assert_eq!(cache.get(&key(), 1_059), Some(collateral("tcb")));
+        assert_eq!(cache.get(&key(), 1_060), None);
+    }
+
+    #[test]
+    fn test_disk_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collateral.json");
+        let mut cache = persisted_cache(&path);
+        cache
+            .insert(key(), collateral("tcb"), 1_000)
+            .unwrap()
+            .save()
+            .unwrap();
+
+        let loaded = load(&path, Duration::from_secs(60), 1_000).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[&key()].collateral, collateral("tcb"));
+        assert_eq!(loaded[&key()].cached_at_secs, 1_000);
+        // Only the file remains: the temporary one was renamed over it
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collateral.json");
+        let mut cache = persisted_cache(&path);
+        let snapshot = cache.insert(key(), collateral("tcb"), 1_000).unwrap();
+        snapshot.save().unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_expired_entries_are_not_saved_or_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collateral.json");
+        let mut cache = persisted_cache(&path);
+        cache.insert(key(), collateral("old"), 1_000);
+        // 60s later the first entry has expired: it is dropped from memory
+        // and from the snapshot
+        let snapshot = cache.insert(other_key(), collateral("new"), 1_060).unwrap();
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(snapshot.entries.len(), 1);
+        snapshot.save().unwrap();
+
+        assert_eq!(
+            load(&path, Duration::from_secs(60), 1_119).unwrap().len(),
+            1
+        );
+        assert!(load(&path, Duration::from_secs(60), 1_120)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_older_snapshot_does_not_overwrite_newer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("collateral.json");
+        let mut cache = persisted_cache(&path);
+        let older = cache.insert(key(), collateral("tcb"), 1_000).unwrap();
+        let newer = cache.insert(other_key(), collateral("tcb"), 1_000).unwrap();
+
+        newer.save().unwrap();
+        older.save().unwrap();
+
+        assert_eq!(
+            load(&path, Duration::from_secs(60), 1_000).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_missing_disk_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.json");
+        assert!(load(&path, DEFAULT_TTL, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_ca_is_rejected() {
+        assert_eq!(static_ca("platform"), Some("platform"));
+        assert_eq!(static_ca("root"), None);
+    }
+}
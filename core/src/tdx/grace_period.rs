@@ -27,7 +27,7 @@ pub fn enforce_grace_period(
 
     let tcb_date = extract_tcb_date(quote, collateral, &report.status)?;
     let tcb_date_secs = DateTime::parse_from_rfc3339(&tcb_date)
-        .map_err(|e| AtlsVerificationError::TcbInfoError(format!("invalid TCB date: {}", e)))?
+        .map_err(|e| AtlsVerificationError::tcb_info_with("invalid TCB date", e))?
         .timestamp();
 
     evaluate_grace_period(&report.status, tcb_date_secs, &tcb_date, now_secs, grace)
@@ -41,7 +41,7 @@ fn evaluate_grace_period(
     grace: u64,
 ) -> Result<(), AtlsVerificationError> {
     let now_secs = i64::try_from(now_secs).map_err(|_| {
-        AtlsVerificationError::TcbInfoError("current time out of range".into())
+        AtlsVerificationError::tcb_info("current time out of range")
     })?;
 
     let grace_secs = i64::try_from(grace).map_err(|_| {
@@ -103,12 +103,12 @@ fn extract_tcb_date(
     expected_status: &str,
 ) -> Result<String, AtlsVerificationError> {
     let tcb_info: TcbInfo = serde_json::from_str(&collateral.tcb_info).map_err(|e| {
-        AtlsVerificationError::TcbInfoError(format!("failed to parse TCB info: {}", e))
+        AtlsVerificationError::tcb_info_with("failed to parse TCB info", e)
     })?;
 
     let pck_leaf = extract_pck_leaf_cert(quote, collateral)?;
     let pck_extension = parse_pck_extension(&pck_leaf).map_err(|e| {
-        AtlsVerificationError::TcbInfoError(format!("failed to parse PCK extension: {}", e))
+        AtlsVerificationError::tcb_info_with("failed to parse PCK extension", e)
     })?;
 
     let tcb_level = match_tcb_level(
@@ -120,7 +120,7 @@ fn extract_tcb_date(
     )?;
 
     if tcb_level.tcb_status != expected_status {
-        return Err(AtlsVerificationError::TcbInfoError(format!(
+        return Err(AtlsVerificationError::tcb_info(format!(
             "matched TCB level has status '{}', expected '{}'",
             tcb_level.tcb_status, expected_status
         )));
@@ -136,35 +136,25 @@ fn extract_pck_leaf_cert(
     if let Some(pem_chain) = &collateral.pck_certificate_chain {
         let certs = parse_pem_chain(pem_chain)?;
         return certs.first().cloned().ok_or_else(|| {
-            AtlsVerificationError::TcbInfoError(
-                "PCK certificate chain is empty".to_string(),
-            )
+            AtlsVerificationError::tcb_info("PCK certificate chain is empty")
         });
     }
 
     let certs = dcap_qvl::intel::extract_cert_chain(quote).map_err(|e| {
-        AtlsVerificationError::TcbInfoError(format!(
-            "failed to extract PCK certificate chain from quote: {}",
-            e
-        ))
+        AtlsVerificationError::tcb_info_with("failed to extract PCK certificate chain from quote", e)
     })?;
 
     certs.first().cloned().ok_or_else(|| {
-        AtlsVerificationError::TcbInfoError("PCK certificate chain is empty".to_string())
+        AtlsVerificationError::tcb_info("PCK certificate chain is empty")
     })
 }
 
 fn parse_pem_chain(pem_chain: &str) -> Result<Vec<Vec<u8>>, AtlsVerificationError> {
     let certs = parse_many(pem_chain).map_err(|e| {
-        AtlsVerificationError::TcbInfoError(format!(
-            "failed to parse PCK certificate chain: {}",
-            e
-        ))
+        AtlsVerificationError::tcb_info_with("failed to parse PCK certificate chain", e)
     })?;
     if certs.is_empty() {
-        return Err(AtlsVerificationError::TcbInfoError(
-            "failed to parse PCK certificate chain".to_string(),
-        ));
+        return Err(AtlsVerificationError::tcb_info("failed to parse PCK certificate chain"));
     }
     Ok(certs.into_iter().map(|pem| pem.contents().to_vec()).collect())
 }
@@ -177,30 +167,24 @@ fn match_tcb_level<'a>(
     fmspc: &[u8],
 ) -> Result<&'a TcbLevel, AtlsVerificationError> {
     let tcb_fmspc = hex::decode(&tcb_info.fmspc).map_err(|e| {
-        AtlsVerificationError::TcbInfoError(format!("failed to decode TCB FMSPC: {}", e))
+        AtlsVerificationError::tcb_info_with("failed to decode TCB FMSPC", e)
     })?;
     if fmspc != tcb_fmspc.as_slice() {
-        return Err(AtlsVerificationError::TcbInfoError(
-            "FMSPC mismatch in TCB info".into(),
-        ));
+        return Err(AtlsVerificationError::tcb_info("FMSPC mismatch in TCB info"));
     }
 
     let is_tdx = quote.report.as_td10().is_some();
     if is_tdx {
         if tcb_info.version < 3 || tcb_info.id != "TDX" {
-            return Err(AtlsVerificationError::TcbInfoError(
-                "TDX quote with non-TDX TCB info".into(),
-            ));
+            return Err(AtlsVerificationError::tcb_info("TDX quote with non-TDX TCB info"));
         }
     } else if tcb_info.version < 2 || tcb_info.id != "SGX" {
-        return Err(AtlsVerificationError::TcbInfoError(
-            "SGX quote with non-SGX TCB info".into(),
-        ));
+        return Err(AtlsVerificationError::tcb_info("SGX quote with non-SGX TCB info"));
     }
 
     let td_report = if is_tdx {
         Some(quote.report.as_td10().ok_or_else(|| {
-            AtlsVerificationError::TcbInfoError("failed to read TD report".into())
+            AtlsVerificationError::tcb_info("failed to read TD report")
         })?)
     } else {
         None
@@ -214,9 +198,7 @@ fn match_tcb_level<'a>(
         let sgx_components: Vec<u8> =
             tcb_level.tcb.sgx_components.iter().map(|c| c.svn).collect();
         if sgx_components.is_empty() {
-            return Err(AtlsVerificationError::TcbInfoError(
-                "no SGX components in TCB info".into(),
-            ));
+            return Err(AtlsVerificationError::tcb_info("no SGX components in TCB info"));
         }
 
         if cpu_svn.iter().zip(&sgx_components).any(|(a, b)| a < b) {
@@ -227,9 +209,7 @@ fn match_tcb_level<'a>(
             let tdx_components: Vec<u8> =
                 tcb_level.tcb.tdx_components.iter().map(|c| c.svn).collect();
             if tdx_components.is_empty() {
-                return Err(AtlsVerificationError::TcbInfoError(
-                    "no TDX components in TCB info".into(),
-                ));
+                return Err(AtlsVerificationError::tcb_info("no TDX components in TCB info"));
             }
             if td_report
                 .tee_tcb_svn
@@ -244,9 +224,7 @@ fn match_tcb_level<'a>(
         return Ok(tcb_level);
     }
 
-    Err(AtlsVerificationError::TcbInfoError(
-        "no matching TCB level found".into(),
-    ))
+    Err(AtlsVerificationError::tcb_info("no matching TCB level found"))
 }
 
 #[cfg(test)]
@@ -3,7 +3,10 @@
 //! This module provides base types and functions for TDX attestation verification
 //! that are not specific to any particular TDX deployment platform.
 
+pub mod collateral_cache;
 pub mod config;
 pub mod grace_period;
+pub mod report;
 
 pub use config::{ExpectedBootchain, TCB_STATUS_LIST};
+pub use report::{CollateralSource, TdxReport};
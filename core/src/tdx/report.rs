@@ -0,0 +1,177 @@
+//! TDX attestation report type.
+
+use std::fmt;
+use std::ops::Deref;
+
+use dcap_qvl::verify::VerifiedReport;
+use sha2::{Digest, Sha256};
+
+use crate::timing::PhaseTimings;
+
+/// TDX attestation report produced by TDX verifiers.
+///
+/// Wraps the DCAP [`VerifiedReport`] together with metadata about the connection
+/// that produced it. Derefs to [`VerifiedReport`], so fields such as `status`,
+/// `advisory_ids` and `report` are accessible directly.
+#[derive(Clone)]
+pub struct TdxReport {
+    /// The cryptographically verified DCAP report.
+    pub verified: VerifiedReport,
+
+    /// Durations of the connection phases that led to this report.
+    pub timings: PhaseTimings,
+
+    /// FMSPC of the platform that produced the quote (upper-case hex).
+    pub fmspc: Option<String>,
+
+    /// Where the collateral used to verify the quote came from.
+    pub collateral_source: Option<CollateralSource>,
+
+    /// The raw quote as received, for archiving or external verifiers
+    /// (empty if the verifier did not record it).
+    pub quote: Vec<u8>,
+
+    /// The event log as received (JSON), if the verifier fetched one.
+    pub event_log: Option<String>,
+}
+
+/// Origin of the collateral (TCB info, QE identity, CRLs) used for verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollateralSource {
+    /// Reused from the verifier's collateral cache.
+    Cache,
+    /// Fetched from the PCCS at this URL during verification.
+    Pccs(String),
+}
+
+impl TdxReport {
+    /// Wrap a verified report with empty timings.
+    pub fn new(verified: VerifiedReport) -> Self {
+        Self {
+            verified,
+            timings: PhaseTimings::default(),
+            fmspc: None,
+            collateral_source: None,
+            quote: Vec::new(),
+            event_log: None,
+        }
+    }
+
+    /// SHA-256 (hex) of MRTD, RTMR0-3 and, when the event log has one, the
+    /// dstack compose hash; `None` for non-TD reports.
+    ///
+    /// MRTD alone is shared by every app on one OS image; the digest changes
+    /// when the firmware, OS image, app or its configuration does. RTMR3 also
+    /// measures per-instance events (dstack's instance ID), so two instances
+    /// of one app have different digests.
+    pub fn measurement_digest(&self) -> Option<String> {
+        let td = self.report.as_td10()?;
+        let registers = [&td.mr_td, &td.rt_mr0, &td.rt_mr1, &td.rt_mr2, &td.rt_mr3];
+        let compose_hash = self.event_log.as_deref().and_then(compose_hash);
+        Some(measurement_digest(&registers, compose_hash.as_deref()))
+    }
+
+    /// SHA-256 (hex) of MRTD, RTMR0-2 and, when the event log has one, the
+    /// dstack compose hash; `None` for non-TD reports.
+    ///
+    /// Unlike [`TdxReport::measurement_digest`] it leaves out RTMR3, so every
+    /// instance of one app deployment shares it: it identifies what runs, not
+    /// where.
+    pub fn app_digest(&self) -> Option<String> {
+        let td = self.report.as_td10()?;
+        let registers = [&td.mr_td, &td.rt_mr0, &td.rt_mr1, &td.rt_mr2];
+        let compose_hash = self.event_log.as_deref().and_then(compose_hash);
+        Some(measurement_digest(&registers, compose_hash.as_deref()))
+    }
+
+    /// Consume self and return the underlying DCAP report.
+    pub fn into_verified(self) -> VerifiedReport {
+        self.verified
+    }
+}
+
+fn measurement_digest(registers: &[&[u8; 48]], compose_hash: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    for register in registers {
+        hasher.update(register);
+    }
+    if let Some(compose_hash) = compose_hash {
+        hasher.update(compose_hash.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// The payload (hex) of the `compose-hash` event in a dstack event log.
+fn compose_hash(event_log: &str) -> Option<String> {
+    let events: Vec<serde_json::Value> = serde_json::from_str(event_log).ok()?;
+    events
+        .iter()
+        .find(|event| event["event"] == crate::dstack::compose_hash::COMPOSE_HASH_EVENT)
+        .and_then(|event| event["event_payload"].as_str())
+        .map(str::to_ascii_lowercase)
+}
+
+// The raw evidence is summarized by size, so logging a report never dumps
+// the full quote or event log.
+impl fmt::Debug for TdxReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TdxReport")
+            .field("verified", &self.verified)
+            .field("timings", &self.timings)
+            .field("fmspc", &self.fmspc)
+            .field("collateral_source", &self.collateral_source)
+            .field("quote", &format_args!("<{} bytes>", self.quote.len()))
+            .field(
+                "event_log",
+                &self
+                    .event_log
+                    .as_ref()
+                    .map(|log| format!("<{} bytes>", log.len())),
+            )
+            .finish()
+    }
+}
+
+impl Deref for TdxReport {
+    type Target = VerifiedReport;
+
+    fn deref(&self) -> &VerifiedReport {
+        &self.verified
+    }
+}
+
+impl From<VerifiedReport> for TdxReport {
+    fn from(verified: VerifiedReport) -> Self {
+        Self::new(verified)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_hash_from_event_log() {
+        let event_log = r#"[
+            {"imr": 3, "event_type": 134217729, "event": "app-id", "event_payload": "ab"},
+            {"imr": 3, "event_type": 134217729, "event": "compose-hash", "event_payload": "C0FFEE"}
+        ]"#;
+        assert_eq!(compose_hash(event_log).as_deref(), Some("c0ffee"));
+        assert_eq!(compose_hash("[]"), None);
+        assert_eq!(compose_hash("not json"), None);
+    }
+
+    #[test]
+    fn test_measurement_digest_covers_every_register() {
+        let zero = [0u8; 48];
+        let one = [1u8; 48];
+        let base = measurement_digest(&[&zero; 5], Some("aa"));
+        for index in 0..5 {
+            let mut registers = [&zero; 5];
+            registers[index] = &one;
+            assert_ne!(measurement_digest(&registers, Some("aa")), base);
+        }
+        assert_ne!(measurement_digest(&[&zero; 5], Some("bb")), base);
+        assert_ne!(measurement_digest(&[&zero; 5], None), base);
+    }
+}
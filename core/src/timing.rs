@@ -0,0 +1,148 @@
+//! Phase timing measurements for aTLS connections.
+//!
+//! Durations are measured with a platform-appropriate clock:
+//! - On native targets: `std::time::Instant`
+//! - On WASM targets: `js_sys::Date::now()` (`Instant` panics on `wasm32-unknown-unknown`)
+
+use serde::Serialize;
+use std::time::Duration;
+
+/// Durations of the individual phases of an aTLS connection.
+///
+/// Phases that were not executed (or not observed by this crate) are `None`.
+/// `tcp_connect` is never set by [`atls_connect`](crate::atls_connect) since it
+/// receives an already-connected stream; bindings that dial the connection fill it in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// Transport connection establishment (TCP connect or WebSocket tunnel setup).
+    pub tcp_connect: Option<Duration>,
+
+    /// TLS handshake, including peer certificate and EKM extraction.
+    pub tls_handshake: Option<Duration>,
+
+    /// Evidence exchange: requesting and receiving the quote and event log.
+    pub evidence_exchange: Option<Duration>,
+
+    /// Collateral retrieval from PCCS (near zero on a cache hit).
+    pub collateral_fetch: Option<Duration>,
+
+    /// Quote verification and policy checks, excluding collateral retrieval.
+    pub verification: Option<Duration>,
+}
+
+impl PhaseTimings {
+    /// Sum of all recorded phases.
+    pub fn total(&self) -> Duration {
+        [
+            self.tcp_connect,
+            self.tls_handshake,
+            self.evidence_exchange,
+            self.collateral_fetch,
+            self.verification,
+        ]
+        .iter()
+        .flatten()
+        .sum()
+    }
+
+    /// The timings in fractional milliseconds, as the language bindings
+    /// report them.
+    pub fn as_millis(&self) -> PhaseTimingsMs {
+        let ms = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+        PhaseTimingsMs {
+            tcp_connect_ms: ms(self.tcp_connect),
+            tls_handshake_ms: ms(self.tls_handshake),
+            evidence_exchange_ms: ms(self.evidence_exchange),
+            collateral_fetch_ms: ms(self.collateral_fetch),
+            verification_ms: ms(self.verification),
+            total_ms: self.total().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+/// [`PhaseTimings`] in milliseconds, with the field names the bindings use.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PhaseTimingsMs {
+    pub tcp_connect_ms: Option<f64>,
+    pub tls_handshake_ms: Option<f64>,
+    pub evidence_exchange_ms: Option<f64>,
+    pub collateral_fetch_ms: Option<f64>,
+    pub verification_ms: Option<f64>,
+    pub total_ms: f64,
+}
+
+/// Stopwatch that works on both native and WASM targets.
+#[derive(Debug, Clone, Copy)]
+pub struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+    #[cfg(target_arch = "wasm32")]
+    start_ms: f64,
+}
+
+impl Stopwatch {
+    /// Start a new stopwatch.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Start a new stopwatch.
+    #[cfg(target_arch = "wasm32")]
+    pub fn start() -> Self {
+        Self {
+            start_ms: js_sys::Date::now(),
+        }
+    }
+
+    /// Time elapsed since the stopwatch was started.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Time elapsed since the stopwatch was started.
+    #[cfg(target_arch = "wasm32")]
+    pub fn elapsed(&self) -> Duration {
+        // Date::now() is not monotonic; clamp to zero if the clock went backwards.
+        let elapsed_ms = (js_sys::Date::now() - self.start_ms).max(0.0);
+        Duration::from_secs_f64(elapsed_ms / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_sums_recorded_phases() {
+        let timings = PhaseTimings {
+            tls_handshake: Some(Duration::from_millis(20)),
+            collateral_fetch: Some(Duration::from_millis(300)),
+            verification: Some(Duration::from_millis(5)),
+            ..Default::default()
+        };
+        assert_eq!(timings.total(), Duration::from_millis(325));
+    }
+
+    #[test]
+    fn test_total_empty() {
+        assert_eq!(PhaseTimings::default().total(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_as_millis() {
+        let timings = PhaseTimings {
+            tls_handshake: Some(Duration::from_millis(250)),
+            verification: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        let ms = timings.as_millis();
+        assert_eq!(ms.tcp_connect_ms, None);
+        assert_eq!(ms.tls_handshake_ms, Some(250.0));
+        assert_eq!(ms.verification_ms, Some(2000.0));
+        assert_eq!(ms.total_ms, 2250.0);
+    }
+}
@@ -3,6 +3,8 @@
 use std::future::Future;
 
 use crate::error::AtlsVerificationError;
+use crate::tdx::TdxReport;
+use crate::timing::PhaseTimings;
 use dcap_qvl::verify::VerifiedReport;
 
 // Platform-specific async I/O traits
@@ -46,7 +48,7 @@ impl<T: AsyncRead + AsyncWrite + Unpin> AsyncByteStream for T {}
 #[derive(Debug, Clone)]
 pub enum Report {
     /// TDX attestation report.
-    Tdx(VerifiedReport),
+    Tdx(TdxReport),
 }
 
 impl Report {
@@ -55,7 +57,7 @@ impl Report {
     /// Returns `Some(&VerifiedReport)` if this is a TDX report, `None` otherwise.
     pub fn as_tdx(&self) -> Option<&VerifiedReport> {
         match self {
-            Report::Tdx(r) => Some(r),
+            Report::Tdx(r) => Some(&r.verified),
         }
     }
 
@@ -64,7 +66,24 @@ impl Report {
     /// Returns `Some(VerifiedReport)` if this is a TDX report, `None` otherwise.
     pub fn into_tdx(self) -> Option<VerifiedReport> {
         match self {
-            Report::Tdx(r) => Some(r),
+            Report::Tdx(r) => Some(r.into_verified()),
+        }
+    }
+
+    /// Durations of the connection phases that produced this report.
+    pub fn timings(&self) -> &PhaseTimings {
+        match self {
+            Report::Tdx(r) => &r.timings,
+        }
+    }
+
+    /// Mutable access to the phase timings.
+    ///
+    /// Used by callers that perform phases outside of this crate (e.g. the
+    /// TCP connect done by the bindings) to record them on the report.
+    pub fn timings_mut(&mut self) -> &mut PhaseTimings {
+        match self {
+            Report::Tdx(r) => &mut r.timings,
         }
     }
 }
@@ -81,6 +100,14 @@ impl Report {
 ///
 /// On native platforms, the trait requires `Send + Sync` and futures must be `Send`.
 /// On wasm32, these bounds are relaxed since wasm is single-threaded.
+///
+/// # Cancellation safety
+///
+/// `verify` borrows the stream and exchanges evidence over it. If the future is
+/// cancelled or returns an error, the stream may be left mid-request or
+/// mid-response and must be discarded. Verifiers never leave shared state (e.g.
+/// caches) half-updated on cancellation. Prefer [`atls_connect`](crate::atls_connect),
+/// which owns the stream and drops it on cancellation.
 #[cfg(not(target_arch = "wasm32"))]
 pub trait AtlsVerifier: Send + Sync {
     /// Verify the remote TEE via the given TLS connection.
@@ -232,6 +232,14 @@ mod integration {
             }
         }
 
+        let timings = report.timings();
+        assert!(timings.evidence_exchange.is_some());
+        assert!(timings.collateral_fetch.is_some());
+        assert!(timings.verification.is_some());
+        // verify() is called directly, so connection phases are not recorded
+        assert!(timings.tcp_connect.is_none());
+        assert!(timings.tls_handshake.is_none());
+
         println!("Verification with disabled runtime verification passed!");
     }
 
@@ -258,6 +266,8 @@ mod integration {
             "Expected verification to succeed with grace_period set: {:?}",
             result.err()
         );
+        let (_stream, report) = result.unwrap();
+        assert!(report.timings().tls_handshake.is_some());
     }
 
     /// Test grace period behavior by forcing an OutOfDate status on a real quote.
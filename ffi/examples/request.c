@@ -0,0 +1,84 @@
+/*
+ * Send one HTTP request to a TEE over attested TLS and print the attestation
+ * and the response.
+ *
+ *   cargo build --release -p atlas-ffi
+ *   cc examples/request.c -Iinclude -L../target/release -latlas -o request
+ *   LD_LIBRARY_PATH=../target/release ./request tee.example.com policy.json
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "atlas.h"
+
+static char *read_file(const char *path) {
+    FILE *file = fopen(path, "rb");
+    if (!file) {
+        return NULL;
+    }
+    fseek(file, 0, SEEK_END);
+    long size = ftell(file);
+    rewind(file);
+    char *text = malloc(size + 1);
+    if (text && fread(text, 1, size, file) == (size_t)size) {
+        text[size] = '\0';
+    } else {
+        free(text);
+        text = NULL;
+    }
+    fclose(file);
+    return text;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 3) {
+        fprintf(stderr, "usage: %s HOST POLICY_JSON_FILE\n", argv[0]);
+        return 2;
+    }
+    const char *host = argv[1];
+    char *policy = read_file(argv[2]);
+    if (!policy) {
+        fprintf(stderr, "failed to read %s\n", argv[2]);
+        return 1;
+    }
+
+    char target[512];
+    snprintf(target, sizeof target, "%s:443", host);
+    char *error = NULL;
+    AtlasConnection *connection = atlas_connect(target, host, policy, "http/1.1", &error);
+    free(policy);
+    if (!connection) {
+        /* {"code": "TCB_STATUS_REJECTED", "message": "..."} */
+        fprintf(stderr, "attestation failed: %s\n", error);
+        atlas_free(error);
+        return 1;
+    }
+
+    char *attestation = atlas_get_attestation_json(connection);
+    fprintf(stderr, "attestation: %s\n", attestation);
+    atlas_free(attestation);
+
+    char request[1024];
+    int length = snprintf(request, sizeof request,
+                          "GET / HTTP/1.1\r\nHost: %s\r\nConnection: close\r\n\r\n", host);
+    if (atlas_write(connection, (const uint8_t *)request, length, &error) < 0) {
+        fprintf(stderr, "write failed: %s\n", error);
+        atlas_free(error);
+        atlas_close(connection);
+        return 1;
+    }
+
+    uint8_t buf[16384];
+    ptrdiff_t read;
+    while ((read = atlas_read(connection, buf, sizeof buf, &error)) > 0) {
+        fwrite(buf, 1, read, stdout);
+    }
+    if (read < 0) {
+        fprintf(stderr, "read failed: %s\n", error);
+        atlas_free(error);
+    }
+    atlas_close(connection);
+    return read < 0;
+}
@@ -0,0 +1,95 @@
+/* atlas: attested TLS (aTLS) client, C API. See ffi/README.md. */
+
+#ifndef ATLAS_H
+#define ATLAS_H
+
+/* Generated by cbindgen from ffi/src/lib.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// An attested TLS connection.
+typedef struct AtlasConnection AtlasConnection;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Connect to `target` (`host:port`) and verify the TEE behind it against
+// `policy_json` (a policy as JSON, e.g. `{"type": "dstack_tdx", ...}`).
+//
+// `server_name` is the name the certificate is checked against and sent as
+// SNI. `alpn` is a comma-separated list of ALPN protocols (e.g. `"h2,http/1.1"`)
+// or `NULL` for none.
+//
+// Returns the connection, or `NULL` with `*error` set if the connection or
+// the attestation failed.
+//
+// # Safety
+//
+// The strings must be null or NUL-terminated, and `error` null or valid for
+// writes.
+struct AtlasConnection *atlas_connect(const char *target,
+                                      const char *server_name,
+                                      const char *policy_json,
+                                      const char *alpn,
+                                      char **error);
+
+// Read up to `len` bytes into `buf`.
+//
+// Returns the number of bytes read, `0` once the server closed the
+// connection, or `-1` with `*error` set.
+//
+// # Safety
+//
+// `connection` must come from `atlas_connect` and not be closed, `buf` valid
+// for `len` bytes of writes, and `error` null or valid for writes.
+ptrdiff_t atlas_read(struct AtlasConnection *connection, uint8_t *buf, size_t len, char **error);
+
+// Write all `len` bytes of `buf`, and flush them.
+//
+// Returns `len`, or `-1` with `*error` set.
+//
+// # Safety
+//
+// `connection` must come from `atlas_connect` and not be closed, `buf` valid
+// for `len` bytes of reads, and `error` null or valid for writes.
+ptrdiff_t atlas_write(struct AtlasConnection *connection,
+                      const uint8_t *buf,
+                      size_t len,
+                      char **error);
+
+// Close `connection` (sending TLS close_notify) and free it. `NULL` is a
+// no-op.
+//
+// # Safety
+//
+// `connection` must be null or come from `atlas_connect`, and must not be
+// used again.
+void atlas_close(struct AtlasConnection *connection);
+
+// The attestation of `connection`, as JSON: `trusted`, `tee_type`,
+// `measurement` (hex MRTD), `tcb_status`, `advisory_ids`, `fmspc` and
+// `timings` (`*_ms`).
+//
+// Returns a string to free with `atlas_free`, or `NULL` if `connection` is
+// null.
+//
+// # Safety
+//
+// `connection` must be null or come from `atlas_connect` and not be closed.
+char *atlas_get_attestation_json(const struct AtlasConnection *connection);
+
+// Free a string the library returned. `NULL` is a no-op.
+//
+// # Safety
+//
+// `string` must be null or come from this library, and must not be used
+// again.
+void atlas_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ATLAS_H */
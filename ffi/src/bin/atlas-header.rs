@@ -0,0 +1,20 @@
+//! Generates the C header from the exported functions:
+//! `cargo run -p atlas-ffi --features header --bin atlas-header -- include/atlas.h`
+//! (run from `ffi/`, or use `make ffi-header`). Without a path, prints it.
+
+fn main() {
+    let crate_dir = env!("CARGO_MANIFEST_DIR");
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+    let header = cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the C header");
+    match std::env::args_os().nth(1) {
+        Some(path) => {
+            header.write_to_file(path);
+        }
+        None => header.write(std::io::stdout()),
+    }
+}
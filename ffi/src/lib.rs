@@ -0,0 +1,425 @@
+//! C ABI for attested TLS, so applications in Go, C++, Swift or any language
+//! with a C FFI can connect to TEEs without Rust.
+//!
+//! The API is blocking: each call runs on a tokio runtime owned by the library
+//! and returns when it's done. A connection is an opaque `AtlasConnection *`
+//! from `atlas_connect`, verified against the policy before it's returned, and
+//! freed by `atlas_close`.
+//!
+//! Failing calls return `NULL` or `-1` and, when the caller passed a non-null
+//! `error`, store a JSON object `{"code": "...", "message": "..."}` in it; the
+//! codes are the stable [`ErrorCode`] strings (e.g. `TCB_STATUS_REJECTED`).
+//! Strings the library returns are freed with `atlas_free`.
+//!
+//! `atlas_read` and `atlas_write` on the same connection may run concurrently
+//! from two threads; `atlas_close` must not race with either.
+//!
+//! `include/atlas.h` is generated from this file by cbindgen (`make ffi-header`).
+
+use atlas_rs::{atls_connect, AtlsVerificationError, ErrorCode, Policy, Report, TlsStream};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+
+/// Runs the I/O of every connection; built on first use and never freed.
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the tokio runtime")
+});
+
+/// An attested TLS connection.
+pub struct AtlasConnection {
+    reader: Mutex<ReadHalf<TlsStream<TcpStream>>>,
+    writer: Mutex<WriteHalf<TlsStream<TcpStream>>>,
+    /// The attestation, as `atlas_get_attestation_json` returns it.
+    attestation: String,
+}
+
+/// A failure, as reported to the caller.
+struct Error {
+    code: ErrorCode,
+    message: String,
+}
+
+impl Error {
+    fn invalid(message: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::InvalidConfiguration,
+            message: message.into(),
+        }
+    }
+
+    fn io(message: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::Io,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AtlsVerificationError> for Error {
+    fn from(e: AtlsVerificationError) -> Self {
+        Self {
+            code: e.code(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// Connect to `target` (`host:port`) and verify the TEE behind it against
+/// `policy_json` (a policy as JSON, e.g. `{"type": "dstack_tdx", ...}`).
+///
+/// `server_name` is the name the certificate is checked against and sent as
+/// SNI. `alpn` is a comma-separated list of ALPN protocols (e.g. `"h2,http/1.1"`)
+/// or `NULL` for none.
+///
+/// Returns the connection, or `NULL` with `*error` set if the connection or
+/// the attestation failed.
+///
+/// # Safety
+///
+/// The strings must be null or NUL-terminated, and `error` null or valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_connect(
+    target: *const c_char,
+    server_name: *const c_char,
+    policy_json: *const c_char,
+    alpn: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut AtlasConnection {
+    guard(error, ptr::null_mut(), || {
+        let target = str_arg(target, "target")?;
+        let server_name = str_arg(server_name, "server_name")?;
+        let policy: Policy = serde_json::from_str(str_arg(policy_json, "policy_json")?)
+            .map_err(|e| Error::invalid(format!("invalid policy: {e}")))?;
+        let alpn = if alpn.is_null() {
+            None
+        } else {
+            let protocols = str_arg(alpn, "alpn")?.split(',').map(str::trim);
+            Some(
+                protocols
+                    .filter(|protocol| !protocol.is_empty())
+                    .map(String::from)
+                    .collect(),
+            )
+        };
+
+        let (tls, report) = RUNTIME.block_on(async {
+            let start = Instant::now();
+            let tcp = TcpStream::connect(target)
+                .await
+                .map_err(|e| Error::io(format!("failed to connect to {target}: {e}")))?;
+            let tcp_connect = start.elapsed();
+            let (tls, mut report) = atls_connect(tcp, server_name, policy, alpn).await?;
+            report.timings_mut().tcp_connect = Some(tcp_connect);
+            Ok::<_, Error>((tls, report))
+        })?;
+
+        let (reader, writer) = tokio::io::split(tls);
+        Ok(Box::into_raw(Box::new(AtlasConnection {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            attestation: attestation(&report).to_string(),
+        })))
+    })
+}
+
+/// Read up to `len` bytes into `buf`.
+///
+/// Returns the number of bytes read, `0` once the server closed the
+/// connection, or `-1` with `*error` set.
+///
+/// # Safety
+///
+/// `connection` must come from `atlas_connect` and not be closed, `buf` valid
+/// for `len` bytes of writes, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_read(
+    connection: *mut AtlasConnection,
+    buf: *mut u8,
+    len: usize,
+    error: *mut *mut c_char,
+) -> isize {
+    guard(error, -1, || {
+        let connection = connection_arg(connection)?;
+        if buf.is_null() || len == 0 {
+            return Err(Error::invalid("buf must be non-null and len non-zero"));
+        }
+        let buf = std::slice::from_raw_parts_mut(buf, len.min(isize::MAX as usize));
+        let mut reader = connection.reader.lock().unwrap_or_else(|e| e.into_inner());
+        let read = RUNTIME
+            .block_on(reader.read(buf))
+            .map_err(|e| Error::io(format!("read failed: {e}")))?;
+        Ok(read as isize)
+    })
+}
+
+/// Write all `len` bytes of `buf`, and flush them.
+///
+/// Returns `len`, or `-1` with `*error` set.
+///
+/// # Safety
+///
+/// `connection` must come from `atlas_connect` and not be closed, `buf` valid
+/// for `len` bytes of reads, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_write(
+    connection: *mut AtlasConnection,
+    buf: *const u8,
+    len: usize,
+    error: *mut *mut c_char,
+) -> isize {
+    guard(error, -1, || {
+        let connection = connection_arg(connection)?;
+        if len == 0 {
+            return Ok(0);
+        }
+        if buf.is_null() || len > isize::MAX as usize {
+            return Err(Error::invalid(
+                "buf must be non-null and len at most SSIZE_MAX",
+            ));
+        }
+        let buf = std::slice::from_raw_parts(buf, len);
+        let mut writer = connection.writer.lock().unwrap_or_else(|e| e.into_inner());
+        RUNTIME
+            .block_on(async {
+                writer.write_all(buf).await?;
+                writer.flush().await
+            })
+            .map_err(|e| Error::io(format!("write failed: {e}")))?;
+        Ok(len as isize)
+    })
+}
+
+/// Close `connection` (sending TLS close_notify) and free it. `NULL` is a
+/// no-op.
+///
+/// # Safety
+///
+/// `connection` must be null or come from `atlas_connect`, and must not be
+/// used again.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_close(connection: *mut AtlasConnection) {
+    if connection.is_null() {
+        return;
+    }
+    let connection = Box::from_raw(connection);
+    let _ = catch_unwind(AssertUnwindSafe(move || {
+        let mut writer = connection.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // A peer that stopped reading mustn't block the caller
+        let _ = RUNTIME.block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), writer.shutdown()).await
+        });
+    }));
+}
+
+/// The attestation of `connection`, as JSON: `trusted`, `tee_type`,
+/// `measurement` (hex MRTD), `tcb_status`, `advisory_ids`, `fmspc` and
+/// `timings` (`*_ms`).
+///
+/// Returns a string to free with `atlas_free`, or `NULL` if `connection` is
+/// null.
+///
+/// # Safety
+///
+/// `connection` must be null or come from `atlas_connect` and not be closed.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_get_attestation_json(
+    connection: *const AtlasConnection,
+) -> *mut c_char {
+    match connection.as_ref() {
+        Some(connection) => c_string(connection.attestation.clone()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Free a string the library returned. `NULL` is a no-op.
+///
+/// # Safety
+///
+/// `string` must be null or come from this library, and must not be used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn atlas_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Run `call`, reporting its error (or panic) in `*error` and returning
+/// `failed` instead.
+fn guard<T>(error: *mut *mut c_char, failed: T, call: impl FnOnce() -> Result<T, Error>) -> T {
+    let error_value = match catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(e)) => e,
+        Err(_) => Error {
+            code: ErrorCode::Internal,
+            message: "internal error".into(),
+        },
+    };
+    if !error.is_null() {
+        let json = json!({ "code": error_value.code, "message": error_value.message });
+        // SAFETY: the caller passes `error` null or valid for writes
+        unsafe { *error = c_string(json.to_string()) };
+    }
+    failed
+}
+
+/// The NUL-terminated string `arg`, named `name` in errors.
+unsafe fn str_arg<'a>(arg: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if arg.is_null() {
+        return Err(Error::invalid(format!("{name} must be non-null")));
+    }
+    CStr::from_ptr(arg)
+        .to_str()
+        .map_err(|_| Error::invalid(format!("{name} must be UTF-8")))
+}
+
+unsafe fn connection_arg<'a>(
+    connection: *mut AtlasConnection,
+) -> Result<&'a AtlasConnection, Error> {
+    connection
+        .as_ref()
+        .ok_or_else(|| Error::invalid("connection must be non-null"))
+}
+
+/// `string` for C, to free with `atlas_free`.
+fn c_string(string: String) -> *mut c_char {
+    CString::new(string.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+fn attestation(report: &Report) -> Value {
+    match report {
+        Report::Tdx(tdx) => json!({
+            "trusted": true,
+            "tee_type": "tdx",
+            "measurement": tdx.report.as_td10().map(|td| hex::encode(td.mr_td)),
+            "tcb_status": tdx.status,
+            "advisory_ids": tdx.advisory_ids,
+            "fmspc": tdx.fmspc,
+            "timings": tdx.timings.as_millis(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV_POLICY: &str = r#"{"type": "dstack_tdx", "disable_runtime_verification": true}"#;
+
+    fn c(string: &str) -> CString {
+        CString::new(string).unwrap()
+    }
+
+    /// Take the error JSON a call stored.
+    fn take_error(error: *mut c_char) -> Value {
+        assert!(!error.is_null());
+        let json = unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        unsafe { atlas_free(error) };
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_connect_rejects_invalid_arguments() {
+        let mut error = ptr::null_mut();
+        let connection = unsafe {
+            atlas_connect(
+                c("127.0.0.1:1").as_ptr(),
+                c("localhost").as_ptr(),
+                c(r#"{"type": "sev_snp"}"#).as_ptr(),
+                ptr::null(),
+                &mut error,
+            )
+        };
+        assert!(connection.is_null());
+        let error_json = take_error(error);
+        assert_eq!(error_json["code"], "INVALID_CONFIGURATION");
+        assert!(error_json["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid policy"));
+
+        let mut error = ptr::null_mut();
+        let connection = unsafe {
+            atlas_connect(
+                c("127.0.0.1:1").as_ptr(),
+                ptr::null(),
+                c(DEV_POLICY).as_ptr(),
+                ptr::null(),
+                &mut error,
+            )
+        };
+        assert!(connection.is_null());
+        assert_eq!(take_error(error)["message"], "server_name must be non-null");
+
+        // Callers may pass no error out-parameter
+        let connection = unsafe {
+            atlas_connect(
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null_mut(),
+            )
+        };
+        assert!(connection.is_null());
+    }
+
+    #[test]
+    fn test_connect_reports_io_errors() {
+        // A port nothing listens on
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = CString::new(listener.local_addr().unwrap().to_string()).unwrap();
+        drop(listener);
+
+        let mut error = ptr::null_mut();
+        let connection = unsafe {
+            atlas_connect(
+                target.as_ptr(),
+                c("localhost").as_ptr(),
+                c(DEV_POLICY).as_ptr(),
+                c("http/1.1").as_ptr(),
+                &mut error,
+            )
+        };
+        assert!(connection.is_null());
+        assert_eq!(take_error(error)["code"], "IO");
+    }
+
+    #[test]
+    fn test_null_connection() {
+        let mut error = ptr::null_mut();
+        let mut buf = [0u8; 4];
+        let read = unsafe { atlas_read(ptr::null_mut(), buf.as_mut_ptr(), 4, &mut error) };
+        assert_eq!(read, -1);
+        assert_eq!(take_error(error)["code"], "INVALID_CONFIGURATION");
+
+        let mut error = ptr::null_mut();
+        let written = unsafe { atlas_write(ptr::null_mut(), buf.as_ptr(), 4, &mut error) };
+        assert_eq!(written, -1);
+        assert_eq!(take_error(error)["message"], "connection must be non-null");
+
+        assert!(unsafe { atlas_get_attestation_json(ptr::null()) }.is_null());
+        unsafe {
+            atlas_close(ptr::null_mut());
+            atlas_free(ptr::null_mut());
+        }
+    }
+}
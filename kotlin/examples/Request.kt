@@ -0,0 +1,33 @@
+// Send one HTTP request to a TEE over attested TLS and print the attestation
+// and the response. See ../README.md for building and running it.
+
+import java.io.File
+import security.concrete.atlas.AtlasException
+import security.concrete.atlas.atlsConnect
+
+fun main(args: Array<String>) {
+    require(args.size == 2) { "usage: Request HOST POLICY_JSON_FILE" }
+    val (host, policyFile) = args
+    val policy = File(policyFile).readText()
+
+    val connection = try {
+        atlsConnect("$host:443", host, policy, listOf("http/1.1"))
+    } catch (e: AtlasException.Verification) {
+        System.err.println("attestation failed [${e.code}]: ${e.reason}")
+        kotlin.system.exitProcess(1)
+    }
+
+    connection.use {
+        val attestation = it.attestation()
+        System.err.println("TCB status ${attestation.tcbStatus}, MRTD ${attestation.measurement}")
+
+        it.write("GET / HTTP/1.1\r\nHost: $host\r\nConnection: close\r\n\r\n".toByteArray())
+        while (true) {
+            val chunk = it.read(16384u)
+            if (chunk.isEmpty()) break
+            System.out.write(chunk)
+        }
+        System.out.flush()
+        it.shutdown()
+    }
+}
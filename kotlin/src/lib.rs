@@ -0,0 +1,273 @@
+//! Kotlin and Java bindings, generated by UniFFI, so Android apps and JVM
+//! backends can connect to TEEs with the same policies as the other clients.
+//!
+//! The API is blocking (call it off the main thread on Android): each call
+//! runs on a tokio runtime owned by the library. `atlsConnect` returns an
+//! `AtlsConnection` only once the TEE's attestation is verified against the
+//! policy; its `read` and `write` may run concurrently from two threads.
+//!
+//! The Kotlin sources are generated from the built library by
+//! `uniffi-bindgen generate --library` (see `README.md`), never edited.
+
+use atlas_rs::{atls_connect as core_atls_connect, AtlsVerificationError, ErrorCode};
+use atlas_rs::{PhaseTimings, Policy, Report, TlsStream};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::runtime::Runtime;
+
+uniffi::setup_scaffolding!();
+
+/// Largest buffer `AtlsConnection.read()` allocates.
+const MAX_READ_BYTES: u32 = 1024 * 1024;
+
+/// Runs the I/O of every connection; built on first use and never freed.
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the tokio runtime")
+});
+
+/// Errors thrown to Kotlin/Java as `AtlasException` subclasses, with the
+/// description in `reason` (`message` belongs to `Throwable`).
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum AtlasError {
+    /// Attestation or the TLS handshake failed; `code` is the stable error
+    /// code, e.g. `TCB_STATUS_REJECTED`.
+    #[error("[{code}] {reason}")]
+    Verification { code: String, reason: String },
+    /// The connection failed or broke.
+    #[error("{reason}")]
+    Io { reason: String },
+    /// An argument (policy, ALPN, read size) is invalid.
+    #[error("{reason}")]
+    InvalidArgument { reason: String },
+}
+
+impl From<AtlsVerificationError> for AtlasError {
+    fn from(e: AtlsVerificationError) -> Self {
+        match e.code() {
+            ErrorCode::Io => AtlasError::Io {
+                reason: e.to_string(),
+            },
+            ErrorCode::InvalidConfiguration | ErrorCode::InvalidServerName => {
+                AtlasError::InvalidArgument {
+                    reason: e.to_string(),
+                }
+            }
+            code => AtlasError::Verification {
+                code: code.to_string(),
+                reason: e.to_string(),
+            },
+        }
+    }
+}
+
+fn io_error(reason: String) -> AtlasError {
+    AtlasError::Io { reason }
+}
+
+/// Per-phase connection durations in milliseconds.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Timings {
+    pub tcp_connect_ms: Option<f64>,
+    pub tls_handshake_ms: Option<f64>,
+    pub evidence_exchange_ms: Option<f64>,
+    pub collateral_fetch_ms: Option<f64>,
+    pub verification_ms: Option<f64>,
+    pub total_ms: f64,
+}
+
+impl From<&PhaseTimings> for Timings {
+    fn from(timings: &PhaseTimings) -> Self {
+        let ms = timings.as_millis();
+        Self {
+            tcp_connect_ms: ms.tcp_connect_ms,
+            tls_handshake_ms: ms.tls_handshake_ms,
+            evidence_exchange_ms: ms.evidence_exchange_ms,
+            collateral_fetch_ms: ms.collateral_fetch_ms,
+            verification_ms: ms.verification_ms,
+            total_ms: ms.total_ms,
+        }
+    }
+}
+
+/// The verified attestation of a connection.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Attestation {
+    pub trusted: bool,
+    pub tee_type: String,
+    /// Workload measurement (hex MRTD for TDX).
+    pub measurement: Option<String>,
+    pub tcb_status: String,
+    pub advisory_ids: Vec<String>,
+    pub fmspc: Option<String>,
+    pub timings: Timings,
+}
+
+impl From<&Report> for Attestation {
+    fn from(report: &Report) -> Self {
+        match report {
+            Report::Tdx(tdx) => Self {
+                trusted: true,
+                tee_type: "tdx".to_string(),
+                measurement: tdx.report.as_td10().map(|td| hex::encode(td.mr_td)),
+                tcb_status: tdx.status.clone(),
+                advisory_ids: tdx.advisory_ids.clone(),
+                fmspc: tdx.fmspc.clone(),
+                timings: (&tdx.timings).into(),
+            },
+        }
+    }
+}
+
+/// An attested TLS connection.
+///
+/// `shutdown()` sends TLS close_notify; `close()` (or Kotlin's `use {}`)
+/// frees the connection.
+#[derive(uniffi::Object)]
+pub struct AtlsConnection {
+    reader: Mutex<ReadHalf<TlsStream<TcpStream>>>,
+    writer: Mutex<WriteHalf<TlsStream<TcpStream>>>,
+    attestation: Attestation,
+}
+
+/// Connect to `target` (`host:port`) and verify the TEE behind it against
+/// `policy_json` (a policy as JSON, e.g. `{"type": "dstack_tdx", ...}`).
+///
+/// `server_name` is the name the certificate is checked against and sent as
+/// SNI; `alpn` the ALPN protocols to offer (e.g. `["http/1.1"]`).
+#[uniffi::export]
+pub fn atls_connect(
+    target: String,
+    server_name: String,
+    policy_json: String,
+    alpn: Vec<String>,
+) -> Result<Arc<AtlsConnection>, AtlasError> {
+    let policy: Policy =
+        serde_json::from_str(&policy_json).map_err(|e| AtlasError::InvalidArgument {
+            reason: format!("invalid policy: {e}"),
+        })?;
+    let alpn = (!alpn.is_empty()).then_some(alpn);
+
+    let (tls, report) = RUNTIME.block_on(async {
+        let start = Instant::now();
+        let tcp = TcpStream::connect(&target)
+            .await
+            .map_err(|e| io_error(format!("failed to connect to {target}: {e}")))?;
+        let tcp_connect = start.elapsed();
+        let (tls, mut report) = core_atls_connect(tcp, &server_name, policy, alpn).await?;
+        report.timings_mut().tcp_connect = Some(tcp_connect);
+        Ok::<_, AtlasError>((tls, report))
+    })?;
+
+    let (reader, writer) = tokio::io::split(tls);
+    Ok(Arc::new(AtlsConnection {
+        reader: Mutex::new(reader),
+        writer: Mutex::new(writer),
+        attestation: (&report).into(),
+    }))
+}
+
+#[uniffi::export]
+impl AtlsConnection {
+    /// The attestation verified when connecting.
+    pub fn attestation(&self) -> Attestation {
+        self.attestation.clone()
+    }
+
+    /// Read up to `max_bytes` (at most 1 MiB per call); empty once the server
+    /// closed the connection.
+    pub fn read(&self, max_bytes: u32) -> Result<Vec<u8>, AtlasError> {
+        if max_bytes == 0 {
+            return Err(AtlasError::InvalidArgument {
+                reason: "max_bytes must be non-zero".into(),
+            });
+        }
+        // The buffer is allocated up front: don't let a huge request reserve it
+        let mut buf = vec![0; max_bytes.min(MAX_READ_BYTES) as usize];
+        let mut reader = self.reader.lock().unwrap_or_else(|e| e.into_inner());
+        let read = RUNTIME
+            .block_on(reader.read(&mut buf))
+            .map_err(|e| io_error(format!("read failed: {e}")))?;
+        buf.truncate(read);
+        Ok(buf)
+    }
+
+    /// Write all of `data`, and flush it.
+    pub fn write(&self, data: Vec<u8>) -> Result<(), AtlasError> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        RUNTIME
+            .block_on(async {
+                writer.write_all(&data).await?;
+                writer.flush().await
+            })
+            .map_err(|e| io_error(format!("write failed: {e}")))
+    }
+
+    /// Send TLS close_notify; later writes fail.
+    pub fn shutdown(&self) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // A peer that stopped reading mustn't block the caller
+        let _ = RUNTIME.block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), writer.shutdown()).await
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV_POLICY: &str = r#"{"type": "dstack_tdx", "disable_runtime_verification": true}"#;
+
+    #[test]
+    fn test_connect_rejects_invalid_policy() {
+        let result = atls_connect(
+            "127.0.0.1:1".into(),
+            "localhost".into(),
+            r#"{"type": "sev_snp"}"#.into(),
+            vec![],
+        );
+        match result {
+            Err(AtlasError::InvalidArgument { reason }) => {
+                assert!(reason.starts_with("invalid policy"))
+            }
+            _ => panic!("expected InvalidArgument"),
+        }
+    }
+
+    #[test]
+    fn test_connect_reports_io_errors() {
+        // A port nothing listens on
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let result = atls_connect(
+            target,
+            "localhost".into(),
+            DEV_POLICY.into(),
+            vec!["http/1.1".into()],
+        );
+        assert!(matches!(result, Err(AtlasError::Io { .. })));
+    }
+
+    #[test]
+    fn test_verification_errors_keep_their_code() {
+        let error: AtlasError = AtlsVerificationError::TcbStatusNotAllowed {
+            status: "OutOfDate".into(),
+            allowed: vec!["UpToDate".into()],
+        }
+        .into();
+        match &error {
+            AtlasError::Verification { code, .. } => assert_eq!(code, "TCB_STATUS_REJECTED"),
+            _ => panic!("expected Verification"),
+        }
+        assert!(error.to_string().starts_with("[TCB_STATUS_REJECTED] "));
+    }
+}
@@ -3,14 +3,15 @@ use napi::bindgen_prelude::*;
 use napi_derive::napi;
 use once_cell::sync::Lazy;
 use atlas_rs::{
-    dstack::merge_with_default_app_compose, atls_connect as core_atls_connect, Policy, Report,
-    TlsStream as CoreTlsStream,
+    dstack::merge_with_default_app_compose, atls_connect as core_atls_connect, PhaseTimings,
+    Policy, Report, TlsStream as CoreTlsStream,
 };
 use rustls::crypto::aws_lc_rs::default_provider;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
 use tokio::net::{lookup_host, TcpStream};
 use tokio::sync::Mutex;
@@ -20,6 +21,37 @@ static CRYPTO_INIT: Lazy<()> = Lazy::new(|| {
     let _ = default_provider().install_default();
 });
 
+/// Per-phase connection durations in milliseconds.
+#[napi(object)]
+pub struct JsTimings {
+    #[napi(js_name = "tcpConnectMs")]
+    pub tcp_connect_ms: Option<f64>,
+    #[napi(js_name = "tlsHandshakeMs")]
+    pub tls_handshake_ms: Option<f64>,
+    #[napi(js_name = "evidenceExchangeMs")]
+    pub evidence_exchange_ms: Option<f64>,
+    #[napi(js_name = "collateralFetchMs")]
+    pub collateral_fetch_ms: Option<f64>,
+    #[napi(js_name = "verificationMs")]
+    pub verification_ms: Option<f64>,
+    #[napi(js_name = "totalMs")]
+    pub total_ms: f64,
+}
+
+impl From<&PhaseTimings> for JsTimings {
+    fn from(timings: &PhaseTimings) -> Self {
+        let ms = |d: Option<Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+        Self {
+            tcp_connect_ms: ms(timings.tcp_connect),
+            tls_handshake_ms: ms(timings.tls_handshake),
+            evidence_exchange_ms: ms(timings.evidence_exchange),
+            collateral_fetch_ms: ms(timings.collateral_fetch),
+            verification_ms: ms(timings.verification),
+            total_ms: timings.total().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
 #[napi(object)]
 pub struct JsAttestation {
     pub trusted: bool,
@@ -30,6 +62,7 @@ pub struct JsAttestation {
     pub tcb_status: String,
     #[napi(js_name = "advisoryIds")]
     pub advisory_ids: Vec<String>,
+    pub timings: JsTimings,
 }
 
 impl From<Report> for JsAttestation {
@@ -41,6 +74,7 @@ impl From<Report> for JsAttestation {
                 measurement: None, // VerifiedReport doesn't expose this directly
                 tcb_status: verified.status.clone(),
                 advisory_ids: verified.advisory_ids.clone(),
+                timings: (&verified.timings).into(),
             },
         }
     }
@@ -93,11 +127,13 @@ pub async fn atls_connect(
         .next()
         .ok_or_else(|| Error::from_reason("unable to resolve target host"))?;
 
+    let connect_start = Instant::now();
     let tcp = TcpStream::connect(tcp_addr)
         .await
         .map_err(|err| Error::from_reason(format!("tcp connect failed: {err}")))?;
+    let tcp_connect = connect_start.elapsed();
 
-    let (tls, report) = core_atls_connect(
+    let (tls, mut report) = core_atls_connect(
         tcp,
         &server_name,
         policy,
@@ -105,6 +141,7 @@ pub async fn atls_connect(
     )
     .await
     .map_err(|err| Error::from_reason(format!("atls handshake failed: {err}")))?;
+    report.timings_mut().tcp_connect = Some(tcp_connect);
 
     let socket_id = NEXT_SOCKET_ID.fetch_add(1, Ordering::SeqCst);
     let (reader, writer) = tokio::io::split(tls);
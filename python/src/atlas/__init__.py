@@ -2,9 +2,34 @@ import logging
 import os
 
 from . import httpx
-from .policy import dev_policy, dstack_tdx_policy, merge_with_default_app_compose
+from ._atlas import (
+    AtlasError,
+    AtlasTimeout,
+    AttestationRejected,
+    CollateralError,
+    MeasurementMismatch,
+    TcbStatusNotAllowed,
+    TlsError,
+    clear_collateral_cache,
+    configure_collateral_cache,
+    prefetch_collateral,
+    verify_quote,
+)
+from .client import HttpClient, HttpResponse
+from .httpx import AsyncHttpxTransport, HttpxTransport
+from .policy import (
+    DstackTdxPolicy,
+    ExpectedBootchain,
+    Policy,
+    compose_hash,
+    dev_policy,
+    dstack_tdx_policy,
+    expected_compose_event,
+    merge_with_default_app_compose,
+)
 from .utils import _get_default_logger
 from .verifiers.errors import AtlsVerificationError
+from .websocket import AtlsWebSocket, websocket_connect
 
 logger = _get_default_logger()
 
@@ -16,8 +41,30 @@ else:
 
 __all__ = [
     "httpx",
+    "HttpxTransport",
+    "AsyncHttpxTransport",
+    "Policy",
+    "DstackTdxPolicy",
+    "ExpectedBootchain",
     "dstack_tdx_policy",
     "dev_policy",
     "merge_with_default_app_compose",
+    "compose_hash",
+    "expected_compose_event",
     "AtlsVerificationError",
+    "AtlasError",
+    "AttestationRejected",
+    "TcbStatusNotAllowed",
+    "MeasurementMismatch",
+    "CollateralError",
+    "TlsError",
+    "AtlasTimeout",
+    "configure_collateral_cache",
+    "prefetch_collateral",
+    "clear_collateral_cache",
+    "verify_quote",
+    "websocket_connect",
+    "AtlsWebSocket",
+    "HttpClient",
+    "HttpResponse",
 ]
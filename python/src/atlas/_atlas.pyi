@@ -1,15 +1,246 @@
 """Type stubs for the Rust _atlas extension module (PyO3)."""
 
+import os
+import socket
+from datetime import datetime
+from types import TracebackType
+from typing import IO, Any, TypedDict
+
+# Dict shapes returned by the bindings. These exist only for type checkers.
+
+class AttestationTimings(TypedDict):
+    """Per-phase durations of a connection's attestation, in milliseconds."""
+
+    tcp_connect_ms: float | None
+    tls_handshake_ms: float | None
+    evidence_exchange_ms: float | None
+    collateral_fetch_ms: float | None
+    verification_ms: float | None
+    total_ms: float
+
+class VerifiedQuote(TypedDict):
+    """Result of ``verify_quote()``; measurements are lowercase hex."""
+
+    tee_type: str
+    tcb_status: str
+    advisory_ids: list[str]
+    fmspc: str | None
+    measurement: str
+    rtmr0: str
+    rtmr1: str
+    rtmr2: str
+    rtmr3: str
+    report_data: str
+
+class Attestation(dict[str, Any]):
+    """``AtlsConnection.attestation``: a dict with the keys ``trusted`` (bool),
+    ``tee_type`` (str), ``measurement`` (str | None), ``tcb_status`` (str),
+    ``advisory_ids`` (list[str]) and ``timings`` (``AttestationTimings``),
+    plus the raw evidence it was verified from."""
+
+    def raw_quote(self) -> bytes: ...
+    def raw_event_log(self) -> bytes | None: ...
+
+class ComposeEvent(TypedDict):
+    """Result of ``expected_compose_event_py()``: an event log entry."""
+
+    imr: int
+    event_type: int
+    digest: str
+    event: str
+    event_payload: str
+
+class AtlasError(OSError):
+    """Base class of aTLS errors. ``code`` holds a machine-readable error code."""
+
+    code: str
+
+class AttestationRejected(AtlasError):
+    """The peer's attestation evidence was invalid or did not satisfy the policy."""
+
+class TcbStatusNotAllowed(AttestationRejected):
+    """The platform TCB status is not allowed (or its grace period expired)."""
+
+class MeasurementMismatch(AttestationRejected):
+    """A measurement (MRTD, RTMR, app compose or OS image hash) did not match."""
+
+class CollateralError(AtlasError):
+    """Intel collateral could not be fetched, was expired, or was invalid."""
+
+class TlsError(AtlasError):
+    """The TLS handshake failed."""
+
+class AtlasTimeout(AtlasError, TimeoutError):
+    """A read on an AtlsConnection timed out. The connection stays usable."""
+
 class AtlsConnection:
     """An attested TLS connection backed by Rust."""
 
     @property
-    def attestation(self) -> dict[str, object]: ...
-    def read(self, size: int) -> bytes: ...
+    def attestation(self) -> Attestation: ...
+    def read(self, size: int, timeout: float | None = None) -> bytes: ...
+    def readinto(
+        self, buffer: bytearray | memoryview, timeout: float | None = None
+    ) -> int: ...
+    def read_exact(self, n: int, timeout: float | None = None) -> bytes: ...
+    def read_until(
+        self,
+        delimiter: bytes,
+        max_bytes: int = 65536,
+        timeout: float | None = None,
+    ) -> bytes: ...
+    def makefile(
+        self,
+        mode: str = "r",
+        buffering: int | None = None,
+        *,
+        encoding: str | None = None,
+        errors: str | None = None,
+        newline: str | None = None,
+    ) -> IO[Any]: ...
+    def settimeout(self, timeout: float | None) -> None: ...
+    def gettimeout(self) -> float | None: ...
     def write(self, data: bytes) -> int: ...
+    def shutdown_write(self) -> None: ...
     def close(self) -> None: ...
+    def reattest(
+        self,
+        policy_json: str | Policy | DstackTdxPolicy | None = None,
+        *,
+        allow_measurement_change: bool = False,
+    ) -> Attestation: ...
+    def __enter__(self) -> AtlsConnection: ...
+    def __exit__(
+        self,
+        exc_type: type[BaseException] | None,
+        exc_value: BaseException | None,
+        traceback: TracebackType | None,
+    ) -> bool: ...
+
+class ServerPolicy:
+    """How ``atls_accept()`` presents itself: certificate, key and guest agent."""
+
+    def __init__(
+        self,
+        certfile: str | os.PathLike[str],
+        keyfile: str | os.PathLike[str],
+        *,
+        agent_socket: str | os.PathLike[str] = "/var/run/dstack.sock",
+        alpn: list[str] | None = None,
+    ) -> None: ...
+    def register_certificate(self) -> None: ...
+
+class ExpectedBootchain:
+    """Expected TDX bootchain measurements (lowercase hex)."""
+
+    def __init__(self, *, mrtd: str, rtmr0: str, rtmr1: str, rtmr2: str) -> None: ...
+    @property
+    def mrtd(self) -> str: ...
+    @property
+    def rtmr0(self) -> str: ...
+    @property
+    def rtmr1(self) -> str: ...
+    @property
+    def rtmr2(self) -> str: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(json: str) -> ExpectedBootchain: ...
+
+class DstackTdxPolicy:
+    """Policy for dstack TDX verification, validated on construction."""
+
+    def __init__(
+        self,
+        *,
+        expected_bootchain: ExpectedBootchain | None = None,
+        app_compose: dict[str, Any] | None = None,
+        os_image_hash: str | None = None,
+        allowed_tcb_status: list[str] | None = None,
+        grace_period: int | None = None,
+        pccs_url: str | None = None,
+        cache_collateral: bool = False,
+        disable_runtime_verification: bool = False,
+    ) -> None: ...
+    @property
+    def expected_bootchain(self) -> ExpectedBootchain | None: ...
+    @property
+    def app_compose(self) -> dict[str, Any] | None: ...
+    @property
+    def os_image_hash(self) -> str | None: ...
+    @property
+    def allowed_tcb_status(self) -> list[str]: ...
+    @property
+    def grace_period(self) -> int | None: ...
+    @property
+    def pccs_url(self) -> str | None: ...
+    @property
+    def cache_collateral(self) -> bool: ...
+    @property
+    def disable_runtime_verification(self) -> bool: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(json: str) -> DstackTdxPolicy: ...
+
+class Policy:
+    """Attestation policy: which verifier to use and its configuration."""
+
+    def __init__(self, dstack_tdx: DstackTdxPolicy) -> None: ...
+    @property
+    def type(self) -> str: ...
+    @property
+    def dstack_tdx(self) -> DstackTdxPolicy | None: ...
+    def to_json(self) -> str: ...
+    @staticmethod
+    def from_json(json: str) -> Policy: ...
 
 def atls_connect(
-    host: str, port: int, server_name: str, policy_json: str
+    host: str,
+    port: int,
+    server_name: str,
+    policy_json: str | Policy | DstackTdxPolicy,
+    alpn: list[str] | None = None,
+    *,
+    proxy: str | None = None,
+    trust_env: bool = True,
+) -> AtlsConnection: ...
+def atls_wrap_socket(
+    sock: socket.socket,
+    server_name: str,
+    policy_json: str | Policy | DstackTdxPolicy,
+    alpn: list[str] | None = None,
+) -> AtlsConnection: ...
+def atls_connect_uds(
+    path: str | os.PathLike[str],
+    server_name: str,
+    policy_json: str | Policy | DstackTdxPolicy,
+    alpn: list[str] | None = None,
+) -> AtlsConnection: ...
+def atls_connect_vsock(
+    cid: int,
+    port: int,
+    server_name: str,
+    policy_json: str | Policy | DstackTdxPolicy,
+    alpn: list[str] | None = None,
+) -> AtlsConnection: ...
+def atls_accept(
+    sock: socket.socket | int,
+    server_policy: ServerPolicy,
+    *,
+    timeout: float | None = None,
 ) -> AtlsConnection: ...
+def configure_collateral_cache(
+    *, ttl: float | None = None, disk_path: str | os.PathLike[str] | None = None
+) -> None: ...
+def prefetch_collateral(
+    fmspc: str, ca: str = "processor", *, pccs_url: str | None = None
+) -> None: ...
+def clear_collateral_cache() -> None: ...
+def verify_quote(
+    quote_bytes: bytes,
+    collateral_bytes: bytes,
+    policy_json: str | Policy | DstackTdxPolicy,
+    at_time: datetime | float | None = None,
+) -> VerifiedQuote: ...
 def merge_with_default_app_compose_py(user_compose_json: str) -> str: ...
+def compose_hash_py(compose_json: str) -> str: ...
+def expected_compose_event_py(compose_json: str) -> ComposeEvent: ...
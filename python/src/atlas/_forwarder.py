@@ -0,0 +1,163 @@
+"""
+Loopback forwarder onto attested connections.
+
+Libraries that dial their own sockets (gRPC's C core, for one) cannot use an
+``AtlsConnection`` directly. ``AtlsForwarder`` listens on ``127.0.0.1`` and,
+for every local connection it accepts, opens an attested connection to the
+remote server and pumps bytes both ways. A local connection whose remote
+fails attestation is closed before any byte is forwarded, so the library sees
+a dropped connection rather than talking to an unverified server.
+
+The loopback leg is plaintext; any local process can connect to the listener
+and reach the attested server through it.
+"""
+
+import socket
+import threading
+
+from ._atlas import atls_connect
+from .policy import policy_to_json
+from .utils import _get_default_logger
+
+logger = _get_default_logger()
+
+CHUNK_SIZE = 64 * 1024
+READ_POLL_INTERVAL = 1.0
+
+
+class AtlsForwarder:
+    """Forward local TCP connections to ``host:port`` over aTLS."""
+
+    def __init__(self, host, port, policy, *, server_name=None, alpn=None):
+        self.host = host
+        self.port = port
+        self.server_name = server_name or host
+        self.alpn = alpn
+        self._policy_json = policy_to_json(policy)
+        self._lock = threading.Lock()
+        self._closed = False
+        self._pairs = set()
+        self._attestation = None
+        self._last_error = None
+
+        self._listener = socket.create_server(("127.0.0.1", 0))
+        self.local_address = self._listener.getsockname()
+        self._accept_thread = threading.Thread(
+            target=self._accept_loop, name="atls-forwarder", daemon=True
+        )
+        self._accept_thread.start()
+
+    @property
+    def attestation(self):
+        """Attestation of the most recent remote connection, or None."""
+        with self._lock:
+            return self._attestation
+
+    @property
+    def last_error(self):
+        """The most recent connect or attestation failure, or None."""
+        with self._lock:
+            return self._last_error
+
+    def _accept_loop(self):
+        while True:
+            try:
+                local, _ = self._listener.accept()
+            except OSError:
+                return
+            threading.Thread(target=self._serve, args=(local,), daemon=True).start()
+
+    def _serve(self, local):
+        try:
+            conn = atls_connect(
+                self.host,
+                self.port,
+                self.server_name,
+                self._policy_json,
+                alpn=self.alpn,
+            )
+        except Exception as e:
+            logger.error(f"aTLS connection to {self.host}:{self.port} failed: {e}")
+            with self._lock:
+                self._last_error = e
+            local.close()
+            return
+
+        pair = (local, conn)
+        with self._lock:
+            if self._closed:
+                conn.close()
+                local.close()
+                return
+            self._attestation = conn.attestation
+            self._pairs.add(pair)
+
+        upstream = threading.Thread(
+            target=self._pump_upstream, args=(local, conn), daemon=True
+        )
+        upstream.start()
+        self._pump_downstream(conn, local)
+        upstream.join()
+
+        with self._lock:
+            self._pairs.discard(pair)
+        conn.close()
+        local.close()
+
+    def _pump_upstream(self, local, conn):
+        """Local socket to attested connection."""
+        try:
+            while data := local.recv(CHUNK_SIZE):
+                conn.write(data)
+            # Pass the half-close on; the response still flows downstream.
+            conn.shutdown_write()
+        except OSError as e:
+            logger.debug(f"forwarder upstream closed: {e}")
+            # Unblocks the downstream pump's next read.
+            conn.close()
+
+    def _pump_downstream(self, conn, local):
+        """Attested connection to local socket."""
+        try:
+            while True:
+                try:
+                    data = conn.read(CHUNK_SIZE, READ_POLL_INTERVAL)
+                except TimeoutError:
+                    if self._closed:
+                        break
+                    continue
+                if not data:
+                    break
+                local.sendall(data)
+        except OSError as e:
+            logger.debug(f"forwarder downstream closed: {e}")
+        try:
+            local.shutdown(socket.SHUT_RDWR)
+        except OSError:
+            pass
+
+    def close(self):
+        """Stop accepting and close every forwarded connection."""
+        with self._lock:
+            if self._closed:
+                return
+            self._closed = True
+            pairs = list(self._pairs)
+        try:
+            # Wakes the accept thread; close() alone does not on every platform.
+            self._listener.shutdown(socket.SHUT_RDWR)
+        except OSError:
+            pass
+        self._listener.close()
+        for local, _conn in pairs:
+            try:
+                local.shutdown(socket.SHUT_RDWR)
+            except OSError:
+                pass
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc_info):
+        self.close()
+        return False
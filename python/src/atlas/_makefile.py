@@ -0,0 +1,75 @@
+"""
+File objects over an AtlsConnection, backing ``AtlsConnection.makefile()``.
+
+Mirrors ``socket.socket.makefile()``: closing the file does not close the
+connection.
+"""
+
+import io
+
+
+class AtlsConnectionIO(io.RawIOBase):
+    """Raw (unbuffered) I/O on an AtlsConnection, like ``socket.SocketIO``."""
+
+    def __init__(self, conn, mode):
+        if mode not in ("r", "w", "rw"):
+            raise ValueError(f"invalid mode: {mode!r}")
+        super().__init__()
+        self._conn = conn
+        self._reading = "r" in mode
+        self._writing = "w" in mode
+
+    def readable(self):
+        self._checkClosed()
+        return self._reading
+
+    def writable(self):
+        self._checkClosed()
+        return self._writing
+
+    def readinto(self, buffer):
+        self._checkClosed()
+        if not self._reading:
+            raise io.UnsupportedOperation("not readable")
+        return self._conn.readinto(buffer)
+
+    def write(self, buffer):
+        self._checkClosed()
+        if not self._writing:
+            raise io.UnsupportedOperation("not writable")
+        return self._conn.write(bytes(buffer))
+
+
+def makefile(
+    conn, mode="r", buffering=None, *, encoding=None, errors=None, newline=None
+):
+    """Return a file object connected to ``conn``, as ``socket.makefile()`` does.
+
+    ``mode`` may only contain ``r``, ``w`` and ``b``.
+    """
+    if not set(mode) <= {"r", "w", "b"}:
+        raise ValueError(f"invalid mode {mode!r} (only r, w, b allowed)")
+    writing = "w" in mode
+    reading = "r" in mode or not writing
+    binary = "b" in mode
+    raw = AtlsConnectionIO(conn, ("r" if reading else "") + ("w" if writing else ""))
+
+    if buffering is None or buffering < 0:
+        buffering = io.DEFAULT_BUFFER_SIZE
+    if buffering == 0:
+        if not binary:
+            raise ValueError("unbuffered streams must be binary")
+        return raw
+
+    if reading and writing:
+        buffer = io.BufferedRWPair(raw, raw, buffering)
+    elif reading:
+        buffer = io.BufferedReader(raw, buffering)
+    else:
+        buffer = io.BufferedWriter(raw, buffering)
+    if binary:
+        return buffer
+
+    text = io.TextIOWrapper(buffer, io.text_encoding(encoding), errors, newline)
+    text.mode = mode
+    return text
@@ -0,0 +1,83 @@
+"""
+Socket-like wrapper over an AtlsConnection, for HTTP clients built on
+``http.client`` (the stdlib connection and urllib3's).
+"""
+
+import io
+import socket
+
+
+class _AtlsRawIO(io.RawIOBase):
+    """Unbuffered reader over an AtlsSocket's connection, for ``makefile()``."""
+
+    def __init__(self, sock):
+        self._sock = sock
+
+    def readable(self):
+        return True
+
+    def readinto(self, buffer):
+        data = self._sock._conn.read(len(buffer))
+        buffer[: len(data)] = data
+        return len(data)
+
+    def close(self):
+        if not self.closed:
+            self._sock._decref_socketios()
+        super().close()
+
+
+class AtlsSocket:
+    """The subset of the ``socket`` API that http.client and urllib3 use,
+    backed by a Rust AtlsConnection.
+
+    As with a real socket, ``close()`` leaves the connection open until the
+    files returned by ``makefile()`` are closed too, so a response can still
+    be read after http.client closes a ``Connection: close`` connection.
+
+    **Limitation**: timeouts are not forwarded to the Rust side, as with the
+    httpx transport; ``settimeout`` is accepted and ignored.
+    """
+
+    def __init__(self, conn):
+        self._conn = conn
+        self._io_refs = 0
+        self._conn_closed = False
+        self.closed = False
+
+    def sendall(self, data):
+        self._conn.write(bytes(data))
+
+    def makefile(self, mode="rb", buffering=-1):
+        if "w" in mode:
+            raise ValueError("aTLS sockets only support read-mode files")
+        self._io_refs += 1
+        return io.BufferedReader(_AtlsRawIO(self))
+
+    def settimeout(self, timeout):
+        pass  # timeout is not forwarded to Rust; the Rust side blocks on tokio I/O.
+
+    def gettimeout(self):
+        return None
+
+    def shutdown(self, how):
+        if how == socket.SHUT_WR:
+            self._conn.shutdown_write()
+            return
+        self.closed = True
+        self._close_conn()
+
+    def close(self):
+        self.closed = True
+        if self._io_refs <= 0:
+            self._close_conn()
+
+    def _decref_socketios(self):
+        self._io_refs -= 1
+        if self.closed and self._io_refs <= 0:
+            self._close_conn()
+
+    def _close_conn(self):
+        if not self._conn_closed:
+            self._conn_closed = True
+            self._conn.close()
@@ -0,0 +1,185 @@
+"""
+Minimal HTTP client over one attested connection.
+
+``HttpClient`` talks to a single aTLS host without pulling in httpx or
+requests: it keeps one HTTP/1.1 connection alive (reconnecting when the server
+drops it) and returns ``HttpResponse`` objects whose bodies can be read at
+once or iterated in chunks as they arrive, e.g. for streamed completions.
+Framing (``Content-Length``, chunked and close-delimited bodies) is handled
+by ``http.client`` on top of ``AtlsHTTPSConnection``.
+"""
+
+import http.client
+import json as jsonlib
+
+from .http_client import AtlsHTTPSConnection
+from .utils import _get_default_logger
+
+logger = _get_default_logger()
+
+DEFAULT_CHUNK_SIZE = 64 * 1024
+
+# Methods that may be resent when a kept-alive connection turns out closed.
+_IDEMPOTENT_METHODS = frozenset({"GET", "HEAD", "OPTIONS", "PUT", "DELETE"})
+
+
+class HttpResponse:
+    """Response from ``HttpClient``.
+
+    The body is not read up front: use ``read()``/``text()``/``json()``, or
+    iterate over ``iter_bytes()``/``iter_lines()`` to consume it as it arrives.
+    Read the body completely or ``close()`` the response (or use it as a
+    context manager) before sending the next request.
+    """
+
+    def __init__(self, response, attestation, on_abandon):
+        self._response = response
+        self._on_abandon = on_abandon
+        self.status = response.status
+        self.reason = response.reason
+        self.headers = response.headers
+        self.attestation = attestation
+
+    def read(self):
+        """Read and return the rest of the body."""
+        return self._response.read()
+
+    def text(self, encoding=None):
+        """Read the body and decode it (charset from ``Content-Type``, else UTF-8)."""
+        charset = encoding or self.headers.get_content_charset() or "utf-8"
+        return self.read().decode(charset)
+
+    def json(self):
+        """Read the body and parse it as JSON."""
+        return jsonlib.loads(self.read())
+
+    def iter_bytes(self, chunk_size=DEFAULT_CHUNK_SIZE):
+        """Yield body chunks of at most ``chunk_size`` bytes as they arrive."""
+        while chunk := self._response.read1(chunk_size):
+            yield chunk
+
+    def iter_lines(self):
+        """Yield body lines (as bytes, without the line ending) as they arrive."""
+        while line := self._response.readline():
+            yield line.rstrip(b"\r\n")
+
+    def __iter__(self):
+        return self.iter_bytes()
+
+    def close(self):
+        """Release the response. An unread body closes the connection, since
+        it can't carry another request until the body is consumed."""
+        if not self._response.isclosed():
+            self._response.close()
+            self._on_abandon()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.close()
+        return False
+
+    def __repr__(self):
+        return f"<HttpResponse [{self.status}]>"
+
+
+class HttpClient:
+    """HTTP/1.1 client for one attested host.
+
+    Args:
+        host: Server hostname.
+        port: Server port.
+        policy: Attestation policy (dict, ``Policy`` or ``DstackTdxPolicy``).
+        server_name: SNI server name (defaults to ``host``).
+        timeout: Read timeout in seconds for responses, or ``None``.
+        headers: Headers sent with every request.
+
+    A client is not thread-safe; use one per thread.
+    """
+
+    def __init__(
+        self, host, port, policy, *, server_name=None, timeout=None, headers=None
+    ):
+        self._conn = AtlsHTTPSConnection(
+            host, port, policy=policy, server_name=server_name, timeout=timeout
+        )
+        self.headers = dict(headers or {})
+
+    @property
+    def attestation(self):
+        """Attestation report of the current connection (``None`` before the first)."""
+        return self._conn.attestation
+
+    def request(self, method, path, *, headers=None, body=None, json=None):
+        """Send a request and return the ``HttpResponse`` once headers arrive.
+
+        ``body`` may be bytes, str, a file or an iterable of bytes (sent
+        chunked); ``json`` serializes a value as the body instead.
+        """
+        method = method.upper()
+        all_headers = {**self.headers, **(headers or {})}
+        if json is not None:
+            body = jsonlib.dumps(json).encode("utf-8")
+            all_headers.setdefault("Content-Type", "application/json")
+
+        # A kept-alive connection may have been closed by the server while
+        # idle; resend once on a fresh connection when that is safe.
+        reused = self._conn.sock is not None
+        retryable = method in _IDEMPOTENT_METHODS and (
+            body is None or isinstance(body, (bytes, str))
+        )
+        try:
+            response = self._send(method, path, all_headers, body)
+        except (http.client.RemoteDisconnected, BrokenPipeError, ConnectionResetError):
+            if not (reused and retryable):
+                raise
+            logger.debug("aTLS connection dropped while idle, reconnecting")
+            self._conn.close()
+            response = self._send(method, path, all_headers, body)
+        return HttpResponse(response, self._conn.attestation, self._conn.close)
+
+    def _send(self, method, path, headers, body):
+        try:
+            self._conn.request(method, path, body=body, headers=headers)
+            response = self._conn.getresponse()
+        except BaseException:
+            self._conn.close()
+            raise
+        if response.length == 0:
+            # Release bodiless responses (HEAD, 204, ...) right away, so
+            # http.client accepts the next request without a read() first.
+            response.read()
+        return response
+
+    def get(self, path, **kwargs):
+        return self.request("GET", path, **kwargs)
+
+    def post(self, path, **kwargs):
+        return self.request("POST", path, **kwargs)
+
+    def put(self, path, **kwargs):
+        return self.request("PUT", path, **kwargs)
+
+    def patch(self, path, **kwargs):
+        return self.request("PATCH", path, **kwargs)
+
+    def delete(self, path, **kwargs):
+        return self.request("DELETE", path, **kwargs)
+
+    def head(self, path, **kwargs):
+        return self.request("HEAD", path, **kwargs)
+
+    def close(self):
+        """Close the connection."""
+        self._conn.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.close()
+        return False
+
+
+__all__ = ["HttpClient", "HttpResponse"]
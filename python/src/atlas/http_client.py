@@ -0,0 +1,68 @@
+"""
+``http.client`` connection over aTLS.
+
+``AtlsHTTPSConnection`` is a drop-in ``http.client.HTTPSConnection``: the
+same ``request()``/``getresponse()`` interface, with the TCP + TLS session and
+attestation handled by the Rust core. Use it from stdlib-only code or from
+libraries that accept an ``HTTPSConnection`` (class).
+"""
+
+import http.client
+import socket
+
+from ._atlas import atls_connect
+from ._socket import AtlsSocket
+from .policy import policy_to_json
+from .utils import _get_default_logger
+
+logger = _get_default_logger()
+
+
+class AtlsHTTPSConnection(http.client.HTTPSConnection):
+    """``http.client.HTTPSConnection`` whose peer must pass attestation.
+
+    Args:
+        host: Server hostname (an optional ``:port`` suffix is parsed as usual).
+        port: Server port (default 443).
+        policy: Attestation policy (dict, ``Policy`` or ``DstackTdxPolicy``).
+        server_name: SNI server name (defaults to ``host``).
+        timeout: Read timeout in seconds (positive, or ``None``) for
+            responses; the connection handshake itself is not bounded.
+        blocksize: As for ``http.client.HTTPSConnection``.
+
+    Connection failures raise the typed ``atlas.AtlasError`` subclasses, which
+    are ``OSError``s like the ones ``http.client`` raises. Proxy tunnels
+    (``set_tunnel()``) are not supported.
+    """
+
+    def __init__(
+        self,
+        host,
+        port=None,
+        *,
+        policy,
+        server_name=None,
+        timeout=socket._GLOBAL_DEFAULT_TIMEOUT,
+        blocksize=8192,
+    ):
+        super().__init__(host, port, timeout=timeout, blocksize=blocksize)
+        self._atls_policy_json = policy_to_json(policy)
+        self._server_name = server_name or self.host
+        # Attestation report of the latest connection, or None before the first.
+        self.attestation = None
+
+    def set_tunnel(self, host, port=None, headers=None):
+        raise NotImplementedError("aTLS connections do not support proxy tunnels")
+
+    def connect(self):
+        logger.debug("aTLS connecting to %s:%s", self.host, self.port)
+        conn = atls_connect(
+            self.host, self.port, self._server_name, self._atls_policy_json
+        )
+        if self.timeout is not socket._GLOBAL_DEFAULT_TIMEOUT:
+            conn.settimeout(self.timeout)
+        self.attestation = conn.attestation
+        self.sock = AtlsSocket(conn)
+
+
+__all__ = ["AtlsHTTPSConnection"]
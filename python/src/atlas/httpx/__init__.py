@@ -3,5 +3,6 @@ httpx clients with aTLS verification.
 """
 
 from .sync_client import Client
+from .transport import AsyncHttpxTransport, HttpxTransport
 
-__all__ = ["Client"]
+__all__ = ["Client", "HttpxTransport", "AsyncHttpxTransport"]
@@ -5,7 +5,7 @@ httpx.Client with aTLS verification.
 import httpx
 
 from ..utils import _get_default_logger
-from .transport import AtlsNetworkBackend, AtlsNetworkStream
+from .transport import HttpxTransport, _attach_attestation
 
 logger = _get_default_logger()
 
@@ -30,21 +30,8 @@ class Client(httpx.Client):
             raise ValueError(
                 "setting transport argument isn't possible. aTLS uses its own transport"
             )
-        transport = httpx.HTTPTransport()
-        # Accessing httpcore internals to inject our network backend.
-        # httpx is pinned to >=0.28.1,<0.29 in pyproject.toml to guard against breakage.
-        transport._pool._network_backend = AtlsNetworkBackend(
-            atls_policy_per_hostname or {},
-            default_backend=transport._pool._network_backend,
-        )
-        kwargs["transport"] = transport
+        kwargs["transport"] = HttpxTransport(atls_policy_per_hostname)
         super().__init__(*args, **kwargs)
 
     def send(self, request, **kwargs):
-        response = super().send(request, **kwargs)
-        # httpcore includes network_stream in response extensions (http11.py:129)
-        # httpx passes extensions through (default.py:258)
-        stream = response.extensions.get("network_stream")
-        if isinstance(stream, AtlsNetworkStream):
-            response.extensions["attestation"] = stream._conn.attestation
-        return response
+        return _attach_attestation(super().send(request, **kwargs))
@@ -1,18 +1,20 @@
 """
-httpcore NetworkBackend that routes aTLS connections through Rust.
+httpx transports and httpcore NetworkBackends that route aTLS connections through Rust.
 
 For hostnames with an aTLS policy, connections are established through
 the Rust core (TCP + TLS + EKM binding + attestation). For other hostnames,
 requests fall through to the default httpcore backend (standard TLS).
 
-httpcore handles all HTTP/1.1 framing over the attested stream.
+httpcore handles all HTTP/1.1 framing over the attested stream. The async
+variants run the blocking Rust calls in a worker thread.
 """
 
-import json
-
+import anyio
 import httpcore
+import httpx
 
 from .._atlas import atls_connect
+from ..policy import policy_to_json
 from ..utils import _get_default_logger
 from ..verifiers.errors import AtlsVerificationError
 
@@ -71,17 +73,133 @@ class AtlsNetworkBackend(httpcore.NetworkBackend):
                 socket_options=socket_options,
             )
 
-        policy_json = json.dumps(self._policies[host])
-        logger.debug("aTLS connecting to %s:%s", host, port)
+        return AtlsNetworkStream(_connect(host, port, self._policies[host]))
+
+
+class AsyncAtlsNetworkStream(httpcore.AsyncNetworkStream):
+    """Async counterpart of ``AtlsNetworkStream``.
+
+    Each blocking Rust call runs in a worker thread. The same timeout
+    limitation applies.
+    """
+
+    def __init__(self, conn):
+        self._conn = conn
+
+    async def read(self, max_bytes, timeout=None):
+        return bytes(await anyio.to_thread.run_sync(self._conn.read, max_bytes))
+
+    async def write(self, buffer, timeout=None):
+        await anyio.to_thread.run_sync(self._conn.write, bytes(buffer))
+
+    async def aclose(self):
+        await anyio.to_thread.run_sync(self._conn.close)
+
+    async def start_tls(self, ssl_context, server_hostname=None, timeout=None):
+        return self  # TLS already established by Rust
+
+    def get_extra_info(self, info):
+        return None  # No Python ssl_object → httpcore uses HTTP/1.1
+
+
+class AsyncAtlsNetworkBackend(httpcore.AsyncNetworkBackend):
+    """Async counterpart of ``AtlsNetworkBackend``."""
+
+    def __init__(self, policies, default_backend=None):
+        self._policies = policies
+        self._default_backend = default_backend or httpcore.AnyIOBackend()
+
+    async def connect_tcp(
+        self, host, port, timeout=None, local_address=None, socket_options=None
+    ):
+        if host not in self._policies:
+            return await self._default_backend.connect_tcp(
+                host,
+                port,
+                timeout=timeout,
+                local_address=local_address,
+                socket_options=socket_options,
+            )
+
+        policy = self._policies[host]
+        conn = await anyio.to_thread.run_sync(_connect, host, port, policy)
+        return AsyncAtlsNetworkStream(conn)
+
+    async def connect_unix_socket(self, path, timeout=None, socket_options=None):
+        return await self._default_backend.connect_unix_socket(
+            path, timeout=timeout, socket_options=socket_options
+        )
+
+    async def sleep(self, seconds):
+        await self._default_backend.sleep(seconds)
+
+
+def _connect(host, port, policy):
+    """Open an attested connection, raising ``AtlsVerificationError`` on failure."""
+    policy_json = policy_to_json(policy)
+    logger.debug("aTLS connecting to %s:%s", host, port)
+
+    try:
+        conn = atls_connect(host, port, host, policy_json)
+    except Exception as e:
+        raise AtlsVerificationError(
+            f"aTLS connection to {host}:{port} failed: {e}",
+            code=getattr(e, "code", None),
+        ) from e
+
+    logger.debug(
+        "aTLS connected to %s:%s, attestation: %s", host, port, conn.attestation
+    )
+    return conn
 
-        try:
-            conn = atls_connect(host, port, host, policy_json)
-        except Exception as e:
-            raise AtlsVerificationError(
-                f"aTLS connection to {host}:{port} failed: {e}"
-            ) from e
 
-        logger.debug(
-            "aTLS connected to %s:%s, attestation: %s", host, port, conn.attestation
+def _attach_attestation(response):
+    """Copy the attestation report of the response's connection into its extensions."""
+    # httpcore includes network_stream in response extensions (http11.py:129)
+    # httpx passes extensions through (default.py:258)
+    stream = response.extensions.get("network_stream")
+    if isinstance(stream, (AtlsNetworkStream, AsyncAtlsNetworkStream)):
+        response.extensions["attestation"] = stream._conn.attestation
+    return response
+
+
+class HttpxTransport(httpx.HTTPTransport):
+    """httpx transport with aTLS verification.
+
+    Connections to hostnames in ``atls_policy_per_hostname`` go through Rust
+    aTLS; other hostnames use standard HTTPS. Connections are pooled and
+    reused like those of ``httpx.HTTPTransport``, and each response from an
+    attested host carries its connection's report in
+    ``response.extensions["attestation"]``. Other keyword arguments are
+    passed to ``httpx.HTTPTransport``.
+    """
+
+    def __init__(
+        self, atls_policy_per_hostname: dict[str, dict] | None = None, **kwargs
+    ):
+        super().__init__(**kwargs)
+        # Accessing httpcore internals to inject our network backend.
+        # httpx is pinned to >=0.28.1,<0.29 in pyproject.toml to guard against breakage.
+        self._pool._network_backend = AtlsNetworkBackend(
+            atls_policy_per_hostname or {},
+            default_backend=self._pool._network_backend,
         )
-        return AtlsNetworkStream(conn)
+
+    def handle_request(self, request):
+        return _attach_attestation(super().handle_request(request))
+
+
+class AsyncHttpxTransport(httpx.AsyncHTTPTransport):
+    """Async counterpart of ``HttpxTransport``, for ``httpx.AsyncClient``."""
+
+    def __init__(
+        self, atls_policy_per_hostname: dict[str, dict] | None = None, **kwargs
+    ):
+        super().__init__(**kwargs)
+        self._pool._network_backend = AsyncAtlsNetworkBackend(
+            atls_policy_per_hostname or {},
+            default_backend=self._pool._network_backend,
+        )
+
+    async def handle_async_request(self, request):
+        return _attach_attestation(await super().handle_async_request(request))
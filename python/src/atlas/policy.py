@@ -2,14 +2,41 @@
 Policy helpers for building aTLS attestation policies.
 
 Policies are JSON-serializable dicts that map directly to the Rust core's
-Policy enum. They configure what verification checks to perform during
-attestation.
+Policy enum, or the typed ``Policy`` / ``DstackTdxPolicy`` /
+``ExpectedBootchain`` classes, which validate on construction. They configure
+what verification checks to perform during attestation.
 """
 
 import json
-from typing import Optional
-
-from atlas._atlas import merge_with_default_app_compose_py
+from typing import Optional, Union
+
+from atlas._atlas import (
+    DstackTdxPolicy,
+    ExpectedBootchain,
+    Policy,
+    compose_hash_py,
+    expected_compose_event_py,
+    merge_with_default_app_compose_py,
+)
+
+__all__ = [
+    "DstackTdxPolicy",
+    "ExpectedBootchain",
+    "Policy",
+    "compose_hash",
+    "dev_policy",
+    "dstack_tdx_policy",
+    "expected_compose_event",
+    "merge_with_default_app_compose",
+    "policy_to_json",
+]
+
+
+def policy_to_json(policy: Union[dict, Policy, DstackTdxPolicy]) -> str:
+    """Serialize a policy dict or typed policy to the JSON ``atls_connect`` expects."""
+    if isinstance(policy, (Policy, DstackTdxPolicy)):
+        return policy.to_json()
+    return json.dumps(policy)
 
 
 def merge_with_default_app_compose(user_compose: dict) -> dict:
@@ -29,6 +56,44 @@ def merge_with_default_app_compose(user_compose: dict) -> dict:
     return json.loads(result_json)
 
 
+def compose_hash(app_compose: Union[dict, str]) -> str:
+    """Compute the compose hash verification expects for ``app_compose``.
+
+    This is the ``compose-hash`` event payload a dstack CVM deployed with this
+    app_compose records, so deployment tooling can predict it before deploying.
+    Pass the app_compose exactly as it goes into the policy (merge defaults
+    first with ``merge_with_default_app_compose`` if the policy does).
+
+    Args:
+        app_compose: The app_compose dict, or its JSON string.
+
+    Returns:
+        SHA-256 of the app_compose as lowercase hex.
+    """
+    return compose_hash_py(_compose_json(app_compose))
+
+
+def expected_compose_event(app_compose: Union[dict, str]) -> dict:
+    """Return the ``compose-hash`` event log entry for ``app_compose``.
+
+    Args:
+        app_compose: The app_compose dict, or its JSON string, as for
+            ``compose_hash``.
+
+    Returns:
+        Dict with ``imr``, ``event_type``, ``digest`` (the SHA-384 extended
+        into RTMR3), ``event`` (``"compose-hash"``) and ``event_payload``
+        (the compose hash), as the entry appears in the CVM's event log.
+    """
+    return expected_compose_event_py(_compose_json(app_compose))
+
+
+def _compose_json(app_compose: Union[dict, str]) -> str:
+    if isinstance(app_compose, str):
+        return app_compose
+    return json.dumps(app_compose)
+
+
 def dstack_tdx_policy(
     app_compose: Optional[dict] = None,
     expected_bootchain: Optional[dict] = None,
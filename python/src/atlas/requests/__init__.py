@@ -0,0 +1,7 @@
+"""
+requests adapter with aTLS verification.
+"""
+
+from .adapter import AtlasHTTPAdapter
+
+__all__ = ["AtlasHTTPAdapter"]
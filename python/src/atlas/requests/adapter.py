@@ -0,0 +1,148 @@
+"""
+requests transport adapter that routes aTLS connections through Rust.
+
+For hostnames with an aTLS policy, urllib3 connections are established
+through the Rust core (TCP + TLS + EKM binding + attestation) instead of a
+Python socket wrapped in ``ssl``. For other hostnames, the adapter behaves
+like the default ``requests`` adapter (standard TLS).
+
+urllib3 handles all HTTP/1.1 framing over the attested stream.
+"""
+
+from requests.adapters import HTTPAdapter
+from requests.utils import select_proxy
+from urllib3.connection import HTTPSConnection
+from urllib3.connectionpool import HTTPSConnectionPool
+from urllib3.poolmanager import PoolManager
+from urllib3.util import parse_url
+
+from .._atlas import atls_connect
+from .._socket import AtlsSocket
+from ..policy import policy_to_json
+from ..utils import _get_default_logger
+from ..verifiers.errors import AtlsVerificationError
+
+logger = _get_default_logger()
+
+
+class AtlsHTTPSConnection(HTTPSConnection):
+    """urllib3 HTTPS connection whose TLS session is owned by Rust."""
+
+    def __init__(self, *args, atls_policy, **kwargs):
+        super().__init__(*args, **kwargs)
+        self._atls_policy = atls_policy
+        self._atls_conn = None
+
+    def connect(self):
+        host, port = self.host, self.port
+        logger.debug("aTLS connecting to %s:%s", host, port)
+
+        try:
+            conn = atls_connect(host, port, host, policy_to_json(self._atls_policy))
+        except Exception as e:
+            raise AtlsVerificationError(
+                f"aTLS connection to {host}:{port} failed: {e}",
+                code=getattr(e, "code", None),
+            ) from e
+
+        logger.debug(
+            "aTLS connected to %s:%s, attestation: %s", host, port, conn.attestation
+        )
+        self._atls_conn = conn
+        self.sock = AtlsSocket(conn)
+        # The peer was verified by attestation rather than by a CA chain.
+        self.is_verified = True
+
+    @property
+    def is_connected(self):
+        # urllib3 polls the socket's file descriptor, which Rust owns; a
+        # connection dropped while idle surfaces on the next request instead.
+        return self.sock is not None and not self.sock.closed
+
+    @property
+    def attestation(self):
+        """Attestation report of the current connection, or ``None``."""
+        return self._atls_conn.attestation if self._atls_conn else None
+
+
+class AtlsHTTPSConnectionPool(HTTPSConnectionPool):
+    """HTTPS pool of attested connections to one host."""
+
+    ConnectionCls = AtlsHTTPSConnection
+
+    def __init__(self, host, port=None, *, atls_policy, **kwargs):
+        super().__init__(host, port, **kwargs)
+        self.conn_kw["atls_policy"] = atls_policy
+
+
+class AtlsPoolManager(PoolManager):
+    """PoolManager that opens attested pools for hosts with an aTLS policy."""
+
+    def __init__(self, *args, atls_policy_per_hostname, **kwargs):
+        super().__init__(*args, **kwargs)
+        self._policies = atls_policy_per_hostname
+
+    def _new_pool(self, scheme, host, port, request_context=None):
+        if scheme != "https" or host not in self._policies:
+            return super()._new_pool(scheme, host, port, request_context)
+
+        if request_context is None:
+            request_context = self.connection_pool_kw.copy()
+        for key in ("scheme", "host", "port"):
+            request_context.pop(key, None)
+        return AtlsHTTPSConnectionPool(
+            host, port, atls_policy=self._policies[host], **request_context
+        )
+
+
+class AtlasHTTPAdapter(HTTPAdapter):
+    """requests adapter with aTLS verification.
+
+    Connections to hostnames in ``atls_policy_per_hostname`` are routed through
+    Rust aTLS (TLS + EKM binding + attestation). Other hostnames use standard
+    HTTPS. Responses from attested hosts carry the report in
+    ``response.attestation`` (``None`` for other hosts).
+
+    Mount it on a session::
+
+        session = requests.Session()
+        session.mount("https://", AtlasHTTPAdapter(atls_policy_per_hostname={...}))
+
+    aTLS hosts cannot be reached through an HTTP proxy: such requests raise
+    ``AtlsVerificationError`` rather than falling back to unattested TLS.
+    """
+
+    __attrs__ = HTTPAdapter.__attrs__ + ["_atls_policies"]
+
+    def __init__(
+        self, atls_policy_per_hostname: dict[str, dict] | None = None, **kwargs
+    ):
+        self._atls_policies = dict(atls_policy_per_hostname or {})
+        super().__init__(**kwargs)
+
+    def init_poolmanager(self, connections, maxsize, block=False, **pool_kwargs):
+        self._pool_connections = connections
+        self._pool_maxsize = maxsize
+        self._pool_block = block
+        self.poolmanager = AtlsPoolManager(
+            num_pools=connections,
+            maxsize=maxsize,
+            block=block,
+            atls_policy_per_hostname=self._atls_policies,
+            **pool_kwargs,
+        )
+
+    def get_connection_with_tls_context(self, request, verify, proxies=None, cert=None):
+        host = parse_url(request.url).host
+        if host in self._atls_policies and select_proxy(request.url, proxies):
+            raise AtlsVerificationError(
+                f"aTLS connection to {host} cannot go through an HTTP proxy"
+            )
+        return super().get_connection_with_tls_context(request, verify, proxies, cert)
+
+    def build_response(self, req, resp):
+        response = super().build_response(req, resp)
+        # urllib3 keeps the connection on the response until the body is read.
+        connection = getattr(resp, "connection", None)
+        response.attestation = getattr(connection, "attestation", None)
+        return response
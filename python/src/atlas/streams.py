@@ -0,0 +1,189 @@
+"""
+asyncio streams over attested connections.
+
+``open_connection()`` mirrors ``asyncio.open_connection()``: it returns a
+standard ``(StreamReader, StreamWriter)`` pair, plus the attestation report,
+so protocol libraries built on asyncio streams work unchanged over aTLS.
+
+The Rust connection is blocking, so reads and writes run in the event loop's
+default executor; one read is in flight at a time and writes are sent in order.
+Reads wake up every ``READ_POLL_INTERVAL`` seconds so that closing the
+transport never leaves an executor thread blocked on a quiet connection.
+"""
+
+import asyncio
+
+from ._atlas import atls_connect
+from .policy import policy_to_json
+from .utils import _get_default_logger
+
+logger = _get_default_logger()
+
+READ_CHUNK_SIZE = 64 * 1024
+READ_POLL_INTERVAL = 1.0
+WRITE_HIGH_WATER = 64 * 1024
+
+
+class AtlsTransport(asyncio.Transport):
+    """asyncio transport driving a Rust AtlsConnection from executor threads."""
+
+    def __init__(self, loop, conn, protocol):
+        super().__init__(
+            extra={"attestation": conn.attestation, "atls_connection": conn}
+        )
+        self._loop = loop
+        self._conn = conn
+        self._protocol = protocol
+        self._closing = False
+        self._reading = asyncio.Event()
+        self._reading.set()
+        self._pending = []
+        self._pending_size = 0
+        self._writes_ready = asyncio.Event()
+        self._write_paused = False
+
+        protocol.connection_made(self)
+        self._reader_task = loop.create_task(self._read_loop())
+        self._writer_task = loop.create_task(self._write_loop())
+
+    async def _read_loop(self):
+        try:
+            while True:
+                await self._reading.wait()
+                try:
+                    data = await self._loop.run_in_executor(
+                        None, self._conn.read, READ_CHUNK_SIZE, READ_POLL_INTERVAL
+                    )
+                except TimeoutError:
+                    if self._closing:
+                        return
+                    continue
+                if self._closing:
+                    return
+                if not data:
+                    self._protocol.eof_received()
+                    return
+                self._protocol.data_received(bytes(data))
+        except asyncio.CancelledError:
+            raise
+        except Exception as e:
+            self._fail(e)
+
+    async def _write_loop(self):
+        try:
+            while True:
+                await self._writes_ready.wait()
+                if not self._pending:
+                    if self._closing:
+                        break
+                    self._writes_ready.clear()
+                    continue
+                data = b"".join(self._pending)
+                self._pending.clear()
+                await self._loop.run_in_executor(None, self._conn.write, data)
+                self._pending_size -= len(data)
+                if self._write_paused and self._pending_size <= WRITE_HIGH_WATER // 4:
+                    self._write_paused = False
+                    self._protocol.resume_writing()
+        except asyncio.CancelledError:
+            raise
+        except Exception as e:
+            self._fail(e)
+            return
+        await self._loop.run_in_executor(None, self._conn.close)
+        self._reader_task.cancel()
+        self._protocol.connection_lost(None)
+
+    def _fail(self, exc):
+        if self._closing:
+            return
+        self._closing = True
+        self._reader_task.cancel()
+        self._writer_task.cancel()
+        self._conn.close()
+        self._protocol.connection_lost(exc)
+
+    def write(self, data):
+        if self._closing:
+            raise RuntimeError("write on a closing aTLS transport")
+        if not data:
+            return
+        self._pending.append(bytes(data))
+        self._pending_size += len(data)
+        self._writes_ready.set()
+        if not self._write_paused and self._pending_size > WRITE_HIGH_WATER:
+            self._write_paused = True
+            self._protocol.pause_writing()
+
+    def can_write_eof(self):
+        return False  # TLS close_notify ends both directions; no half-close.
+
+    def get_write_buffer_size(self):
+        return self._pending_size
+
+    def is_closing(self):
+        return self._closing
+
+    def close(self):
+        """Send any buffered data, then close the connection."""
+        if self._closing:
+            return
+        self._closing = True
+        self._writes_ready.set()
+
+    def abort(self):
+        if self._closing and self._writer_task.done():
+            return
+        self._closing = True
+        self._reader_task.cancel()
+        self._writer_task.cancel()
+        self._conn.close()
+        self._loop.call_soon(self._protocol.connection_lost, None)
+
+    def pause_reading(self):
+        self._reading.clear()
+
+    def resume_reading(self):
+        self._reading.set()
+
+    def is_reading(self):
+        return self._reading.is_set()
+
+
+def wrap_connection(conn, *, limit=2**16):
+    """Wrap an open AtlsConnection as an asyncio ``(StreamReader, StreamWriter)`` pair.
+
+    Must be called from a running event loop. The writer's
+    ``get_extra_info("attestation")`` returns the attestation report.
+    """
+    loop = asyncio.get_running_loop()
+    reader = asyncio.StreamReader(limit=limit, loop=loop)
+    protocol = asyncio.StreamReaderProtocol(reader, loop=loop)
+    transport = AtlsTransport(loop, conn, protocol)
+    writer = asyncio.StreamWriter(transport, protocol, reader, loop)
+    return reader, writer
+
+
+async def open_connection(host, port, policy, *, server_name=None, limit=2**16):
+    """Open an attested connection, like ``asyncio.open_connection()``.
+
+    Args:
+        host: Target hostname or IP.
+        port: Target port.
+        policy: Policy dict, ``Policy`` or ``DstackTdxPolicy``.
+        server_name: TLS server name (defaults to ``host``).
+        limit: Buffer limit of the ``StreamReader``.
+
+    Returns:
+        ``(reader, writer, attestation)``.
+
+    Raises:
+        AtlasError: If the aTLS handshake or attestation fails.
+    """
+    policy_json = policy_to_json(policy)
+    logger.debug("aTLS connecting to %s:%s", host, port)
+    conn = await asyncio.get_running_loop().run_in_executor(
+        None, atls_connect, host, port, server_name or host, policy_json
+    )
+    reader, writer = wrap_connection(conn, limit=limit)
+    return reader, writer, writer.get_extra_info("attestation")
@@ -1,4 +1,12 @@
 class AtlsVerificationError(Exception):
-    """Exception raised when aTLS verification fails."""
+    """Exception raised when aTLS verification fails.
 
-    pass
+    Attributes:
+        code: Stable machine-readable error code from the Rust core
+            (e.g. ``"TCB_STATUS_REJECTED"``, ``"MRTD_MISMATCH"``), or ``None``
+            if the failure did not originate from attestation verification.
+    """
+
+    def __init__(self, message: str, code: str | None = None):
+        super().__init__(message)
+        self.code = code
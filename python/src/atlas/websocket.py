@@ -0,0 +1,340 @@
+"""
+WebSocket client over attested connections.
+
+``websocket_connect()`` opens an attested connection, performs the HTTP/1.1
+Upgrade handshake on it and returns an ``AtlsWebSocket`` with blocking
+``send()``/``recv()``/``close()``. Framing follows RFC 6455: client frames
+are masked, fragmented messages are reassembled, pings are answered
+automatically. Extensions (e.g. permessage-deflate) are not negotiated.
+"""
+
+import base64
+import hashlib
+import os
+import struct
+import threading
+
+from ._atlas import atls_connect
+from .policy import policy_to_json
+from .utils import _get_default_logger
+
+logger = _get_default_logger()
+
+_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+READ_CHUNK_SIZE = 64 * 1024
+MAX_HANDSHAKE_SIZE = 64 * 1024
+DEFAULT_MAX_MESSAGE_SIZE = 16 * 1024 * 1024
+
+OP_CONTINUATION = 0x0
+OP_TEXT = 0x1
+OP_BINARY = 0x2
+OP_CLOSE = 0x8
+OP_PING = 0x9
+OP_PONG = 0xA
+
+
+class WebSocketError(Exception):
+    """The server broke the WebSocket protocol or refused the upgrade."""
+
+
+class ConnectionClosed(Exception):
+    """The WebSocket is closed. ``code`` and ``reason`` come from the close frame."""
+
+    def __init__(self, code=None, reason=""):
+        self.code = code
+        self.reason = reason
+        super().__init__(f"WebSocket closed (code={code}, reason={reason!r})")
+
+
+def _accept_key(key):
+    digest = hashlib.sha1((key + _GUID).encode("ascii")).digest()
+    return base64.b64encode(digest).decode("ascii")
+
+
+def _encode_frame(opcode, payload, fin=True):
+    """Build a masked client frame."""
+    header = bytearray([(0x80 if fin else 0) | opcode])
+    length = len(payload)
+    if length < 126:
+        header.append(0x80 | length)
+    elif length < 1 << 16:
+        header.append(0x80 | 126)
+        header += struct.pack("!H", length)
+    else:
+        header.append(0x80 | 127)
+        header += struct.pack("!Q", length)
+    mask = os.urandom(4)
+    masked = bytes(b ^ mask[i % 4] for i, b in enumerate(payload))
+    return bytes(header) + mask + masked
+
+
+class AtlsWebSocket:
+    """A client WebSocket over an AtlsConnection.
+
+    ``send()`` may be called from another thread than ``recv()``; two threads
+    must not ``recv()`` at the same time.
+    """
+
+    def __init__(
+        self,
+        conn,
+        *,
+        buffered=b"",
+        subprotocol=None,
+        max_message_size=DEFAULT_MAX_MESSAGE_SIZE,
+    ):
+        self._conn = conn
+        self._buffer = bytearray(buffered)
+        self._send_lock = threading.Lock()
+        self._close_sent = False
+        self._closed = None
+        self.subprotocol = subprotocol
+        self.max_message_size = max_message_size
+
+    @property
+    def attestation(self):
+        """Attestation report of the underlying connection."""
+        return self._conn.attestation
+
+    def _read_exact(self, size):
+        while len(self._buffer) < size:
+            data = self._conn.read(READ_CHUNK_SIZE)
+            if not data:
+                raise ConnectionClosed(1006, "connection lost")
+            self._buffer += data
+        chunk = bytes(self._buffer[:size])
+        del self._buffer[:size]
+        return chunk
+
+    def _read_frame(self):
+        first, second = self._read_exact(2)
+        fin = bool(first & 0x80)
+        opcode = first & 0x0F
+        if first & 0x70:
+            raise WebSocketError("reserved bits set without a negotiated extension")
+        if second & 0x80:
+            raise WebSocketError("server frames must not be masked")
+        length = second & 0x7F
+        if length == 126:
+            (length,) = struct.unpack("!H", self._read_exact(2))
+        elif length == 127:
+            (length,) = struct.unpack("!Q", self._read_exact(8))
+        if opcode >= OP_CLOSE and (length > 125 or not fin):
+            raise WebSocketError("invalid control frame")
+        if length > self.max_message_size:
+            self._fail(1009, "message too big")
+        return fin, opcode, self._read_exact(length)
+
+    def _send_frame(self, opcode, payload, fin=True):
+        with self._send_lock:
+            if self._close_sent:
+                raise ConnectionClosed(*(self._closed or (None, "")))
+            if opcode == OP_CLOSE:
+                self._close_sent = True
+            self._conn.write(_encode_frame(opcode, payload, fin))
+
+    def _fail(self, code, reason):
+        """Close with an error status and raise."""
+        logger.debug(f"WebSocket protocol failure: {code} {reason}")
+        try:
+            self.close(code, reason)
+        finally:
+            raise WebSocketError(reason)
+
+    def send(self, message):
+        """Send a text (``str``) or binary (bytes-like) message."""
+        if isinstance(message, str):
+            self._send_frame(OP_TEXT, message.encode("utf-8"))
+        else:
+            self._send_frame(OP_BINARY, bytes(message))
+
+    def ping(self, data=b""):
+        """Send a ping; the matching pong is consumed by ``recv()``."""
+        self._send_frame(OP_PING, bytes(data))
+
+    def recv(self):
+        """Receive the next message: ``str`` for text, ``bytes`` for binary.
+
+        Raises:
+            ConnectionClosed: The server closed the WebSocket (or the
+                connection dropped, code 1006).
+        """
+        if self._closed is not None:
+            raise ConnectionClosed(*self._closed)
+
+        message_opcode = None
+        parts = []
+        size = 0
+        while True:
+            fin, opcode, payload = self._read_frame()
+            if opcode == OP_PING:
+                self._send_frame(OP_PONG, payload)
+                continue
+            if opcode == OP_PONG:
+                continue
+            if opcode == OP_CLOSE:
+                self._on_close_frame(payload)
+                raise ConnectionClosed(*self._closed)
+
+            if opcode == OP_CONTINUATION:
+                if message_opcode is None:
+                    self._fail(1002, "unexpected continuation frame")
+            elif opcode in (OP_TEXT, OP_BINARY):
+                if message_opcode is not None:
+                    self._fail(1002, "expected a continuation frame")
+                message_opcode = opcode
+            else:
+                self._fail(1002, f"unknown opcode {opcode:#x}")
+
+            size += len(payload)
+            if size > self.max_message_size:
+                self._fail(1009, "message too big")
+            parts.append(payload)
+            if fin:
+                break
+
+        data = b"".join(parts)
+        if message_opcode == OP_TEXT:
+            try:
+                return data.decode("utf-8")
+            except UnicodeDecodeError:
+                self._fail(1007, "invalid UTF-8 in text message")
+        return data
+
+    def _on_close_frame(self, payload):
+        if len(payload) >= 2:
+            (code,) = struct.unpack("!H", payload[:2])
+            reason = payload[2:].decode("utf-8", errors="replace")
+        else:
+            code, reason = 1005, ""
+        self._closed = (code, reason)
+        if not self._close_sent:
+            try:
+                self._send_frame(OP_CLOSE, payload[:2])
+            except OSError:
+                pass
+        self._conn.close()
+
+    def close(self, code=1000, reason=""):
+        """Start the closing handshake, wait for the server's close frame
+        (discarding any messages still in flight) and close the connection."""
+        if self._closed is not None:
+            return
+        try:
+            payload = struct.pack("!H", code) + reason.encode("utf-8")
+            self._send_frame(OP_CLOSE, payload)
+            while True:
+                _, opcode, payload = self._read_frame()
+                if opcode == OP_CLOSE:
+                    self._on_close_frame(payload)
+                    return
+        except (ConnectionClosed, WebSocketError, OSError):
+            pass
+        self._closed = self._closed or (code, reason)
+        self._conn.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.close()
+        return False
+
+
+def _handshake(conn, host, port, path, headers, subprotocols):
+    key = base64.b64encode(os.urandom(16)).decode("ascii")
+    host_header = host if port == 443 else f"{host}:{port}"
+    lines = [
+        f"GET {path} HTTP/1.1",
+        f"Host: {host_header}",
+        "Upgrade: websocket",
+        "Connection: Upgrade",
+        f"Sec-WebSocket-Key: {key}",
+        "Sec-WebSocket-Version: 13",
+    ]
+    if subprotocols:
+        lines.append(f"Sec-WebSocket-Protocol: {', '.join(subprotocols)}")
+    for name, value in (headers or {}).items():
+        lines.append(f"{name}: {value}")
+    conn.write(("\r\n".join(lines) + "\r\n\r\n").encode("latin-1"))
+
+    response = bytearray()
+    while b"\r\n\r\n" not in response:
+        if len(response) > MAX_HANDSHAKE_SIZE:
+            raise WebSocketError("handshake response too large")
+        data = conn.read(READ_CHUNK_SIZE)
+        if not data:
+            raise WebSocketError("connection closed during the handshake")
+        response += data
+    head, _, rest = bytes(response).partition(b"\r\n\r\n")
+    status_line, *header_lines = head.decode("latin-1").split("\r\n")
+    parts = status_line.split(" ", 2)
+    if len(parts) < 2 or parts[1] != "101":
+        raise WebSocketError(f"server refused the upgrade: {status_line}")
+
+    response_headers = {}
+    for line in header_lines:
+        name, _, value = line.partition(":")
+        response_headers[name.strip().lower()] = value.strip()
+    if response_headers.get("upgrade", "").lower() != "websocket":
+        raise WebSocketError("missing 'Upgrade: websocket' in handshake response")
+    if response_headers.get("sec-websocket-accept") != _accept_key(key):
+        raise WebSocketError("invalid Sec-WebSocket-Accept in handshake response")
+    subprotocol = response_headers.get("sec-websocket-protocol")
+    if subprotocol is not None and subprotocol not in (subprotocols or ()):
+        raise WebSocketError(f"server selected unrequested subprotocol {subprotocol}")
+    return subprotocol, rest
+
+
+def websocket_connect(
+    host,
+    port,
+    path,
+    policy,
+    *,
+    server_name=None,
+    headers=None,
+    subprotocols=None,
+    max_message_size=DEFAULT_MAX_MESSAGE_SIZE,
+):
+    """
+    Open a WebSocket to an attested server.
+
+    Args:
+        host: Server hostname.
+        port: Server port.
+        path: Request path (with query string), e.g. ``"/v1/realtime"``.
+        policy: Attestation policy (dict, ``Policy`` or ``DstackTdxPolicy``).
+        server_name: SNI server name (defaults to ``host``).
+        headers: Extra handshake headers (e.g. ``Authorization``).
+        subprotocols: Subprotocols to offer; the selected one is
+            ``ws.subprotocol``.
+        max_message_size: Largest message ``recv()`` accepts, in bytes.
+
+    Returns:
+        AtlsWebSocket
+
+    Raises:
+        AtlasError: If the connection or attestation fails.
+        WebSocketError: If the server refuses or botches the upgrade.
+    """
+    conn = atls_connect(host, port, server_name or host, policy_to_json(policy))
+    try:
+        subprotocol, rest = _handshake(conn, host, port, path, headers, subprotocols)
+    except BaseException:
+        conn.close()
+        raise
+    return AtlsWebSocket(
+        conn,
+        buffered=rest,
+        subprotocol=subprotocol,
+        max_message_size=max_message_size,
+    )
+
+
+__all__ = [
+    "AtlsWebSocket",
+    "ConnectionClosed",
+    "WebSocketError",
+    "websocket_connect",
+]
@@ -0,0 +1,84 @@
+//! Collateral cache controls.
+//!
+//! Policies with `cache_collateral=True` share one process-wide cache of Intel
+//! collateral, so a long-running service fetches it once per platform and TTL
+//! rather than on every `atls_connect()`. These functions tune that cache.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use atlas_rs::dstack::policy::DEFAULT_PCCS_URL;
+use atlas_rs::tdx::collateral_cache::{self, CollateralCacheConfig};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::errors::verification_error;
+use crate::runtime;
+
+/// Configure the collateral cache.
+///
+/// Args:
+///     ttl: Seconds an entry is reused before collateral is fetched again
+///         (default 8 hours).
+///     disk_path: JSON file to persist the cache to. Entries already in the
+///         file are loaded now, and new entries are written back, so restarts
+///         don't refetch. `None` keeps the cache in memory only.
+///
+/// Raises:
+///     ValueError: If `ttl` is not a positive number.
+///     AtlasError: If `disk_path` exists but can't be read or parsed.
+#[pyfunction]
+#[pyo3(signature = (*, ttl=None, disk_path=None))]
+fn configure_collateral_cache(
+    py: Python<'_>,
+    ttl: Option<f64>,
+    disk_path: Option<PathBuf>,
+) -> PyResult<()> {
+    let ttl = match ttl {
+        None => collateral_cache::DEFAULT_TTL,
+        Some(s) if s.is_finite() && s > 0.0 => Duration::from_secs_f64(s),
+        Some(s) => {
+            return Err(PyValueError::new_err(format!(
+                "ttl must be a positive number of seconds, got {s}"
+            )))
+        }
+    };
+    py.allow_threads(|| collateral_cache::configure(CollateralCacheConfig { ttl, disk_path }))
+        .map_err(verification_error)
+}
+
+/// Fetch collateral for a TDX platform now and cache it.
+///
+/// Args:
+///     fmspc: The platform's FMSPC in hex.
+///     ca: CA of the platform's PCK certificate, `"processor"` or `"platform"`.
+///     pccs_url: PCCS to fetch from (default: the policy default). Must match
+///         the policy's `pccs_url` for the entry to be used.
+///
+/// Raises:
+///     CollateralError: If the PCCS request fails.
+#[pyfunction]
+#[pyo3(signature = (fmspc, ca="processor", *, pccs_url=None))]
+fn prefetch_collateral(
+    py: Python<'_>,
+    fmspc: &str,
+    ca: &str,
+    pccs_url: Option<&str>,
+) -> PyResult<()> {
+    let pccs_url = pccs_url.unwrap_or(DEFAULT_PCCS_URL);
+    py.allow_threads(|| runtime().block_on(collateral_cache::prefetch(pccs_url, fmspc, ca)))
+        .map_err(verification_error)
+}
+
+/// Drop every cached collateral entry held in memory.
+#[pyfunction]
+fn clear_collateral_cache() {
+    collateral_cache::clear();
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(configure_collateral_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(prefetch_collateral, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_collateral_cache, m)?)?;
+    Ok(())
+}
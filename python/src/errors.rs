@@ -0,0 +1,150 @@
+//! Python exception hierarchy.
+//!
+//! Every error raised by the bindings is an `AtlasError` (itself an `IOError`,
+//! which is what the bindings raised before) with a machine-readable `code`
+//! attribute, the core's `ErrorCode` string where there is one:
+//!
+//! ```text
+//! AtlasError
+//! ├── AttestationRejected
+//! │   ├── TcbStatusNotAllowed
+//! │   └── MeasurementMismatch
+//! ├── CollateralError
+//! ├── TlsError
+//! └── AtlasTimeout            (also a TimeoutError)
+//! ```
+
+use atlas_rs::{AtlsVerificationError, ErrorCode};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyIOError, PyTimeoutError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyTuple, PyType};
+
+create_exception!(
+    _atlas,
+    AtlasError,
+    PyIOError,
+    "Base class of aTLS errors. `code` holds a machine-readable error code."
+);
+create_exception!(
+    _atlas,
+    AttestationRejected,
+    AtlasError,
+    "The peer's attestation evidence was invalid or did not satisfy the policy."
+);
+create_exception!(
+    _atlas,
+    TcbStatusNotAllowed,
+    AttestationRejected,
+    "The platform TCB status is not in `allowed_tcb_status` (or its grace period expired)."
+);
+create_exception!(
+    _atlas,
+    MeasurementMismatch,
+    AttestationRejected,
+    "A measurement (MRTD, RTMR, app compose or OS image hash) did not match the policy."
+);
+create_exception!(
+    _atlas,
+    CollateralError,
+    AtlasError,
+    "Intel collateral could not be fetched, was expired, or was invalid."
+);
+create_exception!(_atlas, TlsError, AtlasError, "The TLS handshake failed.");
+
+/// `AtlasTimeout` derives from both `AtlasError` and `TimeoutError`, which
+/// `create_exception!` can't express, so it is built with `type()` once.
+static ATLAS_TIMEOUT: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+fn atlas_timeout_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    ATLAS_TIMEOUT
+        .get_or_try_init(py, || {
+            let bases = PyTuple::new(
+                py,
+                [py.get_type::<AtlasError>(), py.get_type::<PyTimeoutError>()],
+            )?;
+            let namespace = PyDict::new(py);
+            namespace.set_item("__module__", "atlas._atlas")?;
+            namespace.set_item(
+                "__doc__",
+                "A read on an AtlsConnection timed out. The connection stays usable.",
+            )?;
+            let ty = py
+                .get_type::<PyType>()
+                .call1(("AtlasTimeout", bases, namespace))?
+                .downcast_into::<PyType>()?;
+            Ok::<_, PyErr>(ty.unbind())
+        })
+        .map(|ty| ty.bind(py))
+}
+
+fn with_code(err: PyErr, code: &str) -> PyErr {
+    Python::with_gil(|py| {
+        let _ = err.value(py).setattr("code", code);
+    });
+    err
+}
+
+/// Convert a core verification error into the matching `AtlasError` subclass.
+pub fn verification_error(e: AtlsVerificationError) -> PyErr {
+    let message = format!("atls handshake failed: {e}");
+    let code = e.code();
+    let err = match code {
+        ErrorCode::TcbStatusRejected | ErrorCode::GracePeriodExpired => {
+            TcbStatusNotAllowed::new_err(message)
+        }
+        ErrorCode::MrtdMismatch
+        | ErrorCode::BootchainMismatch
+        | ErrorCode::RtmrReplayMismatch
+        | ErrorCode::AppComposeMismatch
+        | ErrorCode::OsImageMismatch => MeasurementMismatch::new_err(message),
+        ErrorCode::QuoteInvalid
+        | ErrorCode::EventLogInvalid
+        | ErrorCode::TeeTypeMismatch
+        | ErrorCode::CertificateNotInEventLog
+        | ErrorCode::ReportDataMismatch => AttestationRejected::new_err(message),
+        ErrorCode::CollateralUnavailable
+        | ErrorCode::CollateralExpired
+        | ErrorCode::TcbInfoInvalid => CollateralError::new_err(message),
+        ErrorCode::TlsHandshakeFailed
+        | ErrorCode::InvalidServerName
+        | ErrorCode::MissingCertificate => TlsError::new_err(message),
+        _ => AtlasError::new_err(message),
+    };
+    with_code(err, code.as_str())
+}
+
+/// `AtlasError` for a failed read/write on an established connection.
+pub fn io_error(message: String) -> PyErr {
+    with_code(AtlasError::new_err(message), ErrorCode::Io.as_str())
+}
+
+/// `MeasurementMismatch` for a re-attestation that reported a different measurement.
+pub fn measurement_changed_error(message: String) -> PyErr {
+    with_code(
+        MeasurementMismatch::new_err(message),
+        ErrorCode::MrtdMismatch.as_str(),
+    )
+}
+
+/// `AtlasTimeout` for a read that exceeded its timeout.
+pub fn timeout_error(message: String) -> PyErr {
+    let err = Python::with_gil(|py| match atlas_timeout_type(py) {
+        Ok(ty) => PyErr::from_type(ty.clone(), message),
+        Err(e) => e,
+    });
+    with_code(err, "TIMEOUT")
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("AtlasError", py.get_type::<AtlasError>())?;
+    m.add("AttestationRejected", py.get_type::<AttestationRejected>())?;
+    m.add("TcbStatusNotAllowed", py.get_type::<TcbStatusNotAllowed>())?;
+    m.add("MeasurementMismatch", py.get_type::<MeasurementMismatch>())?;
+    m.add("CollateralError", py.get_type::<CollateralError>())?;
+    m.add("TlsError", py.get_type::<TlsError>())?;
+    m.add("AtlasTimeout", atlas_timeout_type(py)?)?;
+    Ok(())
+}
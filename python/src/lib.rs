@@ -1,43 +1,163 @@
 use atlas_rs::{
-    atls_connect as core_atls_connect, dstack::merge_with_default_app_compose, Policy, Report,
+    atls_connect as core_atls_connect,
+    dstack::compose_hash::{get_compose_hash, get_compose_hash_event},
+    dstack::merge_with_default_app_compose,
+    session_binding, AsyncByteStream, AtlsVerifier, PhaseTimings, Policy, Report,
     TlsStream as CoreTlsStream,
 };
 use once_cell::sync::Lazy;
-use pyo3::exceptions::{PyConnectionError, PyIOError, PyValueError};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyConnectionError, PyEOFError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyBytes, PyDict};
 use rustls::crypto::aws_lc_rs::default_provider;
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use std::future::Future;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 
-// Lazily initialized tokio runtime shared across all connections.
-static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
-    tokio::runtime::Builder::new_multi_thread()
+mod collateral;
+mod errors;
+mod local;
+mod offline;
+mod policy;
+mod proxy;
+mod server;
+
+use errors::{io_error, measurement_changed_error, timeout_error, verification_error};
+use policy::{PolicyArg, PyDstackTdxPolicy, PyExpectedBootchain, PyPolicy};
+
+/// The tokio runtime shared across all connections, with the process it was
+/// built in. Set once per process and never freed.
+static RUNTIME: AtomicPtr<(u32, tokio::runtime::Runtime)> = AtomicPtr::new(std::ptr::null_mut());
+/// Serializes building the runtime.
+static RUNTIME_INIT: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// This process's tokio runtime, built on first use.
+///
+/// After `os.fork()` the child inherits the runtime but not its worker
+/// threads, so anything run on it would hang. A forked child therefore gets
+/// a fresh runtime; the inherited one is leaked, since shutting it down would
+/// wait for threads that don't exist.
+fn runtime() -> &'static tokio::runtime::Runtime {
+    let pid = std::process::id();
+    let current = |ptr: *mut (u32, tokio::runtime::Runtime)| {
+        // SAFETY: non-null pointers come from `Box::into_raw` below and are never freed.
+        unsafe { ptr.as_ref() }.filter(|(owner, _)| *owner == pid)
+    };
+    if let Some((_, runtime)) = current(RUNTIME.load(Ordering::Acquire)) {
+        return runtime;
+    }
+
+    let _init = lock(&RUNTIME_INIT);
+    if let Some((_, runtime)) = current(RUNTIME.load(Ordering::Acquire)) {
+        return runtime;
+    }
+    let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
-        .expect("failed to create tokio runtime")
-});
+        .expect("failed to create tokio runtime");
+    let leaked = Box::into_raw(Box::new((pid, runtime)));
+    RUNTIME.store(leaked, Ordering::Release);
+    // SAFETY: `leaked` was just allocated and is never freed.
+    unsafe { &(*leaked).1 }
+}
 
 // Initialize the crypto provider once.
 static CRYPTO_INIT: Lazy<()> = Lazy::new(|| {
     let _ = default_provider().install_default();
 });
 
-type TlsStream = CoreTlsStream<TcpStream>;
+/// TLS over any transport: TCP, a Unix socket or vsock.
+type TlsStream = CoreTlsStream<Box<dyn AsyncByteStream>>;
+
+/// The stream behind a connection: a client [`TlsStream`], or the server side
+/// of one accepted by `atls_accept()`.
+type Stream = Box<dyn AsyncByteStream>;
+
+/// What a connection's attestation is bound to, kept for `reattest()`.
+struct Session {
+    server_name: String,
+    policy: Policy,
+    peer_cert: Vec<u8>,
+    session_ekm: Vec<u8>,
+}
+
+/// Both halves of a split TLS stream, so a verifier can exchange evidence
+/// over it again.
+struct Rejoined<'a> {
+    reader: &'a mut ReadHalf<Stream>,
+    writer: &'a mut WriteHalf<Stream>,
+}
+
+impl AsyncRead for Rejoined<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.reader).poll_read(cx, buf)
+    }
+}
 
-struct ConnectionState {
-    reader: Arc<Mutex<ReadHalf<TlsStream>>>,
-    writer: Arc<Mutex<WriteHalf<TlsStream>>>,
-    attestation: Attestation,
+impl AsyncWrite for Rejoined<'_> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.writer).poll_shutdown(cx)
+    }
+}
+
+/// How often blocking reads and writes wake up to run Python signal handlers.
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run `fut` on the runtime with the GIL released, waking every
+/// `SIGNAL_CHECK_INTERVAL` to run Python signal handlers so Ctrl-C interrupts
+/// a stalled call. If a handler raises (e.g. `KeyboardInterrupt`), `fut` is
+/// dropped and that exception returned.
+fn block_on_interruptible<T, F>(py: Python<'_>, fut: F) -> PyResult<T>
+where
+    T: Send,
+    F: Future<Output = PyResult<T>> + Send,
+{
+    let mut fut = std::pin::pin!(fut);
+    loop {
+        let polled = py.allow_threads(|| {
+            runtime().block_on(tokio::time::timeout(SIGNAL_CHECK_INTERVAL, fut.as_mut()))
+        });
+        match polled {
+            Ok(result) => return result,
+            Err(_) => py.check_signals()?,
+        }
+    }
 }
 
-static CONNECTIONS: Lazy<Mutex<HashMap<u64, ConnectionState>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
-static NEXT_CONN_ID: AtomicU64 = AtomicU64::new(1);
+/// Parse a Python timeout in seconds (`None` = no timeout).
+fn parse_timeout(seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    match seconds {
+        None => Ok(None),
+        Some(s) if s.is_finite() && s > 0.0 => Ok(Some(Duration::from_secs_f64(s))),
+        Some(s) => Err(PyValueError::new_err(format!(
+            "timeout must be a positive number of seconds or None, got {s}"
+        ))),
+    }
+}
 
 #[derive(Clone)]
 struct Attestation {
@@ -46,37 +166,113 @@ struct Attestation {
     measurement: Option<String>,
     tcb_status: String,
     advisory_ids: Vec<String>,
+    timings: PhaseTimings,
+    quote: Vec<u8>,
+    event_log: Option<String>,
 }
 
 impl From<Report> for Attestation {
     fn from(report: Report) -> Self {
         match report {
-            Report::Tdx(verified) => {
-                let measurement = verified
-                    .report
-                    .as_td10()
-                    .map(|td| hex::encode(td.mr_td));
+            Report::Tdx(tdx) => {
+                let measurement = tdx.report.as_td10().map(|td| hex::encode(td.mr_td));
                 Self {
                     trusted: true,
                     tee_type: "tdx".to_string(),
                     measurement,
-                    tcb_status: verified.status.clone(),
-                    advisory_ids: verified.advisory_ids.clone(),
+                    tcb_status: tdx.status.clone(),
+                    advisory_ids: tdx.advisory_ids.clone(),
+                    timings: tdx.timings,
+                    quote: tdx.quote,
+                    event_log: tdx.event_log,
                 }
             }
         }
     }
 }
 
+/// `AtlsConnection.attestation`: a dict of the verified fields, plus the raw
+/// evidence they were verified from.
+#[pyclass(frozen, extends = PyDict, name = "Attestation", module = "atlas._atlas")]
+struct PyAttestation {
+    quote: Vec<u8>,
+    event_log: Option<String>,
+}
+
+#[pymethods]
+impl PyAttestation {
+    /// The TDX quote exactly as the server sent it, for archiving or for
+    /// checking with an external verifier.
+    fn raw_quote<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.quote)
+    }
+
+    /// The event log exactly as the server sent it (UTF-8 JSON), or `None`
+    /// if the verifier did not fetch one.
+    fn raw_event_log<'py>(&self, py: Python<'py>) -> Option<Bound<'py, PyBytes>> {
+        self.event_log
+            .as_ref()
+            .map(|log| PyBytes::new(py, log.as_bytes()))
+    }
+}
+
 impl Attestation {
     fn to_py_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let dict = PyDict::new(py);
+        let attestation = Bound::new(
+            py,
+            PyAttestation {
+                quote: self.quote.clone(),
+                event_log: self.event_log.clone(),
+            },
+        )?;
+        let dict = attestation.as_super();
         dict.set_item("trusted", self.trusted)?;
         dict.set_item("tee_type", &self.tee_type)?;
         dict.set_item("measurement", &self.measurement)?;
         dict.set_item("tcb_status", &self.tcb_status)?;
         dict.set_item("advisory_ids", &self.advisory_ids)?;
-        Ok(dict.into_any().unbind())
+
+        let ms = self.timings.as_millis();
+        let timings = PyDict::new(py);
+        timings.set_item("tcp_connect_ms", ms.tcp_connect_ms)?;
+        timings.set_item("tls_handshake_ms", ms.tls_handshake_ms)?;
+        timings.set_item("evidence_exchange_ms", ms.evidence_exchange_ms)?;
+        timings.set_item("collateral_fetch_ms", ms.collateral_fetch_ms)?;
+        timings.set_item("verification_ms", ms.verification_ms)?;
+        timings.set_item("total_ms", ms.total_ms)?;
+        dict.set_item("timings", timings)?;
+        Ok(attestation.into_any().unbind())
+    }
+}
+
+/// Bytes `read_exact()`/`read_until()` pull from the stream in one go.
+const READ_AHEAD: usize = 16 * 1024;
+
+/// Default `max_bytes` of `read_until()`.
+const DEFAULT_READ_UNTIL_LIMIT: usize = 64 * 1024;
+
+/// The read half of a connection, with data read ahead of what the caller
+/// asked for. Every read is served from `buffered` first.
+struct Reader {
+    half: ReadHalf<Stream>,
+    buffered: Vec<u8>,
+}
+
+impl Reader {
+    /// Append up to `READ_AHEAD` more bytes to `buffered`; returns 0 on EOF.
+    /// Cancel-safe: an interrupted fill leaves `buffered` unchanged.
+    async fn fill(&mut self) -> io::Result<usize> {
+        self.buffered.reserve(READ_AHEAD);
+        (&mut self.half)
+            .take(READ_AHEAD as u64)
+            .read_buf(&mut self.buffered)
+            .await
+    }
+
+    /// Remove and return the first `n` buffered bytes.
+    fn take_buffered(&mut self, n: usize) -> Vec<u8> {
+        let rest = self.buffered.split_off(n);
+        std::mem::replace(&mut self.buffered, rest)
     }
 }
 
@@ -84,119 +280,504 @@ impl Attestation {
 ///
 /// Provides read/write access to an attested TLS stream and the attestation report.
 /// The connection is created by `atls_connect()` and stays open for HTTP communication.
-#[pyclass]
+/// `atls_accept()` returns one for the server side, whose client is not
+/// attested: it has no attestation and can't `reattest()`.
+///
+/// The connection is full duplex: reads and writes take separate locks, so one
+/// thread blocked in `read()` never holds up another thread's `write()`.
+/// Concurrent reads (or concurrent writes) run one at a time, each call as a
+/// whole.
+#[pyclass(frozen)]
 struct AtlsConnection {
-    conn_id: u64,
+    // Dropped by hand, see `Drop`.
+    reader: ManuallyDrop<Mutex<Reader>>,
+    writer: ManuallyDrop<Mutex<WriteHalf<Stream>>>,
+    /// `None` on the server side.
+    session: Option<Session>,
+    attestation: std::sync::Mutex<Option<Attestation>>,
+    /// Default read timeout set by `settimeout()`; `None` blocks indefinitely.
+    read_timeout: std::sync::Mutex<Option<Duration>>,
+    closed: AtomicBool,
+    /// Set by `shutdown_write()`: reads go on, writes fail.
+    write_closed: AtomicBool,
+    /// Process that opened the connection; a forked child can't use it.
+    pid: u32,
 }
 
 impl Drop for AtlsConnection {
     fn drop(&mut self) {
-        let conn_id = self.conn_id;
-        let _ = RUNTIME.block_on(async { CONNECTIONS.lock().await.remove(&conn_id) });
+        if self.forked() {
+            // The stream is registered with the parent's reactor, whose epoll
+            // instance the child shares: dropping it here would deregister the
+            // parent's socket. Leak it instead.
+            return;
+        }
+        // SAFETY: the fields are not used again after `drop`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.reader);
+            ManuallyDrop::drop(&mut self.writer);
+        }
     }
 }
 
-#[pymethods]
 impl AtlsConnection {
-    /// Read up to `size` bytes from the attested TLS stream.
-    ///
-    /// Blocks until data is available. Returns empty bytes on EOF.
-    /// The GIL is released during the blocking read.
-    fn read(&self, py: Python<'_>, size: usize) -> PyResult<Vec<u8>> {
-        let conn_id = self.conn_id;
-        py.allow_threads(|| {
-            RUNTIME.block_on(async {
-                let reader = {
-                    let guard = CONNECTIONS.lock().await;
-                    let state = guard
-                        .get(&conn_id)
-                        .ok_or_else(|| PyIOError::new_err("connection closed"))?;
-                    state.reader.clone()
-                };
-
-                let mut buf = vec![0u8; size];
-                let mut reader = reader.lock().await;
-                match reader.read(&mut buf).await {
-                    Ok(0) => Ok(Vec::new()),
-                    Ok(n) => {
-                        buf.truncate(n);
-                        Ok(buf)
+    /// Wrap an established stream; `attested` is `None` on the server side.
+    fn new(stream: Stream, attested: Option<(Session, Attestation)>) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let (session, attestation) = attested.unzip();
+        Self {
+            reader: ManuallyDrop::new(Mutex::new(Reader {
+                half: reader,
+                buffered: Vec::new(),
+            })),
+            writer: ManuallyDrop::new(Mutex::new(writer)),
+            session,
+            attestation: std::sync::Mutex::new(attestation),
+            read_timeout: std::sync::Mutex::new(None),
+            closed: AtomicBool::new(false),
+            write_closed: AtomicBool::new(false),
+            pid: std::process::id(),
+        }
+    }
+
+    /// Whether this is a forked child's copy of the parent's connection.
+    fn forked(&self) -> bool {
+        self.pid != std::process::id()
+    }
+
+    fn check_open(&self) -> PyResult<()> {
+        if self.forked() {
+            return Err(io_error(
+                "connection was opened before os.fork(); open a new one in this process".into(),
+            ));
+        }
+        if self.closed.load(Ordering::Acquire) {
+            return Err(io_error("connection closed".into()));
+        }
+        Ok(())
+    }
+
+    fn current_attestation(&self) -> PyResult<Attestation> {
+        lock(&self.attestation).clone().ok_or_else(not_attested)
+    }
+
+    /// Run `read` for at most `timeout` (or the `settimeout()` value).
+    async fn with_read_timeout<T>(
+        &self,
+        timeout: Option<Duration>,
+        read: impl Future<Output = PyResult<T>>,
+    ) -> PyResult<T> {
+        let timeout = timeout.or(*lock(&self.read_timeout));
+        // Reads of the TLS stream are cancel-safe: on timeout, already
+        // decrypted data stays buffered for the next read.
+        match timeout {
+            Some(limit) => tokio::time::timeout(limit, read).await.map_err(|_| {
+                timeout_error(format!("read timed out after {:.3}s", limit.as_secs_f64()))
+            })?,
+            None => read.await,
+        }
+    }
+
+    /// Read into `buf`, waiting at most `timeout` (or the `settimeout()`
+    /// value). Returns 0 on EOF.
+    async fn read_into(&self, buf: &mut [u8], timeout: Option<Duration>) -> PyResult<usize> {
+        self.check_open()?;
+        let mut reader = self.reader.lock().await;
+        if !reader.buffered.is_empty() {
+            let n = buf.len().min(reader.buffered.len());
+            buf[..n].copy_from_slice(&reader.take_buffered(n));
+            return Ok(n);
+        }
+        self.with_read_timeout(timeout, async {
+            reader.half.read(buf).await.map_err(read_error)
+        })
+        .await
+    }
+
+    /// Read exactly `n` bytes. On EOF or timeout, what was read so far stays
+    /// buffered for the next read.
+    async fn read_exact_bytes(&self, n: usize, timeout: Option<Duration>) -> PyResult<Vec<u8>> {
+        self.check_open()?;
+        let mut reader = self.reader.lock().await;
+        self.with_read_timeout(timeout, async {
+            while reader.buffered.len() < n {
+                if reader.fill().await.map_err(read_error)? == 0 {
+                    return Err(PyEOFError::new_err(format!(
+                        "connection closed after {} of {n} bytes",
+                        reader.buffered.len()
+                    )));
+                }
+            }
+            Ok(reader.take_buffered(n))
+        })
+        .await
+    }
+
+    /// Read up to and including `delimiter`, looking at most `max_bytes`
+    /// ahead. On failure, what was read so far stays buffered.
+    async fn read_until_delimiter(
+        &self,
+        delimiter: &[u8],
+        max_bytes: usize,
+        timeout: Option<Duration>,
+    ) -> PyResult<Vec<u8>> {
+        self.check_open()?;
+        let mut reader = self.reader.lock().await;
+        self.with_read_timeout(timeout, async {
+            // Where the delimiter may start in data not searched yet.
+            let mut searched = 0;
+            loop {
+                if let Some(pos) = reader.buffered[searched..]
+                    .windows(delimiter.len())
+                    .position(|window| window == delimiter)
+                {
+                    let end = searched + pos + delimiter.len();
+                    if end > max_bytes {
+                        break;
                     }
-                    Err(e) => Err(PyIOError::new_err(format!("read error: {e}"))),
+                    return Ok(reader.take_buffered(end));
+                }
+                if reader.buffered.len() >= max_bytes {
+                    break;
+                }
+                searched = reader.buffered.len().saturating_sub(delimiter.len() - 1);
+                if reader.fill().await.map_err(read_error)? == 0 {
+                    return Err(PyEOFError::new_err(format!(
+                        "connection closed after {} bytes without the delimiter",
+                        reader.buffered.len()
+                    )));
                 }
-            })
+            }
+            Err(PyValueError::new_err(format!(
+                "delimiter not found within {max_bytes} bytes"
+            )))
         })
+        .await
+    }
+
+    async fn write_all(&self, data: &[u8]) -> PyResult<()> {
+        self.check_open()?;
+        if self.write_closed.load(Ordering::Acquire) {
+            return Err(io_error("connection closed for writing".into()));
+        }
+        let mut writer = self.writer.lock().await;
+        writer
+            .write_all(data)
+            .await
+            .map_err(|e| io_error(format!("write error: {e}")))?;
+        writer
+            .flush()
+            .await
+            .map_err(|e| io_error(format!("flush error: {e}")))
+    }
+
+    /// Send `close_notify` and shut the write side down, leaving reads open.
+    async fn shutdown_write_half(&self) -> PyResult<()> {
+        self.check_open()?;
+        if self.write_closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let mut writer = self.writer.lock().await;
+        writer
+            .shutdown()
+            .await
+            .map_err(|e| io_error(format!("shutdown error: {e}")))
+    }
+
+    /// Mark the connection closed and shut its stream down gracefully.
+    ///
+    /// In a forked child the stream is left alone: the socket is shared with
+    /// the parent, which may still be using the connection.
+    async fn shutdown(&self) {
+        if self.closed.swap(true, Ordering::AcqRel) || self.forked() {
+            return;
+        }
+        let mut writer = self.writer.lock().await;
+        let _ = writer.flush().await;
+        let _ = writer.shutdown().await;
+    }
+
+    /// Verify fresh evidence over the connection and store the new attestation.
+    ///
+    /// Any failure closes the connection: the stream may be left mid-response,
+    /// and a peer that failed verification must not be talked to again.
+    async fn verify_again(
+        &self,
+        policy: Option<Policy>,
+        allow_measurement_change: bool,
+    ) -> PyResult<Attestation> {
+        self.check_open()?;
+        let previous = self.current_attestation()?.measurement;
+        let session = self.session.as_ref().ok_or_else(not_attested)?;
+
+        let result = async {
+            let verifier = policy
+                .unwrap_or_else(|| session.policy.clone())
+                .into_verifier()
+                .map_err(verification_error)?;
+            let mut reader = self.reader.lock().await;
+            if !reader.buffered.is_empty() {
+                return Err(io_error("reattest() called with unread data".into()));
+            }
+            let mut writer = self.writer.lock().await;
+            let mut stream = Rejoined {
+                reader: &mut reader.half,
+                writer: &mut writer,
+            };
+            let report = verifier
+                .verify(
+                    &mut stream,
+                    &session.peer_cert,
+                    &session.session_ekm,
+                    &session.server_name,
+                )
+                .await
+                .map_err(verification_error)?;
+            let attestation = Attestation::from(report);
+            if !allow_measurement_change && attestation.measurement != previous {
+                return Err(measurement_changed_error(format!(
+                    "measurement changed from {} to {}",
+                    previous.as_deref().unwrap_or("none"),
+                    attestation.measurement.as_deref().unwrap_or("none"),
+                )));
+            }
+            Ok(attestation)
+        }
+        .await;
+
+        match result {
+            Ok(attestation) => {
+                *lock(&self.attestation) = Some(attestation.clone());
+                Ok(attestation)
+            }
+            Err(e) => {
+                self.shutdown().await;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn read_error(e: io::Error) -> PyErr {
+    io_error(format!("read error: {e}"))
+}
+
+fn not_attested() -> PyErr {
+    io_error("connection was accepted by atls_accept(): its client is not attested".into())
+}
+
+/// Lock a std mutex, ignoring poisoning: the guarded values are plain data
+/// that a panicking holder can't leave half-updated.
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[pymethods]
+impl AtlsConnection {
+    /// Read up to `size` bytes from the attested TLS stream.
+    ///
+    /// Blocks until data is available, or for at most `timeout` seconds
+    /// (default: the value set by `settimeout()`), then raises `AtlasTimeout`.
+    /// Returns empty bytes on EOF. The GIL is released during the blocking read,
+    /// and signal handlers still run, so Ctrl-C raises `KeyboardInterrupt`
+    /// (no data is lost: unread data stays buffered for the next read).
+    #[pyo3(signature = (size, timeout=None))]
+    fn read(&self, py: Python<'_>, size: usize, timeout: Option<f64>) -> PyResult<Vec<u8>> {
+        let timeout = parse_timeout(timeout)?;
+        let mut buf = vec![0u8; size];
+        let n = block_on_interruptible(py, self.read_into(&mut buf, timeout))?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    /// Read into a writable buffer (`bytearray`, `memoryview`, ...) without
+    /// allocating, and return the number of bytes read (0 on EOF).
+    ///
+    /// Same blocking, timeout and interrupt behaviour as `read()`. The GIL is
+    /// released during the read; the buffer stays exported (and cannot be
+    /// resized) meanwhile.
+    #[pyo3(signature = (buffer, timeout=None))]
+    fn readinto(
+        &self,
+        py: Python<'_>,
+        buffer: PyBuffer<u8>,
+        timeout: Option<f64>,
+    ) -> PyResult<usize> {
+        if buffer.readonly() {
+            return Err(PyTypeError::new_err(
+                "readinto() requires a writable buffer",
+            ));
+        }
+        if !buffer.is_c_contiguous() {
+            return Err(PyTypeError::new_err(
+                "readinto() requires a contiguous buffer",
+            ));
+        }
+        let timeout = parse_timeout(timeout)?;
+        // SAFETY: the exported buffer is writable, contiguous and `len_bytes()`
+        // long, and the exporter can't free or resize it while `buffer` holds
+        // the export, which outlives `buf`.
+        let buf = unsafe {
+            std::slice::from_raw_parts_mut(buffer.buf_ptr() as *mut u8, buffer.len_bytes())
+        };
+        block_on_interruptible(py, self.read_into(buf, timeout))
+    }
+
+    /// Read exactly `n` bytes, for length-prefixed protocols.
+    ///
+    /// `timeout` (default: the value set by `settimeout()`) bounds the whole
+    /// call. Raises `EOFError` if the peer closes first, or `AtlasTimeout`;
+    /// either way the bytes received so far stay buffered for the next read.
+    #[pyo3(signature = (n, timeout=None))]
+    fn read_exact(&self, py: Python<'_>, n: usize, timeout: Option<f64>) -> PyResult<Vec<u8>> {
+        let timeout = parse_timeout(timeout)?;
+        block_on_interruptible(py, self.read_exact_bytes(n, timeout))
+    }
+
+    /// Read up to and including `delimiter` (e.g. `b"\r\n"`), for
+    /// line-oriented protocols.
+    ///
+    /// Raises `ValueError` if the delimiter isn't within the first `max_bytes`
+    /// bytes, `EOFError` if the peer closes first, or `AtlasTimeout` once
+    /// `timeout` (default: the value set by `settimeout()`) has elapsed for
+    /// the whole call. On error, nothing is consumed: the data stays buffered
+    /// for the next read.
+    #[pyo3(signature = (delimiter, max_bytes=DEFAULT_READ_UNTIL_LIMIT, timeout=None))]
+    fn read_until(
+        &self,
+        py: Python<'_>,
+        delimiter: &[u8],
+        max_bytes: usize,
+        timeout: Option<f64>,
+    ) -> PyResult<Vec<u8>> {
+        if delimiter.is_empty() {
+            return Err(PyValueError::new_err("delimiter must not be empty"));
+        }
+        let timeout = parse_timeout(timeout)?;
+        block_on_interruptible(py, self.read_until_delimiter(delimiter, max_bytes, timeout))
+    }
+
+    /// Return a file object for the connection, like `socket.makefile()`.
+    ///
+    /// `mode` may only contain `r`, `w` and `b`; the other arguments are as for
+    /// `open()`. Closing the file does not close the connection.
+    #[pyo3(signature = (mode="r", buffering=None, *, encoding=None, errors=None, newline=None))]
+    fn makefile<'py>(
+        slf: &Bound<'py, Self>,
+        mode: &str,
+        buffering: Option<i64>,
+        encoding: Option<&str>,
+        errors: Option<&str>,
+        newline: Option<&str>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("encoding", encoding)?;
+        kwargs.set_item("errors", errors)?;
+        kwargs.set_item("newline", newline)?;
+        py.import("atlas._makefile")?
+            .getattr("makefile")?
+            .call((slf, mode, buffering), Some(&kwargs))
+    }
+
+    /// Set the default read timeout in seconds; `None` makes reads block
+    /// indefinitely (the initial setting).
+    #[pyo3(signature = (timeout))]
+    fn settimeout(&self, timeout: Option<f64>) -> PyResult<()> {
+        let timeout = parse_timeout(timeout)?;
+        self.check_open()?;
+        *lock(&self.read_timeout) = timeout;
+        Ok(())
+    }
+
+    /// Default read timeout in seconds, or `None`.
+    fn gettimeout(&self) -> PyResult<Option<f64>> {
+        self.check_open()?;
+        Ok(lock(&self.read_timeout).map(|t| t.as_secs_f64()))
     }
 
     /// Write data to the attested TLS stream.
     ///
-    /// Returns the number of bytes written. The GIL is released during the write.
+    /// Returns the number of bytes written. The GIL is released during the
+    /// write. Ctrl-C interrupts a stalled write with `KeyboardInterrupt`, after
+    /// which part of `data` may have been sent: close the connection.
     fn write(&self, py: Python<'_>, data: Vec<u8>) -> PyResult<usize> {
-        let conn_id = self.conn_id;
-        let len = data.len();
-        py.allow_threads(|| {
-            RUNTIME.block_on(async {
-                let writer = {
-                    let guard = CONNECTIONS.lock().await;
-                    let state = guard
-                        .get(&conn_id)
-                        .ok_or_else(|| PyIOError::new_err("connection closed"))?;
-                    state.writer.clone()
-                };
-
-                let mut writer = writer.lock().await;
-                writer
-                    .write_all(&data)
-                    .await
-                    .map_err(|e| PyIOError::new_err(format!("write error: {e}")))?;
-                writer
-                    .flush()
-                    .await
-                    .map_err(|e| PyIOError::new_err(format!("flush error: {e}")))?;
+        block_on_interruptible(py, self.write_all(&data))?;
+        Ok(data.len())
+    }
 
-                Ok(len)
-            })
-        })
+    /// Half-close the connection: tell the peer no more data will be sent
+    /// (TLS `close_notify`, then TCP FIN), like
+    /// `socket.shutdown(socket.SHUT_WR)`.
+    ///
+    /// Reads keep working until the peer closes its side, so a request can be
+    /// ended by EOF and its response still read. Later writes raise
+    /// `AtlasError`; calling it again does nothing.
+    fn shutdown_write(&self, py: Python<'_>) -> PyResult<()> {
+        block_on_interruptible(py, self.shutdown_write_half())
     }
 
     /// Close the connection gracefully.
     fn close(&self, py: Python<'_>) -> PyResult<()> {
-        let conn_id = self.conn_id;
-        py.allow_threads(|| {
-            RUNTIME.block_on(async {
-                let writer = {
-                    let mut guard = CONNECTIONS.lock().await;
-                    guard.remove(&conn_id).map(|state| state.writer)
-                };
-
-                if let Some(writer) = writer {
-                    let mut writer = writer.lock().await;
-                    let _ = writer.flush().await;
-                    let _ = writer.shutdown().await;
-                }
+        py.allow_threads(|| runtime().block_on(self.shutdown()));
+        Ok(())
+    }
 
-                Ok(())
-            })
-        })
+    /// Re-run the evidence exchange on this connection and update `attestation`.
+    ///
+    /// Requests a fresh quote (new nonce, same TLS session) and verifies it
+    /// against `policy_json` (default: the policy the connection was opened
+    /// with). Only call it while the connection is idle: no request in flight
+    /// and no unread response. Raises `MeasurementMismatch` if the measurement
+    /// differs from the current one, unless `allow_measurement_change` is true.
+    /// Any failure closes the connection. Returns the new attestation dict.
+    #[pyo3(signature = (policy_json=None, *, allow_measurement_change=false))]
+    fn reattest(
+        &self,
+        py: Python<'_>,
+        policy_json: Option<PolicyArg>,
+        allow_measurement_change: bool,
+    ) -> PyResult<PyObject> {
+        let policy = policy_json.map(PolicyArg::into_policy).transpose()?;
+        let attestation = py.allow_threads(|| {
+            runtime().block_on(self.verify_again(policy, allow_measurement_change))
+        })?;
+        attestation.to_py_dict(py)
+    }
+
+    /// Enter a `with` block; the connection itself is the target.
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Leave a `with` block: close the connection, even when the block raised.
+    ///
+    /// Never suppresses the exception.
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &self,
+        py: Python<'_>,
+        _exc_type: Option<&Bound<'_, PyAny>>,
+        _exc_value: Option<&Bound<'_, PyAny>>,
+        _traceback: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
     }
 
     /// Get the attestation report as a dict.
     ///
-    /// Returns: {"trusted": bool, "tee_type": str, "measurement": str | None, "tcb_status": str, "advisory_ids": list[str]}
+    /// Returns: {"trusted": bool, "tee_type": str, "measurement": str | None, "tcb_status": str,
+    ///           "advisory_ids": list[str], "timings": dict[str, float | None]}
+    ///
+    /// `timings` holds per-phase durations in milliseconds (`tcp_connect_ms`, `tls_handshake_ms`,
+    /// `evidence_exchange_ms`, `collateral_fetch_ms`, `verification_ms`) plus `total_ms`.
+    ///
+    /// Raises `AtlasError` on connections from `atls_accept()`.
     #[getter]
     fn attestation(&self, py: Python<'_>) -> PyResult<PyObject> {
-        let conn_id = self.conn_id;
-        let attestation = py.allow_threads(|| {
-            RUNTIME.block_on(async {
-                let guard = CONNECTIONS.lock().await;
-                let state = guard
-                    .get(&conn_id)
-                    .ok_or_else(|| PyIOError::new_err("connection closed"))?;
-                Ok::<_, PyErr>(state.attestation.clone())
-            })
-        })?;
-
-        attestation.to_py_dict(py)
+        self.check_open()?;
+        self.current_attestation()?.to_py_dict(py)
     }
 }
 
@@ -210,62 +791,190 @@ impl AtlsConnection {
 ///     host: Target hostname or IP.
 ///     port: Target port.
 ///     server_name: TLS SNI server name (usually same as host).
-///     policy_json: JSON string of the attestation policy.
+///     policy_json: The attestation policy: a `Policy` or `DstackTdxPolicy`,
+///         or its JSON string.
+///     alpn: ALPN protocols to offer (default `["http/1.1"]`); e.g. `["h2"]`
+///         for gRPC.
+///     proxy: Proxy URL to tunnel through: `http://` (CONNECT), `socks5://`
+///         or `socks5h://` (proxy-side DNS), optionally with `user:password@`.
+///     trust_env: Without `proxy`, use the proxy from `HTTPS_PROXY` or
+///         `ALL_PROXY` unless `NO_PROXY` covers `host`. Set to False to
+///         always connect directly.
 ///
 /// Returns:
 ///     AtlsConnection with .read()/.write()/.close()/.attestation
 ///
 /// Raises:
-///     ValueError: If the policy JSON is invalid.
-///     ConnectionError: If TCP connection or TLS handshake fails.
-///     IOError: If attestation verification fails.
+///     ValueError: If the policy JSON or proxy URL is invalid.
+///     ConnectionError: If the TCP connection or proxy tunnel fails.
+///     TlsError: If the TLS handshake fails.
+///     AttestationRejected: If the evidence does not satisfy the policy
+///         (`TcbStatusNotAllowed` and `MeasurementMismatch` are subclasses).
+///     CollateralError: If Intel collateral is unavailable or invalid.
+///     AtlasError: Any other aTLS failure; all of the above carry `.code`.
 #[pyfunction]
+#[pyo3(signature = (host, port, server_name, policy_json, alpn=None, *, proxy=None, trust_env=true))]
+#[allow(clippy::too_many_arguments)]
 fn atls_connect(
     py: Python<'_>,
     host: &str,
     port: u16,
     server_name: &str,
-    policy_json: &str,
+    policy_json: PolicyArg,
+    alpn: Option<Vec<String>>,
+    proxy: Option<&str>,
+    trust_env: bool,
 ) -> PyResult<AtlsConnection> {
     // Ensure crypto provider is initialized
     Lazy::force(&CRYPTO_INIT);
 
-    let policy: Policy = serde_json::from_str(policy_json)
-        .map_err(|e| PyValueError::new_err(format!("invalid policy JSON: {e}")))?;
+    let policy = policy_json.into_policy()?;
+    let proxy = match proxy {
+        Some(url) => Some(proxy::Proxy::parse(url)?),
+        None if trust_env => proxy::Proxy::from_env(host)?,
+        None => None,
+    };
 
     let target = format!("{host}:{port}");
     let server_name = server_name.to_string();
 
     py.allow_threads(|| {
-        RUNTIME.block_on(async {
-            let tcp = TcpStream::connect(&target)
-                .await
-                .map_err(|e| PyConnectionError::new_err(format!("tcp connect failed: {e}")))?;
-
-            let (tls, report) =
-                core_atls_connect(tcp, &server_name, policy, Some(vec!["http/1.1".into()]))
+        runtime().block_on(async {
+            let connect_start = Instant::now();
+            let tcp = match &proxy {
+                Some(proxy) => proxy.connect(host, port).await?,
+                None => TcpStream::connect(&target)
                     .await
-                    .map_err(|e| PyIOError::new_err(format!("atls handshake failed: {e}")))?;
+                    .map_err(|e| PyConnectionError::new_err(format!("tcp connect failed: {e}")))?,
+            };
+            let tcp_connect = connect_start.elapsed();
 
-            let conn_id = NEXT_CONN_ID.fetch_add(1, Ordering::SeqCst);
-            let (reader, writer) = tokio::io::split(tls);
+            handshake(tcp, &server_name, policy, alpn, Some(tcp_connect)).await
+        })
+    })
+}
 
-            let attestation: Attestation = report.into();
+/// Run the aTLS handshake over a connected stream and wrap it in an `AtlsConnection`.
+async fn handshake(
+    stream: impl AsyncByteStream + 'static,
+    server_name: &str,
+    policy: Policy,
+    alpn: Option<Vec<String>>,
+    tcp_connect: Option<Duration>,
+) -> PyResult<AtlsConnection> {
+    let alpn = alpn.unwrap_or_else(|| vec!["http/1.1".into()]);
+    let (tls, mut report) = core_atls_connect(
+        Box::new(stream) as Box<dyn AsyncByteStream>,
+        server_name,
+        policy.clone(),
+        Some(alpn),
+    )
+    .await
+    .map_err(verification_error)?;
+
+    let (peer_cert, session_ekm) = session_binding(&tls).map_err(verification_error)?;
+    let session = Session {
+        server_name: server_name.to_string(),
+        policy,
+        peer_cert,
+        session_ekm,
+    };
+
+    report.timings_mut().tcp_connect = tcp_connect;
+    let attestation: Attestation = report.into();
+
+    Ok(AtlsConnection::new(
+        Box::new(tls),
+        Some((session, attestation)),
+    ))
+}
+
+/// Run the aTLS handshake over an already-connected Python socket.
+///
+/// The socket's descriptor is duplicated, so `sock` stays a valid object, but
+/// it must not be used for I/O afterwards (close it when convenient). Only
+/// TCP sockets (`AF_INET`/`AF_INET6`, `SOCK_STREAM`) are supported; use this
+/// with custom dialing logic, SOCKS libraries or pre-established tunnels.
+///
+/// Args:
+///     sock: A connected `socket.socket`.
+///     server_name: Server name for SNI and certificate verification.
+///     policy_json: The attestation policy: a `Policy` or `DstackTdxPolicy`,
+///         or its JSON string.
+///     alpn: ALPN protocols to offer, as for `atls_connect()`.
+///
+/// Returns:
+///     AtlsConnection, as from `atls_connect()` (`timings.tcp_connect_ms` is None).
+///
+/// Raises:
+///     ValueError: If the policy JSON is invalid or `sock` is not a TCP socket.
+///     AtlasError: If the handshake fails, as for `atls_connect()`.
+#[pyfunction]
+#[pyo3(signature = (sock, server_name, policy_json, alpn=None))]
+fn atls_wrap_socket(
+    py: Python<'_>,
+    sock: &Bound<'_, PyAny>,
+    server_name: &str,
+    policy_json: PolicyArg,
+    alpn: Option<Vec<String>>,
+) -> PyResult<AtlsConnection> {
+    Lazy::force(&CRYPTO_INIT);
 
-            CONNECTIONS.lock().await.insert(
-                conn_id,
-                ConnectionState {
-                    reader: Arc::new(Mutex::new(reader)),
-                    writer: Arc::new(Mutex::new(writer)),
-                    attestation,
-                },
-            );
+    let policy = policy_json.into_policy()?;
+    let std_stream = dup_tcp_socket(sock, "atls_wrap_socket")?;
+    let server_name = server_name.to_string();
 
-            Ok(AtlsConnection { conn_id })
+    py.allow_threads(|| {
+        runtime().block_on(async {
+            std_stream
+                .set_nonblocking(true)
+                .map_err(|e| io_error(format!("socket setup failed: {e}")))?;
+            let tcp = TcpStream::from_std(std_stream)
+                .map_err(|e| io_error(format!("socket setup failed: {e}")))?;
+            handshake(tcp, &server_name, policy, alpn, None).await
         })
     })
 }
 
+/// Duplicate the descriptor of a connected Python TCP socket into a std stream.
+///
+/// `caller` names the function in the error for other sockets.
+fn dup_tcp_socket(sock: &Bound<'_, PyAny>, caller: &str) -> PyResult<std::net::TcpStream> {
+    let socket_module = sock.py().import("socket")?;
+    let family = sock.getattr("family")?;
+    let is_inet = family.eq(socket_module.getattr("AF_INET")?)?
+        || family.eq(socket_module.getattr("AF_INET6")?)?;
+    let is_stream = sock
+        .getattr("type")?
+        .eq(socket_module.getattr("SOCK_STREAM")?)?;
+    if !is_inet || !is_stream {
+        return Err(PyValueError::new_err(format!(
+            "{caller}() requires a connected TCP socket"
+        )));
+    }
+
+    // `dup().detach()` hands us a new descriptor that Python no longer owns.
+    let fd: i64 = sock
+        .call_method0("dup")?
+        .call_method0("detach")?
+        .extract()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::FromRawFd;
+        // SAFETY: `fd` is a freshly duplicated, open TCP socket descriptor
+        // that nothing else owns.
+        Ok(unsafe { std::net::TcpStream::from_raw_fd(fd as std::os::fd::RawFd) })
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::FromRawSocket;
+        // SAFETY: as above, for a duplicated Windows socket handle.
+        let socket = fd as std::os::windows::io::RawSocket;
+        Ok(unsafe { std::net::TcpStream::from_raw_socket(socket) })
+    }
+}
+
 /// Merge a user-provided app_compose JSON with default values.
 ///
 /// Args:
@@ -275,8 +984,7 @@ fn atls_connect(
 ///     JSON string of the merged app_compose with all defaults filled in.
 #[pyfunction]
 fn merge_with_default_app_compose_py(user_compose_json: &str) -> PyResult<String> {
-    let value: serde_json::Value = serde_json::from_str(user_compose_json)
-        .map_err(|e| PyValueError::new_err(format!("invalid JSON: {e}")))?;
+    let value = parse_compose(user_compose_json)?;
 
     let merged = merge_with_default_app_compose(&value);
 
@@ -284,11 +992,67 @@ fn merge_with_default_app_compose_py(user_compose_json: &str) -> PyResult<String
         .map_err(|e| PyValueError::new_err(format!("serialization error: {e}")))
 }
 
+fn parse_compose(compose_json: &str) -> PyResult<serde_json::Value> {
+    serde_json::from_str(compose_json)
+        .map_err(|e| PyValueError::new_err(format!("invalid JSON: {e}")))
+}
+
+/// Hash an app_compose the way verification does.
+///
+/// Args:
+///     compose_json: JSON string of the app_compose exactly as it appears in
+///         the policy (after merging defaults, if the policy was built so).
+///
+/// Returns:
+///     SHA-256 of the compose as lowercase hex, the `compose-hash` event
+///     payload a CVM running it records.
+#[pyfunction]
+fn compose_hash_py(compose_json: &str) -> PyResult<String> {
+    get_compose_hash(&parse_compose(compose_json)?)
+        .map_err(|e| PyValueError::new_err(format!("serialization error: {e}")))
+}
+
+/// The `compose-hash` event a dstack CVM running an app_compose records.
+///
+/// Args:
+///     compose_json: JSON string of the app_compose, as for `compose_hash_py()`.
+///
+/// Returns:
+///     {"imr": int, "event_type": int, "digest": str, "event": str,
+///      "event_payload": str}, as the entry appears in the CVM's event log.
+#[pyfunction]
+fn expected_compose_event_py<'py>(
+    py: Python<'py>,
+    compose_json: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let event = get_compose_hash_event(&parse_compose(compose_json)?)
+        .map_err(|e| PyValueError::new_err(format!("serialization error: {e}")))?;
+    let dict = PyDict::new(py);
+    dict.set_item("imr", event.imr)?;
+    dict.set_item("event_type", event.event_type)?;
+    dict.set_item("digest", event.digest)?;
+    dict.set_item("event", event.event)?;
+    dict.set_item("event_payload", event.event_payload)?;
+    Ok(dict)
+}
+
 /// Atlas Python bindings for attested TLS (aTLS).
 #[pymodule]
 fn _atlas(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<AtlsConnection>()?;
+    m.add_class::<PyAttestation>()?;
+    m.add_class::<PyPolicy>()?;
+    m.add_class::<PyDstackTdxPolicy>()?;
+    m.add_class::<PyExpectedBootchain>()?;
+    errors::register(m)?;
+    collateral::register(m)?;
+    offline::register(m)?;
+    local::register(m)?;
+    server::register(m)?;
     m.add_function(wrap_pyfunction!(atls_connect, m)?)?;
+    m.add_function(wrap_pyfunction!(atls_wrap_socket, m)?)?;
     m.add_function(wrap_pyfunction!(merge_with_default_app_compose_py, m)?)?;
+    m.add_function(wrap_pyfunction!(compose_hash_py, m)?)?;
+    m.add_function(wrap_pyfunction!(expected_compose_event_py, m)?)?;
     Ok(())
 }
@@ -0,0 +1,143 @@
+//! aTLS over local transports.
+//!
+//! On a TEE host, guests are often reachable over a Unix socket (e.g. one
+//! forwarded by the VMM) or vsock rather than TCP. These variants of
+//! `atls_connect()` dial such a transport and run the same handshake and
+//! attestation over it.
+
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+#[cfg(unix)]
+use pyo3::exceptions::PyConnectionError;
+#[cfg(not(target_os = "linux"))]
+use pyo3::exceptions::PyNotImplementedError;
+use pyo3::prelude::*;
+
+use crate::policy::PolicyArg;
+use crate::{handshake, runtime, AtlsConnection, CRYPTO_INIT};
+
+/// Establish an attested TLS connection over a Unix domain socket.
+///
+/// Args:
+///     path: Filesystem path of the socket.
+///     server_name: Server name for SNI and certificate verification.
+///     policy_json: The attestation policy: a `Policy` or `DstackTdxPolicy`,
+///         or its JSON string.
+///     alpn: ALPN protocols to offer, as for `atls_connect()`.
+///
+/// Returns:
+///     AtlsConnection, as from `atls_connect()` (`timings.tcp_connect_ms`
+///     measures the socket connect).
+///
+/// Raises:
+///     ConnectionError: If the socket can't be connected.
+///     NotImplementedError: On platforms without Unix sockets.
+///     AtlasError: If the handshake fails, as for `atls_connect()`.
+#[pyfunction]
+#[pyo3(signature = (path, server_name, policy_json, alpn=None))]
+fn atls_connect_uds(
+    py: Python<'_>,
+    path: PathBuf,
+    server_name: &str,
+    policy_json: PolicyArg,
+    alpn: Option<Vec<String>>,
+) -> PyResult<AtlsConnection> {
+    Lazy::force(&CRYPTO_INIT);
+
+    let policy = policy_json.into_policy()?;
+
+    #[cfg(unix)]
+    {
+        py.allow_threads(|| {
+            runtime().block_on(async {
+                let connect_start = Instant::now();
+                let stream = tokio::net::UnixStream::connect(&path).await.map_err(|e| {
+                    PyConnectionError::new_err(format!(
+                        "unix socket connect to {} failed: {e}",
+                        path.display()
+                    ))
+                })?;
+                let connect = connect_start.elapsed();
+
+                handshake(stream, server_name, policy, alpn, Some(connect)).await
+            })
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (py, path, server_name, policy, alpn);
+        Err(PyNotImplementedError::new_err(
+            "atls_connect_uds() requires Unix domain sockets",
+        ))
+    }
+}
+
+/// Establish an attested TLS connection over vsock (Linux only).
+///
+/// Args:
+///     cid: Context ID of the guest (or `2` for the host, from a guest).
+///     port: vsock port.
+///     server_name: Server name for SNI and certificate verification.
+///     policy_json: The attestation policy: a `Policy` or `DstackTdxPolicy`,
+///         or its JSON string.
+///     alpn: ALPN protocols to offer, as for `atls_connect()`.
+///
+/// Returns:
+///     AtlsConnection, as from `atls_connect()` (`timings.tcp_connect_ms`
+///     measures the vsock connect).
+///
+/// Raises:
+///     ConnectionError: If the vsock connection fails.
+///     NotImplementedError: On platforms other than Linux.
+///     AtlasError: If the handshake fails, as for `atls_connect()`.
+#[pyfunction]
+#[pyo3(signature = (cid, port, server_name, policy_json, alpn=None))]
+fn atls_connect_vsock(
+    py: Python<'_>,
+    cid: u32,
+    port: u32,
+    server_name: &str,
+    policy_json: PolicyArg,
+    alpn: Option<Vec<String>>,
+) -> PyResult<AtlsConnection> {
+    Lazy::force(&CRYPTO_INIT);
+
+    let policy = policy_json.into_policy()?;
+
+    #[cfg(target_os = "linux")]
+    {
+        use tokio_vsock::{VsockAddr, VsockStream};
+
+        py.allow_threads(|| {
+            runtime().block_on(async {
+                let connect_start = Instant::now();
+                let stream = VsockStream::connect(VsockAddr::new(cid, port))
+                    .await
+                    .map_err(|e| {
+                        PyConnectionError::new_err(format!(
+                            "vsock connect to {cid}:{port} failed: {e}"
+                        ))
+                    })?;
+                let connect = connect_start.elapsed();
+
+                handshake(stream, server_name, policy, alpn, Some(connect)).await
+            })
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (py, cid, port, server_name, policy, alpn);
+        Err(PyNotImplementedError::new_err(
+            "atls_connect_vsock() is only supported on Linux",
+        ))
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(atls_connect_uds, m)?)?;
+    m.add_function(wrap_pyfunction!(atls_connect_vsock, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Offline re-verification of recorded evidence.
+
+use atlas_rs::{QuoteCollateralV3, Verifier};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::errors::verification_error;
+use crate::policy::PolicyArg;
+
+/// Unix seconds from `None` (now), a number, or a `datetime`.
+fn unix_secs(at_time: Option<&Bound<'_, PyAny>>) -> PyResult<u64> {
+    let secs: f64 = match at_time {
+        None => {
+            return std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .map_err(|e| PyValueError::new_err(format!("system clock error: {e}")))
+        }
+        Some(t) if t.hasattr("timestamp")? => t.call_method0("timestamp")?.extract()?,
+        Some(t) => t.extract()?,
+    };
+    if !secs.is_finite() || secs < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "at_time must be a datetime or non-negative Unix time, got {secs}"
+        )));
+    }
+    Ok(secs as u64)
+}
+
+/// Verify a recorded TDX quote against recorded collateral, without network access.
+///
+/// Runs DCAP verification as of `at_time`, the policy's TCB status and grace
+/// period checks and, unless runtime verification is disabled, its bootchain
+/// check. Checks that need the live session or the event log (certificate and
+/// report data binding, RTMR replay, app compose, OS image) are not performed.
+///
+/// Args:
+///     quote_bytes: The raw TDX quote.
+///     collateral_bytes: The collateral as JSON (serialized `QuoteCollateralV3`).
+///     policy_json: The attestation policy: a `Policy` or `DstackTdxPolicy`,
+///         or its JSON string.
+///     at_time: Verification time, as a `datetime` or Unix seconds (default: now).
+///
+/// Returns:
+///     {"tee_type": str, "tcb_status": str, "advisory_ids": list[str],
+///      "fmspc": str | None, "measurement": str, "rtmr0".."rtmr3": str,
+///      "report_data": str} (measurements in lowercase hex)
+///
+/// Raises:
+///     ValueError: If the policy or collateral can't be parsed.
+///     AtlasError: If verification fails, as for `atls_connect()`.
+#[pyfunction]
+#[pyo3(signature = (quote_bytes, collateral_bytes, policy_json, at_time=None))]
+fn verify_quote<'py>(
+    py: Python<'py>,
+    quote_bytes: Vec<u8>,
+    collateral_bytes: Vec<u8>,
+    policy_json: PolicyArg,
+    at_time: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let policy = policy_json.into_policy()?;
+    let now_secs = unix_secs(at_time)?;
+    let collateral: QuoteCollateralV3 = serde_json::from_slice(&collateral_bytes)
+        .map_err(|e| PyValueError::new_err(format!("invalid collateral JSON: {e}")))?;
+
+    let report = py.allow_threads(|| match policy.into_verifier()? {
+        Verifier::DstackTdx(verifier) => {
+            verifier.verify_quote_offline(&quote_bytes, &collateral, now_secs)
+        }
+    });
+    let report = report.map_err(verification_error)?;
+    let td = report
+        .report
+        .as_td10()
+        .ok_or_else(|| PyValueError::new_err("quote is not a TDX quote"))?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("tee_type", "tdx")?;
+    dict.set_item("tcb_status", &report.status)?;
+    dict.set_item("advisory_ids", &report.advisory_ids)?;
+    dict.set_item("fmspc", &report.fmspc)?;
+    dict.set_item("measurement", hex::encode(td.mr_td))?;
+    dict.set_item("rtmr0", hex::encode(td.rt_mr0))?;
+    dict.set_item("rtmr1", hex::encode(td.rt_mr1))?;
+    dict.set_item("rtmr2", hex::encode(td.rt_mr2))?;
+    dict.set_item("rtmr3", hex::encode(td.rt_mr3))?;
+    dict.set_item("report_data", hex::encode(td.report_data))?;
+    Ok(dict)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(verify_quote, m)?)?;
+    Ok(())
+}
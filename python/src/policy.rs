@@ -0,0 +1,316 @@
+//! Typed policy classes exposed to Python.
+//!
+//! Thin wrappers around the core policy types, so Python code can build a
+//! validated policy with keyword arguments instead of hand-writing
+//! `policy_json`. Each class round-trips through `to_json()`/`from_json()`
+//! using the core serde format.
+
+use atlas_rs::dstack::policy::DEFAULT_PCCS_URL;
+use atlas_rs::{
+    DstackTdxPolicy as CoreDstackTdxPolicy, ExpectedBootchain as CoreExpectedBootchain,
+    Policy as CorePolicy,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+fn to_json<T: Serialize>(value: &T) -> PyResult<String> {
+    serde_json::to_string(value)
+        .map_err(|e| PyValueError::new_err(format!("serialization error: {e}")))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> PyResult<T> {
+    serde_json::from_str(json)
+        .map_err(|e| PyValueError::new_err(format!("invalid policy JSON: {e}")))
+}
+
+/// Run the core validation, reported as `ValueError`.
+fn validate(policy: &CoreDstackTdxPolicy) -> PyResult<()> {
+    policy
+        .validate()
+        .map_err(|e| PyValueError::new_err(format!("invalid policy: {e}")))
+}
+
+/// Convert a Python object to JSON through the `json` module.
+fn py_to_value(obj: &Bound<'_, PyAny>) -> PyResult<serde_json::Value> {
+    let json: String = obj
+        .py()
+        .import("json")?
+        .call_method1("dumps", (obj,))?
+        .extract()?;
+    from_json(&json)
+}
+
+fn value_to_py<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?.call_method1("loads", (to_json(value)?,))
+}
+
+/// Expected TDX bootchain measurements (lowercase hex).
+#[pyclass(name = "ExpectedBootchain", module = "atlas._atlas", frozen)]
+#[derive(Clone)]
+pub struct PyExpectedBootchain {
+    pub inner: CoreExpectedBootchain,
+}
+
+#[pymethods]
+impl PyExpectedBootchain {
+    #[new]
+    #[pyo3(signature = (*, mrtd, rtmr0, rtmr1, rtmr2))]
+    fn new(mrtd: String, rtmr0: String, rtmr1: String, rtmr2: String) -> PyResult<Self> {
+        let inner = CoreExpectedBootchain {
+            mrtd,
+            rtmr0,
+            rtmr1,
+            rtmr2,
+        };
+        validate(&CoreDstackTdxPolicy {
+            expected_bootchain: Some(inner.clone()),
+            ..Default::default()
+        })?;
+        Ok(Self { inner })
+    }
+
+    #[getter]
+    fn mrtd(&self) -> &str {
+        &self.inner.mrtd
+    }
+
+    #[getter]
+    fn rtmr0(&self) -> &str {
+        &self.inner.rtmr0
+    }
+
+    #[getter]
+    fn rtmr1(&self) -> &str {
+        &self.inner.rtmr1
+    }
+
+    #[getter]
+    fn rtmr2(&self) -> &str {
+        &self.inner.rtmr2
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.inner)
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let inner: CoreExpectedBootchain = from_json(json)?;
+        Self::new(inner.mrtd, inner.rtmr0, inner.rtmr1, inner.rtmr2)
+    }
+
+    fn __eq__(&self, other: &Self) -> PyResult<bool> {
+        Ok(self.to_json()? == other.to_json()?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "ExpectedBootchain.from_json({:?})",
+            self.to_json()?
+        ))
+    }
+}
+
+/// Policy for dstack TDX verification.
+///
+/// Validated on construction: TCB statuses must be known, measurements lowercase
+/// hex, `expected_bootchain` and `os_image_hash` given together, and
+/// `grace_period` requires `OutOfDate` in `allowed_tcb_status`.
+#[pyclass(name = "DstackTdxPolicy", module = "atlas._atlas", frozen)]
+#[derive(Clone)]
+pub struct PyDstackTdxPolicy {
+    pub inner: CoreDstackTdxPolicy,
+}
+
+impl PyDstackTdxPolicy {
+    fn checked(inner: CoreDstackTdxPolicy) -> PyResult<Self> {
+        if inner.expected_bootchain.is_some() != inner.os_image_hash.is_some() {
+            return Err(PyValueError::new_err(
+                "expected_bootchain and os_image_hash must be provided together",
+            ));
+        }
+        validate(&inner)?;
+        Ok(Self { inner })
+    }
+}
+
+#[pymethods]
+impl PyDstackTdxPolicy {
+    #[new]
+    #[pyo3(signature = (
+        *,
+        expected_bootchain=None,
+        app_compose=None,
+        os_image_hash=None,
+        allowed_tcb_status=None,
+        grace_period=None,
+        pccs_url=None,
+        cache_collateral=false,
+        disable_runtime_verification=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        expected_bootchain: Option<PyExpectedBootchain>,
+        app_compose: Option<&Bound<'_, PyAny>>,
+        os_image_hash: Option<String>,
+        allowed_tcb_status: Option<Vec<String>>,
+        grace_period: Option<u64>,
+        pccs_url: Option<String>,
+        cache_collateral: bool,
+        disable_runtime_verification: bool,
+    ) -> PyResult<Self> {
+        let defaults = CoreDstackTdxPolicy::default();
+        Self::checked(CoreDstackTdxPolicy {
+            expected_bootchain: expected_bootchain.map(|b| b.inner),
+            app_compose: app_compose.map(py_to_value).transpose()?,
+            os_image_hash,
+            allowed_tcb_status: allowed_tcb_status.unwrap_or(defaults.allowed_tcb_status),
+            grace_period,
+            pccs_url: Some(pccs_url.unwrap_or_else(|| DEFAULT_PCCS_URL.to_string())),
+            cache_collateral,
+            disable_runtime_verification,
+        })
+    }
+
+    #[getter]
+    fn expected_bootchain(&self) -> Option<PyExpectedBootchain> {
+        self.inner
+            .expected_bootchain
+            .clone()
+            .map(|inner| PyExpectedBootchain { inner })
+    }
+
+    /// A copy of the expected app compose, as a dict.
+    #[getter]
+    fn app_compose<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        self.inner
+            .app_compose
+            .as_ref()
+            .map(|value| value_to_py(py, value))
+            .transpose()
+    }
+
+    #[getter]
+    fn os_image_hash(&self) -> Option<&str> {
+        self.inner.os_image_hash.as_deref()
+    }
+
+    #[getter]
+    fn allowed_tcb_status(&self) -> Vec<String> {
+        self.inner.allowed_tcb_status.clone()
+    }
+
+    #[getter]
+    fn grace_period(&self) -> Option<u64> {
+        self.inner.grace_period
+    }
+
+    #[getter]
+    fn pccs_url(&self) -> Option<&str> {
+        self.inner.pccs_url.as_deref()
+    }
+
+    #[getter]
+    fn cache_collateral(&self) -> bool {
+        self.inner.cache_collateral
+    }
+
+    #[getter]
+    fn disable_runtime_verification(&self) -> bool {
+        self.inner.disable_runtime_verification
+    }
+
+    /// JSON of the full policy (`{"type": "dstack_tdx", ...}`), as accepted by
+    /// `atls_connect()`.
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&CorePolicy::DstackTdx(self.inner.clone()))
+    }
+
+    /// Parse policy JSON, with or without the `"type"` tag.
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        Self::checked(from_json(json)?)
+    }
+
+    fn __eq__(&self, other: &Self) -> PyResult<bool> {
+        Ok(self.to_json()? == other.to_json()?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("DstackTdxPolicy.from_json({:?})", self.to_json()?))
+    }
+}
+
+/// Attestation policy: which verifier to use and its configuration.
+#[pyclass(name = "Policy", module = "atlas._atlas", frozen)]
+#[derive(Clone)]
+pub struct PyPolicy {
+    pub inner: CorePolicy,
+}
+
+#[pymethods]
+impl PyPolicy {
+    #[new]
+    fn new(dstack_tdx: PyDstackTdxPolicy) -> Self {
+        Self {
+            inner: CorePolicy::DstackTdx(dstack_tdx.inner),
+        }
+    }
+
+    /// The policy type tag, e.g. `"dstack_tdx"`.
+    #[getter]
+    fn r#type(&self) -> &'static str {
+        match self.inner {
+            CorePolicy::DstackTdx(_) => "dstack_tdx",
+        }
+    }
+
+    /// The `DstackTdxPolicy`, if this is a `dstack_tdx` policy.
+    #[getter]
+    fn dstack_tdx(&self) -> Option<PyDstackTdxPolicy> {
+        match &self.inner {
+            CorePolicy::DstackTdx(inner) => Some(PyDstackTdxPolicy {
+                inner: inner.clone(),
+            }),
+        }
+    }
+
+    fn to_json(&self) -> PyResult<String> {
+        to_json(&self.inner)
+    }
+
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        match from_json(json)? {
+            CorePolicy::DstackTdx(inner) => Ok(Self::new(PyDstackTdxPolicy::checked(inner)?)),
+        }
+    }
+
+    fn __eq__(&self, other: &Self) -> PyResult<bool> {
+        Ok(self.to_json()? == other.to_json()?)
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!("Policy.from_json({:?})", self.to_json()?))
+    }
+}
+
+/// Policy argument of `atls_connect()`: JSON or one of the typed classes.
+#[derive(FromPyObject)]
+pub enum PolicyArg {
+    Json(String),
+    Policy(PyPolicy),
+    DstackTdx(PyDstackTdxPolicy),
+}
+
+impl PolicyArg {
+    pub fn into_policy(self) -> PyResult<CorePolicy> {
+        match self {
+            Self::Json(json) => from_json(&json),
+            Self::Policy(policy) => Ok(policy.inner),
+            Self::DstackTdx(policy) => Ok(CorePolicy::DstackTdx(policy.inner)),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Server side of aTLS, for services running inside a dstack CVM.
+//!
+//! `atls_accept()` terminates TLS with the CVM's certificate and serves the
+//! client's quote request through the dstack guest agent, so Python services
+//! can be the attested end of a connection, not only the verifying one.
+
+use std::path::PathBuf;
+
+use atlas_rs::dstack::server::DEFAULT_AGENT_SOCKET;
+use atlas_rs::dstack::{AtlsAcceptor, GuestAgent};
+use once_cell::sync::Lazy;
+use pyo3::prelude::*;
+use tokio::net::TcpStream;
+
+use crate::errors::{io_error, timeout_error, verification_error};
+use crate::{block_on_interruptible, dup_tcp_socket, parse_timeout, AtlsConnection, CRYPTO_INIT};
+
+/// How `atls_accept()` presents itself to clients.
+///
+/// Args:
+///     certfile: PEM file with the certificate chain, leaf first.
+///     keyfile: PEM file with the certificate's private key.
+///     agent_socket: Unix socket of the dstack guest agent.
+///     alpn: ALPN protocols to accept, in order of preference (default: none).
+///
+/// Raises:
+///     OSError: If a file can't be read.
+///     AtlasError: If the certificate or key is invalid.
+#[pyclass(frozen, name = "ServerPolicy", module = "atlas._atlas")]
+pub struct PyServerPolicy {
+    acceptor: AtlsAcceptor,
+}
+
+#[pymethods]
+impl PyServerPolicy {
+    #[new]
+    #[pyo3(signature = (certfile, keyfile, *, agent_socket=PathBuf::from(DEFAULT_AGENT_SOCKET), alpn=None))]
+    fn new(
+        certfile: PathBuf,
+        keyfile: PathBuf,
+        agent_socket: PathBuf,
+        alpn: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        Lazy::force(&CRYPTO_INIT);
+
+        let cert_pem = std::fs::read(&certfile)?;
+        let key_pem = std::fs::read(&keyfile)?;
+        let acceptor =
+            AtlsAcceptor::from_pem(&cert_pem, &key_pem, GuestAgent::new(agent_socket), alpn)
+                .map_err(verification_error)?;
+        Ok(Self { acceptor })
+    }
+
+    /// Record the certificate in the CVM's event log, so clients accept it.
+    ///
+    /// Call it once per certificate, before serving: every call extends
+    /// RTMR3, and clients only trust the certificate recorded last.
+    fn register_certificate(&self, py: Python<'_>) -> PyResult<()> {
+        block_on_interruptible(py, async {
+            self.acceptor
+                .register_certificate()
+                .await
+                .map_err(verification_error)
+        })
+    }
+}
+
+/// Accept an aTLS connection from a client, inside a dstack CVM.
+///
+/// Completes the TLS handshake with the policy's certificate and answers the
+/// client's quote request with a quote bound to the session, then returns the
+/// connection for application traffic.
+///
+/// Args:
+///     sock: A connected TCP `socket.socket` (e.g. from `listener.accept()`)
+///         or its file descriptor, or a listening socket to accept one
+///         connection from. The descriptor is duplicated: close `sock` when
+///         convenient, but don't use it for I/O.
+///     server_policy: The `ServerPolicy` to present.
+///     timeout: Seconds the handshake and quote exchange may take (default:
+///         no limit), so a stalled client can't hold up the caller.
+///
+/// Returns:
+///     AtlsConnection with .read()/.write()/.close(). The client is not
+///     attested, so it has no `attestation`.
+///
+/// Raises:
+///     ValueError: If `sock` is not a TCP socket.
+///     AtlasTimeout: If `timeout` elapses first.
+///     TlsError: If the TLS handshake fails.
+///     AtlasError: If the client's quote request is invalid or the guest
+///         agent fails.
+#[pyfunction]
+#[pyo3(signature = (sock, server_policy, *, timeout=None))]
+fn atls_accept(
+    py: Python<'_>,
+    sock: &Bound<'_, PyAny>,
+    server_policy: &PyServerPolicy,
+    timeout: Option<f64>,
+) -> PyResult<AtlsConnection> {
+    let timeout = parse_timeout(timeout)?;
+    let std_stream = accepted_tcp_stream(sock)?;
+    let acceptor = server_policy.acceptor.clone();
+
+    block_on_interruptible(py, async move {
+        std_stream
+            .set_nonblocking(true)
+            .map_err(|e| io_error(format!("socket setup failed: {e}")))?;
+        let tcp = TcpStream::from_std(std_stream)
+            .map_err(|e| io_error(format!("socket setup failed: {e}")))?;
+
+        let accept = acceptor.accept(tcp);
+        let tls = match timeout {
+            Some(limit) => tokio::time::timeout(limit, accept).await.map_err(|_| {
+                timeout_error(format!(
+                    "accept timed out after {:.3}s",
+                    limit.as_secs_f64()
+                ))
+            })?,
+            None => accept.await,
+        }
+        .map_err(verification_error)?;
+        Ok(AtlsConnection::new(Box::new(tls), None))
+    })
+}
+
+/// The connected TCP stream `atls_accept()` serves: `sock` itself, its
+/// descriptor, or a connection accepted from it if it is listening.
+fn accepted_tcp_stream(sock: &Bound<'_, PyAny>) -> PyResult<std::net::TcpStream> {
+    let py = sock.py();
+    let socket_module = py.import("socket")?;
+
+    if sock.extract::<i64>().is_ok() {
+        // Wrap a duplicate: the socket object owns what it wraps
+        let fd = py.import("os")?.call_method1("dup", (sock,))?;
+        let kwargs = pyo3::types::PyDict::new(py);
+        kwargs.set_item("fileno", fd)?;
+        let owned = socket_module.call_method("socket", (), Some(&kwargs))?;
+        let stream = accepted_tcp_stream(&owned);
+        owned.call_method0("close")?;
+        return stream;
+    }
+
+    let listening = match socket_module.getattr("SO_ACCEPTCONN") {
+        Ok(option) => {
+            sock.call_method1("getsockopt", (socket_module.getattr("SOL_SOCKET")?, option))?
+                .extract::<i64>()?
+                != 0
+        }
+        Err(_) => false,
+    };
+    if !listening {
+        return dup_tcp_socket(sock, "atls_accept");
+    }
+
+    let (conn, _addr): (Bound<'_, PyAny>, Bound<'_, PyAny>) =
+        sock.call_method0("accept")?.extract()?;
+    let stream = dup_tcp_socket(&conn, "atls_accept");
+    conn.call_method0("close")?;
+    stream
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyServerPolicy>()?;
+    m.add_function(wrap_pyfunction!(atls_accept, m)?)?;
+    Ok(())
+}
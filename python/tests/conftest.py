@@ -1,6 +1,64 @@
+import socket
+import threading
+
 import pytest
 
 
+class SocketConnection:
+    """Stands in for AtlsConnection over one end of a socketpair."""
+
+    def __init__(self, sock):
+        self.sock = sock
+        self.attestation = {"trusted": True, "tee_type": "tdx"}
+        self.closed = threading.Event()
+
+    def read(self, size, timeout=None):
+        self.sock.settimeout(timeout)
+        try:
+            return self.sock.recv(size)
+        except socket.timeout as e:
+            raise TimeoutError("read timed out") from e
+
+    def write(self, data):
+        self.sock.sendall(data)
+        return len(data)
+
+    def shutdown_write(self):
+        self.sock.shutdown(socket.SHUT_WR)
+
+    def close(self):
+        self.closed.set()
+        try:
+            self.sock.shutdown(socket.SHUT_RDWR)
+        except OSError:
+            pass
+        self.sock.close()
+
+
+@pytest.fixture
+def socket_connection():
+    """Make a SocketConnection whose peer runs `serve(sock)` in a thread."""
+
+    def connect(serve):
+        client, server = socket.socketpair()
+        threading.Thread(target=serve, args=(server,), daemon=True).start()
+        return SocketConnection(client)
+
+    return connect
+
+
+@pytest.fixture
+def upper_echo_connection(socket_connection):
+    """A SocketConnection whose peer echoes everything back upper-cased."""
+
+    def serve(sock):
+        while data := sock.recv(65536):
+            sock.sendall(data.upper())
+        sock.close()
+
+    return socket_connection(serve)
+
+
 @pytest.fixture
 def os_image_hash():
     """OS image hash for testing.
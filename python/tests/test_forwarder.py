@@ -0,0 +1,113 @@
+"""Tests for atlas._forwarder (loopback forwarding onto aTLS connections)."""
+
+import socket
+from unittest.mock import patch
+
+import pytest
+
+from atlas._forwarder import AtlsForwarder
+
+
+@pytest.fixture
+def reply_at_eof_connection(socket_connection):
+    """A SocketConnection whose peer answers, upper-cased, once it reads EOF."""
+
+    def serve(sock):
+        request = b""
+        while data := sock.recv(65536):
+            request += data
+        sock.sendall(request.upper())
+        sock.close()
+
+    return socket_connection(serve)
+
+
+def recv_exactly(sock, size):
+    data = b""
+    while len(data) < size:
+        chunk = sock.recv(size - len(data))
+        if not chunk:
+            break
+        data += chunk
+    return data
+
+
+class TestAtlsForwarder:
+    """Tests for AtlsForwarder."""
+
+    def test_forwards_both_ways(self, upper_echo_connection):
+        conn = upper_echo_connection
+        with patch("atlas._forwarder.atls_connect", return_value=conn) as mock_connect:
+            with AtlsForwarder("tee.example.com", 443, {}, alpn=["h2"]) as forwarder:
+                assert forwarder.local_address[0] == "127.0.0.1"
+                assert forwarder.attestation is None
+
+                with socket.create_connection(forwarder.local_address) as local:
+                    local.sendall(b"hello")
+                    assert recv_exactly(local, 5) == b"HELLO"
+                    assert forwarder.attestation == conn.attestation
+
+        assert mock_connect.call_args.args[:3] == (
+            "tee.example.com",
+            443,
+            "tee.example.com",
+        )
+        assert mock_connect.call_args.kwargs == {"alpn": ["h2"]}
+
+    def test_local_close_closes_remote(self, upper_echo_connection):
+        conn = upper_echo_connection
+        with patch("atlas._forwarder.atls_connect", return_value=conn):
+            with AtlsForwarder("tee.example.com", 443, {}) as forwarder:
+                local = socket.create_connection(forwarder.local_address)
+                local.sendall(b"x")
+                assert recv_exactly(local, 1) == b"X"
+                local.close()
+                assert conn.closed.wait(5)
+
+    def test_local_half_close_keeps_response_flowing(self, reply_at_eof_connection):
+        conn = reply_at_eof_connection
+        with patch("atlas._forwarder.atls_connect", return_value=conn):
+            with AtlsForwarder("tee.example.com", 443, {}) as forwarder:
+                with socket.create_connection(forwarder.local_address) as local:
+                    local.sendall(b"request")
+                    local.shutdown(socket.SHUT_WR)
+                    local.settimeout(5)
+                    assert recv_exactly(local, 7) == b"REQUEST"
+                    assert local.recv(1) == b""
+                assert conn.closed.wait(5)
+
+    def test_failed_attestation_drops_local_connection(self):
+        error = OSError("attestation rejected")
+        with patch("atlas._forwarder.atls_connect", side_effect=error):
+            with AtlsForwarder("tee.example.com", 443, {}) as forwarder:
+                with socket.create_connection(forwarder.local_address) as local:
+                    local.settimeout(5)
+                    try:
+                        assert local.recv(1) == b""
+                    except ConnectionResetError:
+                        pass
+                assert forwarder.last_error is error
+                assert forwarder.attestation is None
+
+    def test_close_stops_forwarding(self, upper_echo_connection):
+        conn = upper_echo_connection
+        with patch("atlas._forwarder.atls_connect", return_value=conn):
+            forwarder = AtlsForwarder("tee.example.com", 443, {})
+            local = socket.create_connection(forwarder.local_address)
+            local.sendall(b"x")
+            assert recv_exactly(local, 1) == b"X"
+
+            forwarder.close()
+            forwarder.close()
+
+            local.settimeout(5)
+            assert recv_exactly(local, 1) == b""
+            assert conn.closed.wait(5)
+            local.close()
+
+            try:
+                socket.create_connection(forwarder.local_address, timeout=1).close()
+            except OSError:
+                pass
+            else:
+                raise AssertionError("forwarder still accepting connections")
@@ -0,0 +1,89 @@
+"""Tests for atlas.grpc (gRPC channels over aTLS)."""
+
+from unittest.mock import MagicMock, patch
+
+import pytest
+
+grpc = pytest.importorskip("grpc")
+
+from atlas.grpc import AttestedChannel, attested_channel  # noqa: E402
+
+
+@pytest.fixture
+def forwarder():
+    forwarder = MagicMock()
+    forwarder.local_address = ("127.0.0.1", 40123)
+    forwarder.attestation = {"trusted": True}
+    forwarder.last_error = None
+    with patch("atlas.grpc.AtlsForwarder", return_value=forwarder) as cls:
+        forwarder.cls = cls
+        yield forwarder
+
+
+class TestAttestedChannel:
+    """Tests for attested_channel() and AttestedChannel."""
+
+    def test_channel_dials_forwarder_with_real_authority(self, forwarder):
+        with patch("atlas.grpc.grpc.insecure_channel") as insecure_channel:
+            channel = attested_channel(
+                "tee.example.com",
+                443,
+                {},
+                options=[("grpc.max_send_message_length", 1)],
+            )
+
+        assert isinstance(channel, grpc.Channel)
+        assert forwarder.cls.call_args.args == ("tee.example.com", 443, {})
+        assert forwarder.cls.call_args.kwargs == {"server_name": None, "alpn": ["h2"]}
+        insecure_channel.assert_called_once_with(
+            "127.0.0.1:40123",
+            options=[
+                ("grpc.default_authority", "tee.example.com:443"),
+                ("grpc.max_send_message_length", 1),
+            ],
+        )
+        assert channel.attestation == {"trusted": True}
+        assert channel.last_error is None
+
+    def test_close_stops_forwarder(self, forwarder):
+        with patch("atlas.grpc.grpc.insecure_channel") as insecure_channel:
+            with attested_channel("tee.example.com", 443, {}):
+                pass
+
+        insecure_channel.return_value.close.assert_called_once()
+        forwarder.close.assert_called_once()
+
+    def test_channel_creation_failure_stops_forwarder(self, forwarder):
+        with patch(
+            "atlas.grpc.grpc.insecure_channel", side_effect=ValueError("bad option")
+        ):
+            with pytest.raises(ValueError):
+                attested_channel("tee.example.com", 443, {})
+
+        forwarder.close.assert_called_once()
+
+    def test_methods_delegate_to_inner_channel(self, forwarder):
+        inner = MagicMock()
+        channel = AttestedChannel(forwarder, inner)
+        callback = MagicMock()
+
+        channel.subscribe(callback, try_to_connect=True)
+        channel.unsubscribe(callback)
+        for name in ("unary_unary", "unary_stream", "stream_unary", "stream_stream"):
+            method = getattr(channel, name)("/pkg.Svc/Call", request_serializer=bytes)
+            assert method is getattr(inner, name).return_value
+            getattr(inner, name).assert_called_once_with(
+                "/pkg.Svc/Call", request_serializer=bytes
+            )
+
+        inner.subscribe.assert_called_once_with(callback, try_to_connect=True)
+        inner.unsubscribe.assert_called_once_with(callback)
+
+    def test_works_with_interceptors(self, forwarder):
+        class Interceptor(grpc.UnaryUnaryClientInterceptor):
+            def intercept_unary_unary(self, continuation, details, request):
+                return continuation(details, request)
+
+        channel = AttestedChannel(forwarder, MagicMock())
+        intercepted = grpc.intercept_channel(channel, Interceptor())
+        assert isinstance(intercepted, grpc.Channel)
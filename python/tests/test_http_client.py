@@ -0,0 +1,73 @@
+"""Tests for atlas.http_client.AtlsHTTPSConnection."""
+
+import http.client
+import io
+from unittest.mock import MagicMock, patch
+
+import pytest
+
+from atlas.http_client import AtlsHTTPSConnection
+from atlas.policy import dev_policy
+
+
+def fake_connection(response):
+    """A MagicMock AtlsConnection that answers every read from `response`."""
+    conn = MagicMock()
+    conn.attestation = {"trusted": True, "tee_type": "tdx"}
+    stream = io.BytesIO(response)
+    conn.read.side_effect = lambda size: stream.read(size)
+    return conn
+
+
+class TestAtlsHTTPSConnection:
+    """Tests for the http.client-compatible connection."""
+
+    def test_request_and_response(self):
+        conn = fake_connection(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello")
+        with patch("atlas.http_client.atls_connect", return_value=conn) as connect:
+            client = AtlsHTTPSConnection("tee.example.com", policy=dev_policy())
+            client.request("GET", "/v1/models", headers={"X-Test": "1"})
+            response = client.getresponse()
+
+            assert isinstance(client, http.client.HTTPSConnection)
+            assert response.status == 200
+            assert response.read() == b"hello"
+            assert client.attestation == conn.attestation
+
+        assert connect.call_args.args[:3] == ("tee.example.com", 443, "tee.example.com")
+        sent = b"".join(call.args[0] for call in conn.write.call_args_list)
+        assert sent.startswith(b"GET /v1/models HTTP/1.1\r\n")
+        assert b"Host: tee.example.com\r\n" in sent
+        assert b"X-Test: 1\r\n" in sent
+        conn.settimeout.assert_not_called()
+
+    def test_connection_close_response_stays_readable(self):
+        conn = fake_connection(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nuntil eof")
+        with patch("atlas.http_client.atls_connect", return_value=conn):
+            client = AtlsHTTPSConnection("tee.example.com", 8443, policy={})
+            client.request("GET", "/")
+            response = client.getresponse()
+
+            conn.close.assert_not_called()
+            assert response.read() == b"until eof"
+            response.close()
+            conn.close.assert_called_once()
+
+    def test_timeout_and_server_name(self):
+        conn = fake_connection(b"")
+        with patch("atlas.http_client.atls_connect", return_value=conn) as connect:
+            client = AtlsHTTPSConnection(
+                "10.0.0.5:8443",
+                policy={},
+                server_name="tee.example.com",
+                timeout=2.5,
+            )
+            client.connect()
+
+        assert connect.call_args.args[:3] == ("10.0.0.5", 8443, "tee.example.com")
+        conn.settimeout.assert_called_once_with(2.5)
+
+    def test_tunnels_are_refused(self):
+        client = AtlsHTTPSConnection("tee.example.com", policy={})
+        with pytest.raises(NotImplementedError):
+            client.set_tunnel("proxy.example.com", 3128)
@@ -0,0 +1,93 @@
+"""Tests for atlas._makefile (AtlsConnection.makefile)."""
+
+import io
+
+import pytest
+
+from atlas._makefile import AtlsConnectionIO, makefile
+
+
+class FakeConnection:
+    """Stands in for AtlsConnection: serves `data` in small reads, records writes."""
+
+    def __init__(self, data=b""):
+        self.data = data
+        self.written = b""
+
+    def readinto(self, buffer):
+        n = min(len(buffer), 4, len(self.data))
+        buffer[:n] = self.data[:n]
+        self.data = self.data[n:]
+        return n
+
+    def write(self, data):
+        self.written += data
+        return len(data)
+
+
+class TestMakefile:
+    """Tests for the makefile() helper."""
+
+    def test_binary_reader_supports_readline(self):
+        conn = FakeConnection(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+        f = makefile(conn, "rb")
+
+        assert f.readline() == b"HTTP/1.1 200 OK\r\n"
+        assert f.readline() == b"Content-Length: 2\r\n"
+        assert f.read() == b"\r\nok"
+
+    def test_text_reader(self):
+        conn = FakeConnection(b"hello\nworld\n")
+        f = makefile(conn, encoding="utf-8")
+
+        assert f.readline() == "hello\n"
+        assert f.mode == "r"
+
+    def test_binary_writer_flushes_to_connection(self):
+        conn = FakeConnection()
+        f = makefile(conn, "wb")
+
+        f.write(b"GET / HTTP/1.1\r\n")
+        assert conn.written == b""
+        f.flush()
+
+        assert conn.written == b"GET / HTTP/1.1\r\n"
+
+    def test_read_write_pair(self):
+        conn = FakeConnection(b"pong")
+        f = makefile(conn, "rwb")
+
+        f.write(b"ping")
+        f.flush()
+
+        assert f.read(4) == b"pong"
+        assert conn.written == b"ping"
+
+    def test_unbuffered_binary_returns_raw(self):
+        f = makefile(FakeConnection(), "rb", buffering=0)
+
+        assert isinstance(f, AtlsConnectionIO)
+
+    def test_unbuffered_text_is_rejected(self):
+        with pytest.raises(ValueError, match="unbuffered streams must be binary"):
+            makefile(FakeConnection(), "r", buffering=0)
+
+    def test_invalid_mode_is_rejected(self):
+        with pytest.raises(ValueError, match="invalid mode"):
+            makefile(FakeConnection(), "a")
+
+    def test_raw_io_enforces_direction(self):
+        raw = AtlsConnectionIO(FakeConnection(b"x"), "r")
+
+        assert raw.readable()
+        assert not raw.writable()
+        with pytest.raises(io.UnsupportedOperation):
+            raw.write(b"x")
+
+        raw = AtlsConnectionIO(FakeConnection(b"x"), "w")
+        with pytest.raises(io.UnsupportedOperation):
+            raw.readinto(bytearray(1))
+
+    def test_raw_io_rejects_invalid_mode(self):
+        with pytest.raises(ValueError, match="invalid mode"):
+            AtlsConnectionIO(FakeConnection(), "x")
@@ -63,7 +63,34 @@ class TestModuleInit:
         import atlas
 
         assert hasattr(atlas, "httpx")
+        assert hasattr(atlas, "HttpxTransport")
+        assert hasattr(atlas, "AsyncHttpxTransport")
         assert hasattr(atlas, "dstack_tdx_policy")
         assert hasattr(atlas, "dev_policy")
         assert hasattr(atlas, "merge_with_default_app_compose")
+        assert hasattr(atlas, "compose_hash")
+        assert hasattr(atlas, "expected_compose_event")
         assert hasattr(atlas, "AtlsVerificationError")
+        assert hasattr(atlas, "AtlasTimeout")
+        assert hasattr(atlas, "configure_collateral_cache")
+        assert hasattr(atlas, "prefetch_collateral")
+        assert hasattr(atlas, "clear_collateral_cache")
+        assert hasattr(atlas, "verify_quote")
+        assert hasattr(atlas, "websocket_connect")
+        assert hasattr(atlas, "HttpClient")
+
+    def test_exception_hierarchy(self):
+        """Test that aTLS errors share AtlasError and keep builtin bases."""
+        import atlas
+
+        assert issubclass(atlas.AtlasError, OSError)
+        assert issubclass(atlas.TcbStatusNotAllowed, atlas.AttestationRejected)
+        assert issubclass(atlas.MeasurementMismatch, atlas.AttestationRejected)
+        for cls in (
+            atlas.AttestationRejected,
+            atlas.CollateralError,
+            atlas.TlsError,
+            atlas.AtlasTimeout,
+        ):
+            assert issubclass(cls, atlas.AtlasError)
+        assert issubclass(atlas.AtlasTimeout, TimeoutError)
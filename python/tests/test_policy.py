@@ -1,10 +1,21 @@
 """Tests for atlas.policy module."""
 
+import hashlib
 import json
 
 import pytest
 
-from atlas.policy import dev_policy, dstack_tdx_policy, merge_with_default_app_compose
+from atlas.policy import (
+    DstackTdxPolicy,
+    ExpectedBootchain,
+    Policy,
+    compose_hash,
+    dev_policy,
+    dstack_tdx_policy,
+    expected_compose_event,
+    merge_with_default_app_compose,
+    policy_to_json,
+)
 
 
 class TestDstackTdxPolicy:
@@ -123,3 +134,87 @@ class TestMergeWithDefaultAppCompose:
         assert result["docker_compose_file"] == "my-compose.yml"
         # Defaults should still be present
         assert "runner" in result
+
+
+class TestComposeHash:
+    """Tests for compose_hash and expected_compose_event."""
+
+    def test_compose_hash_is_sha256_of_compact_json(self):
+        compose = {"runner": "docker-compose"}
+        expected = hashlib.sha256(b'{"runner":"docker-compose"}').hexdigest()
+        assert compose_hash(compose) == expected
+        assert compose_hash(json.dumps(compose)) == expected
+
+    def test_compose_hash_depends_on_compose(self):
+        base = merge_with_default_app_compose({})
+        changed = {**base, "docker_compose_file": "services: {}"}
+        assert compose_hash(base) != compose_hash(changed)
+
+    def test_expected_compose_event(self):
+        compose = merge_with_default_app_compose({})
+        event = expected_compose_event(compose)
+        assert event["event"] == "compose-hash"
+        assert event["imr"] == 3
+        assert event["event_type"] == 0x08000001
+        assert event["event_payload"] == compose_hash(compose)
+        assert len(event["digest"]) == 96
+
+    def test_invalid_json_raises(self):
+        with pytest.raises(ValueError):
+            compose_hash("{not json")
+
+
+class TestTypedPolicy:
+    """Tests for the Policy, DstackTdxPolicy and ExpectedBootchain classes."""
+
+    def test_dstack_tdx_policy_defaults(self):
+        policy = DstackTdxPolicy(disable_runtime_verification=True)
+        assert policy.allowed_tcb_status == ["UpToDate"]
+        assert policy.cache_collateral is False
+        assert policy.expected_bootchain is None
+
+    def test_dstack_tdx_policy_round_trips(self, bootchain, os_image_hash):
+        policy = DstackTdxPolicy(
+            expected_bootchain=ExpectedBootchain(**bootchain),
+            os_image_hash=os_image_hash,
+            app_compose={"docker_compose_file": "test-compose"},
+            allowed_tcb_status=["UpToDate", "SWHardeningNeeded"],
+        )
+
+        parsed = json.loads(policy.to_json())
+        assert parsed["type"] == "dstack_tdx"
+        assert parsed["expected_bootchain"] == bootchain
+        assert DstackTdxPolicy.from_json(policy.to_json()) == policy
+        assert policy.app_compose == {"docker_compose_file": "test-compose"}
+
+    def test_matches_dict_builder(self):
+        typed = DstackTdxPolicy.from_json(json.dumps(dev_policy()))
+        assert typed.allowed_tcb_status == dev_policy()["allowed_tcb_status"]
+        assert typed.disable_runtime_verification is True
+
+    def test_invalid_tcb_status_is_rejected(self):
+        with pytest.raises(ValueError, match="invalid TCB status"):
+            DstackTdxPolicy(allowed_tcb_status=["Bogus"])
+
+    def test_bootchain_requires_hex(self, bootchain):
+        with pytest.raises(ValueError, match="rtmr0 must be a lowercase hex"):
+            ExpectedBootchain(**{**bootchain, "rtmr0": "NOT-HEX"})
+
+    def test_bootchain_requires_os_image_hash(self, bootchain):
+        with pytest.raises(ValueError, match="must be provided together"):
+            DstackTdxPolicy(expected_bootchain=ExpectedBootchain(**bootchain))
+
+    def test_policy_wraps_dstack_tdx(self):
+        inner = DstackTdxPolicy(disable_runtime_verification=True)
+        policy = Policy(inner)
+
+        assert policy.type == "dstack_tdx"
+        assert policy.dstack_tdx == inner
+        assert Policy.from_json(policy.to_json()) == policy
+
+    def test_policy_to_json_accepts_dicts_and_classes(self):
+        inner = DstackTdxPolicy(disable_runtime_verification=True)
+
+        assert json.loads(policy_to_json(dev_policy()))["type"] == "dstack_tdx"
+        assert policy_to_json(inner) == inner.to_json()
+        assert policy_to_json(Policy(inner)) == inner.to_json()
@@ -0,0 +1,135 @@
+"""Tests for atlas.requests.AtlasHTTPAdapter."""
+
+import socket
+from unittest.mock import MagicMock, patch
+
+import pytest
+import requests
+
+from atlas.policy import dev_policy
+from atlas.requests import AtlasHTTPAdapter
+from atlas.requests.adapter import (
+    AtlsHTTPSConnection,
+    AtlsHTTPSConnectionPool,
+    AtlsSocket,
+)
+from atlas.verifiers.errors import AtlsVerificationError
+
+
+class TestAtlsSocket:
+    """Tests for the AtlsSocket class."""
+
+    def test_sendall_delegates_to_conn(self):
+        mock_conn = MagicMock()
+        sock = AtlsSocket(mock_conn)
+
+        sock.sendall(b"data")
+
+        mock_conn.write.assert_called_once_with(b"data")
+
+    def test_makefile_reads_from_conn(self):
+        mock_conn = MagicMock()
+        mock_conn.read.side_effect = [b"hello", b""]
+        sock = AtlsSocket(mock_conn)
+
+        assert sock.makefile("rb").read() == b"hello"
+
+    def test_close_is_idempotent(self):
+        mock_conn = MagicMock()
+        sock = AtlsSocket(mock_conn)
+
+        sock.close()
+        sock.shutdown(2)
+
+        mock_conn.close.assert_called_once()
+        assert sock.closed
+
+    def test_shutdown_write_half_closes_conn(self):
+        mock_conn = MagicMock()
+        sock = AtlsSocket(mock_conn)
+
+        sock.shutdown(socket.SHUT_WR)
+
+        mock_conn.shutdown_write.assert_called_once_with()
+        mock_conn.close.assert_not_called()
+        assert not sock.closed
+
+
+class TestAtlasHTTPAdapter:
+    """Tests for the AtlasHTTPAdapter class."""
+
+    def test_atls_host_gets_attested_pool(self):
+        adapter = AtlasHTTPAdapter(
+            atls_policy_per_hostname={"atls.example.com": dev_policy()}
+        )
+
+        pool = adapter.poolmanager.connection_from_host(
+            "atls.example.com", 443, scheme="https"
+        )
+
+        assert isinstance(pool, AtlsHTTPSConnectionPool)
+        assert pool.conn_kw["atls_policy"] == dev_policy()
+
+    def test_non_atls_host_uses_default_pool(self):
+        adapter = AtlasHTTPAdapter(
+            atls_policy_per_hostname={"atls.example.com": dev_policy()}
+        )
+
+        pool = adapter.poolmanager.connection_from_host(
+            "other.example.com", 443, scheme="https"
+        )
+
+        assert not isinstance(pool, AtlsHTTPSConnectionPool)
+
+    def test_connect_uses_rust(self):
+        conn = AtlsHTTPSConnection("atls.example.com", 443, atls_policy=dev_policy())
+
+        with patch("atlas.requests.adapter.atls_connect") as mock_connect:
+            mock_conn = MagicMock()
+            mock_conn.attestation = {"trusted": True}
+            mock_connect.return_value = mock_conn
+
+            conn.connect()
+
+        assert mock_connect.call_args.args[:3] == (
+            "atls.example.com",
+            443,
+            "atls.example.com",
+        )
+        assert isinstance(conn.sock, AtlsSocket)
+        assert conn.is_verified
+        assert conn.is_connected
+        assert conn.attestation == {"trusted": True}
+
+    def test_connection_failure_propagates_error_code(self):
+        conn = AtlsHTTPSConnection("tee.example.com", 443, atls_policy=dev_policy())
+
+        rust_error = OSError("atls handshake failed: TCB status OutOfDate not allowed")
+        rust_error.code = "TCB_STATUS_REJECTED"
+
+        with patch("atlas.requests.adapter.atls_connect") as mock_connect:
+            mock_connect.side_effect = rust_error
+            with pytest.raises(AtlsVerificationError) as exc_info:
+                conn.connect()
+
+        assert exc_info.value.code == "TCB_STATUS_REJECTED"
+
+    def test_build_response_exposes_attestation(self):
+        adapter = AtlasHTTPAdapter()
+        resp = MagicMock()
+        resp.connection.attestation = {"trusted": True}
+
+        response = adapter.build_response(requests.PreparedRequest(), resp)
+
+        assert response.attestation == {"trusted": True}
+
+    def test_proxy_is_refused_for_atls_host(self):
+        adapter = AtlasHTTPAdapter(
+            atls_policy_per_hostname={"atls.example.com": dev_policy()}
+        )
+        request = requests.Request("GET", "https://atls.example.com/").prepare()
+
+        with pytest.raises(AtlsVerificationError, match="HTTP proxy"):
+            adapter.get_connection_with_tls_context(
+                request, True, proxies={"https": "http://proxy.example.com:3128"}
+            )
@@ -0,0 +1,133 @@
+"""Tests for atls_accept() and ServerPolicy against a fake dstack guest agent."""
+
+import hashlib
+import json
+import socket
+import socketserver
+import ssl
+import threading
+from http.server import BaseHTTPRequestHandler
+from pathlib import Path
+
+import pytest
+
+from atlas._atlas import AtlasError, ServerPolicy, atls_accept
+
+# Self-signed for `localhost`, valid until 2126.
+CERT = Path(__file__).parent / "data" / "localhost-cert.pem"
+KEY = Path(__file__).parent / "data" / "localhost-key.pem"
+
+pytestmark = pytest.mark.skipif(
+    not hasattr(socketserver, "UnixStreamServer"), reason="needs Unix sockets"
+)
+
+
+@pytest.fixture
+def agent(tmp_path):
+    """A guest agent that answers GetQuote with the report data as the quote.
+
+    Yields (socket path, list of (path, body) requests received).
+    """
+    requests = []
+
+    class Handler(BaseHTTPRequestHandler):
+        def do_POST(self):
+            body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+            requests.append((self.path, body))
+            reply = {}
+            if self.path == "/GetQuote":
+                reply = {"quote": body["report_data"], "event_log": "[]"}
+            data = json.dumps(reply).encode()
+            self.send_response(200)
+            self.send_header("Content-Type", "application/json")
+            self.send_header("Content-Length", str(len(data)))
+            self.end_headers()
+            self.wfile.write(data)
+
+        def log_message(self, *args):
+            pass
+
+    path = tmp_path / "dstack.sock"
+    server = socketserver.UnixStreamServer(str(path), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    yield path, requests
+    server.shutdown()
+    server.server_close()
+
+
+def quote_client(port, result):
+    """Request a quote over TLS, then exchange ping/pong, recording into `result`."""
+    context = ssl.create_default_context(cafile=str(CERT))
+    with socket.create_connection(("127.0.0.1", port)) as raw:
+        with context.wrap_socket(raw, server_hostname="localhost") as tls:
+            body = json.dumps({"nonce_hex": "07" * 32}).encode()
+            tls.sendall(
+                b"POST /tdx_quote HTTP/1.1\r\nHost: localhost\r\n"
+                b"Content-Type: application/json\r\n"
+                b"Content-Length: %d\r\n\r\n" % len(body) + body
+            )
+            response = tls.makefile("rb")
+            result["status"] = response.readline()
+            headers = {}
+            while (line := response.readline()) != b"\r\n":
+                name, value = line.decode().split(":", 1)
+                headers[name.strip().lower()] = value.strip()
+            result["body"] = json.loads(response.read(int(headers["content-length"])))
+
+            tls.sendall(b"ping")
+            result["pong"] = response.read(4)
+
+
+class TestAtlsAccept:
+    """The server side of aTLS, from a plain TLS client's point of view."""
+
+    def test_serves_quote_then_application_data(self, agent):
+        agent_socket, requests = agent
+        policy = ServerPolicy(CERT, KEY, agent_socket=agent_socket)
+        result = {}
+
+        with socket.create_server(("127.0.0.1", 0)) as listener:
+            client = threading.Thread(
+                target=quote_client, args=(listener.getsockname()[1], result)
+            )
+            client.start()
+            with atls_accept(listener, policy, timeout=10) as conn:
+                assert conn.read_exact(4, timeout=10) == b"ping"
+                conn.write(b"pong")
+                with pytest.raises(AtlasError, match="not attested"):
+                    conn.attestation
+            client.join(timeout=10)
+
+        assert result["status"].startswith(b"HTTP/1.1 200")
+        [(path, body)] = requests
+        assert path == "/GetQuote"
+        assert len(body["report_data"]) == 128
+        assert result["body"] == {
+            "quote": {"quote": body["report_data"], "event_log": "[]"}
+        }
+        assert result["pong"] == b"pong"
+
+    def test_register_certificate_emits_its_hash(self, agent):
+        agent_socket, requests = agent
+        policy = ServerPolicy(CERT, KEY, agent_socket=agent_socket)
+
+        policy.register_certificate()
+
+        der = ssl.PEM_cert_to_DER_cert(CERT.read_text())
+        cert_hash = hashlib.sha256(der).hexdigest()
+        assert requests == [
+            (
+                "/EmitEvent",
+                {"event": "New TLS Certificate", "payload": cert_hash.encode().hex()},
+            )
+        ]
+
+    def test_rejects_non_tcp_socket(self, agent):
+        policy = ServerPolicy(CERT, KEY, agent_socket=agent[0])
+        left, right = socket.socketpair()
+        with left, right, pytest.raises(ValueError, match="TCP socket"):
+            atls_accept(left, policy)
+
+    def test_missing_certificate_file(self, tmp_path):
+        with pytest.raises(FileNotFoundError):
+            ServerPolicy(tmp_path / "missing.pem", KEY)
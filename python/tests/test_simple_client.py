@@ -0,0 +1,133 @@
+"""Tests for atlas.client.HttpClient."""
+
+import http.client
+import io
+from unittest.mock import MagicMock, patch
+
+import pytest
+
+from atlas.client import HttpClient, HttpResponse
+from atlas.policy import dev_policy
+
+
+class FakeConnection:
+    """An AtlsConnection that answers each request with the next response."""
+
+    def __init__(self, *responses):
+        self.attestation = {"trusted": True, "tee_type": "tdx"}
+        self.write = MagicMock(side_effect=self._write)
+        self.close = MagicMock()
+        self.settimeout = MagicMock()
+        self._responses = list(responses)
+        self._pending = io.BytesIO()
+
+    def _write(self, data):
+        # Release a response once the request line has been sent, as a
+        # server would; buffered readers must not see it earlier.
+        if b" HTTP/1.1\r\n" in data and self._responses:
+            self._pending = io.BytesIO(self._responses.pop(0))
+
+    def read(self, size):
+        return self._pending.read(size)
+
+    def readinto(self, buf):
+        return self._pending.readinto(buf)
+
+
+def sent(conn):
+    return b"".join(call.args[0] for call in conn.write.call_args_list)
+
+
+class TestHttpClient:
+    """Tests for requests and streamed responses."""
+
+    def test_get_keeps_connection_alive(self):
+        conn = FakeConnection(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n"
+            b'Content-Length: 11\r\n\r\n{"ok":true}',
+        )
+        with patch("atlas.http_client.atls_connect", return_value=conn) as connect:
+            with HttpClient("tee.example.com", 443, dev_policy()) as client:
+                response = client.get("/health", headers={"X-Test": "1"})
+                assert isinstance(response, HttpResponse)
+                assert response.status == 200
+                assert response.text() == "hello"
+                assert response.attestation == conn.attestation
+                assert client.get("/v1/models").json() == {"ok": True}
+
+        connect.assert_called_once()
+        assert b"GET /health HTTP/1.1\r\n" in sent(conn)
+        assert b"X-Test: 1\r\n" in sent(conn)
+        assert b"GET /v1/models HTTP/1.1\r\n" in sent(conn)
+        conn.close.assert_called_once()
+
+    def test_post_json(self):
+        conn = FakeConnection(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+        with patch("atlas.http_client.atls_connect", return_value=conn):
+            client = HttpClient(
+                "tee.example.com", 8443, {}, headers={"Authorization": "Bearer t"}
+            )
+            response = client.post("/v1/items", json={"name": "x"})
+
+        assert response.status == 201
+        request = sent(conn)
+        assert request.startswith(b"POST /v1/items HTTP/1.1\r\n")
+        assert b"Authorization: Bearer t\r\n" in request
+        assert b"Content-Type: application/json\r\n" in request
+        assert request.endswith(b'\r\n\r\n{"name": "x"}')
+
+    def test_streams_chunked_body(self):
+        conn = FakeConnection(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"
+            b"7\r\ndata: a\r\n1\r\n\n\r\n8\r\ndata: b\n\r\n0\r\n\r\n"
+        )
+        with patch("atlas.http_client.atls_connect", return_value=conn):
+            client = HttpClient("tee.example.com", 443, {})
+            with client.post("/v1/chat", body=b"{}") as response:
+                assert list(response.iter_lines()) == [b"data: a", b"data: b"]
+        conn.close.assert_not_called()
+
+    def test_abandoned_body_closes_connection(self):
+        conn = FakeConnection(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\npartial")
+        with patch("atlas.http_client.atls_connect", return_value=conn):
+            client = HttpClient("tee.example.com", 443, {})
+            with client.get("/big") as response:
+                assert next(iter(response)) == b"partial"
+        conn.close.assert_called_once()
+
+    def test_retries_idempotent_request_on_stale_connection(self):
+        stale = FakeConnection(b"HTTP/1.1 204 No Content\r\n\r\n")
+        fresh = FakeConnection(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+        with patch("atlas.http_client.atls_connect", side_effect=[stale, fresh]):
+            client = HttpClient("tee.example.com", 443, {})
+            assert client.get("/first").status == 204
+            # The server dropped the idle connection: the next read sees EOF.
+            assert client.get("/second").read() == b"ok"
+        assert b"GET /second" in sent(fresh)
+
+    def test_post_is_not_retried(self):
+        stale = FakeConnection(b"HTTP/1.1 204 No Content\r\n\r\n")
+        with patch("atlas.http_client.atls_connect", return_value=stale):
+            client = HttpClient("tee.example.com", 443, {})
+            client.get("/first")
+            with pytest.raises(http.client.RemoteDisconnected):
+                client.post("/second", body=b"x")
+        stale.close.assert_called()
+
+
+
+    def test_method_helpers(self):
+        conn = FakeConnection(*[b"HTTP/1.1 204 No Content\r\n\r\n"] * 4)
+        with patch("atlas.http_client.atls_connect", return_value=conn):
+            client = HttpClient("tee.example.com", 443, {})
+            responses = [
+                client.head("/a"),
+                client.put("/b", body="x"),
+                client.patch("/c", body=b"y"),
+                client.delete("/d"),
+            ]
+
+        assert repr(responses[0]) == "<HttpResponse [204]>"
+        for line in (b"HEAD /a ", b"PUT /b ", b"PATCH /c ", b"DELETE /d "):
+            assert line in sent(conn)
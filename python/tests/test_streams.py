@@ -0,0 +1,64 @@
+"""Tests for atlas.streams (asyncio StreamReader/StreamWriter over aTLS)."""
+
+import asyncio
+import socket
+from unittest.mock import patch
+
+from atlas.policy import dev_policy
+from atlas.streams import open_connection
+
+
+class TestOpenConnection:
+    """Tests for open_connection() and the aTLS transport."""
+
+    def run_with_connection(self, conn, scenario):
+        with patch("atlas.streams.atls_connect", return_value=conn) as mock_connect:
+            asyncio.run(scenario())
+        return conn, mock_connect
+
+    def test_returns_streams_and_attestation(self, upper_echo_connection):
+        async def scenario():
+            reader, writer, attestation = await open_connection(
+                "tee.example.com", 443, dev_policy()
+            )
+            assert attestation == {"trusted": True, "tee_type": "tdx"}
+            assert writer.get_extra_info("attestation") == attestation
+
+            writer.write(b"hello\nworld\n")
+            await writer.drain()
+            assert await reader.readline() == b"HELLO\n"
+            assert await reader.readline() == b"WORLD\n"
+
+            writer.close()
+            await writer.wait_closed()
+
+        conn, mock_connect = self.run_with_connection(upper_echo_connection, scenario)
+
+        assert mock_connect.call_args.args[:3] == (
+            "tee.example.com",
+            443,
+            "tee.example.com",
+        )
+        assert conn.closed.is_set()
+
+    def test_large_writes_apply_backpressure(self, upper_echo_connection):
+        async def scenario():
+            reader, writer, _ = await open_connection("tee.example.com", 443, {})
+            writer.write(b"x" * 300_000)
+            assert writer.transport.get_write_buffer_size() > 0
+            await writer.drain()
+            assert await reader.readexactly(300_000) == b"X" * 300_000
+            writer.close()
+            await writer.wait_closed()
+
+        self.run_with_connection(upper_echo_connection, scenario)
+
+    def test_peer_close_is_eof(self, upper_echo_connection):
+        async def scenario():
+            reader, writer, _ = await open_connection("tee.example.com", 443, {})
+            writer.transport._conn.sock.shutdown(socket.SHUT_WR)
+            assert await reader.read() == b""
+            writer.close()
+            await writer.wait_closed()
+
+        self.run_with_connection(upper_echo_connection, scenario)
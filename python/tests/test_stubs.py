@@ -0,0 +1,101 @@
+"""Check that the bundled _atlas.pyi matches the compiled extension module."""
+
+import ast
+import inspect
+from pathlib import Path
+
+import pytest
+
+from atlas import _atlas
+
+STUB_PATH = Path(_atlas.__file__).with_name("_atlas.pyi")
+if not STUB_PATH.exists():
+    STUB_PATH = Path(__file__).parents[1] / "src" / "atlas" / "_atlas.pyi"
+
+
+def stub_signature(node):
+    """(positional names, keyword-only names, names with defaults) of a stub def."""
+    args = node.args
+    positional = [a.arg for a in args.posonlyargs + args.args]
+    defaults = set(positional[len(positional) - len(args.defaults) :])
+    defaults |= {a.arg for a, d in zip(args.kwonlyargs, args.kw_defaults) if d}
+    return positional, [a.arg for a in args.kwonlyargs], defaults
+
+
+def runtime_signature(obj):
+    """The same triple for a PyO3 function, method or constructor."""
+    positional, keyword_only, defaults = [], [], set()
+    for param in inspect.signature(obj).parameters.values():
+        if param.kind is param.KEYWORD_ONLY:
+            keyword_only.append(param.name)
+        else:
+            positional.append(param.name)
+        if param.default is not param.empty:
+            defaults.add(param.name)
+    return positional, keyword_only, defaults
+
+
+def load_stubs():
+    """Top-level functions and classes (with their methods) of the stub file."""
+    tree = ast.parse(STUB_PATH.read_text())
+    functions, classes = {}, {}
+    for node in tree.body:
+        if isinstance(node, ast.FunctionDef):
+            functions[node.name] = node
+        elif isinstance(node, ast.ClassDef):
+            bases = {ast.unparse(base) for base in node.bases}
+            methods = {
+                item.name: item
+                for item in node.body
+                if isinstance(item, ast.FunctionDef)
+                and "property" not in map(ast.unparse, item.decorator_list)
+            }
+            classes[node.name] = (bases, methods)
+    return functions, classes
+
+
+def public_names():
+    return {name for name in dir(_atlas) if not name.startswith("_")}
+
+
+class TestStubs:
+    """The stubs cover the compiled module, with the same signatures."""
+
+    def test_every_runtime_name_has_a_stub(self):
+        functions, classes = load_stubs()
+        missing = public_names() - set(functions) - set(classes)
+        assert not missing, f"add stubs to _atlas.pyi for: {sorted(missing)}"
+
+    def test_every_stub_exists_at_runtime(self):
+        functions, classes = load_stubs()
+        typed_dicts = {n for n, (bases, _) in classes.items() if "TypedDict" in bases}
+        stale = set(functions) | (set(classes) - typed_dicts)
+        stale -= public_names()
+        assert not stale, f"_atlas.pyi declares names the module lacks: {sorted(stale)}"
+
+    @pytest.mark.parametrize("name", sorted(load_stubs()[0]))
+    def test_function_signature(self, name):
+        node = load_stubs()[0][name]
+        assert stub_signature(node) == runtime_signature(getattr(_atlas, name))
+
+    def test_method_signatures(self):
+        _, classes = load_stubs()
+        for class_name, (_, methods) in classes.items():
+            cls = getattr(_atlas, class_name, None)
+            if cls is None or issubclass(cls, BaseException):
+                continue
+            for method_name, node in methods.items():
+                if method_name.startswith("__") and method_name != "__init__":
+                    continue
+                expected = stub_signature(node)
+                if "staticmethod" in map(ast.unparse, node.decorator_list):
+                    actual = runtime_signature(getattr(cls, method_name))
+                elif method_name == "__init__":
+                    # A PyO3 constructor's signature is the class's, without `self`.
+                    if getattr(cls, "__text_signature__", None) is None:
+                        continue
+                    actual = runtime_signature(cls)
+                    expected = (expected[0][1:], *expected[1:])
+                else:
+                    actual = runtime_signature(getattr(cls, method_name))
+                assert expected == actual, f"{class_name}.{method_name}"
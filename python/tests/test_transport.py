@@ -1,10 +1,18 @@
 """Tests for atlas.httpx.transport module."""
 
-from unittest.mock import MagicMock, patch
+from unittest.mock import AsyncMock, MagicMock, patch
 
+import httpx
 import pytest
 
-from atlas.httpx.transport import AtlsNetworkBackend, AtlsNetworkStream
+from atlas.httpx.transport import (
+    AsyncAtlsNetworkBackend,
+    AsyncAtlsNetworkStream,
+    AsyncHttpxTransport,
+    AtlsNetworkBackend,
+    AtlsNetworkStream,
+    HttpxTransport,
+)
 from atlas.policy import dev_policy
 from atlas.verifiers.errors import AtlsVerificationError
 
@@ -104,6 +112,33 @@ class TestAtlsNetworkBackend:
             with pytest.raises(AtlsVerificationError, match="aTLS connection"):
                 backend.connect_tcp("unreachable.example.com", 443)
 
+    def test_connection_failure_propagates_error_code(self):
+        backend = AtlsNetworkBackend(
+            policies={"tee.example.com": dev_policy()},
+        )
+
+        rust_error = OSError("atls handshake failed: TCB status OutOfDate not allowed")
+        rust_error.code = "TCB_STATUS_REJECTED"
+
+        with patch("atlas.httpx.transport.atls_connect") as mock_connect:
+            mock_connect.side_effect = rust_error
+            with pytest.raises(AtlsVerificationError) as exc_info:
+                backend.connect_tcp("tee.example.com", 443)
+
+        assert exc_info.value.code == "TCB_STATUS_REJECTED"
+
+    def test_connection_failure_without_code(self):
+        backend = AtlsNetworkBackend(
+            policies={"tee.example.com": dev_policy()},
+        )
+
+        with patch("atlas.httpx.transport.atls_connect") as mock_connect:
+            mock_connect.side_effect = ConnectionError("connection refused")
+            with pytest.raises(AtlsVerificationError) as exc_info:
+                backend.connect_tcp("tee.example.com", 443)
+
+        assert exc_info.value.code is None
+
     def test_empty_policies_always_delegates(self):
         mock_default = MagicMock()
         backend = AtlsNetworkBackend(policies={}, default_backend=mock_default)
@@ -111,3 +146,110 @@ class TestAtlsNetworkBackend:
         backend.connect_tcp("any.example.com", 443)
 
         mock_default.connect_tcp.assert_called_once()
+
+
+class TestAsyncAtlsNetworkBackend:
+    """Tests for the AsyncAtlsNetworkStream and AsyncAtlsNetworkBackend classes."""
+
+    @pytest.mark.anyio
+    async def test_stream_delegates_to_conn(self):
+        mock_conn = MagicMock()
+        mock_conn.read.return_value = b"hello"
+        stream = AsyncAtlsNetworkStream(mock_conn)
+
+        assert await stream.read(1024) == b"hello"
+        await stream.write(b"data")
+        await stream.aclose()
+
+        mock_conn.read.assert_called_once_with(1024)
+        mock_conn.write.assert_called_once_with(b"data")
+        mock_conn.close.assert_called_once()
+
+    @pytest.mark.anyio
+    async def test_non_atls_host_delegates_to_default(self):
+        mock_default = MagicMock()
+        mock_default.connect_tcp = AsyncMock(return_value="default-stream")
+        backend = AsyncAtlsNetworkBackend(
+            policies={"atls.example.com": dev_policy()},
+            default_backend=mock_default,
+        )
+
+        result = await backend.connect_tcp("other.example.com", 443)
+
+        assert result == "default-stream"
+
+    @pytest.mark.anyio
+    async def test_atls_host_connects_via_rust(self):
+        backend = AsyncAtlsNetworkBackend(policies={"atls.example.com": dev_policy()})
+
+        with patch("atlas.httpx.transport.atls_connect") as mock_connect:
+            mock_conn = MagicMock()
+            mock_connect.return_value = mock_conn
+
+            result = await backend.connect_tcp("atls.example.com", 443)
+
+        assert isinstance(result, AsyncAtlsNetworkStream)
+        assert result._conn is mock_conn
+
+    @pytest.mark.anyio
+    async def test_connection_failure_propagates_error_code(self):
+        backend = AsyncAtlsNetworkBackend(policies={"tee.example.com": dev_policy()})
+
+        rust_error = OSError("atls handshake failed: TCB status OutOfDate not allowed")
+        rust_error.code = "TCB_STATUS_REJECTED"
+
+        with patch("atlas.httpx.transport.atls_connect") as mock_connect:
+            mock_connect.side_effect = rust_error
+            with pytest.raises(AtlsVerificationError) as exc_info:
+                await backend.connect_tcp("tee.example.com", 443)
+
+        assert exc_info.value.code == "TCB_STATUS_REJECTED"
+
+
+class TestHttpxTransport:
+    """Tests for the HttpxTransport and AsyncHttpxTransport classes."""
+
+    def test_transport_injects_atls_backend(self):
+        transport = HttpxTransport(atls_policy_per_hostname={"a.example.com": {}})
+
+        assert isinstance(transport, httpx.HTTPTransport)
+        assert isinstance(transport._pool._network_backend, AtlsNetworkBackend)
+
+    def test_async_transport_injects_atls_backend(self):
+        transport = AsyncHttpxTransport(atls_policy_per_hostname={"a.example.com": {}})
+
+        assert isinstance(transport, httpx.AsyncHTTPTransport)
+        assert isinstance(transport._pool._network_backend, AsyncAtlsNetworkBackend)
+
+    def test_handle_request_populates_attestation_extension(self):
+        mock_conn = MagicMock()
+        mock_conn.attestation = {"trusted": True}
+        response = httpx.Response(
+            200, extensions={"network_stream": AtlsNetworkStream(mock_conn)}
+        )
+        transport = HttpxTransport()
+
+        with patch.object(httpx.HTTPTransport, "handle_request", return_value=response):
+            result = transport.handle_request(
+                httpx.Request("GET", "https://a.example.com/")
+            )
+
+        assert result.extensions["attestation"] == {"trusted": True}
+
+    @pytest.mark.anyio
+    async def test_handle_async_request_populates_attestation_extension(self):
+        mock_conn = MagicMock()
+        mock_conn.attestation = {"trusted": True}
+        response = httpx.Response(
+            200, extensions={"network_stream": AsyncAtlsNetworkStream(mock_conn)}
+        )
+        transport = AsyncHttpxTransport()
+
+        with patch.object(
+            httpx.AsyncHTTPTransport, "handle_async_request", return_value=response
+        ):
+            result = await transport.handle_async_request(
+                httpx.Request("GET", "https://a.example.com/")
+            )
+
+        assert result.extensions["attestation"] == {"trusted": True}
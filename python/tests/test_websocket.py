@@ -0,0 +1,202 @@
+"""Tests for atlas.websocket."""
+
+import base64
+import hashlib
+import struct
+from unittest.mock import MagicMock, patch
+
+import pytest
+
+from atlas.policy import dev_policy
+from atlas.websocket import (
+    AtlsWebSocket,
+    ConnectionClosed,
+    WebSocketError,
+    websocket_connect,
+)
+
+GUID = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11"
+
+
+def server_frame(opcode, payload, fin=True):
+    """An unmasked server-to-client frame."""
+    header = bytes([(0x80 if fin else 0) | opcode])
+    if len(payload) < 126:
+        header += bytes([len(payload)])
+    else:
+        header += bytes([126]) + struct.pack("!H", len(payload))
+    return header + payload
+
+
+def parse_client_frames(data):
+    """Decode masked client frames into (opcode, payload) pairs."""
+    frames = []
+    while data:
+        opcode, length = data[0] & 0x0F, data[1] & 0x7F
+        offset = 2
+        if length == 126:
+            (length,) = struct.unpack("!H", data[2:4])
+            offset = 4
+        assert data[1] & 0x80, "client frames must be masked"
+        mask = data[offset : offset + 4]
+        payload = data[offset + 4 : offset + 4 + length]
+        frames.append((opcode, bytes(b ^ mask[i % 4] for i, b in enumerate(payload))))
+        data = data[offset + 4 + length :]
+    return frames
+
+
+class FakeConnection:
+    """An AtlsConnection that answers the upgrade request, then `frames`."""
+
+    def __init__(self, frames=b"", status=b"101 Switching Protocols", extra=b""):
+        self.attestation = {"trusted": True, "tee_type": "tdx"}
+        self.request = None
+        self.written = b""
+        self.closed = False
+        self._status = status
+        self._extra = extra
+        self._frames = frames
+        self._pending = b""
+
+    def write(self, data):
+        if self.request is None:
+            self.request = data
+            key = next(
+                line.split(b": ", 1)[1]
+                for line in data.split(b"\r\n")
+                if line.startswith(b"Sec-WebSocket-Key:")
+            )
+            accept = base64.b64encode(hashlib.sha1(key + GUID).digest())
+            self._pending = (
+                b"HTTP/1.1 " + self._status + b"\r\n"
+                b"Upgrade: websocket\r\nConnection: Upgrade\r\n"
+                b"Sec-WebSocket-Accept: " + accept + b"\r\n" + self._extra + b"\r\n"
+                + self._frames
+            )
+        else:
+            self.written += data
+
+    def read(self, size):
+        chunk, self._pending = self._pending[:size], self._pending[size:]
+        return chunk
+
+    def close(self):
+        self.closed = True
+
+
+def connect(conn, **kwargs):
+    with patch("atlas.websocket.atls_connect", return_value=conn) as atls_connect:
+        ws = websocket_connect(
+            "tee.example.com", 443, "/v1/realtime", dev_policy(), **kwargs
+        )
+    return ws, atls_connect
+
+
+class TestHandshake:
+    """Tests for the HTTP Upgrade handshake."""
+
+    def test_upgrade_request(self):
+        conn = FakeConnection()
+        ws, atls_connect = connect(conn, headers={"Authorization": "Bearer t"})
+
+        assert isinstance(ws, AtlsWebSocket)
+        assert ws.attestation == conn.attestation
+        assert atls_connect.call_args.args[:3] == (
+            "tee.example.com",
+            443,
+            "tee.example.com",
+        )
+        assert conn.request.startswith(b"GET /v1/realtime HTTP/1.1\r\n")
+        assert b"Host: tee.example.com\r\n" in conn.request
+        assert b"Upgrade: websocket\r\n" in conn.request
+        assert b"Sec-WebSocket-Version: 13\r\n" in conn.request
+        assert b"Authorization: Bearer t\r\n" in conn.request
+
+    def test_rejected_upgrade_closes_connection(self):
+        conn = FakeConnection(status=b"403 Forbidden")
+        with pytest.raises(WebSocketError, match="403"):
+            connect(conn)
+        assert conn.closed
+
+    def test_bad_accept_key(self):
+        conn = FakeConnection()
+        conn.write = MagicMock()
+        conn._pending = (
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n"
+            b"Sec-WebSocket-Accept: bogus\r\n\r\n"
+        )
+        with pytest.raises(WebSocketError, match="Sec-WebSocket-Accept"):
+            connect(conn)
+        assert conn.closed
+
+    def test_subprotocol(self):
+        conn = FakeConnection(extra=b"Sec-WebSocket-Protocol: chat\r\n")
+        ws, _ = connect(conn, subprotocols=["chat", "v2"])
+        assert b"Sec-WebSocket-Protocol: chat, v2\r\n" in conn.request
+        assert ws.subprotocol == "chat"
+
+
+class TestMessages:
+    """Tests for framing, control frames and closing."""
+
+    def test_send_text_and_binary(self):
+        conn = FakeConnection()
+        ws, _ = connect(conn)
+        ws.send("héllo")
+        ws.send(b"\x00" * 300)
+        assert parse_client_frames(conn.written) == [
+            (0x1, "héllo".encode()),
+            (0x2, b"\x00" * 300),
+        ]
+
+    def test_recv_reassembles_fragments_and_answers_pings(self):
+        frames = (
+            server_frame(0x1, b"hel", fin=False)
+            + server_frame(0x9, b"ping")
+            + server_frame(0x0, b"lo")
+            + server_frame(0x2, b"\x01\x02")
+        )
+        conn = FakeConnection(frames)
+        ws, _ = connect(conn)
+
+        assert ws.recv() == "hello"
+        assert ws.recv() == b"\x01\x02"
+        assert parse_client_frames(conn.written) == [(0xA, b"ping")]
+
+    def test_server_close(self):
+        conn = FakeConnection(server_frame(0x8, struct.pack("!H", 1001) + b"bye"))
+        ws, _ = connect(conn)
+
+        with pytest.raises(ConnectionClosed) as exc_info:
+            ws.recv()
+        assert (exc_info.value.code, exc_info.value.reason) == (1001, "bye")
+        assert parse_client_frames(conn.written) == [(0x8, struct.pack("!H", 1001))]
+        assert conn.closed
+        with pytest.raises(ConnectionClosed):
+            ws.send("late")
+
+    def test_client_close(self):
+        conn = FakeConnection(
+            server_frame(0x1, b"in flight") + server_frame(0x8, struct.pack("!H", 1000))
+        )
+        with connect(conn)[0] as ws:
+            pass
+        assert parse_client_frames(conn.written) == [(0x8, struct.pack("!H", 1000))]
+        assert conn.closed
+        ws.close()  # idempotent
+
+    def test_message_too_big(self):
+        conn = FakeConnection(server_frame(0x2, b"x" * 200))
+        ws, _ = connect(conn, max_message_size=100)
+        with pytest.raises(WebSocketError, match="too big"):
+            ws.recv()
+        close_payload = struct.pack("!H", 1009) + b"message too big"
+        assert parse_client_frames(conn.written)[0] == (0x8, close_payload)
+        assert conn.closed
+
+    def test_connection_lost(self):
+        conn = FakeConnection(server_frame(0x1, b"partial")[:4])
+        ws, _ = connect(conn)
+        with pytest.raises(ConnectionClosed) as exc_info:
+            ws.recv()
+        assert exc_info.value.code == 1006
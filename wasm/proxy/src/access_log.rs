@@ -0,0 +1,224 @@
+//! Access log: one record per client connection, written when it closes, for
+//! audits of what went through the proxy.
+//!
+//! Configured by the `[access_log]` section; off unless `path` is set (`-`
+//! for stdout). Each record gives the client address, the kind of tunnel
+//! (`tcp`, `udp` or `mux`) and its target, when it opened, how long it lasted,
+//! the bytes received from and sent to the client, and why it closed. With
+//! `format = "json"` (the default) a record is one JSON object per line:
+//!
+//! ```text
+//! {"time":"2026-10-16T02:38:07.123Z","client":"203.0.113.7:51514","kind":"tcp","target":"tee1.example.com:443","status":200,"reason":"client closed","duration_ms":1834,"bytes_in":5120,"bytes_out":81920}
+//! ```
+//!
+//! With `format = "clf"` it is a Common Log Format line, the request being
+//! the tunnel kind and target, followed by the bytes received, the duration
+//! in milliseconds and the reason:
+//!
+//! ```text
+//! 203.0.113.7 - - [16/Oct/2026:02:38:07 +0000] "TCP tee1.example.com:443" 200 81920 5120 1834 "client closed"
+//! ```
+//!
+//! Tokens and tunnel contents are never logged. Multiplexed sessions get one
+//! record; their streams' targets are in the `tracing` log.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{AccessLogConfig, AccessLogFormat};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Why a connection closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    ClientClosed,
+    TargetClosed,
+    IdleTimeout,
+    MaxLifetime,
+    HandshakeFailed,
+    Unauthorized,
+    ClientNotAllowed,
+    ClientLimited,
+    TargetNotAllowed,
+    TargetUnreachable,
+    AttestationFailed,
+    Error,
+}
+
+impl CloseReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ClientClosed => "client closed",
+            Self::TargetClosed => "target closed",
+            Self::IdleTimeout => "idle timeout",
+            Self::MaxLifetime => "maximum lifetime reached",
+            Self::HandshakeFailed => "handshake failed",
+            Self::Unauthorized => "unauthorized",
+            Self::ClientNotAllowed => "client not allowed",
+            Self::ClientLimited => "client limit reached",
+            Self::TargetNotAllowed => "target not allowed",
+            Self::TargetUnreachable => "target unreachable",
+            Self::AttestationFailed => "attestation failed",
+            Self::Error => "error",
+        }
+    }
+
+    /// The HTTP status closest to this outcome, for the `status` field.
+    pub fn status(self) -> u16 {
+        match self {
+            Self::ClientClosed | Self::TargetClosed | Self::IdleTimeout | Self::MaxLifetime => 200,
+            Self::HandshakeFailed => 400,
+            Self::Unauthorized => 401,
+            Self::ClientNotAllowed | Self::TargetNotAllowed => 403,
+            Self::ClientLimited => 429,
+            Self::TargetUnreachable | Self::AttestationFailed => 502,
+            Self::Error => 500,
+        }
+    }
+}
+
+/// One closed connection.
+#[derive(Debug, Clone)]
+pub struct AccessRecord {
+    pub client: SocketAddr,
+    /// `tcp`, `udp` or `mux`; `None` if the client never got a tunnel.
+    pub kind: Option<&'static str>,
+    pub target: Option<String>,
+    pub started: SystemTime,
+    pub duration: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub reason: CloseReason,
+}
+
+/// Writes access records to a file or stdout.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    /// Open the log `config` describes, or `None` when it is off.
+    pub fn from_config(config: &AccessLogConfig) -> Result<Option<Self>, BoxError> {
+        let Some(path) = &config.path else {
+            return Ok(None);
+        };
+        let out: Box<dyn Write + Send> = if path.as_os_str() == "-" {
+            Box::new(io::stdout())
+        } else {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("failed to open access log {}: {e}", path.display()))?;
+            Box::new(file)
+        };
+        Ok(Some(Self {
+            format: config.format,
+            out: Mutex::new(out),
+        }))
+    }
+
+    pub fn write(&self, record: &AccessRecord) {
+        let mut line = match self.format {
+            AccessLogFormat::Json => json_line(record),
+            AccessLogFormat::Clf => clf_line(record),
+        };
+        line.push('\n');
+        let Ok(mut out) = self.out.lock() else {
+            return;
+        };
+        if let Err(e) = out.write_all(line.as_bytes()).and_then(|()| out.flush()) {
+            tracing::warn!(error = %e, "failed to write the access log");
+        }
+    }
+}
+
+fn json_line(record: &AccessRecord) -> String {
+    let time = DateTime::<Utc>::from(record.started).to_rfc3339_opts(SecondsFormat::Millis, true);
+    serde_json::json!({
+        "time": time,
+        "client": record.client.to_string(),
+        "kind": record.kind,
+        "target": record.target,
+        "status": record.reason.status(),
+        "reason": record.reason.as_str(),
+        "duration_ms": record.duration.as_millis() as u64,
+        "bytes_in": record.bytes_in,
+        "bytes_out": record.bytes_out,
+    })
+    .to_string()
+}
+
+fn clf_line(record: &AccessRecord) -> String {
+    let time = DateTime::<Utc>::from(record.started).format("%d/%b/%Y:%H:%M:%S %z");
+    let request = match (record.kind, &record.target) {
+        (Some(kind), Some(target)) => format!("{} {target}", kind.to_uppercase()),
+        (Some(kind), None) => kind.to_uppercase(),
+        (None, _) => "-".to_string(),
+    };
+    format!(
+        "{} - - [{time}] \"{request}\" {} {} {} {} \"{}\"",
+        record.client.ip(),
+        record.reason.status(),
+        record.bytes_out,
+        record.bytes_in,
+        record.duration.as_millis(),
+        record.reason.as_str(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> AccessRecord {
+        AccessRecord {
+            client: "203.0.113.7:51514".parse().unwrap(),
+            kind: Some("tcp"),
+            target: Some("tee1.example.com:443".to_string()),
+            started: SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_118_287),
+            duration: Duration::from_millis(1834),
+            bytes_in: 5120,
+            bytes_out: 81920,
+            reason: CloseReason::ClientClosed,
+        }
+    }
+
+    #[test]
+    fn test_json_line() {
+        let line: serde_json::Value = serde_json::from_str(&json_line(&record())).unwrap();
+        assert_eq!(line["time"], "2026-10-16T02:38:07.000Z");
+        assert_eq!(line["client"], "203.0.113.7:51514");
+        assert_eq!(line["kind"], "tcp");
+        assert_eq!(line["target"], "tee1.example.com:443");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["reason"], "client closed");
+        assert_eq!(line["duration_ms"], 1834);
+        assert_eq!(line["bytes_in"], 5120);
+        assert_eq!(line["bytes_out"], 81920);
+    }
+
+    #[test]
+    fn test_clf_line() {
+        assert_eq!(
+            clf_line(&record()),
+            "203.0.113.7 - - [16/Oct/2026:02:38:07 +0000] \"TCP tee1.example.com:443\" 200 81920 5120 1834 \"client closed\""
+        );
+
+        let rejected = AccessRecord {
+            kind: None,
+            target: None,
+            bytes_in: 0,
+            bytes_out: 0,
+            reason: CloseReason::Unauthorized,
+            ..record()
+        };
+        assert!(clf_line(&rejected).contains("\"-\" 401 0 0 1834 \"unauthorized\""));
+    }
+}
@@ -0,0 +1,367 @@
+//! Target allowlist.
+//!
+//! Each entry is `host:port`, where:
+//!
+//! - `host` is a hostname (`tee1.example.com`), a wildcard matching any
+//!   subdomain (`*.tee.example.com`, not `tee.example.com` itself), an IP
+//!   address (`10.0.0.5`, `[fd00::5]`) or a CIDR block (`10.0.0.0/24`,
+//!   `[fd00::/64]`).
+//! - `port` is a port (`443`) or an inclusive range (`8000-8100`).
+//!
+//! An entry can instead be a Unix domain socket on the proxy host,
+//! `unix:/run/tee/vllm.sock`, which allows exactly that path (the same
+//! string, not another path to the same socket).
+//!
+//! Hostnames compare case-insensitively. IP patterns only match targets
+//! given as IP addresses: a hostname is not resolved to check it against a
+//! CIDR block, so `10.0.0.0/8` does not allow `internal.example.com`.
+
+use std::fmt;
+use std::net::IpAddr;
+
+/// Targets clients may connect to. Empty rejects every target.
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Tcp {
+        host: HostPattern,
+        ports: (u16, u16),
+    },
+    /// The absolute path of a Unix domain socket.
+    Unix(String),
+}
+
+/// Prefix of targets that are Unix domain sockets.
+pub(crate) const UNIX_PREFIX: &str = "unix:";
+
+#[derive(Debug, Clone)]
+enum HostPattern {
+    /// A hostname, lower-cased.
+    Name(String),
+    /// `*.` followed by this suffix, lower-cased.
+    Subdomain(String),
+    /// An address and prefix length (the full length for a single address).
+    Network(IpAddr, u8),
+}
+
+/// Decides which targets clients may connect to. [`Allowlist`] is the
+/// configured one; an embedding application can supply its own, such as one
+/// backed by a service registry, or a closure.
+pub trait AllowlistProvider: Send + Sync {
+    /// Whether a client may connect to `target` (`host:port`).
+    fn allows(&self, target: &str) -> bool;
+}
+
+impl AllowlistProvider for Allowlist {
+    fn allows(&self, target: &str) -> bool {
+        Allowlist::allows(self, target)
+    }
+}
+
+impl<F: Fn(&str) -> bool + Send + Sync> AllowlistProvider for F {
+    fn allows(&self, target: &str) -> bool {
+        self(target)
+    }
+}
+
+/// An allowlist entry that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEntry {
+    pub entry: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid allowlist entry {:?}: {}",
+            self.entry, self.reason
+        )
+    }
+}
+
+impl std::error::Error for InvalidEntry {}
+
+impl Allowlist {
+    /// Parse allowlist entries, rejecting the whole list if one is invalid.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, InvalidEntry> {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.as_ref();
+                Entry::parse(entry).map_err(|reason| InvalidEntry {
+                    entry: entry.to_string(),
+                    reason,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether some entry matches `target` (`host:port` or `unix:/path`).
+    pub fn allows(&self, target: &str) -> bool {
+        if let Some(path) = unix_path(target) {
+            return self
+                .entries
+                .iter()
+                .any(|entry| matches!(entry, Entry::Unix(socket) if socket == path));
+        }
+        let Some((host, port)) = split_target(target) else {
+            return false;
+        };
+        let port = match port.parse::<u16>() {
+            Ok(port) => port,
+            Err(_) => return false,
+        };
+        let host = Host::parse(host);
+        self.entries.iter().any(|entry| match entry {
+            Entry::Tcp {
+                host: pattern,
+                ports,
+            } => ports.0 <= port && port <= ports.1 && pattern.matches(&host),
+            Entry::Unix(_) => false,
+        })
+    }
+}
+
+impl Entry {
+    fn parse(entry: &str) -> Result<Self, &'static str> {
+        let entry = entry.trim();
+        if entry.starts_with(UNIX_PREFIX) {
+            let path = unix_path(entry).ok_or("Unix socket path must be absolute")?;
+            return Ok(Self::Unix(path.to_string()));
+        }
+        let (host, ports) = split_target(entry).ok_or("expected host:port")?;
+        Ok(Self::Tcp {
+            host: HostPattern::parse(host)?,
+            ports: parse_ports(ports)?,
+        })
+    }
+}
+
+/// The socket path of a `unix:/path` target.
+pub(crate) fn unix_path(target: &str) -> Option<&str> {
+    target
+        .strip_prefix(UNIX_PREFIX)
+        .filter(|path| path.starts_with('/'))
+}
+
+/// Split `host:port`, taking the brackets off an IPv6 host.
+pub(crate) fn split_target(target: &str) -> Option<(&str, &str)> {
+    let (host, port) = target.rsplit_once(':')?;
+    let host = match host.strip_prefix('[') {
+        Some(inner) => inner.strip_suffix(']')?,
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    (!host.is_empty()).then_some((host, port))
+}
+
+fn parse_ports(ports: &str) -> Result<(u16, u16), &'static str> {
+    let parse = |port: &str| port.trim().parse::<u16>().map_err(|_| "invalid port");
+    let (low, high) = match ports.split_once('-') {
+        Some((low, high)) => (parse(low)?, parse(high)?),
+        None => {
+            let port = parse(ports)?;
+            (port, port)
+        }
+    };
+    if low > high {
+        return Err("port range is reversed");
+    }
+    Ok((low, high))
+}
+
+impl HostPattern {
+    fn parse(host: &str) -> Result<Self, &'static str> {
+        if host.contains('/') || host.parse::<IpAddr>().is_ok() {
+            let (addr, prefix) = parse_network(host)?;
+            return Ok(Self::Network(addr, prefix));
+        }
+        if let Some(suffix) = host.strip_prefix("*.") {
+            if suffix.is_empty() || suffix.contains('*') {
+                return Err("wildcard needs a domain after \"*.\"");
+            }
+            return Ok(Self::Subdomain(suffix.to_ascii_lowercase()));
+        }
+        if host.contains('*') {
+            return Err("wildcards are only allowed as a leading \"*.\"");
+        }
+        Ok(Self::Name(host.to_ascii_lowercase()))
+    }
+
+    fn matches(&self, host: &Host) -> bool {
+        match (self, host) {
+            (Self::Name(name), Host::Name(host)) => name == host,
+            (Self::Subdomain(suffix), Host::Name(host)) => host
+                .strip_suffix(suffix.as_str())
+                .and_then(|label| label.strip_suffix('.'))
+                .is_some_and(|label| !label.is_empty()),
+            (Self::Network(network, prefix), Host::Ip(addr)) => {
+                in_network(*addr, *network, *prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A target host: an IP address or a lower-cased hostname.
+enum Host {
+    Ip(IpAddr),
+    Name(String),
+}
+
+impl Host {
+    fn parse(host: &str) -> Self {
+        match host.parse() {
+            Ok(addr) => Self::Ip(addr),
+            Err(_) => Self::Name(host.to_ascii_lowercase()),
+        }
+    }
+}
+
+/// Parse a CIDR block (`10.0.0.0/8`) or a single address (`10.0.0.5`, as a
+/// full-length prefix).
+pub(crate) fn parse_network(network: &str) -> Result<(IpAddr, u8), &'static str> {
+    match network.split_once('/') {
+        Some((addr, prefix)) => {
+            let addr: IpAddr = addr.parse().map_err(|_| "CIDR block needs an IP address")?;
+            let prefix: u8 = prefix.parse().map_err(|_| "invalid CIDR prefix length")?;
+            if prefix > max_prefix(&addr) {
+                return Err("CIDR prefix length too long for the address");
+            }
+            Ok((addr, prefix))
+        }
+        None => {
+            let addr: IpAddr = network.parse().map_err(|_| "invalid IP address")?;
+            Ok((addr, max_prefix(&addr)))
+        }
+    }
+}
+
+fn max_prefix(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+pub(crate) fn in_network(addr: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (addr, network) {
+        (IpAddr::V4(addr), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(addr) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist(entries: &[&str]) -> Allowlist {
+        Allowlist::parse(entries).unwrap()
+    }
+
+    #[test]
+    fn test_exact_entries() {
+        let list = allowlist(&["host1:443", "Host2.example.com:8443"]);
+        assert!(list.allows("host1:443"));
+        assert!(list.allows("host2.example.com:8443"));
+        assert!(list.allows("HOST1:443"));
+        assert!(!list.allows("host1:8443"));
+        assert!(!list.allows("host3:443"));
+        assert!(!list.allows("host1"));
+        assert!(!list.allows("host1:notaport"));
+    }
+
+    #[test]
+    fn test_wildcard_matches_subdomains_only() {
+        let list = allowlist(&["*.tee.example.com:443"]);
+        assert!(list.allows("a.tee.example.com:443"));
+        assert!(list.allows("a.b.tee.example.com:443"));
+        assert!(!list.allows("tee.example.com:443"));
+        assert!(!list.allows("evil-tee.example.com:443"));
+        assert!(!list.allows("a.tee.example.com.evil.com:443"));
+        assert!(!list.allows("a.tee.example.com:8443"));
+    }
+
+    #[test]
+    fn test_port_ranges() {
+        let list = allowlist(&["tee1.example.com:8000-8100"]);
+        assert!(list.allows("tee1.example.com:8000"));
+        assert!(list.allows("tee1.example.com:8050"));
+        assert!(list.allows("tee1.example.com:8100"));
+        assert!(!list.allows("tee1.example.com:7999"));
+        assert!(!list.allows("tee1.example.com:8101"));
+    }
+
+    #[test]
+    fn test_cidr_blocks() {
+        let list = allowlist(&["10.0.0.0/24:443", "[fd00::/64]:443", "192.168.1.5:443"]);
+        assert!(list.allows("10.0.0.17:443"));
+        assert!(!list.allows("10.0.1.17:443"));
+        assert!(list.allows("[fd00::1234]:443"));
+        assert!(!list.allows("[fd01::1]:443"));
+        assert!(list.allows("192.168.1.5:443"));
+        assert!(!list.allows("192.168.1.6:443"));
+        // Hostnames are not resolved to check them against IP patterns
+        assert!(!list.allows("localhost:443"));
+    }
+
+    #[test]
+    fn test_unix_sockets() {
+        let list = allowlist(&["unix:/run/tee/vllm.sock", "vllm.example.com:443"]);
+        assert!(list.allows("unix:/run/tee/vllm.sock"));
+        assert!(!list.allows("unix:/run/tee/other.sock"));
+        assert!(!list.allows("unix:/run/tee/../tee/vllm.sock"));
+        assert!(!list.allows("unix:run/tee/vllm.sock"));
+        assert!(list.allows("vllm.example.com:443"));
+        assert!(!list.allows("unix:443"));
+    }
+
+    #[test]
+    fn test_empty_list_rejects_everything() {
+        let list = allowlist(&[]);
+        assert!(list.is_empty());
+        assert!(!list.allows("any:443"));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        for entry in [
+            "host1",
+            "host1:",
+            "host1:70000",
+            "host1:9000-8000",
+            "*:443",
+            "*.:443",
+            "tee.*.example.com:443",
+            "10.0.0.0/33:443",
+            "host/8:443",
+            "fd00::1:443",
+            "unix:relative.sock",
+        ] {
+            let err = Allowlist::parse(&[entry]).unwrap_err();
+            assert_eq!(err.entry, entry);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! Attestation gating: with `[attestation] policy` set, the proxy verifies a
+//! target's TEE itself, over aTLS, before tunnelling to it, and again every
+//! `interval_secs` while tunnels to it stay open, closing them once it fails.
+//! This is a second check in front of clients that can't be trusted to
+//! verify; clients should still attest their targets end to end.
+//!
+//! The policy file is JSON, as [`atlas_rs::Policy`] reads it, and the
+//! target's host is the name it is attested for. Verdicts are cached per
+//! target for the interval, so a busy target is probed once per interval
+//! rather than once per tunnel. Datagram tunnels are not gated, as their
+//! targets don't serve aTLS over TCP, and `unix:` targets always fail, as
+//! they have no host to attest.
+
+use atlas_rs::{atls_connect, Policy};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::allowlist::{split_target, unix_path};
+use crate::config::AttestationConfig;
+use crate::proxy_protocol;
+use crate::resolve::Resolver;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Expired verdicts are dropped once this many targets are cached.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Attests targets against a policy.
+#[derive(Debug)]
+pub struct Attestor {
+    policy: Policy,
+    interval: Duration,
+    timeout: Duration,
+    /// Whether targets expect a PROXY protocol header first.
+    proxy_protocol: bool,
+    verdicts: Mutex<HashMap<String, Verdict>>,
+}
+
+#[derive(Debug)]
+struct Verdict {
+    checked: Instant,
+    result: Result<(), String>,
+}
+
+impl Attestor {
+    pub fn new(policy: Policy, interval: Duration, timeout: Duration) -> Self {
+        Self {
+            policy,
+            interval,
+            timeout,
+            proxy_protocol: false,
+            verdicts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start probes with a PROXY protocol `LOCAL` header, for targets that
+    /// expect one.
+    pub fn with_proxy_protocol(mut self, proxy_protocol: bool) -> Self {
+        self.proxy_protocol = proxy_protocol;
+        self
+    }
+
+    /// Load the policy `config` names, or `None` when gating is off.
+    pub fn from_config(config: &AttestationConfig) -> Result<Option<Self>, BoxError> {
+        let Some(path) = &config.policy else {
+            return Ok(None);
+        };
+        let invalid = |e: &dyn std::fmt::Display| {
+            format!("invalid attestation policy {}: {e}", path.display())
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read attestation policy {}: {e}", path.display()))?;
+        let policy: Policy = serde_json::from_str(&text).map_err(|e| invalid(&e))?;
+        // Reject a policy no verifier can be built from now, not on the first tunnel
+        policy.clone().into_verifier().map_err(|e| invalid(&e))?;
+        info!(policy = %path.display(), "attestation gating enabled");
+        Ok(Some(Self::new(policy, config.interval(), config.timeout())))
+    }
+
+    /// Whether `target` passes attestation, probing it unless its last
+    /// verdict is recent. The error is the reason it failed.
+    pub async fn check(&self, target: &str, resolver: &Resolver) -> Result<(), String> {
+        if let Some(result) = self.cached(target) {
+            return result;
+        }
+        let result = match tokio::time::timeout(self.timeout, self.probe(target, resolver)).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err("attestation timed out".to_string()),
+        };
+        match &result {
+            Ok(()) => info!(%target, "target passed attestation"),
+            Err(e) => warn!(%target, error = %e, "target failed attestation"),
+        }
+
+        let mut verdicts = self.verdicts.lock().unwrap_or_else(|e| e.into_inner());
+        if verdicts.len() >= PRUNE_THRESHOLD {
+            verdicts.retain(|_, verdict| verdict.checked.elapsed() < self.interval);
+        }
+        let verdict = Verdict {
+            checked: Instant::now(),
+            result: result.clone(),
+        };
+        verdicts.insert(target.to_string(), verdict);
+        result
+    }
+
+    fn cached(&self, target: &str) -> Option<Result<(), String>> {
+        let verdicts = self.verdicts.lock().unwrap_or_else(|e| e.into_inner());
+        let verdict = verdicts.get(target)?;
+        (verdict.checked.elapsed() < self.interval).then(|| verdict.result.clone())
+    }
+
+    /// Connect to `target` and verify it with aTLS; the connection is then
+    /// dropped.
+    async fn probe(&self, target: &str, resolver: &Resolver) -> Result<(), BoxError> {
+        if unix_path(target).is_some() {
+            return Err("Unix socket targets have no host to attest".into());
+        }
+        let (host, _) = split_target(target).ok_or("expected host:port")?;
+        let mut tcp = resolver.connect(target).await?;
+        if self.proxy_protocol {
+            tcp.write_all(&proxy_protocol::local()).await?;
+        }
+        atls_connect(tcp, host, self.policy.clone(), None).await?;
+        Ok(())
+    }
+}
+
+/// Check `target` again every interval, completing with the reason once it
+/// fails; never completes without an attestor.
+pub async fn revoked(attestor: Option<Arc<Attestor>>, target: &str, resolver: &Resolver) -> String {
+    let Some(attestor) = attestor else {
+        return std::future::pending().await;
+    };
+    loop {
+        tokio::time::sleep(attestor.interval).await;
+        if let Err(reason) = attestor.check(target, resolver).await {
+            return reason;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DnsConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_verdicts_are_cached() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        // Not a TEE: accepts and hangs up, so every probe fails
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        let probes = Arc::new(AtomicUsize::new(0));
+        let count = probes.clone();
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                count.fetch_add(1, Ordering::Relaxed);
+                drop(tcp);
+            }
+        });
+
+        let resolver = Resolver::from_config(&DnsConfig::default()).unwrap();
+        let interval = Duration::from_secs(60);
+        let attestor = Attestor::new(Policy::default(), interval, Duration::from_secs(5));
+        assert!(attestor.check(&target, &resolver).await.is_err());
+        assert!(attestor.check(&target, &resolver).await.is_err());
+        assert_eq!(probes.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = AttestationConfig::default();
+        assert!(Attestor::from_config(&config).unwrap().is_none());
+
+        let path = std::env::temp_dir().join("atlas-proxy-test-policy.json");
+        std::fs::write(&path, r#"{"type": "dstack_tdx", "unknown": true"#).unwrap();
+        let config = AttestationConfig {
+            policy: Some(path.clone()),
+            ..AttestationConfig::default()
+        };
+        let err = Attestor::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("invalid attestation policy"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
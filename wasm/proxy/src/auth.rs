@@ -0,0 +1,270 @@
+//! Client authentication, so the proxy isn't an open relay to the allowlisted
+//! targets.
+//!
+//! Configured by the `[auth]` section. When it sets static `tokens` or a
+//! `[auth.jwt]` issuer, every WebSocket handshake must carry a bearer token:
+//!
+//! - `?token=<token>` in the URL (browsers can't set WebSocket headers),
+//! - a `bearer.<token>` entry in `Sec-WebSocket-Protocol`, or
+//! - `Authorization: Bearer <token>`.
+//!
+//! Tokens are never logged.
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::str::FromStr;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use url::form_urlencoded;
+
+use crate::config::AuthConfig;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// `Sec-WebSocket-Protocol` entries carrying a token start with this.
+pub const PROTOCOL_PREFIX: &str = "bearer.";
+
+/// Checks client tokens against the configured secrets or JWT issuer.
+pub struct Authenticator {
+    tokens: Vec<String>,
+    jwt: Option<(DecodingKey, Validation)>,
+}
+
+/// Claims are checked by `Validation`; none are read.
+#[derive(Deserialize)]
+struct Claims {}
+
+impl Authenticator {
+    /// Build the authenticator `config` describes, or `None` when
+    /// authentication is off.
+    pub fn from_config(config: &AuthConfig) -> Result<Option<Self>, BoxError> {
+        let jwt = match &config.jwt {
+            Some(jwt) => {
+                let algorithm = Algorithm::from_str(&jwt.algorithm)
+                    .map_err(|_| format!("unknown JWT algorithm {:?}", jwt.algorithm))?;
+                let key = match (&jwt.secret, &jwt.public_key) {
+                    (Some(secret), None) => DecodingKey::from_secret(secret.as_bytes()),
+                    (None, Some(path)) => {
+                        let pem = std::fs::read(path).map_err(|e| {
+                            format!("failed to read JWT public key {}: {e}", path.display())
+                        })?;
+                        match algorithm {
+                            Algorithm::RS256
+                            | Algorithm::RS384
+                            | Algorithm::RS512
+                            | Algorithm::PS256
+                            | Algorithm::PS384
+                            | Algorithm::PS512 => DecodingKey::from_rsa_pem(&pem)?,
+                            Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(&pem)?,
+                            Algorithm::EdDSA => DecodingKey::from_ed_pem(&pem)?,
+                            _ => return Err(format!("{algorithm:?} needs a secret").into()),
+                        }
+                    }
+                    _ => return Err("JWT auth needs either a secret or a public_key".into()),
+                };
+                let mut validation = Validation::new(algorithm);
+                validation.set_issuer(&[&jwt.issuer]);
+                match &jwt.audience {
+                    Some(audience) => validation.set_audience(&[audience]),
+                    None => validation.validate_aud = false,
+                }
+                Some((key, validation))
+            }
+            None => None,
+        };
+        if config.tokens.is_empty() && jwt.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            tokens: config.tokens.clone(),
+            jwt,
+        }))
+    }
+
+    /// Whether `token` is one of the static tokens or a valid JWT.
+    pub fn check(&self, token: &str) -> bool {
+        // Compare every token, so timing doesn't reveal which one matched
+        let matched = self.tokens.iter().fold(false, |matched, known| {
+            constant_time_eq(known, token) | matched
+        });
+        matched
+            || self.jwt.as_ref().is_some_and(|(key, validation)| {
+                jsonwebtoken::decode::<Claims>(token, key, validation).is_ok()
+            })
+    }
+}
+
+/// Where a client put its token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    Query(String),
+    /// The whole `Sec-WebSocket-Protocol` entry, prefix included.
+    Protocol(String),
+    Header(String),
+}
+
+impl Credential {
+    pub fn token(&self) -> &str {
+        match self {
+            Self::Query(token) | Self::Header(token) => token,
+            Self::Protocol(entry) => &entry[PROTOCOL_PREFIX.len()..],
+        }
+    }
+}
+
+/// Find the client's token in the handshake request.
+pub fn extract_credential(req: &Request) -> Option<Credential> {
+    let query = req.uri().query().and_then(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| Credential::Query(value.into_owned()))
+    });
+    let protocol = || {
+        req.headers()
+            .get_all("sec-websocket-protocol")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .find(|entry| entry.starts_with(PROTOCOL_PREFIX))
+            .map(|entry| Credential::Protocol(entry.to_string()))
+    };
+    let header = || {
+        req.headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| Credential::Header(token.trim().to_string()))
+    };
+    query.or_else(protocol).or_else(header)
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JwtConfig;
+    use jsonwebtoken::{EncodingKey, Header};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tokens(tokens: &[&str]) -> Authenticator {
+        let config = AuthConfig {
+            tokens: tokens.iter().map(|t| t.to_string()).collect(),
+            jwt: None,
+        };
+        Authenticator::from_config(&config).unwrap().unwrap()
+    }
+
+    fn jwt(issuer: &str, audience: &str, exp_offset: i64, secret: &str) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let claims = serde_json::json!({ "iss": issuer, "aud": audience, "exp": now + exp_offset });
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_auth_off_without_tokens_or_jwt() {
+        assert!(Authenticator::from_config(&AuthConfig::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_static_tokens() {
+        let auth = tokens(&["first-token", "second-token"]);
+        assert!(auth.check("first-token"));
+        assert!(auth.check("second-token"));
+        assert!(!auth.check("second-toke"));
+        assert!(!auth.check(""));
+    }
+
+    #[test]
+    fn test_jwt() {
+        let config = AuthConfig {
+            tokens: Vec::new(),
+            jwt: Some(JwtConfig {
+                issuer: "https://auth.example.com".to_string(),
+                audience: Some("atlas-proxy".to_string()),
+                algorithm: "HS256".to_string(),
+                secret: Some("test-secret".to_string()),
+                public_key: None,
+            }),
+        };
+        let auth = Authenticator::from_config(&config).unwrap().unwrap();
+
+        let issuer = "https://auth.example.com";
+        assert!(auth.check(&jwt(issuer, "atlas-proxy", 300, "test-secret")));
+        assert!(!auth.check(&jwt(issuer, "atlas-proxy", -300, "test-secret")));
+        assert!(!auth.check(&jwt(issuer, "other", 300, "test-secret")));
+        assert!(!auth.check(&jwt(
+            "https://evil.example.com",
+            "atlas-proxy",
+            300,
+            "test-secret"
+        )));
+        assert!(!auth.check(&jwt(issuer, "atlas-proxy", 300, "wrong-secret")));
+        assert!(!auth.check("not-a-jwt"));
+    }
+
+    #[test]
+    fn test_jwt_needs_one_key() {
+        let config = AuthConfig {
+            tokens: Vec::new(),
+            jwt: Some(JwtConfig {
+                issuer: "https://auth.example.com".to_string(),
+                ..JwtConfig::default()
+            }),
+        };
+        assert!(Authenticator::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_extract_credential() {
+        let req = Request::builder()
+            .uri("/?target=host1:443&token=abc%2B1")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            extract_credential(&req),
+            Some(Credential::Query("abc+1".to_string()))
+        );
+
+        let req = Request::builder()
+            .uri("/")
+            .header("Sec-WebSocket-Protocol", "atlas, bearer.abc")
+            .body(())
+            .unwrap();
+        let credential = extract_credential(&req).unwrap();
+        assert_eq!(credential, Credential::Protocol("bearer.abc".to_string()));
+        assert_eq!(credential.token(), "abc");
+
+        let req = Request::builder()
+            .uri("/")
+            .header("Authorization", "Bearer abc")
+            .body(())
+            .unwrap();
+        assert_eq!(
+            extract_credential(&req),
+            Some(Credential::Header("abc".to_string()))
+        );
+
+        let req = Request::builder()
+            .uri("/?target=host1:443")
+            .body(())
+            .unwrap();
+        assert_eq!(extract_credential(&req), None);
+    }
+}
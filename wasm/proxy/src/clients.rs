@@ -0,0 +1,149 @@
+//! Client address filtering, so the proxy can be restricted to known
+//! frontends.
+//!
+//! `[clients] allow` and `deny` are CIDR blocks (or single addresses). An
+//! address in `deny` is refused even if `allow` includes it; with `allow`
+//! empty, every address not denied may connect.
+//!
+//! Behind a load balancer, connections come from the balancer, so the client
+//! address is taken from `X-Forwarded-For` instead, but only on connections
+//! from `trusted_proxies`: anyone else could forge the header. The header is
+//! read from the right, skipping trusted proxies, and the first other address
+//! is the client's. Connections are checked when accepted, by their peer
+//! address, except those from trusted proxies, which are checked at the
+//! WebSocket handshake (a refused client gets 403).
+
+use std::net::IpAddr;
+
+use crate::allowlist::{in_network, parse_network};
+use crate::config::ClientsConfig;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Which client addresses may connect.
+#[derive(Debug, Clone, Default)]
+pub struct ClientFilter {
+    allow: Vec<(IpAddr, u8)>,
+    deny: Vec<(IpAddr, u8)>,
+    trusted_proxies: Vec<(IpAddr, u8)>,
+}
+
+impl ClientFilter {
+    pub fn from_config(config: &ClientsConfig) -> Result<Self, BoxError> {
+        let parse = |networks: &[String], name: &str| {
+            networks
+                .iter()
+                .map(|network| {
+                    parse_network(network)
+                        .map_err(|reason| format!("invalid {name} entry {network:?}: {reason}"))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            allow: parse(&config.allow, "[clients] allow")?,
+            deny: parse(&config.deny, "[clients] deny")?,
+            trusted_proxies: parse(&config.trusted_proxies, "[clients] trusted_proxies")?,
+        })
+    }
+
+    /// Whether `client` may connect.
+    pub fn allows(&self, client: IpAddr) -> bool {
+        let matches = |networks: &[(IpAddr, u8)]| {
+            networks
+                .iter()
+                .any(|&(network, prefix)| in_network(client, network, prefix))
+        };
+        !matches(&self.deny) && (self.allow.is_empty() || matches(&self.allow))
+    }
+
+    /// Whether `X-Forwarded-For` from `peer` is believed.
+    pub fn is_trusted_proxy(&self, peer: IpAddr) -> bool {
+        self.trusted_proxies
+            .iter()
+            .any(|&(network, prefix)| in_network(peer, network, prefix))
+    }
+
+    /// The client a trusted proxy `peer` forwards, from its `X-Forwarded-For`
+    /// values, in header order. Falls back to the nearest trusted address
+    /// when the header runs out or can't be parsed.
+    pub fn forwarded_client<'a>(
+        &self,
+        peer: IpAddr,
+        forwarded_for: impl DoubleEndedIterator<Item = &'a str>,
+    ) -> IpAddr {
+        let mut client = peer;
+        let hops = forwarded_for.flat_map(|value| value.split(',')).rev();
+        for hop in hops {
+            let Ok(addr) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+            client = addr;
+            if !self.is_trusted_proxy(addr) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str], trusted_proxies: &[&str]) -> ClientFilter {
+        let list = |entries: &[&str]| entries.iter().map(|e| e.to_string()).collect();
+        let config = ClientsConfig {
+            allow: list(allow),
+            deny: list(deny),
+            trusted_proxies: list(trusted_proxies),
+        };
+        ClientFilter::from_config(&config).unwrap()
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let open = filter(&[], &[], &[]);
+        assert!(open.allows(ip("203.0.113.7")));
+
+        let filter = filter(&["10.0.0.0/8", "fd00::/8"], &["10.0.0.66"], &[]);
+        assert!(filter.allows(ip("10.1.2.3")));
+        assert!(filter.allows(ip("fd00::1")));
+        assert!(!filter.allows(ip("203.0.113.7")));
+        // Deny wins over allow
+        assert!(!filter.allows(ip("10.0.0.66")));
+    }
+
+    #[test]
+    fn test_invalid_entries() {
+        let config = ClientsConfig {
+            deny: vec!["10.0.0.0/33".to_string()],
+            ..ClientsConfig::default()
+        };
+        let err = ClientFilter::from_config(&config).err().unwrap();
+        assert!(err.to_string().contains("[clients] deny"));
+    }
+
+    #[test]
+    fn test_forwarded_client() {
+        let filter = filter(&[], &[], &["10.0.0.0/8"]);
+        let lb = ip("10.0.0.2");
+        assert!(filter.is_trusted_proxy(lb));
+        assert!(!filter.is_trusted_proxy(ip("203.0.113.7")));
+
+        let client = filter.forwarded_client(lb, ["203.0.113.7"].into_iter());
+        assert_eq!(client, ip("203.0.113.7"));
+        // Entries left of the first untrusted address could be forged
+        let client = filter.forwarded_client(lb, ["192.0.2.1, 203.0.113.7, 10.0.0.9"].into_iter());
+        assert_eq!(client, ip("203.0.113.7"));
+        // Several headers read as one list
+        let client = filter.forwarded_client(lb, ["192.0.2.1", "203.0.113.7"].into_iter());
+        assert_eq!(client, ip("203.0.113.7"));
+        let client = filter.forwarded_client(lb, ["not-an-ip, 10.0.0.9"].into_iter());
+        assert_eq!(client, ip("10.0.0.9"));
+        assert_eq!(filter.forwarded_client(lb, std::iter::empty()), lb);
+    }
+}
@@ -0,0 +1,915 @@
+//! Proxy configuration.
+//!
+//! Read from a TOML file given with `--config <path>` or `ATLS_PROXY_CONFIG`,
+//! then overridden by the `ATLS_PROXY_*` environment variables, so a
+//! deployment can keep its settings in a file and still tweak one from the
+//! environment. Without a file, the defaults and environment apply as before.
+//!
+//! ```toml
+//! listen = ["0.0.0.0:9000", "[::]:9000"]
+//! target = "tee1.example.com:443"
+//! allowlist = ["tee1.example.com:443", "tee2.example.com:443"]
+//!
+//! [[listeners]]  # with its own TLS settings (see `listener`)
+//! address = "127.0.0.1:9001"
+//! tls = {}       # plaintext ws://, whatever [tls] says
+//!
+//! [timeouts]
+//! connect_secs = 10    # connecting to a target
+//! handshake_secs = 10  # TLS and WebSocket handshakes with a client
+//! idle_secs = 300      # close tunnels idle this long (0: never)
+//! max_tunnel_secs = 3600  # close tunnels this long after they open (0: never)
+//! rotation_notice_secs = 60  # warn clients this long before (0: no warning)
+//!
+//! [limits]
+//! max_connections = 1024
+//! max_streams_per_session = 64
+//! max_connections_per_ip = 16
+//! connections_per_minute_per_ip = 60
+//! bandwidth_per_connection = 1048576  # bytes per second, each direction
+//!
+//! [clients]
+//! allow = ["198.51.100.0/24"]       # client addresses (see `clients`)
+//! deny = ["198.51.100.66"]
+//! trusted_proxies = ["10.0.0.2"]    # believe their X-Forwarded-For
+//!
+//! [upstream]
+//! proxy_protocol = true  # PROXY v2 header to targets (see `proxy_protocol`)
+//!
+//! [websocket]
+//! compression = true  # permessage-deflate with clients (see `deflate`)
+//!
+//! [tls]
+//! cert = "/etc/atlas-proxy/fullchain.pem"
+//! key = "/etc/atlas-proxy/privkey.pem"
+//!
+//! [auth]
+//! tokens = ["<token>"]  # or ATLS_PROXY_AUTH_TOKENS
+//!
+//! [auth.jwt]
+//! issuer = "https://auth.example.com"
+//! audience = "atlas-proxy"
+//! algorithm = "RS256"
+//! public_key = "/etc/atlas-proxy/jwt.pem"
+//!
+//! [dns]
+//! block_private = true  # hostnames may not resolve to internal addresses
+//! blocked_networks = ["203.0.113.0/24"]
+//! min_ttl_secs = 30     # pin answers at least this long
+//!
+//! [udp]
+//! allowlist = ["quic.example.com:443"]  # datagram tunnels (see `udp`)
+//! max_datagram = 1500
+//!
+//! [sni.routes]  # route clients that name no target by SNI (see `sni`)
+//! "vllm.example.com" = "vllm.internal:443"
+//!
+//! [attestation]
+//! policy = "/etc/atlas-proxy/policy.json"  # attest targets (see `attest`)
+//! interval_secs = 300
+//!
+//! [access_log]
+//! path = "/var/log/atlas-proxy/access.log"  # or "-" for stdout
+//! format = "json"  # or "clf"
+//!
+//! [logging]
+//! connections = true
+//! level = "info"   # RUST_LOG overrides
+//! format = "json"  # or "text"
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Environment variable naming the configuration file.
+pub const CONFIG_ENV: &str = "ATLS_PROXY_CONFIG";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Addresses to listen on, with the `[tls]` settings.
+    pub listen: Vec<String>,
+    /// More addresses, each with its own TLS settings. In a file that has
+    /// them but no `listen`, they are the only addresses.
+    pub listeners: Vec<ListenerConfig>,
+    /// Target of connections that don't name one.
+    pub target: String,
+    /// Targets clients may connect to (see `allowlist`); empty rejects every
+    /// target.
+    pub allowlist: Vec<String>,
+    pub timeouts: Timeouts,
+    pub limits: Limits,
+    pub clients: ClientsConfig,
+    pub upstream: UpstreamConfig,
+    pub websocket: WebSocketConfig,
+    pub tls: TlsConfig,
+    pub auth: AuthConfig,
+    pub dns: DnsConfig,
+    pub udp: UdpConfig,
+    pub sni: SniConfig,
+    pub attestation: AttestationConfig,
+    pub access_log: AccessLogConfig,
+    pub logging: Logging,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    /// Seconds to wait for a target to accept the TCP connection.
+    pub connect_secs: u64,
+    /// Seconds a client has to complete the TLS and WebSocket handshakes.
+    pub handshake_secs: u64,
+    /// Seconds without traffic after which a tunnel or multiplexed session
+    /// is closed; 0 keeps idle tunnels open.
+    pub idle_secs: u64,
+    /// Seconds after which a tunnel or multiplexed session is closed however
+    /// busy it is; 0 for no limit.
+    pub max_tunnel_secs: u64,
+    /// Seconds before `max_tunnel_secs` at which clients are told the tunnel
+    /// or session will close, so they can open a new one; 0 for no notice.
+    pub rotation_notice_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Client connections served at once; more are dropped on accept.
+    pub max_connections: usize,
+    /// Open streams per multiplexed session.
+    pub max_streams_per_session: usize,
+    /// Client connections served at once per source IP; 0 for no limit.
+    pub max_connections_per_ip: usize,
+    /// New connections accepted per minute per source IP; 0 for no limit.
+    pub connections_per_minute_per_ip: u32,
+    /// Bytes per second each direction of a client connection may carry; 0
+    /// for no limit.
+    pub bandwidth_per_connection: u64,
+}
+
+/// Which client addresses may connect (see `clients`); every address by
+/// default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientsConfig {
+    /// CIDR blocks clients may connect from; empty allows every address.
+    pub allow: Vec<String>,
+    /// CIDR blocks clients may not connect from, even if allowed.
+    pub deny: Vec<String>,
+    /// Load balancers whose `X-Forwarded-For` names the client.
+    pub trusted_proxies: Vec<String>,
+}
+
+/// Connections to targets.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    /// Start each connection with a PROXY protocol v2 header naming the
+    /// client (see `proxy_protocol`).
+    pub proxy_protocol: bool,
+}
+
+/// The WebSocket connections of clients.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebSocketConfig {
+    /// Accept permessage-deflate when clients offer it (see `deflate`).
+    pub compression: bool,
+}
+
+/// A listen address with its own TLS settings (see `listener`).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub address: String,
+    /// Replaces `[tls]` for this address; an empty table serves plaintext
+    /// `ws://`.
+    pub tls: Option<TlsConfig>,
+}
+
+/// TLS for the listener (see `tls`): certificate files or ACME, or neither
+/// for plaintext `ws://`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub acme_domains: Vec<String>,
+    pub acme_cache: PathBuf,
+    pub acme_email: Option<String>,
+    pub acme_staging: bool,
+}
+
+/// Resolution of hostname targets (see `resolve`).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    /// Refuse hostnames resolving to loopback, private or link-local addresses.
+    pub block_private: bool,
+    /// Further CIDR blocks hostnames may not resolve to.
+    pub blocked_networks: Vec<String>,
+    /// Cache answers at least this long; 0 follows the record TTL.
+    pub min_ttl_secs: u64,
+    /// Cache answers at most this long; 0 follows the record TTL.
+    pub max_ttl_secs: u64,
+}
+
+/// Datagram tunnels (see `udp`): off unless `allowlist` names targets.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UdpConfig {
+    /// UDP targets clients may reach, in the TCP allowlist's syntax; empty
+    /// refuses every datagram tunnel.
+    pub allowlist: Vec<String>,
+    /// Largest datagram forwarded either way, in bytes; larger ones are dropped.
+    pub max_datagram: usize,
+}
+
+/// Routing by TLS server name (see `sni`): off unless `routes` has entries.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SniConfig {
+    /// Server names and the `host:port` targets they route to.
+    pub routes: BTreeMap<String, String>,
+}
+
+/// Proxy-side attestation of targets (see `attest`): off unless `policy` is
+/// set.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttestationConfig {
+    /// JSON attestation policy file, as `atlas_rs::Policy` reads it.
+    pub policy: Option<PathBuf>,
+    /// Seconds a target's verdict holds; targets of open tunnels are attested
+    /// again this often.
+    pub interval_secs: u64,
+    /// Seconds an attestation may take, connecting included.
+    pub timeout_secs: u64,
+}
+
+/// Client authentication (see `auth`): off unless `tokens` or `jwt` is set.
+#[derive(Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Static bearer tokens, any of which admits a client.
+    pub tokens: Vec<String>,
+    pub jwt: Option<JwtConfig>,
+}
+
+/// JWTs from an issuer, checked with its HMAC `secret` or PEM `public_key`.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    /// Required `iss` claim.
+    pub issuer: String,
+    /// Required `aud` claim, if any.
+    pub audience: Option<String>,
+    /// Signature algorithm, such as `RS256`, `ES256` or `HS256`.
+    pub algorithm: String,
+    pub secret: Option<String>,
+    pub public_key: Option<PathBuf>,
+}
+
+/// Per-connection access records (see `access_log`): off unless `path` is set.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessLogConfig {
+    /// File records are appended to, or `-` for stdout.
+    pub path: Option<PathBuf>,
+    pub format: AccessLogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// One JSON object per line.
+    #[default]
+    Json,
+    /// Common Log Format, with the bytes received, duration and close reason
+    /// appended.
+    Clf,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    /// Log every connection and the target it asks for; errors are always logged.
+    pub connections: bool,
+    /// `tracing` filter, such as `info` or `atlas_proxy=debug`; `RUST_LOG`
+    /// takes precedence.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line, for log pipelines.
+    Json,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: vec!["127.0.0.1:9000".to_string()],
+            listeners: Vec::new(),
+            target: "127.0.0.1:8443".to_string(),
+            allowlist: Vec::new(),
+            timeouts: Timeouts::default(),
+            limits: Limits::default(),
+            clients: ClientsConfig::default(),
+            upstream: UpstreamConfig::default(),
+            websocket: WebSocketConfig::default(),
+            tls: TlsConfig::default(),
+            auth: AuthConfig::default(),
+            dns: DnsConfig::default(),
+            udp: UdpConfig::default(),
+            sni: SniConfig::default(),
+            attestation: AttestationConfig::default(),
+            access_log: AccessLogConfig::default(),
+            logging: Logging::default(),
+        }
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect_secs: 10,
+            handshake_secs: 10,
+            idle_secs: 0,
+            max_tunnel_secs: 0,
+            rotation_notice_secs: 0,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_connections: 1024,
+            max_streams_per_session: 64,
+            max_connections_per_ip: 0,
+            connections_per_minute_per_ip: 0,
+            bandwidth_per_connection: 0,
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            cert: None,
+            key: None,
+            acme_domains: Vec::new(),
+            acme_cache: PathBuf::from("acme-cache"),
+            acme_email: None,
+            acme_staging: false,
+        }
+    }
+}
+
+impl Default for UdpConfig {
+    fn default() -> Self {
+        Self {
+            allowlist: Vec::new(),
+            max_datagram: 1500,
+        }
+    }
+}
+
+impl Default for AttestationConfig {
+    fn default() -> Self {
+        Self {
+            policy: None,
+            interval_secs: 300,
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            issuer: String::new(),
+            audience: None,
+            algorithm: "RS256".to_string(),
+            secret: None,
+            public_key: None,
+        }
+    }
+}
+
+// Secrets are left out, so printing the configuration doesn't leak them.
+impl fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("tokens", &format_args!("<{} tokens>", self.tokens.len()))
+            .field("jwt", &self.jwt)
+            .finish()
+    }
+}
+
+impl fmt::Debug for JwtConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtConfig")
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .field("algorithm", &self.algorithm)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("public_key", &self.public_key)
+            .finish()
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            connections: true,
+            level: "info".to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn connect(&self) -> Duration {
+        Duration::from_secs(self.connect_secs)
+    }
+
+    pub fn handshake(&self) -> Duration {
+        Duration::from_secs(self.handshake_secs)
+    }
+
+    pub fn idle(&self) -> Option<Duration> {
+        (self.idle_secs > 0).then(|| Duration::from_secs(self.idle_secs))
+    }
+
+    pub fn max_tunnel(&self) -> Option<Duration> {
+        (self.max_tunnel_secs > 0).then(|| Duration::from_secs(self.max_tunnel_secs))
+    }
+
+    pub fn rotation_notice(&self) -> Option<Duration> {
+        (self.rotation_notice_secs > 0).then(|| Duration::from_secs(self.rotation_notice_secs))
+    }
+}
+
+impl TlsConfig {
+    fn validate(&self) -> Result<(), BoxError> {
+        if self.cert.is_some() != self.key.is_some() {
+            return Err("TLS cert and key must be set together".into());
+        }
+        if self.cert.is_some() && !self.acme_domains.is_empty() {
+            return Err("set either a TLS cert and key or ACME domains, not both".into());
+        }
+        Ok(())
+    }
+}
+
+impl AttestationConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+}
+
+impl Config {
+    /// Load the file named by `--config` (in `args`) or `ATLS_PROXY_CONFIG`,
+    /// if any, then apply the environment overrides.
+    pub fn load(args: impl IntoIterator<Item = String>) -> Result<Self, BoxError> {
+        let mut args = args.into_iter();
+        let mut path = std::env::var(CONFIG_ENV).ok().map(PathBuf::from);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" | "-c" => {
+                    let value = args.next().ok_or("--config needs a file path")?;
+                    path = Some(PathBuf::from(value));
+                }
+                _ => match arg.strip_prefix("--config=") {
+                    Some(value) => path = Some(PathBuf::from(value)),
+                    None => return Err(format!("unknown argument {arg:?}").into()),
+                },
+            }
+        }
+
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok());
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, BoxError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config {}: {e}", path.display()))?;
+        Self::from_toml(&text).map_err(|e| format!("invalid config {}: {e}", path.display()).into())
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        let mut config: Self = toml::from_str(text)?;
+        // `[[listeners]]` replace the default address unless `listen` is set too
+        if !config.listeners.is_empty() && !text.parse::<toml::Table>()?.contains_key("listen") {
+            config.listen.clear();
+        }
+        Ok(config)
+    }
+
+    /// Override settings with the `ATLS_PROXY_*` variables `var` returns.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) {
+        let var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        if let Some(listen) = var("ATLS_PROXY_LISTEN") {
+            self.listen = parse_list(&listen);
+        }
+        if let Some(target) = var("ATLS_PROXY_TARGET") {
+            self.target = target.trim().to_string();
+        }
+        if let Some(allowlist) = var("ATLS_PROXY_ALLOWLIST") {
+            self.allowlist = parse_list(&allowlist);
+        }
+        if let Some(allow) = var("ATLS_PROXY_CLIENT_ALLOW") {
+            self.clients.allow = parse_list(&allow);
+        }
+        if let Some(deny) = var("ATLS_PROXY_CLIENT_DENY") {
+            self.clients.deny = parse_list(&deny);
+        }
+        if let Some(proxies) = var("ATLS_PROXY_TRUSTED_PROXIES") {
+            self.clients.trusted_proxies = parse_list(&proxies);
+        }
+        if let Some(proxy_protocol) = var("ATLS_PROXY_PROXY_PROTOCOL") {
+            self.upstream.proxy_protocol = proxy_protocol == "1";
+        }
+        if let Some(compression) = var("ATLS_PROXY_WS_COMPRESSION") {
+            self.websocket.compression = compression == "1";
+        }
+        if let Some(cert) = var("ATLS_PROXY_TLS_CERT") {
+            self.tls.cert = Some(PathBuf::from(cert));
+        }
+        if let Some(key) = var("ATLS_PROXY_TLS_KEY") {
+            self.tls.key = Some(PathBuf::from(key));
+        }
+        if let Some(domains) = var("ATLS_PROXY_ACME_DOMAINS") {
+            self.tls.acme_domains = parse_list(&domains);
+        }
+        if let Some(cache) = var("ATLS_PROXY_ACME_CACHE") {
+            self.tls.acme_cache = PathBuf::from(cache);
+        }
+        if let Some(email) = var("ATLS_PROXY_ACME_EMAIL") {
+            self.tls.acme_email = Some(email);
+        }
+        if let Some(staging) = var("ATLS_PROXY_ACME_STAGING") {
+            self.tls.acme_staging = staging == "1";
+        }
+        if let Some(format) = var("ATLS_PROXY_LOG_FORMAT") {
+            self.logging.format = match format.trim() {
+                "json" => LogFormat::Json,
+                _ => LogFormat::Text,
+            };
+        }
+        if let Some(routes) = var("ATLS_PROXY_SNI_ROUTES") {
+            self.sni.routes = parse_list(&routes)
+                .iter()
+                .filter_map(|route| route.split_once('='))
+                .map(|(name, target)| (name.trim().to_string(), target.trim().to_string()))
+                .collect();
+        }
+        if let Some(policy) = var("ATLS_PROXY_ATTESTATION_POLICY") {
+            self.attestation.policy = Some(PathBuf::from(policy));
+        }
+        if let Some(path) = var("ATLS_PROXY_ACCESS_LOG") {
+            self.access_log.path = Some(PathBuf::from(path));
+        }
+        if let Some(block) = var("ATLS_PROXY_DNS_BLOCK_PRIVATE") {
+            self.dns.block_private = block == "1";
+        }
+        if let Some(allowlist) = var("ATLS_PROXY_UDP_ALLOWLIST") {
+            self.udp.allowlist = parse_list(&allowlist);
+        }
+        if let Some(tokens) = var("ATLS_PROXY_AUTH_TOKENS") {
+            self.auth.tokens = parse_list(&tokens);
+        }
+    }
+
+    /// Reject settings that can't work together.
+    pub fn validate(&self) -> Result<(), BoxError> {
+        if self.listen.is_empty() && self.listeners.is_empty() {
+            return Err("no listen address configured".into());
+        }
+        self.tls.validate()?;
+        for listener in &self.listeners {
+            if listener.address.is_empty() {
+                return Err("[[listeners]] entries need an address".into());
+            }
+            if let Some(tls) = &listener.tls {
+                tls.validate()
+                    .map_err(|e| format!("listener {}: {e}", listener.address))?;
+            }
+        }
+        if let Some(jwt) = &self.auth.jwt {
+            if jwt.issuer.is_empty() {
+                return Err("[auth.jwt] needs an issuer".into());
+            }
+            if jwt.secret.is_some() == jwt.public_key.is_some() {
+                return Err("[auth.jwt] needs either a secret or a public_key".into());
+            }
+        }
+        if self.limits.max_connections == 0 || self.limits.max_streams_per_session == 0 {
+            return Err("connection and stream limits must be at least 1".into());
+        }
+        if self.udp.max_datagram == 0 || self.udp.max_datagram > 65507 {
+            return Err("max_datagram must be between 1 and 65507 bytes".into());
+        }
+        let timeouts = &self.timeouts;
+        if timeouts.rotation_notice_secs > 0
+            && timeouts.rotation_notice_secs >= timeouts.max_tunnel_secs
+        {
+            return Err("rotation_notice_secs must be less than a non-zero max_tunnel_secs".into());
+        }
+        if self.attestation.interval_secs == 0 || self.attestation.timeout_secs == 0 {
+            return Err("[attestation] interval_secs and timeout_secs must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+/// Split a comma-separated list, dropping blank entries.
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_defaults_match_previous_behaviour() {
+        let config = Config::default();
+        assert_eq!(config.listen, vec!["127.0.0.1:9000"]);
+        assert_eq!(config.target, "127.0.0.1:8443");
+        assert!(config.allowlist.is_empty());
+        assert_eq!(config.limits.max_streams_per_session, 64);
+        assert_eq!(config.timeouts.idle(), None);
+        assert_eq!(config.timeouts.max_tunnel(), None);
+        assert!(config.udp.allowlist.is_empty());
+        assert_eq!(config.attestation.policy, None);
+        assert_eq!(config.access_log.path, None);
+        assert!(!config.upstream.proxy_protocol);
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r#"
+            listen = ["0.0.0.0:9000", "[::]:9000"]
+            target = "tee1.example.com:443"
+            allowlist = ["tee1.example.com:443"]
+
+            [timeouts]
+            idle_secs = 300
+            max_tunnel_secs = 3600
+            rotation_notice_secs = 60
+
+            [limits]
+            max_connections = 10
+            max_connections_per_ip = 2
+            bandwidth_per_connection = 65536
+
+            [clients]
+            allow = ["198.51.100.0/24"]
+            trusted_proxies = ["10.0.0.2"]
+
+            [upstream]
+            proxy_protocol = true
+
+            [websocket]
+            compression = true
+
+            [tls]
+            cert = "/etc/cert.pem"
+            key = "/etc/key.pem"
+
+            [dns]
+            block_private = true
+            min_ttl_secs = 30
+
+            [udp]
+            allowlist = ["quic.example.com:443"]
+
+            [sni.routes]
+            "vllm.example.com" = "vllm.internal:443"
+
+            [attestation]
+            policy = "/etc/policy.json"
+            interval_secs = 60
+
+            [access_log]
+            path = "-"
+            format = "clf"
+
+            [logging]
+            connections = false
+            format = "json"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.listen, vec!["0.0.0.0:9000", "[::]:9000"]);
+        assert_eq!(config.allowlist, vec!["tee1.example.com:443"]);
+        assert_eq!(config.timeouts.idle(), Some(Duration::from_secs(300)));
+        assert_eq!(
+            config.timeouts.max_tunnel(),
+            Some(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            config.timeouts.rotation_notice(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(config.timeouts.connect_secs, 10);
+        assert_eq!(config.limits.max_connections, 10);
+        assert_eq!(config.limits.max_streams_per_session, 64);
+        assert_eq!(config.limits.max_connections_per_ip, 2);
+        assert_eq!(config.limits.connections_per_minute_per_ip, 0);
+        assert_eq!(config.limits.bandwidth_per_connection, 65536);
+        assert_eq!(config.clients.allow, vec!["198.51.100.0/24"]);
+        assert!(config.clients.deny.is_empty());
+        assert_eq!(config.clients.trusted_proxies, vec!["10.0.0.2"]);
+        assert!(config.upstream.proxy_protocol);
+        assert!(config.websocket.compression);
+        assert_eq!(config.tls.cert, Some(PathBuf::from("/etc/cert.pem")));
+        assert!(config.dns.block_private);
+        assert_eq!(config.dns.min_ttl_secs, 30);
+        assert_eq!(config.udp.allowlist, vec!["quic.example.com:443"]);
+        assert_eq!(config.udp.max_datagram, 1500);
+        assert_eq!(config.sni.routes["vllm.example.com"], "vllm.internal:443");
+        assert_eq!(
+            config.attestation.policy,
+            Some(PathBuf::from("/etc/policy.json"))
+        );
+        assert_eq!(config.attestation.interval(), Duration::from_secs(60));
+        assert_eq!(config.attestation.timeout_secs, 30);
+        assert_eq!(config.access_log.path, Some(PathBuf::from("-")));
+        assert_eq!(config.access_log.format, AccessLogFormat::Clf);
+        assert!(!config.logging.connections);
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.logging.level, "info");
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(Config::from_toml("alowlist = [\"host:443\"]").is_err());
+        assert!(Config::from_toml("[limits]\nmax_conections = 1").is_err());
+        // The stream window is fixed by the mux protocol, not configurable
+        assert!(Config::from_toml("[limits]\nstream_window = 65536").is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let mut config = Config::from_toml("target = \"a:443\"\nallowlist = [\"a:443\"]").unwrap();
+        config.apply_env(env(&[
+            ("ATLS_PROXY_ALLOWLIST", " a:443 , b:443 ,"),
+            ("ATLS_PROXY_LISTEN", "0.0.0.0:1,0.0.0.0:2"),
+            ("ATLS_PROXY_TARGET", ""),
+        ]));
+
+        assert_eq!(config.allowlist, vec!["a:443", "b:443"]);
+        config.apply_env(env(&[(
+            "ATLS_PROXY_SNI_ROUTES",
+            "a.example.com=a:443, b.example.com = b:443",
+        )]));
+        assert_eq!(config.sni.routes.len(), 2);
+        assert_eq!(config.sni.routes["b.example.com"], "b:443");
+        assert_eq!(config.listen, vec!["0.0.0.0:1", "0.0.0.0:2"]);
+        // Blank variables don't override
+        assert_eq!(config.target, "a:443");
+    }
+
+    #[test]
+    fn test_validate_tls() {
+        let mut config = Config::default();
+        config.tls.cert = Some(PathBuf::from("cert.pem"));
+        assert!(config.validate().is_err());
+
+        config.tls.key = Some(PathBuf::from("key.pem"));
+        config.validate().unwrap();
+
+        config.tls.acme_domains = vec!["proxy.example.com".to_string()];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_listeners() {
+        let config = Config::from_toml(
+            r#"
+            [[listeners]]
+            address = "[::]:443"
+            tls = { cert = "/etc/cert.pem", key = "/etc/key.pem" }
+
+            [[listeners]]
+            address = "127.0.0.1:9001"
+            tls = {}
+            "#,
+        )
+        .unwrap();
+        // The default address is replaced
+        assert!(config.listen.is_empty());
+        assert_eq!(config.listeners.len(), 2);
+        assert_eq!(
+            config.listeners[0].tls.as_ref().unwrap().cert,
+            Some(PathBuf::from("/etc/cert.pem"))
+        );
+        assert_eq!(config.listeners[1].tls, Some(TlsConfig::default()));
+        config.validate().unwrap();
+
+        let config = Config::from_toml(
+            r#"
+            listen = ["0.0.0.0:9000"]
+
+            [[listeners]]
+            address = "0.0.0.0:443"
+            tls = { cert = "/etc/cert.pem" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.listen, vec!["0.0.0.0:9000"]);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("listener 0.0.0.0:443"));
+    }
+
+    #[test]
+    fn test_validate_rotation_notice() {
+        let mut config = Config::default();
+        config.timeouts.rotation_notice_secs = 60;
+        assert!(config.validate().is_err());
+
+        config.timeouts.max_tunnel_secs = 60;
+        assert!(config.validate().is_err());
+
+        config.timeouts.max_tunnel_secs = 3600;
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_auth() {
+        let mut config = Config::from_toml(
+            r#"
+            [auth.jwt]
+            issuer = "https://auth.example.com"
+            public_key = "/etc/jwt.pem"
+            "#,
+        )
+        .unwrap();
+        let jwt = config.auth.jwt.clone().unwrap();
+        assert_eq!(jwt.algorithm, "RS256");
+        assert_eq!(jwt.audience, None);
+        config.validate().unwrap();
+
+        config.auth.jwt.as_mut().unwrap().secret = Some("secret".to_string());
+        assert!(config.validate().is_err());
+        assert!(!format!("{:?}", config.auth).contains("\"secret\""));
+
+        config.apply_env(env(&[("ATLS_PROXY_AUTH_TOKENS", "t1,t2")]));
+        assert_eq!(config.auth.tokens, vec!["t1", "t2"]);
+        assert!(!format!("{:?}", config.auth).contains("t1"));
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_arguments() {
+        assert!(Config::load(["--verbose".to_string()]).is_err());
+        assert!(Config::load(["--config".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert!(parse_list("").is_empty());
+        assert_eq!(parse_list("host1:443"), vec!["host1:443"]);
+        assert_eq!(
+            parse_list("  host1:443  ,,host2:8443,"),
+            vec!["host1:443", "host2:8443"]
+        );
+    }
+}
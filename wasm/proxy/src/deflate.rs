@@ -0,0 +1,566 @@
+//! WebSocket compression (permessage-deflate, RFC 7692) with clients: off
+//! unless `[websocket] compression` is set.
+//!
+//! TLS records don't compress, but small tunnel messages, multiplexing frames
+//! and plaintext diagnostics traffic do, which helps on slow links. Browsers
+//! offer the extension on every WebSocket; the proxy accepts an offer it can
+//! honour and always asks for `server_no_context_takeover`, so each message it
+//! sends is compressed on its own and may be left uncompressed when that
+//! doesn't make it smaller (as TLS records never do).
+//!
+//! tungstenite doesn't implement the extension, so [`Deflate`] sits between
+//! it and the client connection and rewrites frames: compressed messages from
+//! the client are inflated and handed on as plain frames, and data frames to
+//! the client are deflated. Until the handshake response has been written,
+//! and for connections that didn't negotiate compression, bytes pass through
+//! untouched.
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Extension token in `Sec-WebSocket-Extensions`.
+const EXTENSION: &str = "permessage-deflate";
+
+/// Parameters of an accepted offer.
+const RESPONSE: &str = "permessage-deflate; server_no_context_takeover";
+
+/// Offer parameters the proxy can accept: it decompresses with the largest
+/// window, so whatever the client compresses with is fine.
+const ACCEPTED_PARAMS: [&str; 3] = [
+    "client_max_window_bits",
+    "client_no_context_takeover",
+    "server_no_context_takeover",
+];
+
+/// Largest message accepted from a client, inflated (tungstenite's default).
+const MAX_MESSAGE: usize = 64 << 20;
+
+/// Messages to the client shorter than this are sent uncompressed.
+const MIN_COMPRESSED: usize = 64;
+
+/// Ends every compressed message, and is left off on the wire.
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+const END_OF_HEAD: &[u8; 4] = b"\r\n\r\n";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_CLOSE: u8 = 0x8;
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const MASK: u8 = 0x80;
+
+/// The `Sec-WebSocket-Extensions` value accepting permessage-deflate, if
+/// one of the `offers` (the request's values of that header) can be.
+pub fn negotiate<'a>(offers: impl Iterator<Item = &'a str>) -> Option<&'static str> {
+    let mut offers = offers.flat_map(|value| value.split(','));
+    let acceptable = offers.any(|offer| {
+        let mut params = offer.split(';').map(str::trim);
+        params.next() == Some(EXTENSION)
+            && params.all(|param| {
+                let name = param.split('=').next().unwrap_or_default().trim();
+                ACCEPTED_PARAMS.contains(&name)
+            })
+    });
+    acceptable.then_some(RESPONSE)
+}
+
+/// Compresses the WebSocket messages on a client connection, once `enabled`
+/// is set by the handshake.
+pub struct Deflate<S> {
+    inner: S,
+    enabled: Arc<AtomicBool>,
+    mode: Mode,
+    read: Inflater,
+    write: Deflater,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Writing the handshake response; the bytes of `\r\n\r\n` matched so far.
+    Handshake(usize),
+    /// Rewriting frames.
+    Frames,
+    /// Compression wasn't negotiated.
+    Passthrough,
+}
+
+/// Inflates the compressed messages read from the client.
+struct Inflater {
+    /// Decompression context, kept across messages unless the client resets
+    /// its own (which needs nothing from this side).
+    decompress: Decompress,
+    /// Bytes read and not yet parsed into frames.
+    raw: Vec<u8>,
+    /// Frames ready for tungstenite, from `pos`.
+    out: Vec<u8>,
+    pos: usize,
+    /// The opcode and compressed payload of a fragmented message so far.
+    message: Option<(u8, Vec<u8>)>,
+    eof: bool,
+}
+
+/// Deflates the data frames written to the client.
+struct Deflater {
+    compress: Compress,
+    /// Bytes written and not yet a whole frame.
+    pending: Vec<u8>,
+    /// Frames to write to the client, from `pos`.
+    out: Vec<u8>,
+    pos: usize,
+}
+
+/// A parsed frame header.
+struct Header {
+    first: u8,
+    mask: Option<[u8; 4]>,
+    len: usize,
+    payload_len: usize,
+}
+
+impl<S> Deflate<S> {
+    pub fn new(inner: S, enabled: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            enabled,
+            mode: Mode::Handshake(0),
+            read: Inflater {
+                decompress: Decompress::new(false),
+                raw: Vec::new(),
+                out: Vec::new(),
+                pos: 0,
+                message: None,
+                eof: false,
+            },
+            write: Deflater {
+                compress: Compress::new(Compression::default(), false),
+                pending: Vec::new(),
+                out: Vec::new(),
+                pos: 0,
+            },
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Deflate<S> {
+    /// Write out the frames already deflated.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let write = &mut self.write;
+        while write.pos < write.out.len() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &write.out[write.pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            write.pos += n;
+        }
+        write.out.clear();
+        write.pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Deflate<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.mode != Mode::Frames {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let read = &mut this.read;
+        loop {
+            if read.pos < read.out.len() {
+                let n = buf.remaining().min(read.out.len() - read.pos);
+                buf.put_slice(&read.out[read.pos..read.pos + n]);
+                read.pos += n;
+                return Poll::Ready(Ok(()));
+            }
+            read.out.clear();
+            read.pos = 0;
+            read.parse()?;
+            if !read.out.is_empty() {
+                continue;
+            }
+            if read.eof {
+                // Hand on a truncated frame so tungstenite reports it
+                read.out = std::mem::take(&mut read.raw);
+                if read.out.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+            let mut chunk = [0u8; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                read.eof = true;
+            }
+            read.raw.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Deflate<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.mode {
+            Mode::Passthrough => Pin::new(&mut this.inner).poll_write(cx, buf),
+            Mode::Handshake(matched) => {
+                // Stop at the end of the response, after which frames start
+                let end = end_of_head(matched, buf).unwrap_or(buf.len());
+                let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..end]))?;
+                this.mode = match end_of_head(matched, &buf[..n]) {
+                    Some(_) if this.enabled.load(Ordering::Relaxed) => Mode::Frames,
+                    Some(_) => Mode::Passthrough,
+                    None => Mode::Handshake(matched_after(matched, &buf[..n])),
+                };
+                Poll::Ready(Ok(n))
+            }
+            Mode::Frames => {
+                ready!(this.poll_drain(cx))?;
+                this.write.pending.extend_from_slice(buf);
+                this.write.deflate()?;
+                // Start sending; the rest goes out on flush or the next write
+                if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Ready(Ok(buf.len()))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+impl Inflater {
+    /// Move the whole frames in `raw` to `out`, inflating compressed
+    /// messages once they are complete.
+    fn parse(&mut self) -> io::Result<()> {
+        let mut start = 0;
+        while let Some(header) = parse_header(&self.raw[start..])? {
+            let frame_len = header.len + header.payload_len;
+            if self.raw.len() - start < frame_len {
+                break;
+            }
+            let frame = &self.raw[start..start + frame_len];
+            start += frame_len;
+            let opcode = header.first & 0x0f;
+            let compressed = header.first & RSV1 != 0;
+            let continues = opcode == OPCODE_CONTINUATION && self.message.is_some();
+            if opcode >= OPCODE_CLOSE || !(compressed || continues) {
+                self.out.extend_from_slice(frame);
+                continue;
+            }
+            if compressed && self.message.is_some() {
+                return Err(invalid("compressed frame inside a fragmented message"));
+            }
+            let (_, payload) = self.message.get_or_insert_with(|| (opcode, Vec::new()));
+            let start_of_frame = payload.len();
+            payload.extend_from_slice(&frame[header.len..]);
+            if let Some(mask) = header.mask {
+                for (i, byte) in payload[start_of_frame..].iter_mut().enumerate() {
+                    *byte ^= mask[i % 4];
+                }
+            }
+            if payload.len() > MAX_MESSAGE {
+                return Err(invalid("compressed message too large"));
+            }
+            if header.first & FIN != 0 {
+                let (opcode, mut payload) = self.message.take().unwrap_or_default();
+                payload.extend_from_slice(&TRAILER);
+                let message = self.inflate(&payload)?;
+                // Masked, as tungstenite expects from a client, with a zero key
+                encode_frame(&mut self.out, FIN | opcode, Some([0; 4]), &message);
+            }
+        }
+        self.raw.drain(..start);
+        Ok(())
+    }
+
+    fn inflate(&mut self, input: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(input.len() * 4);
+        let start = self.decompress.total_in();
+        loop {
+            if output.len() > MAX_MESSAGE {
+                return Err(invalid("inflated message too large"));
+            }
+            if output.len() == output.capacity() {
+                output.reserve(output.len().max(4096));
+            }
+            let consumed = (self.decompress.total_in() - start) as usize;
+            let before = (self.decompress.total_in(), self.decompress.total_out());
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|e| invalid(&format!("invalid compressed message: {e}")))?;
+            if status == Status::StreamEnd {
+                // The client ended its stream; its next message starts a new one
+                self.decompress.reset(false);
+                return Ok(output);
+            }
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if consumed == input.len() && output.len() < output.capacity() {
+                return Ok(output);
+            }
+            let after = (self.decompress.total_in(), self.decompress.total_out());
+            if after == before && output.len() < output.capacity() {
+                return Err(invalid("truncated compressed message"));
+            }
+        }
+    }
+}
+
+impl Deflater {
+    /// Move the whole frames in `pending` to `out`, compressing the data
+    /// messages that shrink.
+    fn deflate(&mut self) -> io::Result<()> {
+        let mut start = 0;
+        while let Some(header) = parse_header(&self.pending[start..])? {
+            let frame_len = header.len + header.payload_len;
+            if self.pending.len() - start < frame_len {
+                break;
+            }
+            let frame = &self.pending[start..start + frame_len];
+            start += frame_len;
+            let opcode = header.first & 0x0f;
+            let payload = &frame[header.len..];
+            // Only unfragmented data messages, which is all tungstenite sends
+            let whole = header.first & FIN != 0 && header.first & RSV1 == 0;
+            let data = opcode != OPCODE_CONTINUATION && opcode < OPCODE_CLOSE;
+            if !(whole && data) || header.mask.is_some() || payload.len() < MIN_COMPRESSED {
+                self.out.extend_from_slice(frame);
+                continue;
+            }
+            let compressed = compress(&mut self.compress, payload)?;
+            if compressed.len() >= payload.len() {
+                self.out.extend_from_slice(frame);
+            } else {
+                encode_frame(&mut self.out, FIN | RSV1 | opcode, None, &compressed);
+            }
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+}
+
+/// Compress one message from a fresh context, without the trailer.
+fn compress(compress: &mut Compress, input: &[u8]) -> io::Result<Vec<u8>> {
+    compress.reset();
+    let start = compress.total_in();
+    let mut output = Vec::with_capacity(input.len() / 2 + 64);
+    loop {
+        if output.len() == output.capacity() {
+            output.reserve(output.len());
+        }
+        let consumed = (compress.total_in() - start) as usize;
+        compress
+            .compress_vec(&input[consumed..], &mut output, FlushCompress::Sync)
+            .map_err(|e| invalid(&e.to_string()))?;
+        // Done once everything is in and the flush fit in the buffer
+        let consumed = (compress.total_in() - start) as usize;
+        if consumed == input.len() && output.len() < output.capacity() {
+            break;
+        }
+    }
+    if output.ends_with(&TRAILER) {
+        output.truncate(output.len() - TRAILER.len());
+    }
+    Ok(output)
+}
+
+/// Parse the frame header at the start of `buf`, `None` if it is incomplete.
+fn parse_header(buf: &[u8]) -> io::Result<Option<Header>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let masked = buf[1] & MASK != 0;
+    let (payload_len, mut len) = match buf[1] & 0x7f {
+        126 if buf.len() < 4 => return Ok(None),
+        126 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() < 10 => return Ok(None),
+        127 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(bytes), 10)
+        }
+        short => (u64::from(short), 2),
+    };
+    if payload_len > MAX_MESSAGE as u64 {
+        return Err(invalid("frame too large"));
+    }
+    let mask = if masked {
+        if buf.len() < len + 4 {
+            return Ok(None);
+        }
+        let mask = [buf[len], buf[len + 1], buf[len + 2], buf[len + 3]];
+        len += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    Ok(Some(Header {
+        first: buf[0],
+        mask,
+        len,
+        payload_len: payload_len as usize,
+    }))
+}
+
+/// Append a frame with an already masked (or unmasked) `payload`.
+fn encode_frame(out: &mut Vec<u8>, first: u8, mask: Option<[u8; 4]>, payload: &[u8]) {
+    out.push(first);
+    let mask_bit = if mask.is_some() { MASK } else { 0 };
+    match payload.len() {
+        len if len < 126 => out.push(mask_bit | len as u8),
+        len if len <= usize::from(u16::MAX) => {
+            out.push(mask_bit | 126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(mask_bit | 127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    if let Some(mask) = mask {
+        out.extend_from_slice(&mask);
+    }
+    out.extend_from_slice(payload);
+}
+
+/// Where the handshake response ends in `buf`, given the bytes of
+/// `\r\n\r\n` already `matched`.
+fn end_of_head(mut matched: usize, buf: &[u8]) -> Option<usize> {
+    for (i, &byte) in buf.iter().enumerate() {
+        matched = next_match(matched, byte);
+        if matched == END_OF_HEAD.len() {
+            return Some(i + 1);
+        }
+    }
+    None
+}
+
+fn matched_after(matched: usize, buf: &[u8]) -> usize {
+    buf.iter()
+        .fold(matched, |matched, &byte| next_match(matched, byte))
+}
+
+fn next_match(matched: usize, byte: u8) -> usize {
+    if byte == END_OF_HEAD[matched] {
+        matched + 1
+    } else if byte == b'\r' {
+        1
+    } else {
+        0
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const RESPONSE_HEAD: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n";
+
+    #[test]
+    fn test_negotiate() {
+        let offers = ["permessage-deflate; client_max_window_bits"];
+        assert_eq!(negotiate(offers.into_iter()), Some(RESPONSE));
+        // The proxy can't shrink its window, so skips to the next offer
+        let offers = ["permessage-deflate; server_max_window_bits=10, permessage-deflate"];
+        assert_eq!(negotiate(offers.into_iter()), Some(RESPONSE));
+        let offers = ["permessage-deflate; server_max_window_bits=10"];
+        assert_eq!(negotiate(offers.into_iter()), None);
+        assert_eq!(negotiate(["x-webkit-deflate-frame"].into_iter()), None);
+        assert_eq!(negotiate(std::iter::empty()), None);
+    }
+
+    async fn handshake(
+        enabled: bool,
+    ) -> (Deflate<tokio::io::DuplexStream>, tokio::io::DuplexStream) {
+        let (proxy, mut client) = tokio::io::duplex(1 << 20);
+        let mut deflate = Deflate::new(proxy, Arc::new(AtomicBool::new(enabled)));
+        deflate.write_all(RESPONSE_HEAD).await.unwrap();
+        let mut head = vec![0u8; RESPONSE_HEAD.len()];
+        client.read_exact(&mut head).await.unwrap();
+        assert_eq!(head, RESPONSE_HEAD);
+        (deflate, client)
+    }
+
+    #[tokio::test]
+    async fn test_frames_to_client_are_compressed() {
+        let (mut deflate, mut client) = handshake(true).await;
+        let message = vec![b'a'; 1000];
+        let mut frame = Vec::new();
+        encode_frame(&mut frame, FIN | 0x2, None, &message);
+        deflate.write_all(&frame).await.unwrap();
+        deflate.flush().await.unwrap();
+
+        let mut head = [0u8; 2];
+        client.read_exact(&mut head).await.unwrap();
+        assert_eq!(head[0], FIN | RSV1 | 0x2);
+        let mut payload = vec![0u8; usize::from(head[1])];
+        client.read_exact(&mut payload).await.unwrap();
+        payload.extend_from_slice(&TRAILER);
+        let mut inflated = Vec::with_capacity(2000);
+        Decompress::new(false)
+            .decompress_vec(&payload, &mut inflated, FlushDecompress::Sync)
+            .unwrap();
+        assert_eq!(inflated, message);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_frames_from_client_are_inflated() {
+        let (mut deflate, mut client) = handshake(true).await;
+        let message = vec![b'b'; 1000];
+        let mut compressed =
+            compress(&mut Compress::new(Compression::default(), false), &message).unwrap();
+        let mask = [1, 2, 3, 4];
+        for (i, byte) in compressed.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        let mut frame = Vec::new();
+        encode_frame(&mut frame, FIN | RSV1 | 0x2, Some(mask), &compressed);
+        client.write_all(&frame).await.unwrap();
+
+        let mut expected = Vec::new();
+        encode_frame(&mut expected, FIN | 0x2, Some([0; 4]), &message);
+        let mut received = vec![0u8; expected.len()];
+        deflate.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_passthrough_without_negotiation() {
+        let (mut deflate, mut client) = handshake(false).await;
+        let mut frame = Vec::new();
+        encode_frame(&mut frame, FIN | 0x2, None, &[b'c'; 1000]);
+        deflate.write_all(&frame).await.unwrap();
+        let mut received = vec![0u8; frame.len()];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, frame);
+    }
+}
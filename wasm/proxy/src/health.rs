@@ -0,0 +1,220 @@
+//! `/healthz` and `/readyz` probes for load balancers and Kubernetes.
+//!
+//! Plain HTTP `GET`s of these paths on the listener (over TLS if it serves
+//! `wss://`) are answered directly instead of being upgraded to a WebSocket:
+//!
+//! - `/healthz`: `200` while the proxy accepts connections.
+//! - `/readyz`: `200` if the default target accepts a connection within
+//!   the connect timeout, `503` otherwise.
+//!
+//! Probes need no token and are not logged as connections.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::mux::SessionConfig;
+use crate::tls::ClientStream;
+
+/// Longest request head read while looking for a probe.
+const MAX_HEAD: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    Health,
+    Ready,
+}
+
+/// Read the request head from `stream`. A probe is returned with the stream
+/// to answer it on; anything else gets the stream back with the head
+/// replayed, ready for the WebSocket handshake.
+pub async fn intercept(mut stream: ClientStream) -> io::Result<(ClientStream, Option<Probe>)> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 2048];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    let probe = parse_probe(&head);
+    Ok((Box::new(Replay::new(head, stream)), probe))
+}
+
+/// Answer `probe`, checking `target` for readiness.
+pub async fn respond(
+    mut stream: ClientStream,
+    probe: Probe,
+    target: &str,
+    session: &SessionConfig,
+) -> io::Result<()> {
+    let ready = match probe {
+        Probe::Health => true,
+        Probe::Ready => matches!(
+            tokio::time::timeout(session.connect_timeout, session.resolver.connect(target)).await,
+            Ok(Ok(_))
+        ),
+    };
+    let (status, body) = if ready {
+        ("200 OK", "ok\n")
+    } else {
+        ("503 Service Unavailable", "default target unreachable\n")
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// The probe a request head asks for, if it is a plain `GET` of a probe path
+/// rather than a WebSocket upgrade.
+fn parse_probe(head: &[u8]) -> Option<Probe> {
+    let head = std::str::from_utf8(head).ok()?;
+    let mut lines = head.split("\r\n");
+    let mut request = lines.next()?.split(' ');
+    if request.next()? != "GET" {
+        return None;
+    }
+    let path = request.next()?;
+    let path = path.split('?').next().unwrap_or(path);
+    let probe = match path {
+        "/healthz" => Probe::Health,
+        "/readyz" => Probe::Ready,
+        _ => return None,
+    };
+    let upgrade = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("upgrade")
+                && value.trim().eq_ignore_ascii_case("websocket")
+        })
+    });
+    (!upgrade).then_some(probe)
+}
+
+/// Reads `prefix` before the rest of `inner`.
+struct Replay<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Replay<S> {
+    fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Replay<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pos < this.prefix.len() {
+            let n = buf.remaining().min(this.prefix.len() - this.pos);
+            buf.put_slice(&this.prefix[this.pos..this.pos + n]);
+            this.pos += n;
+            if this.pos == this.prefix.len() {
+                this.prefix = Vec::new();
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Replay<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_probe() {
+        assert_eq!(
+            parse_probe(b"GET /healthz HTTP/1.1\r\nHost: proxy\r\n\r\n"),
+            Some(Probe::Health)
+        );
+        assert_eq!(
+            parse_probe(b"GET /readyz?verbose=1 HTTP/1.1\r\n\r\n"),
+            Some(Probe::Ready)
+        );
+        assert_eq!(
+            parse_probe(b"GET /healthz HTTP/1.1\r\nUpgrade: websocket\r\n\r\n"),
+            None
+        );
+        assert_eq!(parse_probe(b"GET /?target=host:443 HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(parse_probe(b"POST /healthz HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(parse_probe(b""), None);
+    }
+
+    #[tokio::test]
+    async fn test_intercept_replays_other_requests() {
+        let request = b"GET /?target=host:443 HTTP/1.1\r\nUpgrade: websocket\r\n\r\n";
+        let (client, mut server) = tokio::io::duplex(1024);
+        server.write_all(request).await.unwrap();
+        server.write_all(b"frame").await.unwrap();
+
+        let (mut stream, probe) = intercept(Box::new(client)).await.unwrap();
+        assert_eq!(probe, None);
+        let mut replayed = vec![0u8; request.len() + 5];
+        stream.read_exact(&mut replayed).await.unwrap();
+        assert_eq!(&replayed[..request.len()], request);
+        assert_eq!(&replayed[request.len()..], b"frame");
+    }
+
+    #[tokio::test]
+    async fn test_readiness_follows_default_target() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let up = listener.local_addr().unwrap().to_string();
+        let down = {
+            let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            closed.local_addr().unwrap().to_string()
+        };
+
+        for (target, expected) in [(up.as_str(), "200 OK"), (down.as_str(), "503")] {
+            let (client, mut server) = tokio::io::duplex(1024);
+            respond(
+                Box::new(client),
+                Probe::Ready,
+                target,
+                &SessionConfig::default(),
+            )
+            .await
+            .unwrap();
+            let mut response = String::new();
+            server.read_to_string(&mut response).await.unwrap();
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {expected}")),
+                "{response}"
+            );
+        }
+    }
+}
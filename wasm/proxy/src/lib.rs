@@ -0,0 +1,63 @@
+//! Minimal WebSocket -> TCP forwarder for aTLS tunnels.
+//! Accepts binary WebSocket connections and pipes bytes to a configured TCP target.
+//! With `?mux=1`, one WebSocket carries several tunnels (see `mux`), and the
+//! listeners, each with its own TLS for `wss://` or none, can bind several
+//! addresses across both IP families (see `listener` and `tls`). Settings
+//! come from an optional TOML file and the environment (see [`config`]); logs
+//! go through `tracing` (see `logging`). `/healthz` and `/readyz` answer
+//! probes (see `health`). With `?udp=1`, messages are datagrams to a UDP target (see
+//! `udp`). Clients that name no target can be routed by the server name
+//! of their TLS ClientHello (see `sni`). The proxy can attest targets itself
+//! before tunnelling to them (see `attest`), restrict which client addresses
+//! connect (see `clients`) and tell targets the client's address with a
+//! PROXY protocol header (see `proxy_protocol`). WebSocket messages can be
+//! compressed with clients that offer permessage-deflate (see `deflate`).
+//!
+//! The `atlas-proxy` binary runs a [`ProxyServer`] built from its
+//! configuration; applications can embed one the same way, in their own
+//! binaries or tests:
+//!
+//! ```no_run
+//! use atlas_proxy::ProxyServer;
+//! use tokio::net::TcpListener;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! let listener = TcpListener::bind("127.0.0.1:0").await?;
+//! let server = ProxyServer::builder()
+//!     .target("vllm.example.com:443")
+//!     .allowlist(|target: &str| target == "vllm.example.com:443")
+//!     .listener(listener)
+//!     .build()?;
+//! let shutdown = server.shutdown_handle();
+//! let running = tokio::spawn(server.run());
+//! // ...
+//! shutdown.shutdown();
+//! running.await??;
+//! # Ok(())
+//! # }
+//! ```
+
+mod access_log;
+pub mod allowlist;
+mod attest;
+mod auth;
+mod clients;
+pub mod config;
+mod deflate;
+mod health;
+mod limits;
+mod listener;
+mod logging;
+mod mux;
+mod proxy_protocol;
+mod resolve;
+mod server;
+mod sni;
+mod tls;
+mod udp;
+
+pub use allowlist::{Allowlist, AllowlistProvider};
+pub use config::Config;
+pub use listener::systemd_listeners;
+pub use logging::init as init_logging;
+pub use server::{ProxyServer, ProxyServerBuilder, ReloadHandle, ShutdownHandle};
@@ -0,0 +1,315 @@
+//! Per-client limits: concurrent connections and new-connection rate per
+//! source IP, and bandwidth per connection.
+//!
+//! Each is configured in `[limits]` and disabled when set to 0.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+/// Source IPs tracked before idle ones are pruned.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Why a client connection was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refused {
+    TooManyConnections,
+    RateLimited,
+}
+
+impl std::fmt::Display for Refused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::TooManyConnections => "too many connections from this address",
+            Self::RateLimited => "connection rate limit exceeded",
+        })
+    }
+}
+
+/// Admits client connections per source IP.
+pub struct ClientLimits {
+    max_connections: AtomicUsize,
+    per_minute: AtomicU32,
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+struct Client {
+    active: usize,
+    /// New connections the client may open right now.
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Counts as one of its IP's connections until dropped.
+pub struct ClientGuard {
+    limits: Arc<ClientLimits>,
+    ip: IpAddr,
+}
+
+impl ClientLimits {
+    /// `max_connections` concurrent connections and `per_minute` new ones
+    /// per source IP; 0 disables either.
+    pub fn new(max_connections: usize, per_minute: u32) -> Self {
+        Self {
+            max_connections: AtomicUsize::new(max_connections),
+            per_minute: AtomicU32::new(per_minute),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Change the limits, such as on a configuration reload. Connections
+    /// already admitted are kept.
+    pub fn set_limits(&self, max_connections: usize, per_minute: u32) {
+        self.max_connections
+            .store(max_connections, Ordering::Relaxed);
+        self.per_minute.store(per_minute, Ordering::Relaxed);
+    }
+
+    /// Admit a new connection from `ip`, or say why not.
+    pub fn admit(self: &Arc<Self>, ip: IpAddr) -> Result<ClientGuard, Refused> {
+        let now = Instant::now();
+        let max_connections = self.max_connections.load(Ordering::Relaxed);
+        let per_minute = self.per_minute.load(Ordering::Relaxed);
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if clients.len() >= PRUNE_THRESHOLD {
+            clients.retain(|_, client| {
+                refill(client, per_minute, now);
+                client.active > 0 || client.tokens < f64::from(per_minute)
+            });
+        }
+
+        let client = clients.entry(ip).or_insert_with(|| Client {
+            active: 0,
+            tokens: f64::from(per_minute),
+            refilled: now,
+        });
+        if max_connections > 0 && client.active >= max_connections {
+            return Err(Refused::TooManyConnections);
+        }
+        if per_minute > 0 {
+            refill(client, per_minute, now);
+            if client.tokens < 1.0 {
+                return Err(Refused::RateLimited);
+            }
+            client.tokens -= 1.0;
+        }
+        client.active += 1;
+        Ok(ClientGuard {
+            limits: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// Credit the connections earned since the last refill, up to a minute's
+/// worth.
+fn refill(client: &mut Client, per_minute: u32, now: Instant) {
+    let rate = f64::from(per_minute);
+    let elapsed = now.duration_since(client.refilled).as_secs_f64();
+    client.tokens = (client.tokens + elapsed * rate / 60.0).min(rate);
+    client.refilled = now;
+}
+
+impl Drop for ClientGuard {
+    fn drop(&mut self) {
+        let mut clients = self
+            .limits
+            .clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = clients.get_mut(&self.ip) {
+            client.active -= 1;
+            // Without a rate limit there is nothing left to remember
+            if client.active == 0 && self.limits.per_minute.load(Ordering::Relaxed) == 0 {
+                clients.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Limits each direction of a stream to a number of bytes per second.
+pub struct Throttled<S> {
+    inner: S,
+    read: Bucket,
+    write: Bucket,
+}
+
+impl<S> Throttled<S> {
+    pub fn new(inner: S, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            read: Bucket::new(bytes_per_sec),
+            write: Bucket::new(bytes_per_sec),
+        }
+    }
+}
+
+/// Token bucket holding up to one second of traffic.
+///
+/// A read or write may take the bucket negative; the next one then waits
+/// until it has refilled.
+struct Bucket {
+    rate: f64,
+    available: f64,
+    refilled: tokio::time::Instant,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Bucket {
+    fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec as f64;
+        Self {
+            rate,
+            available: rate,
+            refilled: tokio::time::Instant::now(),
+            sleep: None,
+        }
+    }
+
+    /// Ready once there is credit to transfer at least one byte.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if let Some(sleep) = &mut self.sleep {
+                ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+            let now = tokio::time::Instant::now();
+            let elapsed = now.duration_since(self.refilled).as_secs_f64();
+            self.available = (self.available + elapsed * self.rate).min(self.rate);
+            self.refilled = now;
+            if self.available >= 1.0 {
+                return Poll::Ready(());
+            }
+            let wait = Duration::from_secs_f64((1.0 - self.available) / self.rate);
+            self.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+
+    fn consume(&mut self, bytes: usize) {
+        self.available -= bytes as f64;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Throttled<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.read.poll_ready(cx));
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.read.consume(buf.filled().len() - before);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Throttled<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.write.poll_ready(cx));
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.write.consume(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+
+    #[test]
+    fn test_connections_per_ip() {
+        let limits = Arc::new(ClientLimits::new(2, 0));
+        let first = limits.admit(CLIENT).unwrap();
+        let _second = limits.admit(CLIENT).unwrap();
+        assert_eq!(
+            limits.admit(CLIENT).err(),
+            Some(Refused::TooManyConnections)
+        );
+        // Other clients have their own count
+        let _other = limits.admit(OTHER).unwrap();
+
+        drop(first);
+        let _third = limits.admit(CLIENT).unwrap();
+    }
+
+    #[test]
+    fn test_connection_rate_per_ip() {
+        let limits = Arc::new(ClientLimits::new(0, 3));
+        for _ in 0..3 {
+            drop(limits.admit(CLIENT).unwrap());
+        }
+        assert_eq!(limits.admit(CLIENT).err(), Some(Refused::RateLimited));
+        let _other = limits.admit(OTHER).unwrap();
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limits = Arc::new(ClientLimits::new(0, 0));
+        let guards: Vec<_> = (0..100).map(|_| limits.admit(CLIENT).unwrap()).collect();
+        drop(guards);
+        assert!(limits.clients.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_limits() {
+        let limits = Arc::new(ClientLimits::new(1, 0));
+        let first = limits.admit(CLIENT).unwrap();
+        assert!(limits.admit(CLIENT).is_err());
+        limits.set_limits(2, 0);
+        let _second = limits.admit(CLIENT).unwrap();
+
+        // Lowering the limit keeps admitted connections
+        limits.set_limits(1, 0);
+        assert!(limits.admit(CLIENT).is_err());
+        drop(first);
+        assert!(limits.admit(CLIENT).is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_bandwidth() {
+        let (client, mut server) = tokio::io::duplex(64 * 1024);
+        let mut throttled = Throttled::new(client, 1000);
+        let start = tokio::time::Instant::now();
+
+        let writer = tokio::spawn(async move {
+            for _ in 0..5 {
+                throttled.write_all(&[0u8; 1000]).await.unwrap();
+            }
+        });
+        let mut received = vec![0u8; 5000];
+        server.read_exact(&mut received).await.unwrap();
+        writer.await.unwrap();
+
+        // One second of burst, then 1000 bytes per second
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(3), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    }
+}
@@ -0,0 +1,171 @@
+//! Binding listen addresses.
+//!
+//! `listen` addresses serve the `[tls]` settings, and each `[[listeners]]`
+//! entry its own `tls` table, so one process can serve `wss://` on 443 and
+//! plaintext `ws://` on a loopback port for a local sidecar.
+//!
+//! On Linux an IPv6 wildcard (`[::]`) is dual-stack by default, so listing
+//! `0.0.0.0:9000` with `[::]:9000` would fail to bind the second. An IPv6
+//! address that shares its port with an IPv4 one is therefore bound
+//! IPv6-only; alone, it keeps the system default and also accepts IPv4
+//! clients where the system allows.
+//!
+//! Under systemd socket activation (`LISTEN_PID` and `LISTEN_FDS` naming this
+//! process), the `atlas-proxy` binary takes the sockets systemd passes with
+//! [`systemd_listeners`] and serves them instead of binding any, so systemd
+//! keeps accepting connections while the proxy restarts. The library never
+//! takes them on its own: an application embedding the proxy may have its own
+//! activated sockets.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, RawFd};
+use tokio::net::TcpListener;
+
+/// Connections queued for acceptance, as tokio's own `bind` uses.
+const BACKLOG: i32 = 1024;
+
+/// The first descriptor systemd passes, after stdin, stdout and stderr.
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Bind `address`, one of `addresses` (every address the proxy listens on).
+pub async fn bind(address: &str, addresses: &[String]) -> io::Result<TcpListener> {
+    // Hostnames such as `localhost:9000` are bound as the system resolves them
+    let Ok(addr) = address.parse::<SocketAddr>() else {
+        return TcpListener::bind(address).await;
+    };
+    if !v6_only(addr, addresses) {
+        return TcpListener::bind(addr).await;
+    }
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// The sockets systemd passed to this process, none without socket
+/// activation. The variables are unset so no child process takes the sockets
+/// as its own.
+///
+/// Call it once, before starting any threads (such as the tokio runtime):
+/// it takes ownership of every passed descriptor and changes the environment.
+/// Hand the sockets to [`ProxyServerBuilder::listener`] once the runtime runs.
+///
+/// [`ProxyServerBuilder::listener`]: crate::ProxyServerBuilder::listener
+#[cfg(unix)]
+pub fn systemd_listeners() -> io::Result<Vec<std::net::TcpListener>> {
+    let pid = std::env::var("LISTEN_PID").ok();
+    if pid.and_then(|pid| pid.parse::<u32>().ok()) != Some(std::process::id()) {
+        return Ok(Vec::new());
+    }
+    let count: RawFd = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(var);
+    }
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(|fd| {
+            // SAFETY: systemd hands these descriptors to this process, and
+            // callers take them before anything else in it can
+            unsafe { take(fd) }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn systemd_listeners() -> io::Result<Vec<std::net::TcpListener>> {
+    Ok(Vec::new())
+}
+
+/// Listen on the socket `fd`, which must be a listening TCP socket.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor that nothing else owns.
+#[cfg(unix)]
+unsafe fn take(fd: RawFd) -> io::Result<std::net::TcpListener> {
+    let socket = Socket::from_raw_fd(fd);
+    let tcp = socket.r#type()? == Type::STREAM && socket.local_addr()?.as_socket().is_some();
+    if !tcp {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("socket {fd} from systemd is not a TCP socket (use ListenStream=)"),
+        ));
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// Whether `addr` must be bound IPv6-only: some IPv4 address in `addresses`
+/// has the same (fixed) port.
+fn v6_only(addr: SocketAddr, addresses: &[String]) -> bool {
+    addr.is_ipv6()
+        && addr.port() != 0
+        && addresses
+            .iter()
+            .filter_map(|other| other.parse::<SocketAddr>().ok())
+            .any(|other| other.is_ipv4() && other.port() == addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_v6_only() {
+        let both = addresses(&["0.0.0.0:9000", "[::]:9000"]);
+        assert!(v6_only("[::]:9000".parse().unwrap(), &both));
+        assert!(!v6_only("0.0.0.0:9000".parse().unwrap(), &both));
+
+        let other_port = addresses(&["0.0.0.0:9000", "[::]:9443"]);
+        assert!(!v6_only("[::]:9443".parse().unwrap(), &other_port));
+        let ephemeral = addresses(&["127.0.0.1:0", "[::1]:0"]);
+        assert!(!v6_only("[::1]:0".parse().unwrap(), &ephemeral));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_take_activated_sockets() {
+        use std::os::unix::io::IntoRawFd;
+
+        let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = std_listener.local_addr().unwrap();
+        let listener = unsafe { take(std_listener.into_raw_fd()) }.unwrap();
+        let listener = TcpListener::from_std(listener).unwrap();
+        assert_eq!(listener.local_addr().unwrap(), addr);
+        let (_client, accepted) =
+            tokio::join!(tokio::net::TcpStream::connect(addr), listener.accept());
+        accepted.unwrap();
+
+        let udp = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let err = unsafe { take(udp.into_raw_fd()) }.unwrap_err();
+        assert!(err.to_string().contains("not a TCP socket"));
+    }
+
+    #[tokio::test]
+    async fn test_bind_dual_stack() {
+        // Find a free port, then take it on both families
+        let probe = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let port = probe.local_addr().unwrap().port();
+        drop(probe);
+        let both = addresses(&[&format!("0.0.0.0:{port}"), &format!("[::]:{port}")]);
+        let _ipv4 = bind(&both[0], &both).await.unwrap();
+        // Hosts without IPv6 can't run the rest
+        if let Ok(ipv6) = bind(&both[1], &both).await {
+            assert_eq!(ipv6.local_addr().unwrap().port(), port);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Structured logging with `tracing`.
+//!
+//! Events go to stderr as text or, with `[logging] format = "json"`, one JSON
+//! object per line. Each client connection runs in a `connection` span
+//! carrying `peer`, `target`, `mux` and `udp`, and ends with a `connection closed`
+//! event giving `bytes_in`, `bytes_out`, `duration_ms` and `reason`, which
+//! also goes to the access log if there is one (see `access_log`). Streams of
+//! a multiplexed session get a nested `stream` span with `stream_id` and
+//! `target`. `RUST_LOG` overrides `[logging] level`.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::{Instant, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing_subscriber::EnvFilter;
+
+use crate::access_log::{AccessLog, AccessRecord, CloseReason};
+use crate::config::{LogFormat, Logging};
+
+/// Install the global subscriber described by `config`.
+pub fn init(config: &Logging) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    match config.format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+    }
+}
+
+/// Bytes carried by a client connection.
+#[derive(Debug, Clone, Default)]
+pub struct ByteCounter {
+    read: Arc<AtomicU64>,
+    written: Arc<AtomicU64>,
+}
+
+impl ByteCounter {
+    /// Bytes received from the client.
+    pub fn read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    /// Bytes sent to the client.
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the bytes read from and written to a stream.
+pub struct Counted<S> {
+    inner: S,
+    counter: ByteCounter,
+}
+
+impl<S> Counted<S> {
+    pub fn new(inner: S, counter: ByteCounter) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counted<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let n = buf.filled().len() - before;
+        this.counter.read.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counted<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.counter.written.fetch_add(n as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Logs `connection closed` with the connection's totals when dropped, so
+/// every way out of a connection task is covered.
+pub struct ConnectionLog {
+    peer: SocketAddr,
+    counter: ByteCounter,
+    started: Instant,
+    started_at: SystemTime,
+    enabled: bool,
+    access: Option<Arc<AccessLog>>,
+    kind: Option<&'static str>,
+    target: Option<String>,
+    /// Until told otherwise, the connection ended on an error.
+    reason: CloseReason,
+}
+
+impl ConnectionLog {
+    pub fn new(
+        peer: SocketAddr,
+        counter: ByteCounter,
+        enabled: bool,
+        access: Option<Arc<AccessLog>>,
+    ) -> Self {
+        Self {
+            peer,
+            counter,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+            enabled,
+            access,
+            kind: None,
+            target: None,
+            reason: CloseReason::Error,
+        }
+    }
+
+    /// Record the tunnel the client asked for: `tcp`, `udp` or `mux`.
+    pub fn tunnel(&mut self, kind: &'static str, target: Option<&str>) {
+        self.kind = Some(kind);
+        self.target = target.map(str::to_string);
+    }
+
+    pub fn close(&mut self, reason: CloseReason) {
+        self.reason = reason;
+    }
+
+    /// Don't log this connection, such as a health probe.
+    pub fn quiet(&mut self) {
+        self.enabled = false;
+        self.access = None;
+    }
+}
+
+impl Drop for ConnectionLog {
+    fn drop(&mut self) {
+        let duration = self.started.elapsed();
+        if self.enabled {
+            tracing::info!(
+                bytes_in = self.counter.read(),
+                bytes_out = self.counter.written(),
+                duration_ms = duration.as_millis() as u64,
+                reason = self.reason.as_str(),
+                "connection closed"
+            );
+        }
+        if let Some(access) = &self.access {
+            access.write(&AccessRecord {
+                client: self.peer,
+                kind: self.kind,
+                target: self.target.take(),
+                started: self.started_at,
+                duration,
+                bytes_in: self.counter.read(),
+                bytes_out: self.counter.written(),
+                reason: self.reason,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_counted_stream() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let counter = ByteCounter::default();
+        let mut counted = Counted::new(client, counter.clone());
+
+        counted.write_all(b"hello").await.unwrap();
+        server.write_all(b"hi").await.unwrap();
+        let mut buf = [0u8; 2];
+        counted.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(counter.written(), 5);
+        assert_eq!(counter.read(), 2);
+    }
+}
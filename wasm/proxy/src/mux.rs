@@ -0,0 +1,587 @@
+//! Multiplexed sessions: several tunnels over one WebSocket.
+//!
+//! A client asks for a session by offering the [`PROTOCOL`] subprotocol, which
+//! the proxy echoes, or with `?mux=1` in the URL.
+//!
+//! Each binary message carries one frame: a 1-byte type, the big-endian stream
+//! id, then the payload. Empty messages are keepalives and are ignored.
+//!
+//! | Type | Frame    | Payload                                             |
+//! |------|----------|-----------------------------------------------------|
+//! | 0    | `OPEN`   | target `host:port` (client to proxy)                |
+//! | 1    | `DATA`   | bytes                                               |
+//! | 2    | `WINDOW` | big-endian u32: bytes the peer may send in addition |
+//! | 3    | `CLOSE`  | none: the sender will send no more data             |
+//! | 4    | `RESET`  | optional UTF-8 reason: the stream is aborted        |
+//! | 5    | `GOAWAY` | big-endian u32: seconds until the session closes    |
+//!
+//! Every `OPEN` target is checked against the allowlist, exactly like the
+//! `target` of a plain tunnel; refused and unreachable targets get a `RESET`.
+//! Each direction of a stream may have at most [`INITIAL_WINDOW`] bytes in
+//! flight, a protocol constant the browser assumes too: the proxy stops reading
+//! from a target until the browser grants more, and grants the browser more
+//! once its data is written to the target.
+//!
+//! With `rotation_notice_secs` set, the proxy sends `GOAWAY` (stream id 0)
+//! that long before the session reaches its maximum lifetime; the client
+//! should open new tunnels on a new session and let these finish.
+
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::{info, warn, Instrument};
+
+use crate::access_log::CloseReason;
+use crate::allowlist::AllowlistProvider;
+use crate::attest::{self, Attestor};
+use crate::config::DnsConfig;
+use crate::proxy_protocol::ProxyHeader;
+use crate::resolve::Resolver;
+use crate::server::{expire, going_away, is_target_allowed};
+use crate::tls::ClientStream;
+
+const FRAME_OPEN: u8 = 0;
+const FRAME_DATA: u8 = 1;
+const FRAME_WINDOW: u8 = 2;
+const FRAME_CLOSE: u8 = 3;
+const FRAME_RESET: u8 = 4;
+const FRAME_GOAWAY: u8 = 5;
+
+const HEADER_LEN: usize = 5;
+
+/// `Sec-WebSocket-Protocol` entry negotiating a multiplexed session.
+pub const PROTOCOL: &str = "atlas-mux.v1";
+
+/// Bytes either side may send on a stream before the peer grants more. Fixed
+/// by the protocol: browsers start with this much credit without being told.
+pub const INITIAL_WINDOW: usize = 256 * 1024;
+
+/// Maximum concurrent streams per session, bounding the descriptors one
+/// browser can hold open.
+pub const MAX_STREAMS: usize = 64;
+
+/// Settings of a multiplexed session, from the proxy configuration.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub max_streams: usize,
+    /// How long to wait for a target to accept a stream's connection.
+    pub connect_timeout: Duration,
+    /// Resolves and connects to targets.
+    pub resolver: Arc<Resolver>,
+    /// Close the session after this long without a frame either way.
+    pub idle_timeout: Option<Duration>,
+    /// Close the session (or plain tunnel) this long after it opened.
+    pub max_lifetime: Option<Duration>,
+    /// Tell the client this long before `max_lifetime` that the session (or
+    /// plain tunnel) will close.
+    pub rotation_notice: Option<Duration>,
+    pub log_connections: bool,
+    /// Attests targets before and while tunnelling to them (see `attest`).
+    pub attestation: Option<Arc<Attestor>>,
+    /// Sent to targets before any data, naming the client (see
+    /// `proxy_protocol`).
+    pub proxy_header: Option<ProxyHeader>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            max_streams: MAX_STREAMS,
+            connect_timeout: Duration::from_secs(10),
+            resolver: Arc::new(
+                Resolver::from_config(&DnsConfig::default())
+                    .expect("default DNS settings are valid"),
+            ),
+            idle_timeout: None,
+            max_lifetime: None,
+            rotation_notice: None,
+            log_connections: true,
+            attestation: None,
+            proxy_header: None,
+        }
+    }
+}
+
+impl SessionConfig {
+    /// How long after opening to send the rotation notice, if one is due.
+    pub fn rotation_notice_after(&self) -> Option<Duration> {
+        Some(self.max_lifetime?.saturating_sub(self.rotation_notice?))
+    }
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type WsSink = SplitSink<WebSocketStream<ClientStream>, Message>;
+type WsSource = SplitStream<WebSocketStream<ClientStream>>;
+
+fn encode_frame(kind: u8, id: u32, payload: &[u8]) -> Message {
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.extend_from_slice(payload);
+    Message::Binary(frame)
+}
+
+fn decode_frame(message: &[u8]) -> Option<(u8, u32, &[u8])> {
+    if message.len() < HEADER_LEN {
+        return None;
+    }
+    let id = u32::from_be_bytes(message[1..HEADER_LEN].try_into().ok()?);
+    Some((message[0], id, &message[HEADER_LEN..]))
+}
+
+/// Reported by stream tasks to the session loop, which owns the WebSocket.
+enum Event {
+    /// Bytes read from the target.
+    Data(u32, Vec<u8>),
+    /// Bytes from the browser written to the target.
+    Written(u32, usize),
+    /// The target closed its side.
+    Eof(u32),
+    /// Connecting, reading or writing failed.
+    Failed(u32, String),
+}
+
+struct Stream {
+    /// Data for the target; dropped once the browser sends `CLOSE`.
+    to_target: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    /// Bytes the proxy may still send to the browser.
+    credit: Arc<Semaphore>,
+    /// Bytes received from the browser and not yet written to the target.
+    in_flight: usize,
+    target_closed: bool,
+    task: JoinHandle<()>,
+}
+
+/// Connect to `target` and pump bytes both ways until both sides are closed.
+async fn run_stream(
+    id: u32,
+    target: String,
+    mut from_client: mpsc::UnboundedReceiver<Vec<u8>>,
+    credit: Arc<Semaphore>,
+    events: mpsc::Sender<Event>,
+    config: SessionConfig,
+) {
+    let resolver = &config.resolver;
+    if let Some(attestor) = &config.attestation {
+        if attestor.check(&target, resolver).await.is_err() {
+            let reason = format!("{target} failed attestation");
+            let _ = events.send(Event::Failed(id, reason)).await;
+            return;
+        }
+    }
+    let connect = tokio::time::timeout(config.connect_timeout, resolver.connect(&target));
+    let mut tcp = match connect.await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            warn!(error = %e, "failed to connect to target");
+            let reason = format!("failed to connect to {target}");
+            let _ = events.send(Event::Failed(id, reason)).await;
+            return;
+        }
+        Err(_) => {
+            warn!("timed out connecting to target");
+            let reason = format!("timed out connecting to {target}");
+            let _ = events.send(Event::Failed(id, reason)).await;
+            return;
+        }
+    };
+    if let Some(header) = &config.proxy_header {
+        if let Err(e) = tcp.write_all(&header.encode()).await {
+            warn!(error = %e, "failed to send the PROXY protocol header");
+            let reason = format!("failed to connect to {target}");
+            let _ = events.send(Event::Failed(id, reason)).await;
+            return;
+        }
+    }
+    let (mut tcp_reader, mut tcp_writer) = tokio::io::split(tcp);
+
+    let upload = async {
+        while let Some(chunk) = from_client.recv().await {
+            tcp_writer.write_all(&chunk).await?;
+            let _ = events.send(Event::Written(id, chunk.len())).await;
+        }
+        tcp_writer.shutdown().await
+    };
+    let download = async {
+        let mut buf = vec![0u8; 16 * 1024];
+        loop {
+            let n = tcp_reader.read(&mut buf).await?;
+            if n == 0 {
+                let _ = events.send(Event::Eof(id)).await;
+                return Ok::<(), std::io::Error>(());
+            }
+            // Wait until the browser has room for these bytes
+            match credit.acquire_many(n as u32).await {
+                Ok(permit) => permit.forget(),
+                Err(_) => return Ok(()),
+            }
+            let _ = events.send(Event::Data(id, buf[..n].to_vec())).await;
+        }
+    };
+
+    let revoked = attest::revoked(config.attestation.clone(), &target, resolver);
+    tokio::select! {
+        result = async { tokio::try_join!(upload, download) } => {
+            if let Err(e) = result {
+                let _ = events.send(Event::Failed(id, e.to_string())).await;
+            }
+        }
+        _ = revoked => {
+            let reason = format!("{target} failed attestation");
+            let _ = events.send(Event::Failed(id, reason)).await;
+        }
+    }
+}
+
+/// Serve a multiplexed session until the browser closes the WebSocket.
+pub async fn serve(
+    ws: WebSocketStream<ClientStream>,
+    allowlist: Arc<dyn AllowlistProvider>,
+    config: SessionConfig,
+) -> Result<CloseReason, BoxError> {
+    let (mut ws_sink, mut ws_source) = ws.split();
+    let mut streams: HashMap<u32, Stream> = HashMap::new();
+    let result = run_session(
+        &mut ws_sink,
+        &mut ws_source,
+        &mut streams,
+        allowlist.as_ref(),
+        &config,
+    )
+    .await;
+    for stream in streams.values() {
+        stream.task.abort();
+    }
+    let _ = ws_sink.close().await;
+    result
+}
+
+async fn run_session(
+    ws_sink: &mut WsSink,
+    ws_source: &mut WsSource,
+    streams: &mut HashMap<u32, Stream>,
+    allowlist: &dyn AllowlistProvider,
+    config: &SessionConfig,
+) -> Result<CloseReason, BoxError> {
+    let (events_tx, mut events) = mpsc::channel(64);
+    let lifetime = expire(config.max_lifetime);
+    tokio::pin!(lifetime);
+    let notice = expire(config.rotation_notice_after());
+    tokio::pin!(notice);
+    let mut noticed = false;
+    loop {
+        // Restarted on every frame, so it only fires once the session is idle
+        let idle = expire(config.idle_timeout);
+        tokio::select! {
+            _ = idle => {
+                info!("closing idle multiplexed session");
+                let _ = ws_sink.send(going_away("idle timeout")).await;
+                return Ok(CloseReason::IdleTimeout);
+            }
+            _ = &mut lifetime => {
+                info!("closing multiplexed session at its maximum lifetime");
+                let _ = ws_sink.send(going_away("maximum lifetime reached")).await;
+                return Ok(CloseReason::MaxLifetime);
+            }
+            _ = &mut notice, if !noticed => {
+                noticed = true;
+                let remaining = config.rotation_notice.unwrap_or_default().as_secs() as u32;
+                ws_sink.send(encode_frame(FRAME_GOAWAY, 0, &remaining.to_be_bytes())).await?;
+            }
+            msg = ws_source.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) if msg.is_close() => return Ok(CloseReason::ClientClosed),
+                    Some(Ok(msg)) => msg,
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Ok(CloseReason::ClientClosed),
+                };
+                if !msg.is_binary() {
+                    continue;
+                }
+                let data = msg.into_data();
+                let Some((kind, id, payload)) = decode_frame(&data) else {
+                    continue;
+                };
+                let reset = match kind {
+                    FRAME_OPEN => open_stream(streams, id, payload, allowlist, config, &events_tx),
+                    FRAME_DATA => match streams.get_mut(&id) {
+                        Some(stream) => {
+                            stream.in_flight += payload.len();
+                            match &stream.to_target {
+                                _ if stream.in_flight > INITIAL_WINDOW => {
+                                    Some("flow control window exceeded".to_string())
+                                }
+                                Some(to_target) => {
+                                    let _ = to_target.send(payload.to_vec());
+                                    None
+                                }
+                                None => Some("data after close".to_string()),
+                            }
+                        }
+                        None => None,
+                    },
+                    FRAME_WINDOW => {
+                        if let (Some(stream), Ok(increment)) =
+                            (streams.get(&id), <[u8; 4]>::try_from(payload))
+                        {
+                            let increment = u32::from_be_bytes(increment) as usize;
+                            let room = Semaphore::MAX_PERMITS - stream.credit.available_permits();
+                            stream.credit.add_permits(increment.min(room));
+                        }
+                        None
+                    }
+                    FRAME_CLOSE => {
+                        if let Some(stream) = streams.get_mut(&id) {
+                            stream.to_target = None;
+                            if stream.target_closed {
+                                streams.remove(&id);
+                            }
+                        }
+                        None
+                    }
+                    FRAME_RESET => {
+                        if let Some(stream) = streams.remove(&id) {
+                            stream.task.abort();
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+                if let Some(reason) = reset {
+                    if let Some(stream) = streams.remove(&id) {
+                        stream.task.abort();
+                    }
+                    ws_sink.send(encode_frame(FRAME_RESET, id, reason.as_bytes())).await?;
+                }
+            }
+            Some(event) = events.recv() => match event {
+                Event::Data(id, bytes) => {
+                    if streams.contains_key(&id) {
+                        ws_sink.send(encode_frame(FRAME_DATA, id, &bytes)).await?;
+                    }
+                }
+                Event::Written(id, n) => {
+                    if let Some(stream) = streams.get_mut(&id) {
+                        stream.in_flight = stream.in_flight.saturating_sub(n);
+                        let increment = (n as u32).to_be_bytes();
+                        ws_sink.send(encode_frame(FRAME_WINDOW, id, &increment)).await?;
+                    }
+                }
+                Event::Eof(id) => {
+                    if let Some(stream) = streams.get_mut(&id) {
+                        stream.target_closed = true;
+                        let done = stream.to_target.is_none();
+                        ws_sink.send(encode_frame(FRAME_CLOSE, id, &[])).await?;
+                        if done {
+                            streams.remove(&id);
+                        }
+                    }
+                }
+                Event::Failed(id, reason) => {
+                    if streams.remove(&id).is_some() {
+                        ws_sink.send(encode_frame(FRAME_RESET, id, reason.as_bytes())).await?;
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Handle `OPEN`, returning the reason to reset the stream if it is refused.
+fn open_stream(
+    streams: &mut HashMap<u32, Stream>,
+    id: u32,
+    payload: &[u8],
+    allowlist: &dyn AllowlistProvider,
+    config: &SessionConfig,
+    events: &mpsc::Sender<Event>,
+) -> Option<String> {
+    if streams.contains_key(&id) {
+        return Some("stream id already in use".to_string());
+    }
+    if streams.len() >= config.max_streams {
+        return Some(format!("too many streams (max {})", config.max_streams));
+    }
+    let Ok(target) = std::str::from_utf8(payload) else {
+        return Some("invalid target".to_string());
+    };
+    if !is_target_allowed(target, allowlist) {
+        warn!(%target, "multiplexed target is not in allowlist");
+        return Some(format!("Target {} is not authorized", target));
+    }
+    let span = tracing::info_span!("stream", stream_id = id, %target);
+    if config.log_connections {
+        info!(parent: &span, "opening stream");
+    }
+
+    let (to_target, from_client) = mpsc::unbounded_channel();
+    let credit = Arc::new(Semaphore::new(INITIAL_WINDOW));
+    let task = tokio::spawn(
+        run_stream(
+            id,
+            target.to_string(),
+            from_client,
+            credit.clone(),
+            events.clone(),
+            config.clone(),
+        )
+        .instrument(span),
+    );
+    streams.insert(
+        id,
+        Stream {
+            to_target: Some(to_target),
+            credit,
+            in_flight: 0,
+            target_closed: false,
+            task,
+        },
+    );
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allowlist::Allowlist;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, connect_async};
+
+    #[test]
+    fn test_frame_round_trip() {
+        let Message::Binary(frame) = encode_frame(FRAME_WINDOW, 3, &65536u32.to_be_bytes()) else {
+            panic!("expected a binary message");
+        };
+        assert_eq!(
+            decode_frame(&frame),
+            Some((FRAME_WINDOW, 3, &[0u8, 1, 0, 0][..]))
+        );
+        assert_eq!(decode_frame(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_streams_share_one_websocket() {
+        // Echo target
+        let echo = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut tcp, _)) = echo.accept().await {
+                tokio::spawn(async move {
+                    let (mut r, mut w) = tcp.split();
+                    let _ = tokio::io::copy(&mut r, &mut w).await;
+                });
+            }
+        });
+
+        // Proxy accepting one multiplexed session
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let allowlist = Arc::new(Allowlist::parse(std::slice::from_ref(&echo_addr)).unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = proxy.accept().await.unwrap();
+            let ws = accept_async(Box::new(tcp) as ClientStream).await.unwrap();
+            let _ = serve(ws, allowlist, SessionConfig::default()).await;
+        });
+
+        let (mut ws, _) = connect_async(format!("ws://{proxy_addr}/?mux=1"))
+            .await
+            .unwrap();
+        ws.send(encode_frame(FRAME_OPEN, 1, echo_addr.as_bytes()))
+            .await
+            .unwrap();
+        ws.send(encode_frame(FRAME_OPEN, 2, b"10.0.0.1:22"))
+            .await
+            .unwrap();
+        ws.send(encode_frame(FRAME_DATA, 1, b"ping")).await.unwrap();
+
+        let mut echoed = false;
+        let mut refused = false;
+        while !(echoed && refused) {
+            let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .expect("proxy answered")
+                .unwrap()
+                .unwrap();
+            let data = msg.into_data();
+            match decode_frame(&data) {
+                Some((FRAME_DATA, 1, payload)) => {
+                    assert_eq!(payload, b"ping");
+                    echoed = true;
+                }
+                Some((FRAME_RESET, 2, reason)) => {
+                    assert!(String::from_utf8_lossy(reason).contains("not authorized"));
+                    refused = true;
+                }
+                // WINDOW updates for stream 1
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_closes_at_max_lifetime() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = proxy.accept().await.unwrap();
+            let ws = accept_async(Box::new(tcp) as ClientStream).await.unwrap();
+            let config = SessionConfig {
+                max_lifetime: Some(Duration::from_millis(100)),
+                ..SessionConfig::default()
+            };
+            let _ = serve(ws, Arc::new(Allowlist::default()), config).await;
+        });
+
+        let (mut ws, _) = connect_async(format!("ws://{proxy_addr}/?mux=1"))
+            .await
+            .unwrap();
+        // Keepalives don't extend the lifetime
+        ws.send(Message::Binary(Vec::new())).await.unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("proxy closed the session")
+            .unwrap()
+            .unwrap();
+        let Message::Close(Some(frame)) = msg else {
+            panic!("expected a close frame, got {msg:?}");
+        };
+        assert_eq!(frame.reason, "maximum lifetime reached");
+    }
+
+    #[tokio::test]
+    async fn test_goaway_before_max_lifetime() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (tcp, _) = proxy.accept().await.unwrap();
+            let ws = accept_async(Box::new(tcp) as ClientStream).await.unwrap();
+            let config = SessionConfig {
+                max_lifetime: Some(Duration::from_secs(2)),
+                rotation_notice: Some(Duration::from_secs(1)),
+                ..SessionConfig::default()
+            };
+            let _ = serve(ws, Arc::new(Allowlist::default()), config).await;
+        });
+
+        let (mut ws, _) = connect_async(format!("ws://{proxy_addr}/?mux=1"))
+            .await
+            .unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("proxy sent GOAWAY")
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg, encode_frame(FRAME_GOAWAY, 0, &1u32.to_be_bytes()));
+        let msg = ws.next().await.unwrap().unwrap();
+        assert!(msg.is_close());
+    }
+}
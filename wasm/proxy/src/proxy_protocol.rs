@@ -0,0 +1,119 @@
+//! PROXY protocol v2 headers towards targets: with `[upstream]
+//! proxy_protocol` on, every TCP connection to a target starts with a binary
+//! header naming the browser's address, so the service in the TEE sees the
+//! real client rather than the proxy.
+//!
+//! The source is the client's address (the one `X-Forwarded-For` names when
+//! it comes through a trusted proxy, with port 0 since the header has none)
+//! and the destination the proxy address the client connected to. The
+//! proxy's own connections, such as attestation probes, send a `LOCAL`
+//! header instead. Targets must expect the header: one that doesn't will
+//! see it as the start of the client's TLS stream and fail the handshake.
+
+use std::net::{IpAddr, SocketAddr};
+
+/// Starts every v2 header.
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Version 2, `LOCAL` command: the connection is the proxy's own.
+const LOCAL: u8 = 0x20;
+/// Version 2, `PROXY` command: the connection relays a client's.
+const PROXY: u8 = 0x21;
+
+const UNSPEC: u8 = 0x00;
+const TCP_OVER_IPV4: u8 = 0x11;
+const TCP_OVER_IPV6: u8 = 0x21;
+
+/// The client and proxy addresses of a relayed connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+}
+
+impl ProxyHeader {
+    pub fn new(source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            source,
+            destination,
+        }
+    }
+
+    /// The header's bytes. An IPv4 address paired with an IPv6 one is sent
+    /// IPv4-mapped, as both must have the same family.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut header = SIGNATURE.to_vec();
+        header.push(PROXY);
+        match (self.source.ip(), self.destination.ip()) {
+            (IpAddr::V4(source), IpAddr::V4(destination)) => {
+                header.push(TCP_OVER_IPV4);
+                header.extend_from_slice(&12u16.to_be_bytes());
+                header.extend_from_slice(&source.octets());
+                header.extend_from_slice(&destination.octets());
+            }
+            (source, destination) => {
+                header.push(TCP_OVER_IPV6);
+                header.extend_from_slice(&36u16.to_be_bytes());
+                header.extend_from_slice(&to_ipv6(source).octets());
+                header.extend_from_slice(&to_ipv6(destination).octets());
+            }
+        }
+        header.extend_from_slice(&self.source.port().to_be_bytes());
+        header.extend_from_slice(&self.destination.port().to_be_bytes());
+        header
+    }
+}
+
+/// A `LOCAL` header, for connections the proxy makes on its own behalf.
+pub fn local() -> Vec<u8> {
+    let mut header = SIGNATURE.to_vec();
+    header.extend_from_slice(&[LOCAL, UNSPEC, 0, 0]);
+    header
+}
+
+fn to_ipv6(ip: IpAddr) -> std::net::Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(addr: &str) -> SocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ipv4_header() {
+        let header = ProxyHeader::new(addr("203.0.113.7:51514"), addr("10.0.0.1:9000"));
+        let bytes = header.encode();
+        assert_eq!(&bytes[..12], &SIGNATURE);
+        assert_eq!(&bytes[12..16], &[0x21, 0x11, 0, 12]);
+        assert_eq!(&bytes[16..20], &[203, 0, 113, 7]);
+        assert_eq!(&bytes[20..24], &[10, 0, 0, 1]);
+        assert_eq!(&bytes[24..], &[0xc9, 0x3a, 0x23, 0x28]);
+    }
+
+    #[test]
+    fn test_mixed_families_are_mapped_to_ipv6() {
+        let header = ProxyHeader::new(addr("203.0.113.7:51514"), addr("[2001:db8::1]:9000"));
+        let bytes = header.encode();
+        assert_eq!(bytes.len(), 16 + 36);
+        assert_eq!(&bytes[12..16], &[0x21, 0x21, 0, 36]);
+        let source: [u8; 16] = bytes[16..32].try_into().unwrap();
+        assert_eq!(
+            std::net::Ipv6Addr::from(source),
+            "::ffff:203.0.113.7".parse::<std::net::Ipv6Addr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_local_header() {
+        let header = local();
+        assert_eq!(header.len(), 16);
+        assert_eq!(&header[12..], &[0x20, 0x00, 0, 0]);
+    }
+}
@@ -0,0 +1,329 @@
+//! Target resolution and connection.
+//!
+//! Hostname targets are resolved by the proxy (from the system resolver
+//! configuration) rather than by the OS at connect time, so the addresses
+//! can be checked before connecting. The `[dns]` section controls it:
+//!
+//! - `block_private`: refuse hostnames resolving to loopback, private,
+//!   link-local, CGNAT or unique-local addresses, so an allowlisted name whose
+//!   DNS changes can't point the proxy at internal services.
+//! - `blocked_networks`: further CIDR blocks to refuse.
+//! - `min_ttl_secs` / `max_ttl_secs`: bounds on how long answers are cached.
+//!   A minimum pins answers, so a zero-TTL record can't switch addresses
+//!   between connections.
+//!
+//! Blocked addresses are skipped; a target is refused only if all of its
+//! addresses are. Targets given as IP addresses are connected to directly:
+//! the allowlist already names them explicitly, as are `unix:/path` targets,
+//! Unix domain sockets on the proxy host.
+
+use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use crate::allowlist::{in_network, parse_network, split_target, unix_path};
+use crate::config::DnsConfig;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Private and special-purpose ranges refused by `block_private`.
+const PRIVATE_NETWORKS: &[(IpAddr, u8)] = &[
+    (IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(100, 64, 0, 0)), 10),
+    (IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)), 8),
+    (IpAddr::V4(Ipv4Addr::new(169, 254, 0, 0)), 16),
+    (IpAddr::V4(Ipv4Addr::new(172, 16, 0, 0)), 12),
+    (IpAddr::V4(Ipv4Addr::new(192, 168, 0, 0)), 16),
+    (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 128),
+    (IpAddr::V6(Ipv6Addr::LOCALHOST), 128),
+    (IpAddr::V6(Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0)), 7),
+    (IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0)), 10),
+];
+
+/// A connection to a target.
+pub enum TargetStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Resolves targets and connects to them.
+pub struct Resolver {
+    dns: TokioAsyncResolver,
+    blocked: Vec<(IpAddr, u8)>,
+}
+
+impl fmt::Debug for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resolver")
+            .field("blocked", &self.blocked)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Resolver {
+    /// Build the resolver `config` describes, reading the system resolver
+    /// configuration (or falling back to the library default).
+    pub fn from_config(config: &DnsConfig) -> Result<Self, BoxError> {
+        let (dns_config, mut opts) = hickory_resolver::system_conf::read_system_conf()
+            .unwrap_or_else(|_| (ResolverConfig::default(), ResolverOpts::default()));
+        if config.min_ttl_secs > 0 {
+            opts.positive_min_ttl = Some(Duration::from_secs(config.min_ttl_secs));
+        }
+        if config.max_ttl_secs > 0 {
+            opts.positive_max_ttl = Some(Duration::from_secs(config.max_ttl_secs));
+        }
+
+        let mut blocked = Vec::new();
+        if config.block_private {
+            blocked.extend_from_slice(PRIVATE_NETWORKS);
+        }
+        for network in &config.blocked_networks {
+            let network = parse_network(network)
+                .map_err(|reason| format!("invalid blocked network {network:?}: {reason}"))?;
+            blocked.push(network);
+        }
+
+        Ok(Self {
+            dns: TokioAsyncResolver::tokio(dns_config, opts),
+            blocked,
+        })
+    }
+
+    /// Connect to `target` (`host:port` or `unix:/path`), trying each
+    /// allowed address of a hostname in turn.
+    pub async fn connect(&self, target: &str) -> io::Result<TargetStream> {
+        if let Some(path) = unix_path(target) {
+            return connect_unix(path).await;
+        }
+        let mut last_error = None;
+        for addr in self.resolve(target).await? {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => return Ok(TargetStream::Tcp(stream)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.expect("at least one address was tried"))
+    }
+
+    /// The addresses of `target` (`host:port`) that may be connected to.
+    /// Never empty.
+    pub async fn resolve(&self, target: &str) -> io::Result<Vec<SocketAddr>> {
+        let (host, port) = split_target(target)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "expected host:port"))?;
+        let port: u16 = port
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid port"))?;
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+
+        let lookup = self
+            .dns
+            .lookup_ip(host)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+        let addrs = self.allowed(lookup.iter());
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{host} only resolves to blocked addresses"),
+            ));
+        }
+        Ok(addrs
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect())
+    }
+
+    /// The addresses of `addrs` not in a blocked network.
+    fn allowed(&self, addrs: impl Iterator<Item = IpAddr>) -> Vec<IpAddr> {
+        addrs
+            .filter(|ip| {
+                let ip = canonical(*ip);
+                !self
+                    .blocked
+                    .iter()
+                    .any(|(network, prefix)| in_network(ip, *network, *prefix))
+            })
+            .collect()
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &str) -> io::Result<TargetStream> {
+    Ok(TargetStream::Unix(UnixStream::connect(path).await?))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &str) -> io::Result<TargetStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix socket targets are only supported on Unix",
+    ))
+}
+
+impl AsyncRead for TargetStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for TargetStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            Self::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// IPv4-mapped IPv6 addresses as IPv4, so they match IPv4 blocks.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn resolver(block_private: bool, blocked_networks: &[&str]) -> Resolver {
+        Resolver::from_config(&DnsConfig {
+            block_private,
+            blocked_networks: blocked_networks.iter().map(|n| n.to_string()).collect(),
+            ..DnsConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_blocks_private_ranges() {
+        let resolver = resolver(true, &[]);
+        let addrs = [
+            "10.1.2.3",
+            "127.0.0.1",
+            "169.254.169.254",
+            "172.20.0.1",
+            "192.168.1.1",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+            "93.184.216.34",
+            "2606:2800:220:1::1",
+        ]
+        .map(|ip| ip.parse::<IpAddr>().unwrap());
+        let allowed = resolver.allowed(addrs.into_iter());
+        assert_eq!(
+            allowed,
+            ["93.184.216.34", "2606:2800:220:1::1"].map(|ip| ip.parse::<IpAddr>().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_blocked_networks() {
+        let resolver = resolver(false, &["203.0.113.0/24"]);
+        let addrs = ["203.0.113.9", "198.51.100.9", "10.0.0.1"].map(|ip| ip.parse().unwrap());
+        assert_eq!(
+            resolver.allowed(addrs.into_iter()),
+            ["198.51.100.9", "10.0.0.1"].map(|ip| ip.parse::<IpAddr>().unwrap())
+        );
+
+        let err = Resolver::from_config(&DnsConfig {
+            blocked_networks: vec!["not-a-network".to_string()],
+            ..DnsConfig::default()
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("not-a-network"));
+    }
+
+    #[tokio::test]
+    async fn test_ip_targets_skip_the_blocklist() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        resolver(true, &[]).connect(&target).await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_targets() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = std::env::temp_dir().join(format!("atlas-proxy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("target.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        // The blocklist only applies to resolved addresses
+        let target = format!("unix:{}", path.display());
+        let mut stream = resolver(true, &[]).connect(&target).await.unwrap();
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_hostname_resolving_to_blocked_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let err = resolver(true, &[])
+            .connect(&format!("localhost:{port}"))
+            .await
+            .err()
+            .expect("localhost must be blocked");
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound
+        ));
+    }
+}
@@ -0,0 +1,1163 @@
+//! The forwarder: accepts clients on its listeners and tunnels each one to
+//! its target.
+
+use futures_util::{SinkExt, StreamExt};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::field::Empty;
+use tracing::{debug, info, warn, Instrument, Span};
+use url::form_urlencoded;
+
+use crate::access_log::{AccessLog, CloseReason};
+use crate::allowlist::{Allowlist, AllowlistProvider};
+use crate::attest::{self, Attestor};
+use crate::auth::{self, Authenticator, Credential};
+use crate::clients::ClientFilter;
+use crate::config::Config;
+use crate::deflate::{self, Deflate};
+use crate::health;
+use crate::limits::{ClientGuard, ClientLimits, Refused, Throttled};
+use crate::listener;
+use crate::logging::{ByteCounter, ConnectionLog, Counted};
+use crate::mux::{self, SessionConfig};
+use crate::proxy_protocol::ProxyHeader;
+use crate::resolve::Resolver;
+use crate::sni::{self, Routes};
+use crate::tls::{ClientStream, TlsAcceptor};
+use crate::udp;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// `info!` for per-connection messages, when `[logging] connections` is on.
+macro_rules! conn_log {
+    ($enabled:expr, $($arg:tt)*) => {
+        if $enabled {
+            info!($($arg)*);
+        }
+    };
+}
+
+/// A configured proxy, ready to [`run`](Self::run).
+pub struct ProxyServer {
+    /// Addresses to bind without injected listeners, and their TLS.
+    listen: Vec<(String, Option<Arc<TlsAcceptor>>)>,
+    listeners: Vec<TcpListener>,
+    shared: Arc<Shared>,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+/// Builds a [`ProxyServer`]. Settings come from a [`Config`]; the allowlist
+/// and listeners can be supplied directly instead.
+pub struct ProxyServerBuilder {
+    config: Config,
+    allowlist: Option<Arc<dyn AllowlistProvider>>,
+    udp_allowlist: Option<Arc<dyn AllowlistProvider>>,
+    listeners: Vec<TcpListener>,
+}
+
+/// Stops a running [`ProxyServer`].
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<bool>>);
+
+/// Applies a new configuration to a [`ProxyServer`] without restarting it.
+#[derive(Clone)]
+pub struct ReloadHandle(Arc<Shared>);
+
+/// State shared by every listener.
+struct Shared {
+    settings: RwLock<Arc<Settings>>,
+    /// Providers given to the builder, which a reload keeps.
+    allowlist: Option<Arc<dyn AllowlistProvider>>,
+    udp_allowlist: Option<Arc<dyn AllowlistProvider>>,
+    resolver: Arc<Resolver>,
+    access_log: Option<Arc<AccessLog>>,
+    /// TLS of the `listen` addresses and injected listeners.
+    tls: Option<Arc<TlsAcceptor>>,
+    auth: Option<Authenticator>,
+    connections: Arc<Semaphore>,
+    clients: Arc<ClientLimits>,
+}
+
+/// The settings a reload replaces. Each connection takes the current ones
+/// when it starts and keeps them until it closes.
+struct Settings {
+    target: String,
+    allowlist: Arc<dyn AllowlistProvider>,
+    /// Targets of datagram tunnels.
+    udp_allowlist: Arc<dyn AllowlistProvider>,
+    max_datagram: usize,
+    /// Targets of tunnels that name none, by server name.
+    sni_routes: Arc<Routes>,
+    /// Client addresses that may connect.
+    clients: Arc<ClientFilter>,
+    /// Whether connections to targets start with a PROXY protocol header.
+    proxy_protocol: bool,
+    /// Whether clients may negotiate permessage-deflate.
+    compression: bool,
+    /// Bytes per second per connection and direction; 0 for no limit.
+    bandwidth: u64,
+    handshake_timeout: Duration,
+    session: SessionConfig,
+}
+
+impl ProxyServer {
+    /// Create a builder with the default configuration.
+    pub fn builder() -> ProxyServerBuilder {
+        ProxyServerBuilder::new()
+    }
+
+    /// A handle that stops this server once it runs.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.shutdown.clone())
+    }
+
+    /// A handle that reloads this server's settings while it runs.
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle(self.shared.clone())
+    }
+
+    /// Accept clients until shut down or a listener fails. Without injected
+    /// listeners, binds the configured `listen` addresses first.
+    pub async fn run(self) -> Result<(), BoxError> {
+        let tls = &self.shared.tls;
+        let mut listeners = Vec::new();
+        for listener in self.listeners {
+            // An injected socket (such as one from systemd) serves the TLS of
+            // the address it's configured as
+            let local = listener.local_addr()?;
+            let tls = self
+                .listen
+                .iter()
+                .find(|(addr, _)| addr.parse::<SocketAddr>().ok() == Some(local))
+                .map_or(tls, |(_, tls)| tls);
+            listeners.push((listener, tls.clone()));
+        }
+        if listeners.is_empty() {
+            let addresses: Vec<String> = self.listen.iter().map(|(addr, _)| addr.clone()).collect();
+            for (addr, tls) in &self.listen {
+                let listener = listener::bind(addr, &addresses)
+                    .await
+                    .map_err(|e| format!("failed to listen on {addr}: {e}"))?;
+                listeners.push((listener, tls.clone()));
+            }
+        }
+        let default_target = self.shared.settings().target.clone();
+        for (listener, tls) in &listeners {
+            let scheme = if tls.is_some() { "wss" } else { "ws" };
+            info!(
+                address = %format!("{scheme}://{}", listener.local_addr()?),
+                %default_target,
+                "atlas-proxy listening"
+            );
+        }
+        futures_util::future::try_join_all(listeners.into_iter().map(|(listener, tls)| {
+            accept_loop(
+                listener,
+                tls,
+                self.shared.clone(),
+                self.shutdown.subscribe(),
+            )
+        }))
+        .await?;
+        Ok(())
+    }
+}
+
+impl Default for ProxyServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProxyServerBuilder {
+    /// Create a new builder with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            config: Config::default(),
+            allowlist: None,
+            udp_allowlist: None,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Use these settings, such as from [`Config::load`].
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set the target of connections that don't name one.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.config.target = target.into();
+        self
+    }
+
+    /// Decide which targets are allowed with `provider` instead of the
+    /// configured allowlist.
+    pub fn allowlist(mut self, provider: impl AllowlistProvider + 'static) -> Self {
+        self.allowlist = Some(Arc::new(provider));
+        self
+    }
+
+    /// Decide which targets datagram tunnels may reach with `provider`
+    /// instead of the configured `[udp] allowlist`.
+    pub fn udp_allowlist(mut self, provider: impl AllowlistProvider + 'static) -> Self {
+        self.udp_allowlist = Some(Arc::new(provider));
+        self
+    }
+
+    /// Accept clients on `listener` instead of binding the configured
+    /// addresses, with the TLS of the configured address it's bound to, if
+    /// any. May be called more than once.
+    ///
+    /// The proxy doesn't take systemd's sockets itself; pass those from
+    /// [`systemd_listeners`](crate::systemd_listeners) here.
+    pub fn listener(mut self, listener: TcpListener) -> Self {
+        self.listeners.push(listener);
+        self
+    }
+
+    /// Build the server. Must be called inside the tokio runtime (see
+    /// [`TlsAcceptor::from_config`]).
+    pub fn build(self) -> Result<ProxyServer, BoxError> {
+        let config = self.config;
+        config.validate()?;
+        let resolver = Arc::new(Resolver::from_config(&config.dns)?);
+        let settings = Settings::from_config(
+            &config,
+            self.allowlist.clone(),
+            self.udp_allowlist.clone(),
+            resolver.clone(),
+        )?;
+
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let tls = TlsAcceptor::from_config(&config.tls)?.map(Arc::new);
+        let mut listen: Vec<_> = config
+            .listen
+            .iter()
+            .map(|addr| (addr.clone(), tls.clone()))
+            .collect();
+        for listener in &config.listeners {
+            let own_tls = match &listener.tls {
+                Some(own) => TlsAcceptor::from_config(own)?.map(Arc::new),
+                None => tls.clone(),
+            };
+            listen.push((listener.address.clone(), own_tls));
+        }
+        let auth = Authenticator::from_config(&config.auth)?;
+        if auth.is_none() {
+            warn!("client authentication is off; any client can reach allowlisted targets");
+        }
+
+        let shared = Shared {
+            settings: RwLock::new(Arc::new(settings)),
+            allowlist: self.allowlist,
+            udp_allowlist: self.udp_allowlist,
+            resolver,
+            access_log: AccessLog::from_config(&config.access_log)?.map(Arc::new),
+            tls,
+            auth,
+            connections: Arc::new(Semaphore::new(config.limits.max_connections)),
+            clients: Arc::new(ClientLimits::new(
+                config.limits.max_connections_per_ip,
+                config.limits.connections_per_minute_per_ip,
+            )),
+        };
+        Ok(ProxyServer {
+            listen,
+            listeners: self.listeners,
+            shared: Arc::new(shared),
+            shutdown: Arc::new(watch::channel(false).0),
+        })
+    }
+}
+
+impl Shared {
+    /// The settings for a connection starting now.
+    fn settings(&self) -> Arc<Settings> {
+        self.settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Settings {
+    /// Read the reloadable settings from `config`, using `allowlist` and
+    /// `udp_allowlist` instead of the configured lists when given.
+    fn from_config(
+        config: &Config,
+        allowlist: Option<Arc<dyn AllowlistProvider>>,
+        udp_allowlist: Option<Arc<dyn AllowlistProvider>>,
+        resolver: Arc<Resolver>,
+    ) -> Result<Self, BoxError> {
+        let allowlist = match allowlist {
+            Some(provider) => provider,
+            None => {
+                let allowlist = Allowlist::parse(&config.allowlist)?;
+                if allowlist.is_empty() {
+                    warn!("the allowlist is empty or not set; all targets will be rejected");
+                } else {
+                    info!(entries = allowlist.len(), "allowlist loaded");
+                }
+                Arc::new(allowlist)
+            }
+        };
+        let udp_allowlist = match udp_allowlist {
+            Some(provider) => provider,
+            None => Arc::new(Allowlist::parse(&config.udp.allowlist)?),
+        };
+        if !is_target_allowed(&config.target, allowlist.as_ref()) {
+            return Err(format!("Default target {} is not authorized", config.target).into());
+        }
+        let sni_routes = Routes::from_config(&config.sni)?;
+        if !sni_routes.is_empty() {
+            info!(routes = sni_routes.len(), "SNI routing enabled");
+        }
+        let proxy_protocol = config.upstream.proxy_protocol;
+        let attestor = Attestor::from_config(&config.attestation)?
+            .map(|attestor| Arc::new(attestor.with_proxy_protocol(proxy_protocol)));
+        Ok(Self {
+            target: config.target.clone(),
+            allowlist,
+            udp_allowlist,
+            max_datagram: config.udp.max_datagram,
+            sni_routes: Arc::new(sni_routes),
+            clients: Arc::new(ClientFilter::from_config(&config.clients)?),
+            proxy_protocol,
+            compression: config.websocket.compression,
+            bandwidth: config.limits.bandwidth_per_connection,
+            handshake_timeout: config.timeouts.handshake(),
+            session: SessionConfig {
+                max_streams: config.limits.max_streams_per_session,
+                connect_timeout: config.timeouts.connect(),
+                resolver,
+                idle_timeout: config.timeouts.idle(),
+                max_lifetime: config.timeouts.max_tunnel(),
+                rotation_notice: config.timeouts.rotation_notice(),
+                log_connections: config.logging.connections,
+                attestation: attestor,
+                proxy_header: None,
+            },
+        })
+    }
+}
+
+impl ShutdownHandle {
+    /// Stop accepting clients, so [`ProxyServer::run`] returns. Tunnels
+    /// already open run to completion.
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+impl ReloadHandle {
+    /// Switch to the allowlists, SNI routes, client filters, PROXY protocol
+    /// and compression settings, default target, attestation policy,
+    /// per-client limits, timeouts and `[logging] connections` of `config`. Connections accepted from now on use them; tunnels already
+    /// open keep the settings they started with. The listen addresses, TLS,
+    /// authentication, DNS, access log and `max_connections` only change on a
+    /// restart. An invalid `config` is rejected and the current settings kept.
+    pub fn reload(&self, config: &Config) -> Result<(), BoxError> {
+        let shared = &self.0;
+        config.validate()?;
+        let settings = Settings::from_config(
+            config,
+            shared.allowlist.clone(),
+            shared.udp_allowlist.clone(),
+            shared.resolver.clone(),
+        )?;
+        shared.clients.set_limits(
+            config.limits.max_connections_per_ip,
+            config.limits.connections_per_minute_per_ip,
+        );
+        *shared.settings.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(settings);
+        info!("configuration reloaded");
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ReloadHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReloadHandle").finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn is_target_allowed(target: &str, allowlist: &dyn AllowlistProvider) -> bool {
+    allowlist.allows(target)
+}
+
+/// Tunnel `ws_stream` to `target`, sending it `initial` first: bytes the
+/// client sent before the target was known.
+async fn handle_ws(
+    ws_stream: tokio_tungstenite::WebSocketStream<ClientStream>,
+    target: String,
+    initial: Vec<u8>,
+    allowlist: Arc<dyn AllowlistProvider>,
+    session: &SessionConfig,
+) -> Result<CloseReason, BoxError> {
+    if !is_target_allowed(&target, allowlist.as_ref()) {
+        warn!(%target, "target is not in allowlist");
+        return Err(format!("Target {} is not authorized", target).into());
+    }
+    let mut ws = ws_stream;
+    if let Some(attestor) = &session.attestation {
+        if let Err(e) = attestor.check(&target, &session.resolver).await {
+            warn!(%target, error = %e, "connection rejected: target failed attestation");
+            let _ = ws.send(going_away("target failed attestation")).await;
+            return Ok(CloseReason::AttestationFailed);
+        }
+    }
+    conn_log!(session.log_connections, %target, "connecting to target");
+    let connect = tokio::time::timeout(session.connect_timeout, session.resolver.connect(&target));
+    let mut tcp = match connect.await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            warn!(%target, error = %e, "failed to connect to target");
+            return Ok(CloseReason::TargetUnreachable);
+        }
+        Err(_) => {
+            warn!(%target, "timed out connecting to target");
+            return Ok(CloseReason::TargetUnreachable);
+        }
+    };
+    conn_log!(session.log_connections, %target, "connected to target");
+    if let Some(header) = &session.proxy_header {
+        tcp.write_all(&header.encode()).await?;
+    }
+
+    let (mut ws_sink, mut ws_source) = ws.split();
+    let (mut tcp_reader, mut tcp_writer) = tokio::io::split(tcp);
+    if !initial.is_empty() {
+        tcp_writer.write_all(&initial).await?;
+    }
+    let mut buf = [0u8; 8192];
+    let lifetime = expire(session.max_lifetime);
+    tokio::pin!(lifetime);
+    let notice = expire(session.rotation_notice_after());
+    tokio::pin!(notice);
+    let mut noticed = false;
+    let revoked = attest::revoked(session.attestation.clone(), &target, &session.resolver);
+    tokio::pin!(revoked);
+    let reason = loop {
+        // Restarted on every message, so it only fires once the tunnel is idle
+        let idle = expire(session.idle_timeout);
+        tokio::select! {
+            _ = idle => {
+                conn_log!(session.log_connections, %target, "closing idle tunnel");
+                let _ = ws_sink.send(going_away("idle timeout")).await;
+                let _ = tcp_writer.shutdown().await;
+                break CloseReason::IdleTimeout;
+            }
+            _ = &mut lifetime => {
+                conn_log!(session.log_connections, %target, "closing tunnel at its maximum lifetime");
+                let _ = ws_sink.send(going_away("maximum lifetime reached")).await;
+                let _ = tcp_writer.shutdown().await;
+                break CloseReason::MaxLifetime;
+            }
+            _ = &mut notice, if !noticed => {
+                noticed = true;
+                let remaining = session.rotation_notice.unwrap_or_default();
+                ws_sink.send(rotation_notice(remaining)).await?;
+            }
+            _ = &mut revoked => {
+                conn_log!(session.log_connections, %target, "closing tunnel to a target that failed attestation");
+                let _ = ws_sink.send(going_away("target failed attestation")).await;
+                let _ = tcp_writer.shutdown().await;
+                break CloseReason::AttestationFailed;
+            }
+            msg = ws_source.next() => {
+                match msg {
+                    Some(Ok(msg)) => {
+                        if msg.is_binary() || msg.is_text() {
+                            tcp_writer.write_all(&msg.into_data()).await?;
+                        } else if msg.is_close() {
+                            let _ = ws_sink.send(Message::Close(None)).await;
+                            break CloseReason::ClientClosed;
+                        }
+                    }
+                    Some(Err(e)) => return Err(Box::new(e)),
+                    None => break CloseReason::ClientClosed,
+                }
+            }
+            res = tcp_reader.read(&mut buf) => {
+                match res {
+                    Ok(0) => {
+                        let _ = ws_sink.send(Message::Close(None)).await;
+                        break CloseReason::TargetClosed;
+                    }
+                    Ok(n) => {
+                        ws_sink.send(Message::Binary(buf[..n].to_vec())).await?;
+                    }
+                    Err(e) => return Err(Box::new(e)),
+                }
+            }
+        }
+    };
+    let _ = ws_sink.close().await;
+    Ok(reason)
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    tls: Option<Arc<TlsAcceptor>>,
+    shared: Arc<Shared>,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<(), BoxError> {
+    loop {
+        let (tcp, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = shutdown.wait_for(|stop| *stop) => return Ok(()),
+        };
+        // Trusted proxies are checked and limited by the client they forward
+        // instead, once the WebSocket handshake names it
+        let clients = shared.settings().clients.clone();
+        let trusted = clients.is_trusted_proxy(peer.ip());
+        if !trusted && !clients.allows(peer.ip()) {
+            warn!(%peer, "connection dropped: client address not allowed");
+            continue;
+        }
+        let Ok(permit) = shared.connections.clone().try_acquire_owned() else {
+            warn!(%peer, "connection dropped: connection limit reached");
+            continue;
+        };
+        let client = if trusted {
+            None
+        } else {
+            match shared.clients.admit(peer.ip()) {
+                Ok(client) => Some(client),
+                Err(refused) => {
+                    warn!(%peer, "connection dropped: {refused}");
+                    continue;
+                }
+            }
+        };
+        let shared = shared.clone();
+        let span = tracing::info_span!(
+            "connection",
+            %peer,
+            client = Empty,
+            target = Empty,
+            mux = Empty,
+            udp = Empty
+        );
+        let permits = (permit, client);
+        let tls = tls.clone();
+        tokio::spawn(serve_client(tcp, peer, tls, shared, permits).instrument(span));
+    }
+}
+
+/// Serve one client connection, from the TLS handshake to the end of its
+/// tunnel or session.
+// tungstenite's handshake callback fails with a whole HTTP response
+#[allow(clippy::result_large_err)]
+async fn serve_client(
+    tcp: TcpStream,
+    peer: SocketAddr,
+    tls: Option<Arc<TlsAcceptor>>,
+    shared: Arc<Shared>,
+    _permits: (OwnedSemaphorePermit, Option<ClientGuard>),
+) {
+    let settings = shared.settings();
+    let handshake_timeout = settings.handshake_timeout;
+    let local = tcp
+        .local_addr()
+        .unwrap_or_else(|_| SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)));
+    let counter = ByteCounter::default();
+    let log_connections = settings.session.log_connections;
+    let access_log = shared.access_log.clone();
+    let mut log = ConnectionLog::new(peer, counter.clone(), log_connections, access_log);
+    log.close(CloseReason::HandshakeFailed);
+    let mut stream: ClientStream = match &tls {
+        None => Box::new(tcp),
+        Some(tls) => match tokio::time::timeout(handshake_timeout, tls.accept(tcp)).await {
+            Ok(Ok(Some(stream))) => stream,
+            // An ACME challenge, not a client
+            Ok(Ok(None)) => {
+                log.quiet();
+                return;
+            }
+            Ok(Err(e)) => {
+                warn!(error = %e, "TLS handshake failed");
+                return;
+            }
+            Err(_) => {
+                warn!("TLS handshake timed out");
+                return;
+            }
+        },
+    };
+    stream = Box::new(Counted::new(stream, counter));
+    if settings.bandwidth > 0 {
+        stream = Box::new(Throttled::new(stream, settings.bandwidth));
+    }
+    let intercept = health::intercept(stream);
+    let stream = match tokio::time::timeout(handshake_timeout, intercept).await {
+        Ok(Ok((stream, None))) => stream,
+        Ok(Ok((stream, Some(probe)))) => {
+            log.quiet();
+            let session = &settings.session;
+            let target = &settings.target;
+            if let Err(e) = health::respond(stream, probe, target, session).await {
+                debug!(error = %e, "failed to answer {probe:?} probe");
+            }
+            return;
+        }
+        Ok(Err(e)) => {
+            warn!(error = %e, "failed to read the request");
+            return;
+        }
+        Err(_) => {
+            warn!("request timed out");
+            return;
+        }
+    };
+    let compressed = Arc::new(AtomicBool::new(false));
+    let capture_compressed = compressed.clone();
+    let stream: ClientStream = if settings.compression {
+        Box::new(Deflate::new(stream, compressed))
+    } else {
+        stream
+    };
+    let compression = settings.compression;
+    let default_target = settings.target.clone();
+    let requested = Arc::new(Mutex::new(None));
+    let capture = requested.clone();
+    let multiplexed = Arc::new(AtomicBool::new(false));
+    let capture_mux = multiplexed.clone();
+    let datagram = Arc::new(AtomicBool::new(false));
+    let capture_udp = datagram.clone();
+    let rejected = Arc::new(Mutex::new(None));
+    let capture_rejected = rejected.clone();
+    let notice = Arc::new(AtomicBool::new(false));
+    let capture_notice = notice.clone();
+    let forwarded = Arc::new(Mutex::new(None));
+    let capture_forwarded = forwarded.clone();
+    let admitted = Arc::new(Mutex::new(None));
+    let capture_admitted = admitted.clone();
+    let auth_shared = shared.clone();
+    let clients = settings.clients.clone();
+    let handshake = accept_hdr_async(stream, move |req: &Request, mut response: Response| {
+        let reject = |reason| {
+            if let Ok(mut guard) = capture_rejected.lock() {
+                *guard = Some(reason);
+            }
+        };
+        if clients.is_trusted_proxy(peer.ip()) {
+            let forwarded_for = req.headers().get_all("x-forwarded-for");
+            let forwarded_for = forwarded_for.iter().filter_map(|v| v.to_str().ok());
+            let client = clients.forwarded_client(peer.ip(), forwarded_for);
+            Span::current().record("client", tracing::field::display(client));
+            if let Ok(mut guard) = capture_forwarded.lock() {
+                *guard = Some(client);
+            }
+            if !clients.allows(client) {
+                warn!(%client, "connection rejected: client address not allowed");
+                reject(CloseReason::ClientNotAllowed);
+                return Err(forbidden());
+            }
+            match auth_shared.clients.admit(client) {
+                Ok(guard) => {
+                    if let Ok(mut slot) = capture_admitted.lock() {
+                        *slot = Some(guard);
+                    }
+                }
+                Err(refused) => {
+                    warn!(%client, "connection rejected: {refused}");
+                    reject(CloseReason::ClientLimited);
+                    return Err(too_many_requests(refused));
+                }
+            }
+        }
+        if let Some(auth) = &auth_shared.auth {
+            let credential = auth::extract_credential(req);
+            if !credential.as_ref().is_some_and(|c| auth.check(c.token())) {
+                warn!("connection rejected: missing or invalid token");
+                reject(CloseReason::Unauthorized);
+                return Err(unauthorized());
+            }
+            // Browsers fail the handshake unless an offered protocol is echoed
+            if let Some(Credential::Protocol(entry)) = credential {
+                if let Ok(value) = HeaderValue::from_str(&entry) {
+                    response
+                        .headers_mut()
+                        .insert(header::SEC_WEBSOCKET_PROTOCOL, value);
+                }
+            }
+        }
+        if compression {
+            let offers = req.headers().get_all(header::SEC_WEBSOCKET_EXTENSIONS);
+            let offers = offers.iter().filter_map(|v| v.to_str().ok());
+            if let Some(accepted) = deflate::negotiate(offers) {
+                response.headers_mut().insert(
+                    header::SEC_WEBSOCKET_EXTENSIONS,
+                    HeaderValue::from_static(accepted),
+                );
+                capture_compressed.store(true, Ordering::Relaxed);
+            }
+        }
+        if is_multiplexed(req) {
+            echo_protocol(req, &mut response, mux::PROTOCOL);
+            conn_log!(log_connections, "multiplexed session opened");
+            capture_mux.store(true, Ordering::Relaxed);
+            return Ok(response);
+        }
+        if is_datagram(req) {
+            echo_protocol(req, &mut response, udp::PROTOCOL);
+            capture_udp.store(true, Ordering::Relaxed);
+        }
+        capture_notice.store(wants_notice(req), Ordering::Relaxed);
+        if let Some(tgt) = extract_target(req) {
+            conn_log!(log_connections, target = %tgt, "target requested");
+            if let Ok(mut guard) = capture.lock() {
+                *guard = Some(tgt);
+            }
+        } else {
+            conn_log!(log_connections, "using default target");
+        }
+        Ok(response)
+    });
+    let mut ws_stream = match tokio::time::timeout(handshake_timeout, handshake).await {
+        Ok(Ok(ws)) => ws,
+        Ok(Err(e)) => {
+            match rejected.lock().ok().and_then(|mut guard| guard.take()) {
+                Some(reason) => log.close(reason),
+                None => warn!(error = %e, "WebSocket handshake failed"),
+            }
+            return;
+        }
+        Err(_) => {
+            warn!("WebSocket handshake timed out");
+            return;
+        }
+    };
+
+    // A forwarded client counts against its own limits from here on
+    let _forwarded_guard = admitted.lock().ok().and_then(|mut slot| slot.take());
+
+    // A forwarded client's port is unknown
+    let client = match forwarded.lock().ok().and_then(|guard| *guard) {
+        Some(ip) => SocketAddr::new(ip, 0),
+        None => peer,
+    };
+    let mut session = settings.session.clone();
+    if settings.proxy_protocol {
+        session.proxy_header = Some(ProxyHeader::new(client, local));
+    }
+
+    // Each stream of a multiplexed session is checked against the allowlist
+    if multiplexed.load(Ordering::Relaxed) {
+        Span::current().record("mux", true);
+        log.tunnel("mux", None);
+        let allowlist = settings.allowlist.clone();
+        match mux::serve(ws_stream, allowlist, session).await {
+            Ok(reason) => log.close(reason),
+            Err(e) => {
+                log.close(CloseReason::Error);
+                warn!(error = %e, "multiplexed session failed");
+            }
+        }
+        return;
+    }
+
+    let requested = requested.lock().ok().and_then(|guard| guard.clone());
+    let datagram = datagram.load(Ordering::Relaxed);
+    let routed = requested.is_none() && !datagram && !settings.sni_routes.is_empty();
+    let mut hello = Vec::new();
+    let final_target = if routed {
+        match route_by_sni(&mut ws_stream, &settings.sni_routes, handshake_timeout).await {
+            Ok((target, read)) => {
+                hello = read;
+                target
+            }
+            Err(reason) => {
+                log.close(reason);
+                let _ = ws_stream.close(None).await;
+                return;
+            }
+        }
+    } else {
+        requested.unwrap_or(default_target)
+    };
+    Span::current().record("mux", false);
+    Span::current().record("udp", datagram);
+    Span::current().record("target", final_target.as_str());
+    log.tunnel(if datagram { "udp" } else { "tcp" }, Some(&final_target));
+
+    if datagram {
+        if !is_target_allowed(&final_target, settings.udp_allowlist.as_ref()) {
+            warn!("connection rejected: UDP target is not authorized");
+            log.close(CloseReason::TargetNotAllowed);
+            let _ = ws_stream.close(None).await;
+            return;
+        }
+        let max_datagram = settings.max_datagram;
+        match udp::serve(ws_stream, &final_target, max_datagram, &session).await {
+            Ok(reason) => log.close(reason),
+            Err(e) => {
+                log.close(CloseReason::Error);
+                warn!(error = %e, "datagram tunnel failed");
+            }
+        }
+        return;
+    }
+
+    // The routes are the allowlist of tunnels routed by SNI
+    let allowlist: Arc<dyn AllowlistProvider> = if routed {
+        settings.sni_routes.clone()
+    } else {
+        settings.allowlist.clone()
+    };
+    if !is_target_allowed(&final_target, allowlist.as_ref()) {
+        warn!("connection rejected: target is not authorized");
+        log.close(CloseReason::TargetNotAllowed);
+        let _ = ws_stream.close(None).await;
+        return;
+    }
+
+    // Text messages would corrupt the stream of clients that don't expect them
+    if !notice.load(Ordering::Relaxed) {
+        session.rotation_notice = None;
+    }
+    match handle_ws(ws_stream, final_target.clone(), hello, allowlist, &session).await {
+        Ok(reason) => log.close(reason),
+        Err(e) => {
+            log.close(CloseReason::Error);
+            warn!(error = %e, "tunnel failed");
+        }
+    }
+}
+
+/// Route a tunnel that names no target by the server name in its
+/// ClientHello, returning the target and the bytes read.
+async fn route_by_sni(
+    ws: &mut tokio_tungstenite::WebSocketStream<ClientStream>,
+    routes: &Routes,
+    timeout: Duration,
+) -> Result<(String, Vec<u8>), CloseReason> {
+    let read = tokio::time::timeout(timeout, sni::read_client_hello(ws));
+    let (hello, server_name) = match read.await {
+        Ok(Ok(read)) => read,
+        Ok(Err(e)) => {
+            warn!(error = %e, "failed to read the ClientHello");
+            return Err(CloseReason::HandshakeFailed);
+        }
+        Err(_) => {
+            warn!("timed out waiting for the ClientHello");
+            return Err(CloseReason::HandshakeFailed);
+        }
+    };
+    let Some(server_name) = server_name else {
+        warn!("connection rejected: the ClientHello names no server");
+        return Err(CloseReason::TargetNotAllowed);
+    };
+    match routes.route(&server_name) {
+        Some(target) => {
+            debug!(%server_name, %target, "routed by SNI");
+            Ok((target.to_string(), hello))
+        }
+        None => {
+            warn!(%server_name, "connection rejected: no SNI route for the server name");
+            Err(CloseReason::TargetNotAllowed)
+        }
+    }
+}
+
+/// Complete after `limit`, or never without one.
+pub(crate) async fn expire(limit: Option<Duration>) {
+    match limit {
+        Some(limit) => tokio::time::sleep(limit).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// A text message telling the client its tunnel closes in `remaining`, so it
+/// can open another; only sent to clients that ask with `?notice=1`.
+fn rotation_notice(remaining: Duration) -> Message {
+    let notice = serde_json::json!({
+        "type": "rotate",
+        "close_in_secs": remaining.as_secs(),
+    });
+    Message::Text(notice.to_string())
+}
+
+/// A `Close` telling the browser why the proxy ended the tunnel.
+pub(crate) fn going_away(reason: &'static str) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Away,
+        reason: reason.into(),
+    }))
+}
+
+fn extract_target(req: &Request) -> Option<String> {
+    req.uri().query().and_then(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "target")
+            .map(|(_, value)| value.into_owned())
+    })
+}
+
+fn unauthorized() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("missing or invalid token".to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
+
+fn forbidden() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("client address not allowed".to_string()));
+    *response.status_mut() = StatusCode::FORBIDDEN;
+    response
+}
+
+fn too_many_requests(refused: Refused) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(refused.to_string()));
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    response
+}
+
+fn is_multiplexed(req: &Request) -> bool {
+    offers_protocol(req, mux::PROTOCOL)
+        || req.uri().query().is_some_and(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .any(|(key, value)| key == "mux" && value == "1")
+        })
+}
+
+/// Select `protocol` if the client offered it.
+fn echo_protocol(req: &Request, response: &mut Response, protocol: &'static str) {
+    if offers_protocol(req, protocol) {
+        response.headers_mut().insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(protocol),
+        );
+    }
+}
+
+fn is_datagram(req: &Request) -> bool {
+    offers_protocol(req, udp::PROTOCOL)
+        || req.uri().query().is_some_and(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .any(|(key, value)| key == "udp" && value == "1")
+        })
+}
+
+/// Whether a plain tunnel's client asks for rotation notices.
+fn wants_notice(req: &Request) -> bool {
+    req.uri().query().is_some_and(|query| {
+        form_urlencoded::parse(query.as_bytes()).any(|(key, value)| key == "notice" && value == "1")
+    })
+}
+
+fn offers_protocol(req: &Request, protocol: &str) -> bool {
+    req.headers()
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|entry| entry.trim() == protocol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Uri;
+
+    #[test]
+    fn test_is_target_allowed_in_list() {
+        let allowlist = Allowlist::parse(&["host1:443", "host2:8443"]).unwrap();
+
+        assert!(is_target_allowed("host1:443", &allowlist));
+        assert!(is_target_allowed("host2:8443", &allowlist));
+    }
+
+    #[test]
+    fn test_is_target_allowed_not_in_list() {
+        let allowlist = Allowlist::parse(&["host1:443"]).unwrap();
+
+        assert!(!is_target_allowed("host2:443", &allowlist));
+        assert!(!is_target_allowed("host1:8443", &allowlist));
+        assert!(!is_target_allowed("malicious.com:443", &allowlist));
+    }
+
+    #[test]
+    fn test_is_target_allowed_empty_list() {
+        let allowlist = Allowlist::default();
+        assert!(!is_target_allowed("any:443", &allowlist));
+    }
+
+    #[tokio::test]
+    async fn test_build_checks_default_target() {
+        let err = ProxyServer::builder()
+            .target("host2:443")
+            .allowlist(|target: &str| target == "host1:443")
+            .build()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("host2:443"));
+
+        let config = Config {
+            target: "host1:443".to_string(),
+            allowlist: vec!["host1:443".to_string()],
+            ..Config::default()
+        };
+        ProxyServer::builder().config(config).build().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let mut config = Config {
+            target: "host1:443".to_string(),
+            allowlist: vec!["host1:443".to_string()],
+            ..Config::default()
+        };
+        let server = ProxyServer::builder()
+            .config(config.clone())
+            .build()
+            .unwrap();
+        let reload = server.reload_handle();
+        let before = server.shared.settings();
+
+        config.allowlist.push("host2:443".to_string());
+        reload.reload(&config).unwrap();
+        let after = server.shared.settings();
+        assert!(after.allowlist.allows("host2:443"));
+        // Connections already open keep their snapshot
+        assert!(!before.allowlist.allows("host2:443"));
+
+        // An invalid configuration leaves the settings as they were
+        config.target = "host3:443".to_string();
+        assert!(reload.reload(&config).is_err());
+        assert_eq!(server.shared.settings().target, "host1:443");
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_injected_allowlist() {
+        let server = ProxyServer::builder()
+            .target("host1:443")
+            .allowlist(|target: &str| target.starts_with("host1:"))
+            .build()
+            .unwrap();
+        let config = Config {
+            target: "host1:8443".to_string(),
+            ..Config::default()
+        };
+        server.reload_handle().reload(&config).unwrap();
+        let settings = server.shared.settings();
+        assert_eq!(settings.target, "host1:8443");
+        assert!(settings.allowlist.allows("host1:443"));
+    }
+
+    #[test]
+    fn test_rotation_notice() {
+        let req = Request::builder().uri("/?notice=1").body(()).unwrap();
+        assert!(wants_notice(&req));
+        let req = Request::builder().uri("/?notice=0").body(()).unwrap();
+        assert!(!wants_notice(&req));
+
+        let Message::Text(text) = rotation_notice(Duration::from_secs(60)) else {
+            panic!("expected a text message");
+        };
+        let notice: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(notice["type"], "rotate");
+        assert_eq!(notice["close_in_secs"], 60);
+    }
+
+    #[test]
+    fn test_extract_target_with_target_param() {
+        let uri: Uri = "/tunnel?target=host1:443".parse().unwrap();
+        let req = Request::builder().uri(uri).body(()).unwrap();
+        let result = extract_target(&req);
+        assert_eq!(result, Some("host1:443".to_string()));
+    }
+
+    #[test]
+    fn test_extract_target_with_multiple_params() {
+        let uri: Uri = "/tunnel?foo=bar&target=host2:8443&baz=qux".parse().unwrap();
+        let req = Request::builder().uri(uri).body(()).unwrap();
+        let result = extract_target(&req);
+        assert_eq!(result, Some("host2:8443".to_string()));
+    }
+
+    #[test]
+    fn test_extract_target_no_query() {
+        let uri: Uri = "/tunnel".parse().unwrap();
+        let req = Request::builder().uri(uri).body(()).unwrap();
+        let result = extract_target(&req);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_extract_target_no_target_param() {
+        let uri: Uri = "/tunnel?foo=bar&baz=qux".parse().unwrap();
+        let req = Request::builder().uri(uri).body(()).unwrap();
+        let result = extract_target(&req);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_is_multiplexed() {
+        let req = Request::builder().uri("/?mux=1").body(()).unwrap();
+        assert!(is_multiplexed(&req));
+        let req = Request::builder()
+            .uri("/?target=host1:443&mux=0")
+            .body(())
+            .unwrap();
+        assert!(!is_multiplexed(&req));
+        let req = Request::builder().uri("/tunnel").body(()).unwrap();
+        assert!(!is_multiplexed(&req));
+    }
+
+    #[test]
+    fn test_mux_protocol() {
+        let req = Request::builder()
+            .uri("/")
+            .header("Sec-WebSocket-Protocol", "bearer.abc, atlas-mux.v1")
+            .body(())
+            .unwrap();
+        assert!(is_multiplexed(&req));
+        let req = Request::builder()
+            .uri("/")
+            .header("Sec-WebSocket-Protocol", "atlas-mux.v2")
+            .body(())
+            .unwrap();
+        assert!(!is_multiplexed(&req));
+    }
+
+    #[test]
+    fn test_is_datagram() {
+        let req = Request::builder()
+            .uri("/?target=quic.example.com:443&udp=1")
+            .body(())
+            .unwrap();
+        assert!(is_datagram(&req));
+        let req = Request::builder()
+            .uri("/?target=quic.example.com:443")
+            .header("Sec-WebSocket-Protocol", "atlas-udp.v1")
+            .body(())
+            .unwrap();
+        assert!(is_datagram(&req));
+        assert!(!is_multiplexed(&req));
+        let req = Request::builder().uri("/?udp=0").body(()).unwrap();
+        assert!(!is_datagram(&req));
+    }
+
+    #[test]
+    fn test_extract_target_url_encoded() {
+        let uri: Uri = "/tunnel?target=host%3A443".parse().unwrap();
+        let req = Request::builder().uri(uri).body(()).unwrap();
+        let result = extract_target(&req);
+        // URL decoding should handle %3A -> :
+        assert_eq!(result, Some("host:443".to_string()));
+    }
+}
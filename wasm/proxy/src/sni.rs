@@ -0,0 +1,270 @@
+//! SNI routing: with `[sni] routes` set, a client that names no `?target=`
+//! is routed by the server name in the TLS ClientHello it sends first, so it
+//! only needs the proxy's address.
+//!
+//! The proxy reads the tunnel's first messages until it holds the whole
+//! ClientHello, looks the name up (case-insensitively) and forwards the
+//! buffered bytes to the target it maps to. The routes are the allowlist of
+//! such tunnels: a name without a route is refused, and a routed target needs
+//! no allowlist entry. Clients that pass `?target=`, multiplexed sessions and
+//! datagram tunnels are unaffected.
+
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::allowlist::{split_target, unix_path, AllowlistProvider};
+use crate::config::SniConfig;
+use crate::tls::ClientStream;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Most bytes read while waiting for a complete ClientHello.
+const MAX_HELLO: usize = 64 * 1024;
+
+const CONTENT_HANDSHAKE: u8 = 22;
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const EXTENSION_SERVER_NAME: u16 = 0;
+const NAME_TYPE_HOST: u8 = 0;
+
+/// Server names and the targets they route to.
+#[derive(Debug, Clone, Default)]
+pub struct Routes {
+    /// Lower-cased server name to `host:port`.
+    routes: HashMap<String, String>,
+}
+
+impl Routes {
+    /// Check and load the routes of `config`.
+    pub fn from_config(config: &SniConfig) -> Result<Self, BoxError> {
+        let mut routes = HashMap::new();
+        for (name, target) in &config.routes {
+            if split_target(target).is_none() && unix_path(target).is_none() {
+                return Err(format!("invalid SNI route target {target:?} for {name}").into());
+            }
+            routes.insert(name.to_ascii_lowercase(), target.clone());
+        }
+        Ok(Self { routes })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// The target `server_name` routes to, if any.
+    pub fn route(&self, server_name: &str) -> Option<&str> {
+        self.routes
+            .get(&server_name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// Allows the targets that names route to.
+impl AllowlistProvider for Routes {
+    fn allows(&self, target: &str) -> bool {
+        self.routes.values().any(|routed| routed == target)
+    }
+}
+
+/// Read messages from `ws` until they hold a whole ClientHello, returning
+/// the bytes read and its server name, if it has one.
+pub async fn read_client_hello(
+    ws: &mut WebSocketStream<ClientStream>,
+) -> Result<(Vec<u8>, Option<String>), BoxError> {
+    let mut buf = Vec::new();
+    loop {
+        match server_name(&buf) {
+            Hello::Complete(name) => return Ok((buf, name)),
+            Hello::Invalid => return Err("the tunnel did not start with a TLS ClientHello".into()),
+            Hello::Incomplete if buf.len() > MAX_HELLO => {
+                return Err("the ClientHello is too large".into())
+            }
+            Hello::Incomplete => {}
+        }
+        match ws.next().await {
+            Some(Ok(msg)) if msg.is_binary() || msg.is_text() => buf.extend(msg.into_data()),
+            Some(Ok(msg)) if msg.is_close() => {
+                return Err("the client closed before sending a ClientHello".into())
+            }
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err("the client closed before sending a ClientHello".into()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Hello {
+    /// More bytes are needed.
+    Incomplete,
+    /// Not a ClientHello.
+    Invalid,
+    /// The server name, if the ClientHello has one.
+    Complete(Option<String>),
+}
+
+/// Find the server name of the ClientHello at the start of `buf`, which may
+/// span several TLS records.
+fn server_name(buf: &[u8]) -> Hello {
+    let mut handshake = Vec::new();
+    let mut records = buf;
+    loop {
+        if handshake.len() >= 4 {
+            if handshake[0] != HANDSHAKE_CLIENT_HELLO {
+                return Hello::Invalid;
+            }
+            let len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+            if handshake.len() >= 4 + len {
+                return match parse_client_hello(&handshake[4..4 + len]) {
+                    Some(name) => Hello::Complete(name),
+                    None => Hello::Invalid,
+                };
+            }
+        }
+        if records.len() < 5 {
+            return Hello::Incomplete;
+        }
+        if records[0] != CONTENT_HANDSHAKE {
+            return Hello::Invalid;
+        }
+        let len = u16::from_be_bytes([records[3], records[4]]) as usize;
+        if records.len() < 5 + len {
+            return Hello::Incomplete;
+        }
+        handshake.extend_from_slice(&records[5..5 + len]);
+        records = &records[5 + len..];
+    }
+}
+
+/// The server name of a ClientHello body, `None` if it is malformed and
+/// `Some(None)` if it names no server.
+fn parse_client_hello(body: &[u8]) -> Option<Option<String>> {
+    let mut reader = Reader(body);
+    reader.take(2 + 32)?; // version and random
+    let session_id = reader.u8()? as usize;
+    reader.take(session_id)?;
+    let cipher_suites = reader.u16()? as usize;
+    reader.take(cipher_suites)?;
+    let compression = reader.u8()? as usize;
+    reader.take(compression)?;
+    if reader.0.is_empty() {
+        return Some(None);
+    }
+    let extensions = reader.u16()? as usize;
+    let mut extensions = Reader(reader.take(extensions)?);
+    while !extensions.0.is_empty() {
+        let kind = extensions.u16()?;
+        let len = extensions.u16()? as usize;
+        let data = extensions.take(len)?;
+        if kind != EXTENSION_SERVER_NAME {
+            continue;
+        }
+        let mut list = Reader(data);
+        let len = list.u16()? as usize;
+        let mut names = Reader(list.take(len)?);
+        while !names.0.is_empty() {
+            let name_type = names.u8()?;
+            let len = names.u16()? as usize;
+            let name = names.take(len)?;
+            if name_type == NAME_TYPE_HOST {
+                return std::str::from_utf8(name)
+                    .ok()
+                    .map(|name| Some(name.to_string()));
+            }
+        }
+        return Some(None);
+    }
+    Some(None)
+}
+
+/// Reads big-endian fields from a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    fn client_hello(server_name: &str) -> Vec<u8> {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let name = server_name.to_string().try_into().unwrap();
+        let mut conn = rustls::ClientConnection::new(Arc::new(config), name).unwrap();
+        let mut hello = Vec::new();
+        conn.write_tls(&mut hello).unwrap();
+        hello
+    }
+
+    #[test]
+    fn test_server_name() {
+        let hello = client_hello("vllm.example.com");
+        assert_eq!(
+            server_name(&hello),
+            Hello::Complete(Some("vllm.example.com".to_string()))
+        );
+        assert_eq!(server_name(&hello[..hello.len() - 1]), Hello::Incomplete);
+        assert_eq!(server_name(&[]), Hello::Incomplete);
+        assert_eq!(server_name(b"GET / HTTP/1.1\r\n"), Hello::Invalid);
+    }
+
+    #[test]
+    fn test_server_name_across_records() {
+        let hello = client_hello("vllm.example.com");
+        // Split the handshake message into two records
+        let body = &hello[5..];
+        let (first, second) = body.split_at(40);
+        let mut split = Vec::new();
+        for fragment in [first, second] {
+            split.extend_from_slice(&[CONTENT_HANDSHAKE, 3, 1]);
+            split.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+            split.extend_from_slice(fragment);
+        }
+        assert_eq!(
+            server_name(&split),
+            Hello::Complete(Some("vllm.example.com".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        let config = SniConfig {
+            routes: BTreeMap::from([("VLLM.example.com".to_string(), "10.0.0.5:443".to_string())]),
+        };
+        let routes = Routes::from_config(&config).unwrap();
+        assert_eq!(routes.route("vllm.EXAMPLE.com"), Some("10.0.0.5:443"));
+        assert_eq!(routes.route("other.example.com"), None);
+        assert!(routes.allows("10.0.0.5:443"));
+        assert!(!routes.allows("10.0.0.6:443"));
+
+        let config = SniConfig {
+            routes: BTreeMap::from([("vllm.example.com".to_string(), "10.0.0.5".to_string())]),
+        };
+        assert!(Routes::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! TLS termination for the listeners, so browsers on HTTPS pages can use `wss://`.
+//!
+//! Configured by the `[tls]` section of the config (or the matching
+//! `ATLS_PROXY_*` variables), or a `[[listeners]]` entry's own `tls` table:
+//!
+//! - `cert` / `key`: PEM certificate chain and private key files.
+//! - `acme_domains`: domains to get a certificate for from Let's Encrypt
+//!   (TLS-ALPN-01, so the listener must be reachable on port 443), with
+//!   `acme_cache` (directory keeping the account and certificates), optional
+//!   `acme_email` and `acme_staging` for the staging directory.
+//!
+//! Without either, the listener speaks plaintext `ws://`. This TLS layer only
+//! protects the browser-to-proxy hop: the aTLS session to the TEE still runs
+//! end to end inside the WebSocket.
+
+use futures_util::StreamExt;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::ServerConfig;
+use rustls_acme::caches::DirCache;
+use rustls_acme::{is_tls_alpn_challenge, AcmeConfig};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::LazyConfigAcceptor;
+
+use crate::config::TlsConfig;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A client connection, with or without TLS.
+pub trait ClientIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ClientIo for T {}
+
+pub type ClientStream = Box<dyn ClientIo>;
+
+/// Terminates TLS on accepted connections.
+pub enum TlsAcceptor {
+    /// A certificate loaded from files.
+    Static(tokio_rustls::TlsAcceptor),
+    /// Certificates obtained and renewed over ACME.
+    Acme {
+        /// Answers TLS-ALPN-01 challenges.
+        challenge: Arc<ServerConfig>,
+        /// Serves the issued certificate.
+        default: Arc<ServerConfig>,
+    },
+}
+
+impl TlsAcceptor {
+    /// Build the acceptor `config` describes, or `None` for plaintext.
+    ///
+    /// Must be called inside the tokio runtime: ACME runs a background task
+    /// that orders and renews the certificate.
+    pub fn from_config(config: &TlsConfig) -> Result<Option<Self>, BoxError> {
+        match (&config.cert, &config.key) {
+            (Some(cert), Some(key)) => Ok(Some(Self::from_pem_files(cert, key)?)),
+            _ if !config.acme_domains.is_empty() => Ok(Some(Self::acme(
+                config.acme_domains.clone(),
+                &config.acme_cache,
+                config.acme_email.clone(),
+                config.acme_staging,
+            ))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Serve the PEM certificate chain and private key in these files.
+    pub fn from_pem_files(cert_path: &Path, key_path: &Path) -> Result<Self, BoxError> {
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| {
+                format!(
+                    "failed to read certificates from {}: {e}",
+                    cert_path.display()
+                )
+            })?;
+        if certs.is_empty() {
+            return Err(format!("no certificates found in {}", cert_path.display()).into());
+        }
+        let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| {
+            format!(
+                "failed to read private key from {}: {e}",
+                key_path.display()
+            )
+        })?;
+
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Self::Static(tokio_rustls::TlsAcceptor::from(Arc::new(
+            config,
+        ))))
+    }
+
+    /// Obtain certificates for `domains` from Let's Encrypt, renewing them in
+    /// the background.
+    fn acme(domains: Vec<String>, cache_dir: &Path, email: Option<String>, staging: bool) -> Self {
+        let mut config = AcmeConfig::new(domains)
+            .cache(DirCache::new(cache_dir.to_path_buf()))
+            .directory_lets_encrypt(!staging);
+        if let Some(email) = email {
+            config = config.contact_push(format!("mailto:{email}"));
+        }
+        let mut state = config.state();
+        let challenge = state.challenge_rustls_config();
+        let default = state.default_rustls_config();
+
+        tokio::spawn(async move {
+            while let Some(event) = state.next().await {
+                match event {
+                    Ok(event) => tracing::info!("ACME: {event:?}"),
+                    Err(e) => tracing::warn!("ACME error: {e:?}"),
+                }
+            }
+        });
+
+        Self::Acme { challenge, default }
+    }
+
+    /// Run the TLS handshake on an accepted connection.
+    ///
+    /// Returns `None` for connections that only answered an ACME challenge.
+    pub async fn accept(&self, tcp: TcpStream) -> io::Result<Option<ClientStream>> {
+        match self {
+            Self::Static(acceptor) => Ok(Some(Box::new(acceptor.accept(tcp).await?))),
+            Self::Acme { challenge, default } => {
+                let start = LazyConfigAcceptor::new(Default::default(), tcp).await?;
+                if is_tls_alpn_challenge(&start.client_hello()) {
+                    let mut tls = start.into_stream(challenge.clone()).await?;
+                    tls.shutdown().await?;
+                    return Ok(None);
+                }
+                Ok(Some(Box::new(start.into_stream(default.clone()).await?)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_pem_files_missing_file() {
+        let result = TlsAcceptor::from_pem_files(
+            Path::new("/nonexistent/cert.pem"),
+            Path::new("/nonexistent/key.pem"),
+        );
+        let err = result
+            .err()
+            .expect("missing files must be rejected")
+            .to_string();
+        assert!(err.contains("/nonexistent/cert.pem"));
+    }
+
+    #[test]
+    fn test_from_pem_files_without_certificates() {
+        let path = std::env::temp_dir().join("atlas-proxy-test-empty.pem");
+        std::fs::write(&path, "not a certificate\n").unwrap();
+
+        let err = TlsAcceptor::from_pem_files(&path, &path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("no certificates found"));
+    }
+}
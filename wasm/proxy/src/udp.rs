@@ -0,0 +1,141 @@
+//! Datagram tunnels: WebSocket messages to and from a UDP target, so QUIC
+//! (HTTP/3) backends can be reached through the proxy.
+//!
+//! A client asks for one by offering the [`PROTOCOL`] subprotocol, which the
+//! proxy echoes, or with `?udp=1`, and names its target with `?target=` like a
+//! TCP tunnel. Each binary message is one datagram, in either direction.
+//!
+//! Targets are checked against the `[udp] allowlist`, not the TCP one, so
+//! allowing TCP to a host doesn't open UDP to it; with no entries, every
+//! datagram tunnel is refused. Datagrams larger than `[udp] max_datagram` are
+//! dropped, as a network would, and so are datagrams the target's host
+//! rejects: delivery is the client's concern, as with UDP itself.
+
+use futures_util::{SinkExt, StreamExt};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::UdpSocket;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+use tracing::{debug, info, warn};
+
+use crate::access_log::CloseReason;
+use crate::mux::SessionConfig;
+use crate::server::{expire, going_away};
+use crate::tls::ClientStream;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// `Sec-WebSocket-Protocol` entry asking for a datagram tunnel.
+pub const PROTOCOL: &str = "atlas-udp.v1";
+
+/// Forward datagrams between `ws` and `target` until the client closes.
+pub async fn serve(
+    ws: WebSocketStream<ClientStream>,
+    target: &str,
+    max_datagram: usize,
+    session: &SessionConfig,
+) -> Result<CloseReason, BoxError> {
+    let addr = match session.resolver.resolve(target).await {
+        Ok(addrs) => addrs[0],
+        Err(e) => {
+            warn!(%target, error = %e, "failed to resolve target");
+            return Ok(CloseReason::TargetUnreachable);
+        }
+    };
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+    if session.log_connections {
+        info!(%target, %addr, "datagram tunnel opened");
+    }
+
+    let (mut ws_sink, mut ws_source) = ws.split();
+    // One byte over the limit, to tell an oversized datagram from a full one
+    let mut buf = vec![0u8; max_datagram + 1];
+    let lifetime = expire(session.max_lifetime);
+    tokio::pin!(lifetime);
+    let reason = loop {
+        // Restarted on every datagram, so it only fires once the tunnel is idle
+        let idle = expire(session.idle_timeout);
+        tokio::select! {
+            _ = idle => {
+                let _ = ws_sink.send(going_away("idle timeout")).await;
+                break CloseReason::IdleTimeout;
+            }
+            _ = &mut lifetime => {
+                let _ = ws_sink.send(going_away("maximum lifetime reached")).await;
+                break CloseReason::MaxLifetime;
+            }
+            msg = ws_source.next() => {
+                match msg {
+                    Some(Ok(msg)) => {
+                        if msg.is_binary() || msg.is_text() {
+                            let data = msg.into_data();
+                            if data.len() > max_datagram {
+                                debug!(len = data.len(), "dropping oversized datagram from client");
+                            } else if let Err(e) = socket.send(&data).await {
+                                debug!(error = %e, "failed to send datagram");
+                            }
+                        } else if msg.is_close() {
+                            let _ = ws_sink.send(Message::Close(None)).await;
+                            break CloseReason::ClientClosed;
+                        }
+                    }
+                    Some(Err(e)) => return Err(Box::new(e)),
+                    None => break CloseReason::ClientClosed,
+                }
+            }
+            res = socket.recv(&mut buf) => {
+                match res {
+                    Ok(n) if n > max_datagram => debug!("dropping oversized datagram from target"),
+                    Ok(n) => ws_sink.send(Message::Binary(buf[..n].to_vec())).await?,
+                    // Such as ICMP port unreachable, reported on a connected socket
+                    Err(e) => debug!(error = %e, "failed to receive datagram"),
+                }
+            }
+        }
+    };
+    let _ = ws_sink.close().await;
+    Ok(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::{accept_async, connect_async};
+
+    #[tokio::test]
+    async fn test_datagrams_round_trip() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = echo.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 2048];
+            loop {
+                let (n, peer) = echo.recv_from(&mut buf).await.unwrap();
+                echo.send_to(&buf[..n], peer).await.unwrap();
+            }
+        });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let ws = accept_async(Box::new(tcp) as ClientStream).await.unwrap();
+            let _ = serve(ws, &target, 8, &SessionConfig::default()).await;
+        });
+
+        let (mut ws, _) = connect_async(&url).await.unwrap();
+        ws.send(Message::Binary(b"too large".to_vec()))
+            .await
+            .unwrap();
+        for datagram in [&b"one"[..], b"two"] {
+            ws.send(Message::Binary(datagram.to_vec())).await.unwrap();
+            let reply = ws.next().await.unwrap().unwrap();
+            assert_eq!(reply, Message::Binary(datagram.to_vec()));
+        }
+    }
+}
@@ -362,3 +362,318 @@ async fn test_websocket_binary_data() {
 
     ws_stream.close(None).await.ok();
 }
+
+#[tokio::test]
+async fn test_embedded_proxy_server() {
+    let (echo_addr, _echo_handle) = spawn_echo_server().await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("ws://{}", listener.local_addr().unwrap());
+    let allowed = echo_addr.clone();
+    let server = atlas_proxy::ProxyServer::builder()
+        .target(echo_addr.clone())
+        .allowlist(move |target: &str| target == allowed)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    let running = tokio::spawn(server.run());
+
+    let (mut ws_stream, _) = connect_async(&proxy_url)
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(b"embedded".to_vec()))
+        .await
+        .expect("Failed to send message");
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("Timeout")
+        .expect("Stream ended")
+        .expect("Error receiving");
+    assert_eq!(msg, Message::Binary(b"embedded".to_vec()));
+    ws_stream.close(None).await.ok();
+
+    shutdown.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), running)
+        .await
+        .expect("Server did not stop")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_sni_routing() {
+    let (echo_addr, _echo_handle) = spawn_echo_server().await;
+
+    // Only the route reaches the echo server; the allowlist doesn't name it
+    let mut config = atlas_proxy::Config {
+        target: "127.0.0.1:1".to_string(),
+        allowlist: vec!["127.0.0.1:1".to_string()],
+        ..atlas_proxy::Config::default()
+    };
+    config
+        .sni
+        .routes
+        .insert("tee.example.com".to_string(), echo_addr.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = atlas_proxy::ProxyServer::builder()
+        .config(config)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+    let client_hello = |name: &'static str| {
+        let tls = rustls::ClientConfig::builder()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let mut conn =
+            rustls::ClientConnection::new(std::sync::Arc::new(tls), name.try_into().unwrap())
+                .unwrap();
+        let mut hello = Vec::new();
+        conn.write_tls(&mut hello).unwrap();
+        hello
+    };
+
+    // The ClientHello is forwarded to the routed target, here echoed back
+    let hello = client_hello("tee.example.com");
+    let (mut ws_stream, _) = connect_async(&proxy_url)
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(hello.clone()))
+        .await
+        .expect("Failed to send message");
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("Timeout")
+        .expect("Stream ended")
+        .expect("Error receiving");
+    assert_eq!(msg, Message::Binary(hello));
+    ws_stream.close(None).await.ok();
+
+    // A server name without a route is refused
+    let (mut ws_stream, _) = connect_async(&proxy_url)
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(client_hello("other.example.com")))
+        .await
+        .expect("Failed to send message");
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("Timeout");
+    assert!(matches!(msg, Some(Ok(Message::Close(_))) | None));
+
+    shutdown.shutdown();
+}
+
+#[tokio::test]
+async fn test_client_filtering_behind_trusted_proxy() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Error;
+
+    let (echo_addr, _echo_handle) = spawn_echo_server().await;
+
+    let mut config = atlas_proxy::Config {
+        target: echo_addr.clone(),
+        allowlist: vec![echo_addr.clone()],
+        ..atlas_proxy::Config::default()
+    };
+    config.clients.allow = vec!["203.0.113.0/24".to_string()];
+    config.clients.trusted_proxies = vec!["127.0.0.1".to_string()];
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = atlas_proxy::ProxyServer::builder()
+        .config(config)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+
+    let request = |forwarded_for: &str| {
+        let mut request = proxy_url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        request
+    };
+
+    // The forwarded client is allowed
+    let (mut ws_stream, _) = connect_async(request("203.0.113.7"))
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(b"forwarded".to_vec()))
+        .await
+        .expect("Failed to send message");
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("Timeout")
+        .expect("Stream ended")
+        .expect("Error receiving");
+    assert_eq!(msg, Message::Binary(b"forwarded".to_vec()));
+    ws_stream.close(None).await.ok();
+
+    // Another client is refused at the handshake
+    match connect_async(request("198.51.100.1")).await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 403),
+        other => panic!("expected 403, got {other:?}"),
+    }
+
+    shutdown.shutdown();
+}
+
+#[tokio::test]
+async fn test_client_limits_behind_trusted_proxy() {
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Error;
+
+    let (echo_addr, _echo_handle) = spawn_echo_server().await;
+
+    let mut config = atlas_proxy::Config {
+        target: echo_addr.clone(),
+        allowlist: vec![echo_addr.clone()],
+        ..atlas_proxy::Config::default()
+    };
+    config.clients.trusted_proxies = vec!["127.0.0.1".to_string()];
+    config.limits.max_connections_per_ip = 1;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = atlas_proxy::ProxyServer::builder()
+        .config(config)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+
+    let request = |forwarded_for: &str| {
+        let mut request = proxy_url.as_str().into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        request
+    };
+
+    // The limit counts each forwarded client, not the balancer they share
+    let (_first, _) = connect_async(request("203.0.113.7"))
+        .await
+        .expect("Failed to connect to proxy");
+    let (_other, _) = connect_async(request("203.0.113.8"))
+        .await
+        .expect("Failed to connect to proxy");
+    match connect_async(request("203.0.113.7")).await {
+        Err(Error::Http(response)) => assert_eq!(response.status(), 429),
+        other => panic!("expected 429, got {other:?}"),
+    }
+
+    shutdown.shutdown();
+}
+
+#[tokio::test]
+async fn test_proxy_protocol_header() {
+    let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let target_addr = target.local_addr().unwrap().to_string();
+    let received = tokio::spawn(async move {
+        let (mut tcp, _) = target.accept().await.unwrap();
+        // 16-byte header, 12 bytes of IPv4 addresses and ports, then the data
+        let mut buf = [0u8; 28 + 4];
+        tcp.read_exact(&mut buf).await.unwrap();
+        buf
+    });
+
+    let mut config = atlas_proxy::Config {
+        target: target_addr.clone(),
+        allowlist: vec![target_addr],
+        ..atlas_proxy::Config::default()
+    };
+    config.upstream.proxy_protocol = true;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let server = atlas_proxy::ProxyServer::builder()
+        .config(config)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+
+    let (mut ws_stream, _) = connect_async(format!("ws://{proxy_addr}"))
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(b"data".to_vec()))
+        .await
+        .expect("Failed to send message");
+    let buf = tokio::time::timeout(Duration::from_secs(5), received)
+        .await
+        .expect("Timeout")
+        .unwrap();
+
+    assert_eq!(&buf[..12], b"\r\n\r\n\0\r\nQUIT\n");
+    assert_eq!(&buf[12..16], &[0x21, 0x11, 0, 12]);
+    // From the browser's address to the proxy's
+    assert_eq!(&buf[16..20], &[127, 0, 0, 1]);
+    assert_eq!(&buf[20..24], &[127, 0, 0, 1]);
+    assert_eq!(u16::from_be_bytes([buf[26], buf[27]]), proxy_addr.port());
+    assert_eq!(&buf[28..], b"data");
+
+    ws_stream.close(None).await.ok();
+    shutdown.shutdown();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_target() {
+    let dir = std::env::temp_dir().join(format!("atlas-proxy-it-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("echo.sock");
+    let _ = std::fs::remove_file(&path);
+    let socket = tokio::net::UnixListener::bind(&path).unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = socket.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        stream.write_all(&buf[..n]).await.unwrap();
+    });
+
+    let target = format!("unix:{}", path.display());
+    let config = atlas_proxy::Config {
+        target: target.clone(),
+        allowlist: vec![target],
+        ..atlas_proxy::Config::default()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = atlas_proxy::ProxyServer::builder()
+        .config(config)
+        .listener(listener)
+        .build()
+        .expect("Failed to build proxy");
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+
+    let (mut ws_stream, _) = connect_async(&proxy_url)
+        .await
+        .expect("Failed to connect to proxy");
+    ws_stream
+        .send(Message::Binary(b"over a socket".to_vec()))
+        .await
+        .expect("Failed to send message");
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("Timeout")
+        .expect("Stream ended")
+        .expect("Error receiving");
+    assert_eq!(msg, Message::Binary(b"over a socket".to_vec()));
+    ws_stream.close(None).await.ok();
+
+    shutdown.shutdown();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -1,24 +1,383 @@
-export interface AttestationResult {
-  trusted: boolean;
-  teeType: string;
-  tcbStatus: string;
+import type { AttestationResult, ConnectionDiagnostics } from "./atlas_wasm.js";
+
+export type {
+  AttestationResult,
+  AttestationTimings,
+  AtlsHttpResponse,
+  AtlsRequestBody,
+  AtlsRequestTrailers,
+  ConnectionDiagnostics,
+  HeaderEntry
+} from "./atlas_wasm.js";
+
+/**
+ * Thrown when a new connection attests a different app digest (MRTD, RTMR0-2 and compose hash)
+ * than the last accepted connection; `previous` and `current` are the digests.
+ */
+export class MeasurementChangedError extends Error {
+  readonly code: "MEASUREMENT_CHANGED";
+  readonly previous: string | null;
+  readonly current: string | null;
 }
 
+export function closeAllConnections(): void;
+
 export interface AtlsFetchOptions {
   proxyUrl: string;
   targetHost: string;
+  policy: object;
+  /** Hostname the attestation is verified for, also sent as SNI (default: targetHost's hostname). */
   serverName?: string;
+  /** Server name sent in the TLS ClientHello instead of `serverName` (e.g. for SNI-routing ingresses). */
+  sni?: string;
+  /** ALPN protocols to offer (default `["http/1.1"]`); must include "http/1.1". */
+  alpn?: string[];
   defaultHeaders?: Record<string, string>;
   onAttestation?: (attestation: AttestationResult) => void;
+  /** Maximum concurrent connections to the target (default 6). */
+  maxConnections?: number;
+  /** Close pooled connections idle for this long (default 50000; `Infinity` disables). */
+  idleTimeoutMs?: number;
+  /** Retire connections whose attestation is older than this, so the next request re-attests (default `Infinity`). */
+  attestationMaxAgeMs?: number;
+  /** Interval of WebSocket keepalive messages on quiet connections (default 20000; 0 disables). */
+  keepAliveIntervalMs?: number;
+  /** Accept a different `appDigest` than the last accepted connection, e.g. across redeploys (default false). */
+  allowMeasurementChange?: boolean;
+  /** Called after each new connection is attested. */
+  onAttested?: (attestation: AttestationResult) => void;
+  /** Called when a new connection presents different evidence than the previous one. */
+  onAttestationChanged?: (current: AttestationResult, previous: AttestationResult) => void;
+  /** Called when a pooled connection is closed. */
+  onConnectionClosed?: (event: ConnectionClosedEvent) => void;
+  /** Store cookies across requests: `true` for a private jar, or a shared `CookieJar`. */
+  cookieJar?: boolean | CookieJar;
+  /** Maximum redirects followed per request (default 20). */
+  maxRedirects?: number;
+  /** Turn POST into GET on 301/302, as browsers do (default true). 303 always becomes GET. */
+  rewritePostToGet?: boolean;
+  /** Default size limit (bytes) for `json()`, `text()`, `arrayBuffer()` and `blob()`. */
+  maxBodySize?: number;
+  /** Maximum response header size in bytes (minimum 8192; hyper's ~400 KiB default otherwise). */
+  maxHeaderSize?: number;
+  /** Hard cap on every response body, streamed or not; the stream errors with `BODY_TOO_LARGE` past it. */
+  maxResponseBodySize?: number;
+  /**
+   * Send `Expect: 100-continue` for bodies of at least this many bytes (and all streamed bodies),
+   * so the body is only uploaded once the server accepts the request. Off by default.
+   */
+  expectContinue?: number;
+  /** How long to wait for `100 Continue` before sending the body anyway (default 1000). */
+  expectContinueTimeoutMs?: number;
+  /**
+   * Carry every connection to the proxy over one shared WebSocket (adds `mux=1` to the proxy URL).
+   * Needs a proxy with multiplexing support; each target is still checked against its allowlist.
+   */
+  multiplex?: boolean;
+  /** `https://` WebTransport relay, preferred over `proxyUrl` where WebTransport is supported. */
+  webTransportUrl?: string;
+  /**
+   * Store successful attestations for `getCachedAttestation()` (default true). An object overrides
+   * the 10 minute expiry and the `sessionStorage` backend.
+   */
+  attestationCache?: boolean | { ttlMs?: number; storage?: Storage };
+  /** Default retry policy for transient failures (no retries unless set). */
+  retry?: RetryOptions;
+  /** Interceptors registered up front (more can be added via `fetch.interceptors`). */
+  interceptors?: {
+    request?: RequestInterceptor[];
+    response?: ResponseInterceptor[];
+  };
+}
+
+export interface RetryOptions {
+  /** Extra attempts after the first (default 0). */
+  retries?: number;
+  /** Methods that may be retried (default: idempotent methods). */
+  methods?: string[];
+  /** First backoff delay; doubles on each attempt, with jitter (default 100). */
+  baseDelayMs?: number;
+  /** Backoff cap (default 2000). */
+  maxDelayMs?: number;
+}
+
+/** `RequestInit` accepted by an `AtlsFetch`. */
+export interface AtlsFetchInit extends Omit<RequestInit, "body"> {
+  /**
+   * Request body. A `ReadableStream` or async iterable (e.g. an async generator) is streamed
+   * with `Transfer-Encoding: chunked` and never retried or replayed on redirect.
+   */
+  body?: BodyInit | AsyncIterable<string | ArrayBuffer | ArrayBufferView> | null;
+  /**
+   * Trailer fields sent after a chunked body. A function is called once the body has been sent;
+   * declare its names in a `Trailer` header.
+   */
+  trailers?: HeadersInit | (() => HeadersInit | Promise<HeadersInit>);
+  /** Per-request retry policy, merged over the fetch's default; `false` disables retries. */
+  retry?: RetryOptions | false;
+  /**
+   * `Host` sent with this request (`host[:port]`) instead of the target's, for gateways serving
+   * several virtual hosts. Redirects stay on the attested origin and keep this host.
+   */
+  host?: string;
+}
+
+/** Outgoing request as seen by request interceptors. */
+export interface InterceptedRequest {
+  method: string;
+  /** Must stay on the attested origin. */
+  url: URL;
+  headers: Headers;
+  body: Uint8Array | ReadableStream<Uint8Array> | null;
+  /** Attestation of the connection the request will be sent on. */
+  readonly attestation: AttestationResult;
+}
+
+/** Mutate the request in place, or return a replacement. */
+export type RequestInterceptor = (
+  request: InterceptedRequest
+) => void | InterceptedRequest | Promise<void | InterceptedRequest>;
+
+/** Observe the final response, or return a replacement. */
+export type ResponseInterceptor = (
+  response: AtlsResponse
+) => void | AtlsResponse | Promise<void | AtlsResponse>;
+
+export interface InterceptorList<T> {
+  /** Register an interceptor; returns a function that removes it. */
+  use(interceptor: T): () => void;
+  clear(): void;
+  readonly size: number;
+}
+
+/** Thrown when a redirect leaves the attested origin or exceeds `maxRedirects`. */
+export class RedirectError extends Error {
+  readonly code: "REDIRECT_CROSS_ORIGIN" | "TOO_MANY_REDIRECTS";
+  /** Absolute URL of the redirect target. */
+  readonly location: string;
+}
+
+/** Minimal cookie store capturing `Set-Cookie` and sending `Cookie`. */
+export class CookieJar {
+  /** `isPublicSuffix` replaces the small built-in public suffix list (e.g. with the full PSL). */
+  constructor(options?: { isPublicSuffix?: (domain: string) => boolean });
+  setCookies(setCookieHeaders: string[], url: string | URL): void;
+  getCookieHeader(url: string | URL): string;
+  clear(): void;
+}
+
+export interface ConnectionClosedEvent {
+  /**
+   * What the pool did: `"stale"`, `"idle"`, `"error"`, `"body-incomplete"`, `"closed"`
+   * (closeAllConnections), `"remote"` (the server or tunnel ended it) or
+   * `"attestation-expired"` (`attestationMaxAgeMs`).
+   */
+  reason: "stale" | "idle" | "error" | "body-incomplete" | "closed" | "remote" | "attestation-expired";
+  /** Why the connection ended, when known. */
+  code?: "TUNNEL_CLOSED" | "TLS_ALERT" | "PROTOCOL_ERROR" | "ATTESTATION_EXPIRED";
+  message?: string;
+  /** TLS alert description for `TLS_ALERT` (e.g. `"HandshakeFailure"`). */
+  alert?: string;
+  attestation: AttestationResult;
+}
+
+export interface ConnectionPoolStats {
+  total: number;
+  idle: number;
+  active: number;
+  waiting: number;
+}
+
+export function getConnectionPoolStats(): ConnectionPoolStats;
+
+/** A Server-Sent Event. */
+export interface ServerSentEvent {
+  /** Event type (`"message"` unless the server sent `event:`). */
+  event: string;
+  data: string;
+  /** Last event ID seen on the stream. */
+  id: string;
+  retry?: number;
+}
+
+/** Parse a `text/event-stream` body incrementally. */
+export function parseEventStream(body: ReadableStream<Uint8Array>): AsyncGenerator<ServerSentEvent, void, undefined>;
+
+/** Thrown when a body read with a size guard exceeds its limit. */
+export class BodyTooLargeError extends Error {
+  readonly code: "BODY_TOO_LARGE";
+  readonly limit: number;
+}
+
+export interface BodyReadOptions {
+  /** Maximum body size in bytes (defaults to the fetch's `maxBodySize`). */
+  maxSize?: number;
 }
 
 export interface AtlsResponse extends Response {
   readonly attestation: AttestationResult;
+  /** Number of attempts made for the final request (1 without retries). */
+  readonly attempts: number;
+  /** Read the rest of the body into one `Uint8Array`; rejects with `BODY_TOO_LARGE` past `maxBytes`. */
+  readAll(maxBytes?: number): Promise<Uint8Array>;
+  arrayBuffer(options?: BodyReadOptions): Promise<ArrayBuffer>;
+  text(options?: BodyReadOptions): Promise<string>;
+  json(options?: BodyReadOptions): Promise<any>;
+  blob(options?: BodyReadOptions): Promise<Blob>;
+  /** Iterate the body as Server-Sent Events; breaking out closes the connection. */
+  eventStream(): AsyncGenerator<ServerSentEvent, void, undefined>;
+  /** Trailer fields of a chunked response, once the body is read; null if it did not complete. */
+  trailers(): Promise<Headers | null>;
 }
 
-export type AtlsFetch = (input: RequestInfo | URL, init?: RequestInit) => Promise<AtlsResponse>;
+export type AtlsFetch = ((input: RequestInfo | URL, init?: AtlsFetchInit) => Promise<AtlsResponse>) & {
+  /** Present when `cookieJar` is enabled. */
+  readonly cookieJar?: CookieJar;
+  readonly interceptors: {
+    request: InterceptorList<RequestInterceptor>;
+    response: InterceptorList<ResponseInterceptor>;
+  };
+  /** TLS parameters, timings and collateral details of the connection that served the last response. */
+  diagnostics(): ConnectionDiagnostics | null;
+  /** Last stored attestation of this fetch's target and policy, if not expired. */
+  getCachedAttestation(): CachedAttestation | null;
+  /** Stop `onConnectionClosed` notifications; pooled connections stay usable by other fetches. */
+  dispose(): void;
+};
+
+/** A successful attestation persisted for display across page reloads. */
+export interface CachedAttestation {
+  measurement: string | null;
+  policyFingerprint: string;
+  attestation: AttestationResult;
+  /** Epoch milliseconds. */
+  cachedAt: number;
+  /** Epoch milliseconds. */
+  expiresAt: number;
+}
+
+/**
+ * Last successful attestation of `targetHost` under `policy`, or null. Display-only:
+ * requests always use freshly attested connections.
+ */
+export function getCachedAttestation(options: {
+  targetHost: string;
+  policy: object | string;
+  storage?: Storage;
+}): CachedAttestation | null;
+
+/** Remove every cached attestation from `storage` (default `sessionStorage`). */
+export function clearCachedAttestations(storage?: Storage): void;
+
+/** Stable 64-bit hex fingerprint of a policy, used in attestation cache keys. */
+export function policyFingerprint(policy: object | string): string;
 
 export function createAtlsFetch(options: AtlsFetchOptions): AtlsFetch;
 
-export { AttestedStream } from "./atls_wasm.js";
+/** aTLS settings for `atlsFetch`; the target is taken from the request URL. */
+export type AtlsRequestOptions = Omit<AtlsFetchOptions, "targetHost" | "defaultHeaders">;
+
+export interface AtlsRequestInit extends AtlsFetchInit {
+  atls: AtlsRequestOptions;
+}
+
+/** `fetch()`-compatible function routing the request over aTLS. */
+export function atlsFetch(input: RequestInfo | URL, init: AtlsRequestInit): Promise<AtlsResponse>;
+
+/** A multipart field value: text, a Blob/File, or a value with explicit part metadata. */
+export type MultipartValue =
+  | string
+  | Blob
+  | { value: string | Blob; filename?: string; contentType?: string };
+
+export interface MultipartBody {
+  body: Blob;
+  /** `multipart/form-data; boundary=...`, to send as the Content-Type header. */
+  contentType: string;
+  boundary: string;
+}
+
+/**
+ * Build a `multipart/form-data` body (fields in order).
+ * Throws a `TypeError` for an invalid boundary (1-70 RFC 2046 characters) or a part content type
+ * containing CR or LF.
+ */
+export function encodeMultipart(
+  fields: FormData | Record<string, MultipartValue> | Array<[string, MultipartValue]>,
+  options?: { boundary?: string }
+): MultipartBody;
+
+export interface AtlsStreamOptions {
+  proxyUrl: string;
+  /** Target `host[:port]` (port defaults to 443). */
+  target: string;
+  policy: object | string;
+  /** Hostname the attestation is verified for, also sent as SNI (default: target hostname). */
+  serverName?: string;
+  /** Server name sent in the TLS ClientHello instead of `serverName`. */
+  sni?: string;
+  /** ALPN protocols to offer (default `["http/1.1"]`). */
+  alpn?: string[];
+  /** Bytes read from the connection at a time, and the default size of `read()` (default 65536). */
+  readChunkSize?: number;
+}
+
+/** Raw duplex byte stream over an attested TLS connection (no HTTP). */
+export class AtlsStream {
+  static connect(options: AtlsStreamOptions): Promise<AtlsStream>;
+  readonly attestation: AttestationResult;
+  /** Read up to `n` bytes (default `readChunkSize`); `null` at end of stream. */
+  read(n?: number): Promise<Uint8Array | null>;
+  /** Read exactly `n` bytes; rejects if the stream ends first. */
+  readExactly(n: number): Promise<Uint8Array>;
+  /** Read until the peer closes the stream; rejects with `BODY_TOO_LARGE` past `maxBytes`. */
+  readAll(maxBytes?: number): Promise<Uint8Array>;
+  write(data: Uint8Array | ArrayBuffer | string): Promise<void>;
+  /** Send TLS close_notify after pending writes; reads may continue. */
+  closeWrite(): Promise<void>;
+  close(): Promise<void>;
+}
+
+/** Main-thread proxy for a Worker running `atls-worker.js`. */
+export class AtlsWorker {
+  constructor(worker: Worker);
+  /** Same options as `createAtlsFetch`; callbacks run on the main thread. */
+  createFetch(options: AtlsFetchOptions): AtlsFetch;
+  /** Close the worker's pooled connections. */
+  closeAllConnections(): void;
+  /** Close all connections and terminate the worker. */
+  terminate(): void;
+}
+
+export interface AtlsWebSocketOptions {
+  proxyUrl: string;
+  policy: object;
+  serverName?: string;
+}
+
+/** WebSocket tunneled inside an attested TLS connection (browser `WebSocket`-like API). */
+export class AtlsWebSocket extends EventTarget {
+  static readonly CONNECTING: 0;
+  static readonly OPEN: 1;
+  static readonly CLOSING: 2;
+  static readonly CLOSED: 3;
+
+  constructor(url: string | URL, protocols: string | string[] | undefined, options: AtlsWebSocketOptions);
+
+  readonly url: string;
+  readonly readyState: 0 | 1 | 2 | 3;
+  readonly protocol: string;
+  readonly attestation: AttestationResult | null;
+  binaryType: "arraybuffer" | "blob";
+
+  onopen: ((event: Event) => void) | null;
+  onmessage: ((event: MessageEvent) => void) | null;
+  onerror: ((event: Event) => void) | null;
+  onclose: ((event: CloseEvent) => void) | null;
+
+  send(data: string | ArrayBuffer | ArrayBufferView): void;
+  close(code?: number, reason?: string): void;
+}
+
+export { default as init, AttestedStream, AtlsHttp, Policy, mergeWithDefaultAppCompose } from "./atlas_wasm.js";
 
@@ -46,7 +46,7 @@
  * ```
  */
 
-import init, { AttestedStream, AtlsHttp, mergeWithDefaultAppCompose } from "./atlas_wasm.js";
+import init, { AttestedStream, AtlsHttp, Policy, mergeWithDefaultAppCompose } from "./atlas_wasm.js";
 
 // ============================================================================
 // WASM Initialization
@@ -65,230 +65,2386 @@ async function ensureWasm() {
 // Connection Pool (for HTTP keep-alive / connection reuse)
 // ============================================================================
 
+/** Default maximum number of concurrent connections per target (same as browsers per host). */
+const DEFAULT_MAX_CONNECTIONS = 6;
+
+/**
+ * Idle pooled connections are closed after this long, below the ~60s after
+ * which many proxies and load balancers silently drop idle WebSockets.
+ */
+const DEFAULT_IDLE_TIMEOUT_MS = 50_000;
+
+/** Interval of WebSocket keepalive messages on quiet connections. */
+const DEFAULT_KEEPALIVE_INTERVAL_MS = 20_000;
+
+const yieldToEventLoop = () => new Promise((resolve) => setTimeout(resolve, 0));
+
+function closeQuietly(http) {
+  try {
+    http.close();
+  } catch (e) {
+    // Ignore errors during cleanup
+  }
+}
+
+/** Invoke a notification callback, logging (not propagating) its errors. */
+function notify(name, callback, ...args) {
+  if (typeof callback !== "function") return;
+  try {
+    const result = callback(...args);
+    if (result && typeof result.catch === "function") {
+      result.catch((e) => console.error(`[atls-fetch] ${name} callback failed:`, e));
+    }
+  } catch (e) {
+    console.error(`[atls-fetch] ${name} callback failed:`, e);
+  }
+}
+
+/** Close details of a connection retired by `attestationMaxAgeMs`. */
+const ATTESTATION_EXPIRED = Object.freeze({
+  code: "ATTESTATION_EXPIRED",
+  message: "attestation is older than attestationMaxAgeMs"
+});
+
+/**
+ * Pool of attested connections to a single (wsUrl, serverName).
+ *
+ * Each AtlsHttp carries one request at a time (HTTP/1.1). Overlapping requests
+ * get their own connection up to `maxConnections`; beyond that they wait for a
+ * connection to be released.
+ */
+class ConnectionPool {
+  constructor(maxConnections, idleTimeoutMs = DEFAULT_IDLE_TIMEOUT_MS, attestationMaxAgeMs = Infinity) {
+    this.maxConnections = maxConnections;
+    this.idleTimeoutMs = idleTimeoutMs;
+    this.attestationMaxAgeMs = attestationMaxAgeMs;
+    /** Connections ready for reuse. @type {AtlsHttp[]} */
+    this.idle = [];
+    /** Timers closing idle connections after `idleTimeoutMs`. @type {Map<AtlsHttp, number>} */
+    this.idleTimers = new Map();
+    /** Connections with a request (or unread response body) in flight. @type {Set<AtlsHttp>} */
+    this.active = new Set();
+    /** Connections being established. */
+    this.connecting = 0;
+    /** Requests waiting for a free slot. */
+    this.waiters = [];
+    /**
+     * Close callback of the fetch function that opened each connection, if it
+     * wants one. @type {Map<AtlsHttp, Function>}
+     */
+    this.closeListeners = new Map();
+    /** Connections already closed and reported. @type {WeakSet<AtlsHttp>} */
+    this.closed = new WeakSet();
+    /** How each connection ended, once its driver stopped. @type {WeakMap<AtlsHttp, Object>} */
+    this.closeEvents = new WeakMap();
+    /** Connections whose attestation is older than `attestationMaxAgeMs`. @type {WeakSet<AtlsHttp>} */
+    this.expired = new WeakSet();
+  }
+
+  /**
+   * Close a connection and tell listeners why: `reason` says what the pool did,
+   * `code` (and `message`, `alert`) what happened to the connection, if known.
+   */
+  close(http, reason, details = this.closeEvents.get(http)) {
+    if (this.closed.has(http)) return;
+    this.closed.add(http);
+    closeQuietly(http);
+    const attestation = http.attestation();
+    const event = { reason, attestation };
+    if (details && details.code !== "CONNECTION_CLOSED") Object.assign(event, details);
+    const listener = this.closeListeners.get(http);
+    this.closeListeners.delete(http);
+    listener?.(event);
+  }
+
+  /**
+   * Watch a new connection: drop it from the pool as soon as the server or the
+   * tunnel ends it, and once its attestation is older than `attestationMaxAgeMs`.
+   */
+  track(http) {
+    http.closed?.().then((event) => {
+      this.closeEvents.set(http, event);
+      if (this.idle.includes(http)) {
+        this.clearIdleTimer(http);
+        this.idle = this.idle.filter((h) => h !== http);
+        this.close(http, "remote", event);
+        this.wakeOne();
+      }
+    }, () => {});
+
+    if (Number.isFinite(this.attestationMaxAgeMs)) {
+      const timer = setTimeout(() => {
+        this.expired.add(http);
+        if (this.idle.includes(http)) {
+          this.clearIdleTimer(http);
+          this.idle = this.idle.filter((h) => h !== http);
+          this.close(http, "attestation-expired", ATTESTATION_EXPIRED);
+          this.wakeOne();
+        }
+      }, this.attestationMaxAgeMs);
+      timer?.unref?.();
+    }
+  }
+
+  get size() {
+    return this.idle.length + this.active.size + this.connecting;
+  }
+
+  /**
+   * Get a connection, reusing an idle one, opening a new one (via `connect`)
+   * if below the limit, or waiting for one to be released.
+   * @returns {Promise<{ http: AtlsHttp, reused: boolean }>}
+   */
+  async acquire(connect, signal) {
+    for (;;) {
+      while (this.idle.length > 0) {
+        const http = this.idle.pop();
+        this.clearIdleTimer(http);
+        // hyper flags the connection ready once its driver has processed the end
+        // of the previous body, which may take one more turn of the event loop.
+        if (http.isReady() || (await yieldToEventLoop(), http.isReady())) {
+          this.active.add(http);
+          return { http, reused: true };
+        }
+        this.close(http, "stale");
+      }
+
+      if (this.size < this.maxConnections) {
+        this.connecting++;
+        let http;
+        try {
+          http = await connect();
+        } catch (e) {
+          this.wakeOne();
+          throw e;
+        } finally {
+          this.connecting--;
+        }
+        this.track(http);
+        this.active.add(http);
+        return { http, reused: false };
+      }
+
+      await this.waitForSlot(signal);
+    }
+  }
+
+  waitForSlot(signal) {
+    return new Promise((resolve, reject) => {
+      const waiter = { resolve };
+      if (signal) {
+        const onAbort = () => {
+          this.waiters = this.waiters.filter((w) => w !== waiter);
+          reject(signal.reason);
+        };
+        signal.addEventListener("abort", onAbort, { once: true });
+        waiter.resolve = () => {
+          signal.removeEventListener("abort", onAbort);
+          resolve();
+        };
+      }
+      this.waiters.push(waiter);
+    });
+  }
+
+  wakeOne() {
+    const waiter = this.waiters.shift();
+    if (waiter) waiter.resolve();
+  }
+
+  /** Return a connection whose response body was fully consumed. */
+  release(http) {
+    if (!this.active.delete(http)) return;
+    if (this.expired.has(http) || this.closeEvents.has(http)) {
+      const expired = this.expired.has(http);
+      this.close(http, expired ? "attestation-expired" : "remote", expired ? ATTESTATION_EXPIRED : undefined);
+      this.wakeOne();
+      return;
+    }
+    this.idle.push(http);
+    if (Number.isFinite(this.idleTimeoutMs) && this.idleTimeoutMs > 0) {
+      // Close before an intermediary silently drops it; the next request reconnects
+      const timer = setTimeout(() => {
+        this.idleTimers.delete(http);
+        this.idle = this.idle.filter((h) => h !== http);
+        this.close(http, "idle");
+      }, this.idleTimeoutMs);
+      // Don't keep a Node.js process alive just to close an idle connection
+      timer?.unref?.();
+      this.idleTimers.set(http, timer);
+    }
+    this.wakeOne();
+  }
+
+  clearIdleTimer(http) {
+    clearTimeout(this.idleTimers.get(http));
+    this.idleTimers.delete(http);
+  }
+
+  /** Drop a connection that failed or whose body was not fully consumed. */
+  discard(http, reason = "error") {
+    this.clearIdleTimer(http);
+    this.active.delete(http);
+    this.idle = this.idle.filter((h) => h !== http);
+    this.close(http, reason);
+    this.wakeOne();
+  }
+
+  closeAll() {
+    for (const http of [...this.idle, ...this.active]) {
+      this.clearIdleTimer(http);
+      this.close(http, "closed");
+    }
+    this.idle = [];
+    this.active.clear();
+    // Let waiters retry: they will open fresh connections
+    while (this.waiters.length > 0) this.wakeOne();
+  }
+}
+
+/**
+ * Connection pools keyed by (wsUrl, serverName, sni, policy fingerprint) and
+ * pool settings.
+ * @type {Map<string, ConnectionPool>}
+ */
+const pools = new Map();
+
 /**
- * Connection cache keyed by (wsUrl, serverName).
- * Each entry holds an AtlsHttp instance that can be reused.
- * @type {Map<string, AtlsHttp>}
+ * The pool for `cacheKey` with these settings. Clients configured differently
+ * get separate pools, so none of them changes the limits another relies on.
  */
-const connectionCache = new Map();
+function getPool(cacheKey, maxConnections, idleTimeoutMs, attestationMaxAgeMs) {
+  const key = `${cacheKey}|${maxConnections}|${idleTimeoutMs}|${attestationMaxAgeMs}`;
+  let pool = pools.get(key);
+  if (!pool) {
+    pool = new ConnectionPool(maxConnections, idleTimeoutMs, attestationMaxAgeMs);
+    pools.set(key, pool);
+  }
+  return pool;
+}
+
+/**
+ * Wrap a response body so `onEnd(true)` runs once it is fully read, or
+ * `onEnd(false)` if reading fails or the consumer cancels it.
+ */
+function trackBody(body, onEnd) {
+  let finished = false;
+  const finish = (complete) => {
+    if (!finished) {
+      finished = true;
+      onEnd(complete);
+    }
+  };
+  if (!body) {
+    finish(true);
+    return body;
+  }
+
+  const reader = body.getReader();
+  return new ReadableStream({
+    async pull(controller) {
+      try {
+        const { value, done } = await reader.read();
+        if (done) {
+          controller.close();
+          finish(true);
+        } else {
+          controller.enqueue(value);
+        }
+      } catch (e) {
+        controller.error(e);
+        finish(false);
+      }
+    },
+    cancel(reason) {
+      finish(false);
+      return reader.cancel(reason);
+    }
+  });
+}
+
+/**
+ * Most recent accepted attestation for each (wsUrl, serverName), used to pin
+ * the app digest and to report `onAttestationChanged` when a new connection
+ * presents different evidence. Rejected connections never update it.
+ * @type {Map<string, Object>}
+ */
+const lastAttestations = new Map();
+
+/** Whether two attestations differ in measurement, TCB status or advisories. */
+function attestationDiffers(previous, current) {
+  return previous.measurementDigest !== current.measurementDigest
+    || previous.tcbStatus !== current.tcbStatus
+    || (previous.advisoryIds || []).join(",") !== (current.advisoryIds || []).join(",");
+}
+
+/**
+ * Raised when a new connection attests a different app digest (MRTD, RTMR0-2
+ * and compose hash) than the last accepted one (e.g. the app was redeployed,
+ * even on the same OS image). `previous` and `current` are the digests.
+ */
+export class MeasurementChangedError extends Error {
+  constructor(previous, current) {
+    super(`TEE measurement changed on reconnect: ${previous} -> ${current}`);
+    this.name = "MeasurementChangedError";
+    this.code = "MEASUREMENT_CHANGED";
+    this.previous = previous;
+    this.current = current;
+  }
+}
+
+/**
+ * Raised when a redirect cannot be followed: it leaves the attested origin
+ * (`REDIRECT_CROSS_ORIGIN`) or exceeds `maxRedirects` (`TOO_MANY_REDIRECTS`).
+ */
+export class RedirectError extends Error {
+  constructor(message, code, location) {
+    super(message);
+    this.name = "RedirectError";
+    this.code = code;
+    this.location = location;
+  }
+}
 
 /**
  * Close all cached connections.
  * Call this when you want to clean up resources.
  */
 export function closeAllConnections() {
-  for (const http of connectionCache.values()) {
-    try {
-      http.close();
-    } catch (e) {
-      // Ignore errors during cleanup
-    }
+  for (const pool of pools.values()) {
+    pool.closeAll();
   }
-  connectionCache.clear();
+  pools.clear();
+  lastAttestations.clear();
 }
 
 /**
- * Get the number of cached connections.
- * @returns {{ total: number }}
+ * Get connection pool statistics across all targets.
+ * @returns {{ total: number, idle: number, active: number, waiting: number }}
  */
 export function getConnectionPoolStats() {
-  return { total: connectionCache.size };
+  const stats = { total: 0, idle: 0, active: 0, waiting: 0 };
+  for (const pool of pools.values()) {
+    stats.total += pool.size;
+    stats.idle += pool.idle.length;
+    stats.active += pool.active.size;
+    stats.waiting += pool.waiters.length;
+  }
+  return stats;
 }
 
 // ============================================================================
-// URL Helpers
+// Cookies
 // ============================================================================
 
-function isLoopbackHostname(host) {
-  const value = host?.toLowerCase?.() || "";
-  return value === "localhost" || value === "127.0.0.1" || value === "::1" || value.startsWith("127.");
+/**
+ * Multi-label public suffixes a `Domain` attribute may not name. Single-label
+ * ones (`com`, `dev`, ...) are already refused for lacking an inner dot; pass
+ * `isPublicSuffix` to `CookieJar` for the full Public Suffix List.
+ */
+const PUBLIC_SUFFIXES = new Set([
+  "co.uk", "org.uk", "ac.uk", "gov.uk", "com.au", "net.au", "org.au", "co.jp", "ne.jp",
+  "or.jp", "com.br", "com.cn", "co.in", "co.nz", "co.za", "com.mx", "com.tr",
+  "github.io", "gitlab.io", "pages.dev", "workers.dev", "vercel.app", "netlify.app",
+  "herokuapp.com", "appspot.com", "web.app", "firebaseapp.com", "azurewebsites.net",
+  "cloudfront.net", "amazonaws.com"
+]);
+
+function isBuiltinPublicSuffix(domain) {
+  return PUBLIC_SUFFIXES.has(domain);
 }
 
-function normalizeProxyUrl(raw) {
-  if (!raw) return "";
-  const candidate = /^wss?:\/\//i.test(raw) ? raw : `ws://${raw.replace(/^\/+/, "")}`;
-  try {
-    const url = new URL(candidate);
-    const isProd = typeof process !== "undefined" && process?.env?.NODE_ENV === "production";
-    if (isProd && url.protocol !== "wss:" && !isLoopbackHostname(url.hostname)) {
-      throw new Error("aTLS proxy URL must use wss:// in production");
+/** Whether a URL hostname is an IPv4 or (bracketed) IPv6 literal. */
+function isIpLiteral(hostname) {
+  return hostname.startsWith("[") || /^\d+\.\d+\.\d+\.\d+$/.test(hostname);
+}
+
+/**
+ * Minimal cookie store (RFC 6265 subset) for session-based TEE apps.
+ *
+ * Captures `Set-Cookie` response headers and returns the matching `Cookie`
+ * header for later requests, honoring Domain, Path, Expires/Max-Age and Secure.
+ * A `Domain` naming a public suffix, a single label or (for IP addresses)
+ * anything but the host itself is refused, unless it is the response's host,
+ * in which case the cookie stays host-only.
+ * Pass `cookieJar: true` (or a shared `CookieJar`) to `createAtlsFetch`.
+ */
+export class CookieJar {
+  /**
+   * @param {Object} [options]
+   * @param {(domain: string) => boolean} [options.isPublicSuffix] - Whether `domain` is a public
+   *   suffix (e.g. backed by the Public Suffix List); a small built-in list by default
+   */
+  constructor({ isPublicSuffix = isBuiltinPublicSuffix } = {}) {
+    /** Cookies keyed by `domain|path|name`. @type {Map<string, Object>} */
+    this.cookies = new Map();
+    this.isPublicSuffix = isPublicSuffix;
+  }
+
+  /**
+   * Store cookies from `Set-Cookie` header values received for `url`.
+   * @param {string[]} setCookieHeaders
+   * @param {string | URL} url
+   */
+  setCookies(setCookieHeaders, url) {
+    const { hostname, pathname } = new URL(url);
+    for (const header of setCookieHeaders) {
+      const [pair, ...attributes] = header.split(";");
+      const eq = pair.indexOf("=");
+      if (eq <= 0) continue;
+
+      const cookie = {
+        name: pair.slice(0, eq).trim(),
+        value: pair.slice(eq + 1).trim(),
+        domain: hostname.toLowerCase(),
+        hostOnly: true,
+        path: pathname.slice(0, pathname.lastIndexOf("/")) || "/",
+        secure: false,
+        expires: null
+      };
+      for (const attribute of attributes) {
+        const [rawKey, ...rest] = attribute.split("=");
+        const key = rawKey.trim().toLowerCase();
+        const value = rest.join("=").trim();
+        if (key === "domain" && value) {
+          const domain = value.replace(/^\./, "").toLowerCase();
+          // Only registrable domains may be shared with subdomains: an IP
+          // address, a single label or a public suffix is host-only when it
+          // names the host itself, and rejected otherwise
+          const shareable = !isIpLiteral(hostname) && domain.includes(".")
+            && !this.isPublicSuffix(domain);
+          cookie.rejected = domain !== hostname
+            && (!shareable || !hostname.endsWith(`.${domain}`));
+          cookie.domain = domain;
+          cookie.hostOnly = !shareable;
+        } else if (key === "path" && value.startsWith("/")) {
+          cookie.path = value;
+        } else if (key === "secure") {
+          cookie.secure = true;
+        } else if (key === "max-age" && /^-?\d+$/.test(value)) {
+          cookie.expires = Date.now() + Number(value) * 1000;
+        } else if (key === "expires" && cookie.expires === null) {
+          const time = Date.parse(value);
+          if (!Number.isNaN(time)) cookie.expires = time;
+        }
+      }
+      if (cookie.rejected) continue;
+
+      const key = `${cookie.domain}|${cookie.path}|${cookie.name}`;
+      if (cookie.expires !== null && cookie.expires <= Date.now()) {
+        this.cookies.delete(key);
+      } else {
+        this.cookies.set(key, cookie);
+      }
     }
-    return url.toString();
-  } catch (error) {
-    if (error instanceof Error && /must use wss/i.test(error.message || "")) {
-      throw error;
+  }
+
+  /**
+   * `Cookie` header value for a request to `url`, or `""` if none match.
+   * @param {string | URL} url
+   */
+  getCookieHeader(url) {
+    const { hostname, pathname, protocol } = new URL(url);
+    const host = hostname.toLowerCase();
+    const now = Date.now();
+    const matching = [];
+    for (const [key, cookie] of this.cookies) {
+      if (cookie.expires !== null && cookie.expires <= now) {
+        this.cookies.delete(key);
+        continue;
+      }
+      const domainMatch = host === cookie.domain
+        || (!cookie.hostOnly && !isIpLiteral(host) && host.endsWith(`.${cookie.domain}`));
+      const pathMatch = pathname === cookie.path
+        || (pathname.startsWith(cookie.path)
+          && (cookie.path.endsWith("/") || pathname[cookie.path.length] === "/"));
+      if (domainMatch && pathMatch && (!cookie.secure || protocol === "https:")) {
+        matching.push(cookie);
+      }
     }
-    return candidate;
+    // Longer paths first, as browsers do
+    matching.sort((a, b) => b.path.length - a.path.length);
+    return matching.map((c) => `${c.name}=${c.value}`).join("; ");
+  }
+
+  /** Remove all cookies. */
+  clear() {
+    this.cookies.clear();
   }
 }
 
-function normalizeTarget(value) {
-  if (!value) return "";
-  return value.includes(":") ? value : `${value}:443`;
+// ============================================================================
+// Attestation cache
+// ============================================================================
+
+/** How long a cached attestation is shown before it counts as expired. */
+const DEFAULT_ATTESTATION_CACHE_TTL_MS = 10 * 60 * 1000;
+
+const ATTESTATION_CACHE_PREFIX = "atls:attestation:";
+
+/** JSON with object keys sorted, so equal policies serialize identically. */
+function canonicalJson(value) {
+  if (Array.isArray(value)) return `[${value.map(canonicalJson).join(",")}]`;
+  if (value && typeof value === "object") {
+    const keys = Object.keys(value).filter((k) => value[k] !== undefined).sort();
+    return `{${keys.map((k) => `${JSON.stringify(k)}:${canonicalJson(value[k])}`).join(",")}}`;
+  }
+  return JSON.stringify(value);
 }
 
-function buildProxyUrl(base, target) {
-  const url = new URL(normalizeProxyUrl(base));
-  if (target) {
-    url.searchParams.set("target", target);
+/**
+ * Short, stable fingerprint of a policy (64-bit FNV-1a of its canonical JSON).
+ * Only used as a cache key: a cached result is never trusted for a connection.
+ */
+export function policyFingerprint(policy) {
+  const text = canonicalJson(typeof policy === "string" ? JSON.parse(policy) : policy ?? null);
+  let hash = 0xcbf29ce484222325n;
+  for (const byte of new TextEncoder().encode(text)) {
+    hash = BigInt.asUintN(64, (hash ^ BigInt(byte)) * 0x100000001b3n);
   }
-  return url.toString();
+  return hash.toString(16).padStart(16, "0");
 }
 
-// ============================================================================
-// Main API
-// ============================================================================
+function defaultAttestationStorage() {
+  try {
+    return globalThis.sessionStorage ?? null;
+  } catch (_) {
+    // Access throws when storage is disabled (e.g. sandboxed iframes)
+    return null;
+  }
+}
+
+function attestationCacheKey(targetHost, policy) {
+  return `${ATTESTATION_CACHE_PREFIX}${normalizeTarget(targetHost)}|${policyFingerprint(policy)}`;
+}
+
+/** Record a successful attestation; storage failures (quota, privacy mode) are ignored. */
+function storeAttestation(storage, targetHost, policy, attestation, ttlMs) {
+  const now = Date.now();
+  const entry = {
+    measurement: attestation.measurement ?? null,
+    policyFingerprint: policyFingerprint(policy),
+    attestation,
+    cachedAt: now,
+    expiresAt: now + ttlMs
+  };
+  try {
+    storage.setItem(attestationCacheKey(targetHost, policy), JSON.stringify(entry));
+  } catch (_) {}
+}
 
 /**
- * Create a fetch-compatible function for attested TLS connections.
+ * Most recent successful attestation of `targetHost` under `policy`, as stored
+ * by a `createAtlsFetch` with `attestationCache` enabled (the default), or null
+ * if there is none or it expired.
  *
- * Connections are automatically pooled and reused for subsequent requests
- * to the same target. The `onAttestation` callback is called only once
- * when a new connection is established (not on reused connections).
+ * Lets a reloaded page show the last known trust state immediately, while the
+ * fresh handshake completes. It is display-only: requests are always sent on
+ * connections attested anew.
  *
  * @param {Object} options
- * @param {string} options.proxyUrl - WebSocket proxy URL (e.g., "ws://127.0.0.1:9000")
- * @param {string} options.targetHost - Target TEE server (e.g., "vllm.example.com:443")
- * @param {Object} options.policy - Verification policy
- * @param {string} [options.serverName] - TLS server name (defaults to hostname from targetHost)
- * @param {Object} [options.defaultHeaders] - Default headers to include in all requests
- * @param {Function} [options.onAttestation] - Callback when attestation is received (only on new connections)
- * @returns {Function} A fetch-compatible async function
+ * @param {string} options.targetHost - Target as passed to `createAtlsFetch`
+ * @param {Object | string} options.policy - Policy as passed to `createAtlsFetch`
+ * @param {Storage} [options.storage] - Defaults to `sessionStorage`
+ * @returns {{ measurement: string | null, policyFingerprint: string, attestation: Object,
+ *   cachedAt: number, expiresAt: number } | null}
  */
-export function createAtlsFetch(options) {
-  const { proxyUrl, targetHost, serverName, defaultHeaders, onAttestation, policy } = options;
-
-  if (!proxyUrl || !targetHost) {
-    throw new Error("proxyUrl and targetHost are required for aTLS fetch");
+export function getCachedAttestation({ targetHost, policy, storage = defaultAttestationStorage() }) {
+  if (!storage) return null;
+  const key = attestationCacheKey(targetHost, policy);
+  let entry;
+  try {
+    entry = JSON.parse(storage.getItem(key));
+  } catch (_) {
+    return null;
   }
+  if (!entry || typeof entry.expiresAt !== "number") return null;
+  if (entry.expiresAt <= Date.now()) {
+    try { storage.removeItem(key); } catch (_) {}
+    return null;
+  }
+  return entry;
+}
 
-  if (!policy) {
-    throw new Error("policy is required for aTLS verification. See docs for policy format.");
+/** Remove every cached attestation from `storage` (defaults to `sessionStorage`). */
+export function clearCachedAttestations(storage = defaultAttestationStorage()) {
+  if (!storage) return;
+  const keys = [];
+  for (let i = 0; i < storage.length; i++) {
+    const key = storage.key(i);
+    if (key?.startsWith(ATTESTATION_CACHE_PREFIX)) keys.push(key);
   }
+  for (const key of keys) storage.removeItem(key);
+}
 
-  const normalizedTarget = normalizeTarget(targetHost);
-  const sni = serverName || normalizedTarget.split(":")[0];
-  const host = normalizedTarget.split(":")[1] === "443"
-    ? normalizedTarget.split(":")[0]
-    : normalizedTarget;
-  const wsUrl = buildProxyUrl(proxyUrl, normalizedTarget);
-  const base = new URL(`https://${normalizedTarget}`);
+// ============================================================================
+// Responses
+// ============================================================================
 
-  // Cache key for this connection target
-  const cacheKey = `${wsUrl}|${sni}`;
+/**
+ * Parse a `text/event-stream` body incrementally (WHATWG Server-Sent Events).
+ *
+ * Yields `{ event, data, id, retry }` for each dispatched event. Breaking out
+ * of the loop cancels the body, which closes the underlying connection.
+ *
+ * @param {ReadableStream<Uint8Array>} body
+ * @returns {AsyncGenerator<{ event: string, data: string, id: string, retry?: number }>}
+ */
+export async function* parseEventStream(body) {
+  const reader = body.pipeThrough(new TextDecoderStream()).getReader();
+  let buffer = "";
+  let lastEventId = "";
+  let event = { type: "", data: "", retry: undefined };
+  let completed = false;
 
-  return async function atlsFetch(input, init = {}) {
-    await ensureWasm();
+  try {
+    for (;;) {
+      const { value, done } = await reader.read();
+      if (done) {
+        completed = true;
+        return;
+      }
+      buffer += value;
 
-    // Try to reuse an existing connection
-    let http = connectionCache.get(cacheKey);
-    let attestation;
+      // A trailing "\r" may be the first half of "\r\n": wait for more input
+      let match;
+      while ((match = /\r\n|\r(?!$)|\n/.exec(buffer))) {
+        const line = buffer.slice(0, match.index);
+        buffer = buffer.slice(match.index + match[0].length);
 
-    if (http && http.isReady()) {
-      // Reuse existing connection - no re-attestation needed
-      attestation = http.attestation();
-    } else {
-      // Need to create a new connection
-      // First, clean up any stale connection
-      if (http) {
-        try {
-          http.close();
-        } catch (e) {
-          // Ignore cleanup errors
+        if (line === "") {
+          if (event.data !== "") {
+            yield {
+              event: event.type || "message",
+              data: event.data.endsWith("\n") ? event.data.slice(0, -1) : event.data,
+              id: lastEventId,
+              ...(event.retry !== undefined && { retry: event.retry })
+            };
+          }
+          event = { type: "", data: "", retry: undefined };
+          continue;
         }
-        connectionCache.delete(cacheKey);
-      }
-
-      // Connect and perform aTLS handshake with policy
-      http = await AtlsHttp.connect(wsUrl, sni, policy);
-      connectionCache.set(cacheKey, http);
+        if (line.startsWith(":")) continue;
 
-      // Get attestation
-      attestation = http.attestation();
+        const colon = line.indexOf(":");
+        const field = colon < 0 ? line : line.slice(0, colon);
+        let fieldValue = colon < 0 ? "" : line.slice(colon + 1);
+        if (fieldValue.startsWith(" ")) fieldValue = fieldValue.slice(1);
 
-      // Call attestation callback ONLY for new connections
-      if (onAttestation && typeof onAttestation === "function") {
-        try {
-          await onAttestation(attestation);
-        } catch (e) {
-          console.error("[atls-fetch] onAttestation callback failed:", e);
-          // Clean up the connection on attestation callback failure
-          connectionCache.delete(cacheKey);
-          try { http.close(); } catch (_) {}
-          throw e;
-        }
+        if (field === "event") event.type = fieldValue;
+        else if (field === "data") event.data += `${fieldValue}\n`;
+        else if (field === "id" && !fieldValue.includes("\0")) lastEventId = fieldValue;
+        else if (field === "retry" && /^\d+$/.test(fieldValue)) event.retry = Number(fieldValue);
       }
     }
+  } finally {
+    if (!completed) await reader.cancel().catch(() => {});
+  }
+}
 
-    // Build request from input
-    const request = new Request(input, init);
-    const url = new URL(request.url, base);
-    const path = `${url.pathname}${url.search}`;
+/** Raised when a body read with a size guard exceeds its limit. */
+export class BodyTooLargeError extends Error {
+  constructor(limit) {
+    super(`response body exceeds ${limit} bytes`);
+    this.name = "BodyTooLargeError";
+    this.code = "BODY_TOO_LARGE";
+    this.limit = limit;
+  }
+}
 
-    // Merge headers (default + request headers)
-    const mergedHeaders = [];
-    if (defaultHeaders) {
-      for (const [name, value] of Object.entries(defaultHeaders)) {
-        mergedHeaders.push([name, value]);
-      }
-    }
-    request.headers.forEach((value, name) => {
-      // Override default headers with request headers
-      const idx = mergedHeaders.findIndex(([n]) => n.toLowerCase() === name.toLowerCase());
-      if (idx >= 0) {
-        mergedHeaders[idx] = [name, value];
-      } else {
-        mergedHeaders.push([name, value]);
-      }
-    });
+/**
+ * Read a whole body into one Uint8Array, failing fast once `maxSize` is exceeded
+ * (checked against Content-Length first, then while reading).
+ */
+async function readBody(response, maxSize) {
+  if (response.bodyUsed) throw new TypeError("body has already been consumed");
+  if (!response.body) return new Uint8Array(0);
 
-    // Get body as Uint8Array
-    let body = null;
-    if (request.body) {
-      body = new Uint8Array(await request.arrayBuffer());
-    }
+  const declared = Number(response.headers.get("content-length"));
+  if (Number.isFinite(maxSize) && declared > maxSize) {
+    await response.body.cancel().catch(() => {});
+    throw new BodyTooLargeError(maxSize);
+  }
 
-    // Perform HTTP request via WASM (handles chunked encoding)
-    let result;
-    try {
-      result = await http.fetch(
-        request.method,
-        path,
-        host,
-        mergedHeaders,
-        body
-      );
-    } catch (e) {
-      // On request failure, remove the connection from cache
-      connectionCache.delete(cacheKey);
-      try { http.close(); } catch (_) {}
-      throw e;
-    }
+  const expected = response.headers.has("content-length") ? declared : NaN;
+  return drainReader(response.body.getReader(), maxSize, expected);
+}
 
-    // Convert headers object to Headers instance
-    const responseHeaders = new Headers();
-    for (const [name, value] of Object.entries(result.headers)) {
-      responseHeaders.append(name, value);
+/** Largest declared body length allocated up front; longer bodies are collected in chunks. */
+const MAX_PREALLOCATED_BODY = 64 * 1024 * 1024;
+
+/**
+ * Read everything left in `reader` into one Uint8Array. With a known `expected`
+ * length the output is allocated once and filled in place.
+ */
+async function drainReader(reader, maxSize, expected = NaN, head = null) {
+  const chunks = head && head.byteLength > 0 ? [head] : [];
+  let total = chunks.length ? head.byteLength : 0;
+  let out = Number.isSafeInteger(expected) && expected >= total && expected <= MAX_PREALLOCATED_BODY
+    ? new Uint8Array(expected)
+    : null;
+  if (out && head) out.set(head);
+
+  for (;;) {
+    const { value, done } = await reader.read();
+    if (done) break;
+    if (total + value.byteLength > maxSize) {
+      await reader.cancel().catch(() => {});
+      throw new BodyTooLargeError(maxSize);
+    }
+    if (out && total + value.byteLength > out.length) {
+      // More than declared: fall back to collecting chunks
+      chunks.splice(0, chunks.length, out.subarray(0, total));
+      out = null;
+    }
+    if (out) {
+      out.set(value, total);
+    } else {
+      chunks.push(value);
     }
+    total += value.byteLength;
+  }
 
-    // Create Response object with body stream from WASM
-    const response = new Response(result.body, {
-      status: result.status,
-      statusText: result.statusText,
-      headers: responseHeaders
-    });
+  if (out) return total === out.length ? out : out.subarray(0, total);
+  if (chunks.length === 1) return chunks[0];
+  const joined = new Uint8Array(total);
+  let offset = 0;
+  for (const chunk of chunks) {
+    joined.set(chunk, offset);
+    offset += chunk.byteLength;
+  }
+  return joined;
+}
 
-    // Attach attestation as non-enumerable property
-    Object.defineProperty(response, "attestation", {
-      value: attestation,
-      enumerable: false,
-      configurable: false,
-      writable: false
-    });
+/**
+ * Attach the attestation, trailers and body helpers to a Response.
+ *
+ * `arrayBuffer()`, `text()`, `json()` and `blob()` accept an optional
+ * `{ maxSize }` (bytes), defaulting to `defaultMaxSize`; `readAll(maxBytes)`
+ * returns the body as a Uint8Array without the ArrayBuffer copy.
+ *
+ * `trailers()` resolves with the trailer fields (a `Headers`) once the body has
+ * been read, or with null if it did not complete. `trailerEntries` is a promise
+ * of `[name, value]` pairs (or null).
+ */
+function decorateResponse(response, attestation, defaultMaxSize = Infinity, trailerEntries = null) {
+  // Attach attestation as non-enumerable property
+  Object.defineProperty(response, "attestation", {
+    value: attestation,
+    enumerable: false,
+    configurable: false,
+    writable: false
+  });
+  Object.defineProperty(response, "eventStream", {
+    value() {
+      if (!this.body) throw new TypeError("response has no body");
+      return parseEventStream(this.body);
+    },
+    enumerable: false
+  });
+  const trailers = Promise.resolve(trailerEntries).then((entries) => entries && new Headers(entries));
+  Object.defineProperty(response, "trailers", {
+    value: () => trailers,
+    enumerable: false
+  });
 
-    return response;
+  const read = (options) => readBody(response, options?.maxSize ?? defaultMaxSize);
+  const helpers = {
+    async readAll(maxBytes) {
+      return read({ maxSize: maxBytes });
+    },
+    async arrayBuffer(options) {
+      const bytes = await read(options);
+      return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.byteLength);
+    },
+    async text(options) {
+      return new TextDecoder().decode(await read(options));
+    },
+    async json(options) {
+      return JSON.parse(await helpers.text(options));
+    },
+    async blob(options) {
+      return new Blob([await read(options)], { type: response.headers.get("content-type") || "" });
+    }
   };
+  for (const [name, value] of Object.entries(helpers)) {
+    Object.defineProperty(response, name, { value, enumerable: false });
+  }
+  return response;
+}
+
+// ============================================================================
+// Multipart
+// ============================================================================
+
+/** Escape a field name or filename for a Content-Disposition header (as browsers do). */
+function escapeDispositionValue(value) {
+  return String(value).replace(/\r\n|\r|\n/g, "%0D%0A").replace(/"/g, "%22");
+}
+
+/** RFC 2046 boundary: 1-70 bchars, not ending with a space. */
+const BOUNDARY_PATTERN = /^[0-9A-Za-z'()+_,\-./:=? ]{0,69}[0-9A-Za-z'()+_,\-./:=?]$/;
+
+/** RFC 7230 token characters, which need no quoting in a header parameter. */
+const TOKEN_PATTERN = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+
+/**
+ * Build a `multipart/form-data` body for upload endpoints.
+ *
+ * Values may be strings, Blobs/Files, or `{ value, filename, contentType }`
+ * objects. The result is a Blob, so file contents are not copied into JS memory
+ * until the request is sent over the attested connection.
+ *
+ * @example
+ * ```js
+ * const { body, contentType } = encodeMultipart({
+ *   purpose: "fine-tune",
+ *   file: { value: fileInput.files[0], filename: "data.jsonl" }
+ * })
+ * await fetch("/v1/files", { method: "POST", body, headers: { "Content-Type": contentType } })
+ * ```
+ *
+ * @param {FormData | Object | Array<[string, any]>} fields - Fields in order
+ * @param {Object} [options]
+ * @param {string} [options.boundary] - Boundary to use (random by default; 1-70 RFC 2046 characters)
+ * @returns {{ body: Blob, contentType: string, boundary: string }}
+ * @throws {TypeError} If the boundary is invalid or a content type contains CR or LF
+ */
+export function encodeMultipart(fields, { boundary } = {}) {
+  if (boundary === undefined) {
+    const random = Array.from(crypto.getRandomValues(new Uint8Array(16)), (b) => b.toString(16).padStart(2, "0"));
+    boundary = `----AtlsFormBoundary${random.join("")}`;
+  } else if (typeof boundary !== "string" || !BOUNDARY_PATTERN.test(boundary)) {
+    throw new TypeError(`invalid multipart boundary ${JSON.stringify(boundary)}: expected 1-70 RFC 2046 characters`);
+  }
+  const entries = fields instanceof FormData || Array.isArray(fields)
+    ? [...fields]
+    : Object.entries(fields);
+
+  const parts = [];
+  for (const [name, raw] of entries) {
+    let value = raw;
+    let filename;
+    let contentType;
+    if (raw && typeof raw === "object" && !(raw instanceof Blob)) {
+      ({ value, filename, contentType } = raw);
+    }
+
+    let disposition = `form-data; name="${escapeDispositionValue(name)}"`;
+    if (value instanceof Blob) {
+      filename ??= value.name || "blob";
+      contentType ??= value.type || "application/octet-stream";
+    }
+    // A line break would let the type inject headers into the part
+    if (contentType && /[\r\n]/.test(contentType)) {
+      throw new TypeError(`invalid content type for multipart field ${JSON.stringify(name)}`);
+    }
+    if (filename !== undefined) {
+      disposition += `; filename="${escapeDispositionValue(filename)}"`;
+    }
+
+    let head = `--${boundary}\r\nContent-Disposition: ${disposition}\r\n`;
+    if (contentType) head += `Content-Type: ${contentType}\r\n`;
+    parts.push(`${head}\r\n`, value instanceof Blob ? value : String(value), "\r\n");
+  }
+  parts.push(`--${boundary}--\r\n`);
+
+  const parameter = TOKEN_PATTERN.test(boundary) ? boundary : `"${boundary}"`;
+  const contentType = `multipart/form-data; boundary=${parameter}`;
+  return { body: new Blob(parts, { type: contentType }), contentType, boundary };
+}
+
+// ============================================================================
+// URL Helpers
+// ============================================================================
+
+/** `host[:port]` authority: reg-name or IPv4, or a bracketed IPv6 literal, and an optional port. */
+const AUTHORITY_PATTERN = /^(?:\[[0-9A-Fa-f:.]+\]|[A-Za-z0-9\-._~!$&'()*+,;=%]+)(?::(\d{1,5}))?$/;
+
+/** Return `value` if it is a valid Host authority, else throw a TypeError. */
+function validateAuthority(value) {
+  const match = typeof value === "string" ? AUTHORITY_PATTERN.exec(value) : null;
+  if (!match || (match[1] !== undefined && Number(match[1]) > 65535)) {
+    throw new TypeError(`invalid host ${JSON.stringify(value)}: expected host[:port]`);
+  }
+  return value;
+}
+
+function isLoopbackHostname(host) {
+  const value = host?.toLowerCase?.() || "";
+  return value === "localhost" || value === "127.0.0.1" || value === "::1" || value.startsWith("127.");
+}
+
+function normalizeProxyUrl(raw) {
+  if (!raw) return "";
+  const candidate = /^wss?:\/\//i.test(raw) ? raw : `ws://${raw.replace(/^\/+/, "")}`;
+  try {
+    const url = new URL(candidate);
+    const isProd = typeof process !== "undefined" && process?.env?.NODE_ENV === "production";
+    if (isProd && url.protocol !== "wss:" && !isLoopbackHostname(url.hostname)) {
+      throw new Error("aTLS proxy URL must use wss:// in production");
+    }
+    return url.toString();
+  } catch (error) {
+    if (error instanceof Error && /must use wss/i.test(error.message || "")) {
+      throw error;
+    }
+    return candidate;
+  }
+}
+
+function normalizeTarget(value) {
+  if (!value) return "";
+  return value.includes(":") ? value : `${value}:443`;
+}
+
+function buildWebTransportUrl(base, target) {
+  const url = new URL(base);
+  if (url.protocol !== "https:") {
+    throw new Error("webTransportUrl must use https://");
+  }
+  url.searchParams.set("target", target);
+  return url.toString();
+}
+
+function buildProxyUrl(base, target, multiplex = false) {
+  const url = new URL(normalizeProxyUrl(base));
+  if (target) {
+    url.searchParams.set("target", target);
+  }
+  if (multiplex) {
+    url.searchParams.set("mux", "1");
+  }
+  return url.toString();
+}
+
+// ============================================================================
+// Main API
+// ============================================================================
+
+/** Methods retried by default: those the HTTP spec defines as idempotent. */
+const IDEMPOTENT_METHODS = ["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"];
+
+const DEFAULT_RETRY = {
+  retries: 0,
+  methods: IDEMPOTENT_METHODS,
+  baseDelayMs: 100,
+  maxDelayMs: 2000
+};
+
+/** Codes of errors from a connection that ended under a request (see `AtlsHttp.closed()`). */
+const CONNECTION_LOST_CODES = new Set(["CONNECTION_CLOSED", "TUNNEL_CLOSED", "TLS_ALERT"]);
+
+/**
+ * Whether a failed request may succeed on a fresh connection: tunnel and
+ * transport errors are retried, while aborts, attestation failures (errors with
+ * a `code`) and redirect/measurement errors are not.
+ */
+function isTransientError(error) {
+  if (typeof error === "string") return true;
+  return error instanceof Error && error.name === "Error"
+    && (error.code === undefined || CONNECTION_LOST_CODES.has(error.code));
+}
+
+/** Sleep for `ms`, rejecting early with `signal.reason` if aborted. */
+function delay(ms, signal) {
+  return new Promise((resolve, reject) => {
+    const onAbort = () => {
+      clearTimeout(timer);
+      reject(signal.reason);
+    };
+    const timer = setTimeout(() => {
+      signal.removeEventListener("abort", onAbort);
+      resolve();
+    }, ms);
+    signal.addEventListener("abort", onAbort, { once: true });
+  });
+}
+
+/** Ordered interceptor registry (`use` returns a function removing the interceptor). */
+class InterceptorList {
+  constructor(initial = []) {
+    this.list = [...initial];
+  }
+
+  use(interceptor) {
+    if (typeof interceptor !== "function") {
+      throw new TypeError("interceptor must be a function");
+    }
+    this.list.push(interceptor);
+    return () => {
+      const index = this.list.indexOf(interceptor);
+      if (index >= 0) this.list.splice(index, 1);
+    };
+  }
+
+  clear() {
+    this.list = [];
+  }
+
+  get size() {
+    return this.list.length;
+  }
+
+  [Symbol.iterator]() {
+    return [...this.list][Symbol.iterator]();
+  }
+}
+
+/** Same default as the Fetch standard. */
+const DEFAULT_MAX_REDIRECTS = 20;
+
+const REDIRECT_STATUSES = new Set([301, 302, 303, 307, 308]);
+
+const NULL_BODY_STATUSES = new Set([101, 204, 205, 304]);
+
+/** Request headers dropped when a redirect turns the request into a GET. */
+const BODY_HEADERS = new Set(["content-type", "content-length", "content-encoding", "content-language", "trailer", "expect"]);
+
+/** Encode one chunk of a streamed request body as bytes. */
+function toBytes(chunk) {
+  if (chunk instanceof Uint8Array) return chunk;
+  if (typeof chunk === "string") return new TextEncoder().encode(chunk);
+  if (chunk instanceof ArrayBuffer) return new Uint8Array(chunk);
+  if (ArrayBuffer.isView(chunk)) return new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength);
+  throw new TypeError("streamed request body chunks must be strings, ArrayBuffers or typed arrays");
+}
+
+/**
+ * Return a request body of unknown length as a ReadableStream, or null for
+ * bodies `Request` can buffer itself. Accepts a ReadableStream or any async
+ * iterable (e.g. an async generator) of strings/bytes.
+ */
+function toRequestStream(body) {
+  if (typeof ReadableStream !== "undefined" && body instanceof ReadableStream) return body;
+  if (body == null || typeof body[Symbol.asyncIterator] !== "function") return null;
+  let iterator;
+  return new ReadableStream({
+    start() {
+      iterator = body[Symbol.asyncIterator]();
+    },
+    async pull(controller) {
+      const { value, done } = await iterator.next();
+      if (done) {
+        controller.close();
+      } else {
+        controller.enqueue(toBytes(value));
+      }
+    },
+    async cancel(reason) {
+      await iterator.return?.(reason);
+    }
+  });
+}
+
+/** `init.trailers` as `[name, value]` pairs, or a function producing them once the body is sent. */
+function normalizeTrailers(trailers) {
+  if (trailers == null) return null;
+  if (typeof trailers === "function") {
+    return async () => [...new Headers((await trailers()) ?? [])];
+  }
+  return [...new Headers(trailers)];
+}
+
+/**
+ * Create a fetch-compatible function for attested TLS connections.
+ *
+ * Connections are automatically pooled and reused for subsequent requests
+ * to the same target. Overlapping requests run in parallel on up to
+ * `maxConnections` attested connections; further requests wait for one to
+ * become free (i.e. for a previous response body to be fully read). The
+ * `onAttestation` callback is called only once when a new connection is
+ * established (not on reused connections).
+ *
+ * Redirects are followed (per `init.redirect`) only within the attested origin:
+ * the connection and its attestation are reused, and a redirect to any other
+ * origin fails with a `RedirectError` instead of being followed unattested.
+ *
+ * Interceptors extend the client without forking it. Request interceptors run
+ * on each outgoing request (including redirects and retries) once its
+ * connection is known, and receive `{ method, url, headers, body, attestation }`
+ * to mutate or replace, e.g. to attach a token bound to the attestation.
+ * Response interceptors receive the final Response and may return a new one.
+ * Register them with `fetch.interceptors.request.use(fn)` (returns a remover).
+ *
+ * Transient failures (tunnel drops, transport errors) can be retried with
+ * jittered exponential backoff: each attempt reconnects, re-attests and replays
+ * the request. Only idempotent methods are retried unless `retry.methods` says
+ * otherwise; `response.attempts` reports how many attempts were made.
+ *
+ * The lifecycle callbacks are notifications for UIs (errors they throw are
+ * logged, not propagated): `onAttested` fires for every newly attested
+ * connection, `onAttestationChanged` when a new connection presents different
+ * evidence (measurement digest, TCB status or advisories) than the previous one to the
+ * same target, and `onConnectionClosed` when a pooled connection this fetch
+ * opened is closed. `fetch.dispose()` stops the close notifications of a fetch
+ * that is no longer used; its pooled connections stay available to others.
+ *
+ * @param {Object} options
+ * @param {string} options.proxyUrl - WebSocket proxy URL (e.g., "ws://127.0.0.1:9000")
+ * @param {string} options.targetHost - Target TEE server (e.g., "vllm.example.com:443")
+ * @param {Object} options.policy - Verification policy
+ * @param {string} [options.serverName] - Hostname the attestation is verified for, also sent as SNI
+ *   (defaults to hostname from targetHost)
+ * @param {string} [options.sni] - Server name sent in the TLS ClientHello instead of `serverName`
+ *   (e.g. for an ingress that routes on SNI)
+ * @param {string[]} [options.alpn=["http/1.1"]] - ALPN protocols to offer; must include "http/1.1"
+ * @param {Object} [options.defaultHeaders] - Default headers to include in all requests
+ * @param {Function} [options.onAttestation] - Callback when attestation is received (only on new connections)
+ * @param {number} [options.maxConnections=6] - Maximum concurrent connections to the target
+ * @param {number} [options.idleTimeoutMs=50000] - Close pooled connections idle for this long
+ *   (`Infinity` keeps them until the server or an intermediary closes them)
+ * @param {number} [options.attestationMaxAgeMs=Infinity] - Retire connections whose attestation is
+ *   older than this, so the next request re-attests (reported as `attestation-expired`)
+ * @param {number} [options.keepAliveIntervalMs=20000] - WebSocket keepalive interval (0 disables)
+ * @param {boolean} [options.allowMeasurementChange=false] - Accept a different `appDigest` than
+ *   the last accepted connection instead of failing with MEASUREMENT_CHANGED
+ * @param {Function} [options.onAttested] - `(attestation)` after each new connection is attested
+ * @param {Function} [options.onAttestationChanged] - `(current, previous)` when evidence differs from the previous connection
+ * @param {Function} [options.onConnectionClosed] - `({ reason, code, message, attestation })` when a pooled
+ *   connection this fetch opened is closed; `code` is set when the tunnel, a TLS alert or attestation expiry ended it
+ * @param {boolean | CookieJar} [options.cookieJar] - Store cookies across requests (`true` for a private jar)
+ * @param {number} [options.maxRedirects=20] - Maximum redirects followed per request
+ * @param {boolean} [options.rewritePostToGet=true] - Turn POST into GET on 301/302 (as browsers do)
+ * @param {number} [options.maxBodySize] - Default size limit (bytes) for `json()`, `text()`, `arrayBuffer()` and `blob()`
+ * @param {number} [options.maxHeaderSize] - Maximum response header size in bytes (minimum 8192;
+ *   hyper's ~400 KiB default otherwise)
+ * @param {number} [options.maxResponseBodySize] - Hard cap (bytes) on every response body, including
+ *   streamed ones; the body stream errors with `code: "BODY_TOO_LARGE"` past it
+ * @param {number} [options.expectContinue] - Send `Expect: 100-continue` for bodies of at least this
+ *   many bytes (and all streamed bodies), so a refused upload is never sent; off by default
+ * @param {number} [options.expectContinueTimeoutMs=1000] - How long to wait for `100 Continue`
+ *   before sending the body anyway
+ * @param {Object} [options.interceptors] - Initial `{ request: [], response: [] }` interceptors
+ * @param {boolean} [options.multiplex=false] - Carry all connections to the proxy over one shared
+ *   WebSocket (`mux=1`); needs a proxy with multiplexing support
+ * @param {string} [options.webTransportUrl] - `https://` WebTransport relay, preferred over the
+ *   WebSocket proxy in browsers that support WebTransport
+ * @param {boolean | Object} [options.attestationCache=true] - Store successful attestations for
+ *   `getCachedAttestation()`; `{ ttlMs, storage }` overrides the 10 minute expiry and `sessionStorage`
+ * @param {Object} [options.retry] - Default retry policy `{ retries, methods, baseDelayMs, maxDelayMs }`
+ *   (overridable per request with `init.retry`, or `init.retry: false`)
+ * @returns {Function} A fetch-compatible async function, with an `interceptors` registry,
+ *   a `diagnostics()` method (TLS parameters, timings and collateral details of the connection
+ *   that served the last response, or null) and a `cookieJar` property when enabled
+ */
+export function createAtlsFetch(options) {
+  const {
+    proxyUrl,
+    targetHost,
+    serverName,
+    sni,
+    alpn,
+    defaultHeaders,
+    onAttestation,
+    onAttested,
+    onAttestationChanged,
+    onConnectionClosed,
+    cookieJar,
+    maxRedirects = DEFAULT_MAX_REDIRECTS,
+    rewritePostToGet = true,
+    maxBodySize = Infinity,
+    maxHeaderSize,
+    maxResponseBodySize,
+    expectContinue,
+    expectContinueTimeoutMs,
+    interceptors: initialInterceptors = {},
+    retry: defaultRetry,
+    webTransportUrl,
+    multiplex = false,
+    policy,
+    maxConnections = DEFAULT_MAX_CONNECTIONS,
+    idleTimeoutMs = DEFAULT_IDLE_TIMEOUT_MS,
+    attestationMaxAgeMs = Infinity,
+    attestationCache = true,
+    keepAliveIntervalMs = DEFAULT_KEEPALIVE_INTERVAL_MS,
+    allowMeasurementChange = false
+  } = options;
+
+  if (!Number.isInteger(maxConnections) || maxConnections < 1) {
+    throw new Error("maxConnections must be a positive integer");
+  }
+
+  if (!Number.isInteger(maxRedirects) || maxRedirects < 0) {
+    throw new Error("maxRedirects must be a non-negative integer");
+  }
+
+  if (!proxyUrl || !targetHost) {
+    throw new Error("proxyUrl and targetHost are required for aTLS fetch");
+  }
+
+  if (!policy) {
+    throw new Error("policy is required for aTLS verification. See docs for policy format.");
+  }
+
+  if (expectContinue !== undefined && !(Number.isFinite(expectContinue) && expectContinue >= 0)) {
+    throw new Error("expectContinue must be a non-negative byte count");
+  }
+
+  const httpOptions = {
+    keepAliveIntervalMs,
+    maxHeaderSize,
+    maxBodySize: maxResponseBodySize,
+    expectContinueTimeoutMs,
+    alpn,
+    sni
+  };
+
+  const normalizedTarget = normalizeTarget(targetHost);
+  const verifiedName = serverName || normalizedTarget.split(":")[0];
+  const host = normalizedTarget.split(":")[1] === "443"
+    ? normalizedTarget.split(":")[0]
+    : normalizedTarget;
+  const wsUrl = buildProxyUrl(proxyUrl, normalizedTarget, multiplex);
+  const wtUrl = webTransportUrl ? buildWebTransportUrl(webTransportUrl, normalizedTarget) : null;
+  const base = new URL(`https://${normalizedTarget}`);
+
+  // Cache key for this connection target. Connections attested under one policy
+  // must never serve a client with another, possibly stricter, policy.
+  const endpoint = sni ? `${wsUrl}|${verifiedName}|${sni}` : `${wsUrl}|${verifiedName}`;
+  const cacheKey = `${endpoint}|${policyFingerprint(policy)}`;
+
+  const jar = cookieJar === true ? new CookieJar() : cookieJar || null;
+
+  const interceptors = {
+    request: new InterceptorList(initialInterceptors.request),
+    response: new InterceptorList(initialInterceptors.response)
+  };
+
+  /** Diagnostics of the connection that served the most recent response. */
+  let lastDiagnostics = null;
+
+  const cacheOptions = attestationCache === true ? {} : attestationCache || null;
+  const cacheStorage = cacheOptions && (cacheOptions.storage ?? defaultAttestationStorage());
+  const cacheTtlMs = cacheOptions?.ttlMs ?? DEFAULT_ATTESTATION_CACHE_TTL_MS;
+
+  /**
+   * Connect over WebTransport when configured and available, falling back to
+   * the WebSocket proxy if the WebTransport tunnel cannot be established.
+   * Attestation failures (errors with a `code`) are not retried over WebSocket.
+   */
+  async function openTunnel() {
+    if (wtUrl && typeof WebTransport !== "undefined") {
+      try {
+        return await AtlsHttp.connect(wtUrl, verifiedName, policy, httpOptions);
+      } catch (e) {
+        if (e?.code) throw e;
+        console.warn("[atls-fetch] WebTransport tunnel failed, falling back to WebSocket:", e);
+      }
+    }
+    return AtlsHttp.connect(wsUrl, verifiedName, policy, httpOptions);
+  }
+
+  /**
+   * Open a new connection and attest it.
+   * Fails with a MEASUREMENT_CHANGED error if the app digest differs from the
+   * last accepted connection's, unless `allowMeasurementChange` is set. Other
+   * instances of the same app (pool growth, idle reconnects, retries) share the
+   * app digest. Only an accepted attestation becomes the new reference.
+   */
+  async function connect() {
+    const http = await openTunnel();
+    const attestation = http.attestation();
+
+    const last = lastAttestations.get(cacheKey);
+    if (last && !allowMeasurementChange) {
+      const previous = last.appDigest ?? null;
+      const digest = attestation.appDigest ?? null;
+      if (previous !== digest) {
+        try { http.close(); } catch (_) {}
+        throw new MeasurementChangedError(previous, digest);
+      }
+    }
+
+    // Call attestation callback ONLY for new connections
+    if (onAttestation && typeof onAttestation === "function") {
+      try {
+        await onAttestation(attestation);
+      } catch (e) {
+        console.error("[atls-fetch] onAttestation callback failed:", e);
+        // Clean up the connection on attestation callback failure
+        try { http.close(); } catch (_) {}
+        throw e;
+      }
+    }
+
+    lastAttestations.set(cacheKey, attestation);
+    if (last && attestationDiffers(last, attestation)) {
+      notify("onAttestationChanged", onAttestationChanged, attestation, last);
+    }
+    if (cacheStorage) {
+      storeAttestation(cacheStorage, normalizedTarget, policy, attestation, cacheTtlMs);
+    }
+    notify("onAttested", onAttested, attestation);
+    return http;
+  }
+
+  /**
+   * Connections this fetch opened and still watches for `onConnectionClosed`,
+   * with their pool. Other clients sharing the pool are not told about them.
+   * @type {Map<AtlsHttp, ConnectionPool>}
+   */
+  const watched = new Map();
+  let disposed = false;
+
+  /** Report the closing of `http`, which this fetch just opened, to `onConnectionClosed`. */
+  function watchClose(pool, http) {
+    if (typeof onConnectionClosed === "function" && !disposed) {
+      watched.set(http, pool);
+      pool.closeListeners.set(http, (event) => {
+        watched.delete(http);
+        notify("onConnectionClosed", onConnectionClosed, event);
+      });
+    }
+    return http;
+  }
+
+  /**
+   * Send one request on a pooled connection and wrap the result in a Response.
+   * `headers` are the merged request headers, without cookies.
+   */
+  async function send(method, url, headers, body, signal, trailers, authority = host) {
+    const requestHeaders = [...headers];
+
+    // Attach stored cookies unless the caller set the header explicitly
+    if (jar && !requestHeaders.some(([n]) => n.toLowerCase() === "cookie")) {
+      const cookie = jar.getCookieHeader(url);
+      if (cookie) requestHeaders.push(["Cookie", cookie]);
+    }
+
+    // Reuse an idle connection, open a new one, or wait for one to free up
+    const pool = getPool(cacheKey, maxConnections, idleTimeoutMs, attestationMaxAgeMs);
+    const open = () => connect().then((http) => watchClose(pool, http));
+    let { http, reused } = await pool.acquire(open, signal);
+
+    // Run request interceptors against the connection's attestation, then
+    // perform the HTTP request via WASM (handles chunked encoding).
+    // Aborting closes the connection and rejects with signal.reason (AbortError by default).
+    const prepare = async (http) => {
+      let outgoing = { method, url: new URL(url), headers: new Headers(requestHeaders), body };
+      if (interceptors.request.size > 0) {
+        outgoing.attestation = http.attestation();
+        try {
+          for (const interceptor of interceptors.request) {
+            outgoing = (await interceptor(outgoing)) ?? outgoing;
+          }
+          if (new URL(outgoing.url).origin !== base.origin) {
+            throw new TypeError(`request interceptor moved the request off the attested origin ${base.origin}`);
+          }
+        } catch (e) {
+          // Nothing was sent: the connection is still usable
+          pool.release(http);
+          throw e;
+        }
+      }
+      const target = new URL(outgoing.url);
+      return [
+        outgoing.method,
+        `${target.pathname}${target.search}`,
+        authority,
+        [...new Headers(outgoing.headers)],
+        outgoing.body,
+        signal,
+        trailers
+      ];
+    };
+
+    let result;
+    let args = await prepare(http);
+    try {
+      result = await http.fetch(...args);
+    } catch (e) {
+      // On request failure, drop the connection from the pool
+      pool.discard(http);
+
+      // A kept-alive connection may have been closed by the server in the meantime
+      // (write error, EOF before headers). Reconnect, re-attest and retry once,
+      // unless the body was a stream, which cannot be sent twice.
+      if (!reused || signal.aborted || body instanceof ReadableStream) {
+        throw e;
+      }
+      ({ http, reused } = await pool.acquire(open, signal));
+      args = await prepare(http);
+      try {
+        result = await http.fetch(...args);
+      } catch (retryError) {
+        pool.discard(http);
+        throw retryError;
+      }
+    }
+    const attestation = http.attestation();
+    lastDiagnostics = http.diagnostics();
+
+    // Convert header entries to a Headers instance (keeps repeated Set-Cookie lines)
+    const responseHeaders = new Headers();
+    for (const [name, value] of result.rawHeaders) {
+      responseHeaders.append(name, value);
+    }
+
+    if (jar) {
+      const setCookies = result.rawHeaders
+        .filter(([name]) => name === "set-cookie")
+        .map(([, value]) => value);
+      jar.setCookies(setCookies, url);
+    }
+
+    // Create Response object with body stream from WASM.
+    // The connection goes back to the pool once the body has been fully read.
+    const pooledHttp = http;
+    const responseBody = trackBody(result.body, (complete) => {
+      if (complete) {
+        pool.release(pooledHttp);
+      } else {
+        pool.discard(pooledHttp, "body-incomplete");
+      }
+    });
+    // Responses like 204 and 304 must be constructed without a body
+    const nullBody = NULL_BODY_STATUSES.has(result.status);
+    if (nullBody) {
+      // Read the (empty) body so the connection is released to the pool
+      const reader = responseBody.getReader();
+      (async () => {
+        while (!(await reader.read()).done);
+      })().catch(() => {});
+    }
+    const response = new Response(nullBody ? null : responseBody, {
+      status: result.status,
+      statusText: result.statusText,
+      headers: responseHeaders
+    });
+    return decorateResponse(response, attestation, maxBodySize, nullBody ? [] : result.trailers);
+  }
+
+  /** `send` with retries of transient failures, recording `attempts` on the response. */
+  async function sendWithRetry(retry, method, url, headers, body, signal, trailers, authority) {
+    const retryable = retry.retries > 0
+      && retry.methods.includes(method.toUpperCase())
+      && !(body instanceof ReadableStream);
+    for (let attempt = 1; ; attempt++) {
+      try {
+        const response = await send(method, url, headers, body, signal, trailers, authority);
+        Object.defineProperty(response, "attempts", { value: attempt, enumerable: false });
+        return response;
+      } catch (e) {
+        if (!retryable || attempt > retry.retries || signal.aborted || !isTransientError(e)) {
+          throw e;
+        }
+        // Exponential backoff with jitter in [50%, 100%] of the capped delay
+        const capped = Math.min(retry.maxDelayMs, retry.baseDelayMs * 2 ** (attempt - 1));
+        await delay(capped * (0.5 + Math.random() / 2), signal);
+      }
+    }
+  }
+
+  async function atlsFetch(input, init = {}) {
+    await ensureWasm();
+
+    const retry = init.retry === false
+      ? DEFAULT_RETRY
+      : { ...DEFAULT_RETRY, ...defaultRetry, ...init.retry };
+
+    // Build request from input. Streamed bodies are kept out of the Request
+    // (which would need `duplex: "half"` and cannot take generators) and sent chunked.
+    const streamedBody = toRequestStream(init.body);
+    const request = new Request(input, streamedBody ? { ...init, body: undefined } : init);
+    const { signal } = request;
+    signal.throwIfAborted();
+    if (streamedBody && (request.method === "GET" || request.method === "HEAD")) {
+      throw new TypeError(`request with ${request.method} method cannot have a body`);
+    }
+    let trailers = normalizeTrailers(init.trailers);
+    const authority = init.host === undefined ? host : validateAuthority(init.host);
+
+    const requested = new URL(request.url, base);
+    let url = new URL(`${requested.pathname}${requested.search}`, base);
+
+    // Merge headers (default + request headers)
+    let mergedHeaders = [];
+    if (defaultHeaders) {
+      for (const [name, value] of Object.entries(defaultHeaders)) {
+        mergedHeaders.push([name, value]);
+      }
+    }
+    request.headers.forEach((value, name) => {
+      // Override default headers with request headers
+      const idx = mergedHeaders.findIndex(([n]) => n.toLowerCase() === name.toLowerCase());
+      if (idx >= 0) {
+        mergedHeaders[idx] = [name, value];
+      } else {
+        mergedHeaders.push([name, value]);
+      }
+    });
+
+    // Get body as Uint8Array (buffered, so the request can be replayed on reconnect),
+    // unless it is a stream of unknown length
+    let body = streamedBody;
+    if (!body && request.body) {
+      body = new Uint8Array(await request.arrayBuffer());
+    }
+
+    // Large uploads ask first, so a request the server refuses costs no upload
+    let expectAdded = false;
+    if (expectContinue !== undefined && body
+        && (body instanceof ReadableStream || body.byteLength >= expectContinue)
+        && !mergedHeaders.some(([n]) => n.toLowerCase() === "expect")) {
+      mergedHeaders.push(["Expect", "100-continue"]);
+      expectAdded = true;
+    }
+
+    // Follow redirects within the attested origin (request.redirect: "follow" | "error" | "manual")
+    let method = request.method;
+    for (let hops = 0; ; hops++) {
+      let response = await sendWithRetry(retry, method, url, mergedHeaders, body, signal, trailers, authority);
+      if (response.status === 417 && expectAdded && !(body instanceof ReadableStream)) {
+        // The server doesn't support Expect: send the body right away instead
+        await response.arrayBuffer().catch(() => {});
+        mergedHeaders = mergedHeaders.filter(([n]) => n.toLowerCase() !== "expect");
+        expectAdded = false;
+        response = await sendWithRetry(retry, method, url, mergedHeaders, body, signal, trailers, authority);
+      }
+      const location = response.headers.get("location");
+      if (!REDIRECT_STATUSES.has(response.status) || !location || request.redirect === "manual") {
+        if (hops > 0) {
+          Object.defineProperty(response, "redirected", { value: true });
+          Object.defineProperty(response, "url", { value: url.toString() });
+        }
+        let final = response;
+        for (const interceptor of interceptors.response) {
+          final = (await interceptor(final)) ?? final;
+        }
+        return final;
+      }
+
+      const next = new URL(location, url);
+      let error = null;
+      if (request.redirect === "error") {
+        error = new TypeError(`redirect to ${next} blocked (redirect mode is "error")`);
+      } else if (next.origin !== base.origin) {
+        error = new RedirectError(
+          `redirect to ${next} leaves the attested origin ${base.origin}`,
+          "REDIRECT_CROSS_ORIGIN",
+          next.toString()
+        );
+      } else if (hops >= maxRedirects) {
+        error = new RedirectError(
+          `too many redirects (max ${maxRedirects})`,
+          "TOO_MANY_REDIRECTS",
+          next.toString()
+        );
+      }
+      if (error) {
+        await response.body?.cancel().catch(() => {});
+        throw error;
+      }
+
+      // Drain the (small) redirect body so the connection returns to the pool
+      await response.arrayBuffer().catch(() => {});
+
+      // 303 always becomes GET; 301/302 turn POST into GET unless configured otherwise
+      const status = response.status;
+      if ((status === 303 && method !== "HEAD")
+          || ((status === 301 || status === 302) && method === "POST" && rewritePostToGet)) {
+        method = "GET";
+        body = null;
+        trailers = null;
+        expectAdded = false;
+        mergedHeaders = mergedHeaders.filter(([n]) => !BODY_HEADERS.has(n.toLowerCase()));
+      }
+      if (body instanceof ReadableStream) {
+        throw new TypeError(`cannot follow redirect to ${next}: the streamed request body was already sent`);
+      }
+      url = next;
+    }
+  }
+
+  if (jar) atlsFetch.cookieJar = jar;
+  atlsFetch.interceptors = interceptors;
+  atlsFetch.diagnostics = () => lastDiagnostics;
+  // Pooled connections stay usable by other clients; only the callbacks stop
+  atlsFetch.dispose = () => {
+    disposed = true;
+    for (const [http, pool] of watched) pool.closeListeners.delete(http);
+    watched.clear();
+  };
+  atlsFetch.getCachedAttestation = () =>
+    getCachedAttestation({ targetHost: normalizedTarget, policy, storage: cacheStorage });
+  return atlsFetch;
+}
+
+/**
+ * Drop-in replacement for `fetch()` that routes requests through aTLS.
+ *
+ * Mirrors the browser `fetch(input, init)` signature. The aTLS-specific settings
+ * are passed in `init.atls`; the target host and port are taken from the request
+ * URL, which must therefore be absolute. Connections are pooled exactly like
+ * `createAtlsFetch` (keyed by proxy URL, server name and policy).
+ *
+ * @example
+ * ```js
+ * const response = await atlsFetch("https://vllm.example.com/v1/models", {
+ *   headers: { Authorization: "Bearer ..." },
+ *   atls: { proxyUrl: "wss://proxy.example.com", policy }
+ * })
+ * console.log(response.attestation.tcbStatus)
+ * ```
+ *
+ * @param {RequestInfo | URL} input - Absolute URL or Request
+ * @param {Object} init - Standard fetch init plus `atls`
+ * @param {Object} init.atls
+ * @param {string} init.atls.proxyUrl - WebSocket proxy URL
+ * @param {Object} init.atls.policy - Verification policy
+ * @param {string} [init.atls.serverName] - TLS server name (defaults to the URL hostname)
+ * @param {Function} [init.atls.onAttestation] - Callback when a new connection is attested
+ * @param {Function} [init.atls.onAttested] - Lifecycle callbacks, as in `createAtlsFetch`
+ * @param {Function} [init.atls.onAttestationChanged]
+ * @param {Function} [init.atls.onConnectionClosed]
+ * @returns {Promise<Response>} Response with a non-enumerable `attestation` property
+ */
+export async function atlsFetch(input, init = {}) {
+  const { atls, ...fetchInit } = init;
+  if (!atls) {
+    throw new TypeError("atlsFetch requires init.atls ({ proxyUrl, policy })");
+  }
+
+  const rawUrl = input instanceof Request ? input.url : String(input);
+  let url;
+  try {
+    url = new URL(rawUrl);
+  } catch (_) {
+    throw new TypeError(`atlsFetch requires an absolute URL, got "${rawUrl}"`);
+  }
+  if (url.protocol !== "https:") {
+    throw new TypeError(`atlsFetch only supports https:// URLs, got "${url.protocol}"`);
+  }
+
+  const fetch = createAtlsFetch({
+    ...atls,
+    targetHost: `${url.hostname}:${url.port || "443"}`
+  });
+  return fetch(input, fetchInit);
+}
+
+// ============================================================================
+// Raw duplex stream
+// ============================================================================
+
+/** Default size of `AtlsStream` reads from the connection (and of `read()`). */
+const DEFAULT_READ_CHUNK_SIZE = 64 * 1024;
+
+/**
+ * Raw duplex byte stream over an attested TLS connection, bypassing HTTP.
+ *
+ * For custom binary protocols against TEE services. Reads are buffered so
+ * `read(n)` returns at most `n` bytes regardless of TLS record boundaries;
+ * writes are serialized, so `write` may be called without awaiting.
+ *
+ * @example
+ * ```js
+ * const stream = await AtlsStream.connect({
+ *   proxyUrl: "wss://proxy.example.com",
+ *   target: "db.example.com:5432",
+ *   policy
+ * })
+ * await stream.write(new Uint8Array([0x00, 0x01]))
+ * const reply = await stream.read(1024) // null at end of stream
+ * await stream.close()
+ * ```
+ */
+export class AtlsStream {
+  /**
+   * @param {Object} options
+   * @param {string} options.proxyUrl - WebSocket proxy URL (or `https://` WebTransport relay)
+   * @param {string} options.target - Target `host[:port]` (port defaults to 443)
+   * @param {Object} options.policy - Verification policy
+   * @param {string} [options.serverName] - Hostname the attestation is verified for, also sent as
+   *   SNI (defaults to the target hostname)
+   * @param {string} [options.sni] - Server name sent in the TLS ClientHello instead of `serverName`
+   * @param {string[]} [options.alpn=["http/1.1"]] - ALPN protocols to offer
+   * @param {number} [options.readChunkSize=65536] - Bytes read from the connection at a time,
+   *   and the default size of `read()`
+   * @returns {Promise<AtlsStream>}
+   */
+  static async connect({ proxyUrl, target, policy, serverName, sni, alpn, readChunkSize = DEFAULT_READ_CHUNK_SIZE }) {
+    if (!proxyUrl || !target || !policy) {
+      throw new TypeError("AtlsStream.connect requires proxyUrl, target and policy");
+    }
+    await ensureWasm();
+    const normalizedTarget = normalizeTarget(target);
+    const url = /^https:\/\//i.test(proxyUrl)
+      ? buildWebTransportUrl(proxyUrl, normalizedTarget)
+      : buildProxyUrl(proxyUrl, normalizedTarget);
+    const verifiedName = serverName || normalizedTarget.split(":")[0];
+    const stream = await AttestedStream.connect(url, verifiedName, policy, { alpn, sni, readChunkSize });
+    return new AtlsStream(stream, readChunkSize);
+  }
+
+  constructor(stream, readChunkSize = DEFAULT_READ_CHUNK_SIZE) {
+    this._stream = stream;
+    this._reader = stream.readable.getReader();
+    this._buffer = new Uint8Array(0);
+    this._writeChain = Promise.resolve();
+    this._closed = false;
+    this._readChunkSize = readChunkSize;
+    /** Attestation of the connection. */
+    this.attestation = stream.attestation();
+  }
+
+  /**
+   * Read up to `n` bytes. Resolves with `null` at end of stream.
+   * @param {number} [n=65536]
+   * @returns {Promise<Uint8Array | null>}
+   */
+  async read(n = this._readChunkSize) {
+    if (!Number.isInteger(n) || n < 1) throw new RangeError("read size must be a positive integer");
+    if (this._buffer.length === 0) {
+      const { value, done } = await this._reader.read();
+      if (done) return null;
+      this._buffer = value;
+    }
+    const chunk = this._buffer.subarray(0, n);
+    this._buffer = this._buffer.subarray(chunk.length);
+    return chunk;
+  }
+
+  /**
+   * Read exactly `n` bytes, failing if the stream ends first.
+   * @param {number} n
+   * @returns {Promise<Uint8Array>}
+   */
+  async readExactly(n) {
+    const out = new Uint8Array(n);
+    let offset = 0;
+    while (offset < n) {
+      const chunk = await this.read(n - offset);
+      if (chunk === null) throw new Error(`stream ended after ${offset} of ${n} bytes`);
+      out.set(chunk, offset);
+      offset += chunk.length;
+    }
+    return out;
+  }
+
+  /**
+   * Read until the peer closes the stream.
+   * @param {number} [maxBytes=Infinity] - Fails with `code: "BODY_TOO_LARGE"` past this many bytes
+   * @returns {Promise<Uint8Array>}
+   */
+  async readAll(maxBytes = Infinity) {
+    const head = this._buffer;
+    this._buffer = new Uint8Array(0);
+    if (head.byteLength > maxBytes) {
+      await this._reader.cancel().catch(() => {});
+      throw new BodyTooLargeError(maxBytes);
+    }
+    return drainReader(this._reader, maxBytes, NaN, head);
+  }
+
+  /**
+   * Write bytes to the TEE.
+   * @param {Uint8Array | ArrayBuffer | string} data - Strings are UTF-8 encoded
+   */
+  write(data) {
+    if (this._closed) return Promise.reject(new Error("stream is closed"));
+    const bytes = typeof data === "string"
+      ? new TextEncoder().encode(data)
+      : data instanceof ArrayBuffer ? new Uint8Array(data) : data;
+    // AttestedStream.send must not be called concurrently
+    const next = this._writeChain.then(() => this._stream.send(bytes));
+    this._writeChain = next.catch(() => {});
+    return next;
+  }
+
+  /** Close the write side (TLS close_notify) once pending writes finish; reads may continue. */
+  async closeWrite() {
+    this._closed = true;
+    await this._writeChain;
+    await this._stream.closeWrite();
+  }
+
+  /** Close both directions. */
+  async close() {
+    if (!this._closed) await this.closeWrite().catch(() => {});
+    await this._reader.cancel().catch(() => {});
+  }
+}
+
+// ============================================================================
+// Web Worker offloading
+// ============================================================================
+
+const WORKER_CALLBACKS = ["onAttestation", "onAttested", "onAttestationChanged", "onConnectionClosed"];
+
+function deserializeError(error) {
+  if (error.code === "MEASUREMENT_CHANGED") {
+    return new MeasurementChangedError(error.previous, error.current);
+  }
+  const result = new Error(error.message);
+  result.name = error.name;
+  if (error.code !== undefined) result.code = error.code;
+  return result;
+}
+
+/**
+ * Main-thread proxy for an aTLS worker (see `atls-worker.js`).
+ *
+ * Connections, attestation and HTTP handling all run inside the worker; this
+ * class only forwards requests and streams response bodies back, so quote
+ * verification never blocks the UI thread. Callbacks passed to `createFetch`
+ * run on the main thread.
+ *
+ * @example
+ * ```js
+ * const worker = new AtlsWorker(
+ *   new Worker(new URL("./atls-worker.js", import.meta.url), { type: "module" })
+ * )
+ * const fetch = worker.createFetch({ proxyUrl, targetHost, policy })
+ * const response = await fetch("/v1/chat/completions", { method: "POST", body })
+ * ```
+ */
+export class AtlsWorker {
+  /** @param {Worker} worker - Module worker running `atls-worker.js` */
+  constructor(worker) {
+    this.worker = worker;
+    this.nextId = 1;
+    /** Callbacks of each created fetch function, by fetchId. */
+    this.callbacks = new Map();
+    /** In-flight requests, by id. */
+    this.requests = new Map();
+    /** Latest connection diagnostics reported by the worker, by fetchId. */
+    this.diagnostics = new Map();
+    /**
+     * Attestation cache settings, by fetchId. Workers have no `sessionStorage`,
+     * so attestations are cached on this side.
+     */
+    this.attestationCaches = new Map();
+    worker.addEventListener("message", (event) => this._onMessage(event.data));
+  }
+
+  /**
+   * Create a fetch-compatible function whose requests run in the worker.
+   * Accepts the same options as `createAtlsFetch`.
+   * @returns {Function}
+   */
+  createFetch(options) {
+    if (options.interceptors) {
+      throw new TypeError("interceptors are not supported by AtlsWorker (functions cannot cross threads)");
+    }
+    const fetchId = this.nextId++;
+    const callbacks = {};
+    const serializable = {};
+    for (const [key, value] of Object.entries(options)) {
+      if (WORKER_CALLBACKS.includes(key)) {
+        if (typeof value === "function") callbacks[key] = value;
+      } else {
+        serializable[key] = value;
+      }
+    }
+    const { attestationCache = true, targetHost, policy } = options;
+    if (attestationCache) {
+      const storage = attestationCache.storage ?? defaultAttestationStorage();
+      if (storage) {
+        const ttlMs = attestationCache.ttlMs ?? DEFAULT_ATTESTATION_CACHE_TTL_MS;
+        this.attestationCaches.set(fetchId, { storage, targetHost, policy, ttlMs });
+      }
+    }
+    serializable.attestationCache = false;
+
+    this.callbacks.set(fetchId, callbacks);
+    this.worker.postMessage({
+      type: "create",
+      fetchId,
+      options: serializable,
+      callbacks: Object.keys(callbacks)
+    });
+
+    const workerFetch = async (input, init = {}) => {
+      const request = new Request(input, init);
+      const { signal } = request;
+      signal.throwIfAborted();
+      const body = request.body ? await request.arrayBuffer() : null;
+      return this._fetch(fetchId, request, body, signal, options.maxBodySize, init.retry, init.host);
+    };
+    workerFetch.diagnostics = () => this.diagnostics.get(fetchId) ?? null;
+    workerFetch.getCachedAttestation = () => {
+      const cache = this.attestationCaches.get(fetchId);
+      return cache ? getCachedAttestation(cache) : null;
+    };
+    return workerFetch;
+  }
+
+  _fetch(fetchId, request, body, signal, maxBodySize, retry, host) {
+    const id = this.nextId++;
+    return new Promise((resolve, reject) => {
+      const onAbort = () => this.worker.postMessage({ type: "abort", id });
+      signal.addEventListener("abort", onAbort, { once: true });
+
+      this.requests.set(id, {
+        fetchId,
+        signal,
+        maxBodySize,
+        resolve,
+        reject,
+        controller: null,
+        done: () => signal.removeEventListener("abort", onAbort)
+      });
+      this.worker.postMessage(
+        {
+          type: "fetch",
+          id,
+          fetchId,
+          url: request.url,
+          method: request.method,
+          headers: [...request.headers],
+          body,
+          retry,
+          host
+        },
+        body ? [body] : []
+      );
+    });
+  }
+
+  _onMessage(message) {
+    if (message.type === "event") {
+      notify(message.name, this.callbacks.get(message.fetchId)?.[message.name], ...message.args);
+      return;
+    }
+    if (message.type === "callback") {
+      this._runCallback(message);
+      return;
+    }
+
+    const pending = this.requests.get(message.id);
+    if (!pending) return;
+
+    switch (message.type) {
+      case "response": {
+        const body = new ReadableStream({
+          start: (controller) => {
+            pending.controller = controller;
+          },
+          cancel: () => {
+            this.requests.delete(message.id);
+            pending.done();
+            pending.resolveTrailers?.(null);
+            this.worker.postMessage({ type: "cancel", id: message.id });
+          }
+        });
+        const response = new Response(body, {
+          status: message.status,
+          statusText: message.statusText,
+          headers: message.headers
+        });
+        if (message.diagnostics) this.diagnostics.set(pending.fetchId, message.diagnostics);
+        const cache = this.attestationCaches.get(pending.fetchId);
+        if (cache && message.attestation) {
+          storeAttestation(cache.storage, cache.targetHost, cache.policy, message.attestation, cache.ttlMs);
+        }
+        const trailers = new Promise((resolve) => {
+          pending.resolveTrailers = resolve;
+        });
+        pending.resolve(decorateResponse(response, message.attestation, pending.maxBodySize, trailers));
+        break;
+      }
+      case "chunk":
+        pending.controller?.enqueue(message.chunk);
+        break;
+      case "end":
+        this.requests.delete(message.id);
+        pending.done();
+        pending.controller?.close();
+        pending.resolveTrailers?.(message.trailers ?? []);
+        break;
+      case "error": {
+        this.requests.delete(message.id);
+        pending.done();
+        const error = pending.signal.aborted ? pending.signal.reason : deserializeError(message.error);
+        pending.resolveTrailers?.(null);
+        if (pending.controller) {
+          pending.controller.error(error);
+        } else {
+          pending.reject(error);
+        }
+        break;
+      }
+    }
+  }
+
+  async _runCallback({ callId, fetchId, name, args }) {
+    let error;
+    try {
+      await this.callbacks.get(fetchId)?.[name]?.(...args);
+    } catch (e) {
+      error = { name: e?.name || "Error", message: e?.message || String(e), code: e?.code };
+    }
+    this.worker.postMessage({ type: "callback-result", callId, error });
+  }
+
+  /** Close the worker's pooled connections (the worker keeps running). */
+  closeAllConnections() {
+    this.worker.postMessage({ type: "close" });
+  }
+
+  /** Close all connections and terminate the worker. */
+  terminate() {
+    this.closeAllConnections();
+    this.worker.terminate();
+    for (const pending of this.requests.values()) {
+      const error = new Error("aTLS worker terminated");
+      if (pending.controller) pending.controller.error(error);
+      else pending.reject(error);
+    }
+    this.requests.clear();
+  }
+}
+
+// ============================================================================
+// WebSocket over aTLS
+// ============================================================================
+
+const WS_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const WS_OPCODE = {
+  CONTINUATION: 0x0,
+  TEXT: 0x1,
+  BINARY: 0x2,
+  CLOSE: 0x8,
+  PING: 0x9,
+  PONG: 0xa
+};
+
+function base64(bytes) {
+  let binary = "";
+  for (const byte of bytes) binary += String.fromCharCode(byte);
+  return btoa(binary);
+}
+
+function concatBytes(a, b) {
+  const out = new Uint8Array(a.length + b.length);
+  out.set(a, 0);
+  out.set(b, a.length);
+  return out;
+}
+
+function indexOfHeaderEnd(bytes) {
+  for (let i = 0; i + 3 < bytes.length; i++) {
+    if (bytes[i] === 13 && bytes[i + 1] === 10 && bytes[i + 2] === 13 && bytes[i + 3] === 10) {
+      return i + 4;
+    }
+  }
+  return -1;
+}
+
+/** Encode a masked client frame (RFC 6455 section 5.2). */
+function encodeWsFrame(opcode, payload) {
+  const length = payload.length;
+  const headerLength = length < 126 ? 2 : length < 65536 ? 4 : 10;
+  const frame = new Uint8Array(headerLength + 4 + length);
+  frame[0] = 0x80 | opcode;
+  if (length < 126) {
+    frame[1] = 0x80 | length;
+  } else if (length < 65536) {
+    frame[1] = 0x80 | 126;
+    new DataView(frame.buffer).setUint16(2, length);
+  } else {
+    frame[1] = 0x80 | 127;
+    new DataView(frame.buffer).setBigUint64(2, BigInt(length));
+  }
+  const mask = crypto.getRandomValues(new Uint8Array(4));
+  frame.set(mask, headerLength);
+  for (let i = 0; i < length; i++) {
+    frame[headerLength + 4 + i] = payload[i] ^ mask[i & 3];
+  }
+  return frame;
+}
+
+/**
+ * Decode one server frame from the start of `bytes`.
+ * @returns {{ fin: boolean, opcode: number, payload: Uint8Array, size: number } | null}
+ *   `null` if more bytes are needed.
+ */
+function decodeWsFrame(bytes) {
+  if (bytes.length < 2) return null;
+  const fin = (bytes[0] & 0x80) !== 0;
+  const opcode = bytes[0] & 0x0f;
+  const masked = (bytes[1] & 0x80) !== 0;
+  let length = bytes[1] & 0x7f;
+  let offset = 2;
+  const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  if (length === 126) {
+    if (bytes.length < 4) return null;
+    length = view.getUint16(2);
+    offset = 4;
+  } else if (length === 127) {
+    if (bytes.length < 10) return null;
+    length = Number(view.getBigUint64(2));
+    offset = 10;
+  }
+  let mask = null;
+  if (masked) {
+    if (bytes.length < offset + 4) return null;
+    mask = bytes.subarray(offset, offset + 4);
+    offset += 4;
+  }
+  if (bytes.length < offset + length) return null;
+  const payload = bytes.slice(offset, offset + length);
+  if (mask) {
+    for (let i = 0; i < payload.length; i++) payload[i] ^= mask[i & 3];
+  }
+  return { fin, opcode, payload, size: offset + length };
+}
+
+/**
+ * WebSocket tunneled inside an attested TLS connection.
+ *
+ * Performs the HTTP/1.1 Upgrade handshake inside the aTLS stream (which itself
+ * rides the WebSocket proxy), so the realtime channel is bound to the attested
+ * TEE. Mirrors the browser `WebSocket` API: `onopen`, `onmessage`, `onerror`,
+ * `onclose` (or `addEventListener`), `send()`, `close()` and `readyState`.
+ *
+ * @example
+ * ```js
+ * const ws = new AtlsWebSocket("wss://enclave.example.com/ws", [], {
+ *   proxyUrl: "wss://proxy.example.com",
+ *   policy
+ * })
+ * ws.onopen = () => ws.send("hello")
+ * ws.onmessage = (event) => console.log(event.data, ws.attestation.tcbStatus)
+ * ```
+ */
+export class AtlsWebSocket extends EventTarget {
+  static CONNECTING = 0;
+  static OPEN = 1;
+  static CLOSING = 2;
+  static CLOSED = 3;
+
+  /**
+   * @param {string | URL} url - Target WebSocket URL (`wss://host[:port]/path`)
+   * @param {string | string[]} [protocols] - Subprotocols to request
+   * @param {Object} options
+   * @param {string} options.proxyUrl - aTLS WebSocket proxy URL
+   * @param {Object} options.policy - Verification policy
+   * @param {string} [options.serverName] - TLS server name (defaults to the URL hostname)
+   */
+  constructor(url, protocols = [], options = {}) {
+    super();
+    const target = new URL(url);
+    if (target.protocol !== "wss:") {
+      throw new SyntaxError("AtlsWebSocket requires a wss:// URL");
+    }
+    if (!options.proxyUrl || !options.policy) {
+      throw new TypeError("AtlsWebSocket requires options.proxyUrl and options.policy");
+    }
+
+    this.url = target.toString();
+    this.readyState = AtlsWebSocket.CONNECTING;
+    this.protocol = "";
+    this.binaryType = "arraybuffer";
+    this.attestation = null;
+    this.onopen = null;
+    this.onmessage = null;
+    this.onerror = null;
+    this.onclose = null;
+
+    this._stream = null;
+    this._writeChain = Promise.resolve();
+    this._closeSent = false;
+
+    const requested = typeof protocols === "string" ? [protocols] : protocols;
+    this._connect(target, requested, options).catch((error) => this._fail(error));
+  }
+
+  _emit(event) {
+    const handler = this[`on${event.type}`];
+    if (typeof handler === "function") handler.call(this, event);
+    this.dispatchEvent(event);
+  }
+
+  async _connect(target, protocols, { proxyUrl, policy, serverName }) {
+    await ensureWasm();
+
+    const port = target.port || "443";
+    const sni = serverName || target.hostname;
+    const wsUrl = buildProxyUrl(proxyUrl, `${target.hostname}:${port}`);
+    this._stream = await AttestedStream.connect(wsUrl, sni, policy);
+    this.attestation = this._stream.attestation();
+    this._reader = this._stream.readable.getReader();
+
+    // HTTP/1.1 Upgrade inside the attested stream
+    const key = base64(crypto.getRandomValues(new Uint8Array(16)));
+    const hostHeader = target.port ? `${target.hostname}:${target.port}` : target.hostname;
+    const lines = [
+      `GET ${target.pathname}${target.search} HTTP/1.1`,
+      `Host: ${hostHeader}`,
+      "Upgrade: websocket",
+      "Connection: Upgrade",
+      `Sec-WebSocket-Key: ${key}`,
+      "Sec-WebSocket-Version: 13"
+    ];
+    if (protocols.length > 0) lines.push(`Sec-WebSocket-Protocol: ${protocols.join(", ")}`);
+    await this._write(new TextEncoder().encode(`${lines.join("\r\n")}\r\n\r\n`));
+
+    let buffer = new Uint8Array(0);
+    let headerEnd = -1;
+    while (headerEnd < 0) {
+      const { value, done } = await this._reader.read();
+      if (done) throw new Error("connection closed during WebSocket handshake");
+      buffer = concatBytes(buffer, value);
+      headerEnd = indexOfHeaderEnd(buffer);
+    }
+
+    const head = new TextDecoder().decode(buffer.subarray(0, headerEnd));
+    const [statusLine, ...headerLines] = head.trim().split("\r\n");
+    if (!/^HTTP\/1\.1 101\b/.test(statusLine)) {
+      throw new Error(`WebSocket upgrade rejected: ${statusLine}`);
+    }
+    const headers = new Map();
+    for (const line of headerLines) {
+      const idx = line.indexOf(":");
+      if (idx > 0) headers.set(line.slice(0, idx).trim().toLowerCase(), line.slice(idx + 1).trim());
+    }
+
+    const digest = await crypto.subtle.digest("SHA-1", new TextEncoder().encode(key + WS_GUID));
+    if (headers.get("sec-websocket-accept") !== base64(new Uint8Array(digest))) {
+      throw new Error("WebSocket upgrade failed: invalid Sec-WebSocket-Accept");
+    }
+    this.protocol = headers.get("sec-websocket-protocol") || "";
+
+    this.readyState = AtlsWebSocket.OPEN;
+    this._emit(new Event("open"));
+    await this._readLoop(buffer.subarray(headerEnd));
+  }
+
+  async _readLoop(initial) {
+    let buffer = initial;
+    let fragments = [];
+    let fragmentOpcode = null;
+
+    for (;;) {
+      let frame;
+      while ((frame = decodeWsFrame(buffer))) {
+        buffer = buffer.subarray(frame.size);
+        const { fin, opcode, payload } = frame;
+
+        if (opcode === WS_OPCODE.PING) {
+          await this._sendFrame(WS_OPCODE.PONG, payload);
+        } else if (opcode === WS_OPCODE.PONG) {
+          // Unsolicited pongs are ignored
+        } else if (opcode === WS_OPCODE.CLOSE) {
+          const view = new DataView(payload.buffer, payload.byteOffset, payload.byteLength);
+          const code = payload.length >= 2 ? view.getUint16(0) : 1005;
+          const reason = new TextDecoder().decode(payload.subarray(2));
+          if (!this._closeSent) {
+            await this._sendFrame(WS_OPCODE.CLOSE, payload.subarray(0, 2));
+          }
+          this._finish(code, reason, true);
+          return;
+        } else {
+          if (opcode !== WS_OPCODE.CONTINUATION) fragmentOpcode = opcode;
+          fragments.push(payload);
+          if (fin) {
+            const data = fragments.reduce(concatBytes, new Uint8Array(0));
+            this._deliver(fragmentOpcode, data);
+            fragments = [];
+            fragmentOpcode = null;
+          }
+        }
+      }
+
+      const { value, done } = await this._reader.read();
+      if (done) {
+        this._finish(1006, "", false);
+        return;
+      }
+      buffer = concatBytes(buffer, value);
+    }
+  }
+
+  _deliver(opcode, data) {
+    let payload;
+    if (opcode === WS_OPCODE.TEXT) {
+      payload = new TextDecoder().decode(data);
+    } else if (this.binaryType === "blob") {
+      payload = new Blob([data]);
+    } else {
+      payload = data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength);
+    }
+    this._emit(new MessageEvent("message", { data: payload }));
+  }
+
+  _write(bytes) {
+    // AttestedStream.send must not be called concurrently
+    const next = this._writeChain.then(() => this._stream.send(bytes));
+    this._writeChain = next.catch(() => {});
+    return next;
+  }
+
+  _sendFrame(opcode, payload) {
+    return this._write(encodeWsFrame(opcode, payload));
+  }
+
+  /**
+   * Send a text or binary message.
+   * @param {string | ArrayBuffer | ArrayBufferView} data
+   */
+  send(data) {
+    if (this.readyState === AtlsWebSocket.CONNECTING) {
+      throw new DOMException("WebSocket is still connecting", "InvalidStateError");
+    }
+    if (this.readyState !== AtlsWebSocket.OPEN) return;
+
+    let opcode = WS_OPCODE.BINARY;
+    let payload;
+    if (typeof data === "string") {
+      opcode = WS_OPCODE.TEXT;
+      payload = new TextEncoder().encode(data);
+    } else if (data instanceof ArrayBuffer) {
+      payload = new Uint8Array(data);
+    } else if (ArrayBuffer.isView(data)) {
+      payload = new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+    } else {
+      throw new TypeError("AtlsWebSocket.send accepts strings, ArrayBuffers and typed arrays");
+    }
+    this._sendFrame(opcode, payload).catch((error) => this._fail(error));
+  }
+
+  /**
+   * Start the closing handshake.
+   * @param {number} [code=1000]
+   * @param {string} [reason=""]
+   */
+  close(code = 1000, reason = "") {
+    if (this.readyState === AtlsWebSocket.CLOSING || this.readyState === AtlsWebSocket.CLOSED) {
+      return;
+    }
+    if (this.readyState === AtlsWebSocket.CONNECTING) {
+      this._fail(new Error("WebSocket closed before the connection was established"));
+      return;
+    }
+    this.readyState = AtlsWebSocket.CLOSING;
+    const reasonBytes = new TextEncoder().encode(reason);
+    const payload = new Uint8Array(2 + reasonBytes.length);
+    new DataView(payload.buffer).setUint16(0, code);
+    payload.set(reasonBytes, 2);
+    this._closeSent = true;
+    this._sendFrame(WS_OPCODE.CLOSE, payload).catch((error) => this._fail(error));
+  }
+
+  _finish(code, reason, wasClean) {
+    if (this.readyState === AtlsWebSocket.CLOSED) return;
+    this.readyState = AtlsWebSocket.CLOSED;
+    if (this._stream) {
+      this._writeChain.then(() => this._stream.closeWrite()).catch(() => {});
+    }
+    this._emit(new CloseEvent("close", { code, reason, wasClean }));
+  }
+
+  _fail(error) {
+    if (this.readyState === AtlsWebSocket.CLOSED) return;
+    console.error("[atls-websocket]", error);
+    this._emit(new Event("error"));
+    this._finish(1006, "", false);
+  }
 }
 
 // Re-export for advanced usage
-export { init, AttestedStream, AtlsHttp, mergeWithDefaultAppCompose };
+export { init, AttestedStream, AtlsHttp, Policy, mergeWithDefaultAppCompose };
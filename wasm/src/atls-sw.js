@@ -0,0 +1,105 @@
+/**
+ * aTLS Service Worker - route an app's existing `fetch()` calls through aTLS.
+ *
+ * Call `installAtlsServiceWorker` from a module service worker. Requests the
+ * page makes to a configured origin are intercepted and sent with
+ * `createAtlsFetch`, so every call site gains attested transport unchanged.
+ * Requests to other origins, and page navigations, go to the network as usual.
+ *
+ * @example
+ * ```js
+ * // sw.js
+ * import { installAtlsServiceWorker } from "@concrete-security/atlas-wasm/atls-sw.js"
+ *
+ * installAtlsServiceWorker({
+ *   routes: [{ origin: "https://vllm.example.com", proxyUrl: "wss://proxy.example.com", policy }]
+ * })
+ *
+ * // page
+ * await navigator.serviceWorker.register("/sw.js", { type: "module" })
+ * const models = await (await fetch("https://vllm.example.com/v1/models")).json()
+ * ```
+ *
+ * The page cannot see `response.attestation` across the worker boundary, so
+ * intercepted responses carry it in headers instead:
+ * - `x-atls-attested: true`
+ * - `x-atls-measurement`: workload measurement (when available)
+ * - `x-atls-tcb-status`: TCB status of the attested platform
+ *
+ * A failed attestation or tunnel surfaces in the page as a network error (a
+ * rejected `fetch()`), exactly like an unreachable server; the cause is passed
+ * to `onError` and logged in the service worker console.
+ */
+
+import { createAtlsFetch } from "./atls-fetch.js";
+
+/** Copy `response` with the attestation exposed as `x-atls-*` headers. */
+function withAttestationHeaders(response) {
+  const { attestation } = response;
+  if (!attestation) return response;
+  const headers = new Headers(response.headers);
+  headers.set("x-atls-attested", String(attestation.trusted));
+  if (attestation.measurement) headers.set("x-atls-measurement", attestation.measurement);
+  if (attestation.tcbStatus) headers.set("x-atls-tcb-status", attestation.tcbStatus);
+  return new Response(response.body, {
+    status: response.status,
+    statusText: response.statusText,
+    headers
+  });
+}
+
+/**
+ * Intercept `fetch()` calls to the configured origins and send them over aTLS.
+ *
+ * @param {Object} options
+ * @param {Array<Object>} options.routes - One entry per attested origin: `origin`
+ *   (e.g. "https://vllm.example.com") plus any `createAtlsFetch` option except
+ *   `targetHost`, which is derived from the origin
+ * @param {boolean} [options.claimClients=true] - Activate immediately and take
+ *   control of already-open pages, so their first requests are intercepted too
+ * @param {Function} [options.onError] - `(error, request)` when an intercepted request fails
+ * @returns {Map<string, Function>} The fetch function used for each origin
+ */
+export function installAtlsServiceWorker({ routes, claimClients = true, onError }) {
+  if (!Array.isArray(routes) || routes.length === 0) {
+    throw new Error("installAtlsServiceWorker requires at least one route");
+  }
+
+  const fetchers = new Map();
+  for (const { origin, ...options } of routes) {
+    const url = new URL(origin);
+    if (url.protocol !== "https:") {
+      throw new Error(`aTLS route origin must use https://, got ${origin}`);
+    }
+    if (fetchers.has(url.origin)) {
+      throw new Error(`duplicate aTLS route for ${url.origin}`);
+    }
+    const targetHost = `${url.hostname}:${url.port || 443}`;
+    fetchers.set(url.origin, createAtlsFetch({ ...options, targetHost }));
+  }
+
+  self.addEventListener("fetch", (event) => {
+    const { request } = event;
+    // Navigations load pages, which the browser must fetch itself
+    if (request.mode === "navigate") return;
+    const fetcher = fetchers.get(new URL(request.url).origin);
+    if (!fetcher) return;
+
+    event.respondWith(
+      fetcher(request).then(withAttestationHeaders, (error) => {
+        console.error("[atls-sw] attested request failed:", request.url, error?.code ?? "", error);
+        if (typeof onError === "function") {
+          try { onError(error, request); } catch (_) {}
+        }
+        return Response.error();
+      })
+    );
+  });
+
+  if (claimClients) {
+    self.addEventListener("install", () => self.skipWaiting());
+    self.addEventListener("activate", (event) => event.waitUntil(self.clients.claim()));
+  }
+
+  return fetchers;
+}
@@ -0,0 +1,140 @@
+/**
+ * aTLS Worker - worker-side entry point for `AtlsWorker`.
+ *
+ * Runs the WebSocket tunnel, TLS handshake, quote verification and HTTP
+ * handling inside a dedicated Web Worker, so multi-hundred-millisecond
+ * attestation never blocks the UI thread. The main thread talks to it through
+ * the `AtlsWorker` class exported by `atls-fetch.js`.
+ *
+ * @example
+ * ```js
+ * import { AtlsWorker } from "@concrete-security/atlas-wasm"
+ *
+ * const worker = new AtlsWorker(
+ *   new Worker(new URL("@concrete-security/atlas-wasm/atls-worker.js", import.meta.url), { type: "module" })
+ * )
+ * const fetch = worker.createFetch({ proxyUrl, targetHost, policy })
+ * const response = await fetch("/v1/models")
+ * ```
+ *
+ * Protocol (all messages carry `type`):
+ * - main -> worker: `create`, `fetch`, `abort`, `cancel`, `callback-result`, `close`
+ * - worker -> main: `response`, `chunk`, `end`, `error`, `callback`, `event`
+ */
+
+import { createAtlsFetch, closeAllConnections } from "./atls-fetch.js";
+
+/** Fetch functions created on behalf of the main thread, by fetchId. */
+const fetches = new Map();
+
+/** In-flight requests, by request id. @type {Map<number, { abort: AbortController, reader?: ReadableStreamDefaultReader }>} */
+const requests = new Map();
+
+/** Pending `onAttestation` round-trips to the main thread, by callId. */
+const pendingCallbacks = new Map();
+let nextCallId = 1;
+
+function serializeError(error) {
+  return {
+    name: error?.name || "Error",
+    message: error?.message || String(error),
+    code: error?.code,
+    previous: error?.previous,
+    current: error?.current
+  };
+}
+
+/** Ask the main thread to run `onAttestation`; rejects if the callback threw. */
+function remoteCallback(fetchId, name, args) {
+  const callId = nextCallId++;
+  return new Promise((resolve, reject) => {
+    pendingCallbacks.set(callId, { resolve, reject });
+    self.postMessage({ type: "callback", callId, fetchId, name, args });
+  });
+}
+
+function create({ fetchId, options, callbacks }) {
+  const forward = (name) => (...args) => self.postMessage({ type: "event", fetchId, name, args });
+  fetches.set(fetchId, createAtlsFetch({
+    ...options,
+    onAttestation: callbacks.includes("onAttestation")
+      ? (attestation) => remoteCallback(fetchId, "onAttestation", [attestation])
+      : undefined,
+    onAttested: callbacks.includes("onAttested") ? forward("onAttested") : undefined,
+    onAttestationChanged: callbacks.includes("onAttestationChanged") ? forward("onAttestationChanged") : undefined,
+    onConnectionClosed: callbacks.includes("onConnectionClosed") ? forward("onConnectionClosed") : undefined
+  }));
+}
+
+async function runFetch({ id, fetchId, url, method, headers, body, retry, host }) {
+  const fetch = fetches.get(fetchId);
+  const abort = new AbortController();
+  const entry = { abort };
+  requests.set(id, entry);
+
+  try {
+    if (!fetch) throw new Error("unknown fetch function (was the worker closed?)");
+    const response = await fetch(url, { method, headers, body, retry, host, signal: abort.signal });
+    self.postMessage({
+      type: "response",
+      id,
+      status: response.status,
+      statusText: response.statusText,
+      headers: [...response.headers],
+      attestation: response.attestation,
+      diagnostics: fetch.diagnostics()
+    });
+
+    if (response.body) {
+      entry.reader = response.body.getReader();
+      for (;;) {
+        const { value, done } = await entry.reader.read();
+        if (done) break;
+        // Transfer (not copy) the chunk; copy first if it is a view into a larger buffer
+        const chunk = value.byteOffset === 0 && value.byteLength === value.buffer.byteLength
+          ? value
+          : value.slice();
+        self.postMessage({ type: "chunk", id, chunk }, [chunk.buffer]);
+      }
+    }
+    self.postMessage({ type: "end", id, trailers: [...((await response.trailers()) ?? [])] });
+  } catch (error) {
+    self.postMessage({ type: "error", id, error: serializeError(error) });
+  } finally {
+    requests.delete(id);
+  }
+}
+
+self.addEventListener("message", (event) => {
+  const message = event.data;
+  switch (message.type) {
+    case "create":
+      create(message);
+      break;
+    case "fetch":
+      runFetch(message);
+      break;
+    case "abort":
+      requests.get(message.id)?.abort.abort();
+      break;
+    case "cancel":
+      requests.get(message.id)?.reader?.cancel().catch(() => {});
+      break;
+    case "callback-result": {
+      const pending = pendingCallbacks.get(message.callId);
+      if (!pending) break;
+      pendingCallbacks.delete(message.callId);
+      if (message.error) {
+        pending.reject(Object.assign(new Error(message.error.message), message.error));
+      } else {
+        pending.resolve();
+      }
+      break;
+    }
+    case "close":
+      closeAllConnections();
+      break;
+    default:
+      console.warn("[atls-worker] unknown message type:", message.type);
+  }
+});
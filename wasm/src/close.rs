@@ -0,0 +1,146 @@
+//! Why an [`AtlsHttp`](crate::AtlsHttp) connection ended.
+//!
+//! The hyper connection driver runs in the background, so without this a dead
+//! tunnel only shows up as a generic error on the next request. The driver's
+//! result is classified into a [`CloseEvent`] with a machine-readable code,
+//! which `AtlsHttp.closed()` resolves with and which failed requests carry as
+//! their `code`:
+//!
+//! | Code                | Meaning                                               |
+//! |---------------------|-------------------------------------------------------|
+//! | `CONNECTION_CLOSED` | Clean shutdown (TLS `close_notify`, or `close()`)     |
+//! | `TUNNEL_CLOSED`     | The WebSocket/WebTransport tunnel or proxy went away  |
+//! | `TLS_ALERT`         | The server sent a fatal TLS alert (see `alert`)       |
+//! | `PROTOCOL_ERROR`    | The server sent a malformed or unexpected HTTP message |
+
+use std::error::Error as StdError;
+use std::io;
+
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::{Error, Reflect};
+
+/// Machine-readable reason a connection ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseCode {
+    Closed,
+    TunnelClosed,
+    TlsAlert,
+    ProtocolError,
+}
+
+impl CloseCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "CONNECTION_CLOSED",
+            Self::TunnelClosed => "TUNNEL_CLOSED",
+            Self::TlsAlert => "TLS_ALERT",
+            Self::ProtocolError => "PROTOCOL_ERROR",
+        }
+    }
+}
+
+/// How and why a connection ended, as reported to JavaScript.
+#[derive(Debug, Clone, Serialize)]
+pub struct CloseEvent {
+    #[serde(serialize_with = "serialize_code")]
+    pub code: CloseCode,
+    pub message: String,
+    /// Alert description for `TLS_ALERT` (e.g. "HandshakeFailure").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<String>,
+}
+
+fn serialize_code<S: serde::Serializer>(code: &CloseCode, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(code.as_str())
+}
+
+impl CloseEvent {
+    /// Event for a connection closed without error.
+    pub fn clean() -> Self {
+        Self {
+            code: CloseCode::Closed,
+            message: "connection closed".into(),
+            alert: None,
+        }
+    }
+
+    /// Classify a hyper error from the connection driver or a failed request.
+    pub fn from_error(error: &hyper::Error) -> Self {
+        let mut source: Option<&(dyn StdError + 'static)> = error.source();
+        while let Some(cause) = source {
+            if let Some(alert) = received_alert(cause) {
+                return Self {
+                    code: CloseCode::TlsAlert,
+                    message: format!("server sent TLS alert: {alert:?}"),
+                    alert: Some(format!("{alert:?}")),
+                };
+            }
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                return Self {
+                    code: CloseCode::TunnelClosed,
+                    message: format!("tunnel closed: {io_error}"),
+                    alert: None,
+                };
+            }
+            source = cause.source();
+        }
+
+        let code = if error.is_parse() || error.is_user() {
+            CloseCode::ProtocolError
+        } else {
+            CloseCode::TunnelClosed
+        };
+        Self {
+            code,
+            message: error.to_string(),
+            alert: None,
+        }
+    }
+
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(self).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// JS `Error` for a request that failed because of this event, with `code`
+    /// (and `alert`) set.
+    pub fn to_js_error(&self, context: &str) -> JsValue {
+        let error = Error::new(&format!("{context}: {}", self.message));
+        let _ = Reflect::set(&error, &"code".into(), &self.code.as_str().into());
+        if let Some(alert) = &self.alert {
+            let _ = Reflect::set(&error, &"alert".into(), &alert.into());
+        }
+        error.into()
+    }
+}
+
+/// The alert behind `cause`, if it is a received TLS alert. rustls errors reach
+/// hyper wrapped in an `io::Error`.
+fn received_alert(cause: &(dyn StdError + 'static)) -> Option<rustls::AlertDescription> {
+    let tls = cause.downcast_ref::<rustls::Error>().or_else(|| {
+        cause
+            .downcast_ref::<io::Error>()?
+            .get_ref()?
+            .downcast_ref::<rustls::Error>()
+    });
+    match tls {
+        Some(rustls::Error::AlertReceived(alert)) => Some(*alert),
+        _ => None,
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", test))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_clean_close_serializes_code() {
+        let event = CloseEvent::clean().to_js().unwrap();
+        let code = Reflect::get(&event, &"code".into()).unwrap();
+        assert_eq!(code.as_string().as_deref(), Some("CONNECTION_CLOSED"));
+        assert!(Reflect::get(&event, &"alert".into())
+            .unwrap()
+            .is_undefined());
+    }
+}
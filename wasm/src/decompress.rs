@@ -0,0 +1,141 @@
+//! Streaming response decompression for `Content-Encoding`.
+//!
+//! Enabled with the `compression` feature (on by default). Supports `gzip`,
+//! `deflate` (zlib-wrapped, as used by HTTP) and `br`. Decoders are write-based
+//! so each body frame can be decompressed as it arrives, keeping LLM token
+//! streams incremental.
+//!
+//! Without the feature, no `Accept-Encoding` is sent and [`Decompressor::for_encoding`]
+//! always returns `None`, so call sites don't need their own `cfg` guards.
+
+#![cfg_attr(
+    not(feature = "compression"),
+    allow(dead_code, unused_imports, unused_mut)
+)]
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Value to send in `Accept-Encoding` when the caller doesn't set one.
+pub fn accept_encoding() -> Option<&'static str> {
+    if cfg!(feature = "compression") {
+        Some("gzip, deflate, br")
+    } else {
+        None
+    }
+}
+
+/// Sink collecting decoder output until the next `take`.
+#[derive(Clone, Default)]
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuf {
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compression")]
+enum Decoder {
+    Gzip(flate2::write::GzDecoder<SharedBuf>),
+    Deflate(flate2::write::ZlibDecoder<SharedBuf>),
+    Brotli(Box<brotli::DecompressorWriter<SharedBuf>>),
+}
+
+/// Incremental decompressor for one response body.
+pub struct Decompressor {
+    #[cfg(feature = "compression")]
+    decoder: Decoder,
+    out: SharedBuf,
+}
+
+impl Decompressor {
+    /// Decompression is disabled: every body is passed through unchanged.
+    #[cfg(not(feature = "compression"))]
+    pub fn for_encoding(_encoding: &str) -> Option<Self> {
+        None
+    }
+
+    /// Create a decompressor for a `Content-Encoding` value.
+    ///
+    /// Returns `None` for `identity` and unsupported encodings; the body is then
+    /// passed through unchanged.
+    #[cfg(feature = "compression")]
+    pub fn for_encoding(encoding: &str) -> Option<Self> {
+        let out = SharedBuf::default();
+        let decoder = match encoding.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Decoder::Gzip(flate2::write::GzDecoder::new(out.clone())),
+            "deflate" => Decoder::Deflate(flate2::write::ZlibDecoder::new(out.clone())),
+            "br" => Decoder::Brotli(Box::new(brotli::DecompressorWriter::new(out.clone(), 4096))),
+            _ => return None,
+        };
+        Some(Self { decoder, out })
+    }
+
+    /// Feed a compressed chunk, returning the bytes decoded so far.
+    pub fn push(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        #[cfg(not(feature = "compression"))]
+        self.out.write_all(chunk)?;
+        #[cfg(feature = "compression")]
+        match &mut self.decoder {
+            Decoder::Gzip(d) => d.write_all(chunk)?,
+            Decoder::Deflate(d) => d.write_all(chunk)?,
+            Decoder::Brotli(d) => d.write_all(chunk)?,
+        }
+        Ok(self.out.take())
+    }
+
+    /// Signal the end of the body, returning any remaining decoded bytes.
+    ///
+    /// Fails if the compressed stream is truncated.
+    pub fn finish(mut self) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "compression")]
+        match &mut self.decoder {
+            Decoder::Gzip(d) => d.try_finish()?,
+            Decoder::Deflate(d) => d.try_finish()?,
+            Decoder::Brotli(d) => d.close()?,
+        }
+        Ok(self.out.take())
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_gzip_round_trip_in_chunks() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(b"data: hello\n\ndata: world\n\n")
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decompressor = Decompressor::for_encoding("gzip").unwrap();
+        let mut decoded = Vec::new();
+        for chunk in compressed.chunks(7) {
+            decoded.extend(decompressor.push(chunk).unwrap());
+        }
+        decoded.extend(decompressor.finish().unwrap());
+        assert_eq!(decoded, b"data: hello\n\ndata: world\n\n");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_unsupported_encoding_passes_through() {
+        assert!(Decompressor::for_encoding("identity").is_none());
+        assert!(Decompressor::for_encoding("zstd").is_none());
+    }
+}
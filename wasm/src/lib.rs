@@ -8,24 +8,440 @@
 
 #![cfg(target_arch = "wasm32")]
 
+mod close;
+mod decompress;
 mod hyper_io;
+mod mux;
+mod request;
+mod transport;
 
-use async_io_stream::IoStream;
-use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::future::{self, Either, FutureExt, Shared};
 use futures::io::{ReadHalf, WriteHalf};
 use futures::AsyncReadExt;
-use http_body_util::{BodyExt, Full};
+use http_body_util::BodyExt;
 use hyper::client::conn::http1;
 use hyper::Request;
-use atlas_rs::{dstack::merge_with_default_app_compose, atls_connect, AsyncWriteExt, Policy, TlsStream};
-use serde::Serialize;
+use atlas_rs::timing::Stopwatch;
+use atlas_rs::{
+    dstack::merge_with_default_app_compose, atls_connect, atls_connect_with_sni, AsyncWriteExt,
+    AtlsVerificationError, CollateralSource, DstackTdxPolicy, ErrorCode, PhaseTimings, Policy,
+    Report, TlsStream,
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 use std::{cell::RefCell, rc::Rc};
 use wasm_bindgen::prelude::*;
-use web_sys::js_sys::{Object, Promise, Reflect, Uint8Array};
-use web_sys::ReadableStreamDefaultController;
-use ws_stream_wasm::{WsMeta, WsStreamIo};
+use web_sys::js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use web_sys::{AbortSignal, ReadableStreamDefaultController};
 
+use close::{CloseCode, CloseEvent};
+use decompress::Decompressor;
 use hyper_io::HyperIo;
+use request::{ContinueGate, HeldUpload, RequestBody, RequestTrailers};
+use transport::Tunnel;
+
+// ============================================================================
+// TypeScript Definitions
+// ============================================================================
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+/** Per-phase connection durations in milliseconds. */
+export interface AttestationTimings {
+  tcpConnectMs?: number;
+  tlsHandshakeMs?: number;
+  evidenceExchangeMs?: number;
+  collateralFetchMs?: number;
+  verificationMs?: number;
+  totalMs: number;
+}
+
+/** Result of a successful attestation. */
+export interface AttestationResult {
+  trusted: boolean;
+  teeType: string;
+  /** Workload measurement (hex MRTD for TDX). */
+  measurement?: string;
+  /**
+   * SHA-256 (hex) of MRTD, RTMR0-3 and the compose hash: changes when the OS image, the app or
+   * its configuration does, and differs between instances of one app.
+   */
+  measurementDigest?: string;
+  /**
+   * SHA-256 (hex) of MRTD, RTMR0-2 and the compose hash: like `measurementDigest`, but shared by
+   * every instance of one app deployment.
+   */
+  appDigest?: string;
+  tcbStatus: string;
+  advisoryIds: string[];
+  timings: AttestationTimings;
+}
+
+/** Negotiated TLS parameters and attestation details of one connection. */
+export interface ConnectionDiagnostics {
+  /** e.g. "TLSv1.3". */
+  tlsVersion?: string;
+  /** e.g. "TLS13_AES_256_GCM_SHA384". */
+  cipherSuite?: string;
+  /** Negotiated ALPN protocol, if any. */
+  alpn?: string;
+  timings: AttestationTimings;
+  /** "cache" when the verifier reused cached collateral, "pccs" when it was fetched. */
+  collateralSource?: "cache" | "pccs";
+  /** PCCS the collateral was fetched from (when `collateralSource` is "pccs"). */
+  pccsUrl?: string;
+  /** FMSPC of the attested platform (upper-case hex). */
+  fmspc?: string;
+  tcbStatus: string;
+}
+
+/** A single request header as a `[name, value]` pair. */
+export type HeaderEntry = [string, string];
+
+/**
+ * Request body for `AtlsHttp.fetch`. A `Uint8Array` is sent with `Content-Length`;
+ * a `ReadableStream` of unknown length is sent with `Transfer-Encoding: chunked`.
+ */
+export type AtlsRequestBody = Uint8Array | ReadableStream<Uint8Array>;
+
+/**
+ * Trailer fields sent after a chunked request body. A function is called once the
+ * body has been sent (e.g. to add a checksum); its names must then be declared in
+ * a `Trailer` request header, which is set automatically for a fixed list.
+ */
+export type AtlsRequestTrailers = HeaderEntry[] | (() => HeaderEntry[] | Promise<HeaderEntry[]>);
+
+/** Limits and keepalive settings for `AtlsHttp.connect`. */
+export interface AtlsHttpOptions {
+  /** Interval of WebSocket keepalive messages in milliseconds (0 or unset: off). */
+  keepAliveIntervalMs?: number;
+  /** Maximum size of response headers in bytes (default ~400 KiB, minimum 8192). */
+  maxHeaderSize?: number;
+  /** Maximum decoded response body size in bytes; larger bodies error with `BODY_TOO_LARGE`. */
+  maxBodySize?: number;
+  /**
+   * With `Expect: 100-continue`, how long to wait for the interim response before
+   * sending the body anyway (default 1000).
+   */
+  expectContinueTimeoutMs?: number;
+  /** ALPN protocols to offer (default `["http/1.1"]`); must include "http/1.1". */
+  alpn?: string[];
+  /**
+   * Server name sent in the TLS ClientHello, when it differs from the `serverName`
+   * the attestation is verified for (e.g. behind an SNI-routing ingress).
+   */
+  sni?: string;
+}
+
+/** TLS settings for `AttestedStream.connect`. */
+export interface AttestedStreamOptions {
+  /** ALPN protocols to offer (default `["http/1.1"]`). */
+  alpn?: string[];
+  /** Server name sent in the TLS ClientHello, when it differs from `serverName`. */
+  sni?: string;
+  /** Bytes read from the connection per `readable` chunk (default 16384). */
+  readChunkSize?: number;
+}
+
+/** Response returned by `AtlsHttp.fetch`. */
+export interface AtlsHttpResponse {
+  status: number;
+  statusText: string;
+  /** Header values by lowercase name; repeated headers are joined with ", ". */
+  headers: Record<string, string>;
+  /** Every header line in order, so repeated headers (e.g. Set-Cookie) stay separate. */
+  rawHeaders: HeaderEntry[];
+  body: ReadableStream<Uint8Array>;
+  /** Trailer fields sent after the last chunk; `null` if the body did not complete. */
+  trailers: Promise<HeaderEntry[] | null>;
+}
+
+/** Why an `AtlsHttp` connection ended, from `AtlsHttp.closed()`. */
+export interface AtlsCloseEvent {
+  code: "CONNECTION_CLOSED" | "TUNNEL_CLOSED" | "TLS_ALERT" | "PROTOCOL_ERROR";
+  message: string;
+  /** TLS alert description for `TLS_ALERT` (e.g. "HandshakeFailure"). */
+  alert?: string;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    /// `AttestationResult` as seen from JavaScript.
+    #[wasm_bindgen(typescript_type = "AttestationResult")]
+    pub type JsAttestationResult;
+
+    /// Optional list of request headers.
+    #[wasm_bindgen(typescript_type = "HeaderEntry[] | null | undefined")]
+    pub type JsHeaderEntries;
+
+    /// `AtlsHttpResponse` as seen from JavaScript.
+    #[wasm_bindgen(typescript_type = "AtlsHttpResponse")]
+    pub type JsAtlsHttpResponse;
+
+    /// Promise of the `AtlsCloseEvent` ending a connection.
+    #[wasm_bindgen(typescript_type = "Promise<AtlsCloseEvent>")]
+    pub type JsClosePromise;
+
+    /// `ConnectionDiagnostics` as seen from JavaScript.
+    #[wasm_bindgen(typescript_type = "ConnectionDiagnostics")]
+    pub type JsConnectionDiagnostics;
+
+    /// Optional request body.
+    #[wasm_bindgen(typescript_type = "AtlsRequestBody | null | undefined")]
+    pub type JsRequestBody;
+
+    /// Optional request trailers.
+    #[wasm_bindgen(typescript_type = "AtlsRequestTrailers | null | undefined")]
+    pub type JsRequestTrailers;
+
+    /// Optional `AtlsHttpOptions`.
+    #[wasm_bindgen(typescript_type = "AtlsHttpOptions | null | undefined")]
+    pub type JsHttpOptions;
+
+    /// Optional `AttestedStreamOptions`.
+    #[wasm_bindgen(typescript_type = "AttestedStreamOptions | null | undefined")]
+    pub type JsStreamOptions;
+
+    /// Verification policy: a policy object, its JSON string, or nothing for the default.
+    #[wasm_bindgen(typescript_type = "object | string | null | undefined")]
+    pub type JsPolicy;
+
+    /// Policy fields replacing those of a preset.
+    #[wasm_bindgen(typescript_type = "Record<string, unknown> | null | undefined")]
+    pub type JsPolicyOverrides;
+}
+
+impl AttestationSummary {
+    fn to_js(&self) -> Result<JsAttestationResult, JsValue> {
+        serde_wasm_bindgen::to_value(self)
+            .map(JsCast::unchecked_into)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+// ============================================================================
+// Policy
+// ============================================================================
+
+/// Parse a verification policy from JavaScript.
+///
+/// Accepts a policy object (`{ type: "dstack_tdx", ... }`), the same policy as a
+/// JSON string, or `null`/`undefined` for [`Policy::default()`]. The policy is
+/// validated here so configuration mistakes are reported before connecting.
+fn parse_policy(policy_js: JsPolicy) -> Result<Policy, JsValue> {
+    let invalid = |msg: String| js_error(&msg, ErrorCode::InvalidConfiguration);
+    let policy_js: JsValue = policy_js.into();
+
+    let policy: Policy = if policy_js.is_null() || policy_js.is_undefined() {
+        Policy::default()
+    } else if let Some(json) = policy_js.as_string() {
+        serde_json::from_str(&json).map_err(|e| invalid(format!("invalid policy JSON: {e}")))?
+    } else {
+        serde_wasm_bindgen::from_value(policy_js)
+            .map_err(|e| invalid(format!("invalid policy: {e}")))?
+    };
+
+    match &policy {
+        Policy::DstackTdx(tdx) => tdx.validate().map_err(atls_error)?,
+    }
+    Ok(policy)
+}
+
+/// Ready-made verification policies, exported to JavaScript as `Policy`.
+///
+/// Each constructor returns a plain policy object accepted wherever a policy is
+/// (e.g. `createAtlsFetch({ policy })`). Optional `overrides` replace fields of
+/// the preset, and the result is validated before it is returned.
+#[wasm_bindgen(js_name = Policy)]
+pub struct PolicyPresets {
+    _private: (),
+}
+
+#[wasm_bindgen(js_class = Policy)]
+impl PolicyPresets {
+    /// Relaxed policy for local development: common TCB statuses are accepted and
+    /// runtime verification (bootchain, app_compose, OS image) is skipped.
+    /// Never use it in production.
+    pub fn dev(overrides: JsPolicyOverrides) -> Result<JsValue, JsValue> {
+        policy_preset(DstackTdxPolicy::dev(), overrides, &[])
+    }
+
+    /// Production policy: `UpToDate` platforms only, with runtime verification.
+    /// `overrides` must supply `expected_bootchain`, `app_compose` and
+    /// `os_image_hash`.
+    pub fn strict(overrides: JsPolicyOverrides) -> Result<JsValue, JsValue> {
+        policy_preset(
+            DstackTdxPolicy::default(),
+            overrides,
+            &["expected_bootchain", "app_compose", "os_image_hash"],
+        )
+    }
+
+    /// Policy pinned to the OS image and bootchain of a published dstack
+    /// release (e.g. `"0.5.4.1-nvidia"`). `rtmr0` depends on the VM shape and
+    /// comes from `atlas measure`. `overrides` must supply the deployment's
+    /// `app_compose`.
+    #[wasm_bindgen(js_name = forDstackRelease)]
+    pub fn for_dstack_release(
+        tag: &str,
+        rtmr0: &str,
+        overrides: JsPolicyOverrides,
+    ) -> Result<JsValue, JsValue> {
+        let policy = DstackTdxPolicy::for_dstack_release(tag, rtmr0).map_err(atls_error)?;
+        policy_preset(policy, overrides, &["app_compose"])
+    }
+}
+
+/// Serialize `preset` as a policy object with `overrides` applied, failing if
+/// any of the `required` fields is still unset.
+fn policy_preset(
+    preset: DstackTdxPolicy,
+    overrides: JsPolicyOverrides,
+    required: &[&str],
+) -> Result<JsValue, JsValue> {
+    let invalid = |msg: String| js_error(&msg, ErrorCode::InvalidConfiguration);
+    let mut value = serde_json::to_value(Policy::DstackTdx(preset))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let overrides: JsValue = overrides.into();
+    if !overrides.is_null() && !overrides.is_undefined() {
+        let overrides: serde_json::Map<String, serde_json::Value> =
+            serde_wasm_bindgen::from_value(overrides)
+                .map_err(|e| invalid(format!("invalid policy overrides: {e}")))?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.extend(overrides);
+        }
+    }
+
+    let missing: Vec<_> = required
+        .iter()
+        .filter(|field| value.get(**field).map_or(true, serde_json::Value::is_null))
+        .collect();
+    if !missing.is_empty() {
+        return Err(invalid(format!(
+            "policy is missing required fields: {missing:?}"
+        )));
+    }
+
+    let policy: Policy = serde_json::from_value(value.clone())
+        .map_err(|e| invalid(format!("invalid policy: {e}")))?;
+    match &policy {
+        Policy::DstackTdx(tdx) => tdx.validate().map_err(atls_error)?,
+    }
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Smallest header buffer hyper accepts.
+const MIN_HEADER_SIZE: usize = 8192;
+
+/// Bytes read per `AttestedStream.readable` chunk unless `readChunkSize` is set.
+const DEFAULT_READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Upper bound for `readChunkSize`: each pull allocates a buffer this large.
+const MAX_READ_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// The only application protocol `AtlsHttp` speaks.
+const HTTP_ALPN: &str = "http/1.1";
+
+/// Wait for `100 Continue` before sending the body anyway (as curl does).
+const DEFAULT_EXPECT_CONTINUE_TIMEOUT_MS: u32 = 1000;
+
+/// Size limits and keepalive settings for an [`AtlsHttp`] connection.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct HttpOptions {
+    keep_alive_interval_ms: Option<u32>,
+    max_header_size: Option<usize>,
+    max_body_size: Option<u64>,
+    expect_continue_timeout_ms: Option<u32>,
+    alpn: Option<Vec<String>>,
+    sni: Option<String>,
+}
+
+fn parse_http_options(options_js: JsHttpOptions) -> Result<HttpOptions, JsValue> {
+    let invalid = |msg: String| js_error(&msg, ErrorCode::InvalidConfiguration);
+    let options_js: JsValue = options_js.into();
+    if options_js.is_null() || options_js.is_undefined() {
+        return Ok(HttpOptions::default());
+    }
+    let options: HttpOptions = serde_wasm_bindgen::from_value(options_js)
+        .map_err(|e| invalid(format!("invalid HTTP options: {e}")))?;
+    if let Some(size) = options.max_header_size.filter(|&s| s < MIN_HEADER_SIZE) {
+        return Err(invalid(format!(
+            "maxHeaderSize must be at least {MIN_HEADER_SIZE} bytes, got {size}"
+        )));
+    }
+    if let Some(alpn) = options
+        .alpn
+        .as_ref()
+        .filter(|a| !a.iter().any(|p| p == HTTP_ALPN))
+    {
+        return Err(invalid(format!(
+            "alpn must include \"{HTTP_ALPN}\", got {alpn:?}"
+        )));
+    }
+    Ok(options)
+}
+
+/// TLS settings for an [`AttestedStream`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct StreamOptions {
+    alpn: Option<Vec<String>>,
+    sni: Option<String>,
+    read_chunk_size: Option<usize>,
+}
+
+fn parse_stream_options(options_js: JsStreamOptions) -> Result<StreamOptions, JsValue> {
+    let options_js: JsValue = options_js.into();
+    if options_js.is_null() || options_js.is_undefined() {
+        return Ok(StreamOptions::default());
+    }
+    let invalid = |msg: String| js_error(&msg, ErrorCode::InvalidConfiguration);
+    let options: StreamOptions = serde_wasm_bindgen::from_value(options_js)
+        .map_err(|e| invalid(format!("invalid stream options: {e}")))?;
+    if let Some(size) = options
+        .read_chunk_size
+        .filter(|&s| s == 0 || s > MAX_READ_CHUNK_SIZE)
+    {
+        return Err(invalid(format!(
+            "readChunkSize must be between 1 and {MAX_READ_CHUNK_SIZE}, got {size}"
+        )));
+    }
+    Ok(options)
+}
+
+/// Perform the aTLS handshake over `tunnel`, offering `alpn` (default `http/1.1`).
+///
+/// `sni`, when set, replaces `server_name` in the ClientHello; the attestation is
+/// still verified for `server_name`.
+async fn connect_attested(
+    tunnel: Tunnel,
+    server_name: &str,
+    sni: Option<&str>,
+    policy: Policy,
+    alpn: Option<Vec<String>>,
+) -> Result<(TlsStream<Tunnel>, Report), JsValue> {
+    let alpn = Some(alpn.unwrap_or_else(|| vec![HTTP_ALPN.into()]));
+    match sni {
+        Some(sni) => atls_connect_with_sni(tunnel, sni, server_name, policy, alpn).await,
+        None => atls_connect(tunnel, server_name, policy, alpn).await,
+    }
+    .map_err(atls_error)
+}
+
+/// Error for a response body over `maxBodySize`, with `code = "BODY_TOO_LARGE"`.
+fn body_too_large(limit: u64) -> JsValue {
+    let error = web_sys::js_sys::Error::new(&format!("response body exceeds {limit} bytes"));
+    let _ = Reflect::set(&error, &"code".into(), &"BODY_TOO_LARGE".into());
+    error.into()
+}
 
 // ============================================================================
 // App Compose Utilities
@@ -49,9 +465,102 @@ pub fn merge_with_default_app_compose_js(user_compose: JsValue) -> Result<JsValu
         .map_err(|e| JsValue::from_str(&format!("failed to serialize merged app_compose: {e}")))
 }
 
-type WsIo = IoStream<WsStreamIo, Vec<u8>>;
+// ============================================================================
+// Errors
+// ============================================================================
+
+/// Build a JS `Error` with a machine-readable `code` property.
+fn js_error(message: &str, code: ErrorCode) -> JsValue {
+    let error = web_sys::js_sys::Error::new(message);
+    let _ = Reflect::set(&error, &"code".into(), &JsValue::from_str(code.as_str()));
+    error.into()
+}
 
-fn create_readable_stream(reader: ReadHalf<TlsStream<WsIo>>) -> web_sys::ReadableStream {
+/// Convert a core verification error into a JS `Error` carrying its `code`.
+fn atls_error(e: AtlsVerificationError) -> JsValue {
+    js_error(&e.to_string(), e.code())
+}
+
+/// Error to reject with when `signal` is aborted, matching `fetch` semantics.
+///
+/// Uses `signal.reason` when set, otherwise a `DOMException` named `AbortError`.
+fn abort_error(signal: &AbortSignal) -> JsValue {
+    let reason = signal.reason();
+    if !reason.is_undefined() {
+        return reason;
+    }
+    web_sys::DomException::new_with_message_and_name("The operation was aborted.", "AbortError")
+        .map(Into::into)
+        .unwrap_or_else(|e| e)
+}
+
+/// Future that resolves once an `AbortSignal` fires.
+///
+/// The `abort` listener is removed when the future is dropped.
+struct Aborted {
+    signal: AbortSignal,
+    rx: oneshot::Receiver<()>,
+    listener: Closure<dyn FnMut()>,
+}
+
+impl Aborted {
+    fn new(signal: &AbortSignal) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        let listener = Closure::wrap(Box::new(move || {
+            if let Some(tx) = tx.take() {
+                let _ = tx.send(());
+            }
+        }) as Box<dyn FnMut()>);
+        let _ = signal
+            .add_event_listener_with_callback("abort", listener.as_ref().unchecked_ref());
+        Self {
+            signal: signal.clone(),
+            rx,
+            listener,
+        }
+    }
+}
+
+impl Future for Aborted {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.signal.aborted() {
+            return Poll::Ready(());
+        }
+        match Pin::new(&mut self.rx).poll(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(()),
+            // Sender dropped without firing: never resolve.
+            Poll::Ready(Err(_)) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Aborted {
+    fn drop(&mut self) {
+        let _ = self
+            .signal
+            .remove_event_listener_with_callback("abort", self.listener.as_ref().unchecked_ref());
+    }
+}
+
+/// Await `fut`, or return `None` if `signal` aborts first.
+async fn abortable<F: Future + Unpin>(fut: F, signal: Option<&AbortSignal>) -> Option<F::Output> {
+    match signal {
+        Some(signal) => match future::select(fut, Aborted::new(signal)).await {
+            Either::Left((output, _)) => Some(output),
+            Either::Right(_) => None,
+        },
+        None => Some(fut.await),
+    }
+}
+
+
+fn create_readable_stream(
+    reader: ReadHalf<TlsStream<Tunnel>>,
+    chunk_size: usize,
+) -> web_sys::ReadableStream {
     let reader = Rc::new(RefCell::new(reader));
     let underlying_source = Object::new();
 
@@ -59,7 +568,7 @@ fn create_readable_stream(reader: ReadHalf<TlsStream<WsIo>>) -> web_sys::Readabl
     let pull = Closure::wrap(Box::new(move |controller: ReadableStreamDefaultController| {
         let reader = reader_clone.clone();
         let promise = wasm_bindgen_futures::future_to_promise(async move {
-            let mut buf = vec![0u8; 16 * 1024];
+            let mut buf = vec![0u8; chunk_size];
             let mut reader_ref = reader.borrow_mut();
             match reader_ref.read(&mut buf).await {
                 Ok(0) => {
@@ -91,8 +600,108 @@ fn create_readable_stream(reader: ReadHalf<TlsStream<WsIo>>) -> web_sys::Readabl
 pub struct AttestationSummary {
     pub trusted: bool,
     pub tee_type: String,
+    /// Workload measurement (hex MRTD for TDX). Shared by every app on one OS image.
+    pub measurement: Option<String>,
+    /// Digest of the full measurement set (see `TdxReport::measurement_digest`).
+    pub measurement_digest: Option<String>,
+    /// Digest of the app deployment (see `TdxReport::app_digest`), compared
+    /// when a dead connection is replaced to detect redeployments.
+    pub app_digest: Option<String>,
     pub tcb_status: String,
     pub advisory_ids: Vec<String>,
+    pub timings: AttestationTimings,
+}
+
+impl From<&Report> for AttestationSummary {
+    fn from(report: &Report) -> Self {
+        match report {
+            Report::Tdx(tdx) => AttestationSummary {
+                trusted: true,
+                tee_type: "Tdx".to_string(),
+                measurement: tdx.report.as_td10().map(|td| hex::encode(td.mr_td)),
+                measurement_digest: tdx.measurement_digest(),
+                app_digest: tdx.app_digest(),
+                tcb_status: tdx.status.clone(),
+                advisory_ids: tdx.advisory_ids.clone(),
+                timings: (&tdx.timings).into(),
+            },
+        }
+    }
+}
+
+/// Connection details for debugging attestation issues from JavaScript.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionDiagnostics {
+    pub tls_version: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub alpn: Option<String>,
+    pub timings: AttestationTimings,
+    pub collateral_source: Option<&'static str>,
+    pub pccs_url: Option<String>,
+    pub fmspc: Option<String>,
+    pub tcb_status: String,
+}
+
+impl ConnectionDiagnostics {
+    fn new(tls: &TlsStream<Tunnel>, report: &Report) -> Self {
+        let (_, conn) = tls.get_ref();
+        let Report::Tdx(tdx) = report;
+        let (collateral_source, pccs_url) = match &tdx.collateral_source {
+            Some(CollateralSource::Cache) => (Some("cache"), None),
+            Some(CollateralSource::Pccs(url)) => (Some("pccs"), Some(url.clone())),
+            None => (None, None),
+        };
+        Self {
+            // Debug names are e.g. `TLSv1_3`
+            tls_version: conn
+                .protocol_version()
+                .map(|v| format!("{v:?}").replace('_', ".")),
+            cipher_suite: conn
+                .negotiated_cipher_suite()
+                .map(|s| format!("{:?}", s.suite())),
+            alpn: conn
+                .alpn_protocol()
+                .map(|p| String::from_utf8_lossy(p).into_owned()),
+            timings: report.timings().into(),
+            collateral_source,
+            pccs_url,
+            fmspc: tdx.fmspc.clone(),
+            tcb_status: tdx.status.clone(),
+        }
+    }
+
+    fn to_js(&self) -> Result<JsConnectionDiagnostics, JsValue> {
+        serde_wasm_bindgen::to_value(self)
+            .map(JsCast::unchecked_into)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+/// Per-phase connection durations in milliseconds.
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttestationTimings {
+    pub tcp_connect_ms: Option<f64>,
+    pub tls_handshake_ms: Option<f64>,
+    pub evidence_exchange_ms: Option<f64>,
+    pub collateral_fetch_ms: Option<f64>,
+    pub verification_ms: Option<f64>,
+    pub total_ms: f64,
+}
+
+impl From<&PhaseTimings> for AttestationTimings {
+    fn from(timings: &PhaseTimings) -> Self {
+        let ms = |d: Option<std::time::Duration>| d.map(|d| d.as_secs_f64() * 1000.0);
+        Self {
+            tcp_connect_ms: ms(timings.tcp_connect),
+            tls_handshake_ms: ms(timings.tls_handshake),
+            evidence_exchange_ms: ms(timings.evidence_exchange),
+            collateral_fetch_ms: ms(timings.collateral_fetch),
+            verification_ms: ms(timings.verification),
+            total_ms: timings.total().as_secs_f64() * 1000.0,
+        }
+    }
 }
 
 /// An attested TLS stream over a WebSocket connection.
@@ -102,8 +711,9 @@ pub struct AttestationSummary {
 /// while keeping the write path simple.
 #[wasm_bindgen]
 pub struct AttestedStream {
-    writer: Rc<RefCell<Option<WriteHalf<TlsStream<WsIo>>>>>,
+    writer: Rc<RefCell<Option<WriteHalf<TlsStream<Tunnel>>>>>,
     attestation: AttestationSummary,
+    diagnostics: ConnectionDiagnostics,
     readable: web_sys::ReadableStream,
 }
 
@@ -117,50 +727,52 @@ impl AttestedStream {
     /// - `attestation()`: Attestation verification result
     ///
     /// # Arguments
-    /// * `ws_url` - WebSocket URL (e.g., "ws://proxy:9000?target=host:443")
-    /// * `server_name` - TLS server name for SNI
-    /// * `policy` - Verification policy
+    /// * `ws_url` - WebSocket URL (e.g., "ws://proxy:9000?target=host:443"), or an
+    ///   `https://` WebTransport relay URL
+    /// * `server_name` - Hostname the attestation is verified for (and the SNI,
+    ///   unless `options.sni` is set)
+    /// * `policy` - Verification policy (object or JSON string)
+    /// * `options` - Optional `{ alpn, sni }`
     #[wasm_bindgen(js_name = connect)]
     pub async fn connect(
         ws_url: &str,
         server_name: &str,
-        policy_js: JsValue,
+        policy_js: JsPolicy,
+        options: JsStreamOptions,
     ) -> Result<AttestedStream, JsValue> {
-        // Parse policy from JS object
-        let policy: Policy = serde_wasm_bindgen::from_value(policy_js)
-            .map_err(|e| JsValue::from_str(&format!("invalid policy: {e}")))?;
+        let policy = parse_policy(policy_js)?;
+        let options = parse_stream_options(options)?;
 
-        // 1. Establish WebSocket tunnel
-        let (_meta, ws_stream) = WsMeta::connect(ws_url, None)
-            .await
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        // 1. Establish WebSocket (or WebTransport) tunnel
+        let connect_timer = Stopwatch::start();
+        let tunnel = Tunnel::connect(ws_url, None).await?;
+        let tcp_connect = connect_timer.elapsed();
 
         // 2. Perform aTLS protocol
-        let (tls, report) = atls_connect(
-            ws_stream.into_io(),
+        let (tls, mut report) = connect_attested(
+            tunnel,
             server_name,
+            options.sni.as_deref(),
             policy,
-            Some(vec!["http/1.1".into()]),
+            options.alpn,
         )
-        .await
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        .await?;
 
-        let (reader, writer) = tls.split();
+        report.timings_mut().tcp_connect = Some(tcp_connect);
+        let attestation = AttestationSummary::from(&report);
+        let diagnostics = ConnectionDiagnostics::new(&tls, &report);
 
-        let readable = create_readable_stream(reader);
+        let (reader, writer) = tls.split();
 
-        let attestation = match &report {
-            atlas_rs::Report::Tdx(verified) => AttestationSummary {
-                trusted: true,
-                tee_type: "Tdx".to_string(),
-                tcb_status: verified.status.clone(),
-                advisory_ids: verified.advisory_ids.clone(),
-            },
-        };
+        let readable = create_readable_stream(
+            reader,
+            options.read_chunk_size.unwrap_or(DEFAULT_READ_CHUNK_SIZE),
+        );
 
         Ok(AttestedStream {
             writer: Rc::new(RefCell::new(Some(writer))),
             attestation,
+            diagnostics,
             readable,
         })
     }
@@ -175,9 +787,14 @@ impl AttestedStream {
 
     /// Get the attestation result from the aTLS protocol.
     #[wasm_bindgen(js_name = attestation)]
-    pub fn attestation(&self) -> Result<JsValue, JsValue> {
-        serde_wasm_bindgen::to_value(&self.attestation)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    pub fn attestation(&self) -> Result<JsAttestationResult, JsValue> {
+        self.attestation.to_js()
+    }
+
+    /// Negotiated TLS parameters, timings and collateral details of this connection.
+    #[wasm_bindgen(js_name = diagnostics)]
+    pub fn diagnostics(&self) -> Result<JsConnectionDiagnostics, JsValue> {
+        self.diagnostics.to_js()
     }
 
     /// Send data to the TEE over the attested TLS connection.
@@ -230,8 +847,19 @@ use hyper::client::conn::http1::SendRequest;
 pub struct AtlsHttp {
     /// The hyper HTTP/1.1 sender - can make multiple requests on the same connection.
     /// Stored as Option to allow detecting when the connection is closed.
-    sender: Rc<RefCell<Option<SendRequest<Full<Bytes>>>>>,
+    sender: Rc<RefCell<Option<SendRequest<RequestBody>>>>,
     attestation: AttestationSummary,
+    diagnostics: ConnectionDiagnostics,
+    /// Response bodies larger than this (after decompression) are aborted.
+    max_body_size: Option<u64>,
+    /// How long an `Expect: 100-continue` request waits before sending its body.
+    expect_continue_timeout_ms: u32,
+    /// Body refused by an early final response; dropping it shuts the connection down.
+    held_upload: RefCell<Option<HeldUpload>>,
+    /// How the connection ended, set when the hyper connection driver returns.
+    close_event: Rc<RefCell<Option<CloseEvent>>>,
+    /// Completes once `close_event` is set.
+    driver_done: Shared<oneshot::Receiver<()>>,
 }
 
 #[wasm_bindgen]
@@ -242,71 +870,102 @@ impl AtlsHttp {
     /// The connection uses HTTP keep-alive by default.
     ///
     /// # Arguments
-    /// * `ws_url` - WebSocket URL (e.g., "ws://proxy:9000?target=host:443")
-    /// * `server_name` - TLS server name for SNI
-    /// * `policy` - Verification policy
+    /// * `ws_url` - WebSocket URL (e.g., "ws://proxy:9000?target=host:443"), or an
+    ///   `https://` WebTransport relay URL
+    /// * `server_name` - Hostname the attestation is verified for (and the SNI,
+    ///   unless `options.sni` is set)
+    /// * `policy` - Verification policy (object or JSON string)
+    /// * `options` - Optional `AtlsHttpOptions` (limits, keepalive, `alpn`, `sni`)
     #[wasm_bindgen(js_name = connect)]
     pub async fn connect(
         ws_url: &str,
         server_name: &str,
-        policy_js: JsValue,
+        policy_js: JsPolicy,
+        options: JsHttpOptions,
     ) -> Result<AtlsHttp, JsValue> {
-        // Parse policy from JS object
-        let policy: Policy = serde_wasm_bindgen::from_value(policy_js)
-            .map_err(|e| JsValue::from_str(&format!("invalid policy: {e}")))?;
+        let policy = parse_policy(policy_js)?;
+        let options = parse_http_options(options)?;
 
-        let (_meta, ws_stream) = WsMeta::connect(ws_url, None)
-            .await
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let connect_timer = Stopwatch::start();
+        let tunnel = Tunnel::connect(ws_url, options.keep_alive_interval_ms).await?;
+        let tcp_connect = connect_timer.elapsed();
 
-        let (tls, report) = atls_connect(
-            ws_stream.into_io(),
+        let (tls, mut report) = connect_attested(
+            tunnel,
             server_name,
+            options.sni.as_deref(),
             policy,
-            Some(vec!["http/1.1".into()]),
+            options.alpn,
         )
-        .await
-        .map_err(|e| JsValue::from_str(&e.to_string()))?;
-
-        let attestation = match &report {
-            atlas_rs::Report::Tdx(verified) => AttestationSummary {
-                trusted: true,
-                tee_type: "Tdx".to_string(),
-                tcb_status: verified.status.clone(),
-                advisory_ids: verified.advisory_ids.clone(),
-            },
-        };
+        .await?;
+
+        report.timings_mut().tcp_connect = Some(tcp_connect);
+        let attestation = AttestationSummary::from(&report);
+        let diagnostics = ConnectionDiagnostics::new(&tls, &report);
+        if let Some(alpn) = diagnostics.alpn.as_deref().filter(|&p| p != HTTP_ALPN) {
+            return Err(js_error(
+                &format!("server negotiated unsupported protocol \"{alpn}\""),
+                ErrorCode::InvalidConfiguration,
+            ));
+        }
 
         // Wrap TLS stream for hyper compatibility
         let io = HyperIo::new(tls);
 
         // Perform HTTP/1.1 handshake with hyper
-        let (sender, conn) = http1::handshake(io)
+        let mut builder = http1::Builder::new();
+        if let Some(max_header_size) = options.max_header_size {
+            builder.max_buf_size(max_header_size);
+        }
+        let (sender, conn) = builder
+            .handshake(io)
             .await
             .map_err(|e| JsValue::from_str(&format!("HTTP handshake failed: {e}")))?;
 
         // Spawn the connection driver in the background
-        // This handles the actual HTTP protocol I/O and keeps the connection alive
+        // This handles the actual HTTP protocol I/O and keeps the connection alive.
+        // How it ends is recorded for `closed()` and later request errors.
+        let close_event = Rc::new(RefCell::new(None));
+        let (done_tx, done_rx) = oneshot::channel();
+        let driver_event = close_event.clone();
         wasm_bindgen_futures::spawn_local(async move {
-            if let Err(e) = conn.await {
-                // Log connection errors (in WASM, we can't easily propagate these)
-                web_sys::console::warn_1(&JsValue::from_str(&format!(
-                    "HTTP connection error: {e}"
-                )));
-            }
+            let event = match conn.await {
+                Ok(()) => CloseEvent::clean(),
+                Err(e) => {
+                    web_sys::console::warn_1(&JsValue::from_str(&format!(
+                        "HTTP connection error: {e}"
+                    )));
+                    CloseEvent::from_error(&e)
+                }
+            };
+            *driver_event.borrow_mut() = Some(event);
+            let _ = done_tx.send(());
         });
 
         Ok(AtlsHttp {
             sender: Rc::new(RefCell::new(Some(sender))),
             attestation,
+            diagnostics,
+            max_body_size: options.max_body_size,
+            expect_continue_timeout_ms: options
+                .expect_continue_timeout_ms
+                .unwrap_or(DEFAULT_EXPECT_CONTINUE_TIMEOUT_MS),
+            held_upload: RefCell::new(None),
+            close_event,
+            driver_done: done_rx.shared(),
         })
     }
 
     /// Get attestation result.
     #[wasm_bindgen(js_name = attestation)]
-    pub fn attestation(&self) -> Result<JsValue, JsValue> {
-        serde_wasm_bindgen::to_value(&self.attestation)
-            .map_err(|e| JsValue::from_str(&e.to_string()))
+    pub fn attestation(&self) -> Result<JsAttestationResult, JsValue> {
+        self.attestation.to_js()
+    }
+
+    /// Negotiated TLS parameters, timings and collateral details of this connection.
+    #[wasm_bindgen(js_name = diagnostics)]
+    pub fn diagnostics(&self) -> Result<JsConnectionDiagnostics, JsValue> {
+        self.diagnostics.to_js()
     }
 
     /// Check if the connection is ready for another request.
@@ -325,33 +984,91 @@ impl AtlsHttp {
     #[wasm_bindgen(js_name = close)]
     pub fn close(&self) {
         self.sender.borrow_mut().take();
+        self.held_upload.borrow_mut().take();
+    }
+
+    /// Resolve with an `AtlsCloseEvent` (`{ code, message, alert? }`) once the
+    /// connection has ended, whether closed locally, by the server, or because
+    /// the tunnel dropped. Lets callers react before the next request fails.
+    #[wasm_bindgen(js_name = closed)]
+    pub fn closed(&self) -> JsClosePromise {
+        let done = self.driver_done.clone();
+        let event = self.close_event.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let _ = done.await;
+            let event = event.borrow().clone().unwrap_or_else(CloseEvent::clean);
+            event.to_js()
+        })
+        .unchecked_into()
+    }
+
+    /// Error for a request hyper could not complete, coded with the reason the
+    /// connection ended when it is known.
+    fn request_error(&self, error: &hyper::Error) -> JsValue {
+        let recorded = self.close_event.borrow().clone();
+        let event = recorded
+            .filter(|e| e.code != CloseCode::Closed)
+            .unwrap_or_else(|| CloseEvent::from_error(error));
+        match event.code {
+            CloseCode::TunnelClosed | CloseCode::TlsAlert => event.to_js_error("Request failed"),
+            _ => JsValue::from_str(&format!("Request failed: {error}")),
+        }
     }
 
     /// Perform an HTTP request and return response with streaming body.
     ///
-    /// Returns a JS object: { status, statusText, headers, body }
-    /// where body is a native ReadableStream.
+    /// Returns a JS object: { status, statusText, headers, rawHeaders, body, trailers }
+    /// where body is a native ReadableStream and trailers a Promise settled once
+    /// the body has been read.
     ///
     /// This method uses hyper's HTTP/1.1 client which properly validates
     /// headers (preventing CRLF injection) and handles transfer encodings.
     ///
     /// The connection can be reused for subsequent requests after the response
     /// body is fully consumed. Use `isReady()` to check availability.
+    ///
+    /// If `signal` is given and aborts before the response headers arrive, the
+    /// connection is closed (its protocol state is unknown) and the call rejects
+    /// with the abort reason (an `AbortError` `DOMException` by default). Aborting
+    /// while the body is being read errors the body stream the same way.
+    ///
+    /// A `ReadableStream` body is read as the request is written and sent with
+    /// `Transfer-Encoding: chunked`, ending with the terminal chunk and any
+    /// `trailers`. Trailers on a `Uint8Array` body also switch it to chunked.
+    ///
+    /// If `headers` include `Expect: 100-continue`, the body is only sent once
+    /// the server answers `100 Continue` (or after `expectContinueTimeoutMs`).
+    /// When the final response arrives first, the body is never sent and the
+    /// connection is not reused.
     #[wasm_bindgen(js_name = fetch)]
+    #[allow(clippy::too_many_arguments)]
     pub async fn fetch(
         &self,
         method: &str,
         path: &str,
         host: &str,
-        headers_js: JsValue,
-        body: Option<Vec<u8>>,
-    ) -> Result<JsValue, JsValue> {
+        headers_js: JsHeaderEntries,
+        body: JsRequestBody,
+        signal: Option<AbortSignal>,
+        trailers: JsRequestTrailers,
+    ) -> Result<JsAtlsHttpResponse, JsValue> {
+        if let Some(signal) = signal.as_ref().filter(|s| s.aborted()) {
+            return Err(abort_error(signal));
+        }
+
         // Borrow the sender mutably to send the request
         // We don't take() it - the connection stays alive for reuse
         let mut sender_guard = self.sender.borrow_mut();
-        let sender = sender_guard
-            .as_mut()
-            .ok_or_else(|| JsValue::from_str("connection closed"))?;
+        let sender = sender_guard.as_mut().ok_or_else(|| {
+            self.close_event
+                .borrow()
+                .as_ref()
+                .filter(|e| e.code != CloseCode::Closed)
+                .map_or_else(
+                    || JsValue::from_str("connection closed"),
+                    |e| e.to_js_error("connection closed"),
+                )
+        })?;
 
         // Check if the connection is ready (not busy with another request)
         if !sender.is_ready() {
@@ -365,16 +1082,31 @@ impl AtlsHttp {
             if headers_js.is_null() || headers_js.is_undefined() {
                 vec![]
             } else {
-                serde_wasm_bindgen::from_value(headers_js)
+                serde_wasm_bindgen::from_value(headers_js.into())
                     .map_err(|e| JsValue::from_str(&format!("Invalid headers: {e}")))?
             };
 
-        // Build HTTP request using hyper's type-safe Request builder
-        // This prevents CRLF injection as hyper validates header names and values
+        // Reject anything that could smuggle extra lines into the request
+        // (CR/LF, control characters, malformed tokens) before hyper sees it
         let path = if path.is_empty() { "/" } else { path };
+        request::validate_method(method)
+            .and_then(|()| request::validate_path(path))
+            .and_then(|()| request::validate_host(host))
+            .and_then(|()| {
+                custom_headers
+                    .iter()
+                    .try_for_each(|(name, value)| request::validate_header(name, value))
+            })
+            .map_err(|e| e.to_js())?;
+
+        let trailers = RequestTrailers::from_js(trailers.into())?;
+        let trailer_declaration = trailers.declaration();
+        let body = RequestBody::from_js(body.into(), trailers)?;
+        let expect_continue = custom_headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("expect") && value.trim().eq_ignore_ascii_case("100-continue")
+        });
 
-        let body_bytes = body.unwrap_or_default();
-        let body = Full::new(Bytes::from(body_bytes.clone()));
+        // Build HTTP request using hyper's type-safe Request builder
 
         // Note: We intentionally do NOT set "Connection: close" here
         // This allows HTTP/1.1 keep-alive for connection reuse
@@ -392,20 +1124,57 @@ impl AtlsHttp {
             }
         }
 
-        // Add Content-Length for non-empty bodies
-        if !body_bytes.is_empty() {
-            builder = builder.header("Content-Length", body_bytes.len().to_string());
+        // Advertise supported encodings unless the caller chose their own
+        if let Some(accept) = decompress::accept_encoding() {
+            if !custom_headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("accept-encoding")) {
+                builder = builder.header("Accept-Encoding", accept);
+            }
         }
 
-        let request = builder
+        // Add Content-Length for non-empty bodies of known length; streamed
+        // bodies have none, so hyper sends them chunked
+        if let Some(length) = body.content_length() {
+            builder = builder.header("Content-Length", length.to_string());
+        }
+
+        // Announce fixed trailers unless the caller declared them
+        if let Some(names) = trailer_declaration {
+            if !custom_headers.iter().any(|(n, _)| n.eq_ignore_ascii_case("trailer")) {
+                builder = builder.header("Trailer", names);
+            }
+        }
+
+        // Hold the body back until the server agrees to receive it
+        let (body, mut continue_gate) = if expect_continue && !body.is_empty() {
+            let (body, gate) = ContinueGate::wrap(body, self.expect_continue_timeout_ms);
+            (body, Some(gate))
+        } else {
+            (body, None)
+        };
+
+        let mut request = builder
             .body(body)
             .map_err(|e| JsValue::from_str(&format!("Failed to build request: {e}")))?;
+        if let Some(gate) = continue_gate.as_mut() {
+            gate.listen(&mut request);
+        }
 
         // Send the request using hyper
-        let response = sender
-            .send_request(request)
-            .await
-            .map_err(|e| JsValue::from_str(&format!("Request failed: {e}")))?;
+        let response = abortable(Box::pin(sender.send_request(request)), signal.as_ref()).await;
+        let response = match response {
+            Some(response) => response.map_err(|e| self.request_error(&e))?,
+            None => {
+                // Request may be half-written: the connection cannot be reused.
+                sender_guard.take();
+                return Err(abort_error(signal.as_ref().expect("aborted without signal")));
+            }
+        };
+        if let Some(held) = continue_gate.and_then(ContinueGate::finish) {
+            // Refused before the body was sent: keep the connection open for the
+            // response body, but never reuse it.
+            sender_guard.take();
+            *self.held_upload.borrow_mut() = Some(held);
+        }
 
         // Extract response parts
         let status = response.status().as_u16();
@@ -415,17 +1184,45 @@ impl AtlsHttp {
             .unwrap_or("")
             .to_string();
 
-        // Build headers object
+        // Bodies are decompressed transparently; the encoding and length headers
+        // then describe the wire format, not what the caller reads, so drop them.
+        let decompressor = response
+            .headers()
+            .get(http::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .and_then(Decompressor::for_encoding);
+
+        // Build headers object (repeated headers joined with ", ") and the raw
+        // entry list, which keeps repeated headers such as Set-Cookie separate
         let headers_obj = Object::new();
+        let raw_headers = Array::new();
         for (name, value) in response.headers() {
+            if decompressor.is_some()
+                && (name == http::header::CONTENT_ENCODING || name == http::header::CONTENT_LENGTH)
+            {
+                continue;
+            }
             let value_str = value.to_str().unwrap_or("");
-            Reflect::set(&headers_obj, &name.as_str().into(), &JsValue::from_str(value_str))?;
+            let key: JsValue = name.as_str().into();
+            let combined = match Reflect::get(&headers_obj, &key)?.as_string() {
+                Some(previous) => format!("{previous}, {value_str}"),
+                None => value_str.to_string(),
+            };
+            Reflect::set(&headers_obj, &key, &JsValue::from_str(&combined))?;
+            raw_headers.push(&Array::of2(&key, &JsValue::from_str(value_str)));
         }
 
         // Create ReadableStream from hyper body
         // hyper handles chunked decoding automatically!
         // Note: The connection becomes ready for reuse after the body is fully consumed
-        let body_stream = create_hyper_body_stream(response.into_body());
+        let (trailers, trailers_promise) = Trailers::new();
+        let body_stream = create_hyper_body_stream(
+            response.into_body(),
+            decompressor,
+            self.max_body_size,
+            trailers,
+            signal,
+        );
 
         // Build JS response object
         let result = Object::new();
@@ -436,9 +1233,49 @@ impl AtlsHttp {
             &JsValue::from_str(&status_text),
         )?;
         Reflect::set(&result, &"headers".into(), &headers_obj)?;
+        Reflect::set(&result, &"rawHeaders".into(), &raw_headers)?;
         Reflect::set(&result, &"body".into(), &body_stream)?;
+        Reflect::set(&result, &"trailers".into(), &trailers_promise)?;
 
-        Ok(result.into())
+        Ok(result.unchecked_into())
+    }
+}
+
+/// Collects the trailer fields of a chunked response and settles the JS
+/// promise exposing them once the body ends.
+struct Trailers {
+    entries: Array,
+    resolve: Option<web_sys::js_sys::Function>,
+}
+
+impl Trailers {
+    fn new() -> (Rc<RefCell<Self>>, Promise) {
+        let mut resolve = None;
+        let promise = Promise::new(&mut |res, _rej| resolve = Some(res));
+        let trailers = Trailers {
+            entries: Array::new(),
+            resolve,
+        };
+        (Rc::new(RefCell::new(trailers)), promise)
+    }
+
+    fn extend(&self, fields: &http::HeaderMap) {
+        for (name, value) in fields {
+            let value = JsValue::from_str(value.to_str().unwrap_or(""));
+            self.entries.push(&Array::of2(&name.as_str().into(), &value));
+        }
+    }
+
+    /// Resolve with the collected entries, or with `null` if the body failed.
+    fn settle(&mut self, complete: bool) {
+        if let Some(resolve) = self.resolve.take() {
+            let value = if complete {
+                self.entries.clone().into()
+            } else {
+                JsValue::NULL
+            };
+            let _ = resolve.call1(&JsValue::NULL, &value);
+        }
     }
 }
 
@@ -446,39 +1283,126 @@ impl AtlsHttp {
 ///
 /// hyper automatically handles chunked transfer decoding, so we just
 /// need to iterate over the body frames.
-fn create_hyper_body_stream(body: hyper::body::Incoming) -> web_sys::ReadableStream {
+///
+/// If `decompressor` is set, each frame is decoded before being enqueued. If the
+/// decoded body grows past `max_body_size`, the stream errors with
+/// `BODY_TOO_LARGE` and the body is dropped, closing the connection.
+///
+/// Trailer fields are collected into `trailers`, which is settled when the
+/// stream closes or errors.
+///
+/// If `signal` aborts, the stream errors with the abort reason and the body is
+/// dropped, which closes the underlying connection.
+fn create_hyper_body_stream(
+    body: hyper::body::Incoming,
+    decompressor: Option<Decompressor>,
+    max_body_size: Option<u64>,
+    trailers: Rc<RefCell<Trailers>>,
+    signal: Option<AbortSignal>,
+) -> web_sys::ReadableStream {
     let body = Rc::new(RefCell::new(Some(body)));
+    let decompressor = Rc::new(RefCell::new(decompressor));
+    let received = Rc::new(std::cell::Cell::new(0u64));
+    // Count decoded bytes; false once the limit is exceeded.
+    let within_limit = move |len: usize| {
+        received.set(received.get() + len as u64);
+        max_body_size.map_or(true, |limit| received.get() <= limit)
+    };
     let underlying_source = Object::new();
 
     let pull = Closure::wrap(Box::new(move |controller: ReadableStreamDefaultController| {
         let body = body.clone();
+        let decompressor = decompressor.clone();
+        let within_limit = within_limit.clone();
+        let trailers = trailers.clone();
+        let signal = signal.clone();
 
         wasm_bindgen_futures::future_to_promise(async move {
             let mut body_opt = body.borrow_mut();
+            let fail = |error: &JsValue| {
+                controller.error_with_e(error);
+                trailers.borrow_mut().settle(false);
+            };
 
-            if let Some(body_inner) = body_opt.as_mut() {
+            // Loop until a chunk is enqueued or the stream ends: a pull that
+            // resolves without enqueuing is not retried by the stream, so frames
+            // that decode to nothing (gzip header, trailers) must not end it.
+            while let Some(body_inner) = body_opt.as_mut() {
                 // Try to get the next frame from the body
-                match body_inner.frame().await {
-                    Some(Ok(frame)) => {
-                        if let Some(data) = frame.data_ref() {
-                            let arr = Uint8Array::from(data.as_ref());
-                            controller.enqueue_with_chunk(&arr.into()).ok();
-                        }
-                        // If it's a trailers frame, we ignore it
+                let frame = match abortable(body_inner.frame(), signal.as_ref()).await {
+                    Some(frame) => frame,
+                    None => {
+                        body_opt.take();
+                        let signal = signal.as_ref().expect("aborted without signal");
+                        fail(&abort_error(signal));
+                        return Ok(JsValue::UNDEFINED);
                     }
+                };
+                let decoded = match frame {
+                    Some(Ok(frame)) => match frame.data_ref() {
+                        Some(data) => match decompressor.borrow_mut().as_mut() {
+                            Some(d) => d.push(data),
+                            None => Ok(data.to_vec()),
+                        },
+                        // Trailers frame: keep the fields for `trailers`
+                        None => {
+                            if let Some(fields) = frame.trailers_ref() {
+                                trailers.borrow().extend(fields);
+                            }
+                            Ok(Vec::new())
+                        }
+                    },
                     Some(Err(e)) => {
-                        let error = JsValue::from_str(&format!("Body read error: {e}"));
-                        controller.error_with_e(&error);
+                        body_opt.take();
+                        fail(&JsValue::from_str(&format!("Body read error: {e}")));
+                        return Ok(JsValue::UNDEFINED);
                     }
                     None => {
-                        // Body complete
-                        controller.close().ok();
+                        // Body complete: flush the decoder's tail
+                        body_opt.take();
+                        let tail = match decompressor.borrow_mut().take() {
+                            Some(d) => d.finish(),
+                            None => Ok(Vec::new()),
+                        };
+                        match tail {
+                            Ok(tail) if !within_limit(tail.len()) => {
+                                fail(&body_too_large(max_body_size.unwrap_or(0)));
+                            }
+                            Ok(tail) => {
+                                if !tail.is_empty() {
+                                    let arr = Uint8Array::from(tail.as_slice());
+                                    controller.enqueue_with_chunk(&arr.into()).ok();
+                                }
+                                controller.close().ok();
+                                trailers.borrow_mut().settle(true);
+                            }
+                            Err(e) => {
+                                fail(&JsValue::from_str(&format!("Decompression error: {e}")));
+                            }
+                        }
+                        return Ok(JsValue::UNDEFINED);
+                    }
+                };
+                match decoded {
+                    Ok(bytes) if bytes.is_empty() => continue,
+                    Ok(bytes) if !within_limit(bytes.len()) => {
+                        body_opt.take();
+                        fail(&body_too_large(max_body_size.unwrap_or(0)));
+                    }
+                    Ok(bytes) => {
+                        let arr = Uint8Array::from(bytes.as_slice());
+                        controller.enqueue_with_chunk(&arr.into()).ok();
+                    }
+                    Err(e) => {
+                        body_opt.take();
+                        fail(&JsValue::from_str(&format!("Decompression error: {e}")));
                     }
                 }
-            } else {
-                controller.close().ok();
+                return Ok(JsValue::UNDEFINED);
             }
 
+            controller.close().ok();
+            trailers.borrow_mut().settle(true);
             Ok(JsValue::UNDEFINED)
         })
     }) as Box<dyn FnMut(ReadableStreamDefaultController) -> Promise>);
@@ -502,8 +1426,12 @@ mod tests {
         let summary = AttestationSummary {
             trusted: true,
             tee_type: "Tdx".to_string(),
+            measurement: None,
+            measurement_digest: None,
+            app_digest: None,
             tcb_status: "UpToDate".to_string(),
             advisory_ids: vec!["INTEL-SA-00001".to_string()],
+            timings: AttestationTimings::default(),
         };
 
         // Test that it can be serialized to JSON
@@ -519,8 +1447,12 @@ mod tests {
         let summary = AttestationSummary {
             trusted: false,
             tee_type: "Snp".to_string(),
+            measurement: None,
+            measurement_digest: None,
+            app_digest: None,
             tcb_status: "SWHardeningNeeded".to_string(),
             advisory_ids: vec![],
+            timings: AttestationTimings::default(),
         };
 
         let json = serde_json::to_string(&summary).unwrap();
@@ -539,14 +1471,20 @@ mod tests {
         let summary = AttestationSummary {
             trusted: true,
             tee_type: "Tdx".to_string(),
+            measurement: None,
+            measurement_digest: None,
+            app_digest: None,
             tcb_status: "UpToDate".to_string(),
             advisory_ids: vec!["ADV1".to_string(), "ADV2".to_string()],
+            timings: AttestationTimings::default(),
         };
 
-        // Test conversion to JsValue via serde-wasm-bindgen
-        let js_value = serde_wasm_bindgen::to_value(&summary).unwrap();
+        // Test conversion to the typed JS object via serde-wasm-bindgen
+        let js_value: JsValue = summary.to_js().unwrap().into();
         assert!(!js_value.is_undefined());
         assert!(!js_value.is_null());
+        let tcb_status = Reflect::get(&js_value, &"tcbStatus".into()).unwrap();
+        assert_eq!(tcb_status.as_string().as_deref(), Some("UpToDate"));
     }
 
     #[wasm_bindgen_test]
@@ -554,11 +1492,71 @@ mod tests {
         let summary = AttestationSummary {
             trusted: true,
             tee_type: "Tdx".to_string(),
+            measurement: None,
+            measurement_digest: None,
+            app_digest: None,
             tcb_status: "UpToDate".to_string(),
             advisory_ids: vec![],
+            timings: AttestationTimings::default(),
         };
 
         let json = serde_json::to_string(&summary).unwrap();
         assert!(json.contains("\"advisoryIds\":[]"));
     }
+
+    #[wasm_bindgen_test]
+    fn test_atls_error_has_code() {
+        let err = atls_error(AtlsVerificationError::TcbStatusNotAllowed {
+            status: "OutOfDate".into(),
+            allowed: vec!["UpToDate".into()],
+        });
+        assert!(err.is_instance_of::<web_sys::js_sys::Error>());
+        let code = Reflect::get(&err, &"code".into()).unwrap();
+        assert_eq!(code.as_string().as_deref(), Some("TCB_STATUS_REJECTED"));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse_policy_from_json_string() {
+        let json = r#"{"type":"dstack_tdx","allowed_tcb_status":["UpToDate"]}"#;
+        let policy = parse_policy(JsValue::from_str(json).unchecked_into()).unwrap();
+        let Policy::DstackTdx(tdx) = policy;
+        assert_eq!(tdx.allowed_tcb_status, vec!["UpToDate".to_string()]);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse_policy_rejects_invalid_tcb_status() {
+        let json = r#"{"type":"dstack_tdx","allowed_tcb_status":["Bogus"]}"#;
+        let err = parse_policy(JsValue::from_str(json).unchecked_into()).unwrap_err();
+        let code = Reflect::get(&err, &"code".into()).unwrap();
+        assert_eq!(code.as_string().as_deref(), Some("INVALID_CONFIGURATION"));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_parse_http_options_rejects_small_header_size() {
+        let options = Object::new();
+        Reflect::set(&options, &"maxHeaderSize".into(), &1024.into()).unwrap();
+        let err = parse_http_options(JsValue::from(options).unchecked_into()).unwrap_err();
+        let code = Reflect::get(&err, &"code".into()).unwrap();
+        assert_eq!(code.as_string().as_deref(), Some("INVALID_CONFIGURATION"));
+
+        let options = parse_http_options(JsValue::UNDEFINED.unchecked_into()).unwrap();
+        assert!(options.max_header_size.is_none() && options.max_body_size.is_none());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_attestation_timings_from_phase_timings() {
+        let timings = AttestationTimings::from(&PhaseTimings {
+            tls_handshake: Some(std::time::Duration::from_millis(40)),
+            verification: Some(std::time::Duration::from_millis(2)),
+            ..Default::default()
+        });
+
+        assert_eq!(timings.tcp_connect_ms, None);
+        assert_eq!(timings.tls_handshake_ms, Some(40.0));
+        assert_eq!(timings.total_ms, 42.0);
+
+        let json = serde_json::to_string(&timings).unwrap();
+        assert!(json.contains("\"tlsHandshakeMs\":40.0"));
+        assert!(json.contains("\"tcpConnectMs\":null"));
+    }
 }
@@ -0,0 +1,403 @@
+//! Several tunnels over one proxy WebSocket.
+//!
+//! Opening a WebSocket per aTLS connection costs a round trip (plus a TLS
+//! handshake for `wss://`) and a file descriptor on the proxy. With `mux=1` in
+//! the proxy URL, every tunnel to that proxy URL shares one WebSocket; each
+//! tunnel is a stream identified by a 32-bit id and names its own `target`.
+//! The session is negotiated with the [`PROTOCOL`] subprotocol; a proxy that
+//! doesn't echo it can't multiplex, and the connection fails rather than
+//! sending frames into a plain tunnel.
+//!
+//! Every binary message carries one frame: a 1-byte type, the big-endian
+//! stream id, then the payload. Empty messages are keepalives.
+//!
+//! | Type | Frame    | Payload                                             |
+//! |------|----------|-----------------------------------------------------|
+//! | 0    | `OPEN`   | target `host:port` (client to proxy)                |
+//! | 1    | `DATA`   | bytes                                               |
+//! | 2    | `WINDOW` | big-endian u32: bytes the peer may send in addition |
+//! | 3    | `CLOSE`  | none: the sender will send no more data             |
+//! | 4    | `RESET`  | optional UTF-8 reason: the stream is aborted        |
+//! | 5    | `GOAWAY` | big-endian u32: seconds until the session closes    |
+//!
+//! Each side may have at most [`INITIAL_WINDOW`] unacknowledged bytes in flight
+//! per stream, and grants more with `WINDOW` as the reader consumes data, so a
+//! slow stream never stalls the others. The proxy checks each `OPEN` target
+//! against its allowlist and answers a refused or unreachable target with
+//! `RESET`, which fails that tunnel only.
+//!
+//! A proxy rotating sessions at a maximum lifetime sends `GOAWAY` (stream id
+//! 0) shortly before; new tunnels then open on a new session while the old
+//! one's streams finish. The WebSocket is closed once its last stream is
+//! dropped.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::StreamExt;
+use wasm_bindgen::JsValue;
+use ws_stream_wasm::{WsMessage, WsMeta, WsStream};
+
+use crate::transport::{js_io_error, spawn_keepalive};
+
+const FRAME_OPEN: u8 = 0;
+const FRAME_DATA: u8 = 1;
+const FRAME_WINDOW: u8 = 2;
+const FRAME_CLOSE: u8 = 3;
+const FRAME_RESET: u8 = 4;
+const FRAME_GOAWAY: u8 = 5;
+
+const HEADER_LEN: usize = 5;
+
+/// `Sec-WebSocket-Protocol` entry negotiating a session with the proxy.
+const PROTOCOL: &str = "atlas-mux.v1";
+
+/// Bytes either side may send on a stream before the peer grants more.
+const INITIAL_WINDOW: u32 = 256 * 1024;
+
+/// Largest `DATA` payload sent in one message.
+const MAX_DATA_FRAME: usize = 16 * 1024;
+
+fn encode_frame(kind: u8, id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn decode_frame(message: &[u8]) -> Option<(u8, u32, &[u8])> {
+    if message.len() < HEADER_LEN {
+        return None;
+    }
+    let id = u32::from_be_bytes(message[1..HEADER_LEN].try_into().ok()?);
+    Some((message[0], id, &message[HEADER_LEN..]))
+}
+
+#[derive(Default)]
+struct StreamState {
+    incoming: VecDeque<u8>,
+    /// Consumed bytes not yet granted back with `WINDOW`.
+    unacked: u32,
+    send_credit: u32,
+    eof: bool,
+    write_closed: bool,
+    reset: Option<String>,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl StreamState {
+    fn wake(&mut self) {
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// One WebSocket to the proxy, shared by every stream opened with its URL.
+struct MuxSession {
+    meta: WsMeta,
+    streams: RefCell<HashMap<u32, StreamState>>,
+    next_id: Cell<u32>,
+    /// Set once the WebSocket has closed; every stream then fails.
+    closed: Cell<bool>,
+    /// Set on `GOAWAY`: the proxy will close the session soon, so new
+    /// streams go to a new one.
+    draining: Cell<bool>,
+}
+
+thread_local! {
+    static SESSIONS: RefCell<HashMap<String, Weak<MuxSession>>> = RefCell::new(HashMap::new());
+}
+
+impl MuxSession {
+    /// Reuse the open session for `url`, or connect a new one.
+    async fn get_or_connect(url: &str, keepalive_ms: Option<u32>) -> Result<Rc<Self>, JsValue> {
+        let existing = SESSIONS.with(|sessions| {
+            let mut sessions = sessions.borrow_mut();
+            sessions.retain(|_, session| session.strong_count() > 0);
+            sessions.get(url).and_then(Weak::upgrade)
+        });
+        if let Some(session) = existing.filter(|s| !s.closed.get() && !s.draining.get()) {
+            return Ok(session);
+        }
+
+        let (meta, messages) = WsMeta::connect(url, Some(vec![PROTOCOL]))
+            .await
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        if meta.protocol() != PROTOCOL {
+            let _ = meta.wrapped().close();
+            return Err(JsValue::from_str(
+                "the proxy does not support multiplexed tunnels",
+            ));
+        }
+        if let Some(interval) = keepalive_ms.filter(|&ms| ms > 0) {
+            spawn_keepalive(meta.wrapped().clone(), interval);
+        }
+        let session = Rc::new(Self {
+            meta,
+            streams: RefCell::new(HashMap::new()),
+            next_id: Cell::new(1),
+            closed: Cell::new(false),
+            draining: Cell::new(false),
+        });
+        SESSIONS.with(|sessions| {
+            sessions
+                .borrow_mut()
+                .insert(url.to_string(), Rc::downgrade(&session))
+        });
+        wasm_bindgen_futures::spawn_local(read_loop(Rc::downgrade(&session), messages));
+        Ok(session)
+    }
+
+    fn send(&self, kind: u8, id: u32, payload: &[u8]) -> io::Result<()> {
+        self.meta
+            .wrapped()
+            .send_with_u8_array(&encode_frame(kind, id, payload))
+            .map_err(js_io_error)
+    }
+
+    fn dispatch(&self, message: &[u8]) {
+        let Some((kind, id, payload)) = decode_frame(message) else {
+            return;
+        };
+        if kind == FRAME_GOAWAY {
+            self.draining.set(true);
+            return;
+        }
+        let mut streams = self.streams.borrow_mut();
+        // Frames for a stream dropped in the meantime are ignored
+        let Some(state) = streams.get_mut(&id) else {
+            return;
+        };
+        match kind {
+            FRAME_DATA => state.incoming.extend(payload),
+            FRAME_WINDOW => {
+                if let Ok(increment) = <[u8; 4]>::try_from(payload) {
+                    state.send_credit = state
+                        .send_credit
+                        .saturating_add(u32::from_be_bytes(increment));
+                }
+            }
+            FRAME_CLOSE => state.eof = true,
+            FRAME_RESET => {
+                let reason = String::from_utf8_lossy(payload);
+                state.reset = Some(if reason.is_empty() {
+                    "stream reset by proxy".into()
+                } else {
+                    reason.into_owned()
+                });
+            }
+            _ => return,
+        }
+        state.wake();
+    }
+
+    fn fail_all(&self) {
+        self.closed.set(true);
+        for state in self.streams.borrow_mut().values_mut() {
+            state.wake();
+        }
+    }
+}
+
+impl Drop for MuxSession {
+    fn drop(&mut self) {
+        let _ = self.meta.wrapped().close();
+    }
+}
+
+/// Dispatch incoming frames until the WebSocket closes or the session is dropped.
+async fn read_loop(session: Weak<MuxSession>, mut messages: WsStream) {
+    while let Some(message) = messages.next().await {
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+        if let WsMessage::Binary(data) = message {
+            session.dispatch(&data);
+        }
+    }
+    if let Some(session) = session.upgrade() {
+        session.fail_all();
+    }
+}
+
+/// A tunnel carried as one stream of a shared [`MuxSession`].
+pub(crate) struct MuxStream {
+    session: Rc<MuxSession>,
+    id: u32,
+}
+
+impl MuxStream {
+    /// Open a stream to the `target` named in `url`, sharing the WebSocket of
+    /// every other stream to the same proxy URL.
+    pub(crate) async fn connect(url: &str, keepalive_ms: Option<u32>) -> Result<Self, JsValue> {
+        let parsed = web_sys::Url::new(url)?;
+        let target = parsed
+            .search_params()
+            .get("target")
+            .ok_or_else(|| JsValue::from_str("multiplexed tunnels need a target parameter"))?;
+        parsed.search_params().delete("target");
+
+        let session = MuxSession::get_or_connect(&parsed.href(), keepalive_ms).await?;
+        let id = session.next_id.get();
+        session.next_id.set(id.wrapping_add(1).max(1));
+        session.streams.borrow_mut().insert(
+            id,
+            StreamState {
+                send_credit: INITIAL_WINDOW,
+                ..Default::default()
+            },
+        );
+        let stream = Self { session, id };
+        stream
+            .session
+            .send(FRAME_OPEN, id, target.as_bytes())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(stream)
+    }
+
+    fn closed_error() -> io::Error {
+        io::Error::new(io::ErrorKind::ConnectionAborted, "proxy connection closed")
+    }
+}
+
+impl AsyncRead for MuxStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut streams = this.session.streams.borrow_mut();
+        let state = streams
+            .get_mut(&this.id)
+            .expect("stream state outlives the stream");
+
+        if !state.incoming.is_empty() {
+            let n = buf.len().min(state.incoming.len());
+            for (dst, src) in buf.iter_mut().zip(state.incoming.drain(..n)) {
+                *dst = src;
+            }
+            state.unacked += n as u32;
+            if state.unacked >= INITIAL_WINDOW / 2 {
+                let increment = std::mem::take(&mut state.unacked);
+                drop(streams);
+                this.session
+                    .send(FRAME_WINDOW, this.id, &increment.to_be_bytes())?;
+            }
+            return Poll::Ready(Ok(n));
+        }
+        if let Some(reason) = &state.reset {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                reason.clone(),
+            )));
+        }
+        if state.eof {
+            return Poll::Ready(Ok(0));
+        }
+        if this.session.closed.get() {
+            return Poll::Ready(Err(Self::closed_error()));
+        }
+        state.read_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for MuxStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let mut streams = this.session.streams.borrow_mut();
+        let state = streams
+            .get_mut(&this.id)
+            .expect("stream state outlives the stream");
+
+        if let Some(reason) = &state.reset {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                reason.clone(),
+            )));
+        }
+        if this.session.closed.get() {
+            return Poll::Ready(Err(Self::closed_error()));
+        }
+        if state.write_closed {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if state.send_credit == 0 {
+            state.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf
+            .len()
+            .min(state.send_credit as usize)
+            .min(MAX_DATA_FRAME);
+        state.send_credit -= n as u32;
+        drop(streams);
+        this.session.send(FRAME_DATA, this.id, &buf[..n])?;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Data is handed to the WebSocket as it is written; the window bounds
+        // how much can be queued there.
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let mut streams = this.session.streams.borrow_mut();
+        let state = streams
+            .get_mut(&this.id)
+            .expect("stream state outlives the stream");
+        if state.write_closed || state.reset.is_some() || this.session.closed.get() {
+            return Poll::Ready(Ok(()));
+        }
+        state.write_closed = true;
+        drop(streams);
+        Poll::Ready(this.session.send(FRAME_CLOSE, this.id, &[]))
+    }
+}
+
+impl Drop for MuxStream {
+    fn drop(&mut self) {
+        let state = self.session.streams.borrow_mut().remove(&self.id);
+        let finished = state.is_some_and(|s| s.reset.is_some() || (s.eof && s.write_closed));
+        if !finished && !self.session.closed.get() {
+            let _ = self.session.send(FRAME_RESET, self.id, &[]);
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", test))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_frame_round_trip() {
+        let frame = encode_frame(FRAME_OPEN, 7, b"vllm.example.com:443");
+        assert_eq!(&frame[..HEADER_LEN], &[FRAME_OPEN, 0, 0, 0, 7]);
+        assert_eq!(
+            decode_frame(&frame),
+            Some((FRAME_OPEN, 7, &b"vllm.example.com:443"[..]))
+        );
+        // Empty keepalive messages and truncated headers carry no frame
+        assert_eq!(decode_frame(&[]), None);
+        assert_eq!(decode_frame(&[FRAME_DATA, 0, 0]), None);
+    }
+}
@@ -0,0 +1,503 @@
+//! Validation of outgoing request parts before they reach hyper.
+//!
+//! hyper already refuses header values containing CR/LF, but its errors are
+//! untyped strings and some checks (request-target shape, `Host`) are looser
+//! than we want for attested requests, where every byte on the wire should be
+//! exactly what the caller intended. Everything is checked up front so a bad
+//! request fails with `code = "INVALID_REQUEST"` before a byte is written and
+//! the connection stays usable.
+//!
+//! Header values are never echoed in errors, since they often carry tokens.
+//!
+//! This module also builds the request body: a `Uint8Array` is sent with
+//! `Content-Length`, while a `ReadableStream` (length unknown up front) or a
+//! body with trailers is sent with `Transfer-Encoding: chunked`. With
+//! `Expect: 100-continue`, the body is held back by a [`ContinueGate`] until the
+//! server asks for it.
+
+use std::cell::Cell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use futures::stream::{self, LocalBoxStream};
+use futures::StreamExt;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use http_body_util::BodyStream;
+use hyper::body::{Body, Frame, SizeHint};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{ArrayBuffer, Error, Function, Promise, Reflect, Uint8Array};
+
+/// Why a request was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidRequest {
+    /// Method is not an RFC 9110 token.
+    Method(String),
+    /// Path is not an origin-form request target (`/...`, visible ASCII only).
+    Path,
+    /// Host is not a `host[:port]` authority (no userinfo, port at most 65535).
+    Host,
+    /// Header name is not an RFC 9110 token.
+    HeaderName(String),
+    /// Header value contains CR, LF, NUL or another control character.
+    HeaderValue(String),
+    /// Body is neither a `Uint8Array` nor an unlocked `ReadableStream`.
+    Body,
+}
+
+impl fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Method(method) => write!(f, "invalid HTTP method {method:?}"),
+            Self::Path => {
+                f.write_str("invalid request path: must start with '/', visible ASCII only")
+            }
+            Self::Host => f.write_str(
+                "invalid Host: expected host[:port] (reg-name, IPv4 or [IPv6], port 0-65535)",
+            ),
+            Self::HeaderName(name) => write!(f, "invalid header name {name:?}"),
+            Self::HeaderValue(name) => {
+                write!(
+                    f,
+                    "invalid value for header {name:?}: control characters are not allowed"
+                )
+            }
+            Self::Body => f.write_str(
+                "invalid request body: expected a Uint8Array or an unlocked ReadableStream",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidRequest {}
+
+impl InvalidRequest {
+    /// JS `Error` carrying `code = "INVALID_REQUEST"`.
+    pub fn to_js(&self) -> JsValue {
+        let error = Error::new(&self.to_string());
+        let _ = Reflect::set(&error, &"code".into(), &"INVALID_REQUEST".into());
+        error.into()
+    }
+}
+
+/// `tchar` from RFC 9110 section 5.6.2.
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(is_tchar)
+}
+
+pub fn validate_method(method: &str) -> Result<(), InvalidRequest> {
+    if is_token(method) {
+        Ok(())
+    } else {
+        Err(InvalidRequest::Method(method.to_string()))
+    }
+}
+
+pub fn validate_path(path: &str) -> Result<(), InvalidRequest> {
+    if path.starts_with('/') && path.bytes().all(|b| b.is_ascii_graphic()) {
+        Ok(())
+    } else {
+        Err(InvalidRequest::Path)
+    }
+}
+
+/// Characters of an RFC 3986 `reg-name` (unreserved, sub-delims and `%` escapes).
+fn is_reg_name_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=%".contains(&b)
+}
+
+/// Check `host` is an RFC 3986 authority without userinfo: a reg-name or IPv4
+/// address, or a bracketed IPv6 literal, then an optional port.
+pub fn validate_host(host: &str) -> Result<(), InvalidRequest> {
+    let (name_ok, port) = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((literal, after)) => (
+                !literal.is_empty()
+                    && literal
+                        .bytes()
+                        .all(|b| b.is_ascii_hexdigit() || b == b':' || b == b'.'),
+                if after.is_empty() {
+                    None
+                } else {
+                    Some(after.strip_prefix(':').ok_or(InvalidRequest::Host)?)
+                },
+            ),
+            None => return Err(InvalidRequest::Host),
+        },
+        None => {
+            let (name, port) = match host.split_once(':') {
+                Some((name, port)) => (name, Some(port)),
+                None => (host, None),
+            };
+            (!name.is_empty() && name.bytes().all(is_reg_name_char), port)
+        }
+    };
+    let port_ok = port.map_or(true, |port| {
+        !port.is_empty()
+            && port.len() <= 5
+            && port.bytes().all(|b| b.is_ascii_digit())
+            && port.parse::<u32>().is_ok_and(|p| p <= 65535)
+    });
+    if name_ok && port_ok {
+        Ok(())
+    } else {
+        Err(InvalidRequest::Host)
+    }
+}
+
+pub fn validate_header(name: &str, value: &str) -> Result<(), InvalidRequest> {
+    if !is_token(name) {
+        return Err(InvalidRequest::HeaderName(name.to_string()));
+    }
+    // field-value: visible characters, spaces, tabs and obs-text
+    if value.bytes().any(|b| (b < 0x20 && b != b'\t') || b == 0x7f) {
+        return Err(InvalidRequest::HeaderValue(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Parse and validate `[name, value]` entries into a header map.
+fn header_map(entries: Vec<(String, String)>) -> Result<HeaderMap, InvalidRequest> {
+    let mut map = HeaderMap::with_capacity(entries.len());
+    for (name, value) in entries {
+        validate_header(&name, &value)?;
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| InvalidRequest::HeaderName(name.clone()))?;
+        let header_value =
+            HeaderValue::from_str(&value).map_err(|_| InvalidRequest::HeaderValue(name))?;
+        map.append(header_name, header_value);
+    }
+    Ok(map)
+}
+
+/// Error reading a streamed request body; aborts the request.
+#[derive(Debug)]
+pub struct BodyError(String);
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body: {}", self.0)
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+impl BodyError {
+    fn from_js(value: JsValue) -> Self {
+        let message = value
+            .dyn_ref::<Error>()
+            .map(|e| String::from(e.message()))
+            .or_else(|| value.as_string())
+            .unwrap_or_else(|| "stream errored".into());
+        Self(message)
+    }
+}
+
+/// Trailer fields sent after the last chunk of a request body.
+pub enum RequestTrailers {
+    None,
+    /// Known up front; their names are declared in a `Trailer` header.
+    Fixed(HeaderMap),
+    /// Called once the body has been sent (e.g. for a checksum); may return a
+    /// Promise. The caller declares the names in a `Trailer` header.
+    Deferred(Function),
+}
+
+impl RequestTrailers {
+    /// Parse `HeaderEntry[] | (() => HeaderEntry[] | Promise<HeaderEntry[]>) | null`.
+    pub fn from_js(value: JsValue) -> Result<Self, JsValue> {
+        if value.is_null() || value.is_undefined() {
+            return Ok(Self::None);
+        }
+        if let Some(function) = value.dyn_ref::<Function>() {
+            return Ok(Self::Deferred(function.clone()));
+        }
+        let entries: Vec<(String, String)> = serde_wasm_bindgen::from_value(value)
+            .map_err(|e| JsValue::from_str(&format!("Invalid trailers: {e}")))?;
+        let map = header_map(entries).map_err(|e| e.to_js())?;
+        Ok(if map.is_empty() {
+            Self::None
+        } else {
+            Self::Fixed(map)
+        })
+    }
+
+    /// Value for the `Trailer` header announcing fixed trailer names.
+    pub fn declaration(&self) -> Option<String> {
+        match self {
+            Self::Fixed(map) => {
+                let names: Vec<&str> = map.keys().map(HeaderName::as_str).collect();
+                Some(names.join(", "))
+            }
+            _ => None,
+        }
+    }
+
+    /// The trailers frame, once the body has been sent.
+    async fn into_frame(self) -> Option<Result<Frame<Bytes>, BodyError>> {
+        let map = match self {
+            Self::None => return None,
+            Self::Fixed(map) => map,
+            Self::Deferred(function) => {
+                let entries = match function.call0(&JsValue::NULL) {
+                    Ok(value) => match value.dyn_into::<Promise>() {
+                        Ok(promise) => JsFuture::from(promise).await,
+                        Err(value) => Ok(value),
+                    },
+                    Err(e) => Err(e),
+                };
+                let entries: Vec<(String, String)> = match entries {
+                    Ok(value) if value.is_null() || value.is_undefined() => return None,
+                    Ok(value) => match serde_wasm_bindgen::from_value(value) {
+                        Ok(entries) => entries,
+                        Err(e) => return Some(Err(BodyError(format!("invalid trailers: {e}")))),
+                    },
+                    Err(e) => return Some(Err(BodyError::from_js(e))),
+                };
+                match header_map(entries) {
+                    Ok(map) => map,
+                    Err(e) => return Some(Err(BodyError(e.to_string()))),
+                }
+            }
+        };
+        Some(Ok(Frame::trailers(map)))
+    }
+}
+
+/// Body of an outgoing request.
+pub enum RequestBody {
+    /// Body of known length (`None` when empty), sent with `Content-Length`.
+    Full(Option<Bytes>),
+    /// Body produced frame by frame. Without a `Content-Length` header it is
+    /// sent with `Transfer-Encoding: chunked` and ended by the terminal chunk
+    /// (plus trailers, if any).
+    Chunked(LocalBoxStream<'static, Result<Frame<Bytes>, BodyError>>),
+}
+
+impl RequestBody {
+    /// Build the body from `Uint8Array | ReadableStream<Uint8Array> | null`.
+    ///
+    /// Streams are read as hyper writes the request, so only one chunk is
+    /// buffered at a time. Trailers force chunked encoding, since they can only
+    /// follow a chunked body.
+    pub fn from_js(body: JsValue, trailers: RequestTrailers) -> Result<Self, JsValue> {
+        let no_trailers = matches!(trailers, RequestTrailers::None);
+        let data: LocalBoxStream<'static, Result<Frame<Bytes>, BodyError>> =
+            if body.is_null() || body.is_undefined() {
+                if no_trailers {
+                    return Ok(Self::Full(None));
+                }
+                stream::empty().boxed_local()
+            } else if let Some(array) = body.dyn_ref::<Uint8Array>() {
+                let bytes = Bytes::from(array.to_vec());
+                if no_trailers {
+                    return Ok(Self::Full(Some(bytes).filter(|b| !b.is_empty())));
+                }
+                stream::once(future::ready(Ok(Frame::data(bytes)))).boxed_local()
+            } else if let Some(readable) = body.dyn_ref::<web_sys::ReadableStream>() {
+                wasm_streams::ReadableStream::from_raw(readable.clone())
+                    .try_into_stream()
+                    .map_err(|_| InvalidRequest::Body.to_js())?
+                    .map(|chunk| chunk.map_err(BodyError::from_js).and_then(chunk_bytes))
+                    .boxed_local()
+            } else {
+                return Err(InvalidRequest::Body.to_js());
+            };
+
+        let trailers = stream::once(trailers.into_frame()).filter_map(future::ready);
+        Ok(Self::Chunked(data.chain(trailers).boxed_local()))
+    }
+
+    /// Length to send in `Content-Length`, for non-empty bodies of known length.
+    pub fn content_length(&self) -> Option<usize> {
+        match self {
+            Self::Full(Some(bytes)) => Some(bytes.len()),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Full(None))
+    }
+
+    /// Hold the body back until `gate` resolves; an error fails the request.
+    fn gated(self, gate: impl Future<Output = Result<(), BodyError>> + 'static) -> Self {
+        let wait = stream::once(gate).filter_map(|opened| future::ready(opened.err().map(Err)));
+        Self::Chunked(wait.chain(BodyStream::new(self)).boxed_local())
+    }
+}
+
+/// `Expect: 100-continue` handling for one request.
+///
+/// The body is sent once the server answers `100 Continue`, or after a timeout
+/// for servers and intermediaries that never send interim responses. If the
+/// final response arrives first, the body is never sent: the connection cannot
+/// be reused and is shut down when the [`HeldUpload`] is dropped.
+pub struct ContinueGate {
+    continued: Option<oneshot::Sender<()>>,
+    released: Rc<Cell<bool>>,
+    abandon: oneshot::Sender<()>,
+}
+
+/// Keeps a connection whose body was refused alive until it is dropped, so
+/// the early response can still be read.
+pub struct HeldUpload {
+    _abandon: oneshot::Sender<()>,
+}
+
+impl ContinueGate {
+    /// Gate `body` behind a `100 Continue` or `timeout_ms` of silence.
+    pub fn wrap(body: RequestBody, timeout_ms: u32) -> (RequestBody, Self) {
+        let (continued, continue_rx) = oneshot::channel();
+        let (abandon, abandon_rx) = oneshot::channel::<()>();
+        let released = Rc::new(Cell::new(false));
+
+        let release = released.clone();
+        let gate = async move {
+            let timeout = JsFuture::from(crate::transport::sleep_promise(timeout_ms as i32));
+            let waiting = future::select(continue_rx, timeout);
+            match future::select(waiting, abandon_rx).await {
+                // `100 Continue`, or no interim response in time
+                Either::Left((Either::Left((Ok(()), _)) | Either::Right(_), _)) => {
+                    release.set(true);
+                    Ok(())
+                }
+                // hyper dropped the callback: the final response came first
+                Either::Left((Either::Left((Err(_), _)), abandon_rx)) => {
+                    let _ = abandon_rx.await;
+                    Err(BodyError("request refused before 100 Continue".into()))
+                }
+                Either::Right(_) => Err(BodyError("request refused before 100 Continue".into())),
+            }
+        };
+
+        let gate_state = Self {
+            continued: Some(continued),
+            released,
+            abandon,
+        };
+        (body.gated(gate), gate_state)
+    }
+
+    /// Open the gate when `request` receives `100 Continue`.
+    pub fn listen<B>(&mut self, request: &mut http::Request<B>) {
+        let continued = Arc::new(Mutex::new(self.continued.take()));
+        hyper::ext::on_informational(request, move |response| {
+            if response.status() == http::StatusCode::CONTINUE {
+                if let Some(tx) = continued.lock().ok().and_then(|mut tx| tx.take()) {
+                    let _ = tx.send(());
+                }
+            }
+        });
+    }
+
+    /// After the response head: `None` if the body was sent, otherwise the
+    /// handle keeping the refused upload (and its connection) open.
+    pub fn finish(self) -> Option<HeldUpload> {
+        (!self.released.get()).then_some(HeldUpload {
+            _abandon: self.abandon,
+        })
+    }
+}
+
+/// A stream chunk as a data frame; only byte chunks are accepted.
+fn chunk_bytes(value: JsValue) -> Result<Frame<Bytes>, BodyError> {
+    let array = if let Some(buffer) = value.dyn_ref::<ArrayBuffer>() {
+        Uint8Array::new(buffer)
+    } else {
+        value
+            .dyn_into::<Uint8Array>()
+            .map_err(|_| BodyError("stream chunks must be Uint8Array".into()))?
+    };
+    Ok(Frame::data(Bytes::from(array.to_vec())))
+}
+
+impl Body for RequestBody {
+    type Data = Bytes;
+    type Error = BodyError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BodyError>>> {
+        match self.get_mut() {
+            Self::Full(bytes) => Poll::Ready(bytes.take().map(|b| Ok(Frame::data(b)))),
+            Self::Chunked(frames) => frames.poll_next_unpin(cx),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self {
+            Self::Full(bytes) => SizeHint::with_exact(bytes.as_ref().map_or(0, |b| b.len() as u64)),
+            Self::Chunked(_) => SizeHint::default(),
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", test))]
+mod tests {
+    use super::*;
+    use wasm_bindgen_test::*;
+
+    #[wasm_bindgen_test]
+    fn test_rejects_crlf_in_header_value() {
+        let err = validate_header("X-Token", "abc\r\nX-Admin: true").unwrap_err();
+        assert_eq!(err, InvalidRequest::HeaderValue("X-Token".into()));
+        // The value is never echoed
+        assert!(!err.to_string().contains("X-Admin"));
+        assert!(validate_header("X-Token", "abc\tdef ghi").is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_rejects_invalid_tokens() {
+        assert!(validate_header("X Bad", "v").is_err());
+        assert!(validate_header("", "v").is_err());
+        assert!(validate_method("GET").is_ok());
+        assert!(validate_method("GET /evil HTTP/1.1\r\n").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_validates_path_and_host() {
+        assert!(validate_path("/v1/models?limit=10").is_ok());
+        assert!(validate_path("/a b").is_err());
+        assert!(validate_path("http://evil.com/").is_err());
+        assert!(validate_host("vllm.example.com:8443").is_ok());
+        assert!(validate_host("vllm.example.com\r\nX: y").is_err());
+        assert!(validate_host("user@vllm.example.com").is_err());
+        assert!(validate_host("[::1]:8443").is_ok());
+        assert!(validate_host("10.0.0.1").is_ok());
+        assert!(validate_host("vllm.example.com:70000").is_err());
+        assert!(validate_host("vllm.example.com:").is_err());
+        assert!(validate_host("a:1:2").is_err());
+        assert!(validate_host("[::1").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_fixed_trailers_are_declared() {
+        let entries = vec![("X-Checksum", "sha256=abc"), ("X-Rows", "42")];
+        let value = serde_wasm_bindgen::to_value(&entries).unwrap();
+        let trailers = RequestTrailers::from_js(value).unwrap();
+        assert_eq!(
+            trailers.declaration().as_deref(),
+            Some("x-checksum, x-rows")
+        );
+
+        let bad = serde_wasm_bindgen::to_value(&vec![("X-Checksum", "a\r\nb")]).unwrap();
+        assert!(RequestTrailers::from_js(bad).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Tunnel transports carrying the TLS byte stream between the browser and the proxy.
+//!
+//! The tunnel is chosen from the URL scheme:
+//! - `ws://` / `wss://`: a WebSocket (supported everywhere).
+//! - `https://`: a WebTransport bidirectional stream (HTTP/3 over QUIC), for
+//!   lower-latency tunnels in browsers that implement the API. The relay at the
+//!   URL must accept WebTransport sessions and forward the stream to the target.
+//!
+//! Only standard globals are used (no `window` or other DOM APIs), so the same
+//! build runs in Deno and edge runtimes. Runtimes that expose `WebSocketStream`
+//! but no `WebSocket` constructor get a `ws://` tunnel over `WebSocketStream`.
+//!
+//! With `mux=1` in a `ws://` / `wss://` URL, the tunnel is instead a stream of a
+//! WebSocket shared with every other tunnel to that proxy URL (see [`crate::mux`]).
+//!
+//! All variants expose `futures::io::{AsyncRead, AsyncWrite}`, so the aTLS
+//! handshake and everything above it are transport-agnostic.
+//!
+//! Writes are backpressured. `WebSocket.send` never blocks and the browser
+//! buffers whatever it is given, so [`WsTunnel`] watches `bufferedAmount` and
+//! returns `Poll::Pending` from `poll_write` above [`WS_HIGH_WATER_MARK`] and
+//! from `poll_flush` until it drops to [`WS_LOW_WATER_MARK`]. WebTransport
+//! and `WebSocketStream` streams already apply backpressure through their
+//! `WritableStream`.
+//!
+//! Idle WebSockets through corporate proxies and load balancers are often cut
+//! silently, which later surfaces as an unexplained EOF. With a keepalive
+//! interval, [`WsTunnel`] sends an empty binary message whenever the send buffer
+//! is empty. The proxy writes its (empty) payload to TCP, so the TLS stream is
+//! unaffected. Browsers can't send WebSocket ping frames from script, hence the
+//! application-level message.
+//!
+//! WebSocket compression (permessage-deflate) is negotiated by the runtime,
+//! which offers it on every socket, and applied below the messages seen here:
+//! tunnels work the same whether or not the proxy accepts it.
+
+use async_io_stream::IoStream;
+use futures::io::{AsyncRead, AsyncWrite};
+use futures::stream::{IntoAsyncRead, LocalBoxStream, StreamExt, TryStreamExt};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::js_sys::{self, Array, Function, Promise, Reflect, Uint8Array};
+use web_sys::WebSocket;
+use ws_stream_wasm::{WsMeta, WsStreamIo};
+
+use crate::mux::MuxStream;
+
+type WsIo = IoStream<WsStreamIo, Vec<u8>>;
+
+/// `poll_write` waits while more than this many bytes are queued in the WebSocket.
+pub(crate) const WS_HIGH_WATER_MARK: u32 = 1024 * 1024;
+
+/// `poll_flush` waits until at most this many bytes are queued in the WebSocket.
+pub(crate) const WS_LOW_WATER_MARK: u32 = 64 * 1024;
+
+/// How often `bufferedAmount` is re-checked while waiting (the browser fires no
+/// event when the send buffer drains).
+const WS_DRAIN_POLL_MS: i32 = 10;
+
+/// Byte stream to the proxy, over WebSocket or WebTransport.
+pub(crate) enum Tunnel {
+    WebSocket(WsTunnel),
+    WebSocketStream(StreamIo),
+    WebTransport(StreamIo),
+    Multiplexed(MuxStream),
+}
+
+impl Tunnel {
+    /// Open a tunnel, choosing the transport from the URL scheme.
+    ///
+    /// `keepalive_ms` enables WebSocket keepalive messages at that interval
+    /// (ignored for WebTransport, whose QUIC session has its own keepalive).
+    pub(crate) async fn connect(url: &str, keepalive_ms: Option<u32>) -> Result<Self, JsValue> {
+        if url.starts_with("https://") {
+            StreamIo::web_transport(url).await.map(Tunnel::WebTransport)
+        } else if is_multiplexed(url) {
+            MuxStream::connect(url, keepalive_ms)
+                .await
+                .map(Tunnel::Multiplexed)
+        } else if !has_global("WebSocket") && has_global("WebSocketStream") {
+            StreamIo::web_socket_stream(url)
+                .await
+                .map(Tunnel::WebSocketStream)
+        } else {
+            let (meta, ws_stream) = WsMeta::connect(url, None)
+                .await
+                .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            if let Some(interval) = keepalive_ms.filter(|&ms| ms > 0) {
+                spawn_keepalive(meta.wrapped().clone(), interval);
+            }
+            Ok(Tunnel::WebSocket(WsTunnel {
+                meta,
+                io: ws_stream.into_io(),
+                drain_timer: None,
+            }))
+        }
+    }
+}
+
+impl AsyncRead for Tunnel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Tunnel::WebSocket(io) => Pin::new(io).poll_read(cx, buf),
+            Tunnel::WebSocketStream(io) | Tunnel::WebTransport(io) => {
+                Pin::new(io).poll_read(cx, buf)
+            }
+            Tunnel::Multiplexed(io) => Pin::new(io).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Tunnel {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Tunnel::WebSocket(io) => Pin::new(io).poll_write(cx, buf),
+            Tunnel::WebSocketStream(io) | Tunnel::WebTransport(io) => {
+                Pin::new(io).poll_write(cx, buf)
+            }
+            Tunnel::Multiplexed(io) => Pin::new(io).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Tunnel::WebSocket(io) => Pin::new(io).poll_flush(cx),
+            Tunnel::WebSocketStream(io) | Tunnel::WebTransport(io) => Pin::new(io).poll_flush(cx),
+            Tunnel::Multiplexed(io) => Pin::new(io).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Tunnel::WebSocket(io) => Pin::new(io).poll_close(cx),
+            Tunnel::WebSocketStream(io) | Tunnel::WebTransport(io) => Pin::new(io).poll_close(cx),
+            Tunnel::Multiplexed(io) => Pin::new(io).poll_close(cx),
+        }
+    }
+}
+
+/// WebSocket tunnel whose writes wait for the browser's send buffer to drain.
+pub(crate) struct WsTunnel {
+    meta: WsMeta,
+    io: WsIo,
+    /// Pending re-check of `bufferedAmount`.
+    drain_timer: Option<JsFuture>,
+}
+
+/// Promise resolving after `ms` milliseconds (works in windows and workers).
+pub(crate) fn sleep_promise(ms: i32) -> Promise {
+    Promise::new(&mut |resolve, _reject| {
+        let set_timeout = Reflect::get(&js_sys::global(), &"setTimeout".into())
+            .ok()
+            .and_then(|f| f.dyn_into::<Function>().ok());
+        match set_timeout {
+            Some(set_timeout) => {
+                let _ = set_timeout.call2(&JsValue::NULL, &resolve, &ms.into());
+            }
+            None => {
+                let _ = resolve.call0(&JsValue::NULL);
+            }
+        }
+    })
+}
+
+/// Send an empty binary message every `interval_ms` while the socket is open
+/// and nothing is queued (pending data already keeps the connection active).
+pub(crate) fn spawn_keepalive(socket: WebSocket, interval_ms: u32) {
+    let interval_ms = i32::try_from(interval_ms).unwrap_or(i32::MAX);
+    wasm_bindgen_futures::spawn_local(async move {
+        loop {
+            let _ = JsFuture::from(sleep_promise(interval_ms)).await;
+            if socket.ready_state() != WebSocket::OPEN {
+                break;
+            }
+            if socket.buffered_amount() == 0 {
+                let _ = socket.send_with_u8_array(&[]);
+            }
+        }
+    });
+}
+
+impl WsTunnel {
+    /// Ready once at most `limit` bytes are queued in the WebSocket.
+    fn poll_buffered_below(&mut self, cx: &mut Context<'_>, limit: u32) -> Poll<()> {
+        loop {
+            if self.meta.buffered_amount() <= limit {
+                self.drain_timer = None;
+                return Poll::Ready(());
+            }
+            let timer = self
+                .drain_timer
+                .get_or_insert_with(|| JsFuture::from(sleep_promise(WS_DRAIN_POLL_MS)));
+            match Pin::new(timer).poll(cx) {
+                Poll::Ready(_) => self.drain_timer = None,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl AsyncRead for WsTunnel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for WsTunnel {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this
+            .poll_buffered_below(cx, WS_HIGH_WATER_MARK)
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+        Pin::new(&mut this.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.io).poll_flush(cx) {
+            Poll::Ready(Ok(())) => this.poll_buffered_below(cx, WS_LOW_WATER_MARK).map(Ok),
+            other => other,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_close(cx)
+    }
+}
+
+/// A `ReadableStream`/`WritableStream` pair used as a byte pipe: a WebTransport
+/// bidirectional stream or a `WebSocketStream` connection.
+///
+/// `WebTransport` and `WebSocketStream` are looked up at runtime rather than
+/// through `web-sys`, whose bindings are still behind `web_sys_unstable_apis`
+/// (or missing).
+pub(crate) struct StreamIo {
+    reader: IntoAsyncRead<LocalBoxStream<'static, io::Result<Vec<u8>>>>,
+    writer: wasm_streams::writable::IntoAsyncWrite<'static>,
+    /// Object whose `close()` tears the connection down.
+    session: JsValue,
+}
+
+pub(crate) fn js_io_error(e: JsValue) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        e.as_string().unwrap_or_else(|| format!("{e:?}")),
+    )
+}
+
+async fn await_promise(value: JsValue) -> Result<JsValue, JsValue> {
+    JsFuture::from(value.dyn_into::<Promise>()?).await
+}
+
+/// Whether `url` asks for a stream of a shared WebSocket (`mux=1`).
+fn is_multiplexed(url: &str) -> bool {
+    web_sys::Url::new(url)
+        .ok()
+        .and_then(|url| url.search_params().get("mux"))
+        .is_some_and(|mux| mux == "1")
+}
+
+fn has_global(name: &str) -> bool {
+    Reflect::get(&js_sys::global(), &name.into()).is_ok_and(|v| !v.is_undefined())
+}
+
+/// `new <name>(url)`, failing cleanly if the runtime lacks the constructor.
+fn construct_global(name: &str, url: &str) -> Result<JsValue, JsValue> {
+    let ctor = Reflect::get(&js_sys::global(), &name.into())?;
+    if ctor.is_undefined() {
+        return Err(JsValue::from_str(&format!(
+            "{name} is not supported in this environment"
+        )));
+    }
+    Reflect::construct(ctor.unchecked_ref(), &Array::of1(&url.into()))
+}
+
+impl StreamIo {
+    async fn web_transport(url: &str) -> Result<Self, JsValue> {
+        let session = construct_global("WebTransport", url)?;
+        await_promise(Reflect::get(&session, &"ready".into())?).await?;
+
+        let create: Function =
+            Reflect::get(&session, &"createBidirectionalStream".into())?.dyn_into()?;
+        let stream = await_promise(create.call0(&session)?).await?;
+        Self::from_streams(&stream, session)
+    }
+
+    async fn web_socket_stream(url: &str) -> Result<Self, JsValue> {
+        let session = construct_global("WebSocketStream", url)?;
+        let opened = await_promise(Reflect::get(&session, &"opened".into())?).await?;
+        Self::from_streams(&opened, session)
+    }
+
+    /// Wrap the `readable`/`writable` pair of `streams`.
+    fn from_streams(streams: &JsValue, session: JsValue) -> Result<Self, JsValue> {
+        let readable: web_sys::ReadableStream =
+            Reflect::get(streams, &"readable".into())?.dyn_into()?;
+        let writable: web_sys::WritableStream =
+            Reflect::get(streams, &"writable".into())?.dyn_into()?;
+
+        // Chunks are Uint8Arrays (WebTransport) or ArrayBuffers (WebSocketStream);
+        // a plain (non-BYOB) reader works everywhere
+        let chunks = wasm_streams::ReadableStream::from_raw(readable)
+            .into_stream()
+            .map(|chunk| {
+                chunk
+                    .map(|value| Uint8Array::new(&value).to_vec())
+                    .map_err(js_io_error)
+            })
+            .boxed_local();
+
+        Ok(Self {
+            reader: chunks.into_async_read(),
+            writer: wasm_streams::WritableStream::from_raw(writable).into_async_write(),
+            session,
+        })
+    }
+}
+
+impl Drop for StreamIo {
+    fn drop(&mut self) {
+        if let Ok(close) = Reflect::get(&self.session, &"close".into()) {
+            if let Some(close) = close.dyn_ref::<Function>() {
+                let _ = close.call0(&self.session);
+            }
+        }
+    }
+}
+
+impl AsyncRead for StreamIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().reader).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for StreamIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().writer).poll_close(cx)
+    }
+}
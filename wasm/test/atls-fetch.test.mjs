@@ -0,0 +1,296 @@
+/**
+ * atls-fetch tests over a fake transport (see fake-wasm.mjs): pooling,
+ * redirects, retries, aborts and measurement pinning, without a TEE or the
+ * wasm build.
+ *
+ * Run with: node --test test/*.test.mjs
+ */
+
+import { test, beforeEach } from "node:test";
+import assert from "node:assert/strict";
+import { copyFileSync, mkdtempSync } from "node:fs";
+import { tmpdir } from "node:os";
+import { dirname, join } from "node:path";
+import { fileURLToPath, pathToFileURL } from "node:url";
+
+// atls-fetch.js imports "./atlas_wasm.js": load it next to the fake instead
+const here = dirname(fileURLToPath(import.meta.url));
+const dir = mkdtempSync(join(tmpdir(), "atls-fetch-test-"));
+copyFileSync(join(here, "../src/atls-fetch.js"), join(dir, "atls-fetch.js"));
+copyFileSync(join(here, "fake-wasm.mjs"), join(dir, "atlas_wasm.js"));
+const { createAtlsFetch, atlsFetch, closeAllConnections, CookieJar, encodeMultipart } = await import(
+  pathToFileURL(join(dir, "atls-fetch.js"))
+);
+const { transport, reset, attestation, hang } = await import(pathToFileURL(join(dir, "atlas_wasm.js")));
+
+const POLICY = { type: "dstack_tdx", allowed_tcb_status: ["UpToDate"] };
+const LAX_POLICY = { type: "dstack_tdx", allowed_tcb_status: ["UpToDate", "OutOfDate"] };
+
+function client(options = {}) {
+  return createAtlsFetch({
+    proxyUrl: "ws://proxy.test",
+    targetHost: "tee.example.com",
+    policy: POLICY,
+    attestationCache: false,
+    ...options
+  });
+}
+
+beforeEach(() => {
+  closeAllConnections();
+  reset();
+});
+
+test("reuses a pooled connection for the same policy", async () => {
+  const fetch = client();
+  assert.equal(await (await fetch("https://tee.example.com/a")).text(), "ok");
+  assert.equal(await (await fetch("https://tee.example.com/b")).text(), "ok");
+  assert.equal(transport.connects.length, 1);
+  assert.deepEqual(transport.connections[0].requests.map((r) => r.path), ["/a", "/b"]);
+});
+
+test("never reuses a connection attested under another policy", async () => {
+  await (await client({ policy: LAX_POLICY })("https://tee.example.com/")).text();
+  await (await client({ policy: POLICY })("https://tee.example.com/")).text();
+  assert.equal(transport.connects.length, 2);
+  assert.deepEqual(transport.connects.map((c) => c.policy), [LAX_POLICY, POLICY]);
+
+  // The same policy, written in another key order, shares the pool
+  const reordered = { allowed_tcb_status: ["UpToDate"], type: "dstack_tdx" };
+  await (await client({ policy: reordered })("https://tee.example.com/")).text();
+  assert.equal(transport.connects.length, 2);
+});
+
+test("keeps pool settings per client", async () => {
+  const release = [];
+  transport.handler = (request) =>
+    request.path === "/slow" ? new Promise((resolve) => release.push(resolve)) : { body: "ok" };
+
+  // A one-connection client doesn't shrink the pool of a six-connection one
+  const narrow = client({ maxConnections: 1 });
+  const wide = client({ maxConnections: 6 });
+  const first = wide("https://tee.example.com/slow");
+  const second = wide("https://tee.example.com/slow");
+  await (await narrow("https://tee.example.com/")).text();
+  while (release.length < 2) await new Promise((resolve) => setTimeout(resolve, 1));
+  assert.equal(transport.connects.length, 3);
+  release.forEach((resolve) => resolve({ body: "done" }));
+  assert.equal(await (await first).text(), "done");
+  assert.equal(await (await second).text(), "done");
+});
+
+test("follows redirects up to maxRedirects", async () => {
+  transport.handler = (request) => {
+    const hop = Number(request.path.slice(1) || 0);
+    return { status: 302, headers: { Location: `/${hop + 1}` } };
+  };
+  const fetch = client({ maxRedirects: 2 });
+  await assert.rejects(fetch("https://tee.example.com/"), { code: "TOO_MANY_REDIRECTS" });
+  assert.deepEqual(transport.connections[0].requests.map((r) => r.path), ["/", "/1", "/2"]);
+
+  transport.handler = (request) =>
+    request.path === "/" ? { status: 301, headers: { Location: "/done" } } : { body: request.path };
+  const response = await fetch("https://tee.example.com/");
+  assert.equal(response.redirected, true);
+  assert.equal(await response.text(), "/done");
+});
+
+test("refuses redirects off the attested origin", async () => {
+  transport.handler = () => ({ status: 307, headers: { Location: "https://evil.example.com/" } });
+  await assert.rejects(client()("https://tee.example.com/"), {
+    name: "RedirectError",
+    code: "REDIRECT_CROSS_ORIGIN"
+  });
+});
+
+test("retries transient failures on a fresh connection", async () => {
+  let failures = 2;
+  transport.handler = () => {
+    if (failures-- > 0) throw new Error("tunnel dropped");
+    return { body: "ok" };
+  };
+  const response = await client()("https://tee.example.com/", {
+    retry: { retries: 3, baseDelayMs: 1 }
+  });
+  assert.equal(await response.text(), "ok");
+  assert.equal(response.attempts, 3);
+  assert.equal(transport.connects.length, 3);
+});
+
+test("doesn't retry non-idempotent or attestation failures", async () => {
+  transport.handler = () => {
+    throw new Error("tunnel dropped");
+  };
+  const fetch = client({ retry: { retries: 3, baseDelayMs: 1 } });
+  await assert.rejects(fetch("https://tee.example.com/", { method: "POST", body: "x" }), /tunnel dropped/);
+  assert.equal(transport.connects.length, 1);
+
+  transport.handler = () => {
+    throw Object.assign(new Error("bootchain mismatch"), { code: "BOOTCHAIN_MISMATCH" });
+  };
+  await assert.rejects(fetch("https://tee.example.com/"), { code: "BOOTCHAIN_MISMATCH" });
+  assert.equal(transport.connects.length, 2);
+});
+
+test("aborting a request rejects it and drops its connection", async () => {
+  transport.handler = (request, http, signal) => hang(signal);
+  const controller = new AbortController();
+  const pending = client()("https://tee.example.com/", { signal: controller.signal });
+  while (transport.connections.length === 0 || transport.connections[0].requests.length === 0) {
+    await new Promise((resolve) => setTimeout(resolve, 1));
+  }
+  controller.abort();
+  await assert.rejects(pending, { name: "AbortError" });
+  assert.equal(transport.connections[0].open, false);
+
+  // Aborting before sending doesn't connect at all
+  await assert.rejects(client()("https://tee.example.com/", { signal: AbortSignal.abort() }), {
+    name: "AbortError"
+  });
+  assert.equal(transport.connects.length, 1);
+});
+
+test("fails when a new connection presents another app digest", async () => {
+  transport.attestation = (n) => attestation(n === 1 ? "digest-a" : "digest-b");
+  const changes = [];
+  const fetch = client({ onAttestationChanged: (current, previous) => changes.push([previous, current]) });
+  await (await fetch("https://tee.example.com/")).text();
+
+  // The pooled connection died: the next request reconnects and re-attests
+  transport.connections[0].open = false;
+  const changed = {
+    name: "MeasurementChangedError",
+    code: "MEASUREMENT_CHANGED",
+    previous: "digest-a",
+    current: "digest-b"
+  };
+  await assert.rejects(fetch("https://tee.example.com/"), changed);
+  assert.equal(transport.connections[1].open, false);
+
+  // The rejected digest doesn't become the reference: later connections fail too
+  await assert.rejects(fetch("https://tee.example.com/"), changed);
+  assert.equal(transport.connections[2].open, false);
+  assert.deepEqual(changes, []);
+
+  // So does a client created after the change
+  await assert.rejects(client()("https://tee.example.com/"), changed);
+});
+
+test("accepts other instances of the same app", async () => {
+  // Same image and compose hash, a different RTMR3 per instance
+  transport.attestation = (n) => attestation("digest-a", "UpToDate", n);
+  const release = [];
+  transport.handler = (request) =>
+    request.path === "/slow" ? new Promise((resolve) => release.push(resolve)) : { body: "ok" };
+  const fetch = client();
+
+  // Pool growth: a second instance serves a concurrent request
+  const first = fetch("https://tee.example.com/slow");
+  const second = fetch("https://tee.example.com/slow");
+  while (release.length < 2) await new Promise((resolve) => setTimeout(resolve, 1));
+  release.forEach((resolve) => resolve({ body: "done" }));
+  assert.equal(await (await first).text(), "done");
+  assert.equal(await (await second).text(), "done");
+
+  // Replacing dead connections: a third instance takes over
+  transport.connections.forEach((http) => { http.open = false; });
+  assert.equal(await (await fetch("https://tee.example.com/")).text(), "ok");
+  assert.deepEqual(
+    transport.connections.map((http) => http.attestation().measurementDigest),
+    ["digest-a/instance-1", "digest-a/instance-2", "digest-a/instance-3"]
+  );
+});
+
+test("allowMeasurementChange accepts a redeployed app", async () => {
+  transport.attestation = (n) => attestation(n === 1 ? "digest-a" : "digest-b");
+  const changes = [];
+  const fetch = client({
+    allowMeasurementChange: true,
+    onAttestationChanged: (current, previous) => changes.push([previous, current])
+  });
+  await (await fetch("https://tee.example.com/")).text();
+  transport.connections[0].open = false;
+  const response = await fetch("https://tee.example.com/");
+  assert.equal(response.attestation.appDigest, "digest-b");
+  assert.deepEqual(changes.map(([p, c]) => [p.appDigest, c.appDigest]), [["digest-a", "digest-b"]]);
+});
+
+test("reports closes only to the client that opened the connection", async () => {
+  const closedA = [];
+  const closedB = [];
+  const a = client({ onConnectionClosed: (event) => closedA.push(event.reason) });
+  const b = client({ onConnectionClosed: (event) => closedB.push(event.reason) });
+  await (await a("https://tee.example.com/")).text();
+  // b reuses a's connection, opening none of its own
+  await (await b("https://tee.example.com/")).text();
+  assert.equal(transport.connects.length, 1);
+
+  closeAllConnections();
+  assert.deepEqual(closedA, ["closed"]);
+  assert.deepEqual(closedB, []);
+
+  // A disposed client is told nothing more
+  await (await a("https://tee.example.com/")).text();
+  a.dispose();
+  closeAllConnections();
+  assert.deepEqual(closedA, ["closed"]);
+});
+
+test("atlsFetch takes the target from the URL", async () => {
+  const response = await atlsFetch("https://other.example.com:8443/x", {
+    atls: { proxyUrl: "ws://proxy.test", policy: POLICY, attestationCache: false }
+  });
+  assert.equal(await response.text(), "ok");
+  assert.equal(response.attestation.appDigest, "digest-a");
+  assert.equal(transport.connects[0].serverName, "other.example.com");
+  assert.match(transport.connects[0].url, /other\.example\.com(:|%3A)8443/);
+});
+
+test("cookie jar refuses Domain attributes naming public suffixes", () => {
+  const jar = new CookieJar();
+  jar.setCookies(["a=1; Domain=com", "b=2; Domain=co.uk", "c=3; Domain=example.co.uk"], "https://app.example.co.uk/");
+  jar.setCookies(["d=4; Domain=.localhost"], "https://localhost/");
+  assert.equal(jar.getCookieHeader("https://other.co.uk/"), "");
+  assert.equal(jar.getCookieHeader("https://api.example.co.uk/"), "c=3");
+  // Naming a single-label host itself keeps the cookie host-only
+  assert.equal(jar.getCookieHeader("https://localhost/"), "d=4");
+
+  const strict = new CookieJar({ isPublicSuffix: (domain) => domain === "example.co.uk" });
+  strict.setCookies(["c=3; Domain=example.co.uk"], "https://app.example.co.uk/");
+  assert.equal(strict.getCookieHeader("https://app.example.co.uk/"), "");
+});
+
+test("cookie jar keeps cookies of IP hosts host-only", () => {
+  const jar = new CookieJar();
+  jar.setCookies(["a=1; Domain=0.0.1", "b=2; Domain=10.0.0.1", "c=3"], "https://10.0.0.1/");
+  assert.equal(jar.getCookieHeader("https://10.0.0.1/"), "b=2; c=3");
+  assert.equal(jar.getCookieHeader("https://1.10.0.0.1.example/"), "");
+
+  jar.setCookies(["d=4; Domain=[::1]"], "https://[::1]/");
+  assert.equal(jar.getCookieHeader("https://[::1]/"), "d=4");
+});
+
+test("multipart refuses line breaks in a part's content type", async () => {
+  const injected = new Blob(["x"], { type: "text/plain" });
+  Object.defineProperty(injected, "type", { value: "text/plain\r\nX-Injected: 1" });
+  assert.throws(() => encodeMultipart({ file: injected }), TypeError);
+  assert.throws(
+    () => encodeMultipart({ file: { value: "x", contentType: "text/plain\nX-Injected: 1" } }),
+    TypeError
+  );
+
+  const { body } = encodeMultipart({ file: { value: "x", contentType: "text/plain" } }, { boundary: "b" });
+  assert.match(await body.text(), /\r\nContent-Type: text\/plain\r\n\r\nx\r\n--b--\r\n$/);
+});
+
+test("multipart validates the boundary", () => {
+  for (const boundary of ["", "a".repeat(71), "ends with space ", "semi;colon", "line\r\nbreak", 42]) {
+    assert.throws(() => encodeMultipart({ a: "1" }, { boundary }), TypeError, String(boundary));
+  }
+  assert.equal(encodeMultipart({ a: "1" }, { boundary: "a".repeat(70) }).boundary, "a".repeat(70));
+  // Characters outside HTTP tokens are quoted in the Content-Type parameter
+  assert.equal(
+    encodeMultipart({ a: "1" }, { boundary: "with space:colon" }).contentType,
+    'multipart/form-data; boundary="with space:colon"'
+  );
+});
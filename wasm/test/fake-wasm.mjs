@@ -0,0 +1,115 @@
+/**
+ * Stand-in for the wasm-bindgen module (`atlas_wasm.js`) in the atls-fetch
+ * tests: `AtlsHttp` connections answer from a scripted handler instead of
+ * tunnelling to an attested TEE.
+ */
+
+/** Test-controlled behavior, reset with `reset()`. */
+export const transport = {
+  /** Every `AtlsHttp.connect` call: `{ url, serverName, policy, options }`. */
+  connects: [],
+  /** Every connection opened, in order. @type {FakeHttp[]} */
+  connections: [],
+  /** Attestation of the n-th connection (1-based). */
+  attestation: () => attestation("digest-a"),
+  /** `(request, http, signal) => { status, headers, body }`, may throw or hang. */
+  handler: () => ({ status: 200, body: "ok" })
+};
+
+export function reset() {
+  transport.connects = [];
+  transport.connections = [];
+  transport.attestation = () => attestation("digest-a");
+  transport.handler = () => ({ status: 200, body: "ok" });
+}
+
+/**
+ * Attestation of `instance` of the app deployment `appDigest`: instances share
+ * the app digest, but their RTMR3 (so `measurementDigest`) differs.
+ */
+export function attestation(appDigest, tcbStatus = "UpToDate", instance = 1) {
+  return {
+    trusted: true,
+    teeType: "Tdx",
+    measurement: "mrtd",
+    measurementDigest: `${appDigest}/instance-${instance}`,
+    appDigest,
+    tcbStatus,
+    advisoryIds: [],
+    timings: {}
+  };
+}
+
+/** Never settles until `signal` aborts, then rejects like a real fetch. */
+export function hang(signal) {
+  return new Promise((_, reject) => {
+    signal.addEventListener("abort", () => reject(signal.reason), { once: true });
+  });
+}
+
+class FakeHttp {
+  constructor(attestation) {
+    this._attestation = attestation;
+    this.open = true;
+    this.requests = [];
+  }
+
+  attestation() {
+    return this._attestation;
+  }
+
+  diagnostics() {
+    return { tlsVersion: "TLSv1_3" };
+  }
+
+  isReady() {
+    return this.open;
+  }
+
+  close() {
+    this.open = false;
+  }
+
+  closed() {
+    return new Promise(() => {});
+  }
+
+  async fetch(method, path, authority, headers, body, signal) {
+    signal?.throwIfAborted();
+    const request = { method, path, authority, headers, body };
+    this.requests.push(request);
+    const reply = await transport.handler(request, this, signal);
+    const bytes = new TextEncoder().encode(reply.body ?? "");
+    return {
+      status: reply.status ?? 200,
+      statusText: "",
+      rawHeaders: Object.entries(reply.headers ?? {}).map(([name, value]) => [name.toLowerCase(), value]),
+      body: new ReadableStream({
+        start(controller) {
+          if (bytes.length > 0) controller.enqueue(bytes);
+          controller.close();
+        }
+      }),
+      trailers: null
+    };
+  }
+}
+
+export class AtlsHttp {
+  static async connect(url, serverName, policy, options) {
+    transport.connects.push({ url, serverName, policy, options });
+    const http = new FakeHttp(transport.attestation(transport.connects.length));
+    transport.connections.push(http);
+    return http;
+  }
+}
+
+export class AttestedStream {}
+
+export class Policy {}
+
+export function mergeWithDefaultAppCompose(compose) {
+  return compose;
+}
+
+export default async function init() {}